@@ -89,6 +89,8 @@ pub fn build_program(args: &BuildArgs) -> Result<Utf8PathBuf> {
             "link-arg=-Ttext=0x00200800",
             "-C",
             "panic=abort",
+            "-C",
+            "force-frame-pointers=yes",
         ];
 
         let result = Command::new("cargo")
@@ -0,0 +1,218 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion, SamplingMode, Throughput,
+};
+use sp1_core::alu::{AluEvent, BitmanipEvent, DivRemEvent, MulEvent};
+use sp1_core::cpu::{AuipcEvent, CpuEvent};
+use sp1_core::runtime::{
+    AluClass, EventSink, ExecutionRecord, Instruction, Opcode, Program, Runtime,
+};
+
+/// The system allocator, counting allocations and the peak number of bytes allocated.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK_ALLOCATED.fetch_max(allocated, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// A criterion measurement of the allocations of a benchmark, instead of its time.
+#[derive(Debug, Clone, Copy)]
+enum Allocations {
+    /// The number of allocations.
+    Count,
+
+    /// The peak number of bytes allocated, above what was allocated before.
+    PeakBytes,
+}
+
+impl Allocations {
+    fn unit(&self) -> &'static str {
+        match self {
+            Allocations::Count => "allocations",
+            Allocations::PeakBytes => "B",
+        }
+    }
+}
+
+impl Measurement for Allocations {
+    type Intermediate = (usize, usize);
+    type Value = usize;
+
+    fn start(&self) -> Self::Intermediate {
+        let allocated = ALLOCATED.load(Ordering::Relaxed);
+        PEAK_ALLOCATED.store(allocated, Ordering::Relaxed);
+        (ALLOCATIONS.load(Ordering::Relaxed), allocated)
+    }
+
+    fn end(&self, (allocations, allocated): Self::Intermediate) -> Self::Value {
+        match self {
+            Allocations::Count => ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+            Allocations::PeakBytes => PEAK_ALLOCATED.load(Ordering::Relaxed) - allocated,
+        }
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        self
+    }
+}
+
+impl ValueFormatter for Allocations {
+    fn scale_values(&self, typical_value: f64, values: &mut [f64]) -> &'static str {
+        const MIB: f64 = (1 << 20) as f64;
+        if matches!(self, Allocations::PeakBytes) && typical_value >= MIB {
+            values.iter_mut().for_each(|value| *value /= MIB);
+            return "MiB";
+        }
+        self.unit()
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        _throughput: &Throughput,
+        _values: &mut [f64],
+    ) -> &'static str {
+        self.unit()
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        self.unit()
+    }
+}
+
+/// A sink keeping the CPU events in a `Vec` and every other event in a record, to compare the
+/// arena of the record against a plain vector.
+#[derive(Default)]
+struct VecSink {
+    cpu_events: Vec<CpuEvent>,
+    record: ExecutionRecord,
+}
+
+impl EventSink for VecSink {
+    fn on_cpu_event(&mut self, event: &CpuEvent) {
+        self.cpu_events.push(*event);
+    }
+
+    fn on_auipc_event(&mut self, event: &AuipcEvent) {
+        self.record.on_auipc_event(event);
+    }
+
+    fn on_alu_event(&mut self, class: AluClass, event: &AluEvent) {
+        self.record.on_alu_event(class, event);
+    }
+
+    fn on_mul_event(&mut self, event: &MulEvent) {
+        self.record.on_mul_event(event);
+    }
+
+    fn on_divrem_event(&mut self, event: &DivRemEvent) {
+        self.record.on_divrem_event(event);
+    }
+
+    fn on_bitmanip_event(&mut self, event: &BitmanipEvent) {
+        self.record.on_bitmanip_event(event);
+    }
+}
+
+const LOOP_ITERATIONS: u32 = 1 << 20;
+
+/// A loop of ALU instructions running millions of cycles, where the CPU events dominate the
+/// memory of the record.
+fn alu_loop_program(iterations: u32) -> Program {
+    let instructions = vec![
+        Instruction::new(Opcode::ADD, 5, 0, iterations, false, true),
+        Instruction::new(Opcode::ADD, 6, 0, 3, false, true),
+        // loop:
+        Instruction::new(Opcode::MUL, 7, 6, 5, false, false),
+        Instruction::new(Opcode::XOR, 6, 7, 6, false, false),
+        Instruction::new(Opcode::SRL, 7, 6, 3, false, true),
+        Instruction::new(Opcode::ADD, 5, 5, -1i32 as u32, false, true),
+        Instruction::new(Opcode::BNE, 5, 0, -16i32 as u32, false, true),
+    ];
+    Program::new(instructions, 0, 0)
+}
+
+/// Run the loop keeping the CPU events in the arena of the record or in a `Vec`.
+fn run(program: &Program, vec: bool) {
+    let mut runtime = Runtime::new(program.clone());
+    if vec {
+        runtime.set_event_sink(Box::<VecSink>::default());
+    }
+    runtime.run();
+    black_box(&runtime);
+}
+
+/// Measure `allocations` of the loop with the CPU events in the arena of the record and in a
+/// `Vec`. Each run is measured on its own, since the peak of several runs is not their sum.
+fn memory_benchmark(c: &mut Criterion<Allocations>, allocations: Allocations) {
+    let program = alu_loop_program(LOOP_ITERATIONS);
+    let mut group = c.benchmark_group(format!("memory_{}", allocations.unit()));
+    group.sample_size(10);
+    group.sampling_mode(SamplingMode::Flat);
+    for (name, vec) in [("arena", false), ("vec", true)] {
+        group.bench_function(BenchmarkId::new(name, LOOP_ITERATIONS), |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| {
+                        let start = allocations.start();
+                        run(&program, vec);
+                        allocations.end(start)
+                    })
+                    .sum()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn count_benchmark(c: &mut Criterion<Allocations>) {
+    memory_benchmark(c, Allocations::Count);
+}
+
+fn peak_benchmark(c: &mut Criterion<Allocations>) {
+    memory_benchmark(c, Allocations::PeakBytes);
+}
+
+criterion_group! {
+    name = count;
+    config = Criterion::default().with_measurement(Allocations::Count);
+    targets = count_benchmark
+}
+criterion_group! {
+    name = peak;
+    config = Criterion::default().with_measurement(Allocations::PeakBytes);
+    targets = peak_benchmark
+}
+criterion_main!(count, peak);
@@ -0,0 +1,187 @@
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sp1_core::runtime::{
+    default_syscall_map, AluClass, ExecutionRecord, Instruction, MemoryRecords, Opcode, Program,
+    Runtime, DEFAULT_TRACE_LOG_CAPACITY,
+};
+
+const NUM_PROGRAMS: u32 = 10_000;
+
+/// Tiny programs of three instructions each, as produced by a fuzzer.
+fn programs() -> Vec<Arc<Program>> {
+    (0..NUM_PROGRAMS)
+        .map(|i| {
+            let instructions = vec![
+                Instruction::new(Opcode::ADD, 29, 0, i, false, true),
+                Instruction::new(Opcode::ADD, 30, 0, 37, false, true),
+                Instruction::new(Opcode::ADD, 31, 30, 29, false, false),
+            ];
+            Arc::new(Program::new(instructions, 0, 0))
+        })
+        .collect()
+}
+
+const ALU_LOOP_ITERATIONS: u32 = 1 << 20;
+
+/// A loop of ALU instructions, where the per-instruction bookkeeping of the runtime dominates.
+fn alu_loop_program(iterations: u32) -> Program {
+    let instructions = vec![
+        Instruction::new(Opcode::ADD, 5, 0, iterations, false, true),
+        Instruction::new(Opcode::ADD, 6, 0, 3, false, true),
+        // loop:
+        Instruction::new(Opcode::MUL, 7, 6, 5, false, false),
+        Instruction::new(Opcode::XOR, 6, 7, 6, false, false),
+        Instruction::new(Opcode::SRL, 7, 6, 3, false, true),
+        Instruction::new(Opcode::ADD, 5, 5, -1i32 as u32, false, true),
+        Instruction::new(Opcode::BNE, 5, 0, -16i32 as u32, false, true),
+    ];
+    Program::new(instructions, 0, 0)
+}
+
+const QUERY_LOOP_ITERATIONS: u32 = 1 << 16;
+const QUERY_SHARD_SIZE: u32 = 1 << 12;
+
+/// The record of a short ALU loop split into many small shards.
+fn sharded_record() -> ExecutionRecord {
+    let mut runtime = Runtime::new(alu_loop_program(QUERY_LOOP_ITERATIONS));
+    runtime.shard_size = QUERY_SHARD_SIZE;
+    runtime.run();
+    runtime.record
+}
+
+const POSTPROCESS_WORDS: u32 = 10_000_000;
+
+/// A runtime whose final memory holds ten million words. Every tenth word is from the memory
+/// image, and half of those were never accessed.
+fn large_final_state() -> Runtime {
+    let mut program = Program::new(vec![], 0, 0);
+    program.memory_image = (0..POSTPROCESS_WORDS / 10).map(|i| (i * 40, i)).collect();
+    let mut runtime = Runtime::new(program);
+    for i in 0..POSTPROCESS_WORDS {
+        let entry = if i % 20 == 0 {
+            (i / 10, 0, 0)
+        } else {
+            (i, 1, i)
+        };
+        runtime.state.memory.insert(i * 4, entry);
+    }
+    runtime
+}
+
+/// The records as plain tuples, to compare them.
+fn flatten_records(records: &MemoryRecords) -> Vec<(u32, u32, u32, u32, u32)> {
+    [&records.first, &records.last, &records.program]
+        .into_iter()
+        .flatten()
+        .map(|(addr, record, used)| (*addr, record.value, record.shard, record.timestamp, *used))
+        .collect()
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let programs = programs();
+
+    let mut group = c.benchmark_group("runtime");
+    group.sample_size(10);
+    group.bench_function(format!("fresh:{}", NUM_PROGRAMS), |b| {
+        b.iter(|| {
+            for program in programs.iter() {
+                let mut runtime = Runtime::new(program.as_ref().clone());
+                runtime.run();
+                black_box(&runtime.record);
+            }
+        })
+    });
+    group.bench_function(format!("reset:{}", NUM_PROGRAMS), |b| {
+        b.iter(|| {
+            let mut runtime = Runtime::new(programs[0].as_ref().clone());
+            for program in programs.iter() {
+                runtime.reset_with_program(program.clone());
+                runtime.run();
+                black_box(&runtime.record);
+            }
+        })
+    });
+    // Constructing a runtime, against building the syscall map every runtime used to build.
+    group.bench_function(format!("new:{}", NUM_PROGRAMS), |b| {
+        b.iter(|| {
+            for program in programs.iter() {
+                black_box(Runtime::new(program.as_ref().clone()));
+            }
+        })
+    });
+    group.bench_function(format!("default_syscall_map:{}", NUM_PROGRAMS), |b| {
+        b.iter(|| {
+            for _ in programs.iter() {
+                black_box(default_syscall_map());
+            }
+        })
+    });
+    let program = alu_loop_program(ALU_LOOP_ITERATIONS);
+    group.bench_function(format!("alu_loop:{}", ALU_LOOP_ITERATIONS), |b| {
+        b.iter(|| {
+            let mut runtime = Runtime::new(program.clone());
+            runtime.run();
+            black_box(&runtime.record);
+        })
+    });
+    // The same loop keeping the last cycles in the trace log, which should cost less than a tenth
+    // more.
+    group.bench_function(format!("alu_loop_trace_log:{}", ALU_LOOP_ITERATIONS), |b| {
+        b.iter(|| {
+            let mut runtime = Runtime::new(program.clone());
+            runtime.enable_trace_log(DEFAULT_TRACE_LOG_CAPACITY);
+            runtime.run();
+            black_box(&runtime.record);
+        })
+    });
+    group.finish();
+
+    // Query the CPU and MUL events of every shard, by scanning the record or through its indices.
+    let mut record = sharded_record();
+    let shards = record.cpu_events.last().unwrap().shard;
+    let mut group = c.benchmark_group("shard_queries");
+    group.bench_function(format!("scan:{}", shards), |b| {
+        b.iter(|| {
+            for shard in 1..=shards {
+                let cpu = record.cpu_events.iter().filter(|e| e.shard == shard);
+                let muls = cpu
+                    .clone()
+                    .filter(|e| AluClass::from_opcode(e.instruction.opcode) == Some(AluClass::Mul))
+                    .count();
+                black_box((cpu.count(), muls));
+            }
+        })
+    });
+    record.build_indices();
+    group.bench_function(format!("indexed:{}", shards), |b| {
+        b.iter(|| {
+            for shard in 1..=shards {
+                black_box((
+                    record.cpu_events_for_shard(shard).len(),
+                    record.alu_events_for_shard(shard, AluClass::Mul).len(),
+                ));
+            }
+        })
+    });
+    group.finish();
+
+    // Build the memory records of a large final state serially and in parallel.
+    let runtime = large_final_state();
+    assert_eq!(
+        flatten_records(&runtime.build_memory_records(false)),
+        flatten_records(&runtime.build_memory_records(true))
+    );
+    let mut group = c.benchmark_group("postprocess");
+    group.sample_size(10);
+    group.bench_function(format!("serial:{}", POSTPROCESS_WORDS), |b| {
+        b.iter(|| black_box(runtime.build_memory_records(false)))
+    });
+    group.bench_function(format!("parallel:{}", POSTPROCESS_WORDS), |b| {
+        b.iter(|| black_box(runtime.build_memory_records(true)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sp1_core::runtime::Program;
+
+// Disassembling arbitrary bytes must return an error instead of panicking. Inputs that crash
+// belong in `corpus/elf`, which the unit tests replay.
+fuzz_target!(|data: &[u8]| {
+    let _ = Program::try_from_elf(data);
+});
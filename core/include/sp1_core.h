@@ -0,0 +1,55 @@
+#ifndef SP1_CORE_H
+#define SP1_CORE_H
+
+#include <stdint.h>
+#include <stddef.h>
+
+#define SP1_OK 0
+#define SP1_ERR_NULL_POINTER -1
+#define SP1_ERR_PANIC -2
+#define SP1_ERR_UNSUPPORTED_SYSCALL 1
+#define SP1_ERR_INPUT_EXHAUSTED 2
+#define SP1_ERR_UNIMPLEMENTED 3
+#define SP1_ERR_BREAKPOINT 4
+#define SP1_ERR_UNINITIALIZED_READ 5
+#define SP1_ERR_CANCELLED 6
+#define SP1_ERR_LIVELOCK_SUSPECTED 7
+#define SP1_ERR_INPUT_FRAME_MISMATCH 8
+#define SP1_ERR_DUPLICATE_ACCESS 9
+#define SP1_ERR_WRITE_TO_READ_ONLY 10
+#define SP1_ERR_INPUT_READ_FAILED 11
+#define SP1_ERR_PC_OUT_OF_BOUNDS 12
+#define SP1_ERR_OPCODE_FORBIDDEN 13
+#define SP1_ERR_SYSCALL_FORBIDDEN 14
+#define SP1_ERR_PROTECTION_FAULT 15
+#define SP1_ERR_ADDRESS_OUT_OF_RANGE 16
+#define SP1_ERR_STOPPED 17
+#define SP1_ERR_REWOUND 18
+#define SP1_ERR_SYSCALL_FOOTPRINT_VIOLATION 19
+#define SP1_ERR_RESERVED_ADDRESS 20
+#define SP1_ERR_UNCONSTRAINED_FORBIDDEN 21
+#define SP1_ERR_SCRATCH_READ 22
+#define SP1_ERR_SYSCALL_CLOCK_MISMATCH 23
+#define SP1_ERR_INPUT_WOULD_BLOCK 24
+
+typedef struct ProgramHandle ProgramHandle;
+
+typedef struct RuntimeHandle RuntimeHandle;
+
+ProgramHandle *sp1_program_load(const uint8_t *elf_ptr, size_t elf_len);
+
+void sp1_program_free(ProgramHandle *program);
+
+RuntimeHandle *sp1_runtime_new(const ProgramHandle *program);
+
+void sp1_runtime_free(RuntimeHandle *rt);
+
+int32_t sp1_runtime_write_stdin(RuntimeHandle *rt, const uint8_t *ptr, size_t len);
+
+int32_t sp1_runtime_run(RuntimeHandle *rt);
+
+uint64_t sp1_runtime_cycles(const RuntimeHandle *rt);
+
+size_t sp1_runtime_output(const RuntimeHandle *rt, uint8_t *out_ptr, size_t out_len);
+
+#endif /* SP1_CORE_H */
@@ -1,6 +1,7 @@
 use std::array::IntoIter;
+use std::fmt::Debug;
 use std::mem::size_of;
-use std::ops::{Index, IndexMut};
+use std::ops::{BitAnd, BitOr, BitXor, Index, IndexMut, Not, Shr};
 
 use core::borrow::{Borrow, BorrowMut};
 use p3_air::AirBuilder;
@@ -13,20 +14,35 @@ use super::SP1AirBuilder;
 /// The size of a word in bytes.
 pub const WORD_SIZE: usize = 4;
 
-/// A word is a 32-bit value represented in an AIR.
-#[derive(AlignedBorrow, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+/// The size of a double word in bytes, the word size of 64-bit targets.
+pub const DOUBLE_WORD_SIZE: usize = 8;
+
+/// A word is a 32-bit value represented in an AIR. Words of other sizes, such as the 64-bit words
+/// of [`DoubleWord`], have `N` bytes.
+#[derive(AlignedBorrow, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(C)]
-pub struct Word<T>(pub [T; WORD_SIZE]);
+pub struct Word<T, const N: usize = WORD_SIZE>(pub [T; N]);
 
-impl<T> Word<T> {
+/// A 64-bit value represented in an AIR.
+pub type DoubleWord<T> = Word<T, DOUBLE_WORD_SIZE>;
+
+impl<T: Default, const N: usize> Default for Word<T, N> {
+    fn default() -> Self {
+        Word(core::array::from_fn(|_| T::default()))
+    }
+}
+
+impl<T, const N: usize> Word<T, N> {
     /// Applies `f` to each element of the word.
-    pub fn map<F, S>(self, f: F) -> Word<S>
+    pub fn map<F, S>(self, f: F) -> Word<S, N>
     where
         F: FnMut(T) -> S,
     {
         Word(self.0.map(f))
     }
+}
 
+impl<T> Word<T> {
     /// Extends a variable to a word.
     pub fn extend_var<AB: SP1AirBuilder<Var = T>>(var: T) -> Word<AB::Expr> {
         Word([
@@ -53,10 +69,68 @@ impl<T: AbstractField> Word<T> {
 impl<F: Field> Word<F> {
     /// Converts a word to a u32.
     pub fn to_u32(&self) -> u32 {
-        u32::from_le_bytes(self.0.map(|x| x.to_string().parse::<u8>().unwrap()))
+        self.to_value()
+    }
+}
+
+impl<F: Field, const N: usize> Word<F, N> {
+    /// Creates a word from the little-endian bytes of a value.
+    pub fn from_value<V: WordValue<N>>(value: V) -> Self {
+        Word(value.to_le_bytes().map(F::from_canonical_u8))
+    }
+
+    /// Converts a word to the value it holds.
+    pub fn to_value<V: WordValue<N>>(&self) -> V {
+        V::from_le_bytes(self.0.map(|x| x.to_string().parse::<u8>().unwrap()))
     }
 }
 
+/// The unsigned integer type holding the value of a word of `N` bytes.
+pub trait WordValue<const N: usize>:
+    Copy
+    + Debug
+    + Eq
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+    + Shr<usize, Output = Self>
+{
+    fn to_le_bytes(self) -> [u8; N];
+
+    fn from_le_bytes(bytes: [u8; N]) -> Self;
+
+    fn wrapping_add(self, other: Self) -> Self;
+
+    fn rotate_right(self, n: u32) -> Self;
+}
+
+macro_rules! impl_word_value {
+    ($ty:ty, $n:expr) => {
+        impl WordValue<{ $n }> for $ty {
+            fn to_le_bytes(self) -> [u8; $n] {
+                <$ty>::to_le_bytes(self)
+            }
+
+            fn from_le_bytes(bytes: [u8; $n]) -> Self {
+                <$ty>::from_le_bytes(bytes)
+            }
+
+            fn wrapping_add(self, other: Self) -> Self {
+                <$ty>::wrapping_add(self, other)
+            }
+
+            fn rotate_right(self, n: u32) -> Self {
+                <$ty>::rotate_right(self, n)
+            }
+        }
+    };
+}
+
+impl_word_value!(u8, 1);
+impl_word_value!(u32, WORD_SIZE);
+impl_word_value!(u64, DOUBLE_WORD_SIZE);
+
 impl<V: Copy> Word<V> {
     /// Reduces a word to a single variable.
     pub fn reduce<AB: AirBuilder<Var = V>>(&self) -> AB::Expr {
@@ -69,7 +143,7 @@ impl<V: Copy> Word<V> {
     }
 }
 
-impl<T> Index<usize> for Word<T> {
+impl<T, const N: usize> Index<usize> for Word<T, N> {
     type Output = T;
 
     fn index(&self, index: usize) -> &Self::Output {
@@ -77,7 +151,7 @@ impl<T> Index<usize> for Word<T> {
     }
 }
 
-impl<T> IndexMut<usize> for Word<T> {
+impl<T, const N: usize> IndexMut<usize> for Word<T, N> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         &mut self.0[index]
     }
@@ -96,9 +170,9 @@ impl<F: Field> From<u32> for Word<F> {
     }
 }
 
-impl<T> IntoIterator for Word<T> {
+impl<T, const N: usize> IntoIterator for Word<T, N> {
     type Item = T;
-    type IntoIter = IntoIter<T, WORD_SIZE>;
+    type IntoIter = IntoIter<T, N>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.0.into_iter()
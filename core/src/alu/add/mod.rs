@@ -142,15 +142,14 @@ mod tests {
 
     use super::AddChip;
     use crate::{
-        alu::AluEvent,
-        runtime::{ExecutionRecord, Opcode},
+        runtime::{synthesize_simple_alu, ExecutionRecord, Opcode},
         utils::{BabyBearPoseidon2, StarkUtils},
     };
 
     #[test]
     fn generate_trace() {
         let mut shard = ExecutionRecord::default();
-        shard.add_events = vec![AluEvent::new(0, Opcode::ADD, 14, 8, 6)];
+        shard.add_events = vec![synthesize_simple_alu(Opcode::ADD, 8, 6, 1, 0).1];
         let chip = AddChip::default();
         let trace: RowMajorMatrix<BabyBear> =
             chip.generate_trace(&shard, &mut ExecutionRecord::default());
@@ -166,10 +165,9 @@ mod tests {
         for _ in 0..1000 {
             let operand_1 = thread_rng().gen_range(0..u32::MAX);
             let operand_2 = thread_rng().gen_range(0..u32::MAX);
-            let result = operand_1.wrapping_add(operand_2);
             shard
                 .add_events
-                .push(AluEvent::new(0, Opcode::ADD, result, operand_1, operand_2));
+                .push(synthesize_simple_alu(Opcode::ADD, operand_1, operand_2, 1, 0).1);
         }
 
         let chip = AddChip::default();
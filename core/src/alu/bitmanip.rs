@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use crate::runtime::Opcode;
+
+/// The execution of a Zbb bit manipulation instruction. No chip constrains these yet, so records
+/// with such events cannot be proven.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BitmanipEvent {
+    /// The shard of the instruction.
+    pub shard: u32,
+
+    /// The clock cycle that the operation occurs on.
+    pub clk: u32,
+
+    /// The opcode of the operation.
+    pub opcode: Opcode,
+
+    /// The result of the operation.
+    pub a: u32,
+
+    /// The first input operand.
+    pub b: u32,
+
+    /// The second input operand, zero for CLZ, CTZ and CPOP.
+    pub c: u32,
+
+    /// The bits of `b`, least significant first, for CLZ, CTZ and CPOP, whose result is a
+    /// function of all of them.
+    pub bits: Option<[bool; 32]>,
+}
+
+impl BitmanipEvent {
+    pub fn new(shard: u32, clk: u32, opcode: Opcode, a: u32, b: u32, c: u32) -> Self {
+        let bits = matches!(opcode, Opcode::CLZ | Opcode::CTZ | Opcode::CPOP)
+            .then(|| core::array::from_fn(|i| (b >> i) & 1 == 1));
+        Self {
+            shard,
+            clk,
+            opcode,
+            a,
+            b,
+            c,
+            bits,
+        }
+    }
+}
@@ -82,6 +82,7 @@ use crate::disassembler::WORD_SIZE;
 use crate::operations::{IsEqualWordOperation, IsZeroWordOperation};
 use crate::runtime::{ExecutionRecord, Opcode};
 use crate::utils::pad_to_power_of_two;
+use serde::{Deserialize, Serialize};
 
 /// The number of main trace columns for `DivRemChip`.
 pub const NUM_DIVREM_COLS: usize = size_of::<DivRemCols<u8>>();
@@ -92,6 +93,101 @@ const BYTE_SIZE: usize = 8;
 /// The size of a 64-bit in bytes.
 const LONG_WORD_SIZE: usize = 2 * WORD_SIZE;
 
+/// The signs, absolute values and results of a DIV* or REM* operation, derived by the runtime when
+/// executing it. For unsigned operations, no operand is negative and absolute values are the
+/// operands themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DivRemMetadata {
+    /// Whether `b` is negative in a signed operation.
+    pub b_neg: bool,
+
+    /// Whether `c` is negative in a signed operation.
+    pub c_neg: bool,
+
+    /// Whether the remainder is negative in a signed operation.
+    pub rem_neg: bool,
+
+    /// Whether the operation is the signed overflow `i32::MIN / -1`.
+    pub is_overflow: bool,
+
+    /// `abs(b)` for signed operations, `b` otherwise.
+    pub abs_b: u32,
+
+    /// `abs(c)` for signed operations, `c` otherwise.
+    pub abs_c: u32,
+
+    /// `abs(remainder)` for signed operations, `remainder` otherwise.
+    pub abs_remainder: u32,
+
+    /// The quotient, following the RISC-V spec for division by zero and overflow.
+    pub quotient: u32,
+
+    /// The remainder, following the RISC-V spec for division by zero and overflow.
+    pub remainder: u32,
+}
+
+impl DivRemMetadata {
+    /// Derive the metadata of `opcode` applied to `b` and `c`.
+    pub fn new(opcode: Opcode, b: u32, c: u32) -> Self {
+        let (quotient, remainder) = get_quotient_and_remainder(b, c, opcode);
+        let signed = is_signed_operation(opcode);
+        let abs = |value: u32| {
+            if signed {
+                (value as i32).unsigned_abs()
+            } else {
+                value
+            }
+        };
+        Self {
+            b_neg: signed && get_msb(b) == 1,
+            c_neg: signed && get_msb(c) == 1,
+            rem_neg: signed && get_msb(remainder) == 1,
+            is_overflow: signed && b as i32 == i32::MIN && c as i32 == -1,
+            abs_b: abs(b),
+            abs_c: abs(c),
+            abs_remainder: abs(remainder),
+            quotient,
+            remainder,
+        }
+    }
+
+    /// The result of the operation: the quotient for DIV and DIVU, the remainder otherwise.
+    pub fn result(&self, opcode: Opcode) -> u32 {
+        if opcode == Opcode::DIV || opcode == Opcode::DIVU {
+            self.quotient
+        } else {
+            self.remainder
+        }
+    }
+}
+
+/// A DIV, DIVU, REM or REMU event, with the metadata derived from its operands.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DivRemEvent {
+    pub event: AluEvent,
+    pub metadata: DivRemMetadata,
+}
+
+impl DivRemEvent {
+    /// The event of `opcode` applied to `b` and `c`, with the result taken from `metadata`.
+    pub fn new(clk: u32, opcode: Opcode, b: u32, c: u32, metadata: DivRemMetadata) -> Self {
+        Self {
+            event: AluEvent::new(clk, opcode, metadata.result(opcode), b, c),
+            metadata,
+        }
+    }
+}
+
+/// Derive the metadata of an event built without it.
+impl From<AluEvent> for DivRemEvent {
+    fn from(event: AluEvent) -> Self {
+        Self {
+            event,
+            metadata: DivRemMetadata::new(event.opcode, event.b, event.c),
+        }
+    }
+}
+
 /// A chip that implements addition for the opcodes DIV/REM.
 #[derive(Default)]
 pub struct DivRemChip;
@@ -195,7 +291,7 @@ impl<F: PrimeField> MachineAir<F> for DivRemChip {
         // Generate the trace rows for each event.
         let mut rows: Vec<[F; NUM_DIVREM_COLS]> = vec![];
         let divrem_events = input.divrem_events.clone();
-        for event in divrem_events.iter() {
+        for &DivRemEvent { event, metadata } in divrem_events.iter() {
             assert!(
                 event.opcode == Opcode::DIVU
                     || event.opcode == Opcode::REMU
@@ -218,7 +314,7 @@ impl<F: PrimeField> MachineAir<F> for DivRemChip {
                 cols.is_c_0.populate(event.c);
             }
 
-            let (quotient, remainder) = get_quotient_and_remainder(event.b, event.c, event.opcode);
+            let (quotient, remainder) = (metadata.quotient, metadata.remainder);
             cols.quotient = Word::from(quotient);
             cols.remainder = Word::from(remainder);
 
@@ -229,20 +325,13 @@ impl<F: PrimeField> MachineAir<F> for DivRemChip {
                 cols.c_msb = F::from_canonical_u8(get_msb(event.c));
                 cols.is_overflow_b.populate(event.b, i32::MIN as u32);
                 cols.is_overflow_c.populate(event.c, -1i32 as u32);
-                if is_signed_operation(event.opcode) {
-                    cols.rem_neg = cols.rem_msb;
-                    cols.b_neg = cols.b_msb;
-                    cols.c_neg = cols.c_msb;
-                    cols.is_overflow =
-                        F::from_bool(event.b as i32 == i32::MIN && event.c as i32 == -1);
-                    cols.abs_remainder = Word::from((remainder as i32).abs() as u32);
-                    cols.abs_c = Word::from((event.c as i32).abs() as u32);
-                    cols.max_abs_c_or_1 = Word::from(u32::max(1, (event.c as i32).abs() as u32));
-                } else {
-                    cols.abs_remainder = cols.remainder;
-                    cols.abs_c = cols.c;
-                    cols.max_abs_c_or_1 = Word::from(u32::max(1, event.c));
-                }
+                cols.rem_neg = F::from_bool(metadata.rem_neg);
+                cols.b_neg = F::from_bool(metadata.b_neg);
+                cols.c_neg = F::from_bool(metadata.c_neg);
+                cols.is_overflow = F::from_bool(metadata.is_overflow);
+                cols.abs_remainder = Word::from(metadata.abs_remainder);
+                cols.abs_c = Word::from(metadata.abs_c);
+                cols.max_abs_c_or_1 = Word::from(u32::max(1, metadata.abs_c));
 
                 // Insert the MSB lookup events.
                 {
@@ -338,8 +427,8 @@ impl<F: PrimeField> MachineAir<F> for DivRemChip {
                         AluEvent {
                             opcode: Opcode::SLT,
                             a: 1,
-                            b: (remainder as i32).abs() as u32,
-                            c: u32::max(1, (event.c as i32).abs() as u32),
+                            b: metadata.abs_remainder,
+                            c: u32::max(1, metadata.abs_c),
                             clk: event.clk,
                         }
                     } else {
@@ -755,16 +844,16 @@ mod tests {
 
     use crate::{
         alu::AluEvent,
-        runtime::{ExecutionRecord, Opcode},
+        runtime::{ExecutionRecord, Instruction, Opcode, Program, Runtime},
         utils::{BabyBearPoseidon2, StarkUtils},
     };
 
-    use super::DivRemChip;
+    use super::{DivRemChip, DivRemEvent, DivRemMetadata};
 
     #[test]
     fn generate_trace() {
         let mut shard = ExecutionRecord::default();
-        shard.divrem_events = vec![AluEvent::new(0, Opcode::DIVU, 2, 17, 3)];
+        shard.divrem_events = vec![AluEvent::new(0, Opcode::DIVU, 2, 17, 3).into()];
         let chip = DivRemChip::default();
         let trace: RowMajorMatrix<BabyBear> =
             chip.generate_trace(&shard, &mut ExecutionRecord::default());
@@ -775,14 +864,9 @@ mod tests {
         u32::MAX - a + 1
     }
 
-    #[test]
-    fn prove_babybear() {
-        let config = BabyBearPoseidon2::new();
-        let mut challenger = config.challenger();
-
-        let mut divrem_events: Vec<AluEvent> = Vec::new();
-
-        let divrems: Vec<(Opcode, u32, u32, u32)> = vec![
+    /// Division and remainder edge cases as `(opcode, a, b, c)`.
+    fn divrem_cases() -> Vec<(Opcode, u32, u32, u32)> {
+        vec![
             (Opcode::DIVU, 3, 20, 6),
             (Opcode::DIVU, 715827879, neg(20), 6),
             (Opcode::DIVU, 0, 20, neg(6)),
@@ -815,14 +899,69 @@ mod tests {
             (Opcode::DIV, neg(1), 0, 0),
             (Opcode::DIV, 1 << 31, 1 << 31, neg(1)),
             (Opcode::REM, 0, 1 << 31, neg(1)),
-        ];
+        ]
+    }
+
+    #[test]
+    fn test_divrem_metadata() {
+        for (opcode, a, b, c) in divrem_cases() {
+            let instructions = vec![
+                Instruction::new(Opcode::ADD, 29, 0, b, false, true),
+                Instruction::new(Opcode::ADD, 30, 0, c, false, true),
+                Instruction::new(opcode, 31, 29, 30, false, false),
+            ];
+            let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+            runtime.run();
+            let DivRemEvent { event, metadata } = runtime.record.divrem_events[0];
+            assert_eq!(event.a, a);
+
+            // Recompute the metadata with 64-bit arithmetic, where neither the absolute values
+            // nor `i32::MIN / -1` overflow.
+            let signed = opcode == Opcode::DIV || opcode == Opcode::REM;
+            let extend = |x: u32| if signed { x as i32 as i64 } else { x as i64 };
+            let (quotient, remainder) = if c == 0 {
+                (u32::MAX, b)
+            } else {
+                (
+                    (extend(b) / extend(c)) as u32,
+                    (extend(b) % extend(c)) as u32,
+                )
+            };
+            let expected = DivRemMetadata {
+                b_neg: extend(b) < 0,
+                c_neg: extend(c) < 0,
+                rem_neg: extend(remainder) < 0,
+                is_overflow: signed && b == 1 << 31 && c == u32::MAX,
+                abs_b: extend(b).unsigned_abs() as u32,
+                abs_c: extend(c).unsigned_abs() as u32,
+                abs_remainder: extend(remainder).unsigned_abs() as u32,
+                quotient,
+                remainder,
+            };
+            assert_eq!(metadata, expected, "{:?} {} {}", opcode, b, c);
+            assert_eq!(metadata.result(opcode), a);
+        }
+
+        // Events built without metadata derive it from their operands.
+        let event = DivRemEvent::from(AluEvent::new(0, Opcode::DIV, 1 << 31, 1 << 31, neg(1)));
+        assert!(event.metadata.is_overflow);
+    }
+
+    #[test]
+    fn prove_babybear() {
+        let config = BabyBearPoseidon2::new();
+        let mut challenger = config.challenger();
+
+        let mut divrem_events: Vec<DivRemEvent> = Vec::new();
+
+        let divrems = divrem_cases();
         for t in divrems.iter() {
-            divrem_events.push(AluEvent::new(0, t.0, t.1, t.2, t.3));
+            divrem_events.push(AluEvent::new(0, t.0, t.1, t.2, t.3).into());
         }
 
         // Append more events until we have 1000 tests.
         for _ in 0..(1000 - divrems.len()) {
-            divrem_events.push(AluEvent::new(0, Opcode::DIVU, 1, 1, 1));
+            divrem_events.push(AluEvent::new(0, Opcode::DIVU, 1, 1, 1).into());
         }
 
         let mut shard = ExecutionRecord::default();
@@ -1,4 +1,5 @@
 pub mod add;
+pub mod bitmanip;
 pub mod bitwise;
 pub mod divrem;
 pub mod lt;
@@ -8,6 +9,7 @@ pub mod sr;
 pub mod sub;
 
 pub use add::*;
+pub use bitmanip::*;
 pub use bitwise::*;
 pub use divrem::*;
 pub use lt::*;
@@ -17,9 +19,16 @@ pub use sr::*;
 pub use sub::*;
 
 use crate::runtime::Opcode;
+use serde::{Deserialize, Serialize};
+
+/// The amount SLL, SRL and SRA shift by: the low 5 bits of `c`.
+#[inline(always)]
+pub const fn shift_amount(c: u32) -> u32 {
+    c & 0x1f
+}
 
 /// A standard format for describing ALU operations that need to be proven.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct AluEvent {
     /// The clock cycle that the operation occurs on.
     pub clk: u32,
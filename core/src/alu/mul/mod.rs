@@ -40,10 +40,12 @@ use tracing::instrument;
 use crate::air::MachineAir;
 use crate::air::{SP1AirBuilder, Word};
 use crate::alu::mul::utils::get_msb;
+use crate::alu::AluEvent;
 use crate::bytes::{ByteLookupEvent, ByteOpcode};
 use crate::disassembler::WORD_SIZE;
 use crate::runtime::{ExecutionRecord, Opcode};
 use crate::utils::pad_to_power_of_two;
+use serde::{Deserialize, Serialize};
 
 /// The number of main trace columns for `MulChip`.
 pub const NUM_MUL_COLS: usize = size_of::<MulCols<u8>>();
@@ -109,6 +111,81 @@ pub struct MulCols<T> {
     pub is_real: T,
 }
 
+/// The signs and the product of a MUL* operation, derived by the runtime when executing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MulMetadata {
+    /// Whether `b` is a signed operand and negative, in which case it is sign extended.
+    pub b_neg: bool,
+
+    /// Whether `c` is a signed operand and negative, in which case it is sign extended.
+    pub c_neg: bool,
+
+    /// The little-endian bytes of the 64-bit product of the sign-extended operands.
+    pub product: [u8; PRODUCT_SIZE],
+}
+
+impl MulMetadata {
+    /// Derive the metadata of `opcode` applied to `b` and `c`.
+    pub fn new(opcode: Opcode, b: u32, c: u32) -> Self {
+        let b_neg = (opcode == Opcode::MULH || opcode == Opcode::MULHSU) && (b as i32) < 0;
+        let c_neg = opcode == Opcode::MULH && (c as i32) < 0;
+        let extend = |value: u32, neg: bool| {
+            if neg {
+                value as i32 as i64 as u64
+            } else {
+                value as u64
+            }
+        };
+        let product = extend(b, b_neg)
+            .wrapping_mul(extend(c, c_neg))
+            .to_le_bytes();
+        Self {
+            b_neg,
+            c_neg,
+            product,
+        }
+    }
+
+    /// The result of the operation: the lower word of the product for MUL and the upper word
+    /// otherwise.
+    pub fn result(&self, opcode: Opcode) -> u32 {
+        let product = u64::from_le_bytes(self.product);
+        if opcode == Opcode::MUL {
+            product as u32
+        } else {
+            (product >> 32) as u32
+        }
+    }
+}
+
+/// A MUL, MULH, MULHU or MULHSU event, with the metadata derived from its operands.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MulEvent {
+    pub event: AluEvent,
+    pub metadata: MulMetadata,
+}
+
+impl MulEvent {
+    /// The event of `opcode` applied to `b` and `c`, with the result taken from `metadata`.
+    pub fn new(clk: u32, opcode: Opcode, b: u32, c: u32, metadata: MulMetadata) -> Self {
+        Self {
+            event: AluEvent::new(clk, opcode, metadata.result(opcode), b, c),
+            metadata,
+        }
+    }
+}
+
+/// Derive the metadata of an event built without it, such as the multiplications checking a
+/// division.
+impl From<AluEvent> for MulEvent {
+    fn from(event: AluEvent) -> Self {
+        Self {
+            event,
+            metadata: MulMetadata::new(event.opcode, event.b, event.c),
+        }
+    }
+}
+
 impl<F: PrimeField> MachineAir<F> for MulChip {
     fn name(&self) -> String {
         "Mul".to_string()
@@ -131,7 +208,7 @@ impl<F: PrimeField> MachineAir<F> for MulChip {
                 let mut record = ExecutionRecord::default();
                 let rows = events
                     .iter()
-                    .map(|event| {
+                    .map(|&MulEvent { event, metadata }| {
                         // Ensure that the opcode is MUL, MULHU, MULH, or MULHSU.
                         assert!(
                             event.opcode == Opcode::MUL
@@ -151,21 +228,17 @@ impl<F: PrimeField> MachineAir<F> for MulChip {
 
                         // Handle b and c's signs.
                         {
-                            let b_msb = get_msb(b_word);
-                            cols.b_msb = F::from_canonical_u8(b_msb);
-                            let c_msb = get_msb(c_word);
-                            cols.c_msb = F::from_canonical_u8(c_msb);
+                            cols.b_msb = F::from_canonical_u8(get_msb(b_word));
+                            cols.c_msb = F::from_canonical_u8(get_msb(c_word));
 
                             // If b is signed and it is negative, sign extend b.
-                            if (event.opcode == Opcode::MULH || event.opcode == Opcode::MULHSU)
-                                && b_msb == 1
-                            {
+                            if metadata.b_neg {
                                 cols.b_sign_extend = F::one();
                                 b.resize(PRODUCT_SIZE, BYTE_MASK);
                             }
 
                             // If c is signed and it is negative, sign extend c.
-                            if event.opcode == Opcode::MULH && c_msb == 1 {
+                            if metadata.c_neg {
                                 cols.c_sign_extend = F::one();
                                 c.resize(PRODUCT_SIZE, BYTE_MASK);
                             }
@@ -209,8 +282,9 @@ impl<F: PrimeField> MachineAir<F> for MulChip {
                             }
                             cols.carry[i] = F::from_canonical_u32(carry[i]);
                         }
+                        debug_assert_eq!(product.map(|x| x as u8), metadata.product);
 
-                        cols.product = product.map(F::from_canonical_u32);
+                        cols.product = metadata.product.map(F::from_canonical_u8);
                         cols.a = Word(a_word.map(F::from_canonical_u8));
                         cols.b = Word(b_word.map(F::from_canonical_u8));
                         cols.c = Word(c_word.map(F::from_canonical_u8));
@@ -223,7 +297,7 @@ impl<F: PrimeField> MachineAir<F> for MulChip {
                         // Range check.
                         {
                             record.add_u16_range_checks(&carry);
-                            record.add_u8_range_checks(&product.map(|x| x as u8));
+                            record.add_u8_range_checks(&metadata.product);
                         }
                         row
                     })
@@ -425,22 +499,22 @@ mod tests {
         utils::{BabyBearPoseidon2, StarkUtils},
     };
 
-    use super::MulChip;
+    use super::{MulChip, MulEvent};
 
     #[test]
     fn generate_trace_mul() {
         let mut shard = ExecutionRecord::default();
 
         // Fill mul_events with 10^7 MULHSU events.
-        let mut mul_events: Vec<AluEvent> = Vec::new();
+        let mut mul_events: Vec<MulEvent> = Vec::new();
         for _ in 0..10i32.pow(7) {
-            mul_events.push(AluEvent::new(
+            mul_events.push(MulEvent::from(AluEvent::new(
                 0,
                 Opcode::MULHSU,
                 0x80004000,
                 0x80000000,
                 0xffff8000,
-            ));
+            )));
         }
         shard.mul_events = mul_events;
         let chip = MulChip::default();
@@ -454,7 +528,7 @@ mod tests {
         let mut challenger = config.challenger();
 
         let mut shard = ExecutionRecord::default();
-        let mut mul_events: Vec<AluEvent> = Vec::new();
+        let mut mul_events: Vec<MulEvent> = Vec::new();
 
         let mul_instructions: Vec<(Opcode, u32, u32, u32)> = vec![
             (Opcode::MUL, 0x00001200, 0x00007e00, 0xb6db6db7),
@@ -509,12 +583,12 @@ mod tests {
             (Opcode::MULH, 0xffffffff, 0x00000001, 0xffffffff),
         ];
         for t in mul_instructions.iter() {
-            mul_events.push(AluEvent::new(0, t.0, t.1, t.2, t.3));
+            mul_events.push(AluEvent::new(0, t.0, t.1, t.2, t.3).into());
         }
 
         // Append more events until we have 1000 tests.
         for _ in 0..(1000 - mul_instructions.len()) {
-            mul_events.push(AluEvent::new(0, Opcode::MUL, 1, 1, 1));
+            mul_events.push(AluEvent::new(0, Opcode::MUL, 1, 1, 1).into());
         }
 
         shard.mul_events = mul_events;
@@ -42,6 +42,7 @@ use tracing::instrument;
 
 use crate::air::MachineAir;
 use crate::air::{SP1AirBuilder, Word};
+use crate::alu::shift_amount;
 use crate::disassembler::WORD_SIZE;
 use crate::runtime::{ExecutionRecord, Opcode};
 use crate::utils::pad_to_power_of_two;
@@ -119,7 +120,8 @@ impl<F: PrimeField> MachineAir<F> for ShiftLeft {
             }
 
             // Variables for bit shifting.
-            let num_bits_to_shift = event.c as usize % BYTE_SIZE;
+            let shamt = shift_amount(event.c) as usize;
+            let num_bits_to_shift = shamt % BYTE_SIZE;
             for i in 0..BYTE_SIZE {
                 cols.shift_by_n_bits[i] = F::from_bool(num_bits_to_shift == i);
             }
@@ -141,7 +143,7 @@ impl<F: PrimeField> MachineAir<F> for ShiftLeft {
             cols.bit_shift_result_carry = bit_shift_result_carry.map(F::from_canonical_u8);
 
             // Variables for byte shifting.
-            let num_bytes_to_shift = (event.c & 0b11111) as usize / BYTE_SIZE;
+            let num_bytes_to_shift = shamt / BYTE_SIZE;
             for i in 0..WORD_SIZE {
                 cols.shift_by_n_bytes[i] = F::from_bool(num_bytes_to_shift == i);
             }
@@ -346,7 +348,7 @@ mod tests {
 
     use crate::{
         alu::AluEvent,
-        runtime::{ExecutionRecord, Opcode},
+        runtime::{tests::shift_program, ExecutionRecord, Opcode, Runtime},
         utils::{BabyBearPoseidon2, StarkUtils},
     };
 
@@ -408,4 +410,32 @@ mod tests {
         let mut challenger = config.challenger();
         verify(&config, &chip, &mut challenger, &proof).unwrap();
     }
+
+    #[test]
+    fn prove_masked_shift_amounts() {
+        let config = BabyBearPoseidon2::new();
+        let mut challenger = config.challenger();
+
+        let shifts = [Opcode::SLL]
+            .into_iter()
+            .flat_map(|opcode| {
+                [32, 33, 0xffffffe1]
+                    .map(|c| [(opcode, 0x87654321, c), (opcode, 0x12345678, c)])
+                    .concat()
+            })
+            .collect::<Vec<_>>();
+        let mut runtime = Runtime::new(shift_program(&shifts));
+        runtime.run();
+
+        let mut shard = ExecutionRecord::default();
+        shard.shift_left_events = runtime.record.shift_left_events;
+        assert_eq!(shard.shift_left_events.len(), shifts.len());
+        let chip = ShiftLeft::default();
+        let trace: RowMajorMatrix<BabyBear> =
+            chip.generate_trace(&shard, &mut ExecutionRecord::default());
+        let proof = prove::<BabyBearPoseidon2, _>(&config, &chip, &mut challenger, trace);
+
+        let mut challenger = config.challenger();
+        verify(&config, &chip, &mut challenger, &proof).unwrap();
+    }
 }
@@ -469,7 +469,7 @@ mod tests {
 
     use crate::{
         alu::AluEvent,
-        runtime::{ExecutionRecord, Opcode},
+        runtime::{tests::shift_program, ExecutionRecord, Opcode, Runtime},
         utils::{BabyBearPoseidon2, StarkUtils},
     };
 
@@ -541,4 +541,32 @@ mod tests {
         let mut challenger = config.challenger();
         verify(&config, &chip, &mut challenger, &proof).unwrap();
     }
+
+    #[test]
+    fn prove_masked_shift_amounts() {
+        let config = BabyBearPoseidon2::new();
+        let mut challenger = config.challenger();
+
+        let shifts = [Opcode::SRL, Opcode::SRA]
+            .into_iter()
+            .flat_map(|opcode| {
+                [32, 33, 0xffffffe1]
+                    .map(|c| [(opcode, 0x87654321, c), (opcode, 0x12345678, c)])
+                    .concat()
+            })
+            .collect::<Vec<_>>();
+        let mut runtime = Runtime::new(shift_program(&shifts));
+        runtime.run();
+
+        let mut shard = ExecutionRecord::default();
+        shard.shift_right_events = runtime.record.shift_right_events;
+        assert_eq!(shard.shift_right_events.len(), shifts.len());
+        let chip = ShiftRightChip::default();
+        let trace: RowMajorMatrix<BabyBear> =
+            chip.generate_trace(&shard, &mut ExecutionRecord::default());
+        let proof = prove::<BabyBearPoseidon2, _>(&config, &chip, &mut challenger, trace);
+
+        let mut challenger = config.challenger();
+        verify(&config, &chip, &mut challenger, &proof).unwrap();
+    }
 }
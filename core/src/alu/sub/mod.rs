@@ -182,15 +182,14 @@ mod tests {
 
     use super::SubChip;
     use crate::{
-        alu::AluEvent,
-        runtime::{ExecutionRecord, Opcode},
+        runtime::{synthesize_simple_alu, ExecutionRecord, Opcode},
         utils::{BabyBearPoseidon2, StarkUtils},
     };
 
     #[test]
     fn generate_trace() {
         let mut shard = ExecutionRecord::default();
-        shard.sub_events = vec![AluEvent::new(0, Opcode::SUB, 14, 8, 6)];
+        shard.sub_events = vec![synthesize_simple_alu(Opcode::SUB, 8, 6, 1, 0).1];
         let chip = SubChip {};
         let trace: RowMajorMatrix<BabyBear> =
             chip.generate_trace(&shard, &mut ExecutionRecord::default());
@@ -207,11 +206,9 @@ mod tests {
         for _i in 0..1000 {
             let operand_1 = thread_rng().gen_range(0..u32::MAX);
             let operand_2 = thread_rng().gen_range(0..u32::MAX);
-            let result = operand_1.wrapping_sub(operand_2);
-
             shard
                 .sub_events
-                .push(AluEvent::new(0, Opcode::SUB, result, operand_1, operand_2));
+                .push(synthesize_simple_alu(Opcode::SUB, operand_1, operand_2, 1, 0).1);
         }
         let chip = SubChip::default();
         let trace: RowMajorMatrix<BabyBear> =
@@ -0,0 +1,38 @@
+//! A debugger for SP1 programs: `sp1-dbg <elf>` reads commands from stdin, see `help`.
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+
+use sp1_core::runtime::{DebuggerSession, Program};
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: sp1-dbg <elf>");
+        return ExitCode::FAILURE;
+    };
+    let program = match std::fs::read(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|elf| Program::try_from_elf(&elf).map_err(|err| err.to_string()))
+    {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("failed to load {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut session = DebuggerSession::new(program);
+    let mut stdout = std::io::stdout();
+    let mut lines = std::io::stdin().lock().lines();
+    while !session.has_quit() {
+        write!(stdout, "(sp1-dbg) ").unwrap();
+        stdout.flush().unwrap();
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+        let output = session.execute(&line);
+        if !output.is_empty() {
+            writeln!(stdout, "{}", output).unwrap();
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -1,7 +1,8 @@
 use super::ByteOpcode;
+use serde::{Deserialize, Serialize};
 
 /// A byte lookup event.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ByteLookupEvent {
     /// The opcode of the operation.
     pub opcode: ByteOpcode,
@@ -18,7 +18,7 @@ impl<F: Field> MachineAir<F> for ByteChip<F> {
     ) -> RowMajorMatrix<F> {
         let (mut trace, event_map) = ByteChip::trace_and_map();
 
-        for (lookup, mult) in input.byte_lookups.iter() {
+        for (lookup, mult) in input.byte_lookups.values().flatten() {
             let (row, index) = event_map[lookup];
 
             // Get the column index for the multiplicity.
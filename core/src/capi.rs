@@ -0,0 +1,291 @@
+//! A C interface for loading programs, executing them and reading back cycle counts and output.
+//!
+//! Every function is safe to call with null pointers and never unwinds across the FFI boundary:
+//! panics are caught and reported as `SP1_ERR_PANIC` (or a null handle). The header for this
+//! module is `include/sp1_core.h` and can be regenerated with `cbindgen --config cbindgen.toml`.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::runtime::{ExecutionError, Program, Runtime};
+
+/// The program executed successfully.
+pub const SP1_OK: i32 = 0;
+/// A null pointer was passed where a handle or buffer was expected.
+pub const SP1_ERR_NULL_POINTER: i32 = -1;
+/// The runtime panicked.
+pub const SP1_ERR_PANIC: i32 = -2;
+/// See [`ExecutionError::UnsupportedSyscall`].
+pub const SP1_ERR_UNSUPPORTED_SYSCALL: i32 = 1;
+/// See [`ExecutionError::InputExhausted`].
+pub const SP1_ERR_INPUT_EXHAUSTED: i32 = 2;
+/// See [`ExecutionError::Unimplemented`].
+pub const SP1_ERR_UNIMPLEMENTED: i32 = 3;
+/// See [`ExecutionError::Breakpoint`].
+pub const SP1_ERR_BREAKPOINT: i32 = 4;
+/// See [`ExecutionError::UninitializedRead`].
+pub const SP1_ERR_UNINITIALIZED_READ: i32 = 5;
+/// See [`ExecutionError::Cancelled`].
+pub const SP1_ERR_CANCELLED: i32 = 6;
+/// See [`ExecutionError::LivelockSuspected`].
+pub const SP1_ERR_LIVELOCK_SUSPECTED: i32 = 7;
+/// See [`ExecutionError::InputFrameMismatch`].
+pub const SP1_ERR_INPUT_FRAME_MISMATCH: i32 = 8;
+/// See [`ExecutionError::DuplicateAccess`].
+pub const SP1_ERR_DUPLICATE_ACCESS: i32 = 9;
+/// See [`ExecutionError::WriteToReadOnly`].
+pub const SP1_ERR_WRITE_TO_READ_ONLY: i32 = 10;
+/// See [`ExecutionError::InputReadFailed`].
+pub const SP1_ERR_INPUT_READ_FAILED: i32 = 11;
+/// See [`ExecutionError::PcOutOfBounds`].
+pub const SP1_ERR_PC_OUT_OF_BOUNDS: i32 = 12;
+/// See [`ExecutionError::OpcodeForbidden`].
+pub const SP1_ERR_OPCODE_FORBIDDEN: i32 = 13;
+/// See [`ExecutionError::SyscallForbidden`].
+pub const SP1_ERR_SYSCALL_FORBIDDEN: i32 = 14;
+/// See [`ExecutionError::ProtectionFault`].
+pub const SP1_ERR_PROTECTION_FAULT: i32 = 15;
+/// See [`ExecutionError::AddressOutOfRange`].
+pub const SP1_ERR_ADDRESS_OUT_OF_RANGE: i32 = 16;
+/// See [`ExecutionError::Stopped`].
+pub const SP1_ERR_STOPPED: i32 = 17;
+/// See [`ExecutionError::Rewound`].
+pub const SP1_ERR_REWOUND: i32 = 18;
+/// See [`ExecutionError::SyscallFootprintViolation`].
+pub const SP1_ERR_SYSCALL_FOOTPRINT_VIOLATION: i32 = 19;
+/// See [`ExecutionError::ReservedAddress`].
+pub const SP1_ERR_RESERVED_ADDRESS: i32 = 20;
+/// See [`ExecutionError::UnconstrainedForbidden`].
+pub const SP1_ERR_UNCONSTRAINED_FORBIDDEN: i32 = 21;
+/// See [`ExecutionError::ScratchRead`].
+pub const SP1_ERR_SCRATCH_READ: i32 = 22;
+/// See [`ExecutionError::SyscallClockMismatch`].
+pub const SP1_ERR_SYSCALL_CLOCK_MISMATCH: i32 = 23;
+/// See [`ExecutionError::InputWouldBlock`].
+pub const SP1_ERR_INPUT_WOULD_BLOCK: i32 = 24;
+
+/// An opaque handle to a loaded program.
+pub struct ProgramHandle {
+    program: Program,
+}
+
+/// An opaque handle to a runtime executing a program.
+pub struct RuntimeHandle {
+    runtime: Runtime,
+}
+
+fn error_code(err: &ExecutionError) -> i32 {
+    match err {
+        ExecutionError::UnsupportedSyscall { .. } => SP1_ERR_UNSUPPORTED_SYSCALL,
+        ExecutionError::InputExhausted { .. } => SP1_ERR_INPUT_EXHAUSTED,
+        ExecutionError::Unimplemented { .. } => SP1_ERR_UNIMPLEMENTED,
+        ExecutionError::Breakpoint { .. } => SP1_ERR_BREAKPOINT,
+        ExecutionError::UninitializedRead { .. } => SP1_ERR_UNINITIALIZED_READ,
+        ExecutionError::Cancelled { .. } => SP1_ERR_CANCELLED,
+        ExecutionError::LivelockSuspected(_) => SP1_ERR_LIVELOCK_SUSPECTED,
+        ExecutionError::InputFrameMismatch { .. } => SP1_ERR_INPUT_FRAME_MISMATCH,
+        ExecutionError::DuplicateAccess { .. } => SP1_ERR_DUPLICATE_ACCESS,
+        ExecutionError::WriteToReadOnly { .. } => SP1_ERR_WRITE_TO_READ_ONLY,
+        ExecutionError::InputReadFailed { .. } => SP1_ERR_INPUT_READ_FAILED,
+        ExecutionError::PcOutOfBounds { .. } => SP1_ERR_PC_OUT_OF_BOUNDS,
+        ExecutionError::OpcodeForbidden { .. } => SP1_ERR_OPCODE_FORBIDDEN,
+        ExecutionError::SyscallForbidden { .. } => SP1_ERR_SYSCALL_FORBIDDEN,
+        ExecutionError::ProtectionFault { .. } => SP1_ERR_PROTECTION_FAULT,
+        ExecutionError::AddressOutOfRange { .. } => SP1_ERR_ADDRESS_OUT_OF_RANGE,
+        ExecutionError::Stopped(_) => SP1_ERR_STOPPED,
+        ExecutionError::Rewound { .. } => SP1_ERR_REWOUND,
+        ExecutionError::SyscallFootprintViolation { .. } => SP1_ERR_SYSCALL_FOOTPRINT_VIOLATION,
+        ExecutionError::ReservedAddress { .. } => SP1_ERR_RESERVED_ADDRESS,
+        ExecutionError::UnconstrainedForbidden { .. } => SP1_ERR_UNCONSTRAINED_FORBIDDEN,
+        ExecutionError::ScratchRead { .. } => SP1_ERR_SCRATCH_READ,
+        ExecutionError::SyscallClockMismatch { .. } => SP1_ERR_SYSCALL_CLOCK_MISMATCH,
+        ExecutionError::InputWouldBlock { .. } => SP1_ERR_INPUT_WOULD_BLOCK,
+    }
+}
+
+/// Load a program from an ELF. Returns null if the ELF is invalid.
+///
+/// # Safety
+///
+/// `elf_ptr` must point to `elf_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sp1_program_load(
+    elf_ptr: *const u8,
+    elf_len: usize,
+) -> *mut ProgramHandle {
+    if elf_ptr.is_null() {
+        return ptr::null_mut();
+    }
+    let elf = std::slice::from_raw_parts(elf_ptr, elf_len);
+    match Program::try_from_elf(elf) {
+        Ok(program) => Box::into_raw(Box::new(ProgramHandle { program })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Free a program returned by `sp1_program_load`.
+///
+/// # Safety
+///
+/// `program` must be null or a handle returned by `sp1_program_load` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn sp1_program_free(program: *mut ProgramHandle) {
+    if !program.is_null() {
+        drop(Box::from_raw(program));
+    }
+}
+
+/// Create a runtime for a program. The program handle is not consumed. Returns null if the
+/// program is invalid.
+///
+/// # Safety
+///
+/// `program` must be null or a live handle returned by `sp1_program_load`.
+#[no_mangle]
+pub unsafe extern "C" fn sp1_runtime_new(program: *const ProgramHandle) -> *mut RuntimeHandle {
+    let Some(program) = program.as_ref() else {
+        return ptr::null_mut();
+    };
+    match catch_unwind(|| Runtime::new(program.program.clone())) {
+        Ok(runtime) => Box::into_raw(Box::new(RuntimeHandle { runtime })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Free a runtime returned by `sp1_runtime_new`.
+///
+/// # Safety
+///
+/// `rt` must be null or a handle returned by `sp1_runtime_new` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn sp1_runtime_free(rt: *mut RuntimeHandle) {
+    if !rt.is_null() {
+        drop(Box::from_raw(rt));
+    }
+}
+
+/// Append bytes to the runtime's input stream.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime handle, and `ptr` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sp1_runtime_write_stdin(
+    rt: *mut RuntimeHandle,
+    ptr: *const u8,
+    len: usize,
+) -> i32 {
+    let Some(rt) = rt.as_mut() else {
+        return SP1_ERR_NULL_POINTER;
+    };
+    if ptr.is_null() {
+        return SP1_ERR_NULL_POINTER;
+    }
+    let input = std::slice::from_raw_parts(ptr, len);
+    match catch_unwind(AssertUnwindSafe(|| rt.runtime.write_stdin_slice(input))) {
+        Ok(()) => SP1_OK,
+        Err(_) => SP1_ERR_PANIC,
+    }
+}
+
+/// Execute the program. Returns `SP1_OK` or one of the `SP1_ERR_*` codes.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime handle.
+#[no_mangle]
+pub unsafe extern "C" fn sp1_runtime_run(rt: *mut RuntimeHandle) -> i32 {
+    let Some(rt) = rt.as_mut() else {
+        return SP1_ERR_NULL_POINTER;
+    };
+    match catch_unwind(AssertUnwindSafe(|| rt.runtime.try_run())) {
+        Ok(Ok(())) => SP1_OK,
+        Ok(Err(err)) => error_code(&err),
+        Err(_) => SP1_ERR_PANIC,
+    }
+}
+
+/// The number of cycles executed so far.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime handle.
+#[no_mangle]
+pub unsafe extern "C" fn sp1_runtime_cycles(rt: *const RuntimeHandle) -> u64 {
+    match rt.as_ref() {
+        Some(rt) => rt.runtime.state.global_clk as u64,
+        None => 0,
+    }
+}
+
+/// Copy up to `out_len` bytes of the program's output into `out_ptr`, returning the total length
+/// of the output. Passing a null `out_ptr` only queries the length.
+///
+/// # Safety
+///
+/// `rt` must be null or a live runtime handle, and `out_ptr` must be null or point to `out_len`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sp1_runtime_output(
+    rt: *const RuntimeHandle,
+    out_ptr: *mut u8,
+    out_len: usize,
+) -> usize {
+    let Some(rt) = rt.as_ref() else {
+        return 0;
+    };
+    let output = &rt.runtime.state.output_stream;
+    if !out_ptr.is_null() {
+        let len = out_len.min(output.len());
+        ptr::copy_nonoverlapping(output.as_ptr(), out_ptr, len);
+    }
+    output.len()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::utils::tests::FIBONACCI_ELF;
+
+    #[test]
+    fn test_ffi_run() {
+        unsafe {
+            let program = sp1_program_load(FIBONACCI_ELF.as_ptr(), FIBONACCI_ELF.len());
+            assert!(!program.is_null());
+
+            let rt = sp1_runtime_new(program);
+            assert!(!rt.is_null());
+            assert_eq!(sp1_runtime_cycles(rt), 0);
+            assert_eq!(sp1_runtime_run(rt), SP1_OK);
+
+            let mut expected = Runtime::new(Program::from(FIBONACCI_ELF));
+            expected.run();
+            assert_eq!(sp1_runtime_cycles(rt), expected.state.global_clk as u64);
+
+            let len = sp1_runtime_output(rt, ptr::null_mut(), 0);
+            assert_eq!(len, expected.state.output_stream.len());
+            let mut output = vec![0u8; len];
+            assert_eq!(sp1_runtime_output(rt, output.as_mut_ptr(), len), len);
+            assert_eq!(output, expected.state.output_stream);
+
+            sp1_runtime_free(rt);
+            sp1_program_free(program);
+        }
+    }
+
+    #[test]
+    fn test_ffi_errors() {
+        unsafe {
+            let bad_elf = [0x7f, b'E', b'L', b'F', 0, 0, 0, 0];
+            assert!(sp1_program_load(bad_elf.as_ptr(), bad_elf.len()).is_null());
+            assert!(sp1_program_load(ptr::null(), 0).is_null());
+            assert!(sp1_runtime_new(ptr::null()).is_null());
+            assert_eq!(sp1_runtime_run(ptr::null_mut()), SP1_ERR_NULL_POINTER);
+            assert_eq!(
+                sp1_runtime_write_stdin(ptr::null_mut(), bad_elf.as_ptr(), 1),
+                SP1_ERR_NULL_POINTER
+            );
+            assert_eq!(sp1_runtime_cycles(ptr::null()), 0);
+        }
+    }
+}
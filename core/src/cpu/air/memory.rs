@@ -7,6 +7,7 @@ use crate::air::{BaseAirBuilder, SP1AirBuilder, Word, WordAirBuilder};
 use crate::cpu::columns::{CpuCols, MemoryColumns, OpcodeSelectorCols, NUM_MEMORY_COLUMNS};
 use crate::cpu::CpuChip;
 use crate::memory::MemoryCols;
+use crate::operations::ByteBitDecomposition;
 use crate::runtime::Opcode;
 
 impl CpuChip {
@@ -76,7 +77,7 @@ impl CpuChip {
             .when(local.selectors.is_lb + local.selectors.is_lh)
             .assert_eq(
                 local.mem_value_is_neg,
-                memory_columns.most_sig_byte_decomp[7],
+                memory_columns.most_sig_byte_decomp.bit(7),
             );
 
         // Use the SUB opcode to compute the signed value of the memory value.
@@ -218,18 +219,15 @@ impl CpuChip {
         local: &CpuCols<AB::Var>,
         unsigned_mem_val: &Word<AB::Var>,
     ) {
-        let mut recomposed_byte = AB::Expr::zero();
-        for i in 0..8 {
-            builder.assert_bool(memory_columns.most_sig_byte_decomp[i]);
-            recomposed_byte +=
-                memory_columns.most_sig_byte_decomp[i] * AB::Expr::from_canonical_u8(1 << i);
-        }
-        builder
-            .when(local.selectors.is_lb)
-            .assert_eq(recomposed_byte.clone(), unsigned_mem_val[0]);
-        builder
-            .when(local.selectors.is_lh)
-            .assert_eq(recomposed_byte, unsigned_mem_val[1]);
+        // At most one of the selectors is set, so this is the byte holding the sign.
+        let most_sig_byte = unsigned_mem_val[0] * local.selectors.is_lb
+            + unsigned_mem_val[1] * local.selectors.is_lh;
+        ByteBitDecomposition::<AB::F>::eval(
+            builder,
+            Word([most_sig_byte]),
+            memory_columns.most_sig_byte_decomp,
+            local.selectors.is_lb + local.selectors.is_lh,
+        );
     }
 
     /// Evaluates the offset value flags.
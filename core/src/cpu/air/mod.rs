@@ -141,6 +141,17 @@ where
         );
 
         // ECALL instructions.
+        // The syscall code is read from t0 at position B and the result is written to a0 at
+        // position A, so `b` must be a register and `c` the immediate zero.
+        builder
+            .when(local.selectors.is_ecall)
+            .assert_zero(local.selectors.imm_b);
+        builder
+            .when(local.selectors.is_ecall)
+            .assert_one(local.selectors.imm_c);
+        for limb in local.op_c_val().0 {
+            builder.when(local.selectors.is_ecall).assert_zero(limb);
+        }
         // TODO:  Need to handle HALT ecall
         // For all non branch or jump instructions, verify that next.pc == pc + 4
         // builder
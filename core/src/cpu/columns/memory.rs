@@ -2,7 +2,7 @@ use core::borrow::{Borrow, BorrowMut};
 use sp1_derive::AlignedBorrow;
 use std::mem::size_of;
 
-use crate::{air::Word, memory::MemoryReadWriteCols};
+use crate::{air::Word, memory::MemoryReadWriteCols, operations::ByteBitDecomposition};
 
 pub const NUM_MEMORY_COLUMNS: usize = size_of::<MemoryColumns<u8>>();
 
@@ -28,5 +28,5 @@ pub struct MemoryColumns<T> {
 
     // LE bit decomposition for the most significant byte of memory value.  This is used to determine
     // the sign for that value (used for LB and LH).
-    pub most_sig_byte_decomp: [T; 8],
+    pub most_sig_byte_decomp: ByteBitDecomposition<T>,
 }
@@ -76,7 +76,7 @@ pub struct CpuCols<T> {
 
     /// The memory value is negative column is equal to:
     ///
-    /// > (is_lbu | is_lhu) & (most_sig_byte_decomp[7] == 1)
+    /// > (is_lbu | is_lhu) & (most_sig_byte_decomp.bit(7) == 1)
     pub mem_value_is_neg: T,
 
     /// The unsigned memory value is the value after the offset logic is applied. Used for the load
@@ -45,6 +45,7 @@ pub struct OpcodeSelectorCols<T> {
 
     /// Miscellaneous.
     pub is_auipc: T,
+    pub is_ecall: T,
     pub is_noop: T,
     pub reg_0_write: T,
 }
@@ -84,6 +85,8 @@ impl<F: PrimeField> OpcodeSelectorCols<F> {
             self.is_jalr = F::one();
         } else if instruction.opcode == Opcode::AUIPC {
             self.is_auipc = F::one();
+        } else if instruction.opcode == Opcode::ECALL {
+            self.is_ecall = F::one();
         } else if instruction.opcode == Opcode::UNIMP {
             self.is_noop = F::one();
         }
@@ -126,6 +129,7 @@ impl<T> IntoIterator for OpcodeSelectorCols<T> {
             self.is_jalr,
             self.is_jal,
             self.is_auipc,
+            self.is_ecall,
             self.is_noop,
             self.reg_0_write,
         ]
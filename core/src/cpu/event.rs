@@ -1,9 +1,10 @@
-use crate::runtime::Instruction;
+use crate::runtime::{Instruction, Opcode};
+use serde::{Deserialize, Serialize};
 
 use super::memory::MemoryRecordEnum;
 
 /// A standard format for describing CPU operations that need to be proven.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct CpuEvent {
     /// The current shard.
     pub shard: u32,
@@ -41,3 +42,36 @@ pub struct CpuEvent {
     /// The memory access record for the memory value.
     pub memory_record: Option<MemoryRecordEnum>,
 }
+
+impl CpuEvent {
+    /// The immediate of a U-type instruction (`auipc`), or `None` for other instructions.
+    ///
+    /// The immediate is both the second and the third operand of a U-type instruction, as both
+    /// are immediates in its encoding, so `b` and `c` are always equal for these events and carry
+    /// no other information.
+    pub fn u_imm(&self) -> Option<u32> {
+        match self.instruction.opcode {
+            Opcode::AUIPC => Some(self.b),
+            _ => None,
+        }
+    }
+}
+
+/// The execution of an `auipc`, from which the CPU chip populates its AUIPC columns.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuipcEvent {
+    /// The shard of the instruction.
+    pub shard: u32,
+
+    /// The clock of the instruction.
+    pub clk: u32,
+
+    /// The program counter of the instruction.
+    pub pc: u32,
+
+    /// The upper immediate, already shifted.
+    pub imm: u32,
+
+    /// The value written to the destination register, `pc + imm` wrapping around.
+    pub result: u32,
+}
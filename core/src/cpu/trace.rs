@@ -2,7 +2,7 @@ use super::columns::{
     AuipcCols, BranchCols, JumpCols, CPU_COL_MAP, NUM_AUIPC_COLS, NUM_BRANCH_COLS, NUM_CPU_COLS,
     NUM_JUMP_COLS, NUM_MEMORY_COLUMNS,
 };
-use super::{CpuChip, CpuEvent};
+use super::{AuipcEvent, CpuChip, CpuEvent};
 use crate::air::MachineAir;
 use crate::alu::{self, AluEvent};
 use crate::bytes::{ByteLookupEvent, ByteOpcode};
@@ -13,11 +13,11 @@ use crate::field::event::FieldEvent;
 use crate::memory::MemoryCols;
 use crate::runtime::{ExecutionRecord, Opcode};
 use hashbrown::HashMap;
+use p3_baby_bear::BabyBear;
 use p3_field::PrimeField;
 use p3_matrix::dense::RowMajorMatrix;
 use p3_maybe_rayon::prelude::IntoParallelRefIterator;
 use p3_maybe_rayon::prelude::ParallelIterator;
-use p3_maybe_rayon::prelude::ParallelSlice;
 use std::borrow::BorrowMut;
 use tracing::instrument;
 
@@ -35,12 +35,19 @@ impl<F: PrimeField> MachineAir<F> for CpuChip {
         let mut new_alu_events = HashMap::new();
         let mut new_blu_events = Vec::new();
         let mut new_field_events: Vec<FieldEvent> = Vec::new();
+        let auipc_events = Self::auipc_events(input);
 
         // Generate the trace rows for each event.
-        let rows_with_events = input
-            .cpu_events
+        let chunk_size = std::cmp::max(input.cpu_events.len() / num_cpus::get(), 1);
+        let chunks = input.cpu_events.chunks(chunk_size).collect::<Vec<_>>();
+        let rows_with_events = chunks
             .par_iter()
-            .map(|op: &CpuEvent| self.event_to_row::<F>(*op))
+            .map(|ops: &&[CpuEvent]| {
+                ops.iter()
+                    .map(|op| self.event_to_row::<F>(*op, auipc_events.get(&(op.shard, op.clk))))
+                    .collect::<Vec<_>>()
+            })
+            .flatten()
             .collect::<Vec<_>>();
 
         let mut rows = Vec::<F>::new();
@@ -78,16 +85,19 @@ impl<F: PrimeField> MachineAir<F> for CpuChip {
         let mut new_alu_events = HashMap::with_capacity(input.cpu_events.len());
         let mut new_blu_events = Vec::with_capacity(input.cpu_events.len());
         let mut new_field_events: Vec<FieldEvent> = Vec::with_capacity(input.cpu_events.len());
+        let auipc_events = Self::auipc_events(input);
 
         // Generate the trace rows for each event.
         let chunk_size = std::cmp::max(input.cpu_events.len() / num_cpus::get(), 1);
-        let events = input
-            .cpu_events
-            .par_chunks(chunk_size)
-            .map(|ops: &[CpuEvent]| {
+        let chunks = input.cpu_events.chunks(chunk_size).collect::<Vec<_>>();
+        let events = chunks
+            .par_iter()
+            .map(|ops: &&[CpuEvent]| {
                 ops.iter()
                     .map(|op| {
-                        let (_, alu_events, blu_events, field_events) = self.event_to_row::<F>(*op);
+                        let auipc = auipc_events.get(&(op.shard, op.clk));
+                        let (_, alu_events, blu_events, field_events) =
+                            self.event_to_row::<F>(*op, auipc);
                         (alu_events, blu_events, field_events)
                     })
                     .collect::<Vec<_>>()
@@ -117,10 +127,29 @@ impl<F: PrimeField> MachineAir<F> for CpuChip {
 }
 
 impl CpuChip {
-    /// Create a row from an event.
+    /// The AUIPC events of `input`, by shard and clock.
+    fn auipc_events(input: &ExecutionRecord) -> HashMap<(u32, u32), AuipcEvent> {
+        input
+            .auipc_events
+            .iter()
+            .map(|event| ((event.shard, event.clk), *event))
+            .collect()
+    }
+
+    /// The byte lookups the trace of `event` adds, which do not depend on the field.
+    pub(crate) fn byte_lookups(
+        &self,
+        event: CpuEvent,
+        auipc: Option<&AuipcEvent>,
+    ) -> Vec<ByteLookupEvent> {
+        self.event_to_row::<BabyBear>(event, auipc).2
+    }
+
+    /// Create a row from an event, and the AUIPC event of the same instruction if it is an AUIPC.
     fn event_to_row<F: PrimeField>(
         &self,
         event: CpuEvent,
+        auipc: Option<&AuipcEvent>,
     ) -> (
         [F; NUM_CPU_COLS],
         HashMap<Opcode, Vec<alu::AluEvent>>,
@@ -169,7 +198,7 @@ impl CpuChip {
         self.populate_memory(cols, event, &mut new_alu_events, &mut new_blu_events);
         self.populate_branch(cols, event, &mut new_alu_events);
         self.populate_jump(cols, event, &mut new_alu_events);
-        self.populate_auipc(cols, event, &mut new_alu_events);
+        self.populate_auipc(cols, event, auipc, &mut new_alu_events);
 
         // Assert that the instruction is not a no-op.
         cols.is_real = F::one();
@@ -265,11 +294,10 @@ impl CpuChip {
                     most_sig_mem_value_byte = cols.unsigned_mem_val.to_u32().to_le_bytes()[1];
                 };
 
-                for i in (0..8).rev() {
-                    memory_columns.most_sig_byte_decomp[i] =
-                        F::from_canonical_u8(most_sig_mem_value_byte >> i & 0x01);
-                }
-                if memory_columns.most_sig_byte_decomp[7] == F::one() {
+                memory_columns
+                    .most_sig_byte_decomp
+                    .populate(most_sig_mem_value_byte);
+                if memory_columns.most_sig_byte_decomp.bit(7) == F::one() {
                     cols.mem_value_is_neg = F::one();
                     let sub_event = AluEvent {
                         clk: event.clk,
@@ -453,20 +481,27 @@ impl CpuChip {
         &self,
         cols: &mut CpuCols<F>,
         event: CpuEvent,
+        auipc: Option<&AuipcEvent>,
         alu_events: &mut HashMap<Opcode, Vec<alu::AluEvent>>,
     ) {
         if matches!(event.instruction.opcode, Opcode::AUIPC) {
+            let auipc = auipc.unwrap_or_else(|| {
+                panic!(
+                    "missing the AUIPC event of shard {} at clk {}",
+                    event.shard, event.clk
+                )
+            });
             let auipc_columns: &mut AuipcCols<F> =
                 cols.opcode_specific_columns[..NUM_AUIPC_COLS].borrow_mut();
 
-            auipc_columns.pc = event.pc.into();
+            auipc_columns.pc = auipc.pc.into();
 
             let add_event = AluEvent {
-                clk: event.clk,
+                clk: auipc.clk,
                 opcode: Opcode::ADD,
-                a: event.a,
-                b: event.pc,
-                c: event.b,
+                a: auipc.result,
+                b: auipc.pc,
+                c: auipc.imm,
             };
 
             alu_events
@@ -509,13 +544,16 @@ impl CpuChip {
 #[cfg(test)]
 mod tests {
     use p3_baby_bear::BabyBear;
+    use p3_field::AbstractField;
     use p3_matrix::dense::RowMajorMatrix;
+    use std::borrow::Borrow;
 
     use super::*;
 
+    use crate::air::Word;
     use crate::utils::{uni_stark_prove as prove, uni_stark_verify as verify};
     use crate::{
-        runtime::{tests::simple_program, ExecutionRecord, Instruction, Runtime},
+        runtime::{tests::simple_program, ExecutionRecord, Instruction, Program, Runtime},
         utils::{BabyBearPoseidon2, StarkUtils},
     };
 
@@ -542,7 +580,8 @@ mod tests {
             c_record: None,
             memory: None,
             memory_record: None,
-        }];
+        }]
+        .into();
         let chip = CpuChip::default();
         let trace: RowMajorMatrix<BabyBear> =
             chip.generate_trace(&shard, &mut ExecutionRecord::default());
@@ -581,4 +620,94 @@ mod tests {
         let mut challenger = config.challenger();
         verify(&config, &chip, &mut challenger, &proof).unwrap();
     }
+
+    #[test]
+    fn prove_auipc() {
+        const PC_BASE: u32 = 0x2000;
+        let imms = [0x1000, 0, 0xffff_f000, 0xffff_e000];
+        let instructions = imms
+            .iter()
+            .enumerate()
+            .map(|(i, imm)| Instruction::new(Opcode::AUIPC, 10 + i as u32, *imm, *imm, true, true))
+            .collect();
+        let mut runtime = Runtime::new(Program::new(instructions, PC_BASE, PC_BASE));
+        runtime.run();
+
+        // The last two wrap around.
+        let expected = [0x3000, 0x2004, 0x1008, 0xc];
+        assert_eq!(
+            [10, 11, 12, 13].map(|register| runtime.registers()[register]),
+            expected
+        );
+        let events = &runtime.record.auipc_events;
+        assert_eq!(events.len(), imms.len());
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(event.pc, PC_BASE + 4 * i as u32);
+            assert_eq!(event.imm, imms[i]);
+            assert_eq!(event.result, expected[i]);
+            assert_eq!(runtime.record.cpu_events[i].u_imm(), Some(imms[i]));
+        }
+
+        let chip = CpuChip::default();
+        let mut output = ExecutionRecord::default();
+        let trace: RowMajorMatrix<BabyBear> = chip.generate_trace(&runtime.record, &mut output);
+        for (row, event) in trace.rows().zip(events.iter()) {
+            let cols: &CpuCols<BabyBear> = row.borrow();
+            let auipc: &AuipcCols<BabyBear> =
+                cols.opcode_specific_columns[..NUM_AUIPC_COLS].borrow();
+            assert_eq!(auipc.pc, Word::from(event.pc));
+        }
+        assert_eq!(
+            output
+                .add_events
+                .iter()
+                .map(|event| (event.a, event.b, event.c))
+                .collect::<Vec<_>>(),
+            events
+                .iter()
+                .map(|event| (event.result, event.pc, event.imm))
+                .collect::<Vec<_>>()
+        );
+
+        let config = BabyBearPoseidon2::new();
+        let mut challenger = config.challenger();
+        let proof = prove::<BabyBearPoseidon2, _>(&config, &chip, &mut challenger, trace);
+        let mut challenger = config.challenger();
+        verify(&config, &chip, &mut challenger, &proof).unwrap();
+    }
+
+    #[test]
+    fn prove_signed_loads() {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, 0x80ff7f81, false, true),
+            Instruction::new(Opcode::ADD, 6, 0, 0x1000, false, true),
+            Instruction::new(Opcode::SW, 5, 6, 0, false, true),
+            Instruction::new(Opcode::LB, 10, 6, 0, false, true),
+            Instruction::new(Opcode::LB, 11, 6, 1, false, true),
+            Instruction::new(Opcode::LH, 12, 6, 0, false, true),
+            Instruction::new(Opcode::LH, 13, 6, 2, false, true),
+            Instruction::new(Opcode::LBU, 14, 6, 3, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime.run();
+        assert_eq!(
+            [10, 11, 12, 13, 14].map(|register| runtime.registers()[register]),
+            [0xffffff81, 0x7f, 0x7f81, 0xffff80ff, 0x80]
+        );
+
+        let chip = CpuChip::default();
+        let trace: RowMajorMatrix<BabyBear> =
+            chip.generate_trace(&runtime.record, &mut ExecutionRecord::default());
+        let negative = trace
+            .rows()
+            .filter(|row| row[CPU_COL_MAP.mem_value_is_neg] == BabyBear::one())
+            .count();
+        assert_eq!(negative, 2);
+
+        let config = BabyBearPoseidon2::new();
+        let mut challenger = config.challenger();
+        let proof = prove::<BabyBearPoseidon2, _>(&config, &chip, &mut challenger, trace);
+        let mut challenger = config.challenger();
+        verify(&config, &chip, &mut challenger, &proof).unwrap();
+    }
 }
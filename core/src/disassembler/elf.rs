@@ -1,9 +1,15 @@
-use elf::abi::{EM_RISCV, ET_EXEC, PF_X, PT_LOAD};
+use core::fmt::{Display, Formatter};
+use elf::abi::{EM_RISCV, ET_EXEC, PF_X, PT_LOAD, STT_FUNC, STT_OBJECT};
 use elf::endian::LittleEndian;
 use elf::file::Class;
+use elf::parse::ParseError;
 use elf::ElfBytes;
 use std::cmp::min;
 use std::collections::BTreeMap;
+use std::ops::Range;
+
+use super::{read_metadata, GuestMetadata};
+use crate::runtime::{Symbol, SymbolTable};
 
 /// The maximum size of the memory in bytes.
 pub const MAXIMUM_MEMORY_SIZE: u32 = u32::MAX;
@@ -11,6 +17,139 @@ pub const MAXIMUM_MEMORY_SIZE: u32 = u32::MAX;
 /// The size of a word in bytes.
 pub const WORD_SIZE: usize = 4;
 
+/// The maximum number of program headers of an ELF.
+const MAX_SEGMENTS: usize = 256;
+
+/// The maximum number of bytes the segments of an ELF can load, including their zero-filled part.
+pub const MAX_LOADED_SIZE: usize = 1 << 26;
+
+/// The magic bytes every ELF starts with.
+const ELFMAG: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+
+/// The size of the header of a 32-bit ELF.
+const ELF32_HEADER_SIZE: usize = 52;
+
+/// The index of the class in the identification bytes, and the class of 32-bit ELFs.
+const EI_CLASS: usize = 4;
+const ELFCLASS32: u8 = 1;
+
+/// An error decoding an ELF, see [`Elf::try_decode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElfError {
+    /// The input does not start with the ELF magic bytes.
+    BadMagic,
+
+    /// The input is shorter than an ELF header.
+    TruncatedHeader { len: usize },
+
+    /// The ELF is not for 32-bit RISC-V.
+    NotRv32 { class: u8, machine: u16 },
+
+    /// The ELF is not an executable, e.g. an object file.
+    NotExecutable { e_type: u16 },
+
+    /// The entrypoint is unaligned or at the end of the address space.
+    InvalidEntrypoint { entry: u32 },
+
+    /// The ELF has more program headers than supported.
+    TooManySegments { count: usize },
+
+    /// A segment does not start at a word boundary.
+    UnalignedSegment { vaddr: u32 },
+
+    /// A value does not fit in 32 bits, or a segment extends past the end of the address space.
+    AddressOverflow { vaddr: u32, size: u32 },
+
+    /// The bytes of a segment extend past the end of the file, or past the end of the segment.
+    SegmentOutOfBounds { offset: u32, size: u32 },
+
+    /// Two loaded segments overlap, starting at `first` and `second`.
+    OverlappingSegments { first: u32, second: u32 },
+
+    /// The segments load more than [`MAX_LOADED_SIZE`] bytes.
+    ImageTooLarge { size: usize },
+
+    /// The ELF has no executable code.
+    NoText,
+
+    /// The word at `addr` of an executable segment is not a RV32IM instruction.
+    InvalidInstruction { addr: u32, word: u32 },
+
+    /// The parser rejected the ELF.
+    Parse(String),
+}
+
+impl ElfError {
+    fn parse(err: ParseError) -> Self {
+        ElfError::Parse(err.to_string())
+    }
+}
+
+impl Display for ElfError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ElfError::BadMagic => write!(f, "not an elf: bad magic bytes"),
+            ElfError::TruncatedHeader { len } => {
+                write!(f, "elf header truncated to {} bytes", len)
+            }
+            ElfError::NotRv32 { class, machine } => write!(
+                f,
+                "must be a 32-bit riscv elf, got class {} and machine {}",
+                class, machine
+            ),
+            ElfError::NotExecutable { e_type } => {
+                write!(f, "must be executable, got elf type {}", e_type)
+            }
+            ElfError::InvalidEntrypoint { entry } => {
+                write!(f, "invalid entrypoint 0x{:08x}", entry)
+            }
+            ElfError::TooManySegments { count } => write!(
+                f,
+                "too many program headers: {} instead of at most {}",
+                count, MAX_SEGMENTS
+            ),
+            ElfError::UnalignedSegment { vaddr } => {
+                write!(f, "segment vaddr 0x{:08x} is unaligned", vaddr)
+            }
+            ElfError::AddressOverflow { vaddr, size } => write!(
+                f,
+                "segment of 0x{:x} bytes at 0x{:08x} exceeds the address space",
+                size, vaddr
+            ),
+            ElfError::SegmentOutOfBounds { offset, size } => write!(
+                f,
+                "segment of 0x{:x} bytes at offset 0x{:x} is out of bounds",
+                size, offset
+            ),
+            ElfError::OverlappingSegments { first, second } => write!(
+                f,
+                "segments at 0x{:08x} and 0x{:08x} overlap",
+                first, second
+            ),
+            ElfError::ImageTooLarge { size } => write!(
+                f,
+                "segments load 0x{:x} bytes, more than the maximum of 0x{:x}",
+                size, MAX_LOADED_SIZE
+            ),
+            ElfError::NoText => write!(f, "elf has no executable segment"),
+            ElfError::InvalidInstruction { addr, word } => {
+                write!(f, "invalid instruction 0x{:08x} at 0x{:08x}", word, addr)
+            }
+            ElfError::Parse(err) => write!(f, "failed to parse elf: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ElfError {}
+
+/// Convert a 64-bit field of the parsed ELF, which always fits in a 32-bit ELF.
+fn to_u32(value: u64) -> Result<u32, ElfError> {
+    value.try_into().map_err(|_| ElfError::AddressOverflow {
+        vaddr: u32::MAX,
+        size: 0,
+    })
+}
+
 /// A RV32IM ELF file.
 #[derive(Debug, Clone)]
 pub struct Elf {
@@ -25,6 +164,16 @@ pub struct Elf {
 
     /// The initial memory image, useful for global constants.
     pub memory_image: BTreeMap<u32, u32>,
+
+    /// The function symbols of the ELF file, empty if it was stripped.
+    pub symbols: SymbolTable,
+
+    /// The data symbols of the ELF file, such as the `tohost` word of the riscv-tests, empty if
+    /// it was stripped.
+    pub objects: SymbolTable,
+
+    /// The build information of the guest, if the ELF has a metadata section.
+    pub metadata: Option<GuestMetadata>,
 }
 
 impl Elf {
@@ -34,122 +183,429 @@ impl Elf {
         pc_start: u32,
         pc_base: u32,
         memory_image: BTreeMap<u32, u32>,
+        symbols: SymbolTable,
     ) -> Self {
         Self {
             instructions,
             pc_start,
             pc_base,
             memory_image,
+            symbols,
+            objects: SymbolTable::default(),
+            metadata: None,
         }
     }
 
     /// Parse the ELF file into a vector of 32-bit encoded instructions and the first memory address.
     ///
-    /// Reference: https://en.wikipedia.org/wiki/Executable_and_Linkable_Format
+    /// Panics if the ELF is malformed, see [`Elf::try_decode`].
     pub fn decode(input: &[u8]) -> Self {
-        let mut image: BTreeMap<u32, u32> = BTreeMap::new();
-        // Parse the ELF file assuming that it is little-endian..
-        let elf = ElfBytes::<LittleEndian>::minimal_parse(input).expect("failed to parse elf");
+        Self::try_decode(input).expect("failed to decode elf")
+    }
+
+    /// Parse the ELF file into a vector of 32-bit encoded instructions and the first memory address,
+    /// returning an error instead of panicking if it is malformed.
+    ///
+    /// Reference: https://en.wikipedia.org/wiki/Executable_and_Linkable_Format
+    pub fn try_decode(input: &[u8]) -> Result<Self, ElfError> {
+        // Check the identification and the machine before handing the header to the parser, for
+        // precise errors.
+        if input.len() < 4 || input[..4] != ELFMAG {
+            return Err(ElfError::BadMagic);
+        }
+        if input.len() < ELF32_HEADER_SIZE {
+            return Err(ElfError::TruncatedHeader { len: input.len() });
+        }
+        let machine = u16::from_le_bytes([input[18], input[19]]);
+        if input[EI_CLASS] != ELFCLASS32 || machine != EM_RISCV {
+            return Err(ElfError::NotRv32 {
+                class: input[EI_CLASS],
+                machine,
+            });
+        }
 
-        // Some sanity checks to make sure that the ELF file is valid.
+        // Parse the ELF file assuming that it is little-endian..
+        let elf = ElfBytes::<LittleEndian>::minimal_parse(input).map_err(ElfError::parse)?;
         if elf.ehdr.class != Class::ELF32 {
-            panic!("must be a 32-bit elf");
-        } else if elf.ehdr.e_machine != EM_RISCV {
-            panic!("must be a riscv machine");
+            return Err(ElfError::NotRv32 {
+                class: input[EI_CLASS],
+                machine,
+            });
         } else if elf.ehdr.e_type != ET_EXEC {
-            panic!("must be executable");
+            return Err(ElfError::NotExecutable {
+                e_type: elf.ehdr.e_type,
+            });
         }
 
         // Get the entrypoint of the ELF file as an u32.
-        let entry: u32 = elf
-            .ehdr
-            .e_entry
-            .try_into()
-            .expect("e_entry was larger than 32 bits");
+        let entry = to_u32(elf.ehdr.e_entry)?;
 
         // Make sure the entrypoint is valid.
         if entry == MAXIMUM_MEMORY_SIZE || entry % WORD_SIZE as u32 != 0 {
-            panic!("invalid entrypoint");
+            return Err(ElfError::InvalidEntrypoint { entry });
         }
 
         // Get the segments of the ELF file.
-        let segments = elf.segments().expect("failed to get segments");
-        if segments.len() > 256 {
-            panic!("too many program headers");
+        let segments = elf.segments().ok_or(ElfError::NoText)?;
+        if segments.len() > MAX_SEGMENTS {
+            return Err(ElfError::TooManySegments {
+                count: segments.len(),
+            });
         }
 
+        let mut image: BTreeMap<u32, u32> = BTreeMap::new();
         let mut instructions: Vec<u32> = Vec::new();
         let mut base_address = u32::MAX;
+        let mut loaded: Vec<Range<u32>> = Vec::new();
 
         // Only read segments that are executable instructions that are also PT_LOAD.
         for segment in segments.iter().filter(|x| x.p_type == PT_LOAD) {
-            // Get the file size of the segment as an u32.
-            let file_size: u32 = segment
-                .p_filesz
-                .try_into()
-                .expect("filesize was larger than 32 bits");
-            if file_size == MAXIMUM_MEMORY_SIZE {
-                panic!("invalid segment file_size");
-            }
-
-            // Get the memory size of the segment as an u32.
-            let mem_size: u32 = segment
-                .p_memsz
-                .try_into()
-                .expect("mem_size was larger than 32 bits");
-            if mem_size == MAXIMUM_MEMORY_SIZE {
-                panic!("Invalid segment mem_size");
-            }
-
-            // Get the virtual address of the segment as an u32.
-            let vaddr: u32 = segment
-                .p_vaddr
-                .try_into()
-                .expect("vaddr was larger than 32 bits");
+            let file_size = to_u32(segment.p_filesz)?;
+            let mem_size = to_u32(segment.p_memsz)?;
+            let vaddr = to_u32(segment.p_vaddr)?;
+            let offset = to_u32(segment.p_offset)?;
             if vaddr % WORD_SIZE as u32 != 0 {
-                panic!("vaddr {vaddr:08x} is unaligned");
+                return Err(ElfError::UnalignedSegment { vaddr });
             }
 
+            // The segment must fit below the end of the address space, and its bytes in the file.
+            let end = vaddr
+                .checked_add(mem_size)
+                .ok_or(ElfError::AddressOverflow {
+                    vaddr,
+                    size: mem_size,
+                })?;
+            let file_end = offset
+                .checked_add(file_size)
+                .filter(|&file_end| file_end as usize <= input.len());
+            if file_size > mem_size || file_end.is_none() {
+                return Err(ElfError::SegmentOutOfBounds {
+                    offset,
+                    size: file_size,
+                });
+            }
+            let total = loaded.iter().map(|range| range.len()).sum::<usize>();
+            if total + mem_size as usize > MAX_LOADED_SIZE {
+                return Err(ElfError::ImageTooLarge {
+                    size: total + mem_size as usize,
+                });
+            }
+            if let Some(other) = loaded
+                .iter()
+                .find(|other| other.start < end && vaddr < other.end)
+            {
+                return Err(ElfError::OverlappingSegments {
+                    first: other.start,
+                    second: vaddr,
+                });
+            }
+            loaded.push(vaddr..end);
+
             // If the virtual address is less than the first memory address, then update the first
             // memory address.
             if (segment.p_flags & PF_X) != 0 && base_address > vaddr {
                 base_address = vaddr;
             }
 
-            // Get the offset to the segment.
-            let offset: u32 = segment
-                .p_offset
-                .try_into()
-                .expect("offset was larger than 32 bits");
-
             // Read the segment and decode each word as an instruction.
+            let data = &input[offset as usize..(offset + file_size) as usize];
             for i in (0..mem_size).step_by(WORD_SIZE) {
-                let addr = vaddr.checked_add(i).expect("invalid segment vaddr");
-                if addr == MAXIMUM_MEMORY_SIZE {
-                    panic!("address [0x{addr:08x}] exceeds maximum address for guest programs [0x{MAXIMUM_MEMORY_SIZE:08x}]");
-                }
+                let addr = vaddr + i;
 
-                // If we are reading past the end of the file, then break.
-                if i >= file_size {
-                    image.insert(addr, 0);
-                    continue;
-                }
-
-                // Get the word as an u32 but make sure we don't read past the end of the file.
-                let mut word = 0;
-                let len = min(file_size - i, WORD_SIZE as u32);
-                for j in 0..len {
-                    let offset = (offset + i + j) as usize;
-                    let byte = input.get(offset).expect("invalid segment offset");
-                    word |= (*byte as u32) << (j * 8);
-                }
+                // If we are reading past the end of the file, then the word is zero.
+                let start = (i as usize).min(data.len());
+                let bytes = &data[start..min(start + WORD_SIZE, data.len())];
+                let mut word = [0; WORD_SIZE];
+                word[..bytes.len()].copy_from_slice(bytes);
+                let word = u32::from_le_bytes(word);
                 image.insert(addr, word);
-                if (segment.p_flags & PF_X) != 0 {
+                if (segment.p_flags & PF_X) != 0 && i < file_size {
                     instructions.push(word);
                 }
             }
         }
+        if instructions.is_empty() {
+            return Err(ElfError::NoText);
+        }
+
+        // Read the function and data symbols, if the ELF file was not stripped.
+        let mut symbols = Vec::new();
+        let mut objects = Vec::new();
+        if let Some((symtab, strtab)) = elf.symbol_table().map_err(ElfError::parse)? {
+            for symbol in symtab.iter() {
+                let table = match symbol.st_symtype() {
+                    STT_FUNC => &mut symbols,
+                    STT_OBJECT => &mut objects,
+                    _ => continue,
+                };
+                let name = strtab
+                    .get(symbol.st_name as usize)
+                    .map_err(ElfError::parse)?;
+                let addr = to_u32(symbol.st_value)?;
+                let size = to_u32(symbol.st_size)?;
+                table.push(Symbol::new(name, addr, size));
+            }
+        }
+
+        Ok(Elf {
+            objects: SymbolTable::new(objects),
+            metadata: read_metadata(&elf),
+            ..Elf::new(
+                instructions,
+                entry,
+                base_address,
+                image,
+                SymbolTable::new(symbols),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::Program;
+    use crate::utils::tests::FIBONACCI_ELF;
+
+    const TEXT: u32 = 0x1000;
+    const NOP: u32 = 0x0000_0013;
+
+    /// A loadable segment: its virtual address, flags, bytes in the file and size in memory.
+    struct Segment {
+        vaddr: u32,
+        flags: u32,
+        data: Vec<u8>,
+        mem_size: u32,
+    }
+
+    fn text(words: &[u32]) -> Segment {
+        let data = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        Segment {
+            vaddr: TEXT,
+            flags: PF_X,
+            data,
+            mem_size: words.len() as u32 * 4,
+        }
+    }
+
+    /// Build a little-endian ELF32 executable for RISC-V loading `segments`, with no sections.
+    fn elf_bytes(entry: u32, segments: &[Segment]) -> Vec<u8> {
+        let mut bytes = vec![0; ELF32_HEADER_SIZE];
+        bytes[..4].copy_from_slice(&ELFMAG);
+        bytes[4..7].copy_from_slice(&[ELFCLASS32, 1, 1]);
+        let put = |bytes: &mut Vec<u8>, offset: usize, value: &[u8]| {
+            bytes[offset..offset + value.len()].copy_from_slice(value)
+        };
+        put(&mut bytes, 16, &ET_EXEC.to_le_bytes());
+        put(&mut bytes, 18, &EM_RISCV.to_le_bytes());
+        put(&mut bytes, 20, &1u32.to_le_bytes());
+        put(&mut bytes, 24, &entry.to_le_bytes());
+        put(&mut bytes, 28, &(ELF32_HEADER_SIZE as u32).to_le_bytes());
+        put(&mut bytes, 40, &(ELF32_HEADER_SIZE as u16).to_le_bytes());
+        put(&mut bytes, 42, &32u16.to_le_bytes());
+        put(&mut bytes, 44, &(segments.len() as u16).to_le_bytes());
+        put(&mut bytes, 46, &40u16.to_le_bytes());
 
-        Elf::new(instructions, entry, base_address, image)
+        let mut offset = ELF32_HEADER_SIZE + 32 * segments.len();
+        for segment in segments {
+            let header = [
+                PT_LOAD,
+                offset as u32,
+                segment.vaddr,
+                segment.vaddr,
+                segment.data.len() as u32,
+                segment.mem_size,
+                segment.flags,
+                4,
+            ];
+            bytes.extend(header.iter().flat_map(|field| field.to_le_bytes()));
+            offset += segment.data.len();
+        }
+        for segment in segments {
+            bytes.extend_from_slice(&segment.data);
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> ElfError {
+        Program::try_from_elf(bytes).unwrap_err()
+    }
+
+    #[test]
+    fn test_decode() {
+        let data = Segment {
+            vaddr: 0x2000,
+            flags: 0,
+            data: 7u32.to_le_bytes().to_vec(),
+            mem_size: 8,
+        };
+        let program = Program::try_from_elf(&elf_bytes(TEXT, &[text(&[NOP, NOP]), data])).unwrap();
+        assert_eq!((program.pc_start, program.pc_base), (TEXT, TEXT));
+        assert_eq!(program.instructions.len(), 2);
+        assert_eq!(program.memory_image[&0x2000], 7);
+        assert_eq!(program.memory_image[&0x2004], 0);
+
+        Program::try_from_elf(FIBONACCI_ELF).unwrap();
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        assert_eq!(decode(b""), ElfError::BadMagic);
+        let mut bytes = elf_bytes(TEXT, &[text(&[NOP])]);
+        bytes[3] = b'G';
+        assert_eq!(decode(&bytes), ElfError::BadMagic);
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let bytes = elf_bytes(TEXT, &[text(&[NOP])]);
+        assert_eq!(decode(&bytes[..20]), ElfError::TruncatedHeader { len: 20 });
+    }
+
+    #[test]
+    fn test_not_rv32() {
+        let mut bytes = elf_bytes(TEXT, &[text(&[NOP])]);
+        bytes[18] = 62;
+        assert_eq!(
+            decode(&bytes),
+            ElfError::NotRv32 {
+                class: ELFCLASS32,
+                machine: 62
+            }
+        );
+        let mut bytes = elf_bytes(TEXT, &[text(&[NOP])]);
+        bytes[EI_CLASS] = 2;
+        assert!(matches!(decode(&bytes), ElfError::NotRv32 { class: 2, .. }));
+    }
+
+    #[test]
+    fn test_not_executable() {
+        let mut bytes = elf_bytes(TEXT, &[text(&[NOP])]);
+        bytes[16] = 1;
+        assert_eq!(decode(&bytes), ElfError::NotExecutable { e_type: 1 });
+    }
+
+    #[test]
+    fn test_invalid_entrypoint() {
+        let bytes = elf_bytes(TEXT + 2, &[text(&[NOP])]);
+        assert_eq!(
+            decode(&bytes),
+            ElfError::InvalidEntrypoint { entry: TEXT + 2 }
+        );
+    }
+
+    #[test]
+    fn test_too_many_segments() {
+        let mut bytes = elf_bytes(TEXT, &[]);
+        bytes[44..46].copy_from_slice(&300u16.to_le_bytes());
+        bytes.resize(ELF32_HEADER_SIZE + 300 * 32, 0);
+        assert_eq!(decode(&bytes), ElfError::TooManySegments { count: 300 });
+    }
+
+    #[test]
+    fn test_unaligned_segment() {
+        let mut segment = text(&[NOP]);
+        segment.vaddr += 2;
+        assert_eq!(
+            decode(&elf_bytes(TEXT, &[segment])),
+            ElfError::UnalignedSegment { vaddr: TEXT + 2 }
+        );
+    }
+
+    #[test]
+    fn test_address_overflow() {
+        let mut segment = text(&[NOP]);
+        segment.vaddr = 0xffff_f000;
+        segment.mem_size = 0x2000;
+        assert_eq!(
+            decode(&elf_bytes(TEXT, &[segment])),
+            ElfError::AddressOverflow {
+                vaddr: 0xffff_f000,
+                size: 0x2000
+            }
+        );
+    }
+
+    #[test]
+    fn test_segment_out_of_bounds() {
+        let mut bytes = elf_bytes(TEXT, &[text(&[NOP, NOP])]);
+        bytes.truncate(bytes.len() - 4);
+        assert_eq!(
+            decode(&bytes),
+            ElfError::SegmentOutOfBounds {
+                offset: (ELF32_HEADER_SIZE + 32) as u32,
+                size: 8
+            }
+        );
+    }
+
+    #[test]
+    fn test_overlapping_segments() {
+        let mut second = text(&[NOP, NOP]);
+        second.vaddr += 4;
+        assert_eq!(
+            decode(&elf_bytes(TEXT, &[text(&[NOP, NOP]), second])),
+            ElfError::OverlappingSegments {
+                first: TEXT,
+                second: TEXT + 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_image_too_large() {
+        let mut segment = text(&[NOP]);
+        segment.mem_size = 2 * MAX_LOADED_SIZE as u32;
+        assert_eq!(
+            decode(&elf_bytes(TEXT, &[segment])),
+            ElfError::ImageTooLarge {
+                size: 2 * MAX_LOADED_SIZE
+            }
+        );
+    }
+
+    #[test]
+    fn test_no_text() {
+        let mut segment = text(&[NOP]);
+        segment.flags = 0;
+        assert_eq!(decode(&elf_bytes(TEXT, &[segment])), ElfError::NoText);
+    }
+
+    #[test]
+    fn test_invalid_instruction() {
+        let bytes = elf_bytes(TEXT, &[text(&[NOP, u32::MAX])]);
+        assert_eq!(
+            decode(&bytes),
+            ElfError::InvalidInstruction {
+                addr: TEXT + 4,
+                word: u32::MAX
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        // Big-endian ELFs are rejected by the parser.
+        let mut bytes = elf_bytes(TEXT, &[text(&[NOP])]);
+        bytes[5] = 2;
+        assert!(matches!(decode(&bytes), ElfError::Parse(_)));
+    }
+
+    /// The malformed ELFs found by fuzzing, or crafted, which must be rejected without panicking.
+    #[test]
+    fn test_fuzz_corpus() {
+        let corpus = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/corpus/elf");
+        let mut count = 0;
+        for entry in std::fs::read_dir(corpus).unwrap() {
+            let path = entry.unwrap().path();
+            let bytes = std::fs::read(&path).unwrap();
+            assert!(
+                Program::try_from_elf(&bytes).is_err(),
+                "{} was accepted",
+                path.display()
+            );
+            count += 1;
+        }
+        assert!(count > 0);
     }
 }
@@ -3,7 +3,7 @@ use rrs_lib::instruction_formats::{
 };
 use rrs_lib::{process_instruction, InstructionProcessor};
 
-use crate::runtime::{Instruction, Opcode, Register};
+use crate::runtime::{Extensions, Instruction, Opcode, Register};
 
 impl Instruction {
     /// Create a new instruction from an R-type instruction.
@@ -66,6 +66,31 @@ impl Instruction {
         )
     }
 
+    /// Create a new instruction from a J-type instruction, with the offset in `op_b`.
+    pub fn from_j_type(opcode: Opcode, dec_insn: JType) -> Self {
+        Self::new(
+            opcode,
+            dec_insn.rd as u32,
+            dec_insn.imm as u32,
+            0,
+            true,
+            true,
+        )
+    }
+
+    /// Create a new instruction from a U-type instruction, with the shifted immediate in both
+    /// `op_b` and `op_c`.
+    pub fn from_u_type(opcode: Opcode, dec_insn: UType) -> Self {
+        Self::new(
+            opcode,
+            dec_insn.rd as u32,
+            dec_insn.imm as u32,
+            dec_insn.imm as u32,
+            true,
+            true,
+        )
+    }
+
     /// Create a new instruction that is not implemented.
     pub fn unimp() -> Self {
         Self::new(Opcode::UNIMP, 0, 0, 0, true, true)
@@ -93,13 +118,47 @@ impl Instruction {
         )
     }
 
+    /// The immediate of an I-type instruction, in `op_c`.
+    ///
+    /// The transpiler stores every immediate sign-extended to 32 bits, so the immediate accessors
+    /// only fix how each format is interpreted: I, S, B and J immediates are signed, and U
+    /// immediates are the upper 20 bits of the word.
+    #[inline(always)]
+    pub fn imm_i(&self) -> i32 {
+        self.op_c as i32
+    }
+
+    /// The immediate of an S-type instruction, in `op_c`.
+    #[inline(always)]
+    pub fn imm_s(&self) -> i32 {
+        self.op_c as i32
+    }
+
+    /// The branch offset of a B-type instruction, in `op_c`.
+    #[inline(always)]
+    pub fn imm_b(&self) -> i32 {
+        self.op_c as i32
+    }
+
+    /// The shifted immediate of a U-type instruction, in `op_b`.
+    #[inline(always)]
+    pub fn imm_u(&self) -> u32 {
+        self.op_b
+    }
+
+    /// The jump offset of a J-type instruction, in `op_b`.
+    #[inline(always)]
+    pub fn imm_j(&self) -> i32 {
+        self.op_b as i32
+    }
+
     /// Decode the instruction in the I-type format.
     #[inline(always)]
     pub fn i_type(&self) -> (Register, Register, u32) {
         (
             Register::from_u32(self.op_a),
             Register::from_u32(self.op_b),
-            self.op_c,
+            self.imm_i() as u32,
         )
     }
 
@@ -109,7 +168,7 @@ impl Instruction {
         (
             Register::from_u32(self.op_a),
             Register::from_u32(self.op_b),
-            self.op_c,
+            self.imm_s() as u32,
         )
     }
 
@@ -119,20 +178,20 @@ impl Instruction {
         (
             Register::from_u32(self.op_a),
             Register::from_u32(self.op_b),
-            self.op_c,
+            self.imm_b() as u32,
         )
     }
 
     /// Decode the instruction in the J-type format.
     #[inline(always)]
     pub fn j_type(&self) -> (Register, u32) {
-        (Register::from_u32(self.op_a), self.op_b)
+        (Register::from_u32(self.op_a), self.imm_j() as u32)
     }
 
     /// Decode the instruction in the U-type format.
     #[inline(always)]
     pub fn u_type(&self) -> (Register, u32) {
-        (Register::from_u32(self.op_a), self.op_b)
+        (Register::from_u32(self.op_a), self.imm_u())
     }
 }
 
@@ -275,25 +334,11 @@ impl InstructionProcessor for InstructionTranspiler {
     }
 
     fn process_jal(&mut self, dec_insn: JType) -> Self::InstructionResult {
-        Instruction::new(
-            Opcode::JAL,
-            dec_insn.rd as u32,
-            dec_insn.imm as u32,
-            0,
-            true,
-            true,
-        )
+        Instruction::from_j_type(Opcode::JAL, dec_insn)
     }
 
     fn process_jalr(&mut self, dec_insn: IType) -> Self::InstructionResult {
-        Instruction::new(
-            Opcode::JALR,
-            dec_insn.rd as u32,
-            dec_insn.rs1 as u32,
-            dec_insn.imm as u32,
-            false,
-            true,
-        )
+        Instruction::from_i_type(Opcode::JALR, dec_insn)
     }
 
     /// LUI instructions are converted to an SLL instruction with imm_b and imm_c turned on.
@@ -311,14 +356,7 @@ impl InstructionProcessor for InstructionTranspiler {
 
     /// AUIPC instructions have the third operand set to imm << 12.
     fn process_auipc(&mut self, dec_insn: UType) -> Self::InstructionResult {
-        Instruction::new(
-            Opcode::AUIPC,
-            dec_insn.rd as u32,
-            dec_insn.imm as u32,
-            dec_insn.imm as u32,
-            true,
-            true,
-        )
+        Instruction::from_u_type(Opcode::AUIPC, dec_insn)
     }
 
     fn process_ecall(&mut self) -> Self::InstructionResult {
@@ -407,11 +445,275 @@ impl InstructionProcessor for InstructionTranspiler {
 
 /// Transpile the instructions from the 32-bit encoded instructions.
 pub fn transpile(instructions_u32: &[u32]) -> Vec<Instruction> {
+    try_transpile(instructions_u32).expect("invalid instruction")
+}
+
+/// Transpile the instructions from the 32-bit encoded instructions, returning the index of the
+/// first word that is not a RV32IM instruction instead of panicking.
+pub fn try_transpile(instructions_u32: &[u32]) -> Result<Vec<Instruction>, usize> {
+    try_transpile_with(instructions_u32, Extensions::empty())
+}
+
+/// Like [`try_transpile`], also accepting the instructions of `extensions`.
+pub fn try_transpile_with(
+    instructions_u32: &[u32],
+    extensions: Extensions,
+) -> Result<Vec<Instruction>, usize> {
+    let zbb = extensions.contains(Extensions::ZBB);
     let mut instructions = Vec::new();
     let mut transpiler = InstructionTranspiler;
-    for instruction_u32 in instructions_u32 {
-        let instruction = process_instruction(&mut transpiler, *instruction_u32).unwrap();
+    for (i, instruction_u32) in instructions_u32.iter().enumerate() {
+        let instruction = zbb
+            .then(|| transpile_zbb(*instruction_u32))
+            .flatten()
+            .or_else(|| process_instruction(&mut transpiler, *instruction_u32))
+            .ok_or(i)?;
         instructions.push(instruction);
     }
-    instructions
+    Ok(instructions)
+}
+
+/// Transpile a Zbb instruction, which the RV32IM decoder does not know, or return `None` if the
+/// word is not one.
+fn transpile_zbb(instruction_u32: u32) -> Option<Instruction> {
+    const OP: u32 = 0b0110011;
+    const OP_IMM: u32 = 0b0010011;
+    let rd = (instruction_u32 >> 7) & 0x1f;
+    let funct3 = (instruction_u32 >> 12) & 0x7;
+    let rs1 = (instruction_u32 >> 15) & 0x1f;
+    let rs2 = (instruction_u32 >> 20) & 0x1f;
+    let funct7 = instruction_u32 >> 25;
+    let opcode = match (instruction_u32 & 0x7f, funct7, funct3) {
+        (OP, 0b0100000, 0b111) => Opcode::ANDN,
+        (OP, 0b0100000, 0b110) => Opcode::ORN,
+        (OP, 0b0100000, 0b100) => Opcode::XNOR,
+        (OP, 0b0000101, 0b100) => Opcode::MIN,
+        (OP, 0b0000101, 0b101) => Opcode::MINU,
+        (OP, 0b0000101, 0b110) => Opcode::MAX,
+        (OP, 0b0000101, 0b111) => Opcode::MAXU,
+        // The unary instructions select the operation with the `rs2` field.
+        (OP_IMM, 0b0110000, 0b001) => {
+            let opcode = match rs2 {
+                0b00000 => Opcode::CLZ,
+                0b00001 => Opcode::CTZ,
+                0b00010 => Opcode::CPOP,
+                _ => return None,
+            };
+            return Some(Instruction::new(opcode, rd, rs1, 0, false, true));
+        }
+        _ => return None,
+    };
+    Some(Instruction::new(opcode, rd, rs1, rs2, false, false))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transpile_zbb() {
+        // The encodings of `<op> a0, a1, a2` and `<op> a0, a1`.
+        let binary = [
+            (0x40c5f533, Opcode::ANDN),
+            (0x40c5e533, Opcode::ORN),
+            (0x40c5c533, Opcode::XNOR),
+            (0x0ac5c533, Opcode::MIN),
+            (0x0ac5d533, Opcode::MINU),
+            (0x0ac5e533, Opcode::MAX),
+            (0x0ac5f533, Opcode::MAXU),
+        ];
+        let unary = [
+            (0x60059513, Opcode::CLZ),
+            (0x60159513, Opcode::CTZ),
+            (0x60259513, Opcode::CPOP),
+        ];
+        let words = binary
+            .iter()
+            .chain(unary.iter())
+            .map(|(word, _)| *word)
+            .collect::<Vec<_>>();
+        let instructions = try_transpile_with(&words, Extensions::ZBB).unwrap();
+        for (instruction, (_, opcode)) in instructions.iter().zip(binary.iter()) {
+            assert_eq!(instruction.opcode, *opcode);
+            assert_eq!(
+                (instruction.op_a, instruction.op_b, instruction.op_c),
+                (10, 11, 12)
+            );
+            assert!(!instruction.imm_b && !instruction.imm_c);
+        }
+        for (instruction, (_, opcode)) in instructions[binary.len()..].iter().zip(unary.iter()) {
+            assert_eq!(instruction.opcode, *opcode);
+            assert_eq!(
+                (instruction.op_a, instruction.op_b, instruction.op_c),
+                (10, 11, 0)
+            );
+            assert!(!instruction.imm_b && instruction.imm_c);
+            assert!(instruction.validate().is_ok());
+        }
+
+        // Without the extension, the words are not instructions, and RV32IM is unchanged.
+        assert_eq!(try_transpile(&words[..2]).map(|_| ()), Err(0));
+        assert_eq!(try_transpile(&words[3..]).map(|_| ()), Err(0));
+        let add = 0x00c58533;
+        assert_eq!(
+            try_transpile_with(&[add], Extensions::ZBB).unwrap()[0].opcode,
+            Opcode::ADD
+        );
+        // An unassigned unary operation is not decoded.
+        assert!(transpile_zbb(0x60359513).is_none());
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    enum Format {
+        I,
+        S,
+        B,
+        U,
+        J,
+    }
+
+    /// Instructions of every format with an immediate, with their major opcode and funct3.
+    const CORPUS: [(Opcode, Format, u32, u32); 14] = [
+        (Opcode::ADD, Format::I, 0x13, 0b000),
+        (Opcode::SLT, Format::I, 0x13, 0b010),
+        (Opcode::XOR, Format::I, 0x13, 0b100),
+        (Opcode::LB, Format::I, 0x03, 0b000),
+        (Opcode::LW, Format::I, 0x03, 0b010),
+        (Opcode::LHU, Format::I, 0x03, 0b101),
+        (Opcode::JALR, Format::I, 0x67, 0b000),
+        (Opcode::SB, Format::S, 0x23, 0b000),
+        (Opcode::SW, Format::S, 0x23, 0b010),
+        (Opcode::BEQ, Format::B, 0x63, 0b000),
+        (Opcode::BLT, Format::B, 0x63, 0b100),
+        (Opcode::BGEU, Format::B, 0x63, 0b111),
+        (Opcode::AUIPC, Format::U, 0x17, 0),
+        (Opcode::JAL, Format::J, 0x6f, 0),
+    ];
+
+    /// Encode an instruction of the corpus back into the word it is transpiled from.
+    fn encode(instruction: &Instruction, format: Format, major: u32, funct3: u32) -> u32 {
+        let (a, b) = (instruction.op_a, instruction.op_b);
+        match format {
+            Format::I => {
+                let imm = instruction.imm_i() as u32;
+                (imm & 0xfff) << 20 | b << 15 | funct3 << 12 | a << 7 | major
+            }
+            Format::S => {
+                let imm = instruction.imm_s() as u32;
+                ((imm >> 5) & 0x7f) << 25
+                    | a << 20
+                    | b << 15
+                    | funct3 << 12
+                    | (imm & 0x1f) << 7
+                    | major
+            }
+            Format::B => {
+                let imm = instruction.imm_b() as u32;
+                ((imm >> 12) & 1) << 31
+                    | ((imm >> 5) & 0x3f) << 25
+                    | b << 20
+                    | a << 15
+                    | funct3 << 12
+                    | ((imm >> 1) & 0xf) << 8
+                    | ((imm >> 11) & 1) << 7
+                    | major
+            }
+            Format::U => instruction.imm_u() | a << 7 | major,
+            Format::J => {
+                let imm = instruction.imm_j() as u32;
+                ((imm >> 20) & 1) << 31
+                    | ((imm >> 1) & 0x3ff) << 21
+                    | ((imm >> 11) & 1) << 20
+                    | ((imm >> 12) & 0xff) << 12
+                    | a << 7
+                    | major
+            }
+        }
+    }
+
+    /// The immediate of an instruction of `format` as the accessor returns it.
+    fn immediate(instruction: &Instruction, format: Format) -> i64 {
+        match format {
+            Format::I => instruction.imm_i() as i64,
+            Format::S => instruction.imm_s() as i64,
+            Format::B => instruction.imm_b() as i64,
+            Format::U => instruction.imm_u() as i64,
+            Format::J => instruction.imm_j() as i64,
+        }
+    }
+
+    /// The immediates of `format` to test: both extremes and a sample of the range in between.
+    fn immediates(format: Format) -> Vec<i64> {
+        let (min, max, align) = match format {
+            Format::I | Format::S => (-2048, 2047, 1),
+            Format::B => (-4096, 4094, 2),
+            Format::U => (0, 0xffff_f000, 0x1000),
+            Format::J => (-(1 << 20), (1 << 20) - 2, 2),
+        };
+        let step = ((max - min) / 97 / align).max(1) * align;
+        let mut values = (min..=max).step_by(step as usize).collect::<Vec<_>>();
+        values.extend([min, max, 0, align, -align]);
+        values.retain(|value| (min..=max).contains(value));
+        values
+    }
+
+    #[test]
+    fn test_immediate_round_trip() {
+        for (opcode, format, major, funct3) in CORPUS {
+            for imm in immediates(format) {
+                let instruction = match format {
+                    Format::I => Instruction::new(opcode, 10, 11, imm as u32, false, true),
+                    Format::S | Format::B => {
+                        Instruction::new(opcode, 12, 13, imm as u32, false, true)
+                    }
+                    Format::U => Instruction::new(opcode, 5, imm as u32, imm as u32, true, true),
+                    Format::J => Instruction::new(opcode, 1, imm as u32, 0, true, true),
+                };
+                let word = encode(&instruction, format, major, funct3);
+                let decoded = transpile(&[word])[0];
+                assert_eq!(decoded.opcode, opcode, "{:#010x}", word);
+                assert_eq!(
+                    (decoded.op_a, decoded.op_b, decoded.op_c),
+                    (instruction.op_a, instruction.op_b, instruction.op_c),
+                    "{:?} {}",
+                    format,
+                    imm
+                );
+                assert_eq!(
+                    (decoded.imm_b, decoded.imm_c),
+                    (instruction.imm_b, instruction.imm_c)
+                );
+                assert_eq!(immediate(&decoded, format), imm);
+                assert_eq!(encode(&decoded, format, major, funct3), word);
+            }
+        }
+    }
+
+    #[test]
+    fn test_immediate_edges() {
+        let words = transpile(&[
+            0x80000013, // addi x0, x0, -2048
+            0x80002023, // sw x0, -2048(x0)
+            0x80000063, // beq x0, x0, -4096
+            0x80000163, // beq x0, x0, -4094
+            0x000000e3, // beq x0, x0, 2048
+            0x80000017, // auipc x0, 0x80000
+            0x8000006f, // jal x0, -1048576
+            0x800000ef, // jal ra, -1048576
+        ]);
+        assert_eq!(words[0].imm_i(), -2048);
+        assert_eq!(words[1].imm_s(), -2048);
+        // Bit 12 is the sign of a branch offset, and bit 11 is just a bit of its magnitude.
+        assert_eq!(words[2].imm_b(), -4096);
+        assert_eq!(words[3].imm_b(), -4094);
+        assert_eq!(words[4].imm_b(), 2048);
+        assert_eq!(words[5].imm_u(), 0x8000_0000);
+        assert_eq!(words[6].imm_j(), -(1 << 20));
+        assert_eq!(words[7].j_type(), (Register::X1, (-(1i32 << 20)) as u32));
+
+        // The executor's decoding agrees with the accessors.
+        assert_eq!(words[0].i_type().2, -2048i32 as u32);
+        assert_eq!(words[3].b_type().2, -4094i32 as u32);
+        assert_eq!(words[5].u_type().1, 0x8000_0000);
+    }
 }
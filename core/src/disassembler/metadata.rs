@@ -0,0 +1,318 @@
+use core::fmt::{Display, Formatter};
+use elf::endian::LittleEndian;
+use elf::ElfBytes;
+use serde::{Deserialize, Serialize};
+
+use super::ElfError;
+
+/// The name of the ELF section holding the [`GuestMetadata`] of a guest.
+pub const METADATA_SECTION: &str = ".sp1_meta";
+
+/// The bytes the serialized metadata starts with, followed by its format version.
+const METADATA_MAGIC: [u8; 4] = *b"SP1M";
+const METADATA_VERSION: u8 = 1;
+
+/// The section types and the size of a section header of a 32-bit ELF.
+const SHT_PROGBITS: u32 = 1;
+const SHT_STRTAB: u32 = 3;
+const SHDR_SIZE: usize = 40;
+
+/// Describes the build of a guest, to tell which one produced a program, see
+/// [`inject_metadata`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestMetadata {
+    /// The name of the guest crate.
+    pub crate_name: String,
+
+    /// The version of the guest crate.
+    pub version: String,
+
+    /// The commit the guest was built from, if it was built from a git checkout.
+    pub git_hash: Option<String>,
+
+    /// The toolchain the guest was built with.
+    pub toolchain: String,
+
+    /// The flags passed to the compiler, e.g. `-C opt-level=3`.
+    pub build_flags: Vec<String>,
+}
+
+impl GuestMetadata {
+    /// Serialize the metadata to the contents of its section.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = METADATA_MAGIC.to_vec();
+        bytes.push(METADATA_VERSION);
+        bincode::serialize_into(&mut bytes, self).expect("failed to serialize the metadata");
+        bytes
+    }
+
+    /// Deserialize the metadata from the contents of its section.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 5 || bytes[..4] != METADATA_MAGIC {
+            return Err("bad magic bytes".to_string());
+        }
+        if bytes[4] != METADATA_VERSION {
+            return Err(format!("unsupported version {}", bytes[4]));
+        }
+        bincode::deserialize(&bytes[5..]).map_err(|err| err.to_string())
+    }
+}
+
+impl Display for GuestMetadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} {}", self.crate_name, self.version)?;
+        if let Some(git_hash) = &self.git_hash {
+            write!(f, " ({})", git_hash)?;
+        }
+        write!(f, " built with {}", self.toolchain)?;
+        if !self.build_flags.is_empty() {
+            write!(f, " [{}]", self.build_flags.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the metadata section of a parsed ELF, if it has one. A corrupted section is only logged,
+/// since the metadata is informational.
+pub(crate) fn read_metadata(elf: &ElfBytes<LittleEndian>) -> Option<GuestMetadata> {
+    let data = elf
+        .section_header_by_name(METADATA_SECTION)
+        .and_then(|header| header.map(|header| elf.section_data(&header)).transpose());
+    let bytes = match data {
+        Ok(Some((bytes, None))) => bytes,
+        Ok(None) => return None,
+        Ok(Some((_, Some(_)))) => {
+            log::warn!("ignoring the compressed {} section", METADATA_SECTION);
+            return None;
+        }
+        Err(err) => {
+            log::warn!(
+                "ignoring the unreadable {} section: {}",
+                METADATA_SECTION,
+                err
+            );
+            return None;
+        }
+    };
+    match GuestMetadata::from_bytes(bytes) {
+        Ok(metadata) => Some(metadata),
+        Err(err) => {
+            log::warn!(
+                "ignoring the corrupted {} section: {}",
+                METADATA_SECTION,
+                err
+            );
+            None
+        }
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn write_u16(bytes: &mut [u8], offset: usize, value: u16) {
+    bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// A section header of a 32-bit ELF: its name, type, flags, address, offset, size, link, info,
+/// alignment and entry size.
+type SectionHeader = [u32; 10];
+
+/// Append `data` to `output` at a word-aligned offset, returning the offset.
+fn append(output: &mut Vec<u8>, data: &[u8]) -> usize {
+    output.resize(output.len().next_multiple_of(4), 0);
+    let offset = output.len();
+    output.extend_from_slice(data);
+    offset
+}
+
+fn section_header(name: u32, sh_type: u32, offset: usize, size: usize) -> SectionHeader {
+    [name, sh_type, 0, 0, offset as u32, size as u32, 0, 0, 1, 0]
+}
+
+/// Patch `metadata` into the `.sp1_meta` section of the ELF in `elf`, replacing the section if
+/// it exists. The section is not loaded, so the program is unchanged but for its metadata.
+///
+/// The new contents and section header table are appended to the file, which keeps the offsets of
+/// everything else, so no linker script is needed.
+pub fn inject_metadata(elf: &[u8], metadata: &GuestMetadata) -> Result<Vec<u8>, ElfError> {
+    // Make sure the ELF loads, which also checks its header.
+    super::Elf::try_decode(elf)?;
+    let invalid = |reason: &str| ElfError::Parse(format!("invalid section headers: {}", reason));
+
+    let shoff = read_u32(elf, 32) as usize;
+    let shnum = read_u16(elf, 48) as usize;
+    let shstrndx = read_u16(elf, 50) as usize;
+    if shnum != 0 && read_u16(elf, 46) as usize != SHDR_SIZE {
+        return Err(invalid("unexpected entry size"));
+    }
+    let table = shoff
+        .checked_add(shnum * SHDR_SIZE)
+        .and_then(|end| elf.get(shoff..end))
+        .ok_or_else(|| invalid("table out of bounds"))?;
+    let mut headers = table
+        .chunks_exact(SHDR_SIZE)
+        .map(|bytes| core::array::from_fn(|i| read_u32(bytes, 4 * i)))
+        .collect::<Vec<SectionHeader>>();
+
+    let mut output = elf.to_vec();
+
+    // Find the names of the sections, creating the table if the ELF has no sections.
+    if headers.is_empty() {
+        headers.push([0; 10]);
+    }
+    let shstrndx = if shnum == 0 {
+        headers.push(section_header(1, SHT_STRTAB, 0, 0));
+        headers.len() - 1
+    } else if shstrndx == 0 {
+        return Err(invalid("no section names"));
+    } else {
+        shstrndx
+    };
+    let strtab = *headers
+        .get(shstrndx)
+        .ok_or_else(|| invalid("no section names"))?;
+    let names = elf
+        .get(strtab[4] as usize..(strtab[4] as usize).saturating_add(strtab[5] as usize))
+        .ok_or_else(|| invalid("section names out of bounds"))?;
+    let name_of = |header: &SectionHeader| {
+        let start = names.get(header[0] as usize..)?;
+        let end = start.iter().position(|&byte| byte == 0)?;
+        Some(&start[..end])
+    };
+    let existing = headers
+        .iter()
+        .position(|header| name_of(header) == Some(METADATA_SECTION.as_bytes()));
+
+    let contents = metadata.to_bytes();
+    let (data, size) = (append(&mut output, &contents), contents.len());
+    match existing {
+        Some(index) => headers[index] = section_header(headers[index][0], SHT_PROGBITS, data, size),
+        None => {
+            // Append the name of the section to a copy of the section names.
+            let mut names = names.to_vec();
+            if names.is_empty() {
+                names.extend_from_slice(b"\0.shstrtab\0");
+            }
+            let name = names.len() as u32;
+            names.extend_from_slice(METADATA_SECTION.as_bytes());
+            names.push(0);
+            let offset = append(&mut output, &names);
+            headers[shstrndx][4] = offset as u32;
+            headers[shstrndx][5] = names.len() as u32;
+            headers.push(section_header(name, SHT_PROGBITS, data, size));
+        }
+    }
+
+    let table = headers
+        .iter()
+        .flatten()
+        .flat_map(|field| field.to_le_bytes())
+        .collect::<Vec<_>>();
+    let shoff = append(&mut output, &table);
+    if output.len() > u32::MAX as usize || headers.len() > u16::MAX as usize {
+        return Err(invalid("too large"));
+    }
+    write_u32(&mut output, 32, shoff as u32);
+    write_u16(&mut output, 46, SHDR_SIZE as u16);
+    write_u16(&mut output, 48, headers.len() as u16);
+    write_u16(&mut output, 50, shstrndx as u16);
+    Ok(output)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Instruction, Opcode, Program, Runtime};
+    use crate::utils::tests::FIBONACCI_ELF;
+
+    fn metadata() -> GuestMetadata {
+        GuestMetadata {
+            crate_name: "fibonacci-program".to_string(),
+            version: "0.1.0".to_string(),
+            git_hash: Some("0123abcd".to_string()),
+            toolchain: "succinct 1.75.0".to_string(),
+            build_flags: vec!["-C".to_string(), "opt-level=3".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_inject_metadata() {
+        let program = Program::from(FIBONACCI_ELF);
+        assert_eq!(program.metadata(), None);
+
+        let elf = inject_metadata(FIBONACCI_ELF, &metadata()).unwrap();
+        let injected = Program::from(&elf);
+        assert_eq!(injected.metadata(), Some(&metadata()));
+        assert_eq!(injected.instructions, program.instructions);
+        assert_eq!(injected.memory_image, program.memory_image);
+        assert_ne!(injected.digest(), program.digest());
+
+        // Injecting again replaces the section.
+        let other = GuestMetadata {
+            git_hash: None,
+            ..metadata()
+        };
+        let elf = inject_metadata(&elf, &other).unwrap();
+        assert_eq!(Program::from(&elf).metadata(), Some(&other));
+        assert_eq!(
+            other.to_string(),
+            "fibonacci-program 0.1.0 built with succinct 1.75.0 [-C opt-level=3]"
+        );
+    }
+
+    #[test]
+    fn test_corrupted_metadata() {
+        let mut elf = inject_metadata(FIBONACCI_ELF, &metadata()).unwrap();
+        let magic = elf
+            .windows(4)
+            .rposition(|window| window == METADATA_MAGIC)
+            .unwrap();
+        elf[magic] = b'X';
+        let program = Program::try_from_elf(&elf).unwrap();
+        assert_eq!(program.metadata(), None);
+        assert_eq!(
+            program.instructions,
+            Program::from(FIBONACCI_ELF).instructions
+        );
+
+        assert!(GuestMetadata::from_bytes(&metadata().to_bytes()[..8]).is_err());
+        assert!(inject_metadata(&FIBONACCI_ELF[..20], &metadata()).is_err());
+    }
+
+    #[test]
+    fn test_metadata_in_errors() {
+        let mut program = Program::new(
+            vec![Instruction::new(Opcode::ADD, 32, 0, 5, false, true)],
+            0,
+            0,
+        );
+        program.metadata = Some(metadata());
+        let message = program.validate().unwrap_err().to_string();
+        assert!(message.contains("guest: fibonacci-program 0.1.0 (0123abcd)"));
+    }
+
+    #[test]
+    #[should_panic(expected = "(guest fibonacci-program 0.1.0")]
+    fn test_metadata_in_panics() {
+        let mut program = Program::new(
+            vec![
+                Instruction::new(Opcode::ADD, 5, 0, 0xdead, false, true),
+                Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            ],
+            0,
+            0,
+        );
+        program.metadata = Some(metadata());
+        Runtime::new(program).run();
+    }
+}
@@ -1,10 +1,12 @@
 mod elf;
 mod instruction;
+mod metadata;
 
 pub use elf::*;
 pub use instruction::*;
+pub use metadata::*;
 
-use crate::runtime::{Instruction, Program};
+use crate::runtime::{Error, Extensions, Instruction, Program, SymbolTable};
 use std::{collections::BTreeMap, fs::File, io::Read};
 
 impl Program {
@@ -15,33 +17,74 @@ impl Program {
             pc_start,
             pc_base,
             memory_image: BTreeMap::new(),
+            symbols: SymbolTable::default(),
+            readonly: Vec::new(),
+            linked: Vec::new(),
+            metadata: None,
+            extensions: Extensions::empty(),
+            data_floor: None,
         }
     }
 
     /// Disassemble a RV32IM ELF to a program that be executed by the VM.
+    ///
+    /// Panics if the ELF is malformed, see [`Program::try_from_elf`].
     pub fn from(input: &[u8]) -> Self {
+        Self::try_from_elf(input).expect("failed to disassemble elf")
+    }
+
+    /// Disassemble a RV32IM ELF to a program that be executed by the VM, returning an error
+    /// instead of panicking if it is malformed. This never panics, whatever the input.
+    pub fn try_from_elf(input: &[u8]) -> Result<Self, ElfError> {
+        Self::try_from_elf_with(input, Extensions::empty())
+    }
+
+    /// Like [`Program::try_from_elf`], also accepting the instructions of `extensions`, which the
+    /// program then enables.
+    pub fn try_from_elf_with(input: &[u8], extensions: Extensions) -> Result<Self, ElfError> {
         // Decode the bytes as an ELF.
-        let elf = Elf::decode(input);
+        let elf = Elf::try_decode(input)?;
 
-        // Transpile the RV32IM instructions.
-        let instructions = transpile(&elf.instructions);
+        // Transpile the RV32IM instructions and those of the enabled extensions.
+        let instructions = try_transpile_with(&elf.instructions, extensions).map_err(|i| {
+            ElfError::InvalidInstruction {
+                addr: elf.pc_base.wrapping_add(i as u32 * WORD_SIZE as u32),
+                word: elf.instructions[i],
+            }
+        })?;
 
         // Return the program.
-        Program {
+        Ok(Program {
             instructions,
             pc_start: elf.pc_start,
             pc_base: elf.pc_base,
             memory_image: elf.memory_image,
-        }
+            symbols: elf.symbols,
+            readonly: Vec::new(),
+            linked: Vec::new(),
+            metadata: elf.metadata,
+            extensions,
+            data_floor: None,
+        })
     }
 
     /// Disassemble a RV32IM ELF to a program that be executed by the VM from a file path.
+    ///
+    /// Panics if the file cannot be read or is malformed, see [`Program::try_from_elf_file`].
     pub fn from_elf(path: &str) -> Self {
+        Self::try_from_elf_file(path).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Disassemble a RV32IM ELF to a program that be executed by the VM from a file path,
+    /// returning an error if the file cannot be read or is malformed.
+    pub fn try_from_elf_file(path: &str) -> Result<Self, Error> {
         let mut elf_code = Vec::new();
         File::open(path)
-            .expect("failed to open input file")
-            .read_to_end(&mut elf_code)
-            .expect("failed to read from input file");
-        Program::from(&elf_code)
+            .and_then(|mut file| file.read_to_end(&mut elf_code))
+            .map_err(|error| Error::Io {
+                path: path.to_string(),
+                error,
+            })?;
+        Ok(Self::try_from_elf(&elf_code)?)
     }
 }
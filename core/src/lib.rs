@@ -16,6 +16,8 @@ extern crate alloc;
 pub mod air;
 pub mod alu;
 pub mod bytes;
+#[cfg(feature = "ffi")]
+pub mod capi;
 pub mod cpu;
 pub mod disassembler;
 pub mod field;
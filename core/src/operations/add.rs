@@ -6,41 +6,56 @@ use sp1_derive::AlignedBorrow;
 use std::mem::size_of;
 
 use crate::air::SP1AirBuilder;
-use crate::air::Word;
+use crate::air::{Word, WordValue, DOUBLE_WORD_SIZE};
+use crate::disassembler::WORD_SIZE;
 
 use crate::runtime::ExecutionRecord;
 use p3_field::AbstractField;
 
 /// A set of columns needed to compute the add of two words.
+///
+/// `NUM_CARRIES` must be `WORD_BYTES - 1`, as there is no carry out of the most significant byte.
 #[derive(AlignedBorrow, Default, Debug, Clone, Copy)]
 #[repr(C)]
-pub struct AddOperation<T> {
+pub struct GenericAddOperation<T, const WORD_BYTES: usize, const NUM_CARRIES: usize> {
     /// The result of `a + b`.
-    pub value: Word<T>,
+    pub value: Word<T, WORD_BYTES>,
 
     /// Trace.
-    pub carry: [T; 3],
+    pub carry: [T; NUM_CARRIES],
 }
 
-impl<F: Field> AddOperation<F> {
-    pub fn populate(&mut self, record: &mut ExecutionRecord, a_u32: u32, b_u32: u32) -> u32 {
-        let expected = a_u32.wrapping_add(b_u32);
-        self.value = Word::from(expected);
-        let a = a_u32.to_le_bytes();
-        let b = b_u32.to_le_bytes();
-
-        let mut carry = [0u8, 0u8, 0u8];
-        if (a[0] as u32) + (b[0] as u32) > 255 {
-            carry[0] = 1;
-            self.carry[0] = F::one();
-        }
-        if (a[1] as u32) + (b[1] as u32) + (carry[0] as u32) > 255 {
-            carry[1] = 1;
-            self.carry[1] = F::one();
-        }
-        if (a[2] as u32) + (b[2] as u32) + (carry[1] as u32) > 255 {
-            carry[2] = 1;
-            self.carry[2] = F::one();
+/// The add of two 32-bit words.
+pub type AddOperation<T> = GenericAddOperation<T, WORD_SIZE, { WORD_SIZE - 1 }>;
+
+/// The add of two 64-bit words.
+pub type AddOperation64<T> = GenericAddOperation<T, DOUBLE_WORD_SIZE, { DOUBLE_WORD_SIZE - 1 }>;
+
+impl<F: Field, const WORD_BYTES: usize, const NUM_CARRIES: usize>
+    GenericAddOperation<F, WORD_BYTES, NUM_CARRIES>
+{
+    const NUM_CARRIES_MATCHES_WORD_BYTES: () = assert!(NUM_CARRIES + 1 == WORD_BYTES);
+
+    pub fn populate<V: WordValue<WORD_BYTES>>(
+        &mut self,
+        record: &mut ExecutionRecord,
+        a_value: V,
+        b_value: V,
+    ) -> V {
+        let () = Self::NUM_CARRIES_MATCHES_WORD_BYTES;
+        let expected = a_value.wrapping_add(b_value);
+        self.value = Word::from_value(expected);
+        let a = a_value.to_le_bytes();
+        let b = b_value.to_le_bytes();
+
+        let mut carry = 0u8;
+        for i in 0..NUM_CARRIES {
+            if (a[i] as u32) + (b[i] as u32) + (carry as u32) > 255 {
+                carry = 1;
+                self.carry[i] = F::one();
+            } else {
+                carry = 0;
+            }
         }
 
         let base = 256u32;
@@ -60,11 +75,12 @@ impl<F: Field> AddOperation<F> {
 
     pub fn eval<AB: SP1AirBuilder>(
         builder: &mut AB,
-        a: Word<AB::Var>,
-        b: Word<AB::Var>,
-        cols: AddOperation<AB::Var>,
+        a: Word<AB::Var, WORD_BYTES>,
+        b: Word<AB::Var, WORD_BYTES>,
+        cols: GenericAddOperation<AB::Var, WORD_BYTES, NUM_CARRIES>,
         is_real: AB::Var,
     ) {
+        let () = Self::NUM_CARRIES_MATCHES_WORD_BYTES;
         let one = AB::Expr::one();
         let base = AB::F::from_canonical_u32(256);
 
@@ -72,29 +88,34 @@ impl<F: Field> AddOperation<F> {
 
         // For each limb, assert that difference between the carried result and the non-carried
         // result is either zero or the base.
-        let overflow_0 = a[0] + b[0] - cols.value[0];
-        let overflow_1 = a[1] + b[1] - cols.value[1] + cols.carry[0];
-        let overflow_2 = a[2] + b[2] - cols.value[2] + cols.carry[1];
-        let overflow_3 = a[3] + b[3] - cols.value[3] + cols.carry[2];
-        builder_is_real.assert_zero(overflow_0.clone() * (overflow_0.clone() - base));
-        builder_is_real.assert_zero(overflow_1.clone() * (overflow_1.clone() - base));
-        builder_is_real.assert_zero(overflow_2.clone() * (overflow_2.clone() - base));
-        builder_is_real.assert_zero(overflow_3.clone() * (overflow_3.clone() - base));
+        let overflows = (0..WORD_BYTES)
+            .map(|i| {
+                let overflow = a[i] + b[i] - cols.value[i];
+                if i == 0 {
+                    overflow
+                } else {
+                    overflow + cols.carry[i - 1]
+                }
+            })
+            .collect::<Vec<AB::Expr>>();
+        for overflow in overflows.iter() {
+            builder_is_real.assert_zero(overflow.clone() * (overflow.clone() - base));
+        }
 
         // If the carry is one, then the overflow must be the base.
-        builder_is_real.assert_zero(cols.carry[0] * (overflow_0.clone() - base));
-        builder_is_real.assert_zero(cols.carry[1] * (overflow_1.clone() - base));
-        builder_is_real.assert_zero(cols.carry[2] * (overflow_2.clone() - base));
+        for i in 0..NUM_CARRIES {
+            builder_is_real.assert_zero(cols.carry[i] * (overflows[i].clone() - base));
+        }
 
         // If the carry is not one, then the overflow must be zero.
-        builder_is_real.assert_zero((cols.carry[0] - one.clone()) * overflow_0.clone());
-        builder_is_real.assert_zero((cols.carry[1] - one.clone()) * overflow_1.clone());
-        builder_is_real.assert_zero((cols.carry[2] - one.clone()) * overflow_2.clone());
+        for i in 0..NUM_CARRIES {
+            builder_is_real.assert_zero((cols.carry[i] - one.clone()) * overflows[i].clone());
+        }
 
         // Assert that the carry is either zero or one.
-        builder_is_real.assert_bool(cols.carry[0]);
-        builder_is_real.assert_bool(cols.carry[1]);
-        builder_is_real.assert_bool(cols.carry[2]);
+        for i in 0..NUM_CARRIES {
+            builder_is_real.assert_bool(cols.carry[i]);
+        }
         builder_is_real.assert_bool(is_real);
 
         // Range check each byte.
@@ -0,0 +1,212 @@
+//! An operation to decompose a word into its bits.
+//!
+//! Each byte of the word is recomposed from its own eight bits, so every constraint has degree at
+//! most two and the sums never wrap around the field. The recomposition also range checks the
+//! bytes of the word, so no byte lookups are needed.
+use core::borrow::Borrow;
+use core::borrow::BorrowMut;
+use p3_air::AirBuilder;
+use p3_field::AbstractField;
+use p3_field::Field;
+use sp1_derive::AlignedBorrow;
+use std::mem::size_of;
+
+use crate::air::SP1AirBuilder;
+use crate::air::{Word, WordValue};
+use crate::disassembler::WORD_SIZE;
+
+/// A set of columns holding the little-endian bit decomposition of a word.
+#[derive(AlignedBorrow, Default, Debug, Clone, Copy)]
+#[repr(C)]
+pub struct GenericBitDecomposition<T, const WORD_BYTES: usize> {
+    /// The bits of each byte of the word, least significant first.
+    pub bits: [[T; 8]; WORD_BYTES],
+}
+
+/// The bits of a 32-bit word.
+pub type WordBitDecomposition<T> = GenericBitDecomposition<T, WORD_SIZE>;
+
+/// The bits of a byte.
+pub type ByteBitDecomposition<T> = GenericBitDecomposition<T, 1>;
+
+impl<T: Copy, const WORD_BYTES: usize> GenericBitDecomposition<T, WORD_BYTES> {
+    /// The bit `i` of the word, counting from the least significant one.
+    pub fn bit(&self, i: usize) -> T {
+        self.bits[i / 8][i % 8]
+    }
+
+    /// The value of the bits `lo..hi` of the word, for sub-fields of at most 31 bits.
+    pub fn bits_range<E: AbstractField>(&self, lo: usize, hi: usize) -> E
+    where
+        T: Into<E>,
+    {
+        assert!(lo <= hi && hi <= WORD_BYTES * 8 && hi - lo < 32);
+        (lo..hi).fold(E::zero(), |acc, i| {
+            acc + Into::<E>::into(self.bit(i)) * E::from_canonical_u32(1 << (i - lo))
+        })
+    }
+}
+
+impl<F: Field, const WORD_BYTES: usize> GenericBitDecomposition<F, WORD_BYTES> {
+    pub fn populate<V: WordValue<WORD_BYTES>>(&mut self, x: V) -> V {
+        for (bits, byte) in self.bits.iter_mut().zip(x.to_le_bytes()) {
+            for (i, bit) in bits.iter_mut().enumerate() {
+                *bit = F::from_canonical_u8(byte >> i & 1);
+            }
+        }
+        x
+    }
+
+    /// Constrains the bits to be boolean, and to recompose the bytes of `input` when `is_real`.
+    pub fn eval<AB: SP1AirBuilder>(
+        builder: &mut AB,
+        input: Word<AB::Expr, WORD_BYTES>,
+        cols: GenericBitDecomposition<AB::Var, WORD_BYTES>,
+        is_real: AB::Expr,
+    ) {
+        for (bits, byte) in cols.bits.iter().zip(input.0) {
+            let mut recomposed_byte = AB::Expr::zero();
+            for (i, &bit) in bits.iter().enumerate() {
+                builder.assert_bool(bit);
+                recomposed_byte += bit * AB::F::from_canonical_u8(1 << i);
+            }
+            builder
+                .when(is_real.clone())
+                .assert_eq(recomposed_byte, byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::borrow::{Borrow, BorrowMut};
+    use core::mem::size_of;
+
+    use p3_air::{Air, BaseAir};
+    use p3_baby_bear::BabyBear;
+    use p3_field::{Field, PrimeField32};
+    use p3_matrix::dense::RowMajorMatrix;
+    use p3_matrix::MatrixRowSlices;
+    use rand::{thread_rng, Rng};
+    use sp1_derive::AlignedBorrow;
+
+    use super::*;
+    use crate::air::MachineAir;
+    use crate::runtime::ExecutionRecord;
+    use crate::utils::{uni_stark_prove as prove, uni_stark_verify as verify};
+    use crate::utils::{BabyBearPoseidon2, StarkUtils};
+
+    #[derive(AlignedBorrow, Debug, Clone)]
+    pub struct TestCols<T> {
+        pub input: Word<T>,
+        pub decomposition: WordBitDecomposition<T>,
+    }
+
+    pub const NUM_TEST_COLS: usize = size_of::<TestCols<u8>>();
+
+    /// Decomposes random words, zero and all ones.
+    struct BitDecompositionChip;
+
+    impl<F: PrimeField32> MachineAir<F> for BitDecompositionChip {
+        fn name(&self) -> String {
+            "BitDecomposition".to_string()
+        }
+
+        fn generate_trace(
+            &self,
+            _: &ExecutionRecord,
+            _: &mut ExecutionRecord,
+        ) -> RowMajorMatrix<F> {
+            let mut rng = thread_rng();
+            let mut inputs = (0..254).map(|_| rng.gen()).collect::<Vec<u32>>();
+            inputs.extend([0, u32::MAX]);
+            let rows = inputs
+                .into_iter()
+                .map(|input| {
+                    let mut row = [F::zero(); NUM_TEST_COLS];
+                    let cols: &mut TestCols<F> = row.as_mut_slice().borrow_mut();
+                    cols.input = Word::from(input);
+                    assert_eq!(cols.decomposition.populate(input), input);
+                    row
+                })
+                .collect::<Vec<_>>();
+            RowMajorMatrix::new(rows.into_iter().flatten().collect(), NUM_TEST_COLS)
+        }
+    }
+
+    impl<F: Field> BaseAir<F> for BitDecompositionChip {
+        fn width(&self) -> usize {
+            NUM_TEST_COLS
+        }
+    }
+
+    impl<AB: SP1AirBuilder> Air<AB> for BitDecompositionChip {
+        fn eval(&self, builder: &mut AB) {
+            let main = builder.main();
+            let local: &TestCols<AB::Var> = main.row_slice(0).borrow();
+            WordBitDecomposition::<AB::F>::eval(
+                builder,
+                local.input.map(|byte| byte.into()),
+                local.decomposition,
+                AB::Expr::one(),
+            );
+
+            // The sub-fields of the word recompose its bytes.
+            for i in 0..WORD_SIZE {
+                let byte: AB::Expr = local.decomposition.bits_range(8 * i, 8 * i + 8);
+                builder.assert_eq(byte, local.input[i]);
+            }
+            let halves: [AB::Expr; 2] = [
+                local.decomposition.bits_range(0, 16),
+                local.decomposition.bits_range(16, 32),
+            ];
+            builder.assert_eq(
+                halves[0].clone() + halves[1].clone() * AB::F::from_canonical_u32(1 << 16),
+                local.input.reduce::<AB>(),
+            );
+
+            // A dummy constraint to keep the degree 3.
+            builder.assert_zero(
+                local.input[0] * local.input[0] * local.input[0]
+                    - local.input[0] * local.input[0] * local.input[0],
+            );
+        }
+    }
+
+    #[test]
+    fn test_populate() {
+        for input in [0, 1, 0x80, 0xdeadbeef, u32::MAX] {
+            let mut cols = WordBitDecomposition::<BabyBear>::default();
+            cols.populate(input);
+            for i in 0..32 {
+                assert_eq!(cols.bit(i), BabyBear::from_canonical_u32(input >> i & 1));
+            }
+            let top: BabyBear = cols.bits_range(24, 32);
+            assert_eq!(top, BabyBear::from_canonical_u32(input >> 24));
+            let middle: BabyBear = cols.bits_range(4, 20);
+            assert_eq!(middle, BabyBear::from_canonical_u32(input >> 4 & 0xffff));
+        }
+
+        let mut byte = ByteBitDecomposition::<BabyBear>::default();
+        byte.populate(0xa5u8);
+        assert_eq!(
+            byte.bits_range::<BabyBear>(0, 8),
+            BabyBear::from_canonical_u8(0xa5)
+        );
+        assert_eq!(byte.bit(7), BabyBear::one());
+    }
+
+    #[test]
+    fn prove_babybear() {
+        let config = BabyBearPoseidon2::new();
+        let mut challenger = config.challenger();
+
+        let chip = BitDecompositionChip;
+        let trace: RowMajorMatrix<BabyBear> =
+            chip.generate_trace(&ExecutionRecord::default(), &mut ExecutionRecord::default());
+        let proof = prove::<BabyBearPoseidon2, _>(&config, &chip, &mut challenger, trace);
+
+        let mut challenger = config.challenger();
+        verify(&config, &chip, &mut challenger, &proof).unwrap();
+    }
+}
@@ -5,7 +5,7 @@ use sp1_derive::AlignedBorrow;
 use std::mem::size_of;
 
 use crate::air::SP1AirBuilder;
-use crate::air::Word;
+use crate::air::{Word, WordValue, DOUBLE_WORD_SIZE};
 use crate::bytes::utils::shr_carry;
 use crate::bytes::ByteLookupEvent;
 use crate::bytes::ByteOpcode;
@@ -18,18 +18,24 @@ use p3_field::AbstractField;
 /// Note that we decompose shifts into a byte shift and a bit shift.
 #[derive(AlignedBorrow, Default, Debug, Clone, Copy)]
 #[repr(C)]
-pub struct FixedRotateRightOperation<T> {
+pub struct GenericFixedRotateRightOperation<T, const WORD_BYTES: usize> {
     /// The output value.
-    pub value: Word<T>,
+    pub value: Word<T, WORD_BYTES>,
 
     /// The shift output of `shrcarry` on each byte of a word.
-    pub shift: Word<T>,
+    pub shift: Word<T, WORD_BYTES>,
 
     /// The carry ouytput of `shrcarry` on each byte of a word.
-    pub carry: Word<T>,
+    pub carry: Word<T, WORD_BYTES>,
 }
 
-impl<F: Field> FixedRotateRightOperation<F> {
+/// `rotateright` of a 32-bit word.
+pub type FixedRotateRightOperation<T> = GenericFixedRotateRightOperation<T, WORD_SIZE>;
+
+/// `rotateright` of a 64-bit word.
+pub type FixedRotateRightOperation64<T> = GenericFixedRotateRightOperation<T, DOUBLE_WORD_SIZE>;
+
+impl<F: Field, const WORD_BYTES: usize> GenericFixedRotateRightOperation<F, WORD_BYTES> {
     pub fn nb_bytes_to_shift(rotation: usize) -> usize {
         rotation / 8
     }
@@ -43,7 +49,12 @@ impl<F: Field> FixedRotateRightOperation<F> {
         1 << (8 - nb_bits_to_shift)
     }
 
-    pub fn populate(&mut self, record: &mut ExecutionRecord, input: u32, rotation: usize) -> u32 {
+    pub fn populate<V: WordValue<WORD_BYTES>>(
+        &mut self,
+        record: &mut ExecutionRecord,
+        input: V,
+        rotation: usize,
+    ) -> V {
         let input_bytes = input.to_le_bytes().map(F::from_canonical_u8);
         let expected = input.rotate_right(rotation as u32);
 
@@ -53,18 +64,15 @@ impl<F: Field> FixedRotateRightOperation<F> {
         let carry_multiplier = F::from_canonical_u32(Self::carry_multiplier(rotation));
 
         // Perform the byte shift.
-        let input_bytes_rotated = Word([
-            input_bytes[nb_bytes_to_shift % WORD_SIZE],
-            input_bytes[(1 + nb_bytes_to_shift) % WORD_SIZE],
-            input_bytes[(2 + nb_bytes_to_shift) % WORD_SIZE],
-            input_bytes[(3 + nb_bytes_to_shift) % WORD_SIZE],
-        ]);
+        let input_bytes_rotated = Word::<F, WORD_BYTES>(core::array::from_fn(|i| {
+            input_bytes[(i + nb_bytes_to_shift) % WORD_BYTES]
+        }));
 
         // For each byte, calculate the shift and carry. If it's not the first byte, calculate the
         // new byte value using the current shifted byte and the last carry.
         let mut first_shift = F::zero();
         let mut last_carry = F::zero();
-        for i in (0..WORD_SIZE).rev() {
+        for i in (0..WORD_BYTES).rev() {
             let b = input_bytes_rotated[i].to_string().parse::<u8>().unwrap();
             let c = nb_bits_to_shift as u8;
 
@@ -82,7 +90,7 @@ impl<F: Field> FixedRotateRightOperation<F> {
             self.shift[i] = F::from_canonical_u8(shift);
             self.carry[i] = F::from_canonical_u8(carry);
 
-            if i == WORD_SIZE - 1 {
+            if i == WORD_BYTES - 1 {
                 first_shift = self.shift[i];
             } else {
                 self.value[i] = self.shift[i] + last_carry * carry_multiplier;
@@ -92,19 +100,19 @@ impl<F: Field> FixedRotateRightOperation<F> {
         }
 
         // For the first byte, we didn't know the last carry so compute the rotated byte here.
-        self.value[WORD_SIZE - 1] = first_shift + last_carry * carry_multiplier;
+        self.value[WORD_BYTES - 1] = first_shift + last_carry * carry_multiplier;
 
         // Check that the value is correct.
-        assert_eq!(self.value.to_u32(), expected);
+        assert_eq!(self.value.to_value::<V>(), expected);
 
         expected
     }
 
     pub fn eval<AB: SP1AirBuilder>(
         builder: &mut AB,
-        input: Word<AB::Var>,
+        input: Word<AB::Var, WORD_BYTES>,
         rotation: usize,
-        cols: FixedRotateRightOperation<AB::Var>,
+        cols: GenericFixedRotateRightOperation<AB::Var, WORD_BYTES>,
         is_real: AB::Var,
     ) {
         // Compute some constants with respect to the rotation needed for the rotation.
@@ -113,18 +121,15 @@ impl<F: Field> FixedRotateRightOperation<F> {
         let carry_multiplier = AB::F::from_canonical_u32(Self::carry_multiplier(rotation));
 
         // Perform the byte shift.
-        let input_bytes_rotated = Word([
-            input[nb_bytes_to_shift % WORD_SIZE],
-            input[(1 + nb_bytes_to_shift) % WORD_SIZE],
-            input[(2 + nb_bytes_to_shift) % WORD_SIZE],
-            input[(3 + nb_bytes_to_shift) % WORD_SIZE],
-        ]);
+        let input_bytes_rotated = Word::<AB::Var, WORD_BYTES>(core::array::from_fn(|i| {
+            input[(i + nb_bytes_to_shift) % WORD_BYTES]
+        }));
 
         // For each byte, calculate the shift and carry. If it's not the first byte, calculate the
         // new byte value using the current shifted byte and the last carry.
         let mut first_shift = AB::Expr::zero();
         let mut last_carry = AB::Expr::zero();
-        for i in (0..WORD_SIZE).rev() {
+        for i in (0..WORD_BYTES).rev() {
             builder.send_byte_pair(
                 AB::F::from_canonical_u32(ByteOpcode::ShrCarry as u32),
                 cols.shift[i],
@@ -134,7 +139,7 @@ impl<F: Field> FixedRotateRightOperation<F> {
                 is_real,
             );
 
-            if i == WORD_SIZE - 1 {
+            if i == WORD_BYTES - 1 {
                 first_shift = cols.shift[i].into();
             } else {
                 builder.assert_eq(cols.value[i], cols.shift[i] + last_carry * carry_multiplier);
@@ -145,7 +150,7 @@ impl<F: Field> FixedRotateRightOperation<F> {
 
         // For the first byte, we didn't know the last carry so compute the rotated byte here.
         builder.assert_eq(
-            cols.value[WORD_SIZE - 1],
+            cols.value[WORD_BYTES - 1],
             first_shift + last_carry * carry_multiplier,
         );
     }
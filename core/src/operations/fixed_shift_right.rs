@@ -5,7 +5,7 @@ use sp1_derive::AlignedBorrow;
 use std::mem::size_of;
 
 use crate::air::SP1AirBuilder;
-use crate::air::Word;
+use crate::air::{Word, WordValue, DOUBLE_WORD_SIZE};
 use crate::bytes::utils::shr_carry;
 use crate::bytes::ByteLookupEvent;
 use crate::bytes::ByteOpcode;
@@ -18,18 +18,24 @@ use p3_field::AbstractField;
 /// Note that we decompose shifts into a byte shift and a bit shift.
 #[derive(AlignedBorrow, Default, Debug, Clone, Copy)]
 #[repr(C)]
-pub struct FixedShiftRightOperation<T> {
+pub struct GenericFixedShiftRightOperation<T, const WORD_BYTES: usize> {
     /// The output value.
-    pub value: Word<T>,
+    pub value: Word<T, WORD_BYTES>,
 
     /// The shift output of `shrcarry` on each byte of a word.
-    pub shift: Word<T>,
+    pub shift: Word<T, WORD_BYTES>,
 
     /// The carry ouytput of `shrcarry` on each byte of a word.
-    pub carry: Word<T>,
+    pub carry: Word<T, WORD_BYTES>,
 }
 
-impl<F: Field> FixedShiftRightOperation<F> {
+/// `>>` of a 32-bit word.
+pub type FixedShiftRightOperation<T> = GenericFixedShiftRightOperation<T, WORD_SIZE>;
+
+/// `>>` of a 64-bit word.
+pub type FixedShiftRightOperation64<T> = GenericFixedShiftRightOperation<T, DOUBLE_WORD_SIZE>;
+
+impl<F: Field, const WORD_BYTES: usize> GenericFixedShiftRightOperation<F, WORD_BYTES> {
     pub fn nb_bytes_to_shift(rotation: usize) -> usize {
         rotation / 8
     }
@@ -43,7 +49,16 @@ impl<F: Field> FixedShiftRightOperation<F> {
         1 << (8 - nb_bits_to_shift)
     }
 
-    pub fn populate(&mut self, record: &mut ExecutionRecord, input: u32, rotation: usize) -> u32 {
+    pub fn populate<V: WordValue<WORD_BYTES>>(
+        &mut self,
+        record: &mut ExecutionRecord,
+        input: V,
+        rotation: usize,
+    ) -> V {
+        assert!(
+            rotation < WORD_BYTES * 8,
+            "shift of {rotation} bits is out of range"
+        );
         let input_bytes = input.to_le_bytes().map(F::from_canonical_u8);
         let expected = input >> rotation;
 
@@ -53,10 +68,10 @@ impl<F: Field> FixedShiftRightOperation<F> {
         let carry_multiplier = F::from_canonical_u32(Self::carry_multiplier(rotation));
 
         // Perform the byte shift.
-        let mut word = [F::zero(); WORD_SIZE];
-        for i in 0..WORD_SIZE {
-            if i + nb_bytes_to_shift < WORD_SIZE {
-                word[i] = input_bytes[(i + nb_bytes_to_shift) % WORD_SIZE];
+        let mut word = [F::zero(); WORD_BYTES];
+        for i in 0..WORD_BYTES {
+            if i + nb_bytes_to_shift < WORD_BYTES {
+                word[i] = input_bytes[i + nb_bytes_to_shift];
             }
         }
         let input_bytes_rotated = Word(word);
@@ -65,7 +80,7 @@ impl<F: Field> FixedShiftRightOperation<F> {
         // new byte value using the current shifted byte and the last carry.
         let mut first_shift = F::zero();
         let mut last_carry = F::zero();
-        for i in (0..WORD_SIZE).rev() {
+        for i in (0..WORD_BYTES).rev() {
             let b = input_bytes_rotated[i].to_string().parse::<u8>().unwrap();
             let c = nb_bits_to_shift as u8;
             let (shift, carry) = shr_carry(b, c);
@@ -81,7 +96,7 @@ impl<F: Field> FixedShiftRightOperation<F> {
             self.shift[i] = F::from_canonical_u8(shift);
             self.carry[i] = F::from_canonical_u8(carry);
 
-            if i == WORD_SIZE - 1 {
+            if i == WORD_BYTES - 1 {
                 first_shift = self.shift[i];
             } else {
                 self.value[i] = self.shift[i] + last_carry * carry_multiplier;
@@ -91,19 +106,19 @@ impl<F: Field> FixedShiftRightOperation<F> {
         }
 
         // For the first byte, we don't move over the carry as this is a shift, not a rotate.
-        self.value[WORD_SIZE - 1] = first_shift;
+        self.value[WORD_BYTES - 1] = first_shift;
 
         // Assert the answer is correct.
-        assert_eq!(self.value.to_u32(), expected);
+        assert_eq!(self.value.to_value::<V>(), expected);
 
         expected
     }
 
     pub fn eval<AB: SP1AirBuilder>(
         builder: &mut AB,
-        input: Word<AB::Var>,
+        input: Word<AB::Var, WORD_BYTES>,
         rotation: usize,
-        cols: FixedShiftRightOperation<AB::Var>,
+        cols: GenericFixedShiftRightOperation<AB::Var, WORD_BYTES>,
         is_real: AB::Var,
     ) {
         // Compute some constants with respect to the rotation needed for the rotation.
@@ -112,19 +127,19 @@ impl<F: Field> FixedShiftRightOperation<F> {
         let carry_multiplier = AB::F::from_canonical_u32(Self::carry_multiplier(rotation));
 
         // Perform the byte shift.
-        let mut word = vec![AB::Expr::zero(); WORD_SIZE];
-        for i in 0..WORD_SIZE {
-            if i + nb_bytes_to_shift < WORD_SIZE {
-                word[i] = input[(i + nb_bytes_to_shift) % WORD_SIZE].into();
+        let input_bytes_rotated = Word::<AB::Expr, WORD_BYTES>(core::array::from_fn(|i| {
+            if i + nb_bytes_to_shift < WORD_BYTES {
+                input[i + nb_bytes_to_shift].into()
+            } else {
+                AB::Expr::zero()
             }
-        }
-        let input_bytes_rotated = Word(word.try_into().unwrap());
+        }));
 
         // For each byte, calculate the shift and carry. If it's not the first byte, calculate the
         // new byte value using the current shifted byte and the last carry.
         let mut first_shift = AB::Expr::zero();
         let mut last_carry = AB::Expr::zero();
-        for i in (0..WORD_SIZE).rev() {
+        for i in (0..WORD_BYTES).rev() {
             builder.send_byte_pair(
                 AB::F::from_canonical_u32(ByteOpcode::ShrCarry as u32),
                 cols.shift[i],
@@ -134,7 +149,7 @@ impl<F: Field> FixedShiftRightOperation<F> {
                 is_real,
             );
 
-            if i == WORD_SIZE - 1 {
+            if i == WORD_BYTES - 1 {
                 first_shift = cols.shift[i].into();
             } else {
                 builder.assert_eq(cols.value[i], cols.shift[i] + last_carry * carry_multiplier);
@@ -144,6 +159,56 @@ impl<F: Field> FixedShiftRightOperation<F> {
         }
 
         // For the first byte, we don't move over the carry as this is a shift, not a rotate.
-        builder.assert_eq(cols.value[WORD_SIZE - 1], first_shift);
+        builder.assert_eq(cols.value[WORD_BYTES - 1], first_shift);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use p3_baby_bear::BabyBear;
+
+    use super::*;
+
+    #[test]
+    fn test_byte_lookup_multiplicities() {
+        let inputs =
+            (0..256u32)
+                .map(|i| i.wrapping_mul(0x9e3779b9))
+                .chain([0, 1, u32::MAX, 0x80000000]);
+
+        let mut record = ExecutionRecord::default();
+        let mut expected = BTreeMap::<ByteLookupEvent, usize>::new();
+        let mut calls = 0;
+        for input in inputs {
+            for rotation in 0..32 {
+                let mut cols = FixedShiftRightOperation::<BabyBear>::default();
+                cols.populate(&mut record, input, rotation);
+                calls += 1;
+
+                // One `ShrCarry` lookup per byte of the byte-shifted input.
+                let shifted = (input >> (rotation / 8 * 8)).to_le_bytes();
+                for b in shifted {
+                    let c = (rotation % 8) as u8;
+                    let (shift, carry) = shr_carry(b, c);
+                    let event = ByteLookupEvent::new(
+                        ByteOpcode::ShrCarry,
+                        shift as u32,
+                        carry as u32,
+                        b as u32,
+                        c as u32,
+                    );
+                    *expected.entry(event).or_default() += 1;
+                }
+            }
+        }
+
+        let aggregated = record.byte_lookups[&record.index]
+            .iter()
+            .map(|(event, mult)| (*event, *mult))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(aggregated, expected);
+        assert_eq!(record.nb_byte_lookups(), calls * WORD_SIZE);
     }
 }
@@ -8,6 +8,7 @@ mod add;
 mod add4;
 mod add5;
 mod and;
+mod bit_decomposition;
 pub mod field;
 mod fixed_rotate_right;
 mod fixed_shift_right;
@@ -22,6 +23,7 @@ pub use add::*;
 pub use add4::*;
 pub use add5::*;
 pub use and::*;
+pub use bit_decomposition::*;
 pub use fixed_rotate_right::*;
 pub use fixed_shift_right::*;
 pub use is_equal_word::*;
@@ -30,3 +32,80 @@ pub use is_zero_word::*;
 pub use not::*;
 pub use or::*;
 pub use xor::*;
+
+#[cfg(test)]
+mod tests {
+    use p3_baby_bear::BabyBear;
+
+    use super::*;
+    use crate::air::{Word, DOUBLE_WORD_SIZE};
+    use crate::disassembler::WORD_SIZE;
+    use crate::runtime::ExecutionRecord;
+
+    /// Runs every gadget over a corpus of words of type `$ty` with `$bytes` bytes, comparing the
+    /// returned and populated values against native integer arithmetic.
+    macro_rules! test_gadgets {
+        ($name:ident, $ty:ty, $bytes:expr) => {
+            #[test]
+            fn $name() {
+                let check = |value: Word<BabyBear, { $bytes }>, result: $ty, expected: $ty| {
+                    assert_eq!(result, expected);
+                    assert_eq!(value.to_value::<$ty>(), expected);
+                };
+
+                let values = [
+                    0,
+                    1,
+                    <$ty>::MAX,
+                    <$ty>::MAX >> 1,
+                    1 << (<$ty>::BITS - 1),
+                    0xff,
+                ]
+                .into_iter()
+                .chain((1..8).map(|i: $ty| i.wrapping_mul(0x9e3779b97f4a7c15u64 as $ty)))
+                .collect::<Vec<$ty>>();
+
+                let mut record = ExecutionRecord::default();
+                for &x in values.iter() {
+                    let mut not = GenericNotOperation::<BabyBear, { $bytes }>::default();
+                    let result = not.populate(&mut record, x);
+                    check(not.value, result, !x);
+
+                    for &y in values.iter() {
+                        let mut add =
+                            GenericAddOperation::<BabyBear, { $bytes }, { $bytes - 1 }>::default();
+                        let result = add.populate(&mut record, x, y);
+                        check(add.value, result, x.wrapping_add(y));
+
+                        let mut xor = GenericXorOperation::<BabyBear, { $bytes }>::default();
+                        let result = xor.populate(&mut record, x, y);
+                        check(xor.value, result, x ^ y);
+
+                        let mut and = GenericAndOperation::<BabyBear, { $bytes }>::default();
+                        let result = and.populate(&mut record, x, y);
+                        check(and.value, result, x & y);
+
+                        let mut or = GenericOrOperation::<BabyBear, { $bytes }>::default();
+                        let result = or.populate(&mut record, x, y);
+                        check(or.value, result, x | y);
+                    }
+
+                    for rotation in 0..<$ty>::BITS as usize {
+                        let mut shift =
+                            GenericFixedShiftRightOperation::<BabyBear, { $bytes }>::default();
+                        let result = shift.populate(&mut record, x, rotation);
+                        check(shift.value, result, x >> rotation);
+
+                        let mut rotate =
+                            GenericFixedRotateRightOperation::<BabyBear, { $bytes }>::default();
+                        let result = rotate.populate(&mut record, x, rotation);
+                        check(rotate.value, result, x.rotate_right(rotation as u32));
+                    }
+                }
+            }
+        };
+    }
+
+    test_gadgets!(test_gadgets_32, u32, WORD_SIZE);
+    test_gadgets!(test_gadgets_64, u64, DOUBLE_WORD_SIZE);
+}
@@ -6,7 +6,7 @@ use sp1_derive::AlignedBorrow;
 use std::mem::size_of;
 
 use crate::air::SP1AirBuilder;
-use crate::air::Word;
+use crate::air::{Word, WordValue, DOUBLE_WORD_SIZE};
 use crate::bytes::ByteOpcode;
 use crate::disassembler::WORD_SIZE;
 use crate::runtime::ExecutionRecord;
@@ -15,16 +15,22 @@ use p3_field::AbstractField;
 /// A set of columns needed to compute the not of a word.
 #[derive(AlignedBorrow, Default, Debug, Clone, Copy)]
 #[repr(C)]
-pub struct NotOperation<T> {
+pub struct GenericNotOperation<T, const WORD_BYTES: usize> {
     /// The result of `!x`.
-    pub value: Word<T>,
+    pub value: Word<T, WORD_BYTES>,
 }
 
-impl<F: Field> NotOperation<F> {
-    pub fn populate(&mut self, record: &mut ExecutionRecord, x: u32) -> u32 {
+/// The not of a 32-bit word.
+pub type NotOperation<T> = GenericNotOperation<T, WORD_SIZE>;
+
+/// The not of a 64-bit word.
+pub type NotOperation64<T> = GenericNotOperation<T, DOUBLE_WORD_SIZE>;
+
+impl<F: Field, const WORD_BYTES: usize> GenericNotOperation<F, WORD_BYTES> {
+    pub fn populate<V: WordValue<WORD_BYTES>>(&mut self, record: &mut ExecutionRecord, x: V) -> V {
         let expected = !x;
         let x_bytes = x.to_le_bytes();
-        for i in 0..WORD_SIZE {
+        for i in 0..WORD_BYTES {
             self.value[i] = F::from_canonical_u8(!x_bytes[i]);
         }
         record.add_u8_range_checks(&x_bytes);
@@ -34,11 +40,11 @@ impl<F: Field> NotOperation<F> {
     #[allow(unused_variables)]
     pub fn eval<AB: SP1AirBuilder>(
         builder: &mut AB,
-        a: Word<AB::Var>,
-        cols: NotOperation<AB::Var>,
+        a: Word<AB::Var, WORD_BYTES>,
+        cols: GenericNotOperation<AB::Var, WORD_BYTES>,
         is_real: AB::Var,
     ) {
-        for i in (0..WORD_SIZE).step_by(2) {
+        for i in (0..WORD_BYTES).step_by(2) {
             builder.send_byte_pair(
                 AB::F::from_canonical_u32(ByteOpcode::U8Range as u32),
                 AB::F::zero(),
@@ -50,7 +56,7 @@ impl<F: Field> NotOperation<F> {
         }
 
         // For any byte b, b + !b = 0xFF.
-        for i in 0..WORD_SIZE {
+        for i in 0..WORD_BYTES {
             builder
                 .when(is_real)
                 .assert_eq(cols.value[i] + a[i], AB::F::from_canonical_u8(u8::MAX));
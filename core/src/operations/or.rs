@@ -6,7 +6,7 @@ use sp1_derive::AlignedBorrow;
 use std::mem::size_of;
 
 use crate::air::SP1AirBuilder;
-use crate::air::Word;
+use crate::air::{Word, WordValue, DOUBLE_WORD_SIZE};
 use crate::bytes::ByteOpcode;
 use crate::disassembler::WORD_SIZE;
 use crate::runtime::ExecutionRecord;
@@ -16,17 +16,28 @@ use crate::runtime::ExecutionRecord;
 /// TODO: This is currently not in use, and thus not tested thoroughly yet.
 #[derive(AlignedBorrow, Default, Debug, Clone, Copy)]
 #[repr(C)]
-pub struct OrOperation<T> {
+pub struct GenericOrOperation<T, const WORD_BYTES: usize> {
     /// The result of `x | y`.
-    pub value: Word<T>,
+    pub value: Word<T, WORD_BYTES>,
 }
 
-impl<F: Field> OrOperation<F> {
-    pub fn populate(&mut self, record: &mut ExecutionRecord, x: u32, y: u32) -> u32 {
+/// The or of two 32-bit words.
+pub type OrOperation<T> = GenericOrOperation<T, WORD_SIZE>;
+
+/// The or of two 64-bit words.
+pub type OrOperation64<T> = GenericOrOperation<T, DOUBLE_WORD_SIZE>;
+
+impl<F: Field, const WORD_BYTES: usize> GenericOrOperation<F, WORD_BYTES> {
+    pub fn populate<V: WordValue<WORD_BYTES>>(
+        &mut self,
+        record: &mut ExecutionRecord,
+        x: V,
+        y: V,
+    ) -> V {
         let expected = x | y;
         let x_bytes = x.to_le_bytes();
         let y_bytes = y.to_le_bytes();
-        for i in 0..WORD_SIZE {
+        for i in 0..WORD_BYTES {
             self.value[i] = F::from_canonical_u8(x_bytes[i] | y_bytes[i]);
             record.lookup_or(x_bytes[i], y_bytes[i]);
         }
@@ -35,12 +46,12 @@ impl<F: Field> OrOperation<F> {
 
     pub fn eval<AB: SP1AirBuilder>(
         builder: &mut AB,
-        a: Word<AB::Var>,
-        b: Word<AB::Var>,
-        cols: OrOperation<AB::Var>,
+        a: Word<AB::Var, WORD_BYTES>,
+        b: Word<AB::Var, WORD_BYTES>,
+        cols: GenericOrOperation<AB::Var, WORD_BYTES>,
         is_real: AB::Var,
     ) {
-        for i in 0..WORD_SIZE {
+        for i in 0..WORD_BYTES {
             builder.send_byte(
                 AB::F::from_canonical_u32(ByteOpcode::OR as u32),
                 cols.value[i],
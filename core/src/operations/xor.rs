@@ -6,7 +6,7 @@ use sp1_derive::AlignedBorrow;
 use std::mem::size_of;
 
 use crate::air::SP1AirBuilder;
-use crate::air::Word;
+use crate::air::{Word, WordValue, DOUBLE_WORD_SIZE};
 use crate::bytes::ByteLookupEvent;
 use crate::bytes::ByteOpcode;
 use crate::disassembler::WORD_SIZE;
@@ -15,17 +15,28 @@ use crate::runtime::ExecutionRecord;
 /// A set of columns needed to compute the xor of two words.
 #[derive(AlignedBorrow, Default, Debug, Clone, Copy)]
 #[repr(C)]
-pub struct XorOperation<T> {
+pub struct GenericXorOperation<T, const WORD_BYTES: usize> {
     /// The result of `x ^ y`.
-    pub value: Word<T>,
+    pub value: Word<T, WORD_BYTES>,
 }
 
-impl<F: Field> XorOperation<F> {
-    pub fn populate(&mut self, record: &mut ExecutionRecord, x: u32, y: u32) -> u32 {
+/// The xor of two 32-bit words.
+pub type XorOperation<T> = GenericXorOperation<T, WORD_SIZE>;
+
+/// The xor of two 64-bit words.
+pub type XorOperation64<T> = GenericXorOperation<T, DOUBLE_WORD_SIZE>;
+
+impl<F: Field, const WORD_BYTES: usize> GenericXorOperation<F, WORD_BYTES> {
+    pub fn populate<V: WordValue<WORD_BYTES>>(
+        &mut self,
+        record: &mut ExecutionRecord,
+        x: V,
+        y: V,
+    ) -> V {
         let expected = x ^ y;
         let x_bytes = x.to_le_bytes();
         let y_bytes = y.to_le_bytes();
-        for i in 0..WORD_SIZE {
+        for i in 0..WORD_BYTES {
             self.value[i] = F::from_canonical_u8(x_bytes[i] ^ y_bytes[i]);
             let xor = x_bytes[i] ^ y_bytes[i];
             self.value[i] = F::from_canonical_u8(xor);
@@ -45,12 +56,12 @@ impl<F: Field> XorOperation<F> {
     #[allow(unused_variables)]
     pub fn eval<AB: SP1AirBuilder>(
         builder: &mut AB,
-        a: Word<AB::Var>,
-        b: Word<AB::Var>,
-        cols: XorOperation<AB::Var>,
+        a: Word<AB::Var, WORD_BYTES>,
+        b: Word<AB::Var, WORD_BYTES>,
+        cols: GenericXorOperation<AB::Var, WORD_BYTES>,
         is_real: AB::Var,
     ) {
-        for i in 0..WORD_SIZE {
+        for i in 0..WORD_BYTES {
             builder.send_byte(
                 AB::F::from_canonical_u32(ByteOpcode::XOR as u32),
                 cols.value[i],
@@ -154,6 +154,7 @@ mod tests {
                 pc_start: 0,
                 pc_base: 0,
                 memory_image: BTreeMap::new(),
+                ..Default::default()
             }),
             ..Default::default()
         };
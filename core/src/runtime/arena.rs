@@ -0,0 +1,443 @@
+//! Chunked storage for the events of a record, which grows without ever copying the events
+//! already stored and keeps its chunks for the next run after a reset.
+use core::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut, Range};
+
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The number of events in a full chunk of an [`EventArena`].
+pub const EVENT_ARENA_CHUNK_SIZE: usize = 1 << 20;
+
+/// A sequence of events stored in chunks of [`EVENT_ARENA_CHUNK_SIZE`] events.
+///
+/// The first chunk grows like a `Vec`, so short runs allocate no more than they need, and every
+/// further chunk is allocated at its full size. Growing the arena never moves the events already
+/// stored, which avoids the copies and the doubled peak memory of reallocating one large `Vec`.
+/// [`EventArena::clear`] keeps the chunks to be reused by the next events.
+///
+/// The events are not contiguous in general: consumers iterate over them with
+/// [`EventArena::iter`] or [`EventArena::chunks`], or call [`EventArena::make_contiguous`] to get a
+/// single slice.
+pub struct EventArena<T> {
+    chunks: Vec<Vec<T>>,
+    spare: Vec<Vec<T>>,
+    len: usize,
+}
+
+impl<T> EventArena<T> {
+    pub const fn new() -> Self {
+        Self {
+            chunks: Vec::new(),
+            spare: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, event: T) {
+        match self.chunks.last_mut() {
+            Some(chunk) if chunk.len() < EVENT_ARENA_CHUNK_SIZE => chunk.push(event),
+            _ => {
+                let mut chunk = match self.spare.pop() {
+                    Some(chunk) => chunk,
+                    // The first chunk starts small and grows, later ones are allocated in full.
+                    None if self.chunks.is_empty() => Vec::new(),
+                    None => Vec::with_capacity(EVENT_ARENA_CHUNK_SIZE),
+                };
+                chunk.push(event);
+                self.chunks.push(chunk);
+            }
+        }
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let chunk = self.chunks.last_mut()?;
+        let event = chunk.pop();
+        if chunk.is_empty() {
+            self.spare.push(self.chunks.pop().unwrap());
+        }
+        self.len -= 1;
+        event
+    }
+
+    /// Keep the first `len` events, keeping the chunks freed for the next events.
+    pub fn truncate(&mut self, len: usize) {
+        while self.len > len {
+            let chunk = self.chunks.last_mut().unwrap();
+            let keep = chunk.len().saturating_sub(self.len - len);
+            self.len -= chunk.len() - keep;
+            chunk.truncate(keep);
+            if chunk.is_empty() {
+                self.spare.push(self.chunks.pop().unwrap());
+            }
+        }
+    }
+
+    /// Remove every event, keeping the chunks for the next events.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Move the events of `other` to the end of this arena, leaving it empty. The chunks are
+    /// moved rather than copied.
+    pub fn append(&mut self, other: &mut Self) {
+        self.len += other.len;
+        other.len = 0;
+        self.chunks.append(&mut other.chunks);
+    }
+
+    /// The chunk and the position in it of the event at `index`.
+    fn locate(&self, mut index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            if index < chunk.len() {
+                return Some((i, index));
+            }
+            index -= chunk.len();
+        }
+        unreachable!()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let (chunk, i) = self.locate(index)?;
+        Some(&self.chunks[chunk][i])
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let (chunk, i) = self.locate(index)?;
+        Some(&mut self.chunks[chunk][i])
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.chunks.first().and_then(|chunk| chunk.first())
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.chunks.last().and_then(|chunk| chunk.last())
+    }
+
+    pub fn iter(&self) -> ArenaIter<'_, T> {
+        self.range(0..self.len)
+    }
+
+    /// The events at the positions in `range`, panicking if it is out of bounds.
+    pub fn range(&self, range: Range<usize>) -> ArenaIter<'_, T> {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "range {:?} out of bounds of an arena of {} events",
+            range,
+            self.len
+        );
+        let (chunk, start) = self.locate(range.start).unwrap_or((self.chunks.len(), 0));
+        let mut chunks = self.chunks[chunk.min(self.chunks.len())..].iter();
+        let current = chunks
+            .next()
+            .map_or(&[][..], |chunk| &chunk[start..])
+            .iter();
+        ArenaIter {
+            chunks,
+            current,
+            remaining: range.len(),
+        }
+    }
+
+    /// The position of the first event in `range` for which `pred` is false, if it is true for
+    /// the events before it and false for those after, as for `slice::partition_point`.
+    pub fn partition_point(&self, range: Range<usize>, mut pred: impl FnMut(&T) -> bool) -> usize {
+        let (mut lo, mut hi) = (range.start, range.end);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if pred(&self[mid]) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.chunks.iter_mut().flat_map(|chunk| chunk.iter_mut())
+    }
+
+    /// The events in slices of `size` events, except at the end of each chunk and of the arena,
+    /// where they may be shorter. Unlike `slice::chunks`, the slices never span two chunks.
+    pub fn chunks(&self, size: usize) -> impl Iterator<Item = &[T]> + '_ {
+        self.chunks.iter().flat_map(move |chunk| chunk.chunks(size))
+    }
+
+    /// Move every event into a single chunk and return them as a slice. The other chunks are
+    /// freed, and further events are stored in new chunks, so the events are only copied once.
+    pub fn make_contiguous(&mut self) -> &mut [T] {
+        if self.chunks.len() > 1 {
+            let mut contiguous = Vec::with_capacity(self.len);
+            for mut chunk in self.chunks.drain(..) {
+                contiguous.append(&mut chunk);
+            }
+            self.chunks.push(contiguous);
+        }
+        match self.chunks.first_mut() {
+            Some(chunk) => chunk,
+            None => &mut [],
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T> Default for EventArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clones the events, but not the chunks kept for reuse.
+impl<T: Clone> Clone for EventArena<T> {
+    fn clone(&self) -> Self {
+        Self {
+            chunks: self.chunks.clone(),
+            spare: Vec::new(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T: Debug> Debug for EventArena<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Index<usize> for EventArena<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        let len = self.len;
+        self.get(index).unwrap_or_else(|| {
+            panic!(
+                "index {} out of bounds of an arena of {} events",
+                index, len
+            )
+        })
+    }
+}
+
+impl<T> IndexMut<usize> for EventArena<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let len = self.len;
+        self.get_mut(index).unwrap_or_else(|| {
+            panic!(
+                "index {} out of bounds of an arena of {} events",
+                index, len
+            )
+        })
+    }
+}
+
+impl<T> Extend<T> for EventArena<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, events: I) {
+        for event in events {
+            self.push(event);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for EventArena<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(events: I) -> Self {
+        let mut arena = Self::new();
+        arena.extend(events);
+        arena
+    }
+}
+
+impl<T> From<Vec<T>> for EventArena<T> {
+    fn from(events: Vec<T>) -> Self {
+        events.into_iter().collect()
+    }
+}
+
+impl<T> IntoIterator for EventArena<T> {
+    type Item = T;
+    type IntoIter = std::iter::Flatten<std::vec::IntoIter<Vec<T>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.into_iter().flatten()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a EventArena<T> {
+    type Item = &'a T;
+    type IntoIter = ArenaIter<'a, T>;
+
+    fn into_iter(self) -> ArenaIter<'a, T> {
+        self.iter()
+    }
+}
+
+/// An iterator over the events of an [`EventArena`], see [`EventArena::iter`].
+pub struct ArenaIter<'a, T> {
+    chunks: std::slice::Iter<'a, Vec<T>>,
+    current: std::slice::Iter<'a, T>,
+    remaining: usize,
+}
+
+impl<'a, T> Clone for ArenaIter<'a, T> {
+    fn clone(&self) -> Self {
+        Self {
+            chunks: self.chunks.clone(),
+            current: self.current.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<'a, T> Iterator for ArenaIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if let Some(event) = self.current.next() {
+                self.remaining -= 1;
+                return Some(event);
+            }
+            self.current = self.chunks.next()?.iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for ArenaIter<'a, T> {}
+
+/// Serialized as a sequence, like a `Vec`.
+impl<T: Serialize> Serialize for EventArena<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for EventArena<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ArenaVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for ArenaVisitor<T> {
+            type Value = EventArena<T>;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                f.write_str("a sequence of events")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut arena = EventArena::new();
+                while let Some(event) = seq.next_element()? {
+                    arena.push(event);
+                }
+                Ok(arena)
+            }
+        }
+
+        deserializer.deserialize_seq(ArenaVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// An arena of `len` events, over several chunks if `len` exceeds the chunk size.
+    fn arena(len: usize) -> EventArena<u32> {
+        (0..len as u32).collect()
+    }
+
+    #[test]
+    fn test_chunked_storage() {
+        let len = 2 * EVENT_ARENA_CHUNK_SIZE + 10;
+        let mut events = arena(len);
+        assert_eq!(events.len(), len);
+        assert_eq!(events.chunks.len(), 3);
+        assert_eq!(
+            events[EVENT_ARENA_CHUNK_SIZE],
+            EVENT_ARENA_CHUNK_SIZE as u32
+        );
+        assert_eq!(events.last(), Some(&(len as u32 - 1)));
+        assert!(events.iter().copied().eq(0..len as u32));
+        assert_eq!(events.iter().len(), len);
+        assert!(events
+            .range(EVENT_ARENA_CHUNK_SIZE - 2..EVENT_ARENA_CHUNK_SIZE + 2)
+            .copied()
+            .eq(EVENT_ARENA_CHUNK_SIZE as u32 - 2..EVENT_ARENA_CHUNK_SIZE as u32 + 2));
+        assert_eq!(events.range(len..len).next(), None);
+        assert_eq!(
+            events.partition_point(0..len, |event| *event < 1 << 20),
+            1 << 20
+        );
+        assert_eq!(events.partition_point(10..20, |_| true), 20);
+        assert!(events.chunks(1000).all(|chunk| chunk.len() <= 1000));
+        assert_eq!(
+            events.chunks(1000).map(|chunk| chunk.len()).sum::<usize>(),
+            len
+        );
+
+        events[5] = 0;
+        assert_eq!(events.get(5), Some(&0));
+        assert_eq!(events.get(len), None);
+
+        let bytes = bincode::serialize(&events).unwrap();
+        assert_eq!(bytes, bincode::serialize(&events.to_vec()).unwrap());
+        let deserialized: EventArena<u32> = bincode::deserialize(&bytes).unwrap();
+        assert!(deserialized.iter().eq(events.iter()));
+
+        let contiguous = events.make_contiguous().to_vec();
+        assert_eq!(contiguous.len(), len);
+        events.push(7);
+        assert_eq!(events.chunks.len(), 2);
+        assert_eq!(events[len], 7);
+    }
+
+    #[test]
+    fn test_chunk_recycling() {
+        let mut events = arena(EVENT_ARENA_CHUNK_SIZE + 1);
+        events.truncate(EVENT_ARENA_CHUNK_SIZE - 1);
+        assert_eq!(events.len(), EVENT_ARENA_CHUNK_SIZE - 1);
+        assert_eq!(events.spare.len(), 1);
+
+        events.clear();
+        assert!(events.is_empty());
+        assert_eq!(events.first(), None);
+        assert_eq!(events.spare.len(), 2);
+
+        // The chunks are reused, in any order, for the next events.
+        events.extend(0..EVENT_ARENA_CHUNK_SIZE as u32 + 1);
+        assert!(events.spare.is_empty());
+        assert_eq!(events.pop(), Some(EVENT_ARENA_CHUNK_SIZE as u32));
+        assert_eq!(events.spare.len(), 1);
+
+        let mut other = arena(3);
+        events.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(events.len(), EVENT_ARENA_CHUNK_SIZE + 3);
+        assert_eq!(events[EVENT_ARENA_CHUNK_SIZE + 2], 2);
+    }
+}
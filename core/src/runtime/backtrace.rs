@@ -0,0 +1,309 @@
+use core::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Opcode, Register, Runtime};
+
+/// The maximum number of frames captured in a backtrace.
+pub const MAX_BACKTRACE_FRAMES: usize = 64;
+
+/// Why the program stopped executing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum HaltReason {
+    /// The program halted or fell through its last instruction.
+    #[default]
+    Finished,
+
+    /// The guest panicked.
+    Panicked {
+        message: String,
+        backtrace: Vec<BacktraceFrame>,
+    },
+
+    /// An assertion of the guest failed, see [`GuestAssertion`].
+    AssertionFailed {
+        assertion: GuestAssertion,
+        backtrace: Vec<BacktraceFrame>,
+    },
+}
+
+/// A failed assertion reported by the guest with the `ASSERT_FAILED` syscall, which also halts
+/// the program. The message and either value are omitted when the guest passes a null pointer
+/// for them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestAssertion {
+    /// The program counter of the `ecall`.
+    pub pc: u32,
+
+    /// A code identifying the assertion, chosen by the guest.
+    pub code: u32,
+
+    pub message: Option<String>,
+
+    pub expected: Option<[u8; 32]>,
+
+    pub actual: Option<[u8; 32]>,
+
+    /// The clock of the `ecall` in its shard.
+    pub clk: u32,
+}
+
+/// A frame of a guest backtrace, innermost first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BacktraceFrame {
+    /// The program counter of the frame: the faulting instruction for the innermost frame, and the
+    /// call instruction for every other frame.
+    pub pc: u32,
+
+    /// The name of the function containing `pc`, if the program has a symbol for it.
+    pub function: Option<String>,
+}
+
+impl Display for BacktraceFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match &self.function {
+            Some(function) => write!(f, "0x{:08x} {}", self.pc, function),
+            None => write!(f, "0x{:08x} <unknown>", self.pc),
+        }
+    }
+}
+
+impl Runtime {
+    /// Capture the guest backtrace at the current program counter by walking the frame pointer
+    /// chain in s0.
+    ///
+    /// Each frame saves the return address at `fp - 4` and the caller's frame pointer at `fp - 8`.
+    /// The walk stops at the first frame that does not look like a call from inside the program,
+    /// so a guest built without frame pointers only gets the innermost frame.
+    pub fn backtrace(&self) -> Vec<BacktraceFrame> {
+        let mut pcs = vec![self.state.pc];
+        let mut fp = self.register(Register::X8);
+        while pcs.len() < MAX_BACKTRACE_FRAMES && fp % 4 == 0 && fp >= 8 {
+            let ra = self.word(fp - 4);
+            let caller_fp = self.word(fp - 8);
+            let call = ra.wrapping_sub(4);
+            if !self.is_call(call) {
+                break;
+            }
+            pcs.push(call);
+            if caller_fp <= fp {
+                break;
+            }
+            fp = caller_fp;
+        }
+        pcs.into_iter()
+            .map(|pc| BacktraceFrame {
+                pc,
+                function: self
+                    .program
+                    .symbols
+                    .lookup(pc)
+                    .map(|symbol| symbol.name.clone()),
+            })
+            .collect()
+    }
+
+    /// Whether the instruction at `pc` is a `jal` or `jalr` that links to ra.
+    fn is_call(&self, pc: u32) -> bool {
+        if pc % 4 != 0 {
+            return false;
+        }
+        let index = (pc.wrapping_sub(self.program.pc_base) / 4) as usize;
+        self.program
+            .instructions
+            .get(index)
+            .is_some_and(|instruction| {
+                matches!(instruction.opcode, Opcode::JAL | Opcode::JALR)
+                    && instruction.op_a == Register::X1 as u32
+            })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::disassembler::transpile;
+    use crate::runtime::{ExecutionRecord, Instruction, Program, Symbol, SymbolTable, SyscallCode};
+    use crate::syscall::{MAX_ASSERTION_MESSAGE_LEN, PANIC_EXIT_CODE};
+
+    /// A program where `main` calls `outer`, which calls `inner`, which panics with the message
+    /// "inner failed", compiled with frame pointers.
+    fn panic_program() -> Program {
+        let code = [
+            // main:
+            0x00080137, // lui sp, 0x80
+            0x00010413, // mv s0, sp
+            0x008000ef, // jal ra, outer
+            0x0000006f, // j .
+            // outer:
+            0xff010113, // addi sp, sp, -16
+            0x00112623, // sw ra, 12(sp)
+            0x00812423, // sw s0, 8(sp)
+            0x01010413, // addi s0, sp, 16
+            0x014000ef, // jal ra, inner
+            0x00c12083, // lw ra, 12(sp)
+            0x00812403, // lw s0, 8(sp)
+            0x01010113, // addi sp, sp, 16
+            0x00008067, // ret
+            // inner:
+            0xff010113, // addi sp, sp, -16
+            0x00112623, // sw ra, 12(sp)
+            0x00812423, // sw s0, 8(sp)
+            0x01010413, // addi s0, sp, 16
+            0x00002537, // lui a0, 0x2
+            0x00c00593, // li a1, 12
+            0x07300293, // li t0, 115
+            0x00000073, // ecall
+            0x00c12083, // lw ra, 12(sp)
+            0x00812403, // lw s0, 8(sp)
+            0x01010113, // addi sp, sp, 16
+            0x00008067, // ret
+        ];
+        let mut program = Program::new(transpile(&code), 0x1000, 0x1000);
+        for (i, chunk) in b"inner failed".chunks(4).enumerate() {
+            let word = u32::from_le_bytes(chunk.try_into().unwrap());
+            program.memory_image.insert(0x2000 + i as u32 * 4, word);
+        }
+        program.symbols = SymbolTable::new(vec![
+            Symbol::new("main", 0x1000, 0x10),
+            Symbol::new("outer", 0x1010, 0x24),
+            Symbol::new("inner", 0x1034, 0x30),
+        ]);
+        program
+    }
+
+    #[test]
+    fn test_panic_backtrace() {
+        let mut runtime = Runtime::new(panic_program());
+        let HaltReason::Panicked { message, backtrace } = runtime.run() else {
+            panic!("expected the program to panic");
+        };
+        assert_eq!(message, "inner failed");
+        let functions = backtrace
+            .iter()
+            .map(|frame| frame.function.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(functions, ["inner", "outer", "main"]);
+        let pcs = backtrace.iter().map(|frame| frame.pc).collect::<Vec<_>>();
+        assert_eq!(pcs, [0x1050, 0x1020, 0x1008]);
+    }
+
+    #[test]
+    fn test_panic_without_frame_pointers() {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 8, 0, 0x100, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, SyscallCode::PANIC as u32, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0x1000, 0x1000));
+        assert_eq!(
+            runtime.run(),
+            HaltReason::Panicked {
+                message: String::new(),
+                backtrace: vec![BacktraceFrame {
+                    pc: 0x1008,
+                    function: None
+                }],
+            }
+        );
+    }
+
+    /// Fail an assertion with code 7, passing the message "sum mismatch" at 0x2000 and values at
+    /// 0x3000 and 0x3020 unless their pointer is replaced by null.
+    fn assertion_program(message: bool, values: bool) -> Program {
+        assertion_program_with(message.then_some(b"sum mismatch".as_slice()), values)
+    }
+
+    /// Like [`assertion_program`], with the given message.
+    fn assertion_program_with(message: Option<&[u8]>, values: bool) -> Program {
+        let pointer = |present: bool, addr: u32| if present { addr } else { 0 };
+        let source = format!(
+            "
+            li   a0, 7
+            li   a1, {}
+            li   a2, {}
+            li   a3, {}
+            li   a4, {}
+            li   t0, {}
+            ecall
+            li   a0, 1
+            ",
+            pointer(message.is_some(), 0x2000),
+            message.map_or(0, |message| message.len()),
+            pointer(values, 0x3000),
+            pointer(values, 0x3020),
+            SyscallCode::ASSERT_FAILED as u32
+        );
+        let mut program = crate::utils::asm::assemble(&source, 0x1000).unwrap();
+        for (i, chunk) in message.unwrap_or_default().chunks(4).enumerate() {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            program
+                .memory_image
+                .insert(0x2000 + i as u32 * 4, u32::from_le_bytes(word));
+        }
+        for i in 0..8 {
+            program.memory_image.insert(0x3000 + i * 4, 0x0101_0101 * i);
+            program.memory_image.insert(0x3020 + i * 4, 0x0202_0202 * i);
+        }
+        program
+    }
+
+    fn value(byte: u8) -> [u8; 32] {
+        core::array::from_fn(|i| byte * (i / 4) as u8)
+    }
+
+    #[test]
+    fn test_assertion_failed() {
+        let mut runtime = Runtime::new(assertion_program(true, true));
+        let HaltReason::AssertionFailed {
+            assertion,
+            backtrace,
+        } = runtime.run()
+        else {
+            panic!("expected an assertion to fail");
+        };
+        let expected = GuestAssertion {
+            pc: 0x1018,
+            code: 7,
+            message: Some("sum mismatch".to_string()),
+            expected: Some(value(1)),
+            actual: Some(value(2)),
+            clk: 25,
+        };
+        assert_eq!(assertion, expected);
+        assert_eq!(backtrace[0].pc, 0x1018);
+        assert_eq!(runtime.exit_code(), Some(PANIC_EXIT_CODE));
+        assert_eq!(runtime.register(Register::X10), 7);
+
+        let record = ExecutionRecord::try_from_bytes(&runtime.record.to_bytes()).unwrap();
+        assert_eq!(record.guest_assertion, Some(expected));
+    }
+
+    #[test]
+    fn test_partial_assertions() {
+        let assertion = |message: bool, values: bool| {
+            let mut runtime = Runtime::new(assertion_program(message, values));
+            runtime.run();
+            runtime.record.guest_assertion.unwrap()
+        };
+        let message_only = assertion(true, false);
+        assert_eq!(message_only.message.as_deref(), Some("sum mismatch"));
+        assert_eq!((message_only.expected, message_only.actual), (None, None));
+        let values_only = assertion(false, true);
+        assert_eq!(values_only.message, None);
+        assert_eq!(values_only.expected, Some(value(1)));
+        assert_eq!(values_only.actual, Some(value(2)));
+    }
+
+    #[test]
+    fn test_long_assertion_message() {
+        let message = vec![b'x'; MAX_ASSERTION_MESSAGE_LEN as usize + 100];
+        let mut runtime = Runtime::new(assertion_program_with(Some(&message), false));
+        runtime.run();
+        let message = runtime.record.guest_assertion.unwrap().message.unwrap();
+        let (kept, rest) = message.split_at(MAX_ASSERTION_MESSAGE_LEN as usize);
+        assert!(kept.bytes().all(|byte| byte == b'x'));
+        assert_eq!(rest, "... <100 more bytes>");
+    }
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+
+use super::{ExecutionRecord, Runtime, ShardProvenance};
+
+/// The state of the machine where a shard starts, to chain the proofs of consecutive shards, see
+/// [`ExecutionRecord::shard_boundaries`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardBoundary {
+    pub shard: u32,
+
+    /// The pc of the first instruction of the shard.
+    pub entry_pc: u32,
+
+    /// The pc of the last instruction of the shard.
+    pub exit_pc: u32,
+
+    /// The number of instructions executed before the shard.
+    pub entry_global_clk: u32,
+
+    /// The registers before the first instruction of the shard, which are the registers after the
+    /// last instruction of the previous shard.
+    pub register_file: [u32; 32],
+
+    /// A blake3 hash of the words accessed before the shard, with their values, sorted by address.
+    /// Only computed if [`Runtime::set_memory_root_hints`] was called.
+    pub memory_root_hint: Option<[u8; 32]>,
+}
+
+impl ExecutionRecord {
+    /// The boundaries of the shards executed so far, in order. The sharded records keep them all
+    /// in the last shard.
+    pub fn shard_boundaries(&self) -> &[ShardBoundary] {
+        &self.shard_boundaries
+    }
+
+    /// The boundary of `shard`, if it was executed.
+    pub fn shard_boundary(&self, shard: u32) -> Option<&ShardBoundary> {
+        self.shard_boundaries
+            .iter()
+            .find(|boundary| boundary.shard == shard)
+    }
+}
+
+impl Runtime {
+    /// Hash the memory accessed so far at every shard boundary, see
+    /// [`ShardBoundary::memory_root_hint`]. This sorts the whole memory once per shard, so it is
+    /// off by default.
+    pub fn set_memory_root_hints(&mut self, enabled: bool) {
+        self.memory_root_hints = enabled;
+    }
+
+    /// A hash of the words accessed so far and their values, sorted by address.
+    pub fn memory_root_hint(&self) -> [u8; 32] {
+        let mut words = self
+            .state
+            .memory
+            .iter()
+            .filter(|(_, &(_, shard, _))| shard != 0)
+            .map(|(&addr, &(value, _, _))| (addr, value))
+            .collect::<Vec<_>>();
+        words.sort_unstable();
+        let mut hasher = blake3::Hasher::new();
+        for (addr, value) in words {
+            hasher.update(&addr.to_le_bytes());
+            hasher.update(&value.to_le_bytes());
+        }
+        *hasher.finalize().as_bytes()
+    }
+
+    /// Record that the instruction at `pc` is about to execute, opening the boundary of the
+    /// current shard if it is its first instruction.
+    #[inline]
+    pub(crate) fn enter_shard(&mut self, pc: u32) {
+        let shard = self.state.current_shard;
+        if self
+            .record
+            .shard_boundaries
+            .last()
+            .is_some_and(|boundary| boundary.shard == shard)
+        {
+            return;
+        }
+        let provenance = if self.record.provenance.checkpoints.is_empty() {
+            ShardProvenance::Executed
+        } else {
+            ShardProvenance::ResumedFromCheckpoint
+        };
+        self.record.provenance.shards.insert(shard, provenance);
+        let boundary = ShardBoundary {
+            shard,
+            entry_pc: pc,
+            exit_pc: pc,
+            entry_global_clk: self.state.global_clk,
+            register_file: self.registers(),
+            memory_root_hint: self.memory_root_hints.then(|| self.memory_root_hint()),
+        };
+        self.record.shard_boundaries.push(boundary);
+    }
+
+    /// Record that the instruction at `pc` executed in the current shard.
+    #[inline]
+    pub(crate) fn exit_shard(&mut self, pc: u32) {
+        if let Some(boundary) = self.record.shard_boundaries.last_mut() {
+            boundary.exit_pc = pc;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::cpu::{CpuEvent, MemoryRecordEnum};
+    use crate::runtime::tests::fibonacci_program;
+
+    fn sharded_runtime() -> Runtime {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.shard_size = 256;
+        runtime.run();
+        runtime
+    }
+
+    #[test]
+    fn test_shard_boundaries() {
+        let runtime = sharded_runtime();
+        let record = &runtime.record;
+        let boundaries = record.shard_boundaries();
+        let last_shard = record.cpu_events.last().unwrap().shard;
+        assert!(last_shard > 3);
+        assert_eq!(boundaries.len(), last_shard as usize);
+
+        // Replay the register writes of the events to reconstruct the register file at each
+        // boundary.
+        let apply = |registers: &mut [u32; 32], event: &CpuEvent| {
+            if let Some(MemoryRecordEnum::Write(record)) = event.a_record {
+                registers[event.instruction.op_a as usize] = record.value;
+            }
+        };
+        let mut registers = [0; 32];
+        let mut events = record.cpu_events.iter().enumerate().peekable();
+        for (boundary, shard) in boundaries.iter().zip(1..) {
+            assert_eq!(boundary.shard, shard);
+            assert_eq!(boundary.memory_root_hint, None);
+            let (first, event) = events.next().unwrap();
+            assert_eq!(boundary.entry_pc, event.pc);
+            assert_eq!(boundary.entry_global_clk, first as u32);
+            assert_eq!(boundary.register_file, registers);
+
+            let mut last = event;
+            apply(&mut registers, event);
+            while let Some((_, event)) = events.next_if(|(_, event)| event.shard == shard) {
+                apply(&mut registers, event);
+                last = event;
+            }
+            assert_eq!(boundary.exit_pc, last.pc);
+        }
+        assert_eq!(registers, runtime.registers());
+    }
+
+    #[test]
+    fn test_memory_root_hints() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.shard_size = 256;
+        runtime.set_memory_root_hints(true);
+        runtime.run();
+        let hints = runtime
+            .record
+            .shard_boundaries()
+            .iter()
+            .map(|boundary| boundary.memory_root_hint.unwrap())
+            .collect::<Vec<_>>();
+        assert_ne!(hints[1], hints[2]);
+
+        let boundaries = sharded_runtime().record.shard_boundaries;
+        assert!(boundaries
+            .iter()
+            .zip(runtime.record.shard_boundaries())
+            .all(|(a, b)| (a.entry_pc, a.register_file) == (b.entry_pc, b.register_file)));
+    }
+}
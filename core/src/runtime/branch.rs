@@ -0,0 +1,254 @@
+use core::fmt::{Display, Formatter};
+use std::collections::{BTreeMap, HashMap};
+
+use super::Runtime;
+
+/// The number of distinct branch pcs tracked by [`Runtime::enable_branch_stats`]. Executions of
+/// branches beyond it are only counted in [`BranchStats::overflow`].
+pub const MAX_BRANCH_PCS: usize = 1 << 16;
+
+/// How often a branch of the program was taken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchEntry {
+    /// The pc of the branch instruction.
+    pub pc: u32,
+
+    /// The signed distance in bytes from the branch to its target.
+    pub offset: i32,
+
+    pub taken: u64,
+    pub not_taken: u64,
+
+    /// The binary entropy of the outcome of the branch, from 0 for a branch that always goes the
+    /// same way to 1 for a branch taken half of the time, which is the hardest to predict.
+    pub entropy: f64,
+
+    /// The function containing the branch, if the program has a symbol table.
+    pub function: Option<String>,
+}
+
+impl BranchEntry {
+    /// The number of times the branch was executed.
+    pub fn total(&self) -> u64 {
+        self.taken + self.not_taken
+    }
+}
+
+/// The branch statistics of an execution, see [`Runtime::branch_stats`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BranchStats {
+    /// The branches that were executed, most executed first.
+    pub entries: Vec<BranchEntry>,
+
+    /// The number of taken branches by distance to their target, rounded up to a power of two
+    /// and negative for backward branches.
+    pub taken_distances: BTreeMap<i32, u64>,
+
+    /// The number of executions of branches that did not fit in the table.
+    pub overflow: u64,
+}
+
+impl Display for BranchStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        writeln!(
+            f,
+            "{} branches, {} executions untracked",
+            self.entries.len(),
+            self.overflow
+        )?;
+        for entry in self.entries.iter() {
+            write!(
+                f,
+                "  pc=0x{:08x} {:+}: {} taken, {} not taken, entropy {:.2}",
+                entry.pc, entry.offset, entry.taken, entry.not_taken, entry.entropy
+            )?;
+            match &entry.function {
+                Some(function) => writeln!(f, " in {}", function)?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The binary entropy of an outcome with the given counts.
+fn entropy(taken: u64, not_taken: u64) -> f64 {
+    let total = (taken + not_taken) as f64;
+    [taken, not_taken]
+        .into_iter()
+        .filter(|&count| count > 0)
+        .map(|count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BranchCounts {
+    offset: i32,
+    taken: u64,
+    not_taken: u64,
+}
+
+/// Counts the outcomes of the branches as they are executed.
+#[derive(Debug, Default)]
+pub(crate) struct BranchCollector {
+    branches: HashMap<u32, BranchCounts>,
+    overflow: u64,
+}
+
+impl BranchCollector {
+    fn record(&mut self, pc: u32, offset: i32, taken: bool) {
+        let len = self.branches.len();
+        let counts = match self.branches.get_mut(&pc) {
+            Some(counts) => counts,
+            None if len >= MAX_BRANCH_PCS => {
+                self.overflow += 1;
+                return;
+            }
+            None => self.branches.entry(pc).or_insert(BranchCounts {
+                offset,
+                ..Default::default()
+            }),
+        };
+        if taken {
+            counts.taken += 1;
+        } else {
+            counts.not_taken += 1;
+        }
+    }
+}
+
+impl Runtime {
+    /// Count how often each branch is taken from now on, to find the branches of the guest that
+    /// are worth making branchless. Branches executed in unconstrained blocks are not counted.
+    pub fn enable_branch_stats(&mut self) {
+        self.branch_collector = Some(BranchCollector::default());
+    }
+
+    /// The branch statistics gathered so far, or `None` if [`Runtime::enable_branch_stats`] was
+    /// not called.
+    pub fn branch_stats(&self) -> Option<BranchStats> {
+        let collector = self.branch_collector.as_ref()?;
+        let mut taken_distances = BTreeMap::new();
+        let mut entries = collector
+            .branches
+            .iter()
+            .map(|(&pc, counts)| {
+                if counts.taken > 0 {
+                    let distance = counts.offset.unsigned_abs().next_power_of_two() as i32;
+                    *taken_distances
+                        .entry(distance * counts.offset.signum())
+                        .or_default() += counts.taken;
+                }
+                BranchEntry {
+                    pc,
+                    offset: counts.offset,
+                    taken: counts.taken,
+                    not_taken: counts.not_taken,
+                    entropy: entropy(counts.taken, counts.not_taken),
+                    function: self
+                        .program
+                        .symbols
+                        .lookup(pc)
+                        .map(|symbol| symbol.name.clone()),
+                }
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| (std::cmp::Reverse(entry.total()), entry.pc));
+        Some(BranchStats {
+            entries,
+            taken_distances,
+            overflow: collector.overflow,
+        })
+    }
+
+    /// Count the outcome of the branch at `pc` that just moved the pc to `next_pc`. A branch to
+    /// the next instruction counts as not taken, which it is indistinguishable from.
+    pub(crate) fn record_branch(&mut self, pc: u32, offset: u32, next_pc: u32) {
+        if let Some(collector) = &mut self.branch_collector {
+            collector.record(pc, offset as i32, next_pc != pc.wrapping_add(4));
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::{Instruction, Opcode, Program};
+
+    /// A loop over the bits of `pattern` from the lowest, counting the set ones in x14.
+    fn bit_count_program(pattern: u32, bits: u32) -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 12, 0, pattern, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, bits, false, true),
+            // loop:
+            Instruction::new(Opcode::AND, 13, 12, 1, false, true),
+            Instruction::new(Opcode::BEQ, 13, 0, 8, false, true),
+            Instruction::new(Opcode::ADD, 14, 14, 1, false, true),
+            // skip:
+            Instruction::new(Opcode::SRL, 12, 12, 1, false, true),
+            Instruction::new(Opcode::ADD, 11, 11, 1, false, true),
+            Instruction::new(Opcode::BNE, 11, 10, -20i32 as u32, false, true),
+        ];
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_branch_stats() {
+        let pattern = 0b1011_0010_1101_0001;
+        let mut runtime = Runtime::new(bit_count_program(pattern, 16));
+        runtime.enable_branch_stats();
+        runtime.run();
+        let stats = runtime.branch_stats().unwrap();
+
+        assert_eq!(stats.overflow, 0);
+        assert_eq!(stats.entries.len(), 2);
+        // Both branches run once per iteration, so they are ordered by pc.
+        let [beq, bne] = [&stats.entries[0], &stats.entries[1]];
+        assert_eq!((bne.pc, bne.offset), (28, -20));
+        assert_eq!((bne.taken, bne.not_taken), (15, 1));
+        assert_eq!((beq.pc, beq.offset), (12, 8));
+        assert_eq!((beq.taken, beq.not_taken), (8, 8));
+        assert_eq!(beq.entropy, 1.0);
+        assert!(bne.entropy > 0.0 && bne.entropy < beq.entropy);
+        assert_eq!(stats.taken_distances[&-32], 15);
+        assert_eq!(stats.taken_distances[&8], 8);
+
+        let mut runtime = Runtime::new(bit_count_program(u32::MAX, 16));
+        runtime.enable_branch_stats();
+        runtime.run();
+        let beq = runtime.branch_stats().unwrap().entries[0].clone();
+        assert_eq!((beq.taken, beq.not_taken, beq.entropy), (0, 16, 0.0));
+        assert!(Runtime::new(bit_count_program(0, 1))
+            .branch_stats()
+            .is_none());
+    }
+
+    #[test]
+    fn test_branch_stats_functions() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.enable_branch_stats();
+        runtime.run();
+        let stats = runtime.branch_stats().unwrap();
+        assert!(stats
+            .entries
+            .windows(2)
+            .all(|pair| pair[0].total() >= pair[1].total()));
+        assert!(stats.entries.iter().any(|entry| entry.function.is_some()));
+    }
+
+    #[test]
+    fn test_branch_table_overflow() {
+        let mut collector = BranchCollector::default();
+        for pc in 0..MAX_BRANCH_PCS as u32 + 3 {
+            collector.record(pc * 4, 8, true);
+        }
+        collector.record(0, 8, false);
+        assert_eq!(collector.branches.len(), MAX_BRANCH_PCS);
+        assert_eq!(collector.overflow, 3);
+        assert_eq!(collector.branches[&0].not_taken, 1);
+    }
+}
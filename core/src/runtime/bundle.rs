@@ -0,0 +1,262 @@
+use core::fmt::{Display, Formatter};
+
+use super::{AccessPosition, AluClass, ExecutionRecord, Opcode, RecordFilter};
+use crate::alu::AluEvent;
+use crate::bytes::ByteLookupEvent;
+use crate::cpu::{AuipcEvent, CpuChip, CpuEvent, MemoryRecordEnum};
+
+/// An access of an instruction to a register or to memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BundledAccess {
+    pub position: AccessPosition,
+
+    /// The register, or the aligned address of the word of memory.
+    pub addr: u32,
+
+    pub record: MemoryRecordEnum,
+}
+
+/// The events of an [`ExecutionRecord`] for a single instruction, see
+/// [`ExecutionRecord::events_at_clk`].
+#[derive(Debug, Clone)]
+pub struct EventBundle {
+    pub cpu: CpuEvent,
+
+    /// The function containing the pc and the offset of the pc in it, if the program has a
+    /// symbol for it.
+    pub symbol: Option<(String, u32)>,
+
+    /// The accesses of the instruction, in the order A, B, C and memory.
+    pub accesses: Vec<BundledAccess>,
+
+    /// The event of the instruction in the vectors of ALU events, if it is an ALU instruction
+    /// whose class is recorded.
+    pub alu: Option<AluEvent>,
+
+    pub auipc: Option<AuipcEvent>,
+
+    /// The byte lookups the CPU trace adds for the instruction, empty unless the record filter
+    /// includes [`RecordFilter::BYTE_LOOKUPS`]. The lookups of the chips the instruction is
+    /// delegated to, such as the ALU chips, are not included.
+    pub byte_lookups: Vec<ByteLookupEvent>,
+}
+
+impl ExecutionRecord {
+    /// The events of the instruction executed at `clk` in `shard`, or `None` if there is none.
+    /// Panics unless the indices were built since the record was last mutated.
+    pub fn events_at_clk(&self, shard: u32, clk: u32) -> Option<EventBundle> {
+        let event = self
+            .events_in_clk_range(shard, clk..clk.saturating_add(1))
+            .next()?;
+        Some(self.bundle(event))
+    }
+
+    /// The events of every execution of the instruction at `pc`, in execution order. Panics
+    /// unless the indices were built since the record was last mutated.
+    pub fn events_at_pc(&self, pc: u32) -> Vec<EventBundle> {
+        self.cpu_events
+            .iter()
+            .filter(|event| event.pc == pc)
+            .map(|event| self.bundle(event))
+            .collect()
+    }
+
+    fn bundle(&self, event: &CpuEvent) -> EventBundle {
+        let instruction = event.instruction;
+        let mut accesses = Vec::new();
+        let registers = [
+            (AccessPosition::A, instruction.op_a, event.a_record),
+            (AccessPosition::B, instruction.op_b, event.b_record),
+            (AccessPosition::C, instruction.op_c, event.c_record),
+            (
+                AccessPosition::Memory,
+                event.b.wrapping_add(event.c) & !3,
+                event.memory_record,
+            ),
+        ];
+        for (position, addr, record) in registers {
+            if let Some(record) = record {
+                accesses.push(BundledAccess {
+                    position,
+                    addr,
+                    record,
+                });
+            }
+        }
+
+        let alu = AluClass::from_opcode(instruction.opcode).and_then(|class| {
+            let events = self.alu_events_for_shard(event.shard, class);
+            let start = events.partition_point(|alu| alu.clk < event.clk);
+            events
+                .iter()
+                .skip(start)
+                .take_while(|alu| alu.clk == event.clk)
+                .find(|alu| alu.opcode == instruction.opcode)
+        });
+
+        let auipc = (instruction.opcode == Opcode::AUIPC)
+            .then(|| {
+                let key = (event.shard, event.clk);
+                let start = self
+                    .auipc_events
+                    .partition_point(|auipc| (auipc.shard, auipc.clk) < key);
+                self.auipc_events
+                    .get(start)
+                    .filter(|auipc| (auipc.shard, auipc.clk) == key)
+                    .copied()
+            })
+            .flatten();
+
+        let byte_lookups = if self.filter.contains(RecordFilter::BYTE_LOOKUPS) {
+            CpuChip.byte_lookups(*event, auipc.as_ref())
+        } else {
+            Vec::new()
+        };
+
+        let symbol = self
+            .program
+            .symbols
+            .lookup(event.pc)
+            .map(|symbol| (symbol.name.clone(), event.pc - symbol.addr));
+
+        EventBundle {
+            cpu: *event,
+            symbol,
+            accesses,
+            alu,
+            auipc,
+            byte_lookups,
+        }
+    }
+}
+
+impl Display for EventBundle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let event = &self.cpu;
+        write!(
+            f,
+            "shard {} clk {} pc 0x{:08x}",
+            event.shard, event.clk, event.pc
+        )?;
+        if let Some((name, offset)) = &self.symbol {
+            write!(f, " <{}+0x{:x}>", name, offset)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "  {:?}", event.instruction)?;
+        writeln!(
+            f,
+            "  a=0x{:08x} b=0x{:08x} c=0x{:08x}",
+            event.a, event.b, event.c
+        )?;
+        for access in self.accesses.iter() {
+            let location = match access.position {
+                AccessPosition::Memory => format!("mem[0x{:08x}]", access.addr),
+                position => format!("{:?} x{}", position, access.addr),
+            };
+            match access.record {
+                MemoryRecordEnum::Read(record) => writeln!(
+                    f,
+                    "  {}: read 0x{:08x} (prev shard {} clk {})",
+                    location, record.value, record.prev_shard, record.prev_timestamp
+                )?,
+                MemoryRecordEnum::Write(record) => writeln!(
+                    f,
+                    "  {}: write 0x{:08x} -> 0x{:08x} (prev shard {} clk {})",
+                    location,
+                    record.prev_value,
+                    record.value,
+                    record.prev_shard,
+                    record.prev_timestamp
+                )?,
+            }
+        }
+        if let Some(alu) = &self.alu {
+            writeln!(
+                f,
+                "  alu {}: 0x{:08x} = 0x{:08x}, 0x{:08x}",
+                alu.opcode.mnemonic(),
+                alu.a,
+                alu.b,
+                alu.c
+            )?;
+        }
+        if let Some(auipc) = &self.auipc {
+            writeln!(
+                f,
+                "  auipc: 0x{:08x} = pc + 0x{:08x}",
+                auipc.result, auipc.imm
+            )?;
+        }
+        for lookup in self.byte_lookups.iter() {
+            writeln!(
+                f,
+                "  byte {:?}: a1=0x{:x} a2=0x{:x} b=0x{:x} c=0x{:x}",
+                lookup.opcode, lookup.a1, lookup.a2, lookup.b, lookup.c
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::simple_memory_program;
+    use crate::runtime::{Runtime, Symbol, SymbolTable};
+
+    fn memory_record() -> ExecutionRecord {
+        let mut program = simple_memory_program();
+        program.symbols = SymbolTable::new(vec![Symbol::new("main", 0, 0x100)]);
+        let mut runtime = Runtime::new(program);
+        runtime.run();
+        let mut record = runtime.record;
+        record.build_indices();
+        record
+    }
+
+    #[test]
+    fn test_store_byte_bundle() {
+        let record = memory_record();
+        // The first SB writes 0x25 to the lowest byte of 0x12348765.
+        let sb = record.events_at_pc(0x3c);
+        assert_eq!(sb.len(), 1);
+        assert_eq!(sb[0].cpu.instruction.opcode, Opcode::SB);
+        let bundle = record
+            .events_at_clk(sb[0].cpu.shard, sb[0].cpu.clk)
+            .unwrap();
+        assert_eq!(bundle.cpu.pc, 0x3c);
+
+        let memory = bundle
+            .accesses
+            .iter()
+            .find(|access| access.position == AccessPosition::Memory)
+            .unwrap();
+        assert_eq!(memory.addr, 0x43627530);
+        let MemoryRecordEnum::Write(write) = memory.record else {
+            panic!("SB does not write memory");
+        };
+        assert_eq!((write.prev_value, write.value), (0x12348765, 0x12348725));
+        assert!(bundle.alu.is_none());
+
+        let dump = bundle.to_string();
+        assert!(dump.contains("pc 0x0000003c <main+0x3c>"), "{}", dump);
+        assert!(dump.contains("sb"), "{}", dump);
+        assert!(
+            dump.contains("mem[0x43627530]: write 0x12348765 -> 0x12348725"),
+            "{}",
+            dump
+        );
+        assert!(dump.contains("A x17: read 0x38276525"), "{}", dump);
+    }
+
+    #[test]
+    fn test_alu_bundle() {
+        let record = memory_record();
+        let bundle = &record.events_at_pc(0)[0];
+        let alu = bundle.alu.unwrap();
+        assert_eq!((alu.opcode, alu.a), (Opcode::ADD, 0x12348765));
+        assert!(record.events_at_clk(bundle.cpu.shard, 0).is_none());
+        assert!(record.events_at_pc(0x1000).is_empty());
+        assert!(bundle.to_string().contains("alu add: 0x12348765"));
+    }
+}
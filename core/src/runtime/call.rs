@@ -0,0 +1,184 @@
+use core::fmt::{Display, Formatter};
+
+use super::{ExecutionError, Register, Runtime};
+
+/// The return address of functions called with [`Runtime::call_function`], which stop once they
+/// return to it. It is never part of a program.
+pub const CALL_RETURN_ADDRESS: u32 = 0xffff_fffc;
+
+/// The stack pointer of functions called with [`Runtime::call_function`], at the end of the heap
+/// of the guest allocator.
+pub const CALL_STACK_TOP: u32 = 0x0c00_0000;
+
+/// The number of arguments passed in registers, a0 to a7.
+const MAX_CALL_ARGS: usize = 8;
+
+/// An error that stops a function called with [`Runtime::call_function`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallError {
+    /// More arguments were passed than fit in a0 to a7.
+    TooManyArguments(usize),
+
+    /// The function did not return after `cycles` cycles.
+    CycleLimit { cycles: u64, pc: u32 },
+
+    /// The function jumped outside of the program instead of returning.
+    LeftProgram { pc: u32 },
+
+    /// The guest faulted.
+    Execution(ExecutionError),
+}
+
+impl Display for CallError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CallError::TooManyArguments(count) => write!(
+                f,
+                "{} arguments were passed but at most {} are supported",
+                count, MAX_CALL_ARGS
+            ),
+            CallError::CycleLimit { cycles, pc } => write!(
+                f,
+                "function did not return after {} cycles, at pc=0x{:x}",
+                cycles, pc
+            ),
+            CallError::LeftProgram { pc } => {
+                write!(f, "function jumped outside of the program to pc=0x{:x}", pc)
+            }
+            CallError::Execution(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+impl From<ExecutionError> for CallError {
+    fn from(err: ExecutionError) -> Self {
+        CallError::Execution(err)
+    }
+}
+
+impl Runtime {
+    /// Call the guest function at `pc` with `args` in a0 to a7 and return a0 and a1 once it
+    /// returns, or an error if it runs for more than `max_cycles` cycles.
+    ///
+    /// The function runs in an unconstrained fork of the current state with its own stack, so
+    /// the state of the runtime, its memory and its record are left untouched. It can be called
+    /// before the program is run or between two steps.
+    pub fn call_function(
+        &mut self,
+        pc: u32,
+        args: &[u32],
+        max_cycles: u64,
+    ) -> Result<Vec<u32>, CallError> {
+        if args.len() > MAX_CALL_ARGS {
+            return Err(CallError::TooManyArguments(args.len()));
+        }
+        assert!(
+            !self.unconstrained,
+            "cannot call a function from an unconstrained block"
+        );
+
+        // The fork does not cover the in-memory input stream, and the halt reason and exit code.
+        let input_stream_len = self.state.input_stream.len();
+        let input_stream_ptr = self.state.input_stream_ptr;
+        let input_frame_ptr = self.input_frame_ptr;
+        let halt_reason = self.halt_reason.clone();
+        let exit_code = self.exit_code.take();
+
+        self.enter_unconstrained();
+        let result = self.call_in_fork(pc, args, max_cycles);
+        self.exit_unconstrained();
+
+        self.truncate_input_queue(input_stream_len);
+        self.state.input_stream_ptr = input_stream_ptr;
+        self.input_frame_ptr = input_frame_ptr;
+        self.halt_reason = halt_reason;
+        self.exit_code = exit_code;
+        result
+    }
+
+    fn call_in_fork(
+        &mut self,
+        pc: u32,
+        args: &[u32],
+        max_cycles: u64,
+    ) -> Result<Vec<u32>, CallError> {
+        // Load the part of the memory image that execution has not loaded yet, marking it as
+        // absent so that it is removed again when the fork exits.
+        let program = self.program.clone();
+        for (&addr, &value) in program.memory_image.iter() {
+            if !self.state.memory.contains_key(&addr) {
+                self.unconstrained_state.memory_diff.insert(addr, None);
+                self.state.memory.insert(addr, (value, 0, 0));
+            }
+        }
+
+        let (shard, clk) = (self.current_shard(), self.state.clk);
+        for (i, &arg) in args.iter().enumerate() {
+            self.mw(Register::X10 as u32 + i as u32, arg, shard, clk);
+        }
+        self.mw(Register::X1 as u32, CALL_RETURN_ADDRESS, shard, clk);
+        self.mw(Register::X2 as u32, CALL_STACK_TOP, shard, clk);
+        self.state.pc = pc;
+
+        let mut cycles = 0;
+        while self.state.pc != CALL_RETURN_ADDRESS {
+            if self.is_done() || !self.pc_in_program() {
+                return Err(CallError::LeftProgram { pc: self.state.pc });
+            }
+            if cycles >= max_cycles {
+                return Err(CallError::CycleLimit {
+                    cycles,
+                    pc: self.state.pc,
+                });
+            }
+            self.step()?;
+            cycles += 1;
+        }
+        Ok(vec![
+            self.register(Register::X10),
+            self.register(Register::X11),
+        ])
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+
+    #[test]
+    fn test_call_function() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        let memset = runtime.program.symbols.get("memset").unwrap().addr;
+        runtime.run();
+
+        let registers = runtime.registers();
+        let memory = runtime.state.memory.clone();
+        let (pc, global_clk) = (runtime.state.pc, runtime.state.global_clk);
+        let digest = runtime.record.digest();
+
+        // memset(dest, c, n) returns dest.
+        let dest = 0x0800_0000;
+        let result = runtime.call_function(memset, &[dest, 0xab, 64], 10_000);
+        assert_eq!(result.unwrap()[0], dest);
+
+        assert_eq!(runtime.registers(), registers);
+        assert_eq!(runtime.state.memory, memory);
+        assert_eq!(runtime.word(dest), 0);
+        assert_eq!(
+            (runtime.state.pc, runtime.state.global_clk),
+            (pc, global_clk)
+        );
+        assert_eq!(runtime.record.digest(), digest);
+        assert!(!runtime.unconstrained);
+
+        let result = runtime.call_function(memset, &[dest, 0xab, 1 << 20], 100);
+        assert!(matches!(
+            result,
+            Err(CallError::CycleLimit { cycles: 100, .. })
+        ));
+        assert_eq!(runtime.state.memory, memory);
+    }
+}
@@ -0,0 +1,342 @@
+//! A call graph of the execution, built from a shadow stack of the calls made so far.
+//!
+//! A `jal`/`jalr` linking to `ra` calls its target, and pushes a frame returning to the address
+//! after it. A `jalr` that does not link, or links to `t0`, and jumps to the return address of a
+//! frame returns from it, and from every frame above it, as for a `longjmp`. A jump without link
+//! to the start of a symbol that returns nowhere is a tail call, which replaces the current frame.
+//! Any other jump is ignored, so irregular control flow loses precision rather than failing.
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{Opcode, Register, Runtime};
+
+/// The maximum number of frames on the shadow stack. The calls of deeper frames are counted, but
+/// their cycles are not.
+pub const MAX_SHADOW_STACK_DEPTH: usize = 1 << 16;
+
+/// A function of a [`CallGraph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallGraphNode {
+    /// The entry point of the function, the target of the calls to it.
+    pub addr: u32,
+
+    /// The name of the function, or its address if the program has no symbol for it.
+    pub name: String,
+}
+
+/// The calls from one function to another in a [`CallGraph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallGraphEdge {
+    pub caller: u32,
+
+    pub callee: u32,
+
+    /// The number of calls, including those that have not returned yet.
+    pub calls: u64,
+
+    /// The cycles spent in the callee over all calls, including the functions it called.
+    pub cycles: u64,
+}
+
+/// The calls of an execution, see [`Runtime::call_graph`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallGraph {
+    /// The functions, sorted by address. The first function of the execution is always one.
+    pub nodes: Vec<CallGraphNode>,
+
+    /// The edges, sorted by caller and callee.
+    pub edges: Vec<CallGraphEdge>,
+}
+
+impl CallGraph {
+    /// The edge from `caller` to `callee`, looked up by name.
+    pub fn edge(&self, caller: &str, callee: &str) -> Option<&CallGraphEdge> {
+        let addr = |name: &str| {
+            self.nodes
+                .iter()
+                .find(|node| node.name == name)
+                .map(|node| node.addr)
+        };
+        let (caller, callee) = (addr(caller)?, addr(callee)?);
+        self.edges
+            .iter()
+            .find(|edge| edge.caller == caller && edge.callee == callee)
+    }
+
+    /// The graph in the DOT language of Graphviz, with edges labelled by their calls and cycles.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph calls {\n");
+        for node in self.nodes.iter() {
+            dot.push_str(&format!(
+                "    \"0x{:08x}\" [label=\"{}\"];\n",
+                node.addr,
+                node.name.replace('\\', "\\\\").replace('"', "\\\"")
+            ));
+        }
+        for edge in self.edges.iter() {
+            dot.push_str(&format!(
+                "    \"0x{:08x}\" -> \"0x{:08x}\" [label=\"{} calls, {} cycles\"];\n",
+                edge.caller, edge.callee, edge.calls, edge.cycles
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// A call that has not returned yet.
+#[derive(Debug, Clone)]
+struct ShadowFrame {
+    return_addr: u32,
+    caller: u32,
+    callee: u32,
+
+    /// The global clock of the first instruction of the callee.
+    entry_clk: u32,
+}
+
+/// The shadow stack and the calls and cycles of every edge so far.
+#[derive(Debug, Clone)]
+pub(crate) struct CallGraphTracker {
+    root: u32,
+    frames: Vec<ShadowFrame>,
+    edges: BTreeMap<(u32, u32), (u64, u64)>,
+}
+
+impl CallGraphTracker {
+    pub(crate) fn new(root: u32) -> Self {
+        Self {
+            root,
+            frames: Vec::new(),
+            edges: BTreeMap::new(),
+        }
+    }
+
+    fn push(&mut self, return_addr: u32, caller: u32, callee: u32, entry_clk: u32) {
+        self.edges.entry((caller, callee)).or_default().0 += 1;
+        if self.frames.len() < MAX_SHADOW_STACK_DEPTH {
+            self.frames.push(ShadowFrame {
+                return_addr,
+                caller,
+                callee,
+                entry_clk,
+            });
+        }
+    }
+
+    /// Return from the frames above the first `depth` ones, before the instruction at `clk`.
+    fn unwind(&mut self, depth: usize, clk: u32) {
+        while self.frames.len() > depth {
+            let frame = self.frames.pop().unwrap();
+            self.edges
+                .entry((frame.caller, frame.callee))
+                .or_default()
+                .1 += (clk - frame.entry_clk) as u64;
+        }
+    }
+}
+
+impl Runtime {
+    /// Build a call graph of the execution from now on, see [`Runtime::call_graph`]. Calls in
+    /// unconstrained blocks are ignored.
+    pub fn enable_call_graph(&mut self) {
+        if self.call_graph.is_none() {
+            self.call_graph = Some(CallGraphTracker::new(self.state.pc));
+        }
+    }
+
+    /// Update the shadow stack for the jump of the current instruction, linking to `rd`, to
+    /// `target`.
+    pub(crate) fn observe_jump(&mut self, opcode: Opcode, rd: Register, target: u32) {
+        let is_entry = rd == Register::X0 && self.is_function_entry(target);
+        let Some(tracker) = &mut self.call_graph else {
+            return;
+        };
+        let (pc, clk) = (self.state.pc, self.state.global_clk);
+        let caller = tracker
+            .frames
+            .last()
+            .map_or(tracker.root, |frame| frame.callee);
+        if rd == Register::X1 {
+            tracker.push(pc.wrapping_add(4), caller, target, clk + 1);
+            return;
+        }
+        let returns = opcode == Opcode::JALR && matches!(rd, Register::X0 | Register::X5);
+        let frame = tracker
+            .frames
+            .iter()
+            .rposition(|frame| frame.return_addr == target);
+        match frame {
+            Some(depth) if returns => tracker.unwind(depth, clk + 1),
+            None if is_entry && !tracker.frames.is_empty() => {
+                let return_addr = tracker.frames.last().unwrap().return_addr;
+                tracker.unwind(tracker.frames.len() - 1, clk + 1);
+                tracker.push(return_addr, caller, target, clk + 1);
+            }
+            _ => {}
+        }
+    }
+
+    /// The calls of the execution so far between the functions of the guest, as described in the
+    /// [module documentation](self). Calls that have not returned yet count the cycles up to now.
+    /// Returns `None` if [`Runtime::enable_call_graph`] was not called.
+    pub fn call_graph(&self) -> Option<CallGraph> {
+        let tracker = self.call_graph.as_ref()?;
+        let mut edges = tracker.edges.clone();
+        for frame in tracker.frames.iter() {
+            edges.entry((frame.caller, frame.callee)).or_default().1 +=
+                (self.state.global_clk - frame.entry_clk) as u64;
+        }
+
+        let mut addrs = BTreeSet::from([tracker.root]);
+        addrs.extend(edges.keys().flat_map(|(caller, callee)| [*caller, *callee]));
+        Some(CallGraph {
+            nodes: addrs
+                .into_iter()
+                .map(|addr| CallGraphNode {
+                    addr,
+                    name: self.function_name(addr),
+                })
+                .collect(),
+            edges: edges
+                .into_iter()
+                .map(|((caller, callee), (calls, cycles))| CallGraphEdge {
+                    caller,
+                    callee,
+                    calls,
+                    cycles,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Program, Symbol, SymbolTable};
+    use crate::utils::asm::assemble;
+
+    fn call_graph(mut program: Program, symbols: &[(&str, u32, u32)]) -> CallGraph {
+        program.symbols = SymbolTable::new(
+            symbols
+                .iter()
+                .map(|(name, addr, size)| Symbol::new(name, *addr, *size))
+                .collect(),
+        );
+        let mut runtime = Runtime::new(program);
+        runtime.enable_call_graph();
+        runtime.run();
+        runtime.call_graph().unwrap()
+    }
+
+    fn edges(graph: &CallGraph) -> Vec<(String, String, u64, u64)> {
+        let name = |addr: u32| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| node.addr == addr)
+                .unwrap()
+                .name
+                .clone()
+        };
+        graph
+            .edges
+            .iter()
+            .map(|edge| {
+                (
+                    name(edge.caller),
+                    name(edge.callee),
+                    edge.calls,
+                    edge.cycles,
+                )
+            })
+            .collect()
+    }
+
+    /// `main` calls `a` twice, which calls `b`, and `b` directly.
+    #[test]
+    fn test_call_graph() {
+        let program = assemble(
+            "
+            main:   call a
+                    call b
+                    call a
+                    j    done
+            a:      mv   s1, ra
+                    call b
+                    addi a0, a0, 1
+                    mv   ra, s1
+                    ret
+            b:      addi a0, a0, 2
+                    ret
+            done:   nop
+            ",
+            0,
+        )
+        .unwrap();
+        let graph = call_graph(program, &[("main", 0, 16), ("a", 16, 20), ("b", 36, 8)]);
+        assert_eq!(
+            edges(&graph),
+            vec![
+                ("main".to_string(), "a".to_string(), 2, 14),
+                ("main".to_string(), "b".to_string(), 1, 2),
+                ("a".to_string(), "b".to_string(), 2, 4),
+            ]
+        );
+        assert_eq!(graph.edge("a", "b").unwrap().calls, 2);
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph calls {\n"));
+        assert!(dot.contains("    \"0x00000010\" [label=\"a\"];\n"));
+        assert!(
+            dot.contains("    \"0x00000000\" -> \"0x00000010\" [label=\"2 calls, 14 cycles\"];\n")
+        );
+    }
+
+    /// `main` calls `c`, which tail calls `d`, which returns to `main`, and then calls `e`, which
+    /// calls `f`, which jumps straight back to `main` and unwinds both frames. `g` is called last
+    /// and never returns.
+    #[test]
+    fn test_irregular_control_flow() {
+        let program = assemble(
+            "
+            main:   call c
+                    call e
+            after:  call g
+            c:      addi a0, a0, 1
+                    j    d
+            d:      addi a0, a0, 2
+                    ret
+            e:      li   s2, 8
+                    call f
+                    ret
+            f:      jalr zero, s2, 0
+            g:      addi a0, a0, 3
+                    nop
+            ",
+            0,
+        )
+        .unwrap();
+        let graph = call_graph(
+            program,
+            &[
+                ("main", 0, 12),
+                ("c", 12, 8),
+                ("d", 20, 8),
+                ("e", 28, 12),
+                ("f", 40, 4),
+                ("g", 44, 8),
+            ],
+        );
+        assert_eq!(
+            edges(&graph),
+            vec![
+                ("main".to_string(), "c".to_string(), 1, 2),
+                ("main".to_string(), "e".to_string(), 1, 3),
+                ("main".to_string(), "g".to_string(), 1, 2),
+                ("c".to_string(), "d".to_string(), 1, 2),
+                ("e".to_string(), "f".to_string(), 1, 1),
+            ]
+        );
+    }
+}
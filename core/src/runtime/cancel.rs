@@ -0,0 +1,122 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::{ExecutionError, HaltReason, Runtime};
+
+/// The default number of cycles between two checks of the cancel token.
+pub const DEFAULT_CANCEL_CHECK_INTERVAL: u32 = 4096;
+
+impl Runtime {
+    /// Cancel execution once `token` is set. The token is checked every `cancel_check_interval`
+    /// cycles, before the next instruction is executed, so a cancelled runtime never holds a
+    /// partially executed instruction.
+    pub fn set_cancel_token(&mut self, token: Arc<AtomicBool>) {
+        self.cancel_token = Some(token);
+    }
+
+    /// Execute the program like [`Runtime::try_run`], cancelling it with
+    /// [`ExecutionError::Cancelled`] if it has not finished after `timeout`.
+    ///
+    /// The timeout uses its own cancel token: a token set with [`Runtime::set_cancel_token`] is not
+    /// checked during this run and is restored afterwards.
+    pub fn run_with_timeout(&mut self, timeout: Duration) -> Result<HaltReason, ExecutionError> {
+        let token = Arc::new(AtomicBool::new(false));
+        let previous = self.cancel_token.replace(token.clone());
+
+        // Dropping `done` wakes the timer up early when the program finishes in time.
+        let (done, finished) = mpsc::channel::<()>();
+        let timer = thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(timeout) {
+                token.store(true, Ordering::Relaxed);
+            }
+        });
+
+        let result = self.try_run();
+        drop(done);
+        timer.join().expect("timer thread panicked");
+        self.cancel_token = previous;
+
+        result.map(|()| self.halt_reason.clone())
+    }
+
+    /// Return [`ExecutionError::Cancelled`] if the cancel token was set, checking it only every
+    /// `cancel_check_interval` cycles.
+    pub(crate) fn check_cancelled(&self) -> Result<(), ExecutionError> {
+        let Some(token) = &self.cancel_token else {
+            return Ok(());
+        };
+        let at_cycle = self.state.global_clk;
+        if at_cycle % self.cancel_check_interval.max(1) == 0 && token.load(Ordering::Relaxed) {
+            return Err(ExecutionError::Cancelled {
+                at_cycle,
+                pc: self.state.pc,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::{Instruction, Opcode, Program, Register};
+
+    /// A program that jumps to itself forever.
+    fn infinite_loop_program() -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 29, 0, 5, false, true),
+            Instruction::new(Opcode::JAL, 0, 0, 0, true, true),
+        ];
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_cancel_token() {
+        let mut runtime = Runtime::new(infinite_loop_program());
+        runtime.emit_events = false;
+        let token = Arc::new(AtomicBool::new(false));
+        runtime.set_cancel_token(token.clone());
+
+        let start = Instant::now();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            token.store(true, Ordering::Relaxed);
+        });
+        let result = runtime.try_run();
+        canceller.join().unwrap();
+
+        let Err(ExecutionError::Cancelled { at_cycle, pc }) = result else {
+            panic!("expected the program to be cancelled, got {:?}", result);
+        };
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(at_cycle % DEFAULT_CANCEL_CHECK_INTERVAL, 0);
+        assert_eq!(at_cycle, runtime.state.global_clk);
+        assert_eq!(pc, 4);
+        assert_eq!(runtime.state.pc, 4);
+        assert_eq!(runtime.register(Register::X29), 5);
+    }
+
+    #[test]
+    fn test_run_with_timeout() {
+        let mut runtime = Runtime::new(infinite_loop_program());
+        runtime.emit_events = false;
+        runtime.cancel_check_interval = 1;
+        assert!(matches!(
+            runtime.run_with_timeout(Duration::from_millis(50)),
+            Err(ExecutionError::Cancelled { .. })
+        ));
+        assert!(runtime.cancel_token.is_none());
+
+        let mut runtime = Runtime::new(fibonacci_program());
+        assert_eq!(
+            runtime.run_with_timeout(Duration::from_secs(600)),
+            Ok(HaltReason::Finished)
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use super::{CpuRecord, ExecutionRecord, ExecutionState, Runtime, ShardBoundary, ShardProvenance};
+
+/// A snapshot of a runtime in between two instructions, from which execution can be resumed.
+#[derive(Debug, Clone)]
+pub struct ExecutionCheckpoint {
+    /// The execution state at the time of the checkpoint.
+    pub state: ExecutionState,
+
+    /// The events emitted before the checkpoint, as a prefix of the record of the execution.
+    pub record_mark: RecordMark,
+
+    pub(crate) cpu_record: CpuRecord,
+
+    pub(crate) cycle_tracker: HashMap<String, (u32, u32)>,
+}
+
+/// The length of every list of a record that grows during execution, at a checkpoint. The record
+/// at the checkpoint is the prefix of any later record of the same execution up to the mark, so
+/// a checkpoint does not copy the events before it.
+#[derive(Debug, Clone, Default)]
+pub struct RecordMark {
+    cpu_events: usize,
+    auipc_events: usize,
+    add_events: usize,
+    mul_events: usize,
+    sub_events: usize,
+    bitwise_events: usize,
+    shift_left_events: usize,
+    shift_right_events: usize,
+    divrem_events: usize,
+    lt_events: usize,
+    bitmanip_events: usize,
+    field_events: usize,
+    sha_extend_events: usize,
+    sha_compress_events: usize,
+    keccak_permute_events: usize,
+    ed_add_events: usize,
+    ed_decompress_events: usize,
+    weierstrass_add_events: usize,
+    weierstrass_double_events: usize,
+    k256_decompress_events: usize,
+    blake3_compress_inner_events: usize,
+    mem64_events: usize,
+    shard_boundaries: usize,
+
+    /// The boundary of the current shard, whose exit is updated by every instruction.
+    last_boundary: Option<ShardBoundary>,
+
+    /// The current shard, the provenance of later shards being dropped.
+    shard: u32,
+    checkpoints: usize,
+    replays: usize,
+}
+
+impl ExecutionRecord {
+    /// Mark the events emitted so far, see [`RecordMark`].
+    pub(crate) fn mark(&self, shard: u32) -> RecordMark {
+        RecordMark {
+            cpu_events: self.cpu_events.len(),
+            auipc_events: self.auipc_events.len(),
+            add_events: self.add_events.len(),
+            mul_events: self.mul_events.len(),
+            sub_events: self.sub_events.len(),
+            bitwise_events: self.bitwise_events.len(),
+            shift_left_events: self.shift_left_events.len(),
+            shift_right_events: self.shift_right_events.len(),
+            divrem_events: self.divrem_events.len(),
+            lt_events: self.lt_events.len(),
+            bitmanip_events: self.bitmanip_events.len(),
+            field_events: self.field_events.len(),
+            sha_extend_events: self.sha_extend_events.len(),
+            sha_compress_events: self.sha_compress_events.len(),
+            keccak_permute_events: self.keccak_permute_events.len(),
+            ed_add_events: self.ed_add_events.len(),
+            ed_decompress_events: self.ed_decompress_events.len(),
+            weierstrass_add_events: self.weierstrass_add_events.len(),
+            weierstrass_double_events: self.weierstrass_double_events.len(),
+            k256_decompress_events: self.k256_decompress_events.len(),
+            blake3_compress_inner_events: self.blake3_compress_inner_events.len(),
+            mem64_events: self.mem64_events.len(),
+            shard_boundaries: self.shard_boundaries.len(),
+            last_boundary: self.shard_boundaries.last().copied(),
+            shard,
+            checkpoints: self.provenance.checkpoints.len(),
+            replays: self.provenance.replays.len(),
+        }
+    }
+
+    /// Truncate a record of an execution to the events emitted before `mark` was taken, dropping
+    /// everything set once execution finishes and by the chips.
+    pub(crate) fn truncate_to_mark(&mut self, mark: &RecordMark) {
+        let Self {
+            index: _,
+            program: _,
+            cpu_events,
+            auipc_events,
+            instruction_counts,
+            add_events,
+            mul_events,
+            sub_events,
+            bitwise_events,
+            shift_left_events,
+            shift_right_events,
+            divrem_events,
+            lt_events,
+            bitmanip_events,
+            byte_lookups,
+            field_events,
+            sha_extend_events,
+            sha_compress_events,
+            keccak_permute_events,
+            ed_add_events,
+            ed_decompress_events,
+            weierstrass_add_events,
+            weierstrass_double_events,
+            k256_decompress_events,
+            blake3_compress_inner_events,
+            mem64_events,
+            first_memory_record,
+            last_memory_record,
+            program_memory_record,
+            final_shard,
+            guest_assertion,
+            public_values_digest,
+            output_channels,
+            shard_boundaries,
+            provenance,
+            filter: _,
+            indices,
+            indices_dirty,
+        } = self;
+        cpu_events.truncate(mark.cpu_events);
+        auipc_events.truncate(mark.auipc_events);
+        instruction_counts.clear();
+        add_events.truncate(mark.add_events);
+        mul_events.truncate(mark.mul_events);
+        sub_events.truncate(mark.sub_events);
+        bitwise_events.truncate(mark.bitwise_events);
+        shift_left_events.truncate(mark.shift_left_events);
+        shift_right_events.truncate(mark.shift_right_events);
+        divrem_events.truncate(mark.divrem_events);
+        lt_events.truncate(mark.lt_events);
+        bitmanip_events.truncate(mark.bitmanip_events);
+        byte_lookups.clear();
+        field_events.truncate(mark.field_events);
+        sha_extend_events.truncate(mark.sha_extend_events);
+        sha_compress_events.truncate(mark.sha_compress_events);
+        keccak_permute_events.truncate(mark.keccak_permute_events);
+        ed_add_events.truncate(mark.ed_add_events);
+        ed_decompress_events.truncate(mark.ed_decompress_events);
+        weierstrass_add_events.truncate(mark.weierstrass_add_events);
+        weierstrass_double_events.truncate(mark.weierstrass_double_events);
+        k256_decompress_events.truncate(mark.k256_decompress_events);
+        blake3_compress_inner_events.truncate(mark.blake3_compress_inner_events);
+        mem64_events.truncate(mark.mem64_events);
+        first_memory_record.clear();
+        last_memory_record.clear();
+        program_memory_record.clear();
+        *final_shard = None;
+        *guest_assertion = None;
+        *public_values_digest = None;
+        output_channels.clear();
+        shard_boundaries.truncate(mark.shard_boundaries);
+        if let (Some(last), Some(boundary)) = (shard_boundaries.last_mut(), mark.last_boundary) {
+            *last = boundary;
+        }
+        provenance.shards.retain(|shard, _| *shard <= mark.shard);
+        provenance.checkpoints.truncate(mark.checkpoints);
+        provenance.replays.truncate(mark.replays);
+        *indices = None;
+        *indices_dirty = false;
+    }
+}
+
+impl Runtime {
+    /// Take a checkpoint of the current execution.
+    ///
+    /// Checkpoints can only be taken in constrained mode, since unconstrained blocks are rolled
+    /// back when they exit.
+    pub fn checkpoint(&self) -> ExecutionCheckpoint {
+        assert!(
+            !self.unconstrained,
+            "cannot checkpoint in unconstrained mode"
+        );
+        ExecutionCheckpoint {
+            state: self.state.clone(),
+            record_mark: self.record.mark(self.state.current_shard),
+            cpu_record: self.cpu_record,
+            cycle_tracker: self.cycle_tracker.clone(),
+        }
+    }
+
+    /// Resume execution from a checkpoint taken on a runtime for the same program. `record` is a
+    /// record of the execution the checkpoint was taken in, as it was at the checkpoint or at any
+    /// point after it, such as the record of the finished execution. The shard of the checkpoint
+    /// and the ones after it are tagged as [`ShardProvenance::ResumedFromCheckpoint`] in the
+    /// record.
+    ///
+    /// The runtime is reset as by [`Runtime::reset_with_program`] and initialized, keeping its
+    /// program even if `initialize` already relocated it. Afterwards the rest of the program is
+    /// executed with `step` until `is_done`, followed by `finalize`.
+    pub fn restore(&mut self, checkpoint: &ExecutionCheckpoint, record: &ExecutionRecord) {
+        let layout_offset = self.layout_offset;
+        self.reset_with_program(self.program.clone());
+        self.layout_offset = layout_offset;
+        self.initialize();
+
+        self.state = checkpoint.state.clone();
+        self.record = record.clone();
+        self.record.truncate_to_mark(&checkpoint.record_mark);
+        self.record.program = self.program.clone();
+        self.record.filter = self.record_filter;
+        self.record.provenance.checkpoints.push(checkpoint.id());
+        if let Some(provenance) = self
+            .record
+            .provenance
+            .shards
+            .get_mut(&self.state.current_shard)
+        {
+            *provenance = ShardProvenance::ResumedFromCheckpoint;
+        }
+        self.cpu_record = checkpoint.cpu_record;
+        self.cycle_tracker = checkpoint.cycle_tracker.clone();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::Runtime;
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let mut expected = Runtime::new(fibonacci_program());
+        expected.run();
+
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.initialize();
+        while runtime.state.global_clk < 1000 {
+            assert!(!runtime.is_done());
+            runtime.step().unwrap();
+        }
+        let checkpoint = runtime.checkpoint();
+        assert_eq!(checkpoint.state.global_clk, 1000);
+
+        // The record of the execution at the checkpoint, and once it finished.
+        let partial = runtime.record.clone();
+        while !runtime.is_done() {
+            runtime.step().unwrap();
+        }
+        runtime.finalize();
+
+        for record in [&partial, &runtime.record] {
+            let mut resumed = Runtime::new(fibonacci_program());
+            resumed.restore(&checkpoint, record);
+            while !resumed.is_done() {
+                resumed.step().unwrap();
+            }
+            resumed.finalize();
+
+            assert_eq!(resumed.state.global_clk, expected.state.global_clk);
+            assert_eq!(resumed.registers(), expected.registers());
+            assert_eq!(resumed.state.memory, expected.state.memory);
+
+            // Apart from how its shards were produced, the record is the one of a full run.
+            resumed.record.provenance = expected.record.provenance.clone();
+            assert_eq!(resumed.record.digest(), expected.record.digest());
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! Export of an execution in the Chrome trace event format, which Perfetto and `chrome://tracing`
+//! load.
+//!
+//! Every cycle is one microsecond of trace time. Instructions are aggregated into spans per call
+//! of a function, inferred from the `jal`/`jalr` instructions of the CPU events: a jump linking
+//! to `ra` enters a function, and a jump to the return address of a frame on the stack leaves it
+//! and every frame above it. A jump without link to the start of a symbol is a tail call, which
+//! replaces the current frame, and a `ret` to an address no frame returns to closes every frame
+//! but the outermost one. Frames still open at the end of the execution, such as those of
+//! functions that never return, end with it.
+use serde_json::{json, Value};
+
+use super::{Error, Opcode, Register, Runtime, SyscallCode};
+
+/// How an execution is exported with [`Runtime::chrome_trace`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChromeTraceOptions {
+    /// Spans shorter than this many cycles are merged into the span of their caller.
+    pub min_span_cycles: u32,
+
+    /// The maximum number of spans exported. The shortest spans are merged into the spans of their
+    /// callers until there are at most this many, but the outermost spans are always kept.
+    pub max_spans: Option<usize>,
+}
+
+/// A call of a function, from the cycle of its first instruction to the cycle after its last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSpan {
+    /// The name of the function, or its address if the program has no symbol for it.
+    pub function: String,
+
+    pub start: u32,
+
+    pub end: u32,
+
+    /// The number of spans enclosing this one.
+    pub depth: usize,
+}
+
+/// A function call that has not returned yet.
+struct Frame {
+    function: String,
+    start: u32,
+    return_addr: Option<u32>,
+}
+
+/// The calls that have not returned yet, and the spans of those that did.
+struct CallStack {
+    frames: Vec<Frame>,
+    spans: Vec<FunctionSpan>,
+}
+
+impl CallStack {
+    fn push(&mut self, function: String, start: u32, return_addr: Option<u32>) {
+        self.frames.push(Frame {
+            function,
+            start,
+            return_addr,
+        });
+    }
+
+    /// Close the frames above the first `depth` ones at cycle `end`.
+    fn truncate(&mut self, depth: usize, end: u32) {
+        while self.frames.len() > depth {
+            let frame = self.frames.pop().unwrap();
+            self.spans.push(FunctionSpan {
+                function: frame.function,
+                start: frame.start,
+                end,
+                depth: self.frames.len(),
+            });
+        }
+    }
+}
+
+impl Runtime {
+    /// The name of the function containing `pc`.
+    pub(crate) fn function_name(&self, pc: u32) -> String {
+        match self.program.symbols.lookup(pc) {
+            Some(symbol) => symbol.name.clone(),
+            None => format!("0x{:08x}", pc),
+        }
+    }
+
+    /// Whether `pc` is the first instruction of a function.
+    pub(crate) fn is_function_entry(&self, pc: u32) -> bool {
+        self.program
+            .symbols
+            .lookup(pc)
+            .is_some_and(|symbol| symbol.addr == pc)
+    }
+
+    /// The calls of the execution so far, inferred from its CPU events as described in the
+    /// [module documentation](self), sorted by start and depth.
+    pub fn function_spans(&self) -> Vec<FunctionSpan> {
+        let events = &self.record.cpu_events;
+        let Some(first) = events.first() else {
+            return Vec::new();
+        };
+        let mut stack = CallStack {
+            frames: Vec::new(),
+            spans: Vec::new(),
+        };
+        stack.push(self.function_name(first.pc), 0, None);
+
+        for (i, event) in events.iter().enumerate() {
+            let instruction = &event.instruction;
+            if !matches!(instruction.opcode, Opcode::JAL | Opcode::JALR) {
+                continue;
+            }
+            let cycle = i as u32 + 1;
+            let next_pc = events.get(i + 1).map_or(self.state.pc, |next| next.pc);
+            if instruction.op_a == Register::X1 as u32 {
+                stack.push(
+                    self.function_name(next_pc),
+                    cycle,
+                    Some(event.pc.wrapping_add(4)),
+                );
+            } else if instruction.op_a != Register::X0 as u32 {
+                continue;
+            } else if let Some(depth) = stack
+                .frames
+                .iter()
+                .rposition(|frame| frame.return_addr == Some(next_pc))
+            {
+                stack.truncate(depth, cycle);
+            } else if self.is_function_entry(next_pc) {
+                let return_addr = stack.frames.last().unwrap().return_addr;
+                stack.truncate(stack.frames.len() - 1, cycle);
+                stack.push(self.function_name(next_pc), cycle, return_addr);
+            } else if instruction.opcode == Opcode::JALR && instruction.op_b == Register::X1 as u32
+            {
+                stack.truncate(1, cycle);
+            }
+        }
+        stack.truncate(0, events.len() as u32);
+
+        let mut spans = stack.spans;
+        spans.sort_by_key(|span| (span.start, span.depth));
+        spans
+    }
+
+    /// The execution so far in the Chrome trace event format, as JSON. Function calls are
+    /// duration events, syscalls instant events and the start of every shard a metadata event.
+    ///
+    /// This needs the CPU events, so the runtime must have recorded them.
+    pub fn chrome_trace(&self, options: &ChromeTraceOptions) -> Vec<u8> {
+        let mut spans = self.function_spans();
+        spans.retain(|span| span.depth == 0 || span.end - span.start >= options.min_span_cycles);
+        if let Some(max_spans) = options.max_spans {
+            let mut durations = spans
+                .iter()
+                .filter(|span| span.depth > 0)
+                .map(|span| span.end - span.start)
+                .collect::<Vec<_>>();
+            let kept = max_spans.saturating_sub(spans.len() - durations.len());
+            if kept < durations.len() {
+                durations.sort_unstable();
+                // The longest span that does not fit. Spans as long as it are dropped as well, to
+                // keep the limit.
+                let threshold = durations[durations.len() - 1 - kept];
+                spans.retain(|span| span.depth == 0 || span.end - span.start > threshold);
+            }
+        }
+
+        let mut events = vec![json!({
+            "name": "process_name",
+            "ph": "M",
+            "pid": 0,
+            "tid": 0,
+            "args": { "name": "sp1 guest" },
+        })];
+        events.extend(spans.iter().map(|span| {
+            json!({
+                "name": span.function,
+                "cat": "function",
+                "ph": "X",
+                "ts": span.start,
+                "dur": span.end - span.start,
+                "pid": 0,
+                "tid": 0,
+                "args": { "depth": span.depth },
+            })
+        }));
+
+        let mut shard = None;
+        for (i, event) in self.record.cpu_events.iter().enumerate() {
+            if shard != Some(event.shard) {
+                shard = Some(event.shard);
+                events.push(json!({
+                    "name": "shard",
+                    "ph": "M",
+                    "ts": i,
+                    "pid": 0,
+                    "tid": 0,
+                    "args": { "shard": event.shard, "entry_pc": event.pc },
+                }));
+            }
+            if event.instruction.opcode == Opcode::ECALL {
+                let name = match SyscallCode::try_from_u32(event.b) {
+                    Some(code) => format!("{:?}", code),
+                    None => format!("syscall 0x{:x}", event.b),
+                };
+                events.push(json!({
+                    "name": name,
+                    "cat": "syscall",
+                    "ph": "i",
+                    "s": "t",
+                    "ts": i,
+                    "pid": 0,
+                    "tid": 0,
+                    "args": { "pc": event.pc },
+                }));
+            }
+        }
+
+        let trace = json!({
+            "traceEvents": Value::Array(events),
+            "displayTimeUnit": "ns",
+        });
+        serde_json::to_vec(&trace).expect("failed to serialize the trace")
+    }
+
+    /// Write the execution so far to `path` in the Chrome trace event format, see
+    /// [`Runtime::chrome_trace`].
+    pub fn export_chrome_trace(&self, path: &str) -> Result<(), Error> {
+        self.export_chrome_trace_with(path, &ChromeTraceOptions::default())
+    }
+
+    /// Like [`Runtime::export_chrome_trace`], with the size of the output set by `options`.
+    pub fn export_chrome_trace_with(
+        &self,
+        path: &str,
+        options: &ChromeTraceOptions,
+    ) -> Result<(), Error> {
+        std::fs::write(path, self.chrome_trace(options)).map_err(|error| Error::Io {
+            path: path.to_string(),
+            error,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::{Symbol, SymbolTable};
+    use crate::utils::asm::assemble;
+
+    fn spans(trace: &Value) -> Vec<&Value> {
+        trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|event| event["ph"] == "X")
+            .collect()
+    }
+
+    #[test]
+    fn test_export_fibonacci() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.run();
+        let path = std::env::temp_dir().join("sp1_test_export_fibonacci.json");
+        runtime.export_chrome_trace(path.to_str().unwrap()).unwrap();
+        let trace: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let spans = spans(&trace);
+        let outermost = spans
+            .iter()
+            .filter(|span| span["args"]["depth"] == 0)
+            .collect::<Vec<_>>();
+        assert!(spans.len() > outermost.len());
+        let total = outermost
+            .iter()
+            .map(|span| span["dur"].as_u64().unwrap())
+            .sum::<u64>();
+        assert_eq!(total, runtime.state.global_clk as u64);
+
+        // Capping the number of spans merges the short ones, but keeps the outermost ones.
+        let options = ChromeTraceOptions {
+            min_span_cycles: 0,
+            max_spans: Some(outermost.len() + 2),
+        };
+        let capped: Value = serde_json::from_slice(&runtime.chrome_trace(&options)).unwrap();
+        let capped = super::tests::spans(&capped);
+        assert!(capped.len() <= outermost.len() + 2);
+        assert!(capped.len() >= outermost.len());
+    }
+
+    /// `main` calls `outer`, which tail calls `inner`, which returns to `main`, and then calls
+    /// `spin`, which halts without returning.
+    #[test]
+    fn test_tail_and_non_returning_calls() {
+        let mut program = assemble(
+            "
+            main:   call outer
+                    call spin
+            outer:  addi a0, a0, 1
+                    j    inner
+            inner:  addi a0, a0, 2
+                    ret
+            spin:   li   t0, 100
+                    li   a0, 0
+                    ecall
+            ",
+            0,
+        )
+        .unwrap();
+        program.symbols = SymbolTable::new(vec![
+            Symbol::new("main", 0, 8),
+            Symbol::new("outer", 8, 8),
+            Symbol::new("inner", 16, 8),
+            Symbol::new("spin", 24, 12),
+        ]);
+        let mut runtime = Runtime::new(program);
+        runtime.run();
+        let spans = runtime
+            .function_spans()
+            .into_iter()
+            .map(|span| (span.function, span.start, span.end, span.depth))
+            .collect::<Vec<_>>();
+        assert_eq!(runtime.state.global_clk, 9);
+        assert_eq!(
+            spans,
+            vec![
+                ("main".to_string(), 0, 9, 0),
+                ("outer".to_string(), 1, 3, 1),
+                ("inner".to_string(), 3, 5, 1),
+                ("spin".to_string(), 6, 9, 1),
+            ]
+        );
+        let trace: Value =
+            serde_json::from_slice(&runtime.chrome_trace(&ChromeTraceOptions::default())).unwrap();
+        assert!(trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|event| event["ph"] == "i" && event["name"] == "HALT"));
+    }
+}
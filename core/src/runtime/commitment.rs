@@ -0,0 +1,362 @@
+use core::fmt::{Debug, Formatter};
+
+use p3_baby_bear::BabyBear;
+use p3_field::{AbstractField, PrimeField32};
+use p3_symmetric::Permutation;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{ExecutionState, Runtime};
+use crate::utils::{poseidon2_perm, Poseidon2Perm};
+
+/// The number of elements of the state of the Poseidon2 sponge.
+const WIDTH: usize = 16;
+
+/// The number of elements absorbed and squeezed per permutation.
+const RATE: usize = 8;
+
+/// The number of bytes packed into each absorbed element, so that it is below the modulus.
+const BYTES_PER_ELEMENT: usize = 3;
+
+/// The hash function the public values of the guest are committed with, see
+/// [`Runtime::set_public_values_hasher`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PublicValuesHasher {
+    #[default]
+    Blake3,
+
+    /// A sponge over the Poseidon2 permutation of [`crate::utils::BabyBearPoseidon2`]. Bytes are
+    /// packed three per element, followed by the number of bytes, and the digest is the first
+    /// eight elements of the state as little-endian words.
+    Poseidon2,
+}
+
+impl PublicValuesHasher {
+    /// The digest of `bytes`, computed in a single pass.
+    pub fn hash(self, bytes: &[u8]) -> [u8; 32] {
+        match self {
+            PublicValuesHasher::Blake3 => *blake3::hash(bytes).as_bytes(),
+            PublicValuesHasher::Poseidon2 => {
+                let perm = poseidon2_perm();
+                let mut elements = bytes
+                    .chunks(BYTES_PER_ELEMENT)
+                    .map(pack)
+                    .collect::<Vec<_>>();
+                elements.push(BabyBear::from_wrapped_u64(bytes.len() as u64));
+                let mut state = [BabyBear::zero(); WIDTH];
+                for block in elements.chunks(RATE) {
+                    state[..block.len()].copy_from_slice(block);
+                    perm.permute_mut(&mut state);
+                }
+                squeeze(&state)
+            }
+        }
+    }
+}
+
+/// Pack up to three bytes into an element, in little-endian order.
+fn pack(bytes: &[u8]) -> BabyBear {
+    let value = bytes
+        .iter()
+        .rev()
+        .fold(0u32, |acc, &byte| (acc << 8) | byte as u32);
+    BabyBear::from_canonical_u32(value)
+}
+
+fn squeeze(state: &[BabyBear; WIDTH]) -> [u8; 32] {
+    let mut digest = [0u8; 32];
+    for (chunk, element) in digest.chunks_exact_mut(4).zip(state.iter()) {
+        chunk.copy_from_slice(&element.as_canonical_u32().to_le_bytes());
+    }
+    digest
+}
+
+/// The Poseidon2 sponge of [`PublicValuesHasher::Poseidon2`], absorbing bytes as they come.
+#[derive(Clone)]
+struct Poseidon2Sponge {
+    perm: Poseidon2Perm,
+    state: [BabyBear; WIDTH],
+
+    /// The elements not absorbed yet, fewer than `RATE`.
+    elements: Vec<BabyBear>,
+
+    /// The bytes not packed into an element yet, fewer than `BYTES_PER_ELEMENT`.
+    bytes: Vec<u8>,
+
+    len: u64,
+}
+
+impl Poseidon2Sponge {
+    fn new() -> Self {
+        Self {
+            perm: poseidon2_perm(),
+            state: [BabyBear::zero(); WIDTH],
+            elements: Vec::with_capacity(RATE),
+            bytes: Vec::with_capacity(BYTES_PER_ELEMENT),
+            len: 0,
+        }
+    }
+
+    fn push_element(&mut self, element: BabyBear) {
+        self.elements.push(element);
+        if self.elements.len() == RATE {
+            self.absorb_elements();
+        }
+    }
+
+    fn absorb_elements(&mut self) {
+        self.state[..self.elements.len()].copy_from_slice(&self.elements);
+        self.perm.permute_mut(&mut self.state);
+        self.elements.clear();
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.bytes.push(byte);
+            if self.bytes.len() == BYTES_PER_ELEMENT {
+                let element = pack(&self.bytes);
+                self.bytes.clear();
+                self.push_element(element);
+            }
+        }
+        self.len += bytes.len() as u64;
+    }
+
+    fn finalize(mut self) -> [u8; 32] {
+        if !self.bytes.is_empty() {
+            let element = pack(&self.bytes);
+            self.push_element(element);
+        }
+        self.push_element(BabyBear::from_wrapped_u64(self.len));
+        if !self.elements.is_empty() {
+            self.absorb_elements();
+        }
+        squeeze(&self.state)
+    }
+}
+
+#[derive(Clone)]
+enum HasherState {
+    Blake3(Box<blake3::Hasher>),
+    Poseidon2(Box<Poseidon2Sponge>),
+}
+
+/// Hashes the public values as the guest commits them, so that their digest is known as soon as
+/// execution finishes.
+///
+/// Only the choice of hasher is serialized: a deserialized digester starts over, and catches up
+/// with the output stream when the digest is taken.
+#[derive(Clone)]
+pub(crate) struct PublicValuesDigester {
+    hasher: PublicValuesHasher,
+    state: HasherState,
+
+    /// The number of bytes of the output stream absorbed so far.
+    absorbed: usize,
+}
+
+impl PublicValuesDigester {
+    pub(crate) fn new(hasher: PublicValuesHasher) -> Self {
+        let state = match hasher {
+            PublicValuesHasher::Blake3 => HasherState::Blake3(Box::default()),
+            PublicValuesHasher::Poseidon2 => {
+                HasherState::Poseidon2(Box::new(Poseidon2Sponge::new()))
+            }
+        };
+        Self {
+            hasher,
+            state,
+            absorbed: 0,
+        }
+    }
+
+    pub(crate) fn hasher(&self) -> PublicValuesHasher {
+        self.hasher
+    }
+
+    #[inline]
+    fn update(&mut self, bytes: &[u8]) {
+        match &mut self.state {
+            HasherState::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            HasherState::Poseidon2(sponge) => sponge.update(bytes),
+        }
+        self.absorbed += bytes.len();
+    }
+
+    /// Absorb the bytes of the output stream not absorbed yet, starting over if it was truncated
+    /// behind the back of the digester.
+    fn sync(&mut self, public_values: &[u8]) {
+        if public_values.len() < self.absorbed {
+            *self = Self::new(self.hasher);
+        }
+        self.update(&public_values[self.absorbed..]);
+    }
+
+    /// The digest of `public_values`, the output stream the digester absorbed a prefix of.
+    fn digest(&self, public_values: &[u8]) -> [u8; 32] {
+        let mut digester = self.clone();
+        digester.sync(public_values);
+        match digester.state {
+            HasherState::Blake3(hasher) => *hasher.finalize().as_bytes(),
+            HasherState::Poseidon2(sponge) => sponge.finalize(),
+        }
+    }
+}
+
+impl Default for PublicValuesDigester {
+    fn default() -> Self {
+        Self::new(PublicValuesHasher::default())
+    }
+}
+
+impl Debug for PublicValuesDigester {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PublicValuesDigester")
+            .field("hasher", &self.hasher)
+            .field("absorbed", &self.absorbed)
+            .finish()
+    }
+}
+
+impl Serialize for PublicValuesDigester {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.hasher.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicValuesDigester {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PublicValuesHasher::deserialize(deserializer).map(Self::new)
+    }
+}
+
+impl ExecutionState {
+    /// Append `bytes` to the public values in the output stream, absorbing them into their
+    /// digest.
+    pub(crate) fn commit_public_values(&mut self, bytes: &[u8]) {
+        self.output_stream.extend_from_slice(bytes);
+        self.public_values_digester.sync(&self.output_stream);
+    }
+
+    /// The digest of the public values committed so far.
+    pub fn public_values_digest(&self) -> [u8; 32] {
+        self.public_values_digester.digest(&self.output_stream)
+    }
+}
+
+impl Runtime {
+    /// Commit to the public values with `hasher` instead of blake3. The values already committed
+    /// are hashed again when the digest is taken.
+    pub fn set_public_values_hasher(&mut self, hasher: PublicValuesHasher) {
+        self.state.public_values_digester = PublicValuesDigester::new(hasher);
+    }
+
+    /// The digest of the public values the guest committed so far, maintained as they are
+    /// written. Commits of unconstrained blocks are rolled back with the output stream.
+    pub fn public_values_digest(&self) -> [u8; 32] {
+        self.state.public_values_digest()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Instruction, Opcode, Program, SyscallCode};
+
+    /// Commit `len` bytes at `addr` with `code`, passing `fd` as the first argument.
+    fn commit(code: SyscallCode, fd: u32, addr: u32, len: u32) -> [Instruction; 5] {
+        [
+            Instruction::new(Opcode::ADD, 5, 0, code as u32, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, fd, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, addr, false, true),
+            Instruction::new(Opcode::ADD, 12, 0, len, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ]
+    }
+
+    fn ecall(code: SyscallCode) -> [Instruction; 2] {
+        [
+            Instruction::new(Opcode::ADD, 5, 0, code as u32, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ]
+    }
+
+    /// Commit 16 bytes in two writes, with an unconstrained block committing in between.
+    fn commit_program() -> Program {
+        let mut instructions = Vec::new();
+        instructions.extend(commit(SyscallCode::WRITE, 3, 0x1000, 7));
+        instructions.extend(ecall(SyscallCode::ENTER_UNCONSTRAINED));
+        // Skip the block when it is entered again after exiting.
+        instructions.push(Instruction::new(Opcode::BEQ, 10, 0, 32, false, true));
+        instructions.extend(commit(SyscallCode::WRITE, 3, 0x1000, 16));
+        instructions.extend(ecall(SyscallCode::EXIT_UNCONSTRAINED));
+        instructions.extend(commit(SyscallCode::WRITE_CHANNEL, 0, 0x1007, 9));
+        let mut program = Program::new(instructions, 0, 0);
+        for (i, word) in [0x0403_0201, 0x0807_0605, 0x0c0b_0a09, 0x100f_0e0d]
+            .into_iter()
+            .enumerate()
+        {
+            program.memory_image.insert(0x1000 + 4 * i as u32, word);
+        }
+        program
+    }
+
+    #[test]
+    fn test_incremental_digest() {
+        let expected = (1..=16).collect::<Vec<u8>>();
+        for hasher in [PublicValuesHasher::Blake3, PublicValuesHasher::Poseidon2] {
+            let mut runtime = Runtime::new(commit_program());
+            runtime.set_public_values_hasher(hasher);
+            runtime.run();
+
+            assert_eq!(runtime.state.output_stream, expected);
+            let digest = runtime.public_values_digest();
+            assert_eq!(digest, hasher.hash(&expected));
+            assert_eq!(runtime.record.public_values_digest, Some(digest));
+            assert_eq!(
+                runtime.state.public_values_digester.absorbed,
+                expected.len()
+            );
+        }
+        assert_eq!(
+            PublicValuesHasher::Blake3.hash(&expected),
+            *blake3::hash(&expected).as_bytes()
+        );
+        assert_ne!(
+            PublicValuesHasher::Poseidon2.hash(&expected),
+            PublicValuesHasher::Poseidon2.hash(&expected[..15])
+        );
+    }
+
+    #[test]
+    fn test_streaming_matches_single_pass() {
+        let bytes = (0..100u8).collect::<Vec<_>>();
+        for len in [0, 1, 2, 3, 23, 24, 25, 48, 100] {
+            let mut digester = PublicValuesDigester::new(PublicValuesHasher::Poseidon2);
+            for chunk in bytes[..len].chunks(5) {
+                digester.update(chunk);
+            }
+            assert_eq!(
+                digester.digest(&bytes[..len]),
+                PublicValuesHasher::Poseidon2.hash(&bytes[..len]),
+                "len {}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn test_deserialized_digest_catches_up() {
+        let mut runtime = Runtime::new(commit_program());
+        runtime.set_public_values_hasher(PublicValuesHasher::Poseidon2);
+        runtime.run();
+
+        let state = ExecutionState::try_from_bytes(&runtime.state.to_bytes()).unwrap();
+        assert_eq!(
+            state.public_values_digester.hasher(),
+            PublicValuesHasher::Poseidon2
+        );
+        assert_eq!(state.public_values_digest(), runtime.public_values_digest());
+    }
+}
@@ -0,0 +1,573 @@
+//! Conditions over registers and memory that stop execution once they hold, see
+//! [`Runtime::add_condition`].
+//!
+//! Conditions can be parsed from a compact syntax, for tools that take them on the command line:
+//!
+//! ```text
+//! x10 == 0xdead && [0x1000] > 100
+//! (a0 != 0 || sp < 0x1000) && [0x2000] >= 7
+//! ```
+//!
+//! Registers are named `x0` to `x31` or by their ABI names, `[addr]` is the word at `addr`, and
+//! numbers are decimal or `0x` hexadecimal. Comparisons are unsigned, and `&&` binds tighter
+//! than `||`.
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+
+use super::{ExecutionError, Register, Runtime, ABI_NAMES};
+
+/// A value a [`Condition`] compares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Register(Register),
+
+    /// The word at an aligned address.
+    Word(u32),
+
+    Constant(u32),
+}
+
+/// An unsigned comparison of two operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn holds(self, left: u32, right: u32) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+}
+
+/// A predicate over the registers and memory of a runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare {
+        comparison: Comparison,
+        left: Operand,
+        right: Operand,
+    },
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    /// Parse a condition in the syntax described in the [module documentation](self).
+    pub fn parse(source: &str) -> Result<Self, ConditionParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            next: 0,
+            end: source.len(),
+        };
+        let condition = parser.or()?;
+        match parser.peek() {
+            None => Ok(condition),
+            Some((position, token)) => Err(ConditionParseError {
+                position,
+                message: format!("unexpected `{}`", token),
+            }),
+        }
+    }
+
+    /// Whether the condition holds for the current state of `runtime`. Only the operands needed
+    /// to decide it are read, without recording any memory access.
+    pub fn evaluate(&self, runtime: &Runtime) -> bool {
+        self.evaluate_with(&mut |operand| match operand {
+            Operand::Register(register) => runtime.register(register),
+            Operand::Word(addr) => runtime.word(addr),
+            Operand::Constant(value) => value,
+        })
+    }
+
+    fn evaluate_with(&self, read: &mut impl FnMut(Operand) -> u32) -> bool {
+        match self {
+            Condition::Compare {
+                comparison,
+                left,
+                right,
+            } => {
+                let left = read(*left);
+                comparison.holds(left, read(*right))
+            }
+            Condition::And(left, right) => left.evaluate_with(read) && right.evaluate_with(read),
+            Condition::Or(left, right) => left.evaluate_with(read) || right.evaluate_with(read),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+/// An error in the source of a [`Condition`], at the byte offset `position`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for ConditionParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "at position {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for ConditionParseError {}
+
+/// Why execution stopped before the program finished, without the guest faulting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// The condition at `index` of [`Runtime::add_condition`] held after the instruction at `pc`,
+    /// executed at global cycle `clk`.
+    ConditionMet { index: usize, clk: u32, pc: u32 },
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StopReason::ConditionMet { index, clk, pc } => write!(
+                f,
+                "condition {} met after cycle {} at pc=0x{:x}",
+                index, clk, pc
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Operand(Operand),
+    Comparison(Comparison),
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Token::Operand(Operand::Register(register)) => write!(f, "x{}", *register as u32),
+            Token::Operand(Operand::Word(addr)) => write!(f, "[0x{:x}]", addr),
+            Token::Operand(Operand::Constant(value)) => write!(f, "{}", value),
+            Token::Comparison(comparison) => {
+                let symbol = match comparison {
+                    Comparison::Eq => "==",
+                    Comparison::Ne => "!=",
+                    Comparison::Lt => "<",
+                    Comparison::Le => "<=",
+                    Comparison::Gt => ">",
+                    Comparison::Ge => ">=",
+                };
+                write!(f, "{}", symbol)
+            }
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+}
+
+/// Split `source` into tokens, each with the byte offset it starts at.
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ConditionParseError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while position < bytes.len() {
+        let start = position;
+        let rest = &source[start..];
+        let symbol = [
+            ("==", Token::Comparison(Comparison::Eq)),
+            ("!=", Token::Comparison(Comparison::Ne)),
+            ("<=", Token::Comparison(Comparison::Le)),
+            (">=", Token::Comparison(Comparison::Ge)),
+            ("<", Token::Comparison(Comparison::Lt)),
+            (">", Token::Comparison(Comparison::Gt)),
+            ("&&", Token::And),
+            ("||", Token::Or),
+            ("(", Token::Open),
+            (")", Token::Close),
+        ]
+        .into_iter()
+        .find(|(symbol, _)| rest.starts_with(symbol));
+        if let Some((symbol, token)) = symbol {
+            tokens.push((start, token));
+            position += symbol.len();
+            continue;
+        }
+
+        let c = bytes[start];
+        if c.is_ascii_whitespace() {
+            position += 1;
+        } else if c == b'[' {
+            let close = rest.find(']').ok_or_else(|| ConditionParseError {
+                position: start,
+                message: "unclosed `[`".to_string(),
+            })?;
+            let inner = &rest[1..close];
+            let offset = start + 1 + (inner.len() - inner.trim_start().len());
+            let addr = parse_number(inner.trim(), offset)?;
+            if addr % 4 != 0 {
+                return Err(ConditionParseError {
+                    position: offset,
+                    message: format!("unaligned word address 0x{:x}", addr),
+                });
+            }
+            tokens.push((start, Token::Operand(Operand::Word(addr))));
+            position += close + 1;
+        } else if c.is_ascii_alphanumeric() || c == b'_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            let operand = if c.is_ascii_digit() {
+                Operand::Constant(parse_number(word, start)?)
+            } else {
+                Operand::Register(parse_register(word).ok_or_else(|| ConditionParseError {
+                    position: start,
+                    message: format!("unknown register `{}`", word),
+                })?)
+            };
+            tokens.push((start, Token::Operand(operand)));
+            position += len;
+        } else {
+            let c = rest.chars().next().unwrap();
+            return Err(ConditionParseError {
+                position: start,
+                message: format!("unexpected character `{}`", c),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// A decimal or `0x` hexadecimal number that fits in 32 bits, starting at `position`.
+fn parse_number(word: &str, position: usize) -> Result<u32, ConditionParseError> {
+    let parsed = match word.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => word.parse(),
+    };
+    parsed.map_err(|_| ConditionParseError {
+        position,
+        message: format!("invalid number `{}`", word),
+    })
+}
+
+fn parse_register(name: &str) -> Option<Register> {
+    let index = match ABI_NAMES.iter().position(|&abi| abi == name) {
+        Some(index) => index as u32,
+        None if name == "fp" => 8,
+        None => name.strip_prefix('x')?.parse().ok()?,
+    };
+    Register::try_from_u32(index)
+}
+
+/// A recursive descent parser over the tokens of a condition.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+
+    /// The length of the source, where errors at its end are reported.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(usize, Token)> {
+        self.tokens.get(self.next).cloned()
+    }
+
+    fn or(&mut self) -> Result<Condition, ConditionParseError> {
+        let mut condition = self.and()?;
+        while let Some((_, Token::Or)) = self.peek() {
+            self.next += 1;
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, ConditionParseError> {
+        let mut condition = self.comparison()?;
+        while let Some((_, Token::And)) = self.peek() {
+            self.next += 1;
+            condition = Condition::And(Box::new(condition), Box::new(self.comparison()?));
+        }
+        Ok(condition)
+    }
+
+    fn comparison(&mut self) -> Result<Condition, ConditionParseError> {
+        if let Some((_, Token::Open)) = self.peek() {
+            self.next += 1;
+            let condition = self.or()?;
+            return match self.peek() {
+                Some((_, Token::Close)) => {
+                    self.next += 1;
+                    Ok(condition)
+                }
+                found => Err(self.expected("`)`", found)),
+            };
+        }
+        let left = self.operand()?;
+        let comparison = match self.peek() {
+            Some((_, Token::Comparison(comparison))) => comparison,
+            found => return Err(self.expected("a comparison", found)),
+        };
+        self.next += 1;
+        let right = self.operand()?;
+        Ok(Condition::Compare {
+            comparison,
+            left,
+            right,
+        })
+    }
+
+    fn operand(&mut self) -> Result<Operand, ConditionParseError> {
+        match self.peek() {
+            Some((_, Token::Operand(operand))) => {
+                self.next += 1;
+                Ok(operand)
+            }
+            found => Err(self.expected("a register, a word or a number", found)),
+        }
+    }
+
+    fn expected(&self, what: &str, found: Option<(usize, Token)>) -> ConditionParseError {
+        match found {
+            Some((position, token)) => ConditionParseError {
+                position,
+                message: format!("expected {}, found `{}`", what, token),
+            },
+            None => ConditionParseError {
+                position: self.end,
+                message: format!("expected {}, found the end", what),
+            },
+        }
+    }
+}
+
+impl Runtime {
+    /// Stop execution with [`StopReason::ConditionMet`] after the first instruction after which
+    /// `condition` holds, returning its index. Conditions are checked in the order they were
+    /// added, after every instruction while there is at least one.
+    ///
+    /// A condition that still holds stops execution again after the next instruction, so remove
+    /// it with [`Runtime::clear_conditions`] to continue past it.
+    pub fn add_condition(&mut self, condition: Condition) -> usize {
+        self.conditions.push(condition);
+        self.conditions.len() - 1
+    }
+
+    pub fn clear_conditions(&mut self) {
+        self.conditions.clear();
+    }
+
+    /// Return the first condition that holds after the instruction at `pc`, executed at cycle
+    /// `clk`, as an error.
+    pub(crate) fn check_conditions(&self, clk: u32, pc: u32) -> Result<(), ExecutionError> {
+        match self
+            .conditions
+            .iter()
+            .position(|condition| condition.evaluate(self))
+        {
+            Some(index) => Err(ExecutionError::Stopped(StopReason::ConditionMet {
+                index,
+                clk,
+                pc,
+            })),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::utils::asm::assemble;
+
+    fn compare(comparison: Comparison, left: Operand, right: Operand) -> Condition {
+        Condition::Compare {
+            comparison,
+            left,
+            right,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let condition = Condition::parse("x10 == 0xdead && [0x1000] > 100").unwrap();
+        assert_eq!(
+            condition,
+            Condition::And(
+                Box::new(compare(
+                    Comparison::Eq,
+                    Operand::Register(Register::X10),
+                    Operand::Constant(0xdead)
+                )),
+                Box::new(compare(
+                    Comparison::Gt,
+                    Operand::Word(0x1000),
+                    Operand::Constant(100)
+                )),
+            )
+        );
+
+        // `&&` binds tighter than `||`, unless overridden with parentheses.
+        let a = compare(
+            Comparison::Ne,
+            Operand::Register(Register::X10),
+            Operand::Constant(0),
+        );
+        let b = compare(
+            Comparison::Le,
+            Operand::Register(Register::X2),
+            Operand::Register(Register::X8),
+        );
+        let c = compare(Comparison::Ge, Operand::Constant(1), Operand::Word(0x2000));
+        let and =
+            |l: &Condition, r: &Condition| Condition::And(Box::new(l.clone()), Box::new(r.clone()));
+        let or =
+            |l: &Condition, r: &Condition| Condition::Or(Box::new(l.clone()), Box::new(r.clone()));
+        assert_eq!(
+            "a0 != 0 || sp <= fp && 1 >= [ 0x2000 ]".parse::<Condition>(),
+            Ok(or(&a, &and(&b, &c)))
+        );
+        assert_eq!(
+            Condition::parse("(a0!=0||x2<=s0)&&1>=[8192]"),
+            Ok(and(&or(&a, &b), &c))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |source: &str| {
+            let err = Condition::parse(source).unwrap_err();
+            (err.position, err.message)
+        };
+        assert_eq!(
+            error("x10 == 0xdead &&"),
+            (
+                16,
+                "expected a register, a word or a number, found the end".to_string()
+            )
+        );
+        assert_eq!(error("x32 == 1"), (0, "unknown register `x32`".to_string()));
+        assert_eq!(error("x1 = 1"), (3, "unexpected character `=`".to_string()));
+        assert_eq!(
+            error("[0x1002] == 1"),
+            (1, "unaligned word address 0x1002".to_string())
+        );
+        assert_eq!(error("[0x1000 == 1").0, 0);
+        assert_eq!(
+            error("x1 == 99999999999"),
+            (6, "invalid number `99999999999`".to_string())
+        );
+        assert_eq!(
+            error("(x1 == 1"),
+            (8, "expected `)`, found the end".to_string())
+        );
+        assert_eq!(error("x1 == 1 x2"), (8, "unexpected `x2`".to_string()));
+        assert_eq!(
+            error("x1 && x2"),
+            (3, "expected a comparison, found `&&`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let condition = Condition::parse("x1 == 1 && [4] == 2 || x2 == 3").unwrap();
+        let reads = |x1: u32| {
+            let mut reads = Vec::new();
+            let holds = condition.evaluate_with(&mut |operand| {
+                reads.push(operand);
+                match operand {
+                    Operand::Register(Register::X1) => x1,
+                    Operand::Word(_) => 2,
+                    Operand::Register(_) => 0,
+                    Operand::Constant(value) => value,
+                }
+            });
+            (holds, reads)
+        };
+        let (x1, x2, word) = (
+            Operand::Register(Register::X1),
+            Operand::Register(Register::X2),
+            Operand::Word(4),
+        );
+        // x2 is not read once the `&&` holds, and the word not once its left side fails.
+        assert_eq!(
+            reads(1),
+            (
+                true,
+                vec![x1, Operand::Constant(1), word, Operand::Constant(2)]
+            )
+        );
+        assert_eq!(
+            reads(0),
+            (
+                false,
+                vec![x1, Operand::Constant(1), x2, Operand::Constant(3)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_stop_at_condition() {
+        // Counts a0 up by one each iteration, storing it at 0x1000 every time.
+        let program = assemble(
+            "
+                    li   a0, 0
+                    li   t1, 100
+            loop:   addi a0, a0, 1
+                    sw   a0, 0x1000(zero)
+                    bne  a0, t1, loop
+            ",
+            0,
+        )
+        .unwrap();
+        let mut runtime = Runtime::new(program.clone());
+        runtime.add_condition(Condition::parse("x1 == 7").unwrap());
+        let index = runtime.add_condition(Condition::parse("a0 >= 10 && [0x1000] == 10").unwrap());
+
+        // a0 reaches 10 at the addi of the tenth iteration, but the word only after its sw.
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::Stopped(StopReason::ConditionMet {
+                index,
+                clk: 2 + 3 * 9 + 1,
+                pc: 12,
+            }))
+        );
+        assert_eq!(runtime.state.global_clk, 2 + 3 * 9 + 2);
+        assert_eq!(runtime.state.pc, 16);
+
+        // Evaluating the conditions leaves the record and the memory untouched.
+        let mut reference = Runtime::new(program);
+        reference.initialize();
+        for _ in 0..runtime.state.global_clk {
+            reference.step().unwrap();
+        }
+        assert_eq!(runtime.state.memory, reference.state.memory);
+        assert_eq!(runtime.record.digest(), reference.record.digest());
+
+        // Without conditions, execution continues to the end.
+        runtime.clear_conditions();
+        assert_eq!(runtime.run_steps(u64::MAX), Ok(true));
+        assert_eq!(runtime.word(0x1000), 100);
+    }
+}
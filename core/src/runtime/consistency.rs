@@ -0,0 +1,431 @@
+use core::fmt::{Display, Formatter};
+use std::collections::BTreeMap;
+
+use super::{ExecutionRecord, Program, MAX_REGISTER_ADDR, NUM_REGISTERS};
+use crate::cpu::{CpuEvent, MemoryRecordEnum};
+
+/// A violation of the invariants the memory argument relies on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryInconsistency {
+    /// An address was finalized but never initialized, neither in `first_memory_record` nor by a
+    /// used program memory entry.
+    Uninitialized { addr: u32 },
+
+    /// An address was initialized more than once.
+    InitializedMultipleTimes { addr: u32, count: usize },
+
+    /// An address was finalized although its program memory entry is marked as unused.
+    UnusedProgramMemoryFinalized { addr: u32 },
+
+    /// A program memory entry does not match the program's memory image.
+    ProgramMemoryMismatch { addr: u32 },
+
+    /// An address was initialized to a non-zero value outside of the program memory.
+    NonZeroInitialValue { addr: u32, value: u32 },
+
+    /// An address was last accessed after the final clock of the execution.
+    TimestampAfterEnd {
+        addr: u32,
+        shard: u32,
+        timestamp: u32,
+    },
+
+    /// An address in the range reserved for registers that is not a register.
+    InvalidRegisterAddress { addr: u32 },
+
+    /// A used program memory entry has no final record although the address was written, see
+    /// [`Runtime::set_sparse_memory_records`](super::Runtime::set_sparse_memory_records).
+    ElidedWrittenMemory { addr: u32 },
+}
+
+impl Display for MemoryInconsistency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MemoryInconsistency::Uninitialized { addr } => {
+                write!(f, "0x{:x} is finalized but never initialized", addr)
+            }
+            MemoryInconsistency::InitializedMultipleTimes { addr, count } => {
+                write!(f, "0x{:x} is initialized {} times", addr, count)
+            }
+            MemoryInconsistency::UnusedProgramMemoryFinalized { addr } => write!(
+                f,
+                "0x{:x} is finalized but its program memory is marked as unused",
+                addr
+            ),
+            MemoryInconsistency::ProgramMemoryMismatch { addr } => write!(
+                f,
+                "program memory at 0x{:x} does not match the memory image",
+                addr
+            ),
+            MemoryInconsistency::NonZeroInitialValue { addr, value } => {
+                write!(
+                    f,
+                    "0x{:x} is initialized to {} instead of zero",
+                    addr, value
+                )
+            }
+            MemoryInconsistency::TimestampAfterEnd {
+                addr,
+                shard,
+                timestamp,
+            } => write!(
+                f,
+                "0x{:x} is last accessed at shard {} timestamp {}, after the end of execution",
+                addr, shard, timestamp
+            ),
+            MemoryInconsistency::InvalidRegisterAddress { addr } => {
+                write!(
+                    f,
+                    "0x{:x} is reserved for registers but is not a register",
+                    addr
+                )
+            }
+            MemoryInconsistency::ElidedWrittenMemory { addr } => write!(
+                f,
+                "0x{:x} has no final record but was written during execution",
+                addr
+            ),
+        }
+    }
+}
+
+/// An access recorded in a CPU event that is out of order with the previous access to the same
+/// address.
+#[derive(Debug, Clone)]
+pub struct AccessOrderingViolation {
+    /// The accessed address.
+    pub addr: u32,
+
+    /// The (shard, timestamp) of the offending access.
+    pub access: (u32, u32),
+
+    /// The (shard, timestamp) the offending access claims the previous access happened at.
+    pub claimed_previous: (u32, u32),
+
+    /// The CPU event containing the offending access.
+    pub event: CpuEvent,
+
+    /// The (shard, timestamp) of the last access to the address in an earlier CPU event, and that
+    /// event.
+    pub previous: Option<((u32, u32), CpuEvent)>,
+}
+
+impl Display for AccessOrderingViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "access to 0x{:x} at {:?} (pc=0x{:x}) claims the previous access was at {:?}",
+            self.addr, self.access, self.event.pc, self.claimed_previous
+        )?;
+        match &self.previous {
+            Some((access, event)) => write!(
+                f,
+                ", but the last access was at {:?} (pc=0x{:x})",
+                access, event.pc
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The address of every memory access of a CPU event, in the order they happen.
+fn accesses(event: &CpuEvent) -> impl Iterator<Item = (u32, MemoryRecordEnum)> {
+    let memory_addr = event.b.wrapping_add(event.c) & !3;
+    [
+        (memory_addr, event.memory_record),
+        (event.instruction.op_c, event.c_record),
+        (event.instruction.op_b, event.b_record),
+        (event.instruction.op_a, event.a_record),
+    ]
+    .into_iter()
+    .filter_map(|(addr, record)| record.map(|record| (addr, record)))
+}
+
+/// The (shard, timestamp) of an access and of the access preceding it.
+fn timestamps(record: &MemoryRecordEnum) -> ((u32, u32), (u32, u32)) {
+    match record {
+        MemoryRecordEnum::Read(record) => (
+            (record.shard, record.timestamp),
+            (record.prev_shard, record.prev_timestamp),
+        ),
+        MemoryRecordEnum::Write(record) => (
+            (record.shard, record.timestamp),
+            (record.prev_shard, record.prev_timestamp),
+        ),
+    }
+}
+
+impl ExecutionRecord {
+    /// Checks that the global memory records of a finished execution of `program` are consistent
+    /// with each other, returning every violation found.
+    ///
+    /// Timestamps can only be checked when CPU events were recorded, since the final clock is
+    /// taken from the last CPU event.
+    pub fn check_memory_consistency(
+        &self,
+        program: &Program,
+    ) -> Result<(), Vec<MemoryInconsistency>> {
+        let mut inconsistencies = Vec::new();
+
+        // How often each address is initialized, and which program memory entries are unused.
+        let mut initialized = BTreeMap::<u32, usize>::new();
+        let mut unused_program_memory = BTreeMap::<u32, ()>::new();
+        for (addr, record, _) in self.first_memory_record.iter() {
+            *initialized.entry(*addr).or_default() += 1;
+            if record.value != 0 {
+                inconsistencies.push(MemoryInconsistency::NonZeroInitialValue {
+                    addr: *addr,
+                    value: record.value,
+                });
+            }
+        }
+        for (addr, record, used) in self.program_memory_record.iter() {
+            if program.memory_image.get(addr) != Some(&record.value) {
+                inconsistencies.push(MemoryInconsistency::ProgramMemoryMismatch { addr: *addr });
+            }
+            if *used == 0 {
+                unused_program_memory.insert(*addr, ());
+            } else {
+                *initialized.entry(*addr).or_default() += 1;
+            }
+        }
+
+        // The clock right after the last instruction, as (shard, clk).
+        let end = self
+            .cpu_events
+            .last()
+            .map(|event| (event.shard, event.clk + 4));
+
+        for (addr, record, _) in self.last_memory_record.iter() {
+            let addr = *addr;
+            match initialized.get(&addr).copied().unwrap_or(0) {
+                0 if unused_program_memory.contains_key(&addr) => {
+                    inconsistencies.push(MemoryInconsistency::UnusedProgramMemoryFinalized { addr })
+                }
+                0 => inconsistencies.push(MemoryInconsistency::Uninitialized { addr }),
+                1 => {}
+                count => inconsistencies
+                    .push(MemoryInconsistency::InitializedMultipleTimes { addr, count }),
+            }
+            if let Some(end) = end {
+                if (record.shard, record.timestamp) > end {
+                    inconsistencies.push(MemoryInconsistency::TimestampAfterEnd {
+                        addr,
+                        shard: record.shard,
+                        timestamp: record.timestamp,
+                    });
+                }
+            }
+            if (NUM_REGISTERS..=MAX_REGISTER_ADDR).contains(&addr) {
+                inconsistencies.push(MemoryInconsistency::InvalidRegisterAddress { addr });
+            }
+        }
+
+        if inconsistencies.is_empty() {
+            Ok(())
+        } else {
+            Err(inconsistencies)
+        }
+    }
+
+    /// Checks that the memory accesses recorded in the CPU events are strictly increasing in
+    /// (shard, timestamp) per address, and that each access points back to the last one,
+    /// returning the first violation.
+    ///
+    /// Accesses made by precompiles are not part of the CPU events, so an access may point to a
+    /// later access than the last one seen in the CPU events, but never to an earlier one.
+    pub fn check_access_ordering(&self) -> Result<(), Box<AccessOrderingViolation>> {
+        let mut last_access = BTreeMap::<u32, ((u32, u32), &CpuEvent)>::new();
+        for event in self.cpu_events.iter() {
+            for (addr, record) in accesses(event) {
+                let (access, claimed_previous) = timestamps(&record);
+                let previous = last_access.get(&addr).copied();
+                let out_of_order = claimed_previous >= access
+                    || previous.is_some_and(|(last, _)| claimed_previous < last);
+                if out_of_order {
+                    return Err(Box::new(AccessOrderingViolation {
+                        addr,
+                        access,
+                        claimed_previous,
+                        event: *event,
+                        previous: previous.map(|(last, event)| (last, *event)),
+                    }));
+                }
+                last_access.insert(addr, (access, event));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::cpu::MemoryRecord;
+    use crate::runtime::tests::{fibonacci_program, simple_program};
+    use crate::runtime::{Instruction, Opcode, Runtime, SyscallCode};
+
+    fn record(value: u32, shard: u32, timestamp: u32) -> MemoryRecord {
+        MemoryRecord {
+            value,
+            shard,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_consistent_records() {
+        for program in [simple_program(), fibonacci_program()] {
+            let mut runtime = Runtime::new(program);
+            runtime.run();
+            assert_eq!(
+                runtime.record.check_memory_consistency(&runtime.program),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn test_inconsistent_records() {
+        let mut program = simple_program();
+        program.memory_image.insert(0x1000, 5);
+        program.memory_image.insert(0x1004, 6);
+        let mut runtime = Runtime::new(program);
+        runtime.run();
+        let program = runtime.program.clone();
+        let valid = runtime.record;
+        assert_eq!(valid.check_memory_consistency(&program), Ok(()));
+
+        let check = |corrupt: &dyn Fn(&mut ExecutionRecord)| {
+            let mut record = valid.clone();
+            corrupt(&mut record);
+            record.check_memory_consistency(&program).unwrap_err()
+        };
+
+        assert_eq!(
+            check(&|r| r.last_memory_record.push((0x2000, record(1, 1, 1), 1))),
+            vec![MemoryInconsistency::Uninitialized { addr: 0x2000 }]
+        );
+        assert_eq!(
+            check(&|r| {
+                let first = r.first_memory_record[0];
+                r.first_memory_record.push(first);
+            }),
+            vec![MemoryInconsistency::InitializedMultipleTimes {
+                addr: valid.first_memory_record[0].0,
+                count: 2
+            }]
+        );
+        assert_eq!(
+            check(&|r| r.last_memory_record.push((0x1000, record(5, 1, 1), 1))),
+            vec![MemoryInconsistency::UnusedProgramMemoryFinalized { addr: 0x1000 }]
+        );
+        assert_eq!(
+            check(&|r| r.program_memory_record[1].1.value = 7),
+            vec![MemoryInconsistency::ProgramMemoryMismatch { addr: 0x1004 }]
+        );
+        assert_eq!(
+            check(&|r| r.first_memory_record[0].1.value = 3),
+            vec![MemoryInconsistency::NonZeroInitialValue {
+                addr: valid.first_memory_record[0].0,
+                value: 3
+            }]
+        );
+        assert_eq!(
+            check(&|r| r.last_memory_record[0].1.timestamp = 1000),
+            vec![MemoryInconsistency::TimestampAfterEnd {
+                addr: valid.last_memory_record[0].0,
+                shard: valid.last_memory_record[0].1.shard,
+                timestamp: 1000
+            }]
+        );
+        assert_eq!(
+            check(&|r| {
+                r.first_memory_record.push((36, record(0, 0, 0), 1));
+                r.last_memory_record.push((36, record(1, 1, 1), 1));
+            }),
+            vec![MemoryInconsistency::InvalidRegisterAddress { addr: 36 }]
+        );
+    }
+
+    /// Repeatedly increments the word at 0x1000.
+    fn increment_program(n: usize) -> Program {
+        let mut instructions = vec![Instruction::new(Opcode::ADD, 6, 0, 0x1000, false, true)];
+        for _ in 0..n {
+            instructions.extend([
+                Instruction::new(Opcode::LW, 5, 6, 0, false, true),
+                Instruction::new(Opcode::ADD, 5, 5, 1, false, true),
+                Instruction::new(Opcode::SW, 5, 6, 0, false, true),
+            ]);
+        }
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_access_ordering_across_shards() {
+        let mut runtime = Runtime::new(increment_program(10));
+        // Without syscalls, a shard of size 2 fits two instructions.
+        runtime.syscall_map.clear();
+        runtime.shard_size = 2;
+        runtime.run();
+        assert_eq!(runtime.word(0x1000), 10);
+        assert_eq!(runtime.record.cpu_events.last().unwrap().shard, 16);
+        runtime.record.check_access_ordering().unwrap();
+    }
+
+    #[test]
+    fn test_access_ordering_with_syscalls() {
+        let sha_extend = SyscallCode::SHA_EXTEND as u32;
+        let mut instructions = vec![];
+        for _ in 0..10 {
+            instructions.extend([
+                Instruction::new(Opcode::ADD, 10, 0, 0x1000, false, true),
+                Instruction::new(Opcode::ADD, 5, 0, sha_extend, false, true),
+                Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+                Instruction::new(Opcode::LW, 7, 0, 0x1040, false, true),
+                Instruction::new(Opcode::SW, 7, 0, 0x1000, false, true),
+            ]);
+        }
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime
+            .syscall_map
+            .retain(|code, _| *code == SyscallCode::SHA_EXTEND);
+        // The shard ends as soon as another sha extend would not fit, so every shard holds at
+        // most a few instructions around one syscall.
+        runtime.shard_size = 250;
+        runtime.run();
+        assert!(runtime.record.cpu_events.last().unwrap().shard > 5);
+        runtime.record.check_access_ordering().unwrap();
+    }
+
+    #[test]
+    fn test_access_ordering_violation() {
+        let mut runtime = Runtime::new(increment_program(2));
+        runtime.run();
+        let valid = runtime.record;
+        valid.check_access_ordering().unwrap();
+
+        // Point the second load of 0x1000 back before the first store to it.
+        let mut record = valid.clone();
+        let Some(MemoryRecordEnum::Read(ref mut read)) = record.cpu_events[4].memory_record else {
+            panic!("expected a load");
+        };
+        read.prev_timestamp = 0;
+        let violation = record.check_access_ordering().unwrap_err();
+        assert_eq!(violation.addr, 0x1000);
+        assert_eq!(violation.claimed_previous, (1, 0));
+        assert_eq!(violation.event.pc, 16);
+        assert_eq!(violation.previous.unwrap().1.pc, 12);
+
+        // Make an access happen before the access it points back to.
+        let mut record = valid;
+        let Some(MemoryRecordEnum::Write(ref mut write)) = record.cpu_events[3].memory_record
+        else {
+            panic!("expected a store");
+        };
+        write.timestamp = write.prev_timestamp;
+        let violation = record.check_access_ordering().unwrap_err();
+        assert_eq!(violation.addr, 0x1000);
+        assert_eq!(violation.event.pc, 12);
+    }
+}
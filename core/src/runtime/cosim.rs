@@ -0,0 +1,375 @@
+use core::fmt::{Display, Formatter};
+use std::collections::BTreeMap;
+
+use super::{ExecutionError, InputOrigin, Opcode, Register, Runtime, SyscallCode};
+
+/// One of the two guests of a [`CoSimulator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Guest {
+    A,
+    B,
+}
+
+impl Guest {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn peer(self) -> Guest {
+        match self {
+            Guest::A => Guest::B,
+            Guest::B => Guest::A,
+        }
+    }
+}
+
+/// A guest waiting for input that the other guest will never write, see
+/// [`CoSimError::Deadlock`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitingGuest {
+    pub guest: Guest,
+
+    /// The pc of the read.
+    pub pc: u32,
+
+    /// The output channels of the other guest routed to its input.
+    pub channels: Vec<u32>,
+}
+
+/// An error that stops a [`CoSimulator`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoSimError {
+    /// The execution of `guest` stopped with an error.
+    Execution { guest: Guest, error: ExecutionError },
+
+    /// No guest can make progress: each is waiting for input or has finished.
+    Deadlock { waiting: Vec<WaitingGuest> },
+}
+
+impl Display for CoSimError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CoSimError::Execution { guest, error } => {
+                write!(f, "guest {:?} failed: {}", guest, error)
+            }
+            CoSimError::Deadlock { waiting } => {
+                write!(f, "deadlock:")?;
+                for waiting in waiting {
+                    write!(
+                        f,
+                        " guest {:?} waits at pc=0x{:x} on channels {:?} of guest {:?};",
+                        waiting.guest,
+                        waiting.pc,
+                        waiting.channels,
+                        waiting.guest.peer()
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CoSimError {}
+
+/// Executes two guests that talk to each other through their input and output streams, with the
+/// bytes each writes to a routed output channel appended to the input of the other.
+///
+/// The guests run in turns of a fixed number of cycles, `A` first, and the routed output of a
+/// guest is forwarded at the end of its turn, so the interleaving only depends on the guests and
+/// the length of the turns. A guest reading input that was not written yet waits for its next
+/// turn instead of failing, see [`Runtime::set_input_blocking`].
+pub struct CoSimulator {
+    runtimes: [Runtime; 2],
+
+    /// The output channels of each guest routed to the input of the other.
+    routes: [Vec<u32>; 2],
+
+    /// The number of bytes of each routed channel of each guest forwarded so far.
+    forwarded: [BTreeMap<u32, usize>; 2],
+
+    /// The number of cycles of a turn.
+    turn_cycles: u64,
+
+    /// The pc each guest waits for input at, if it does.
+    waiting: [Option<u32>; 2],
+}
+
+impl CoSimulator {
+    /// Co-simulate `a` and `b` in turns of `turn_cycles` cycles, without any route between them.
+    ///
+    /// Panics if `turn_cycles` is zero.
+    pub fn new(mut a: Runtime, mut b: Runtime, turn_cycles: u64) -> Self {
+        assert!(turn_cycles > 0, "turns must have at least one cycle");
+        for runtime in [&mut a, &mut b] {
+            runtime.set_input_blocking(true);
+            runtime.initialize();
+        }
+        Self {
+            runtimes: [a, b],
+            routes: Default::default(),
+            forwarded: Default::default(),
+            turn_cycles,
+            waiting: [None; 2],
+        }
+    }
+
+    /// Append the bytes `from` writes to its output channel `channel` to the input of the other
+    /// guest.
+    pub fn with_route(mut self, from: Guest, channel: u32) -> Self {
+        self.routes[from.index()].push(channel);
+        self
+    }
+
+    pub fn runtime(&self, guest: Guest) -> &Runtime {
+        &self.runtimes[guest.index()]
+    }
+
+    pub fn into_runtimes(self) -> (Runtime, Runtime) {
+        let [a, b] = self.runtimes;
+        (a, b)
+    }
+
+    /// Execute both guests until they finish, returning an error if one fails or if neither can
+    /// make progress.
+    pub fn run(&mut self) -> Result<(), CoSimError> {
+        loop {
+            let mut progress = false;
+            for guest in [Guest::A, Guest::B] {
+                progress |= self.run_turn(guest)?;
+                self.forward(guest);
+            }
+            if self.runtimes.iter().all(Runtime::is_done) {
+                for runtime in self.runtimes.iter_mut() {
+                    runtime.finalize();
+                }
+                return Ok(());
+            }
+            if !progress {
+                return Err(CoSimError::Deadlock {
+                    waiting: self.waiting_guests(),
+                });
+            }
+        }
+    }
+
+    /// Execute a turn of `guest`, returning whether it executed any instruction.
+    fn run_turn(&mut self, guest: Guest) -> Result<bool, CoSimError> {
+        let runtime = &mut self.runtimes[guest.index()];
+        let waiting = &mut self.waiting[guest.index()];
+        *waiting = None;
+        let mut progress = false;
+        for _ in 0..self.turn_cycles {
+            if runtime.is_done() {
+                break;
+            }
+            match runtime.step() {
+                Ok(()) => progress = true,
+                Err(ExecutionError::InputWouldBlock { pc, .. }) => {
+                    *waiting = Some(pc);
+                    break;
+                }
+                Err(error) => return Err(CoSimError::Execution { guest, error }),
+            }
+        }
+        Ok(progress)
+    }
+
+    /// Append the bytes `from` wrote to its routed channels since its last turn to the input of
+    /// the other guest. Bytes written in an unconstrained block may still be rolled back, so they
+    /// are only forwarded once it exits.
+    fn forward(&mut self, from: Guest) {
+        let [a, b] = &mut self.runtimes;
+        let (source, sink) = match from {
+            Guest::A => (a, b),
+            Guest::B => (b, a),
+        };
+        if source.unconstrained {
+            return;
+        }
+        for &channel in self.routes[from.index()].iter() {
+            let forwarded = self.forwarded[from.index()].entry(channel).or_default();
+            let bytes = &source.output_channel(channel)[*forwarded..];
+            if !bytes.is_empty() {
+                sink.append_input(InputOrigin::Host, bytes);
+                *forwarded += bytes.len();
+            }
+        }
+    }
+
+    fn waiting_guests(&self) -> Vec<WaitingGuest> {
+        [Guest::A, Guest::B]
+            .into_iter()
+            .filter_map(|guest| {
+                let pc = self.waiting[guest.index()]?;
+                Some(WaitingGuest {
+                    guest,
+                    pc,
+                    channels: self.routes[guest.peer().index()].clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Runtime {
+    /// Stop reads of more input than was written with [`ExecutionError::InputWouldBlock`] before
+    /// they execute, instead of failing them, so that [`Runtime::step`] can be called again once
+    /// more input is written. Reads are never blocked while a stdin reader is set.
+    pub fn set_input_blocking(&mut self, enabled: bool) {
+        self.input_blocking = enabled;
+    }
+
+    /// Returns an error if the next instruction reads more input than is available.
+    pub(crate) fn check_input_available(&self) -> Result<(), ExecutionError> {
+        let instruction = self.fetch();
+        if instruction.opcode != Opcode::ECALL
+            || self.register(Register::X5) != SyscallCode::LWA as u32
+            || self.stdin_reader.is_some()
+        {
+            return Ok(());
+        }
+        // The syscall reads at most a word.
+        let len = self.register(Register::X11).min(4);
+        let available = self.state.input_stream.len() - self.state.input_stream_ptr;
+        if (len as usize) <= available {
+            return Ok(());
+        }
+        Err(ExecutionError::InputWouldBlock {
+            fd: self.register(Register::X10),
+            len,
+            pc: self.state.pc,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::Program;
+    use crate::utils::asm::assemble;
+
+    /// Sends the numbers from 0 to 9 on channel 1, each time reading a reply that must be the
+    /// number plus 100.
+    fn ping_program() -> Program {
+        assemble(
+            "       li   s0, 0
+                    li   s1, 10
+                    li   s2, 0x1000
+             loop:  sw   s0, 0(s2)
+                    li   t0, 113
+                    li   a0, 1
+                    mv   a1, s2
+                    li   a2, 4
+                    ecall
+                    li   t0, 101
+                    li   a0, 0
+                    li   a1, 4
+                    ecall
+                    addi t1, s0, 100
+                    bne  a0, t1, fail
+                    addi s0, s0, 1
+                    bne  s0, s1, loop
+                    j    end
+             fail:  unimp
+             end:   nop",
+            0,
+        )
+        .unwrap()
+    }
+
+    /// Reads ten numbers, replying to each with the number plus 100 on channel 2.
+    fn pong_program() -> Program {
+        assemble(
+            "       li   s0, 0
+                    li   s1, 10
+                    li   s2, 0x1000
+             loop:  li   t0, 101
+                    li   a0, 0
+                    li   a1, 4
+                    ecall
+                    addi a0, a0, 100
+                    sw   a0, 0(s2)
+                    li   t0, 113
+                    li   a0, 2
+                    mv   a1, s2
+                    li   a2, 4
+                    ecall
+                    addi s0, s0, 1
+                    bne  s0, s1, loop",
+            0,
+        )
+        .unwrap()
+    }
+
+    fn words(words: impl Iterator<Item = u32>) -> Vec<u8> {
+        words.flat_map(u32::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn test_ping_pong() {
+        let mut clocks = Vec::new();
+        for turn_cycles in [1, 7, 1000] {
+            let mut cosim = CoSimulator::new(
+                Runtime::new(ping_program()),
+                Runtime::new(pong_program()),
+                turn_cycles,
+            )
+            .with_route(Guest::A, 1)
+            .with_route(Guest::B, 2);
+            cosim.run().unwrap();
+
+            let (a, b) = cosim.into_runtimes();
+            assert!(a.is_done() && b.is_done());
+            assert_eq!(a.output_channel(1), words(0..10));
+            assert_eq!(b.output_channel(2), words(100..110));
+            assert_eq!(b.state.input_stream, a.output_channel(1));
+            assert_eq!(a.state.input_stream, b.output_channel(2));
+            assert_eq!(a.state.input_stream_ptr, a.state.input_stream.len());
+            assert_eq!(b.state.input_stream_ptr, b.state.input_stream.len());
+            a.record.check_access_ordering().unwrap();
+            clocks.push((a.state.global_clk, b.state.global_clk));
+        }
+        // The length of the turns changes the interleaving, not the executions.
+        assert!(clocks.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+
+    #[test]
+    fn test_deadlock() {
+        // Both guests start by reading.
+        let mut cosim = CoSimulator::new(
+            Runtime::new(pong_program()),
+            Runtime::new(pong_program()),
+            100,
+        )
+        .with_route(Guest::A, 2)
+        .with_route(Guest::B, 2);
+        let err = cosim.run().unwrap_err();
+        assert_eq!(
+            err,
+            CoSimError::Deadlock {
+                waiting: vec![
+                    WaitingGuest {
+                        guest: Guest::A,
+                        pc: 24,
+                        channels: vec![2],
+                    },
+                    WaitingGuest {
+                        guest: Guest::B,
+                        pc: 24,
+                        channels: vec![2],
+                    },
+                ]
+            }
+        );
+        assert!(err.to_string().contains("guest A waits at pc=0x18"));
+        assert_eq!(cosim.runtime(Guest::A).state.pc, 24);
+
+        // Outside of a co-simulation, the read fails.
+        let mut runtime = Runtime::new(pong_program());
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::InputExhausted { pc: 24 })
+        );
+    }
+}
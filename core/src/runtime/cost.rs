@@ -0,0 +1,371 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::{Instruction, Opcode, Runtime, SyscallCode, BYTE_LOOKUP_CLASS};
+
+/// The height of the byte lookup table, which every shard with a byte lookup has to commit to.
+const BYTE_TABLE_HEIGHT: u64 = 1 << 16;
+
+/// The rows an instruction or a syscall adds to each table, keyed by the event class names of
+/// [`ShardStats::event_counts`](super::ShardStats::event_counts). Byte lookups are keyed by
+/// [`BYTE_LOOKUP_CLASS`] and counted with multiplicity.
+pub type RowCost = Vec<(&'static str, u64)>;
+
+/// Row weights used to estimate the size of the traces a program expands to when proven.
+///
+/// The defaults mirror the events the runtime emits and the dependencies the CPU and ALU chips add
+/// when generating their traces. They are exact for the CPU, ALU, field and byte lookup tables of
+/// programs without precompiles, and approximate for precompiles.
+#[derive(Debug, Clone)]
+pub struct ProverCostModel {
+    /// The rows added by each executed instruction, including its CPU row.
+    pub opcodes: HashMap<Opcode, RowCost>,
+
+    /// The rows added by each syscall, on top of the rows of the `ecall` instruction.
+    pub syscalls: HashMap<SyscallCode, RowCost>,
+
+    /// The rows added when a branch is taken.
+    pub branch_taken: RowCost,
+
+    /// The rows added when `lb` or `lh` loads a negative value, which is sign extended.
+    pub negative_signed_load: RowCost,
+
+    /// The rows added by each register or memory access of an instruction.
+    pub memory_access: RowCost,
+}
+
+impl Default for ProverCostModel {
+    fn default() -> Self {
+        let memory_op = vec![("cpu", 1), ("add", 1), (BYTE_LOOKUP_CLASS, 2)];
+        let branch = vec![("cpu", 1), ("lt", 2)];
+        let jump = vec![("cpu", 1), ("add", 1)];
+        let opcodes = [
+            (Opcode::ADD, vec![("cpu", 1), ("add", 1)]),
+            (Opcode::SUB, vec![("cpu", 1), ("sub", 1)]),
+            (
+                Opcode::XOR,
+                vec![("cpu", 1), ("bitwise", 1), (BYTE_LOOKUP_CLASS, 4)],
+            ),
+            (
+                Opcode::OR,
+                vec![("cpu", 1), ("bitwise", 1), (BYTE_LOOKUP_CLASS, 4)],
+            ),
+            (
+                Opcode::AND,
+                vec![("cpu", 1), ("bitwise", 1), (BYTE_LOOKUP_CLASS, 4)],
+            ),
+            (Opcode::SLL, vec![("cpu", 1), ("shift_left", 1)]),
+            (
+                Opcode::SRL,
+                vec![("cpu", 1), ("shift_right", 1), (BYTE_LOOKUP_CLASS, 9)],
+            ),
+            (
+                Opcode::SRA,
+                vec![("cpu", 1), ("shift_right", 1), (BYTE_LOOKUP_CLASS, 9)],
+            ),
+            (Opcode::SLT, vec![("cpu", 1), ("lt", 1)]),
+            (Opcode::SLTU, vec![("cpu", 1), ("lt", 1)]),
+            (Opcode::LB, memory_op.clone()),
+            (Opcode::LH, memory_op.clone()),
+            (Opcode::LW, memory_op.clone()),
+            (Opcode::LBU, memory_op.clone()),
+            (Opcode::LHU, memory_op.clone()),
+            (Opcode::SB, memory_op.clone()),
+            (Opcode::SH, memory_op.clone()),
+            (Opcode::SW, memory_op),
+            (Opcode::BEQ, branch.clone()),
+            (Opcode::BNE, branch.clone()),
+            (Opcode::BLT, branch.clone()),
+            (Opcode::BGE, branch.clone()),
+            (Opcode::BLTU, branch.clone()),
+            (Opcode::BGEU, branch),
+            (Opcode::JAL, jump.clone()),
+            (Opcode::JALR, jump.clone()),
+            (Opcode::AUIPC, jump),
+            (
+                Opcode::MUL,
+                vec![("cpu", 1), ("mul", 1), (BYTE_LOOKUP_CLASS, 2)],
+            ),
+            (
+                Opcode::MULH,
+                vec![("cpu", 1), ("mul", 1), (BYTE_LOOKUP_CLASS, 2)],
+            ),
+            (
+                Opcode::MULHU,
+                vec![("cpu", 1), ("mul", 1), (BYTE_LOOKUP_CLASS, 2)],
+            ),
+            (
+                Opcode::MULHSU,
+                vec![("cpu", 1), ("mul", 1), (BYTE_LOOKUP_CLASS, 2)],
+            ),
+            (
+                Opcode::DIV,
+                vec![("cpu", 1), ("divrem", 1), (BYTE_LOOKUP_CLASS, 3)],
+            ),
+            (
+                Opcode::DIVU,
+                vec![("cpu", 1), ("divrem", 1), (BYTE_LOOKUP_CLASS, 3)],
+            ),
+            (
+                Opcode::REM,
+                vec![("cpu", 1), ("divrem", 1), (BYTE_LOOKUP_CLASS, 3)],
+            ),
+            (
+                Opcode::REMU,
+                vec![("cpu", 1), ("divrem", 1), (BYTE_LOOKUP_CLASS, 3)],
+            ),
+            (Opcode::ECALL, vec![("cpu", 1)]),
+        ];
+        let syscalls = [
+            (
+                SyscallCode::SHA_EXTEND,
+                vec![("sha_extend", 48), ("field", 240)],
+            ),
+            (
+                SyscallCode::SHA_COMPRESS,
+                vec![("sha_compress", 80), ("field", 80)],
+            ),
+            (
+                SyscallCode::KECCAK_PERMUTE,
+                vec![("keccak_permute", 24), ("field", 100)],
+            ),
+            (SyscallCode::ED_ADD, vec![("ed_add", 1), ("field", 32)]),
+            (
+                SyscallCode::ED_DECOMPRESS,
+                vec![("ed_decompress", 1), ("field", 16)],
+            ),
+            (
+                SyscallCode::SECP256K1_ADD,
+                vec![("weierstrass_add", 1), ("field", 32)],
+            ),
+            (
+                SyscallCode::SECP256K1_DOUBLE,
+                vec![("weierstrass_double", 1), ("field", 16)],
+            ),
+            (
+                SyscallCode::SECP256K1_DECOMPRESS,
+                vec![("k256_decompress", 1), ("field", 16)],
+            ),
+            (
+                SyscallCode::BLAKE3_COMPRESS_INNER,
+                vec![("blake3_compress_inner", 56), ("field", 56)],
+            ),
+            (SyscallCode::LOAD64, vec![("mem64", 1)]),
+            (SyscallCode::STORE64, vec![("mem64", 1)]),
+        ];
+        Self {
+            opcodes: opcodes.into_iter().collect(),
+            syscalls: syscalls.into_iter().collect(),
+            branch_taken: vec![("add", 1)],
+            negative_signed_load: vec![("sub", 1)],
+            memory_access: vec![("field", 1)],
+        }
+    }
+}
+
+/// The estimated rows of one shard.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShardCostEstimate {
+    pub shard: u32,
+
+    /// The estimated number of rows of each table.
+    pub rows: BTreeMap<&'static str, u64>,
+
+    /// The height each table gets padded to, i.e. the next power of two.
+    pub padded_rows: BTreeMap<&'static str, u64>,
+}
+
+/// The estimated size of the traces of a program, see [`Runtime::estimated_prover_cost`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProverCostEstimate {
+    pub shards: Vec<ShardCostEstimate>,
+
+    /// The estimated number of rows of each table, summed over all shards.
+    pub rows: BTreeMap<&'static str, u64>,
+
+    /// The padded height of each table, summed over all shards.
+    pub padded_rows: BTreeMap<&'static str, u64>,
+
+    /// A single scalar estimate: the total padded height of all tables. The byte lookup table
+    /// has a fixed height, so it counts for that height in every shard that uses it.
+    pub cost: u64,
+}
+
+/// Accumulates the estimated rows of every shard during execution.
+#[derive(Debug, Clone)]
+pub(crate) struct CostEstimator {
+    pub(crate) model: ProverCostModel,
+    shards: BTreeMap<u32, BTreeMap<&'static str, u64>>,
+}
+
+impl CostEstimator {
+    pub(crate) fn new(model: ProverCostModel) -> Self {
+        Self {
+            model,
+            shards: BTreeMap::new(),
+        }
+    }
+}
+
+/// Add `times` the cost to the rows of a shard.
+fn add_rows(rows: &mut BTreeMap<&'static str, u64>, cost: &[(&'static str, u64)], times: u64) {
+    for (table, count) in cost {
+        *rows.entry(*table).or_default() += count * times;
+    }
+}
+
+impl Runtime {
+    /// Estimate the rows of every prover table from now on using `model`.
+    pub fn set_prover_cost_model(&mut self, model: ProverCostModel) {
+        self.cost_estimator = Some(CostEstimator::new(model));
+    }
+
+    /// Account for an instruction that just executed at `pc`, using the accesses recorded in the
+    /// current CPU record. `syscall` is the code in t0 when an `ecall` executed, as the syscall
+    /// may have overwritten t0 since.
+    pub(crate) fn estimate_cost(
+        &mut self,
+        instruction: Instruction,
+        pc: u32,
+        next_pc: u32,
+        a: u32,
+        syscall: Option<SyscallCode>,
+    ) {
+        let shard = self.current_shard();
+        let accesses = self.cpu_record.len() as u64;
+        let branch_taken = instruction.is_branch_instruction() && next_pc != pc.wrapping_add(4);
+        let negative_signed_load =
+            matches!(instruction.opcode, Opcode::LB | Opcode::LH) && (a as i32) < 0;
+
+        let Some(CostEstimator { model, shards }) = &mut self.cost_estimator else {
+            return;
+        };
+        let rows = shards.entry(shard).or_default();
+        if let Some(cost) = model.opcodes.get(&instruction.opcode) {
+            add_rows(rows, cost, 1);
+        }
+        if let Some(cost) = syscall.and_then(|syscall| model.syscalls.get(&syscall)) {
+            add_rows(rows, cost, 1);
+        }
+        if branch_taken {
+            add_rows(rows, &model.branch_taken, 1);
+        }
+        if negative_signed_load {
+            add_rows(rows, &model.negative_signed_load, 1);
+        }
+        add_rows(rows, &model.memory_access, accesses);
+    }
+
+    /// The estimated rows of every prover table, or `None` if no cost model was set with
+    /// [`Runtime::set_prover_cost_model`].
+    pub fn estimated_prover_cost(&self) -> Option<ProverCostEstimate> {
+        let estimator = self.cost_estimator.as_ref()?;
+        let mut estimate = ProverCostEstimate::default();
+        for (&shard, rows) in estimator.shards.iter() {
+            let mut shard_estimate = ShardCostEstimate {
+                shard,
+                rows: rows.clone(),
+                padded_rows: BTreeMap::new(),
+            };
+            for (&table, &count) in rows.iter() {
+                let padded = if table == BYTE_LOOKUP_CLASS {
+                    BYTE_TABLE_HEIGHT
+                } else {
+                    count.next_power_of_two()
+                };
+                shard_estimate.padded_rows.insert(table, padded);
+                *estimate.rows.entry(table).or_default() += count;
+                *estimate.padded_rows.entry(table).or_default() += padded;
+                estimate.cost += padded;
+            }
+            estimate.shards.push(shard_estimate);
+        }
+        Some(estimate)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use p3_baby_bear::BabyBear;
+
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::air::MachineAir;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::{ExecutionRecord, Program, Register, Syscall, SyscallContext};
+    use crate::stark::RiscvAir;
+
+    /// The relative error allowed between the estimated and the actual number of rows.
+    const TOLERANCE: f64 = 0.05;
+
+    #[test]
+    fn test_fibonacci_cost_estimate() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.set_prover_cost_model(ProverCostModel::default());
+        runtime.run();
+        let estimate = runtime.estimated_prover_cost().unwrap();
+
+        // Generate the events the chips add as dependencies, like the machine does before
+        // sharding.
+        let mut record = std::mem::take(&mut runtime.record);
+        for air in RiscvAir::<BabyBear>::get_all() {
+            let mut output = ExecutionRecord {
+                index: record.index,
+                ..Default::default()
+            };
+            air.generate_dependencies(&record, &mut output);
+            record.append(&mut output);
+        }
+        let mut actual = record.stats().event_counts();
+        actual.push((BYTE_LOOKUP_CLASS, record.nb_byte_lookups()));
+
+        for (table, count) in actual {
+            let estimated = estimate.rows.get(table).copied().unwrap_or(0);
+            let error = (estimated as f64 - count as f64).abs();
+            assert!(
+                error <= TOLERANCE * count as f64,
+                "estimated {} rows for {} but found {}",
+                estimated,
+                table,
+                count
+            );
+        }
+        assert_eq!(
+            estimate.rows["cpu"], runtime.state.global_clk as u64,
+            "every cycle is a CPU row"
+        );
+        let padded = estimate.shards[0].padded_rows["cpu"];
+        assert!(padded.is_power_of_two() && padded >= estimate.shards[0].rows["cpu"]);
+        assert_eq!(estimate.cost, estimate.padded_rows.values().sum::<u64>());
+    }
+
+    /// A syscall that overwrites t0.
+    struct ClobberT0Syscall;
+
+    impl Syscall for ClobberT0Syscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            let (shard, clk) = (ctx.current_shard(), ctx.clk());
+            ctx.rt.mw(Register::X5 as u32, 0, shard, clk);
+            0
+        }
+    }
+
+    #[test]
+    fn test_syscall_cost_after_clobbered_t0() {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, SyscallCode::LOAD64 as u32, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime
+            .syscall_map
+            .insert(SyscallCode::LOAD64, Arc::new(ClobberT0Syscall));
+        runtime.set_prover_cost_model(ProverCostModel::default());
+        runtime.run();
+
+        // The cost is the one of the syscall the `ecall` was made with.
+        assert_eq!(runtime.register(Register::X5), 0);
+        let estimate = runtime.estimated_prover_cost().unwrap();
+        assert_eq!(estimate.rows["mem64"], 1);
+    }
+}
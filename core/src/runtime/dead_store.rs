@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use nohash_hasher::BuildNoHashHasher;
+
+use super::Runtime;
+
+/// The stores of one instruction whose values were never loaded, see
+/// [`Runtime::dead_store_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadStore {
+    /// The pc of the store, or of the `ecall` of a syscall writing memory.
+    pub pc: u32,
+
+    /// The function containing `pc`, if the program has a symbol for it.
+    pub function: Option<String>,
+
+    /// The number of values it stored that were overwritten before being read.
+    pub overwritten: u64,
+
+    /// The number of words whose final value it stored and that were not read afterwards.
+    pub dead_at_end: u64,
+}
+
+impl DeadStore {
+    /// The number of values it stored that were never read.
+    pub fn count(&self) -> u64 {
+        self.overwritten + self.dead_at_end
+    }
+}
+
+/// Tracks whether the last value written to each word of memory was read.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DeadStoreTracker {
+    /// The pc of the last write to each word, and whether the word was read since.
+    last_writes: HashMap<u32, (u32, bool), BuildNoHashHasher<u32>>,
+
+    /// The number of values stored by each pc that were overwritten without being read.
+    overwritten: HashMap<u32, u64, BuildNoHashHasher<u32>>,
+}
+
+impl DeadStoreTracker {
+    #[inline]
+    pub(crate) fn record_read(&mut self, addr: u32) {
+        if let Some((_, read)) = self.last_writes.get_mut(&addr) {
+            *read = true;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_write(&mut self, addr: u32, pc: u32) {
+        if let Some((prev_pc, false)) = self.last_writes.insert(addr, (pc, false)) {
+            *self.overwritten.entry(prev_pc).or_default() += 1;
+        }
+    }
+}
+
+impl Runtime {
+    /// Track, from now on, which values the guest stores to memory without ever loading them
+    /// back, see [`Runtime::dead_store_report`]. Accesses in unconstrained blocks are ignored.
+    ///
+    /// Stores are tracked per word: `sb` and `sh` overwrite the whole word they store to. The
+    /// bytes of the word they keep are not read back, as `store_rr` peeks at the word instead of
+    /// reading it.
+    pub fn enable_dead_store_tracking(&mut self) {
+        if self.dead_stores.is_none() {
+            self.dead_stores = Some(DeadStoreTracker::default());
+        }
+    }
+
+    /// The instructions that stored values which were overwritten before being read, or still
+    /// unread at this point of the execution, sorted by decreasing count. Returns `None` if
+    /// [`Runtime::enable_dead_store_tracking`] was not called.
+    pub fn dead_store_report(&self) -> Option<Vec<DeadStore>> {
+        let tracker = self.dead_stores.as_ref()?;
+        let mut counts = HashMap::<u32, (u64, u64)>::new();
+        for (&pc, &count) in tracker.overwritten.iter() {
+            counts.entry(pc).or_default().0 += count;
+        }
+        for &(pc, read) in tracker.last_writes.values() {
+            if !read {
+                counts.entry(pc).or_default().1 += 1;
+            }
+        }
+        let mut stores = counts
+            .into_iter()
+            .map(|(pc, (overwritten, dead_at_end))| DeadStore {
+                pc,
+                function: self
+                    .program
+                    .symbols
+                    .lookup(pc)
+                    .map(|symbol| symbol.name.clone()),
+                overwritten,
+                dead_at_end,
+            })
+            .collect::<Vec<_>>();
+        stores.sort_by_key(|store| (std::cmp::Reverse(store.count()), store.pc));
+        Some(stores)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Instruction, Opcode, Program, Symbol, SymbolTable};
+
+    /// Store to 0x1000 twice, optionally loading the word in between, and load it at the end.
+    fn store_program(store: Opcode, read_between: bool) -> Program {
+        let mut instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, 0x1000, false, true),
+            Instruction::new(Opcode::ADD, 6, 0, 1, false, true),
+            Instruction::new(Opcode::SW, 6, 5, 0, false, true),
+        ];
+        if read_between {
+            instructions.push(Instruction::new(Opcode::LW, 7, 5, 0, false, true));
+        }
+        instructions.extend([
+            Instruction::new(store, 6, 5, 0, false, true),
+            Instruction::new(Opcode::LW, 7, 5, 0, false, true),
+        ]);
+        let mut program = Program::new(instructions, 0, 0);
+        program.symbols = SymbolTable::new(vec![Symbol::new("main", 0, 24)]);
+        program
+    }
+
+    fn report(program: Program) -> Vec<DeadStore> {
+        let mut runtime = Runtime::new(program);
+        runtime.enable_dead_store_tracking();
+        runtime.run();
+        runtime.dead_store_report().unwrap()
+    }
+
+    #[test]
+    fn test_overwritten_store() {
+        assert_eq!(
+            report(store_program(Opcode::SW, false)),
+            vec![DeadStore {
+                pc: 8,
+                function: Some("main".to_string()),
+                overwritten: 1,
+                dead_at_end: 0,
+            }]
+        );
+        assert!(report(store_program(Opcode::SW, true)).is_empty());
+
+        // The word `sb` merges its byte into does not count as read.
+        let stores = report(store_program(Opcode::SB, false));
+        assert_eq!(stores.len(), 1);
+        assert_eq!((stores[0].pc, stores[0].overwritten), (8, 1));
+    }
+
+    #[test]
+    fn test_dead_at_end() {
+        let mut program = store_program(Opcode::SW, true);
+        program.instructions.pop();
+        let stores = report(program);
+        assert_eq!(stores.len(), 1);
+        assert_eq!((stores[0].pc, stores[0].dead_at_end), (16, 1));
+        assert_eq!(stores[0].count(), 1);
+        assert!(Runtime::new(store_program(Opcode::SW, false))
+            .dead_store_report()
+            .is_none());
+    }
+}
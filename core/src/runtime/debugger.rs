@@ -0,0 +1,390 @@
+//! An interactive debugger over [`Runtime::step`], driven by text commands so that sessions can be
+//! scripted. The `sp1-dbg` binary reads the commands from stdin.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::{Program, Runtime};
+
+/// The commands of a [`DebuggerSession`], one per line.
+pub const DEBUGGER_HELP: &str = "\
+b <addr|symbol>   set a breakpoint
+s [n]             step n instructions, 1 by default
+c                 continue until a breakpoint, a watchpoint or the end
+regs              print the registers
+x <addr> <len>    print len words of memory from addr
+bt                print the backtrace
+watch <addr>      stop when the word at addr changes
+sym <pc>          print the function containing pc
+help              print this help
+q                 quit";
+
+/// Why a [`DebuggerSession`] stopped executing.
+enum Stop {
+    Breakpoint,
+    Watchpoint { addr: u32, old: u32, new: u32 },
+    Finished,
+    Error(String),
+}
+
+/// A debugging session of a program: its runtime, breakpoints and watchpoints. Each command given
+/// to [`DebuggerSession::execute`] returns the text printed for it.
+pub struct DebuggerSession {
+    runtime: Runtime,
+    breakpoints: BTreeSet<u32>,
+
+    /// The watched words and their values when last checked.
+    watchpoints: BTreeMap<u32, u32>,
+
+    /// Why execution ended, once it did. Commands that execute instructions then do nothing.
+    ended: Option<String>,
+
+    quit: bool,
+}
+
+impl DebuggerSession {
+    /// Start a session stopped before the first instruction of `program`.
+    pub fn new(program: Program) -> Self {
+        let mut runtime = Runtime::new(program);
+        runtime.initialize();
+        Self {
+            runtime,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            ended: None,
+            quit: false,
+        }
+    }
+
+    pub fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+
+    /// Whether the `q` command was given.
+    pub fn has_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Run a command and return its output, without a trailing newline. Invalid commands print
+    /// an error rather than failing.
+    pub fn execute(&mut self, command: &str) -> String {
+        self.dispatch(command)
+            .unwrap_or_else(|error| format!("error: {}", error))
+    }
+
+    /// Run every line of `script` as a command until `q`, and return the transcript: each
+    /// command prefixed with `> `, followed by its output.
+    pub fn run_script(&mut self, script: &str) -> String {
+        let mut transcript = String::new();
+        for command in script
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            writeln!(transcript, "> {}", command).unwrap();
+            let output = self.execute(command);
+            if !output.is_empty() {
+                writeln!(transcript, "{}", output).unwrap();
+            }
+            if self.quit {
+                break;
+            }
+        }
+        transcript
+    }
+
+    fn dispatch(&mut self, command: &str) -> Result<String, String> {
+        let mut words = command.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(String::new());
+        };
+        let args = words.collect::<Vec<_>>();
+        let arity = |min: usize, max: usize| {
+            if (min..=max).contains(&args.len()) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "wrong number of arguments for `{}`, see `help`",
+                    name
+                ))
+            }
+        };
+        match name {
+            "b" | "break" => {
+                arity(1, 1)?;
+                let addr = self.resolve(args[0])?;
+                self.breakpoints.insert(addr);
+                Ok(format!("breakpoint at {}", self.location(addr)))
+            }
+            "s" | "step" => {
+                arity(0, 1)?;
+                let steps = match args.first() {
+                    Some(n) => n
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid count `{}`", n))?,
+                    None => 1,
+                };
+                Ok(self.resume(Some(steps)))
+            }
+            "c" | "continue" => {
+                arity(0, 0)?;
+                Ok(self.resume(None))
+            }
+            "regs" => {
+                arity(0, 0)?;
+                Ok(format!(
+                    "pc=0x{:08x} {}",
+                    self.runtime.state.pc,
+                    self.runtime.state.dump_registers()
+                ))
+            }
+            "x" => {
+                arity(2, 2)?;
+                let addr = parse_u32(args[0])?;
+                let len = parse_u32(args[1])?;
+                let mut words = Vec::new();
+                for i in 0..len {
+                    let word_addr = i
+                        .checked_mul(4)
+                        .and_then(|offset| addr.checked_add(offset))
+                        .ok_or_else(|| "address overflow".to_string())?;
+                    let value = self
+                        .runtime
+                        .try_word(word_addr)
+                        .map_err(|error| error.to_string())?;
+                    words.push(format!("0x{:08x} 0x{:08x}", word_addr, value));
+                }
+                Ok(words.join("\n"))
+            }
+            "bt" => {
+                arity(0, 0)?;
+                Ok(self
+                    .runtime
+                    .backtrace()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, frame)| format!("#{} {}", i, frame))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            "watch" => {
+                arity(1, 1)?;
+                let addr = self.resolve(args[0])?;
+                let value = self
+                    .runtime
+                    .try_word(addr)
+                    .map_err(|error| error.to_string())?;
+                self.watchpoints.insert(addr, value);
+                Ok(format!("watchpoint at 0x{:08x} = 0x{:08x}", addr, value))
+            }
+            "sym" => {
+                arity(1, 1)?;
+                let pc = parse_u32(args[0])?;
+                Ok(self.location(pc))
+            }
+            "h" | "help" => Ok(DEBUGGER_HELP.to_string()),
+            "q" | "quit" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            _ => Err(format!("unknown command `{}`, see `help`", name)),
+        }
+    }
+
+    /// An address, or the entry of a function by name.
+    fn resolve(&self, arg: &str) -> Result<u32, String> {
+        parse_u32(arg).or_else(|_| {
+            self.runtime
+                .program
+                .symbols
+                .get(arg)
+                .map(|symbol| symbol.addr)
+                .ok_or_else(|| format!("no symbol or address `{}`", arg))
+        })
+    }
+
+    /// `pc` and the function containing it with the offset into it, as `0x002009d8 main+0x4`.
+    fn location(&self, pc: u32) -> String {
+        match self.runtime.program.symbols.lookup(pc) {
+            Some(symbol) if symbol.addr == pc => format!("0x{:08x} {}", pc, symbol.name),
+            Some(symbol) => format!("0x{:08x} {}+0x{:x}", pc, symbol.name, pc - symbol.addr),
+            None => format!("0x{:08x} <unknown>", pc),
+        }
+    }
+
+    /// Execute `steps` instructions, or until the end of the program if `None`, stopping early at
+    /// a watchpoint or, when continuing, at a breakpoint.
+    fn resume(&mut self, steps: Option<u64>) -> String {
+        if let Some(ended) = &self.ended {
+            return format!("not running: {}", ended);
+        }
+        let mut executed = 0;
+        let stop = loop {
+            if steps == Some(executed) {
+                break None;
+            }
+            match self.runtime.run_steps(1) {
+                Ok(true) => break Some(Stop::Finished),
+                Ok(false) => {}
+                Err(error) => break Some(Stop::Error(error.to_string())),
+            }
+            executed += 1;
+            if let Some(stop) = self.check_watchpoints() {
+                break Some(stop);
+            }
+            if steps.is_none() && self.breakpoints.contains(&self.runtime.state.pc) {
+                break Some(Stop::Breakpoint);
+            }
+        };
+        let location = self.location(self.runtime.state.pc);
+        match stop {
+            None => location,
+            Some(Stop::Breakpoint) => format!("breakpoint hit at {}", location),
+            Some(Stop::Watchpoint { addr, old, new }) => format!(
+                "watchpoint at 0x{:08x} changed from 0x{:08x} to 0x{:08x}, stopped at {}",
+                addr, old, new, location
+            ),
+            Some(Stop::Finished) => {
+                self.runtime.finalize();
+                let ended = match self.runtime.exit_code() {
+                    Some(code) => format!("program exited with code {}", code),
+                    None => "program finished".to_string(),
+                };
+                self.ended = Some(ended.clone());
+                format!("{} after {} cycles", ended, self.runtime.state.global_clk)
+            }
+            Some(Stop::Error(error)) => {
+                self.ended = Some(error.clone());
+                format!("execution failed at {}: {}", location, error)
+            }
+        }
+    }
+
+    /// The first watched word that changed since it was last checked, updating every value.
+    fn check_watchpoints(&mut self) -> Option<Stop> {
+        let mut stop = None;
+        for (addr, value) in self.watchpoints.iter_mut() {
+            let new = self.runtime.word(*addr);
+            if new != *value && stop.is_none() {
+                stop = Some(Stop::Watchpoint {
+                    addr: *addr,
+                    old: *value,
+                    new,
+                });
+            }
+            *value = new;
+        }
+        stop
+    }
+}
+
+/// A number in hexadecimal with a `0x` prefix, or in decimal.
+fn parse_u32(arg: &str) -> Result<u32, String> {
+    let parsed = match arg.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => arg.parse(),
+    };
+    parsed.map_err(|_| format!("invalid number `{}`", arg))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::Register;
+
+    #[test]
+    fn test_fibonacci_session() {
+        let mut session = DebuggerSession::new(fibonacci_program());
+        let transcript = session.run_script(
+            "
+            b main
+            c
+            sym 0x2009d8
+            q
+            s
+            ",
+        );
+        assert_eq!(
+            transcript,
+            "> b main\n\
+             breakpoint at 0x002009d4 main\n\
+             > c\n\
+             breakpoint hit at 0x002009d4 main\n\
+             > sym 0x2009d8\n\
+             0x002009d8 main+0x4\n\
+             > q\n"
+        );
+        assert!(session.has_quit());
+
+        let sp = session.runtime().register(Register::X2);
+        assert_ne!(sp, 0);
+        let regs = session.execute("regs");
+        assert!(regs.starts_with("pc=0x002009d4 zero=0x00000000 "));
+        assert!(regs.contains(&format!(" sp=0x{:08x} ", sp)));
+        assert_eq!(
+            session.execute("x 0x2009d4 1"),
+            format!("0x002009d4 0x{:08x}", session.runtime().word(0x2009d4))
+        );
+        assert!(session.execute("bt").starts_with("#0 0x002009d4 main"));
+
+        let stepped = session.execute("s 3");
+        assert_eq!(session.runtime().state.global_clk, {
+            let mut runtime = Runtime::new(fibonacci_program());
+            runtime.initialize();
+            while runtime.state.pc != 0x2009d4 {
+                runtime.step().unwrap();
+            }
+            runtime.state.global_clk + 3
+        });
+        assert!(stepped.starts_with("0x"));
+
+        assert!(session.execute("c").starts_with("program "));
+        assert!(session.execute("s").starts_with("not running: "));
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut session = DebuggerSession::new(fibonacci_program());
+        session.execute("b main");
+        session.execute("c");
+        let sp = session.runtime().register(Register::X2);
+
+        // Watch a word of the stack frame of main, which it writes before the end.
+        let mut finished = Runtime::new(fibonacci_program());
+        finished.run();
+        let addr = (1..=16)
+            .map(|i| sp - 4 * i)
+            .find(|addr| finished.word(*addr) != session.runtime().word(*addr))
+            .unwrap();
+        assert!(session
+            .execute(&format!("watch 0x{:x}", addr))
+            .starts_with(&format!("watchpoint at 0x{:08x} = ", addr)));
+        let stop = session.execute("c");
+        assert!(
+            stop.starts_with(&format!("watchpoint at 0x{:08x} changed from ", addr)),
+            "{}",
+            stop
+        );
+    }
+
+    #[test]
+    fn test_invalid_commands() {
+        let mut session = DebuggerSession::new(fibonacci_program());
+        assert_eq!(
+            session.execute("frobnicate"),
+            "error: unknown command `frobnicate`, see `help`"
+        );
+        assert_eq!(
+            session.execute("b no_such_function"),
+            "error: no symbol or address `no_such_function`"
+        );
+        assert_eq!(session.execute("s x"), "error: invalid count `x`");
+        assert_eq!(
+            session.execute("regs 1"),
+            "error: wrong number of arguments for `regs`, see `help`"
+        );
+        assert!(session.execute("x 0x2 1").starts_with("error: "));
+        assert_eq!(session.execute(""), "");
+        assert_eq!(session.runtime().state.global_clk, 0);
+    }
+}
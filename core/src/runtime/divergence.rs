@@ -0,0 +1,224 @@
+use core::fmt::{Display, Formatter};
+
+use super::{ExecutionRecord, Instruction};
+use crate::cpu::CpuEvent;
+
+/// The number of events preceding the first mismatch kept in a [`DivergenceReport`].
+const CONTEXT_EVENTS: usize = 3;
+
+/// A field that differs between two cpu events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    /// The name of the field in [`CpuEvent`].
+    pub field: &'static str,
+
+    /// The value in the original record.
+    pub original: String,
+
+    /// The value in the re-executed record.
+    pub reexecuted: String,
+}
+
+/// The first cycle of a shard at which a re-execution stopped matching the original one.
+#[derive(Debug, Clone)]
+pub struct DivergenceReport {
+    /// The shard that diverged.
+    pub shard: u32,
+
+    /// The index of the mismatching event in the cpu events of the original record, which is its
+    /// global clock for a record that starts at the beginning of the execution.
+    pub global_clk: usize,
+
+    /// The pc of the original event, or of the re-executed one if the original shard ended.
+    pub pc: u32,
+
+    /// The instruction of the original event, or of the re-executed one if the original shard
+    /// ended.
+    pub instruction: Instruction,
+
+    /// The original event, if the original shard did not end before the re-executed one.
+    pub original: Option<CpuEvent>,
+
+    /// The re-executed event, if the re-executed shard did not end before the original one.
+    pub reexecuted: Option<CpuEvent>,
+
+    /// The fields that differ, empty if one of the shards ended early.
+    pub diffs: Vec<FieldDiff>,
+
+    /// The original events preceding the mismatch, oldest first.
+    pub context: Vec<CpuEvent>,
+}
+
+impl Display for DivergenceReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        writeln!(
+            f,
+            "shard {} diverges at global_clk {}, pc=0x{:08x}: {:?}",
+            self.shard, self.global_clk, self.pc, self.instruction
+        )?;
+        match (&self.original, &self.reexecuted) {
+            (Some(_), None) => writeln!(f, "  the re-executed shard ends here")?,
+            (None, Some(_)) => writeln!(f, "  the original shard ends here")?,
+            _ => {
+                for diff in self.diffs.iter() {
+                    writeln!(
+                        f,
+                        "  {}: {} != {}",
+                        diff.field, diff.original, diff.reexecuted
+                    )?;
+                }
+            }
+        }
+        writeln!(f, "  preceded by:")?;
+        for event in self.context.iter() {
+            writeln!(f, "    pc=0x{:08x}: {:?}", event.pc, event.instruction)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DivergenceReport {}
+
+/// Compare two cpu events field by field.
+fn diff_events(original: &CpuEvent, reexecuted: &CpuEvent) -> Vec<FieldDiff> {
+    let mut diffs = Vec::new();
+    macro_rules! diff_fields {
+        ($($field:ident),*) => {
+            $(
+                let (a, b) = (
+                    format!("{:?}", original.$field),
+                    format!("{:?}", reexecuted.$field),
+                );
+                if a != b {
+                    diffs.push(FieldDiff {
+                        field: stringify!($field),
+                        original: a,
+                        reexecuted: b,
+                    });
+                }
+            )*
+        };
+    }
+    diff_fields!(
+        shard,
+        clk,
+        pc,
+        instruction,
+        a,
+        a_record,
+        b,
+        b_record,
+        c,
+        c_record,
+        memory,
+        memory_record
+    );
+    diffs
+}
+
+/// Check that the cpu events of `shard` are the same in `original` and `reexecuted`, comparing
+/// them cycle by cycle and reporting the first mismatch.
+pub fn verify_reexecution(
+    original: &ExecutionRecord,
+    reexecuted: &ExecutionRecord,
+    shard: u32,
+) -> Result<(), DivergenceReport> {
+    let shard_events = |record: &ExecutionRecord| {
+        record
+            .cpu_events
+            .iter()
+            .enumerate()
+            .filter(move |(_, event)| event.shard == shard)
+            .collect::<Vec<_>>()
+    };
+    let original_events = shard_events(original);
+    let reexecuted_events = shard_events(reexecuted);
+
+    let len = original_events.len().max(reexecuted_events.len());
+    for i in 0..len {
+        let original_event = original_events.get(i).map(|(_, event)| **event);
+        let reexecuted_event = reexecuted_events.get(i).map(|(_, event)| **event);
+        let diffs = match (&original_event, &reexecuted_event) {
+            (Some(a), Some(b)) => diff_events(a, b),
+            _ => Vec::new(),
+        };
+        if original_event.is_some() && reexecuted_event.is_some() && diffs.is_empty() {
+            continue;
+        }
+
+        let event = original_event.or(reexecuted_event).unwrap();
+        let global_clk = match original_events.get(i) {
+            Some((index, _)) => *index,
+            None => original_events.last().map_or(0, |(index, _)| index + 1),
+        };
+        let context = original_events
+            [i.saturating_sub(CONTEXT_EVENTS)..i.min(original_events.len())]
+            .iter()
+            .map(|(_, event)| **event)
+            .collect();
+        return Err(DivergenceReport {
+            shard,
+            global_clk,
+            pc: event.pc,
+            instruction: event.instruction,
+            original: original_event,
+            reexecuted: reexecuted_event,
+            diffs,
+            context,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::Runtime;
+
+    #[test]
+    fn test_verify_reexecution() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.run();
+        let original = runtime.record;
+
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.run();
+        assert!(verify_reexecution(&original, &runtime.record, 1).is_ok());
+
+        let mut perturbed = original.clone();
+        perturbed.cpu_events[500].a ^= 1;
+        let report = verify_reexecution(&original, &perturbed, 1).unwrap_err();
+        assert_eq!(report.global_clk, 500);
+        assert_eq!(report.pc, original.cpu_events[500].pc);
+        assert_eq!(report.diffs.len(), 1);
+        assert_eq!(report.diffs[0].field, "a");
+        assert_eq!(
+            report.diffs[0].original,
+            original.cpu_events[500].a.to_string()
+        );
+        assert_eq!(
+            report
+                .context
+                .iter()
+                .map(|event| event.pc)
+                .collect::<Vec<_>>(),
+            original
+                .cpu_events
+                .range(497..500)
+                .map(|event| event.pc)
+                .collect::<Vec<_>>()
+        );
+        assert!(report.to_string().contains("global_clk 500"));
+
+        perturbed.cpu_events.truncate(600);
+        let report = verify_reexecution(&original, &perturbed, 1).unwrap_err();
+        assert_eq!(report.global_clk, 500);
+
+        let mut truncated = original.clone();
+        truncated.cpu_events.truncate(600);
+        let report = verify_reexecution(&original, &truncated, 1).unwrap_err();
+        assert_eq!(report.global_clk, 600);
+        assert!(report.reexecuted.is_none() && report.diffs.is_empty());
+    }
+}
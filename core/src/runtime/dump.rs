@@ -0,0 +1,146 @@
+//! Canonical dumps of the registers and memory of an execution, which are the same for identical
+//! executions regardless of the order of the memory map, so that dumps of two runs can be diffed.
+use std::fmt::{Display, Formatter, Write};
+use std::ops::Range;
+
+use super::{Error, ExecutionState, Register, Runtime, ABI_NAMES, NUM_REGISTERS};
+
+/// The values of the 32 registers, see [`ExecutionState::dump_registers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterFile {
+    pub values: [u32; 32],
+}
+
+impl RegisterFile {
+    pub fn get(&self, register: Register) -> u32 {
+        self.values[register as usize]
+    }
+}
+
+/// The registers in order, as `zero=0x00000000 ra=0x00000000 ...` on a single line.
+impl Display for RegisterFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, (name, value)) in ABI_NAMES.iter().zip(self.values.iter()).enumerate() {
+            if i > 0 {
+                f.write_char(' ')?;
+            }
+            write!(f, "{}=0x{:08x}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl ExecutionState {
+    /// The current values of the registers.
+    pub fn dump_registers(&self) -> RegisterFile {
+        let mut values = [0; 32];
+        for (addr, value) in values.iter_mut().enumerate() {
+            if let Some((word, _, _)) = self.memory.get(&(addr as u32)) {
+                *value = *word;
+            }
+        }
+        RegisterFile { values }
+    }
+
+    /// The words of the memory in `range`, or of the whole memory if `None`, sorted by address.
+    /// The registers, which live at the addresses below 32, are never included.
+    pub fn dump_memory_sorted(&self, range: Option<Range<u32>>) -> Vec<(u32, u32)> {
+        let range = range.unwrap_or(0..u32::MAX);
+        let mut words = self
+            .memory
+            .iter()
+            .filter(|(addr, _)| **addr >= NUM_REGISTERS && range.contains(addr))
+            .map(|(addr, (value, _, _))| (*addr, *value))
+            .collect::<Vec<_>>();
+        words.sort_unstable_by_key(|(addr, _)| *addr);
+        words
+    }
+
+    /// The memory words of [`ExecutionState::dump_memory_sorted`] as text, one
+    /// `0xaddress 0xvalue` line per word.
+    pub fn dump_memory_text(&self, range: Option<Range<u32>>) -> String {
+        let mut text = String::new();
+        for (addr, value) in self.dump_memory_sorted(range) {
+            writeln!(text, "0x{:08x} 0x{:08x}", addr, value).unwrap();
+        }
+        text
+    }
+
+    /// The clocks, program counter, registers and memory as text. The registers are one
+    /// `name=0xvalue` line each, and the memory is formatted as by
+    /// [`ExecutionState::dump_memory_text`].
+    pub fn dump(&self) -> String {
+        let mut text = String::new();
+        writeln!(text, "pc=0x{:08x}", self.pc).unwrap();
+        writeln!(text, "clk={}", self.clk).unwrap();
+        writeln!(text, "global_clk={}", self.global_clk).unwrap();
+        writeln!(text, "shard={}", self.current_shard).unwrap();
+        text.push_str("\n[registers]\n");
+        let registers = self.dump_registers();
+        for (name, value) in ABI_NAMES.iter().zip(registers.values.iter()) {
+            writeln!(text, "{}=0x{:08x}", name, value).unwrap();
+        }
+        text.push_str("\n[memory]\n");
+        text.push_str(&self.dump_memory_text(None));
+        text
+    }
+}
+
+impl Runtime {
+    /// Write the current state to `path` in the format of [`ExecutionState::dump`].
+    pub fn dump_state(&self, path: &str) -> Result<(), Error> {
+        std::fs::write(path, self.state.dump()).map_err(|error| Error::Io {
+            path: path.to_string(),
+            error,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::{fibonacci_program, simple_memory_program};
+
+    fn dump(runtime: &Runtime) -> Vec<u8> {
+        let path =
+            std::env::temp_dir().join(format!("sp1_test_dump_state_{}.txt", std::process::id()));
+        runtime.dump_state(path.to_str().unwrap()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_identical_runs() {
+        let mut first = Runtime::new(fibonacci_program());
+        first.run();
+        let mut second = Runtime::new(fibonacci_program());
+        second.run();
+        let first = dump(&first);
+        assert_eq!(first, dump(&second));
+        assert!(String::from_utf8(first).unwrap().contains("\na0=0x"));
+    }
+
+    #[test]
+    fn test_simple_memory_dump() {
+        let mut runtime = Runtime::new(simple_memory_program());
+        runtime.run();
+        assert_eq!(
+            runtime.state.dump_memory_text(None),
+            include_str!("testdata/simple_memory_program.memory")
+        );
+        assert_eq!(
+            runtime
+                .state
+                .dump_memory_sorted(Some(0x4000_0000..u32::MAX)),
+            vec![(0x43627530, 0x65256525)]
+        );
+
+        let registers = runtime.state.dump_registers();
+        assert_eq!(registers.values, runtime.registers());
+        assert_eq!(registers.get(Register::X29), 0x12348765);
+        assert!(registers
+            .to_string()
+            .starts_with("zero=0x00000000 ra=0x00000000 "));
+    }
+}
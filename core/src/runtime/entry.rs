@@ -0,0 +1,211 @@
+use core::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use super::{CallGraphTracker, Program, Runtime, Symbol};
+
+/// Where to start executing a program, see [`Runtime::set_entry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryPoint {
+    /// The first instruction of the function symbol with this demangled name.
+    Symbol(String),
+
+    /// Any instruction of the text section.
+    Address(u32),
+
+    /// An address that must be the first instruction of a function symbol, so that execution does
+    /// not start in the middle of a function.
+    Function(u32),
+}
+
+impl From<&str> for EntryPoint {
+    fn from(name: &str) -> Self {
+        EntryPoint::Symbol(name.to_string())
+    }
+}
+
+impl From<String> for EntryPoint {
+    fn from(name: String) -> Self {
+        EntryPoint::Symbol(name)
+    }
+}
+
+impl From<u32> for EntryPoint {
+    fn from(addr: u32) -> Self {
+        EntryPoint::Address(addr)
+    }
+}
+
+/// Why an [`EntryPoint`] cannot be used to start a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryError {
+    /// The program has no function symbol with this name.
+    UnknownSymbol(String),
+
+    /// The address is not a multiple of 4.
+    Misaligned { addr: u32 },
+
+    /// The address is not that of an instruction of the program.
+    OutsideText { addr: u32 },
+
+    /// No function symbol starts at the address.
+    NotAFunction { addr: u32 },
+
+    /// The runtime already executed some cycles.
+    AlreadyStarted,
+}
+
+impl Display for EntryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EntryError::UnknownSymbol(name) => write!(f, "no function symbol named `{}`", name),
+            EntryError::Misaligned { addr } => {
+                write!(f, "entry 0x{:08x} is not aligned to 4 bytes", addr)
+            }
+            EntryError::OutsideText { addr } => {
+                write!(f, "entry 0x{:08x} is outside of the text section", addr)
+            }
+            EntryError::NotAFunction { addr } => {
+                write!(f, "entry 0x{:08x} is not the start of a function", addr)
+            }
+            EntryError::AlreadyStarted => write!(f, "execution already started"),
+        }
+    }
+}
+
+impl std::error::Error for EntryError {}
+
+impl Program {
+    /// The function symbols the program can be started from with [`Runtime::set_entry`]: those
+    /// starting at an aligned instruction of the program.
+    pub fn entries(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols
+            .iter()
+            .filter(|symbol| self.check_entry(symbol.addr).is_ok())
+    }
+
+    /// The address of the first instruction to execute when starting at `entry`.
+    pub fn resolve_entry(&self, entry: &EntryPoint) -> Result<u32, EntryError> {
+        let addr = match entry {
+            EntryPoint::Symbol(name) => {
+                self.symbols
+                    .get(name)
+                    .ok_or_else(|| EntryError::UnknownSymbol(name.clone()))?
+                    .addr
+            }
+            EntryPoint::Address(addr) => *addr,
+            EntryPoint::Function(addr) => {
+                if !self.symbols.iter().any(|symbol| symbol.addr == *addr) {
+                    return Err(EntryError::NotAFunction { addr: *addr });
+                }
+                *addr
+            }
+        };
+        self.check_entry(addr)?;
+        Ok(addr)
+    }
+
+    fn check_entry(&self, addr: u32) -> Result<(), EntryError> {
+        if addr % 4 != 0 {
+            return Err(EntryError::Misaligned { addr });
+        }
+        if addr < self.pc_base || addr >= self.text_end() {
+            return Err(EntryError::OutsideText { addr });
+        }
+        Ok(())
+    }
+}
+
+impl Runtime {
+    /// Start the program at `entry` instead of its `pc_start`, so that a guest exporting several
+    /// entry points can serve each of them from the same [`Program`]. Must be called before the
+    /// first cycle, and is undone by [`Runtime::reset_with_program`]. Returns the address of the
+    /// entry.
+    pub fn set_entry(&mut self, entry: impl Into<EntryPoint>) -> Result<u32, EntryError> {
+        if self.state.global_clk != 0 {
+            return Err(EntryError::AlreadyStarted);
+        }
+        let addr = self.program.resolve_entry(&entry.into())?;
+        if addr == self.program.pc_start {
+            return Ok(addr);
+        }
+        // The relocation of a randomized layout, applied by `initialize`, moves the entry along
+        // with the rest of the program.
+        let mut program = (*self.program).clone();
+        program.pc_start = addr;
+        self.program = Arc::new(program);
+        self.record.program = self.program.clone();
+        self.state.pc = addr;
+        if self.call_graph.is_some() {
+            self.call_graph = Some(CallGraphTracker::new(addr));
+        }
+        Ok(addr)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Register, SymbolTable};
+    use crate::utils::asm::assemble;
+
+    /// A guest with a `main` entry setting `t1` to 1 and a `self_test` entry setting it to 2.
+    fn multi_entry_program() -> Program {
+        let mut program = assemble(
+            "main:      li   t1, 1
+                        j    end
+             self_test: li   t1, 2
+             end:       nop",
+            0x1000,
+        )
+        .unwrap();
+        program.symbols = SymbolTable::new(vec![
+            Symbol::new("main", 0x1000, 8),
+            Symbol::new("self_test", 0x1008, 4),
+        ]);
+        program
+    }
+
+    #[test]
+    fn test_multiple_entries() {
+        let program = Arc::new(multi_entry_program());
+        let names = program
+            .entries()
+            .map(|symbol| symbol.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["main", "self_test"]);
+
+        let mut runtime = Runtime::new((*program).clone());
+        runtime.run();
+        assert_eq!(runtime.register(Register::X6), 1);
+
+        runtime.reset_with_program(program.clone());
+        assert_eq!(runtime.set_entry("self_test"), Ok(0x1008));
+        runtime.run();
+        assert_eq!(runtime.register(Register::X6), 2);
+        assert_eq!(program.pc_start, 0x1000);
+        assert_eq!(runtime.set_entry("main"), Err(EntryError::AlreadyStarted));
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        let mut runtime = Runtime::new(multi_entry_program());
+        assert_eq!(
+            runtime.set_entry(0x1006),
+            Err(EntryError::Misaligned { addr: 0x1006 })
+        );
+        assert_eq!(
+            runtime.set_entry(0x1010),
+            Err(EntryError::OutsideText { addr: 0x1010 })
+        );
+        assert_eq!(
+            runtime.set_entry("benchmark"),
+            Err(EntryError::UnknownSymbol("benchmark".to_string()))
+        );
+        assert_eq!(
+            runtime.set_entry(EntryPoint::Function(0x1004)),
+            Err(EntryError::NotAFunction { addr: 0x1004 })
+        );
+        assert_eq!(runtime.set_entry(0x1004), Ok(0x1004));
+        assert_eq!(runtime.state.pc, 0x1004);
+    }
+}
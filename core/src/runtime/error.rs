@@ -0,0 +1,459 @@
+use core::fmt::{Display, Formatter};
+
+use super::{
+    format_journal, ClockFault, DuplicateAccess, LivelockSuspected, Opcode, ProgramValidationError,
+    StopReason, SyscallCode, SyscallJournalEntry,
+};
+use crate::disassembler::ElfError;
+
+/// An error that stops the execution of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionError {
+    /// The guest invoked a syscall that is not registered with the runtime.
+    UnsupportedSyscall { code: u32, pc: u32 },
+
+    /// The guest tried to read more input than was written to the input stream.
+    InputExhausted { pc: u32 },
+
+    /// The guest executed an `unimp` instruction.
+    Unimplemented { pc: u32 },
+
+    /// The guest executed an `ebreak` instruction.
+    Breakpoint { pc: u32 },
+
+    /// The guest read memory that was never written, under `UninitMemoryPolicy::Trap`.
+    UninitializedRead { addr: u32, pc: u32 },
+
+    /// Execution was cancelled through the runtime's cancel token before the instruction at `pc`
+    /// was executed.
+    Cancelled { at_cycle: u32, pc: u32 },
+
+    /// The guest seems to be stuck in a loop, under `LivelockAction::Abort`.
+    LivelockSuspected(LivelockSuspected),
+
+    /// The guest expected a different type for the next frame of the input stream than the host
+    /// wrote, under an input schema. Both are frame tags.
+    InputFrameMismatch {
+        frame: u32,
+        expected: u32,
+        found: u32,
+        pc: u32,
+    },
+
+    /// The instruction at `pc` accessed the same operand twice, which is only checked in debug
+    /// builds or with `Runtime::full_validation`.
+    DuplicateAccess { access: DuplicateAccess, pc: u32 },
+
+    /// The guest wrote to the word at `addr`, which overlaps a range marked with
+    /// `Program::mark_readonly`.
+    WriteToReadOnly { addr: u32, pc: u32 },
+
+    /// The reader set with `Runtime::set_stdin_reader` failed while the guest read input.
+    InputReadFailed { kind: std::io::ErrorKind, pc: u32 },
+
+    /// The program counter left the program without the guest halting. Falling through the last
+    /// instruction still finishes execution.
+    PcOutOfBounds { pc: u32 },
+
+    /// The guest executed an opcode forbidden with `Runtime::restrict_opcodes`.
+    OpcodeForbidden { opcode: Opcode, pc: u32 },
+
+    /// The guest invoked a syscall outside of the whitelist set with `Runtime::restrict_syscalls`.
+    SyscallForbidden { code: u32, pc: u32 },
+
+    /// The guest accessed the word at `addr` in a region it may not access, such as the guard
+    /// region below the stack set with `Runtime::configure_stack`.
+    ProtectionFault { region: String, addr: u32, pc: u32 },
+
+    /// A load, store or syscall accessed `base + offset`, which wraps around to `effective`, in a
+    /// word that is reserved for registers or at or above `MEMORY_ADDR_LIMIT`.
+    AddressOutOfRange {
+        base: u32,
+        offset: u32,
+        effective: u32,
+        pc: u32,
+    },
+
+    /// Execution was stopped on request of the host, such as by a condition added with
+    /// `Runtime::add_condition`.
+    Stopped(StopReason),
+
+    /// The runtime was asked to execute the instruction at `pc` while its state is `cycles`
+    /// cycles behind the execution after `Runtime::reverse_step`.
+    Rewound { cycles: usize, pc: u32 },
+
+    /// The syscall invoked at `pc` accessed the word at `addr`, outside of its
+    /// `Syscall::memory_footprint`, see `Runtime::set_syscall_footprint_checks`.
+    SyscallFootprintViolation {
+        code: SyscallCode,
+        addr: u32,
+        pc: u32,
+    },
+
+    /// A load, store or syscall at `pc` accessed the word at `addr`, which is below the lowest
+    /// address of memory the guest may access, `Program::min_data_addr`.
+    ReservedAddress {
+        addr: u32,
+        min_data_addr: u32,
+        pc: u32,
+    },
+
+    /// The guest tried to enter an unconstrained block at `pc` while they are forbidden, see
+    /// `RuntimeOptions::forbid_unconstrained`.
+    UnconstrainedForbidden { pc: u32 },
+
+    /// The guest loaded the word at `addr` of the scratch region outside of an unconstrained
+    /// block, see `RuntimeOptions::scratch_region`.
+    ScratchRead { addr: u32, pc: u32 },
+
+    /// The syscall invoked at `pc` miscounted its cycles. The journal lists its accesses and
+    /// clock advances if `Runtime::set_syscall_clock_journal` is enabled, and is empty otherwise.
+    SyscallClockMismatch {
+        code: SyscallCode,
+        pc: u32,
+        fault: ClockFault,
+        journal: Vec<SyscallJournalEntry>,
+    },
+
+    /// The guest at `pc` reads `len` bytes of input from file descriptor `fd` while fewer are
+    /// available, with `Runtime::set_input_blocking` enabled. The read was not executed, so
+    /// execution can go on once more input is written.
+    InputWouldBlock { fd: u32, len: u32, pc: u32 },
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ExecutionError::UnsupportedSyscall { code, pc } => {
+                write!(f, "unsupported syscall {} at pc=0x{:x}", code, pc)
+            }
+            ExecutionError::InputExhausted { pc } => write!(
+                f,
+                "not enough input was passed in, read past the end of the input stream at pc=0x{:x}",
+                pc
+            ),
+            ExecutionError::Unimplemented { pc } => {
+                write!(f, "unimp instruction encountered at pc=0x{:x}", pc)
+            }
+            ExecutionError::Breakpoint { pc } => write!(f, "ebreak encountered at pc=0x{:x}", pc),
+            ExecutionError::UninitializedRead { addr, pc } => write!(
+                f,
+                "read of uninitialized memory at addr=0x{:x}, pc=0x{:x}",
+                addr, pc
+            ),
+            ExecutionError::Cancelled { at_cycle, pc } => write!(
+                f,
+                "execution cancelled at cycle {} before pc=0x{:x}",
+                at_cycle, pc
+            ),
+            ExecutionError::LivelockSuspected(report) => write!(f, "{}", report),
+            ExecutionError::InputFrameMismatch {
+                frame,
+                expected,
+                found,
+                pc,
+            } => write!(
+                f,
+                "input frame {} has tag 0x{:08x} but the guest expected tag 0x{:08x} at pc=0x{:x}",
+                frame, found, expected, pc
+            ),
+            ExecutionError::DuplicateAccess { access, pc } => {
+                write!(f, "{} at pc=0x{:x}", access, pc)
+            }
+            ExecutionError::WriteToReadOnly { addr, pc } => write!(
+                f,
+                "write to read-only memory at addr=0x{:x}, pc=0x{:x}",
+                addr, pc
+            ),
+            ExecutionError::InputReadFailed { kind, pc } => write!(
+                f,
+                "reading from the stdin reader failed with {} at pc=0x{:x}",
+                kind, pc
+            ),
+            ExecutionError::PcOutOfBounds { pc } => {
+                write!(f, "pc=0x{:x} is outside of the program", pc)
+            }
+            ExecutionError::OpcodeForbidden { opcode, pc } => {
+                write!(f, "forbidden opcode {} at pc=0x{:x}", opcode, pc)
+            }
+            ExecutionError::SyscallForbidden { code, pc } => {
+                write!(f, "forbidden syscall {} at pc=0x{:x}", code, pc)
+            }
+            ExecutionError::ProtectionFault { region, addr, pc } => write!(
+                f,
+                "protection fault: access to the {} region at addr=0x{:x}, pc=0x{:x}",
+                region, addr, pc
+            ),
+            ExecutionError::AddressOutOfRange {
+                base,
+                offset,
+                effective,
+                pc,
+            } => write!(
+                f,
+                "address 0x{:x} + 0x{:x} = 0x{:x} is out of range at pc=0x{:x}",
+                base, offset, effective, pc
+            ),
+            ExecutionError::Stopped(reason) => write!(f, "execution stopped: {}", reason),
+            ExecutionError::Rewound { cycles, pc } => write!(
+                f,
+                "cannot execute pc=0x{:x} while the state is rewound by {} cycles, restore the \
+                 present first",
+                pc, cycles
+            ),
+            ExecutionError::SyscallFootprintViolation { code, addr, pc } => write!(
+                f,
+                "syscall {:?} accessed addr=0x{:x} outside of its memory footprint at pc=0x{:x}",
+                code, addr, pc
+            ),
+            ExecutionError::ReservedAddress {
+                addr,
+                min_data_addr,
+                pc,
+            } => write!(
+                f,
+                "address 0x{:x} is reserved, below the lowest data address 0x{:x}, at pc=0x{:x}",
+                addr, min_data_addr, pc
+            ),
+            ExecutionError::UnconstrainedForbidden { pc } => {
+                write!(f, "unconstrained blocks are forbidden, entered at pc=0x{:x}", pc)
+            }
+            ExecutionError::ScratchRead { addr, pc } => write!(
+                f,
+                "load from the scratch region at addr=0x{:x}, pc=0x{:x}",
+                addr, pc
+            ),
+            ExecutionError::SyscallClockMismatch {
+                code,
+                pc,
+                fault,
+                journal,
+            } => write!(
+                f,
+                "syscall {:?} at pc=0x{:x} {}, journal: {}",
+                code,
+                pc,
+                fault,
+                format_journal(journal)
+            ),
+            ExecutionError::InputWouldBlock { fd, len, pc } => write!(
+                f,
+                "read of {} input bytes from fd {} at pc=0x{:x} would block",
+                len, fd, pc
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+/// Any error of the runtime, for hosts that handle the errors of loading and executing a program
+/// in one place instead of panicking.
+#[derive(Debug)]
+pub enum Error {
+    /// The ELF of the program is malformed.
+    Elf(ElfError),
+
+    /// The program contains malformed instructions.
+    Program(ProgramValidationError),
+
+    /// The execution of the program stopped with an error.
+    Execution(ExecutionError),
+
+    /// Reading or writing the file at `path` failed.
+    Io { path: String, error: std::io::Error },
+
+    /// A register index that is not below 32.
+    InvalidRegister { index: u32 },
+
+    /// A word access at an address that is not a multiple of 4.
+    UnalignedAddress { addr: u32 },
+
+    /// A memory access at an address reserved for registers.
+    RegisterAddress { addr: u32 },
+
+    /// A slice of `len` words starting at `addr` extends past the end of the address space.
+    AddressOverflow { addr: u32, len: usize },
+
+    /// The output stream does not hold a value of the requested type at `offset`.
+    Output { offset: usize, message: String },
+
+    /// A function of [`RuntimeOptions::trace_filter`](super::RuntimeOptions::trace_filter) is not
+    /// in the symbol table of the program, with the names of the functions closest to it.
+    UnknownSymbol {
+        name: String,
+        near_matches: Vec<String>,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Elf(err) => write!(f, "invalid elf: {}", err),
+            Error::Program(err) => write!(f, "{}", err),
+            Error::Execution(err) => write!(f, "execution failed: {}", err),
+            Error::Io { path, error } => write!(f, "failed to access {}: {}", path, error),
+            Error::InvalidRegister { index } => write!(f, "invalid register x{}", index),
+            Error::UnalignedAddress { addr } => write!(f, "unaligned word address 0x{:x}", addr),
+            Error::RegisterAddress { addr } => {
+                write!(f, "address 0x{:x} is reserved for registers", addr)
+            }
+            Error::AddressOverflow { addr, len } => write!(
+                f,
+                "{} words starting at 0x{:x} overflow the address space",
+                len, addr
+            ),
+            Error::Output { offset, message } => write!(
+                f,
+                "failed to read the output stream at offset {}: {}",
+                offset, message
+            ),
+            Error::UnknownSymbol { name, near_matches } => {
+                write!(f, "function `{}` is not in the symbol table", name)?;
+                if !near_matches.is_empty() {
+                    write!(f, ", did you mean {}?", near_matches.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Elf(err) => Some(err),
+            Error::Program(err) => Some(err),
+            Error::Execution(err) => Some(err),
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ElfError> for Error {
+    fn from(err: ElfError) -> Self {
+        Error::Elf(err)
+    }
+}
+
+impl From<ProgramValidationError> for Error {
+    fn from(err: ProgramValidationError) -> Self {
+        Error::Program(err)
+    }
+}
+
+impl From<ExecutionError> for Error {
+    fn from(err: ExecutionError) -> Self {
+        Error::Execution(err)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runtime::tests::simple_program;
+    use crate::runtime::{Instruction, Program, Register, Runtime, SyscallArgs, SyscallContext};
+
+    fn invalid_program() -> Program {
+        Program::new(
+            vec![Instruction::new(Opcode::ADD, 32, 0, 5, false, true)],
+            0,
+            0,
+        )
+    }
+
+    #[test]
+    fn test_program_from_elf_file() {
+        let missing = "/nonexistent/sp1/program.elf";
+        match Program::try_from_elf_file(missing) {
+            Err(Error::Io { path, error }) => {
+                assert_eq!(path, missing);
+                assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let path = std::env::temp_dir().join("sp1_test_program_from_elf_file.elf");
+        std::fs::write(&path, b"not an elf").unwrap();
+        let result = Program::try_from_elf_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::Elf(ElfError::BadMagic))));
+    }
+
+    #[test]
+    fn test_try_new() {
+        let err = Runtime::try_new(invalid_program()).err().unwrap();
+        assert!(matches!(&err, Error::Program(err) if err.invalid.len() == 1));
+        assert!(err.to_string().contains("pc=0x0"));
+        assert!(Runtime::try_new(simple_program()).is_ok());
+    }
+
+    #[test]
+    fn test_try_reset_with_program() {
+        let mut runtime = Runtime::new(simple_program());
+        runtime.run();
+        let result = runtime.try_reset_with_program(Arc::new(invalid_program()));
+        assert!(matches!(result, Err(Error::Program(_))));
+        // The runtime is left as it was.
+        assert_eq!(runtime.register(Register::X31), 42);
+    }
+
+    #[test]
+    fn test_accessors() {
+        let mut runtime = Runtime::new(simple_program());
+        runtime.run();
+        runtime.state.memory.insert(0x1000, (9, 0, 0));
+        assert_eq!(runtime.try_register(31).unwrap(), 42);
+        assert!(matches!(
+            runtime.try_register(32),
+            Err(Error::InvalidRegister { index: 32 })
+        ));
+        assert_eq!(Register::try_from_u32(32), None);
+        assert_eq!(runtime.try_word(0x1000).unwrap(), 9);
+        assert!(matches!(
+            runtime.try_word(0x1001),
+            Err(Error::UnalignedAddress { addr: 0x1001 })
+        ));
+    }
+
+    #[test]
+    fn test_syscall_context_slice() {
+        let mut runtime = Runtime::new(simple_program());
+        runtime.state.memory.insert(0x1004, (42, 0, 0));
+        let args = SyscallArgs {
+            code: 0,
+            a0: 0,
+            a1: 0,
+        };
+        let ctx = SyscallContext::new(&mut runtime, args);
+        assert_eq!(ctx.try_slice(0x1000, 2).unwrap(), vec![0, 42]);
+        assert_eq!(ctx.try_slice(u32::MAX - 3, 1).unwrap(), vec![0]);
+        assert!(matches!(
+            ctx.try_slice(u32::MAX - 3, 2),
+            Err(Error::AddressOverflow { len: 2, .. })
+        ));
+        assert!(matches!(
+            ctx.try_slice(2, 1),
+            Err(Error::UnalignedAddress { addr: 2 })
+        ));
+        assert!(matches!(
+            ctx.try_word(2),
+            Err(Error::UnalignedAddress { addr: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_try_read_stdout() {
+        let mut runtime = Runtime::new(simple_program());
+        runtime.state.output_stream.extend(7u32.to_le_bytes());
+        match runtime.try_read_stdout::<u64>() {
+            Err(Error::Output { offset, .. }) => assert_eq!(offset, 0),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(runtime.state.output_stream_ptr, 0);
+        assert_eq!(runtime.try_read_stdout::<u32>().unwrap(), 7);
+    }
+}
@@ -0,0 +1,72 @@
+use super::{ExecutionError, IoStats, Program, Runtime};
+
+/// The cost of executing a program, measured without recording any events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleEstimate {
+    /// The number of cycles the program ran for.
+    pub cycles: u64,
+
+    /// The data the program read and wrote.
+    pub io: IoStats,
+}
+
+/// Execute an ELF on the given input, only keeping track of the cycle count and I/O.
+///
+/// This is the entrypoint used for cycle estimation in the browser (see the `wasm` feature).
+pub fn estimate_cycles(elf: &[u8], stdin: &[u8]) -> Result<CycleEstimate, ExecutionError> {
+    let mut runtime = Runtime::new(Program::from(elf));
+    runtime.emit_events = false;
+    runtime.trace_buf = None;
+    runtime.write_stdin_slice(stdin);
+    runtime.try_run()?;
+    Ok(CycleEstimate {
+        cycles: runtime.state.global_clk as u64,
+        io: runtime.io_stats(),
+    })
+}
+
+/// [`estimate_cycles`] exported to JavaScript, returning the number of cycles or throwing the
+/// execution error.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = estimateCycles)]
+pub fn estimate_cycles_js(elf: &[u8], stdin: &[u8]) -> Result<u64, wasm_bindgen::JsError> {
+    estimate_cycles(elf, stdin)
+        .map(|estimate| estimate.cycles)
+        .map_err(|err| wasm_bindgen::JsError::new(&err.to_string()))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::utils::tests::FIBONACCI_ELF;
+
+    #[test]
+    fn test_estimate_cycles() {
+        let mut runtime = Runtime::new(Program::from(FIBONACCI_ELF));
+        runtime.run();
+
+        let estimate = estimate_cycles(FIBONACCI_ELF, &[]).unwrap();
+        assert_eq!(estimate.cycles, runtime.state.global_clk as u64);
+        assert_eq!(estimate.io, runtime.io_stats());
+    }
+
+    #[test]
+    fn test_no_events_emitted() {
+        let mut runtime = Runtime::new(Program::from(FIBONACCI_ELF));
+        runtime.emit_events = false;
+        runtime.run();
+        assert!(runtime.record.cpu_events.is_empty());
+        assert!(runtime.record.add_events.is_empty());
+    }
+
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    #[wasm_bindgen_test::wasm_bindgen_test]
+    fn test_estimate_cycles_js() {
+        let estimate = estimate_cycles(FIBONACCI_ELF, &[]).unwrap();
+        assert!(estimate.cycles > 0);
+        assert_eq!(
+            estimate_cycles_js(FIBONACCI_ELF, &[]).ok(),
+            Some(estimate.cycles)
+        );
+    }
+}
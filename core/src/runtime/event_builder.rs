@@ -0,0 +1,444 @@
+use std::collections::BTreeMap;
+
+use super::invariants::{alu_result, instruction_accesses, Access};
+use super::{check_cpu_event, AccessPosition, EventViolation, Instruction, Opcode};
+use crate::alu::{AluEvent, DivRemMetadata};
+use crate::cpu::{CpuEvent, MemoryReadRecord, MemoryRecordEnum, MemoryWriteRecord};
+
+/// Builds the [`CpuEvent`] the runtime emits for an instruction, for tests of chips that need
+/// realistic events.
+///
+/// The accesses are derived from the instruction the way `Runtime::execute` makes them: each at
+/// the clock of its [`AccessPosition`], with the previous access of its word taken from
+/// [`CpuEventBuilder::with_prev`], or from an earlier access of the same instruction. The memory
+/// value is the word read by a load, or the word a store writes merged from the previous one.
+#[derive(Debug, Clone)]
+pub struct CpuEventBuilder {
+    shard: u32,
+    clk: u32,
+    pc: u32,
+    instruction: Instruction,
+    operands: (u32, u32, u32),
+
+    /// The value, shard and timestamp of the last access of each word accessed before.
+    prev: BTreeMap<u32, (u32, u32, u32)>,
+}
+
+impl CpuEventBuilder {
+    /// The event of `instruction` executed at `pc` and clock `clk` of `shard`, with all operands
+    /// zero and every word accessed for the first time.
+    pub fn new(shard: u32, clk: u32, pc: u32, instruction: Instruction) -> Self {
+        Self {
+            shard,
+            clk,
+            pc,
+            instruction,
+            operands: (0, 0, 0),
+            prev: BTreeMap::new(),
+        }
+    }
+
+    /// Set the operands: `a` is the result of the instruction, or the value of its first
+    /// register for branches and stores, and `b` and `c` are register values or immediates.
+    pub fn with_operands(mut self, a: u32, b: u32, c: u32) -> Self {
+        self.operands = (a, b, c);
+        self
+    }
+
+    /// Set the word at `addr`, a register or an aligned address, to hold `value` as last accessed
+    /// at `timestamp` of `shard`.
+    pub fn with_prev(mut self, addr: u32, value: u32, shard: u32, timestamp: u32) -> Self {
+        self.prev.insert(addr, (value, shard, timestamp));
+        self
+    }
+
+    /// Build the event, returning the first invariant of [`check_cpu_event`] it breaks, e.g. a
+    /// result in `a` the instruction does not compute or a previous access that is not before
+    /// the access.
+    pub fn build(mut self) -> Result<CpuEvent, EventViolation> {
+        let instruction = self.instruction;
+        let (a, b, c) = self.operands;
+        let [a_access, b_access, c_access, memory_access] = instruction_accesses(&instruction);
+        let addr = b.wrapping_add(c);
+        let memory = match memory_access {
+            Access::None => None,
+            Access::Read => Some(self.prev_value(addr & !3)),
+            Access::Write => Some(store_value(
+                instruction.opcode,
+                addr,
+                a,
+                self.prev_value(addr & !3),
+            )),
+        };
+
+        let mut event = CpuEvent {
+            shard: self.shard,
+            clk: self.clk,
+            pc: self.pc,
+            instruction,
+            a,
+            a_record: None,
+            b,
+            b_record: None,
+            c,
+            c_record: None,
+            memory,
+            memory_record: None,
+        };
+        // The accesses in the order of their clocks, so that an access of a word accessed twice
+        // follows the first one.
+        event.memory_record =
+            self.access(AccessPosition::Memory, memory_access, addr & !3, memory)?;
+        event.c_record = self.access(AccessPosition::C, c_access, instruction.op_c, Some(c))?;
+        event.b_record = self.access(AccessPosition::B, b_access, instruction.op_b, Some(b))?;
+        event.a_record = self.access(AccessPosition::A, a_access, instruction.op_a, Some(a))?;
+
+        check_cpu_event(&event, self.shard)?;
+        Ok(event)
+    }
+
+    fn prev_value(&self, addr: u32) -> u32 {
+        self.prev.get(&addr).map_or(0, |(value, _, _)| *value)
+    }
+
+    /// The record of the access at `position` of the word at `addr`, which sets it to `value` if
+    /// it is a write. A read of a word accessed before must find its value.
+    fn access(
+        &mut self,
+        position: AccessPosition,
+        access: Access,
+        addr: u32,
+        value: Option<u32>,
+    ) -> Result<Option<MemoryRecordEnum>, EventViolation> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let timestamp = self.clk + position as u32;
+        let prev = self.prev.get(&addr).copied();
+        let (prev_value, prev_shard, prev_timestamp) = prev.unwrap_or_default();
+        if access == Access::Read && prev.is_some() && prev_value != value {
+            return Err(EventViolation::WrongValue {
+                position,
+                expected: value,
+                found: prev_value,
+            });
+        }
+        // The records are built without `new`, whose assertions are checked by
+        // `check_cpu_event` instead.
+        let record = match access {
+            Access::None => return Ok(None),
+            Access::Read => MemoryRecordEnum::Read(MemoryReadRecord {
+                value,
+                shard: self.shard,
+                timestamp,
+                prev_shard,
+                prev_timestamp,
+            }),
+            Access::Write => MemoryRecordEnum::Write(MemoryWriteRecord {
+                value,
+                shard: self.shard,
+                timestamp,
+                prev_value,
+                prev_shard,
+                prev_timestamp,
+            }),
+        };
+        self.prev
+            .insert(addr, (record.value(), self.shard, timestamp));
+        Ok(Some(record))
+    }
+}
+
+/// The word a store of `a` at `addr` writes over `word`.
+fn store_value(opcode: Opcode, addr: u32, a: u32, word: u32) -> u32 {
+    match opcode {
+        Opcode::SB => {
+            let shift = 8 * (addr % 4);
+            (word & !(0xff << shift)) | ((a & 0xff) << shift)
+        }
+        Opcode::SH => {
+            let shift = 16 * ((addr >> 1) % 2);
+            (word & !(0xffff << shift)) | ((a & 0xffff) << shift)
+        }
+        _ => a,
+    }
+}
+
+/// Builds the [`AluEvent`] the runtime emits for an ALU operation, with its result derived from
+/// the operands.
+#[derive(Debug, Clone, Copy)]
+pub struct AluEventBuilder {
+    clk: u32,
+    opcode: Opcode,
+    b: u32,
+    c: u32,
+    a: Option<u32>,
+}
+
+impl AluEventBuilder {
+    /// The event of `opcode` applied to `b` and `c` at clock zero.
+    pub fn new(opcode: Opcode, b: u32, c: u32) -> Self {
+        Self {
+            clk: 0,
+            opcode,
+            b,
+            c,
+            a: None,
+        }
+    }
+
+    pub fn with_clk(mut self, clk: u32) -> Self {
+        self.clk = clk;
+        self
+    }
+
+    /// Expect the result to be `a`, instead of only computing it.
+    pub fn with_result(mut self, a: u32) -> Self {
+        self.a = Some(a);
+        self
+    }
+
+    /// Build the event, returning [`EventViolation::WrongResult`] if the expected result is not
+    /// the one the operation computes.
+    ///
+    /// Panics if the opcode is not an ALU operation.
+    pub fn build(self) -> Result<AluEvent, EventViolation> {
+        let Self {
+            clk,
+            opcode,
+            b,
+            c,
+            a,
+        } = self;
+        let expected = alu_result(opcode, b, c)
+            .unwrap_or_else(|| panic!("{:?} is not an ALU operation", opcode));
+        if let Some(found) = a.filter(|&a| a != expected) {
+            return Err(EventViolation::WrongResult { expected, found });
+        }
+        Ok(AluEvent::new(clk, opcode, expected, b, c))
+    }
+}
+
+/// The events of `opcode x5, x6, x7` executed at clock `clk` of `shard` with `b` in `x6` and `c`
+/// in `x7`, each register accessed for the first time: the CPU event and the ALU event it emits.
+///
+/// Panics if the opcode is not an ALU operation.
+pub fn synthesize_simple_alu(
+    opcode: Opcode,
+    b: u32,
+    c: u32,
+    shard: u32,
+    clk: u32,
+) -> (CpuEvent, AluEvent) {
+    let alu_event = AluEventBuilder::new(opcode, b, c)
+        .with_clk(clk)
+        .build()
+        .unwrap();
+    let cpu_event = CpuEventBuilder::new(
+        shard,
+        clk,
+        0,
+        Instruction::new(opcode, 5, 6, 7, false, false),
+    )
+    .with_operands(alu_event.a, b, c)
+    .with_prev(6, b, 0, 0)
+    .with_prev(7, c, 0, 0)
+    .build()
+    .unwrap();
+    (cpu_event, alu_event)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+    use crate::utils::asm::assemble;
+
+    type Events = (CpuEvent, Option<AluEvent>);
+
+    /// The events of the instruction at `pc` of `src` executed by the runtime, and the ones built
+    /// from its operands and the state of the runtime before it.
+    fn run_and_build(src: &str, pc: u32) -> (Events, Events) {
+        let mut runtime = Runtime::new(assemble(src, 0).unwrap());
+        runtime.initialize();
+        while runtime.state.pc != pc {
+            runtime.step().unwrap();
+        }
+        let (shard, clk) = (runtime.current_shard(), runtime.state.clk);
+        let instruction = runtime.fetch();
+        let words = runtime.state.memory.clone();
+        let add_events = runtime.record.add_events.len();
+        runtime.step().unwrap();
+
+        let event = *runtime.record.cpu_events.last().unwrap();
+        let alu_event = runtime.record.add_events.get(add_events).copied();
+        let (a, b, c) = (event.a, event.b, event.c);
+        let mut addrs = vec![instruction.op_a];
+        addrs.extend((!instruction.imm_b).then_some(instruction.op_b));
+        addrs.extend((!instruction.imm_c).then_some(instruction.op_c));
+        addrs.extend(
+            instruction
+                .is_memory_instruction()
+                .then_some(b.wrapping_add(c) & !3),
+        );
+        let mut builder = CpuEventBuilder::new(shard, clk, pc, instruction).with_operands(a, b, c);
+        for addr in addrs {
+            if let Some(&(value, shard, timestamp)) = words.get(&addr) {
+                builder = builder.with_prev(addr, value, shard, timestamp);
+            }
+        }
+        let built = builder.build().unwrap();
+        let built_alu = alu_event.map(|_| {
+            AluEventBuilder::new(instruction.opcode, b, c)
+                .with_clk(clk)
+                .with_result(a)
+                .build()
+                .unwrap()
+        });
+        ((event, alu_event), (built, built_alu))
+    }
+
+    fn assert_same_bytes<T: serde::Serialize>(found: &T, expected: &T, src: &str) {
+        assert_eq!(
+            bincode::serialize(found).unwrap(),
+            bincode::serialize(expected).unwrap(),
+            "{}",
+            src
+        );
+    }
+
+    #[test]
+    fn test_builder_matches_runtime() {
+        let programs = [
+            // Registers read and written by the previous instructions.
+            (
+                "li   t1, 5
+                 li   t2, 7
+                 add  t0, t1, t2",
+                8,
+            ),
+            // A register read twice and written.
+            (
+                "li   t1, 5
+                 add  t1, t1, t1",
+                4,
+            ),
+            (
+                "li   t1, 5
+                 addi t0, t1, -6",
+                4,
+            ),
+            // A byte stored over a word written before, and a byte loaded from one.
+            (
+                "li   t1, 0x1000
+                 li   t2, 0x12345678
+                 sw   t2, 0(t1)
+                 li   t2, 0xab
+                 sb   t2, 2(t1)",
+                16,
+            ),
+            (
+                "li   t1, 0x1000
+                 li   t2, -1
+                 sw   t2, 0(t1)
+                 lb   t0, 1(t1)",
+                12,
+            ),
+            (
+                "       li   t1, 3
+                        li   t2, 3
+                        beq  t1, t2, end
+                        nop
+                 end:   nop",
+                8,
+            ),
+            (
+                "       call f
+                 f:     nop",
+                0,
+            ),
+        ];
+        for (src, pc) in programs {
+            let ((event, alu_event), (built, built_alu)) = run_and_build(src, pc);
+            assert_same_bytes(&built, &event, src);
+            assert_eq!(built_alu.is_some(), alu_event.is_some(), "{}", src);
+            if let (Some(built_alu), Some(alu_event)) = (built_alu, alu_event) {
+                assert_same_bytes(&built_alu, &alu_event, src);
+            }
+        }
+    }
+
+    #[test]
+    fn test_builder_violations() {
+        let add = Instruction::new(Opcode::ADD, 5, 6, 7, false, false);
+        let violation = CpuEventBuilder::new(1, 5, 0, add)
+            .with_operands(3, 1, 1)
+            .with_prev(6, 1, 1, 1)
+            .with_prev(7, 1, 1, 1)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            violation,
+            EventViolation::WrongResult {
+                expected: 2,
+                found: 3
+            }
+        );
+
+        // A register read with another value than the one it holds.
+        let violation = CpuEventBuilder::new(1, 5, 0, add)
+            .with_operands(2, 1, 1)
+            .with_prev(7, 4, 1, 1)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            violation,
+            EventViolation::WrongValue {
+                position: AccessPosition::C,
+                expected: 1,
+                found: 4
+            }
+        );
+
+        // A register accessed after the instruction.
+        let violation = CpuEventBuilder::new(1, 5, 0, add)
+            .with_operands(2, 1, 1)
+            .with_prev(6, 1, 1, 9)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            violation,
+            EventViolation::WrongPrevTimestamp {
+                position: AccessPosition::B,
+                timestamp: (1, 7),
+                prev: (1, 9)
+            }
+        );
+
+        assert_eq!(
+            AluEventBuilder::new(Opcode::SUB, 1, 2)
+                .with_result(0)
+                .build()
+                .unwrap_err(),
+            EventViolation::WrongResult {
+                expected: u32::MAX,
+                found: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_synthesize_simple_alu() {
+        let (cpu_event, alu_event) = synthesize_simple_alu(Opcode::DIVU, 7, 2, 1, 5);
+        assert_eq!((alu_event.a, alu_event.clk), (3, 5));
+        assert_eq!(
+            DivRemMetadata::new(Opcode::DIVU, 7, 2).result(Opcode::DIVU),
+            alu_event.a
+        );
+        assert_eq!((cpu_event.a, cpu_event.b, cpu_event.c), (3, 7, 2));
+        let Some(MemoryRecordEnum::Write(record)) = cpu_event.a_record else {
+            panic!("the result is not written");
+        };
+        assert_eq!((record.shard, record.timestamp), (1, 8));
+    }
+}
@@ -0,0 +1,162 @@
+use core::ops::{BitOr, BitOrAssign};
+use serde::{Deserialize, Serialize};
+
+use super::Opcode;
+
+/// The RISC-V extensions beyond RV32IM a program may use, see [`Program::extensions`].
+///
+/// Their instructions are rejected when decoding an ELF and when validating a program unless the
+/// extension is enabled. None of them can be proven yet: their events are kept in the record for
+/// a future chip.
+///
+/// [`Program::extensions`]: super::Program::extensions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Extensions(u32);
+
+impl Extensions {
+    /// The ANDN, ORN, XNOR, CLZ, CTZ, CPOP, MIN, MAX, MINU and MAXU instructions of Zbb.
+    pub const ZBB: Self = Self(1 << 0);
+
+    /// Only RV32IM.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Whether every extension in `other` is enabled.
+    #[inline(always)]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether an instruction with `opcode` may be used.
+    pub fn allows(self, opcode: Opcode) -> bool {
+        !opcode.is_bitmanip() || self.contains(Self::ZBB)
+    }
+}
+
+impl BitOr for Extensions {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Extensions {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Error, Instruction, InstructionError, Program, Register, Runtime};
+
+    fn zbb_program(opcode: Opcode, b: u32, c: u32) -> Program {
+        let instruction = match opcode {
+            Opcode::CLZ | Opcode::CTZ | Opcode::CPOP => {
+                Instruction::new(opcode, 12, 10, 0, false, true)
+            }
+            _ => Instruction::new(opcode, 12, 10, 11, false, false),
+        };
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 10, 0, b, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, c, false, true),
+            instruction,
+        ];
+        let mut program = Program::new(instructions, 0, 0);
+        program.extensions = Extensions::ZBB;
+        program
+    }
+
+    /// Execute `opcode` on `b` and `c`, or on `b` alone for the unary instructions, checking that
+    /// its event matches, and return the result.
+    fn execute(opcode: Opcode, b: u32, c: u32) -> u32 {
+        let mut runtime = Runtime::new(zbb_program(opcode, b, c));
+        runtime.set_event_validation(true);
+        runtime.run();
+        let result = runtime.register(Register::X12);
+        let event = &runtime.record.bitmanip_events[0];
+        assert_eq!(runtime.record.bitmanip_events.len(), 1);
+        assert_eq!((event.opcode, event.a, event.b), (opcode, result, b));
+        result
+    }
+
+    #[test]
+    fn test_logic() {
+        assert_eq!(execute(Opcode::ANDN, 0, 0), 0);
+        assert_eq!(execute(Opcode::ANDN, u32::MAX, 0), u32::MAX);
+        assert_eq!(execute(Opcode::ANDN, u32::MAX, u32::MAX), 0);
+        assert_eq!(execute(Opcode::ANDN, 0xf0f0, 0x00ff), 0xf000);
+        assert_eq!(execute(Opcode::ORN, 0, 0), u32::MAX);
+        assert_eq!(execute(Opcode::ORN, 0, u32::MAX), 0);
+        assert_eq!(execute(Opcode::ORN, 1, u32::MAX - 1), 1);
+        assert_eq!(execute(Opcode::XNOR, 0, 0), u32::MAX);
+        assert_eq!(execute(Opcode::XNOR, u32::MAX, 0), 0);
+        assert_eq!(execute(Opcode::XNOR, 1 << 31, 1 << 31), u32::MAX);
+    }
+
+    #[test]
+    fn test_counts() {
+        for (b, clz, ctz, cpop) in [
+            (0, 32, 32, 0),
+            (u32::MAX, 0, 0, 32),
+            (1, 31, 0, 1),
+            (1 << 31, 0, 31, 1),
+            (1 << 4, 27, 4, 1),
+        ] {
+            assert_eq!(execute(Opcode::CLZ, b, 0), clz);
+            assert_eq!(execute(Opcode::CTZ, b, 0), ctz);
+            assert_eq!(execute(Opcode::CPOP, b, 0), cpop);
+        }
+
+        // The count instructions carry the bits of their operand.
+        let mut runtime = Runtime::new(zbb_program(Opcode::CPOP, (1 << 31) | 1, 0));
+        runtime.run();
+        let bits = runtime.record.bitmanip_events[0].bits.unwrap();
+        assert!(bits[0] && bits[31]);
+        assert_eq!(bits.iter().filter(|bit| **bit).count(), 2);
+        let mut runtime = Runtime::new(zbb_program(Opcode::MIN, 1, 2));
+        runtime.run();
+        assert!(runtime.record.bitmanip_events[0].bits.is_none());
+    }
+
+    #[test]
+    fn test_min_max() {
+        assert_eq!(execute(Opcode::MIN, 0, u32::MAX), u32::MAX);
+        assert_eq!(execute(Opcode::MAX, 0, u32::MAX), 0);
+        assert_eq!(execute(Opcode::MINU, 0, u32::MAX), 0);
+        assert_eq!(execute(Opcode::MAXU, 0, u32::MAX), u32::MAX);
+        assert_eq!(execute(Opcode::MIN, 1 << 31, i32::MAX as u32), 1 << 31);
+        assert_eq!(
+            execute(Opcode::MAX, 1 << 31, i32::MAX as u32),
+            i32::MAX as u32
+        );
+        assert_eq!(
+            execute(Opcode::MINU, 1 << 31, i32::MAX as u32),
+            i32::MAX as u32
+        );
+        assert_eq!(execute(Opcode::MAXU, 1 << 31, i32::MAX as u32), 1 << 31);
+        assert_eq!(execute(Opcode::MIN, 1, 1), 1);
+        assert_eq!(execute(Opcode::MAXU, 0, 0), 0);
+    }
+
+    #[test]
+    fn test_extension_disabled() {
+        let mut program = zbb_program(Opcode::ANDN, 1, 2);
+        program.extensions = Extensions::empty();
+        let Err(Error::Program(err)) = Runtime::try_new(program) else {
+            panic!("expected the program to be rejected");
+        };
+        assert_eq!(err.invalid.len(), 1);
+        assert_eq!(
+            err.invalid[0].error,
+            InstructionError::ExtensionDisabled {
+                opcode: Opcode::ANDN
+            }
+        );
+        assert!(Extensions::empty().allows(Opcode::ADD));
+        assert!(!Extensions::default().allows(Opcode::CPOP));
+    }
+}
@@ -0,0 +1,213 @@
+use core::fmt::{Display, Formatter};
+use std::collections::BTreeMap;
+
+use super::Runtime;
+use crate::alu::AluEvent;
+use crate::cpu::CpuEvent;
+
+/// The modulus of the BabyBear field the events are proven over.
+pub const BABYBEAR_MODULUS: u32 = 0x7800_0001;
+
+/// An address that is not representable as a BabyBear element, see [`check_addr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldRangeError {
+    pub addr: u32,
+}
+
+impl Display for FieldRangeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "address 0x{:x} is not below the BabyBear modulus 0x{:x}",
+            self.addr, BABYBEAR_MODULUS
+        )
+    }
+}
+
+impl std::error::Error for FieldRangeError {}
+
+/// Check that the address of a word is representable as a BabyBear element, as the memory
+/// argument requires. This is the only place the range of addresses is decided.
+#[inline]
+pub fn check_addr(addr: u32) -> Result<(), FieldRangeError> {
+    if addr < BABYBEAR_MODULUS {
+        Ok(())
+    } else {
+        Err(FieldRangeError { addr })
+    }
+}
+
+/// Whether `value` is reduced when it is converted to a BabyBear element as a whole rather than
+/// as bytes, so that the field element no longer determines it.
+#[inline]
+pub fn check_value_reducible(value: u32) -> bool {
+    value >= BABYBEAR_MODULUS
+}
+
+/// The events emitted with a value that is converted to a BabyBear element as a whole and is
+/// reduced, see [`Runtime::set_field_range_checks`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldRangeSummary {
+    /// The number of such events.
+    pub events: u64,
+
+    /// The number of such events by the pc of their instruction.
+    pub pcs: BTreeMap<u32, u64>,
+}
+
+impl FieldRangeSummary {
+    fn record(&mut self, pc: u32) {
+        self.events += 1;
+        *self.pcs.entry(pc).or_default() += 1;
+    }
+}
+
+impl Runtime {
+    /// Check the values of every CPU and ALU event that are converted to BabyBear elements as a
+    /// whole, such as the pc, the clock and the address of memory accesses, and count the events
+    /// where one is reduced, see [`Runtime::field_range_summary`]. Execution goes on, and a
+    /// warning with the count is logged once it finishes.
+    ///
+    /// The operands are decomposed into bytes, so they are never reduced. This is meant for
+    /// debugging runtime changes and custom syscalls.
+    pub fn set_field_range_checks(&mut self, enabled: bool) {
+        self.field_range = enabled.then(FieldRangeSummary::default);
+    }
+
+    /// The events emitted so far with a reduced value, if [`Runtime::set_field_range_checks`]
+    /// is enabled.
+    pub fn field_range_summary(&self) -> Option<&FieldRangeSummary> {
+        self.field_range.as_ref()
+    }
+
+    /// Count `event` if one of its values converted to a field element is reduced.
+    pub(crate) fn check_cpu_event_range(&mut self, event: &CpuEvent) {
+        let Some(summary) = &mut self.field_range else {
+            return;
+        };
+        if self.unconstrained {
+            return;
+        }
+        let addr = event
+            .instruction
+            .is_memory_instruction()
+            .then(|| event.b.wrapping_add(event.c) & !3);
+        let reduced = [event.shard, event.clk, event.pc]
+            .into_iter()
+            .chain(addr)
+            .any(check_value_reducible);
+        if reduced {
+            summary.record(event.pc);
+        }
+    }
+
+    /// Count `event` if one of its values converted to a field element is reduced.
+    pub(crate) fn check_alu_event_range(&mut self, event: &AluEvent) {
+        let Some(summary) = &mut self.field_range else {
+            return;
+        };
+        if !self.unconstrained && check_value_reducible(event.clk) {
+            summary.record(self.state.pc);
+        }
+    }
+
+    /// Log a warning if events with reduced values were emitted.
+    pub(crate) fn warn_field_range(&self) {
+        let Some(summary) = &self.field_range else {
+            return;
+        };
+        if summary.events > 0 {
+            let pcs = summary
+                .pcs
+                .iter()
+                .map(|(pc, count)| format!("0x{:x} ({})", pc, count))
+                .collect::<Vec<_>>();
+            tracing::warn!(
+                "{} events have values reduced by the BabyBear field, at pcs {}",
+                summary.events,
+                pcs.join(", ")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::{AccessPosition, ExecutionError};
+    use crate::utils::asm::assemble;
+
+    #[test]
+    fn test_check_addr() {
+        assert_eq!(check_addr(BABYBEAR_MODULUS - 1), Ok(()));
+        assert_eq!(
+            check_addr(BABYBEAR_MODULUS),
+            Err(FieldRangeError {
+                addr: BABYBEAR_MODULUS
+            })
+        );
+        assert!(!check_value_reducible(BABYBEAR_MODULUS - 1));
+        assert!(check_value_reducible(u32::MAX));
+    }
+
+    #[test]
+    fn test_access_past_modulus() {
+        // The word at 0x78000000 is the last one below the modulus.
+        let program = assemble(
+            "li   t1, 0x78000000
+             lw   t0, 0(t1)
+             lw   t0, 4(t1)",
+            0,
+        )
+        .unwrap();
+        let mut runtime = Runtime::new(program);
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::AddressOutOfRange {
+                base: 0x7800_0000,
+                offset: 4,
+                effective: 0x7800_0004,
+                pc: 8
+            })
+        );
+
+        // Accesses made without computing an effective address stop execution as well.
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.initialize();
+        assert_eq!(runtime.mr_cpu(0x7800_0004, AccessPosition::Memory), 0);
+        assert_eq!(
+            runtime.pending_error,
+            Some(ExecutionError::AddressOutOfRange {
+                base: 0x7800_0004,
+                offset: 0,
+                effective: 0x7800_0004,
+                pc: runtime.state.pc
+            })
+        );
+        assert!(runtime.state.memory.get(&0x7800_0004).is_none());
+    }
+
+    #[test]
+    fn test_field_range_summary() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.set_field_range_checks(true);
+        runtime.run();
+        assert_eq!(
+            runtime.field_range_summary(),
+            Some(&FieldRangeSummary::default())
+        );
+
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.set_field_range_checks(true);
+        runtime.event_tamper = Some(|event| {
+            if event.shard == 1 && event.clk == 5 {
+                event.pc = BABYBEAR_MODULUS;
+            }
+        });
+        runtime.run();
+        let summary = runtime.field_range_summary().unwrap();
+        assert_eq!(summary.events, 1);
+        assert_eq!(summary.pcs, BTreeMap::from([(BABYBEAR_MODULUS, 1)]));
+    }
+}
@@ -0,0 +1,156 @@
+use core::ops::{BitOr, BitOrAssign};
+
+/// Which classes of events are recorded in the [`ExecutionRecord`](super::ExecutionRecord) during
+/// execution and trace generation.
+///
+/// Filtering out events makes execution cheaper for consumers that only need part of the record,
+/// but the record can only be proven if every class in [`RecordFilter::provable`] is recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecordFilter(u16);
+
+impl RecordFilter {
+    /// CPU events.
+    pub const CPU: Self = Self(1 << 0);
+    /// ADD events.
+    pub const ADD: Self = Self(1 << 1);
+    /// SUB events.
+    pub const SUB: Self = Self(1 << 2);
+    /// XOR, OR and AND events.
+    pub const BITWISE: Self = Self(1 << 3);
+    /// SLL, SRL and SRA events.
+    pub const SHIFT: Self = Self(1 << 4);
+    /// SLT and SLTU events.
+    pub const LT: Self = Self(1 << 5);
+    /// MUL, MULH, MULHU and MULHSU events.
+    pub const MUL: Self = Self(1 << 6);
+    /// DIV, DIVU, REM and REMU events.
+    pub const DIVREM: Self = Self(1 << 7);
+    /// Byte lookups, added while generating traces.
+    pub const BYTE_LOOKUPS: Self = Self(1 << 8);
+    /// The first, last and program memory records computed once execution finishes.
+    pub const MEMORY: Self = Self(1 << 9);
+    /// Events of the precompile syscalls.
+    pub const PRECOMPILES: Self = Self(1 << 10);
+    /// Events of the Zbb bit manipulation instructions.
+    pub const BITMANIP: Self = Self(1 << 11);
+
+    /// Every ALU event class.
+    pub const ALU: Self = Self(
+        Self::ADD.0
+            | Self::SUB.0
+            | Self::BITWISE.0
+            | Self::SHIFT.0
+            | Self::LT.0
+            | Self::MUL.0
+            | Self::DIVREM.0,
+    );
+
+    /// Record nothing.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Record every class of events.
+    pub const fn all() -> Self {
+        Self((1 << 12) - 1)
+    }
+
+    /// The classes of events that must be recorded for the record to be provable.
+    ///
+    /// Every chip's trace is built from its events and the CPU, memory and byte lookup chips
+    /// interact with all of them, so a missing class makes the lookup arguments fail: this is
+    /// [`RecordFilter::all`].
+    pub const fn provable() -> Self {
+        Self::all()
+    }
+
+    /// Whether every class in `other` is recorded.
+    #[inline(always)]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether a record produced with this filter can be proven.
+    pub const fn is_provable(self) -> bool {
+        self.contains(Self::provable())
+    }
+
+    /// Start recording the classes in `other`.
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Stop recording the classes in `other`.
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl Default for RecordFilter {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl BitOr for RecordFilter {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for RecordFilter {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.insert(rhs);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::alu::AluEvent;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::Runtime;
+
+    #[test]
+    fn test_record_filter() {
+        let mut filter = RecordFilter::CPU | RecordFilter::ADD;
+        assert!(filter.contains(RecordFilter::ADD));
+        assert!(!filter.contains(RecordFilter::ALU));
+        filter.remove(RecordFilter::CPU);
+        assert_eq!(filter, RecordFilter::ADD);
+        assert!(!filter.is_provable());
+        assert!(RecordFilter::default().is_provable());
+        assert!(RecordFilter::all().contains(RecordFilter::ALU | RecordFilter::PRECOMPILES));
+    }
+
+    #[test]
+    fn test_alu_only_filter() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.run();
+
+        let mut filtered = Runtime::new(fibonacci_program());
+        filtered.record_filter = RecordFilter::ALU;
+        filtered.run();
+
+        let values = |events: &[AluEvent]| {
+            events
+                .iter()
+                .map(|event| (event.clk, event.opcode, event.a, event.b, event.c))
+                .collect::<Vec<_>>()
+        };
+        assert!(filtered.record.cpu_events.is_empty());
+        assert!(filtered.record.last_memory_record.is_empty());
+        assert!(!runtime.record.add_events.is_empty());
+        assert_eq!(
+            values(&filtered.record.add_events),
+            values(&runtime.record.add_events)
+        );
+        assert_eq!(
+            values(&filtered.record.lt_events),
+            values(&runtime.record.lt_events)
+        );
+        assert_eq!(filtered.state.global_clk, runtime.state.global_clk);
+    }
+}
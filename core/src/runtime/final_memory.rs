@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Error, ExecutionRecord, MAX_REGISTER_ADDR};
+
+/// The memory of the guest at the end of an execution, see [`ExecutionRecord::final_memory`].
+///
+/// Words the guest never accessed read as zero, and the addresses reserved for registers are
+/// rejected, so that assertions on the final memory cannot confuse the two.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalMemory {
+    /// The aligned address and final value of every word in the record, sorted by address.
+    words: Vec<(u32, u32)>,
+}
+
+impl ExecutionRecord {
+    /// The final value of every word of memory in the record: that of `last_memory_record`, or
+    /// that of `program_memory_record` for the words of the image without a final record, such as
+    /// those the guest never used. The record must have been finalized.
+    pub fn final_memory(&self) -> FinalMemory {
+        let mut words = self
+            .last_memory_record
+            .iter()
+            .chain(self.program_memory_record.iter())
+            .filter(|(addr, _, _)| *addr > MAX_REGISTER_ADDR)
+            .map(|(addr, record, _)| (*addr, record.value))
+            .collect::<Vec<_>>();
+        // The sort is stable, so the final record of a word comes before its image entry and is
+        // the one kept.
+        words.sort_by_key(|(addr, _)| *addr);
+        words.dedup_by_key(|(addr, _)| *addr);
+        FinalMemory { words }
+    }
+}
+
+impl FinalMemory {
+    /// The number of words in the record.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// The final value of the word at `addr`, which must be aligned.
+    pub fn word(&self, addr: u32) -> Result<u32, Error> {
+        if addr % 4 != 0 {
+            return Err(Error::UnalignedAddress { addr });
+        }
+        check_range(addr, 4)?;
+        Ok(self.word_unchecked(addr))
+    }
+
+    /// The final value of the byte at `addr`.
+    pub fn byte(&self, addr: u32) -> Result<u8, Error> {
+        check_range(addr, 1)?;
+        Ok(self.byte_unchecked(addr))
+    }
+
+    /// The final values of the `len` bytes starting at `addr`.
+    pub fn slice(&self, addr: u32, len: usize) -> Result<Vec<u8>, Error> {
+        check_range(addr, len)?;
+        Ok((0..len as u32)
+            .map(|offset| self.byte_unchecked(addr + offset))
+            .collect())
+    }
+
+    /// The little-endian `u64` in the 8 bytes starting at `addr`.
+    pub fn u64_le(&self, addr: u32) -> Result<u64, Error> {
+        let bytes = self.slice(addr, 8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// The words in the record with their final value, sorted by address.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.words.iter().copied()
+    }
+
+    fn word_unchecked(&self, addr: u32) -> u32 {
+        match self.words.binary_search_by_key(&addr, |(addr, _)| *addr) {
+            Ok(index) => self.words[index].1,
+            Err(_) => 0,
+        }
+    }
+
+    fn byte_unchecked(&self, addr: u32) -> u8 {
+        let word = self.word_unchecked(addr & !3);
+        (word >> ((addr % 4) * 8)) as u8
+    }
+}
+
+/// Check that the `len` bytes starting at `addr` fit in the address space and are not reserved
+/// for registers.
+fn check_range(addr: u32, len: usize) -> Result<(), Error> {
+    if addr & !3 <= MAX_REGISTER_ADDR {
+        return Err(Error::RegisterAddress { addr });
+    }
+    let fits = u32::try_from(len.saturating_sub(1))
+        .ok()
+        .and_then(|last| addr.checked_add(last))
+        .is_some();
+    if !fits {
+        return Err(Error::AddressOverflow { addr, len });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+    use crate::utils::asm::assemble;
+
+    /// Reads the word of the image at 0x2004 and stores it plus one at 0x2010, leaving the word of
+    /// the image at 0x2000 unused.
+    fn image_runtime(sparse: bool) -> Runtime {
+        let mut program = assemble(
+            "li   t0, 0x2004
+             lw   t1, 0(t0)
+             addi t1, t1, 1
+             sw   t1, 12(t0)",
+            0,
+        )
+        .unwrap();
+        program.memory_image.insert(0x2000, 0xdeadbeef);
+        program.memory_image.insert(0x2004, 0x0807_0605);
+        let mut runtime = Runtime::new(program);
+        runtime.set_sparse_memory_records(sparse);
+        runtime.run();
+        runtime
+    }
+
+    #[test]
+    fn test_image_backed() {
+        for sparse in [false, true] {
+            let memory = image_runtime(sparse).record.final_memory();
+            assert_eq!(memory.word(0x2000).unwrap(), 0xdeadbeef);
+            assert_eq!(memory.word(0x2004).unwrap(), 0x0807_0605);
+            assert_eq!(memory.word(0x2010).unwrap(), 0x0807_0606);
+            assert_eq!(memory.byte(0x2003).unwrap(), 0xde);
+            assert_eq!(memory.u64_le(0x2000).unwrap(), 0x0807_0605_dead_beef);
+        }
+    }
+
+    #[test]
+    fn test_never_touched() {
+        let memory = image_runtime(false).record.final_memory();
+        assert_eq!(memory.word(0x2008).unwrap(), 0);
+        assert_eq!(memory.byte(0xffff_ffff).unwrap(), 0);
+        assert!(ExecutionRecord::default().final_memory().is_empty());
+
+        assert!(matches!(
+            memory.word(0x2002),
+            Err(Error::UnalignedAddress { addr: 0x2002 })
+        ));
+        assert!(matches!(
+            memory.byte(5),
+            Err(Error::RegisterAddress { addr: 5 })
+        ));
+        assert!(matches!(
+            memory.word(40),
+            Err(Error::RegisterAddress { addr: 40 })
+        ));
+        assert!(matches!(
+            memory.u64_le(0xffff_fffc),
+            Err(Error::AddressOverflow { len: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn test_slice_across_words() {
+        let memory = image_runtime(false).record.final_memory();
+        assert_eq!(
+            memory.slice(0x2002, 16).unwrap(),
+            vec![0xad, 0xde, 5, 6, 7, 8, 0, 0, 0, 0, 0, 0, 0, 0, 6, 6]
+        );
+        assert!(memory.slice(0x2000, 0).unwrap().is_empty());
+        let addrs = memory.iter().map(|(addr, _)| addr).collect::<Vec<_>>();
+        assert_eq!(addrs, vec![0x2000, 0x2004, 0x2010]);
+
+        let bytes = bincode::serialize(&memory).unwrap();
+        let deserialized: FinalMemory = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(deserialized, memory);
+    }
+}
@@ -0,0 +1,268 @@
+use core::fmt::{Display, Formatter};
+
+use super::{AccessPosition, RecordFilter, Runtime, MAX_REGISTER_ADDR, NUM_REGISTERS};
+
+/// An invariant the state of a finished execution breaks, found by
+/// [`Runtime::check_final_invariants`]. These come from bookkeeping bugs in the runtime or its
+/// syscalls, such as a syscall accessing memory at a clock it then rewinds, and otherwise only
+/// surface as proving failures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinalStateViolation {
+    /// A word of memory was last accessed after the final `(shard, clk)` of the execution.
+    AccessAfterEnd {
+        addr: u32,
+        shard: u32,
+        timestamp: u32,
+        end: (u32, u32),
+    },
+
+    /// An address in the range reserved for registers that is not a register.
+    InvalidRegisterAddress { addr: u32 },
+
+    /// An access of a CPU event of the final shard is at a clock beyond the final one.
+    EventAfterEnd { pc: u32, clk: u32, end: u32 },
+
+    /// The number of CPU events differs from the number of instructions executed outside of
+    /// unconstrained blocks.
+    EventCountMismatch { events: usize, global_clk: u32 },
+}
+
+impl Display for FinalStateViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FinalStateViolation::AccessAfterEnd {
+                addr,
+                shard,
+                timestamp,
+                end,
+            } => write!(
+                f,
+                "0x{:x} is last accessed at (shard, clk) {:?}, after the end of execution at {:?}",
+                addr,
+                (shard, timestamp),
+                end
+            ),
+            FinalStateViolation::InvalidRegisterAddress { addr } => write!(
+                f,
+                "0x{:x} is reserved for registers but is not a register",
+                addr
+            ),
+            FinalStateViolation::EventAfterEnd { pc, clk, end } => write!(
+                f,
+                "the event at pc=0x{:x} accesses clk {}, after the final clk {}",
+                pc, clk, end
+            ),
+            FinalStateViolation::EventCountMismatch { events, global_clk } => write!(
+                f,
+                "{} cpu events were recorded for {} instructions",
+                events, global_clk
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FinalStateViolation {}
+
+impl Runtime {
+    /// Check the state after the last instruction before [`Runtime::finalize`] is called, and
+    /// panic with every violation found at the end of [`Runtime::run`]. This is meant for
+    /// debugging the runtime and syscalls. It is also enabled by setting
+    /// `CHECK_FINAL_INVARIANTS=true`.
+    pub fn set_final_invariant_checks(&mut self, enabled: bool) {
+        self.final_invariant_checks = enabled;
+    }
+
+    /// Checks that no word of memory was accessed after the final `(shard, clk)`, that the only
+    /// addresses reserved for registers in use are those of the 32 registers, and, when the CPU
+    /// events were recorded, that there is one per instruction and none of the final shard
+    /// accesses a clock beyond the final one.
+    pub fn check_final_invariants(&self) -> Result<(), Vec<FinalStateViolation>> {
+        let end = (self.state.current_shard, self.state.clk);
+        let mut violations = Vec::new();
+
+        let mut addrs = self.state.memory.keys().copied().collect::<Vec<_>>();
+        addrs.sort_unstable();
+        for addr in addrs {
+            let (_, shard, timestamp) = self.state.memory[&addr];
+            if (shard, timestamp) > end {
+                violations.push(FinalStateViolation::AccessAfterEnd {
+                    addr,
+                    shard,
+                    timestamp,
+                    end,
+                });
+            }
+            if (NUM_REGISTERS..=MAX_REGISTER_ADDR).contains(&addr) {
+                violations.push(FinalStateViolation::InvalidRegisterAddress { addr });
+            }
+        }
+
+        let recorded = self.emit_events
+            && self.record_filter.contains(RecordFilter::CPU)
+            && self.event_sink.is_none();
+        if recorded {
+            let events = &self.record.cpu_events;
+            for event in events.iter().filter(|event| event.shard == end.0) {
+                // The accesses of an instruction are at its clock plus their position.
+                let clk = event.clk + AccessPosition::A as u32;
+                if clk > end.1 {
+                    violations.push(FinalStateViolation::EventAfterEnd {
+                        pc: event.pc,
+                        clk,
+                        end: end.1,
+                    });
+                }
+            }
+            // The clocks and events of unconstrained blocks are rolled back when they exit.
+            if events.len() != self.state.global_clk as usize {
+                violations.push(FinalStateViolation::EventCountMismatch {
+                    events: events.len(),
+                    global_clk: self.state.global_clk,
+                });
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Panic with every violation of [`Runtime::check_final_invariants`].
+    pub(crate) fn assert_final_invariants(&self) {
+        if let Err(violations) = self.check_final_invariants() {
+            let report = violations
+                .iter()
+                .map(|violation| format!("\n  {}", violation))
+                .collect::<String>();
+            panic!("invalid final state:{}", report);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runtime::tests::{fibonacci_program, simple_memory_program};
+    use crate::runtime::{Syscall, SyscallCode, SyscallContext};
+    use crate::utils::asm::assemble;
+
+    /// Writes a0 to the word at 0x1000 at a clock 100 cycles later than its own, and, if a1 is not
+    /// zero, to the address 36 reserved for registers.
+    struct RewindingSyscall;
+
+    impl Syscall for RewindingSyscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            let args = ctx.args();
+            let (shard, clk) = (ctx.current_shard(), ctx.clk());
+            ctx.rt.mw(0x1000, args.a0, shard, clk + 100);
+            if args.a1 != 0 {
+                ctx.rt.state.memory.insert(36, (args.a1, shard, clk));
+            }
+            0
+        }
+    }
+
+    fn run_rewinding_syscall(a1: u32) -> Runtime {
+        let program = assemble(
+            &format!(
+                "
+                li   t0, {}
+                li   a0, 7
+                li   a1, {}
+                ecall
+                ",
+                SyscallCode::WRITE_CHANNEL as u32,
+                a1
+            ),
+            0,
+        )
+        .unwrap();
+        let mut runtime = Runtime::new(program);
+        runtime
+            .syscall_map
+            .insert(SyscallCode::WRITE_CHANNEL, Arc::new(RewindingSyscall));
+        runtime.run();
+        runtime
+    }
+
+    #[test]
+    fn test_valid_final_state() {
+        for program in [fibonacci_program(), simple_memory_program()] {
+            let mut runtime = Runtime::new(program);
+            runtime.set_final_invariant_checks(true);
+            runtime.run();
+            assert_eq!(runtime.check_final_invariants(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_access_after_end() {
+        let runtime = run_rewinding_syscall(0);
+        let end = (1, runtime.state.clk);
+        assert_eq!(end, (1, 17));
+        assert_eq!(
+            runtime.check_final_invariants(),
+            Err(vec![FinalStateViolation::AccessAfterEnd {
+                addr: 0x1000,
+                shard: 1,
+                timestamp: 113,
+                end,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_invalid_register_address() {
+        let violations = run_rewinding_syscall(1)
+            .check_final_invariants()
+            .unwrap_err();
+        assert_eq!(violations.len(), 2);
+        assert_eq!(
+            violations[0],
+            FinalStateViolation::InvalidRegisterAddress { addr: 36 }
+        );
+    }
+
+    #[test]
+    fn test_event_after_end() {
+        let mut runtime = Runtime::new(simple_memory_program());
+        runtime.event_tamper = Some(|event| event.clk += 100);
+        runtime.run();
+        let violations = runtime.check_final_invariants().unwrap_err();
+        assert_eq!(violations.len(), runtime.record.cpu_events.len());
+        assert!(violations
+            .iter()
+            .all(|violation| matches!(violation, FinalStateViolation::EventAfterEnd { .. })));
+    }
+
+    #[test]
+    fn test_event_count_mismatch() {
+        let mut runtime = Runtime::new(simple_memory_program());
+        runtime.run();
+        let global_clk = runtime.state.global_clk;
+        runtime.record.cpu_events.pop();
+        assert_eq!(
+            runtime.check_final_invariants(),
+            Err(vec![FinalStateViolation::EventCountMismatch {
+                events: global_clk as usize - 1,
+                global_clk,
+            }])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "after the end of execution")]
+    fn test_final_invariant_checks_panic() {
+        let program = assemble("li t0, 113\necall", 0).unwrap();
+        let mut runtime = Runtime::new(program);
+        runtime
+            .syscall_map
+            .insert(SyscallCode::WRITE_CHANNEL, Arc::new(RewindingSyscall));
+        runtime.set_final_invariant_checks(true);
+        runtime.run();
+    }
+}
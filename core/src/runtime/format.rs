@@ -0,0 +1,384 @@
+use core::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{ExecutionRecord, ExecutionState, Program, ProgramPatch};
+
+/// The length of the header preceding every encoded value: four magic bytes identifying the type,
+/// followed by a little-endian u32 holding the format version in its low 24 bits and the
+/// [`Encoding`] of the rest of the data in its top byte.
+pub(crate) const HEADER_LEN: usize = 8;
+
+/// How the data following the header of an encoded value is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// bincode with fixed-size integers, the only encoding before the others were added.
+    Raw = 0,
+
+    /// bincode with variable-length integers, which takes a byte for most of the small numbers of
+    /// the records, such as the differences between the clocks and pcs of consecutive events.
+    Compact = 1,
+
+    /// The compact encoding, deflated.
+    Compressed = 2,
+}
+
+impl Encoding {
+    /// The encoding `to_bytes` writes: compressed with the `compression` feature, which is slower
+    /// to write, and compact otherwise. Every encoding can be read either way.
+    pub const DEFAULT: Encoding = if cfg!(feature = "compression") {
+        Encoding::Compressed
+    } else {
+        Encoding::Compact
+    };
+
+    fn from_u8(encoding: u8) -> Option<Self> {
+        match encoding {
+            0 => Some(Encoding::Raw),
+            1 => Some(Encoding::Compact),
+            2 => Some(Encoding::Compressed),
+            _ => None,
+        }
+    }
+}
+
+/// An error decoding a value written with `to_bytes`.
+#[derive(Debug)]
+pub enum FormatError {
+    /// The data does not start with the header of the expected type.
+    InvalidHeader { kind: &'static str },
+
+    /// The data was written with a different version of the format.
+    VersionMismatch {
+        kind: &'static str,
+        expected: u32,
+        found: u32,
+    },
+
+    /// The header names an encoding this version does not know.
+    UnknownEncoding { kind: &'static str, encoding: u8 },
+
+    /// The header is valid but the rest of the data could not be decoded.
+    Decode {
+        kind: &'static str,
+        error: bincode::Error,
+    },
+
+    /// The data is truncated or does not decompress.
+    Corrupted { kind: &'static str },
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FormatError::InvalidHeader { kind } => write!(f, "data is not an encoded {}", kind),
+            FormatError::VersionMismatch {
+                kind,
+                expected,
+                found,
+            } => write!(
+                f,
+                "unsupported {} format version: expected {}, found {}",
+                kind, expected, found
+            ),
+            FormatError::UnknownEncoding { kind, encoding } => {
+                write!(f, "unknown {} encoding {}", kind, encoding)
+            }
+            FormatError::Decode { kind, error } => {
+                write!(f, "failed to decode {}: {}", kind, error)
+            }
+            FormatError::Corrupted { kind } => write!(f, "encoded {} is corrupted", kind),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Deflate `bytes`, favoring speed over size.
+pub(crate) fn deflate(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+pub(crate) fn inflate(bytes: &[u8], kind: &'static str) -> Result<Vec<u8>, FormatError> {
+    let mut inflated = Vec::new();
+    flate2::read::DeflateDecoder::new(bytes)
+        .read_to_end(&mut inflated)
+        .map_err(|_| FormatError::Corrupted { kind })?;
+    Ok(inflated)
+}
+
+pub(crate) fn write_header(bytes: &mut Vec<u8>, magic: [u8; 4], version: u32, encoding: Encoding) {
+    bytes.extend_from_slice(&magic);
+    bytes.extend_from_slice(&(version | (encoding as u32) << 24).to_le_bytes());
+}
+
+/// Checks the magic bytes and returns the format version and the encoding.
+pub(crate) fn read_header(
+    bytes: &[u8],
+    kind: &'static str,
+    magic: [u8; 4],
+) -> Result<(u32, Encoding), FormatError> {
+    if bytes.len() < HEADER_LEN || bytes[..4] != magic {
+        return Err(FormatError::InvalidHeader { kind });
+    }
+    let word = u32::from_le_bytes(bytes[4..HEADER_LEN].try_into().unwrap());
+    let encoding = (word >> 24) as u8;
+    let encoding =
+        Encoding::from_u8(encoding).ok_or(FormatError::UnknownEncoding { kind, encoding })?;
+    Ok((word & 0x00ff_ffff, encoding))
+}
+
+/// A type with a versioned binary encoding.
+trait Versioned: Serialize + DeserializeOwned {
+    /// A name for the type used in errors.
+    const KIND: &'static str;
+
+    /// Identifies the type of an encoded value.
+    const MAGIC: [u8; 4];
+
+    /// Must be incremented whenever the serialized layout of the type changes.
+    const VERSION: u32;
+
+    fn encode(&self, encoding: Encoding) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        write_header(&mut bytes, Self::MAGIC, Self::VERSION, encoding);
+        match encoding {
+            Encoding::Raw => bincode::serialize_into(&mut bytes, self),
+            Encoding::Compact => bincode::DefaultOptions::new().serialize_into(&mut bytes, self),
+            Encoding::Compressed => {
+                let compact = bincode::DefaultOptions::new()
+                    .serialize(self)
+                    .expect("serialization failed");
+                bytes.extend(deflate(&compact));
+                Ok(())
+            }
+        }
+        .expect("serialization failed");
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, FormatError> {
+        let (version, encoding) = read_header(bytes, Self::KIND, Self::MAGIC)?;
+        if version != Self::VERSION {
+            return Err(FormatError::VersionMismatch {
+                kind: Self::KIND,
+                expected: Self::VERSION,
+                found: version,
+            });
+        }
+        let data = &bytes[HEADER_LEN..];
+        match encoding {
+            Encoding::Raw => bincode::deserialize(data),
+            Encoding::Compact => bincode::DefaultOptions::new().deserialize(data),
+            Encoding::Compressed => {
+                bincode::DefaultOptions::new().deserialize(&inflate(data, Self::KIND)?)
+            }
+        }
+        .map_err(|error| FormatError::Decode {
+            kind: Self::KIND,
+            error,
+        })
+    }
+}
+
+impl Versioned for Program {
+    const KIND: &'static str = "program";
+    const MAGIC: [u8; 4] = *b"SP1P";
+    const VERSION: u32 = 6;
+}
+
+impl Versioned for ExecutionState {
+    const KIND: &'static str = "execution state";
+    const MAGIC: [u8; 4] = *b"SP1S";
+    const VERSION: u32 = 2;
+}
+
+impl Versioned for ExecutionRecord {
+    const KIND: &'static str = "execution record";
+    const MAGIC: [u8; 4] = *b"SP1R";
+    const VERSION: u32 = 11;
+}
+
+impl Versioned for ProgramPatch {
+    const KIND: &'static str = "program patch";
+    const MAGIC: [u8; 4] = *b"SP1D";
+    const VERSION: u32 = 1;
+}
+
+impl Program {
+    /// Encode the program in a versioned binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode(Encoding::DEFAULT)
+    }
+
+    /// Decode a program written by `to_bytes`.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        Self::decode(bytes)
+    }
+}
+
+impl ExecutionState {
+    /// Encode the state in a versioned binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode(Encoding::DEFAULT)
+    }
+
+    /// Decode a state written by `to_bytes`.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        Self::decode(bytes)
+    }
+}
+
+impl ExecutionRecord {
+    /// Encode the record, including its program, in a versioned binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode(Encoding::DEFAULT)
+    }
+
+    /// Decode a record written by `to_bytes`.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        Self::decode(bytes)
+    }
+}
+
+impl ProgramPatch {
+    /// Encode the patch in a versioned binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode(Encoding::DEFAULT)
+    }
+
+    /// Decode a patch written by `to_bytes`.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        Self::decode(bytes)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::Runtime;
+    use crate::utils::tests::{
+        BLAKE3_COMPRESS_ELF, ED_ADD_ELF, ED_DECOMPRESS_ELF, KECCAK_PERMUTE_ELF, SECP256K1_ADD_ELF,
+        SECP256K1_DECOMPRESS_ELF, SECP256K1_DOUBLE_ELF, SHA_COMPRESS_ELF, SHA_EXTEND_ELF,
+    };
+
+    #[test]
+    fn test_program_round_trip() {
+        let program = fibonacci_program();
+        let bytes = program.to_bytes();
+        let decoded = Program::try_from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.pc_start, program.pc_start);
+        assert_eq!(decoded.pc_base, program.pc_base);
+        assert_eq!(decoded.memory_image, program.memory_image);
+        assert_eq!(decoded.symbols, program.symbols);
+        assert_eq!(decoded.instructions.len(), program.instructions.len());
+        assert_eq!(decoded.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_record_and_state_round_trip() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.run();
+
+        let bytes = runtime.record.to_bytes();
+        let record = ExecutionRecord::try_from_bytes(&bytes).unwrap();
+        assert_eq!(record.cpu_events.len(), runtime.record.cpu_events.len());
+        assert_eq!(record.add_events.len(), runtime.record.add_events.len());
+        assert_eq!(record.byte_lookups, runtime.record.byte_lookups);
+        assert_eq!(record.to_bytes(), bytes);
+
+        let bytes = runtime.state.to_bytes();
+        let state = ExecutionState::try_from_bytes(&bytes).unwrap();
+        assert_eq!(state.global_clk, runtime.state.global_clk);
+        assert_eq!(state.memory, runtime.state.memory);
+        assert_eq!(state.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_corrupted_header() {
+        let mut bytes = fibonacci_program().to_bytes();
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] ^= 0xff;
+        assert!(matches!(
+            Program::try_from_bytes(&wrong_magic),
+            Err(FormatError::InvalidHeader { kind: "program" })
+        ));
+        assert!(matches!(
+            ExecutionRecord::try_from_bytes(&bytes),
+            Err(FormatError::InvalidHeader { .. })
+        ));
+        assert!(matches!(
+            Program::try_from_bytes(&bytes[..3]),
+            Err(FormatError::InvalidHeader { .. })
+        ));
+
+        bytes[4..8].copy_from_slice(&7u32.to_le_bytes());
+        let err = Program::try_from_bytes(&bytes).unwrap_err();
+        assert!(matches!(
+            err,
+            FormatError::VersionMismatch {
+                expected: 6,
+                found: 7,
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "unsupported program format version: expected 6, found 7"
+        );
+
+        bytes[4..8].copy_from_slice(&(4u32 | 9 << 24).to_le_bytes());
+        assert!(matches!(
+            Program::try_from_bytes(&bytes),
+            Err(FormatError::UnknownEncoding { encoding: 9, .. })
+        ));
+    }
+
+    #[test]
+    fn test_record_encodings() {
+        // Gather the events of every precompile, and of every ALU operation along the way.
+        let mut record = ExecutionRecord::default();
+        for elf in [
+            BLAKE3_COMPRESS_ELF,
+            ED_ADD_ELF,
+            ED_DECOMPRESS_ELF,
+            KECCAK_PERMUTE_ELF,
+            SECP256K1_ADD_ELF,
+            SECP256K1_DECOMPRESS_ELF,
+            SECP256K1_DOUBLE_ELF,
+            SHA_COMPRESS_ELF,
+            SHA_EXTEND_ELF,
+        ] {
+            let mut runtime = Runtime::new(Program::from(elf));
+            runtime.run();
+            record.append(&mut runtime.record);
+        }
+        let stats = record.stats();
+        assert!(stats.nb_mul_events > 0 && stats.nb_lt_events > 0 && stats.nb_bitwise_events > 0);
+        assert!(stats.nb_shift_left_events > 0 && stats.nb_shift_right_events > 0);
+        assert!(stats.nb_sha_compress_events > 0 && stats.nb_blake3_compress_inner_events > 0);
+        assert!(stats.nb_ed_decompress_events > 0 && stats.nb_k256_decompress_events > 0);
+
+        let raw = record.encode(Encoding::Raw);
+        let compact = record.encode(Encoding::Compact);
+        let compressed = record.encode(Encoding::Compressed);
+        for bytes in [&raw, &compact, &compressed] {
+            let decoded = ExecutionRecord::try_from_bytes(bytes).unwrap();
+            assert_eq!(format!("{:?}", decoded.stats()), format!("{:?}", stats));
+            assert_eq!(decoded.digest(), record.digest());
+        }
+        assert!(compact.len() < raw.len());
+        assert!(compressed.len() < compact.len());
+        assert!(matches!(
+            ExecutionRecord::try_from_bytes(&compressed[..compressed.len() / 2]),
+            Err(FormatError::Corrupted { .. } | FormatError::Decode { .. })
+        ));
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashSet;
+use std::mem::size_of;
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+use nohash_hasher::BuildNoHashHasher;
+
+use super::{Error, Program, Runtime, RuntimeOptions, MAX_REGISTER_ADDR};
+
+/// The number of words of a page of a [`FrozenState`].
+pub const FROZEN_PAGE_WORDS: usize = 1024;
+
+/// The bytes of a page of a [`FrozenState`].
+const PAGE_BYTES: u32 = FROZEN_PAGE_WORDS as u32 * 4;
+
+/// The words of a page, `None` for those not in the memory image.
+type Page = Arc<[Option<u32>]>;
+
+/// The memory of a program before its first instruction, built once and shared by the runtimes
+/// executing it, see [`Runtime::new_from_frozen`].
+///
+/// The memory image of the program and the mapped regions are split into pages, and identical
+/// pages, such as those of zeroed regions, are stored once. The runtimes load a word from the
+/// pages on its first access and keep their writes to themselves, so any number of them can
+/// execute concurrently from the same state.
+#[derive(Debug)]
+pub struct FrozenState {
+    /// The program with the mapped regions in its memory image.
+    program: Arc<Program>,
+
+    /// The pages of the memory image, by address divided by the page size.
+    pages: HashMap<u32, Page, BuildNoHashHasher<u32>>,
+
+    /// The number of distinct pages.
+    unique_pages: usize,
+
+    /// The digest of `program`, see [`Program::digest`].
+    digest: [u8; 32],
+}
+
+impl FrozenState {
+    /// Freeze the memory of `program` with each of `regions` mapped at its address, given as the
+    /// address and the bytes to map there, padded with zeros to a multiple of 4 bytes. A region
+    /// overwrites the words of the memory image and of the regions before it.
+    ///
+    /// Returns an error if the program contains malformed instructions, or if a region is not
+    /// aligned, extends past the end of the address space or overlaps the registers.
+    pub fn new(program: &Program, regions: &[(u32, &[u8])]) -> Result<Self, Error> {
+        program.validate()?;
+        let mut program = program.clone();
+        for &(addr, bytes) in regions {
+            if addr % 4 != 0 {
+                return Err(Error::UnalignedAddress { addr });
+            }
+            if addr <= MAX_REGISTER_ADDR {
+                return Err(Error::RegisterAddress { addr });
+            }
+            let len = bytes.len().div_ceil(4);
+            let last = u32::try_from(len.saturating_sub(1))
+                .ok()
+                .and_then(|last| last.checked_mul(4))
+                .and_then(|offset| addr.checked_add(offset));
+            if last.is_none() {
+                return Err(Error::AddressOverflow { addr, len });
+            }
+            for (index, chunk) in bytes.chunks(4).enumerate() {
+                let mut word = [0u8; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                program
+                    .memory_image
+                    .insert(addr + index as u32 * 4, u32::from_le_bytes(word));
+            }
+        }
+
+        let mut pages = HashMap::<u32, Vec<Option<u32>>, BuildNoHashHasher<u32>>::default();
+        for (&addr, &value) in program.memory_image.iter() {
+            let page = pages
+                .entry(addr / PAGE_BYTES)
+                .or_insert_with(|| vec![None; FROZEN_PAGE_WORDS]);
+            page[(addr % PAGE_BYTES / 4) as usize] = Some(value);
+        }
+        let mut distinct = HashSet::<Page>::new();
+        let pages = pages
+            .into_iter()
+            .map(|(index, page)| {
+                let page: Page = page.into();
+                let page = match distinct.get(&page) {
+                    Some(shared) => shared.clone(),
+                    None => {
+                        distinct.insert(page.clone());
+                        page
+                    }
+                };
+                (index, page)
+            })
+            .collect();
+
+        Ok(Self {
+            digest: program.digest(),
+            program: Arc::new(program),
+            pages,
+            unique_pages: distinct.len(),
+        })
+    }
+
+    /// The program, with the mapped regions in its memory image.
+    pub fn program(&self) -> &Arc<Program> {
+        &self.program
+    }
+
+    /// The digest of the program with the mapped regions in its memory image, computed once when
+    /// the state is frozen.
+    pub fn digest(&self) -> [u8; 32] {
+        self.digest
+    }
+
+    /// The value of the word at `addr` before the first instruction, `None` if it is not in the
+    /// memory image.
+    #[inline]
+    pub fn word(&self, addr: u32) -> Option<u32> {
+        let page = self.pages.get(&(addr / PAGE_BYTES))?;
+        page[(addr % PAGE_BYTES / 4) as usize]
+    }
+
+    /// An estimate of the bytes of heap the pages take.
+    pub fn heap_size(&self) -> usize {
+        self.unique_pages * FROZEN_PAGE_WORDS * size_of::<Option<u32>>()
+            + self.pages.capacity() * size_of::<(u32, Page)>()
+    }
+}
+
+impl Runtime {
+    /// Create a runtime executing the program of `frozen` from its frozen memory, configured by
+    /// the environment like [`Runtime::new`]. The memory is not copied: words are loaded from the
+    /// shared pages on their first access, and writes only change the memory of this runtime.
+    pub fn new_from_frozen(frozen: Arc<FrozenState>) -> Self {
+        let mut runtime = Self::with_program(frozen.program.clone(), RuntimeOptions::from_env());
+        runtime.frozen = Some(frozen);
+        runtime
+    }
+
+    /// An estimate of the bytes of heap the memory of the runtime takes, leaving out the pages of
+    /// its frozen state, which are shared.
+    pub fn memory_heap_size(&self) -> usize {
+        self.state.memory.capacity() * size_of::<(u32, (u32, u32, u32))>()
+    }
+
+    /// The value of the word at `addr` in the frozen state of the runtime, if any.
+    #[inline]
+    pub(crate) fn frozen_word(&self, addr: u32) -> Option<u32> {
+        self.frozen.as_ref().and_then(|frozen| frozen.word(addr))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Instruction, Opcode, Register, SyscallCode};
+
+    const IMAGE_WORDS: u32 = 1 << 16;
+    const INPUT_ADDR: u32 = 0x0100_0000;
+
+    /// Reads a word from the input stream, adds it to the first word of the mapped input and
+    /// writes the sum back, then adds the last word of the image to it in x9.
+    fn frozen_program() -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 11, 0, 4, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, SyscallCode::LWA as u32, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::ADD, 6, 0, INPUT_ADDR, false, true),
+            Instruction::new(Opcode::LW, 7, 6, 0, false, true),
+            Instruction::new(Opcode::ADD, 7, 7, 10, false, false),
+            Instruction::new(Opcode::SW, 7, 6, 0, false, true),
+            Instruction::new(
+                Opcode::LW,
+                8,
+                0,
+                0x10000 + 4 * (IMAGE_WORDS - 1),
+                false,
+                true,
+            ),
+            Instruction::new(Opcode::ADD, 9, 7, 8, false, false),
+        ];
+        let mut program = Program::new(instructions, 0, 0);
+        for i in 0..IMAGE_WORDS {
+            program.memory_image.insert(0x10000 + 4 * i, i);
+        }
+        program
+    }
+
+    fn frozen_state() -> Arc<FrozenState> {
+        let mut input = 100u32.to_le_bytes().to_vec();
+        // A zeroed buffer spanning several pages after the first word.
+        input.resize(4 * FROZEN_PAGE_WORDS * 8, 0);
+        Arc::new(FrozenState::new(&frozen_program(), &[(INPUT_ADDR, &input)]).unwrap())
+    }
+
+    fn run(mut runtime: Runtime, input: u32) -> Runtime {
+        runtime.state.input_stream = input.to_le_bytes().to_vec();
+        runtime.run();
+        runtime
+    }
+
+    #[test]
+    fn test_concurrent_runtimes() {
+        let frozen = frozen_state();
+        assert_eq!(frozen.word(INPUT_ADDR), Some(100));
+        assert_eq!(frozen.word(INPUT_ADDR + 4), Some(0));
+        assert_eq!(frozen.word(4), None);
+        assert_eq!(frozen.digest(), frozen.program().digest());
+        // The zeroed pages of the input, all but the first, are stored once.
+        assert_eq!(frozen.pages.len(), 64 + 8);
+        assert_eq!(frozen.unique_pages, 64 + 2);
+
+        let runtimes = std::thread::scope(|scope| {
+            let handles = [1, 2].map(|input| {
+                let frozen = frozen.clone();
+                scope.spawn(move || run(Runtime::new_from_frozen(frozen), input))
+            });
+            handles.map(|handle| handle.join().unwrap())
+        });
+        for (runtime, input) in runtimes.iter().zip([1, 2]) {
+            assert_eq!(
+                runtime.register(Register::X9),
+                100 + input + IMAGE_WORDS - 1
+            );
+            assert_eq!(runtime.word(INPUT_ADDR), 100 + input);
+            assert_eq!(runtime.word(0x10004), 1);
+
+            // The records are those of the same state loaded eagerly.
+            let program = (**frozen.program()).clone();
+            let expected = run(Runtime::new(program), input);
+            assert_eq!(
+                runtime.record.first_memory_record,
+                expected.record.first_memory_record
+            );
+            assert_eq!(
+                runtime.record.last_memory_record,
+                expected.record.last_memory_record
+            );
+            assert_eq!(
+                runtime.record.program_memory_record,
+                expected.record.program_memory_record
+            );
+        }
+        assert_eq!(frozen.word(INPUT_ADDR), Some(100));
+    }
+
+    #[test]
+    fn test_shared_memory_size() {
+        let frozen = frozen_state();
+        let eager = run(Runtime::new((**frozen.program()).clone()), 1);
+        let shared = run(Runtime::new_from_frozen(frozen.clone()), 1);
+        assert!(
+            shared.memory_heap_size() * 100 < eager.memory_heap_size(),
+            "{} {}",
+            shared.memory_heap_size(),
+            eager.memory_heap_size()
+        );
+        assert!(frozen.heap_size() < eager.memory_heap_size());
+    }
+
+    #[test]
+    fn test_invalid_regions() {
+        let program = Program::new(vec![], 0, 0);
+        assert!(matches!(
+            FrozenState::new(&program, &[(0x1002, &[1])]),
+            Err(Error::UnalignedAddress { addr: 0x1002 })
+        ));
+        assert!(matches!(
+            FrozenState::new(&program, &[(8, &[1])]),
+            Err(Error::RegisterAddress { addr: 8 })
+        ));
+        assert!(matches!(
+            FrozenState::new(&program, &[(0xffff_fffc, &[0; 5])]),
+            Err(Error::AddressOverflow { len: 2, .. })
+        ));
+    }
+}
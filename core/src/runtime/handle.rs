@@ -0,0 +1,140 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use super::{ExecutionError, HaltReason, Runtime};
+
+/// A run of the program that executes a bounded number of cycles at a time, see
+/// [`Runtime::run_async`].
+///
+/// The handle only stops between two instructions, so dropping it before it completes leaves the
+/// runtime in a consistent state after the last executed cycle, as if it had been stepped by hand.
+pub struct ExecutionHandle<'a> {
+    runtime: &'a mut Runtime,
+    yield_every: u64,
+    started: bool,
+    finished: bool,
+}
+
+impl ExecutionHandle<'_> {
+    /// Execute the next `yield_every` cycles, returning the halt reason once the program finishes
+    /// or faults and `Poll::Pending` if it is still running.
+    pub fn poll_run(&mut self) -> Poll<Result<HaltReason, ExecutionError>> {
+        assert!(!self.finished, "execution handle polled after completion");
+        if !self.started {
+            self.runtime.initialize();
+            self.started = true;
+        }
+        match self.runtime.run_steps(self.yield_every) {
+            Ok(false) => Poll::Pending,
+            Ok(true) => {
+                self.finished = true;
+                self.runtime.finalize();
+                Poll::Ready(Ok(self.runtime.halt_reason.clone()))
+            }
+            Err(err) => {
+                self.finished = true;
+                Poll::Ready(Err(err))
+            }
+        }
+    }
+
+    /// The runtime being executed, e.g. to report progress between two polls.
+    pub fn runtime(&self) -> &Runtime {
+        self.runtime
+    }
+}
+
+impl Future for ExecutionHandle<'_> {
+    type Output = Result<HaltReason, ExecutionError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let handle = self.get_mut();
+        let poll = handle.poll_run();
+        if poll.is_pending() {
+            // There is nothing to wait for, the executor only needs a chance to run other tasks.
+            cx.waker().wake_by_ref();
+        }
+        poll
+    }
+}
+
+impl Runtime {
+    /// Execute the program like [`Runtime::try_run`] as a future that runs `yield_every` cycles
+    /// each time it is polled, so an async executor can interleave other tasks with a long run and
+    /// time it out by dropping the future.
+    pub fn run_async(&mut self, yield_every: u64) -> ExecutionHandle<'_> {
+        ExecutionHandle {
+            runtime: self,
+            yield_every: yield_every.max(1),
+            started: false,
+            finished: false,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+
+    /// A waker counting how often it is woken.
+    fn counting_waker(wakes: &std::cell::Cell<u32>) -> Waker {
+        fn clone(data: *const ()) -> RawWaker {
+            RawWaker::new(data, &VTABLE)
+        }
+        fn wake(data: *const ()) {
+            let wakes = unsafe { &*(data as *const std::cell::Cell<u32>) };
+            wakes.set(wakes.get() + 1);
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, noop);
+        let data = wakes as *const std::cell::Cell<u32> as *const ();
+        unsafe { Waker::from_raw(RawWaker::new(data, &VTABLE)) }
+    }
+
+    /// Poll `future` until it completes, returning its output and the number of polls.
+    fn block_on<F: Future>(future: F) -> (F::Output, u32) {
+        let wakes = std::cell::Cell::new(0);
+        let waker = counting_waker(&wakes);
+        let mut cx = Context::from_waker(&waker);
+        let mut future = core::pin::pin!(future);
+        let mut polls = 1;
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                assert_eq!(wakes.get(), polls - 1);
+                return (output, polls);
+            }
+            polls += 1;
+        }
+    }
+
+    #[test]
+    fn test_run_async() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.run();
+        let (cycles, digest) = (runtime.state.global_clk, runtime.record.digest());
+
+        let mut runtime = Runtime::new(fibonacci_program());
+        let (result, polls) = block_on(runtime.run_async(1000));
+        assert_eq!(result, Ok(HaltReason::Finished));
+        assert_eq!(polls as u64, (cycles as u64).div_ceil(1000).max(1));
+        assert_eq!(runtime.state.global_clk, cycles);
+        assert_eq!(runtime.record.digest(), digest);
+    }
+
+    #[test]
+    fn test_drop_execution_handle() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        let mut handle = runtime.run_async(1000);
+        assert!(handle.poll_run().is_pending());
+        assert_eq!(handle.runtime().state.global_clk, 1000);
+        drop(handle);
+
+        // Every executed cycle was applied in full.
+        assert_eq!(runtime.record.cpu_events.len(), 1000);
+        assert_eq!(runtime.state.global_clk, 1000);
+        assert!(runtime.pending_error.is_none());
+    }
+}
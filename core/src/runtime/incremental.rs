@@ -0,0 +1,216 @@
+use super::{
+    verify_reexecution, ExecutionCheckpoint, ExecutionError, ExecutionRecord, Program, Runtime,
+};
+
+/// A checkpoint together with the part of the input stream consumed before it was taken.
+#[derive(Debug, Clone)]
+struct InputCheckpoint {
+    /// The number of input bytes consumed before the checkpoint.
+    consumed: usize,
+
+    /// The blake3 digest of the consumed input bytes.
+    prefix_digest: [u8; 32],
+
+    checkpoint: ExecutionCheckpoint,
+}
+
+/// The result of a run of an [`IncrementalExecutor`].
+pub struct IncrementalRun {
+    /// The runtime after the program has finished.
+    pub runtime: Runtime,
+
+    /// The global clock of the checkpoint execution was resumed from, if any.
+    pub resumed_from: Option<u32>,
+
+    /// The number of cycles actually executed in this run.
+    pub fresh_cycles: u32,
+}
+
+/// Re-executes a program on changing inputs, skipping the prefix of the execution that only
+/// depends on input bytes that did not change.
+///
+/// Every run stores periodic checkpoints keyed by the input bytes consumed so far. The next run
+/// resumes from the latest checkpoint whose consumed input is a prefix of the new input. Since the
+/// guest can only observe input bytes by consuming them, the execution up to that checkpoint would
+/// have been identical.
+pub struct IncrementalExecutor {
+    program: Program,
+    interval: u32,
+    checkpoints: Vec<InputCheckpoint>,
+
+    /// The record of the last run, which the record at each checkpoint is a prefix of.
+    record: ExecutionRecord,
+
+    paranoid: bool,
+}
+
+impl IncrementalExecutor {
+    /// Create an executor that checkpoints every `interval` cycles.
+    pub fn new(program: Program, interval: u32) -> Self {
+        assert!(interval > 0, "checkpoint interval must be positive");
+        Self {
+            program,
+            interval,
+            checkpoints: Vec::new(),
+            record: ExecutionRecord::default(),
+            paranoid: false,
+        }
+    }
+
+    /// In paranoid mode, every run resumed from a checkpoint is replayed from the start and each
+    /// of its shards is checked against the replay with [`verify_reexecution`], panicking with
+    /// the divergence report on the first mismatch.
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.paranoid = paranoid;
+    }
+
+    /// The index of the latest checkpoint whose consumed input is a prefix of `input`. Checkpoints
+    /// are in the order they were taken, so each consumed at least the input of the previous one.
+    fn resume_point(&self, input: &[u8]) -> Option<usize> {
+        let mut hasher = blake3::Hasher::new();
+        let mut hashed = 0;
+        let mut found = None;
+        for (index, checkpoint) in self.checkpoints.iter().enumerate() {
+            if checkpoint.consumed > input.len() {
+                break;
+            }
+            hasher.update(&input[hashed..checkpoint.consumed]);
+            hashed = checkpoint.consumed;
+            if *hasher.finalize().as_bytes() != checkpoint.prefix_digest {
+                break;
+            }
+            found = Some(index);
+        }
+        found
+    }
+
+    /// Execute the program on the given input.
+    pub fn run(&mut self, input: &[u8]) -> Result<IncrementalRun, ExecutionError> {
+        let mut runtime = Runtime::new(self.program.clone());
+        let mut hasher = blake3::Hasher::new();
+        let mut hashed = 0;
+
+        let resume = self.resume_point(input);
+        let start_clk = match resume {
+            Some(index) => {
+                let resumed = &self.checkpoints[index];
+                runtime.restore(&resumed.checkpoint, &self.record);
+                // The stream of the checkpoint holds the input of the run it was taken in.
+                runtime.state.input_stream.clear();
+                runtime.write_stdin_slice(input);
+                hasher.update(&input[..resumed.consumed]);
+                hashed = resumed.consumed;
+                // Checkpoints before the one we resumed from consumed less input, so they are
+                // still valid. Later ones will be replaced by this run.
+                self.checkpoints.truncate(index + 1);
+                runtime.state.global_clk
+            }
+            None => {
+                runtime.write_stdin_slice(input);
+                runtime.initialize();
+                self.checkpoints.clear();
+                0
+            }
+        };
+
+        while !runtime.is_done() {
+            let clk = runtime.state.global_clk;
+            // The guest can append hints to the input stream, which would not be part of the next
+            // run's input, so only checkpoint while the stream is exactly the host input.
+            if clk != start_clk
+                && clk % self.interval == 0
+                && !runtime.unconstrained
+                && runtime.state.input_stream.len() == input.len()
+            {
+                let consumed = runtime.state.input_stream_ptr;
+                hasher.update(&input[hashed..consumed]);
+                hashed = consumed;
+                self.checkpoints.push(InputCheckpoint {
+                    consumed,
+                    prefix_digest: *hasher.finalize().as_bytes(),
+                    checkpoint: runtime.checkpoint(),
+                });
+            }
+            runtime.step()?;
+        }
+        runtime.finalize();
+        self.record = runtime.record.clone();
+
+        if self.paranoid && resume.is_some() {
+            let mut replay = Runtime::new(self.program.clone());
+            replay.write_stdin_slice(input);
+            replay.try_run()?;
+            for shard in 1..=runtime.current_shard().max(replay.current_shard()) {
+                if let Err(report) = verify_reexecution(&replay.record, &runtime.record, shard) {
+                    panic!("resumed execution diverged from a replay: {}", report);
+                }
+            }
+        }
+
+        Ok(IncrementalRun {
+            fresh_cycles: runtime.state.global_clk - start_clk,
+            resumed_from: resume.map(|_| start_clk),
+            runtime,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Instruction, Opcode, Register};
+
+    /// Reads two words of input with a stretch of setup work before each, and adds them.
+    fn two_reads_program() -> Program {
+        let read_word = vec![
+            Instruction::new(Opcode::ADD, 5, 0, 101, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 4, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        let setup = vec![Instruction::new(Opcode::ADD, 29, 29, 1, false, true); 32];
+
+        let mut instructions = Vec::new();
+        instructions.extend(setup.clone());
+        instructions.extend(read_word.clone());
+        instructions.push(Instruction::new(Opcode::ADD, 30, 10, 0, false, true));
+        instructions.extend(setup);
+        instructions.extend(read_word);
+        instructions.push(Instruction::new(Opcode::ADD, 31, 30, 10, false, false));
+        Program::new(instructions, 0, 0)
+    }
+
+    fn input(a: u32, b: u32) -> Vec<u8> {
+        [a.to_le_bytes(), b.to_le_bytes()].concat()
+    }
+
+    #[test]
+    fn test_incremental_run() {
+        let mut executor = IncrementalExecutor::new(two_reads_program(), 4);
+        executor.set_paranoid(true);
+
+        let first = executor.run(&input(1, 2)).unwrap();
+        assert_eq!(first.resumed_from, None);
+        assert_eq!(first.runtime.register(Register::X31), 3);
+
+        // Only the second word changed, so everything up to the second read is reused.
+        let second = executor.run(&input(1, 40)).unwrap();
+        assert!(second.resumed_from.is_some());
+        assert!(second.fresh_cycles < first.fresh_cycles);
+
+        let mut expected = Runtime::new(two_reads_program());
+        expected.write_stdin_slice(&input(1, 40));
+        expected.run();
+
+        assert_eq!(second.runtime.register(Register::X31), 41);
+        assert_eq!(second.runtime.registers(), expected.registers());
+        assert_eq!(second.runtime.state.global_clk, expected.state.global_clk);
+        let mut record = second.runtime.record.clone();
+        record.provenance = expected.record.provenance.clone();
+        assert_eq!(record.digest(), expected.record.digest());
+
+        // Changing the first word invalidates every checkpoint after the first read.
+        let third = executor.run(&input(7, 40)).unwrap();
+        assert_eq!(third.runtime.register(Register::X31), 47);
+        assert!(third.fresh_cycles > second.fresh_cycles);
+    }
+}
@@ -0,0 +1,319 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::{ArenaIter, ExecutionRecord, Opcode};
+use crate::alu::{AluEvent, DivRemEvent, MulEvent};
+use crate::cpu::CpuEvent;
+
+/// The vectors of ALU events of an [`ExecutionRecord`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AluClass {
+    Add,
+    Sub,
+    Bitwise,
+    ShiftLeft,
+    ShiftRight,
+    Lt,
+    Mul,
+    DivRem,
+}
+
+impl AluClass {
+    pub const ALL: [AluClass; 8] = [
+        AluClass::Add,
+        AluClass::Sub,
+        AluClass::Bitwise,
+        AluClass::ShiftLeft,
+        AluClass::ShiftRight,
+        AluClass::Lt,
+        AluClass::Mul,
+        AluClass::DivRem,
+    ];
+
+    /// The class of the events of `opcode`, or `None` if it is not an ALU opcode.
+    pub fn from_opcode(opcode: Opcode) -> Option<Self> {
+        let class = match opcode {
+            Opcode::ADD => AluClass::Add,
+            Opcode::SUB => AluClass::Sub,
+            Opcode::XOR | Opcode::OR | Opcode::AND => AluClass::Bitwise,
+            Opcode::SLL => AluClass::ShiftLeft,
+            Opcode::SRL | Opcode::SRA => AluClass::ShiftRight,
+            Opcode::SLT | Opcode::SLTU => AluClass::Lt,
+            Opcode::MUL | Opcode::MULH | Opcode::MULHU | Opcode::MULHSU => AluClass::Mul,
+            Opcode::DIV | Opcode::DIVU | Opcode::REM | Opcode::REMU => AluClass::DivRem,
+            _ => return None,
+        };
+        Some(class)
+    }
+}
+
+/// The ALU events of one class of a record, see [`ExecutionRecord::alu_events`]. The events are
+/// viewed without the metadata the mul and divrem events carry.
+#[derive(Debug, Clone, Copy)]
+pub enum AluEvents<'a> {
+    Plain(&'a [AluEvent]),
+    Mul(&'a [MulEvent]),
+    DivRem(&'a [DivRemEvent]),
+}
+
+impl<'a> AluEvents<'a> {
+    pub fn len(&self) -> usize {
+        match self {
+            AluEvents::Plain(events) => events.len(),
+            AluEvents::Mul(events) => events.len(),
+            AluEvents::DivRem(events) => events.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The event at position `i`, if any.
+    pub fn get(&self, i: usize) -> Option<AluEvent> {
+        match self {
+            AluEvents::Plain(events) => events.get(i).copied(),
+            AluEvents::Mul(events) => events.get(i).map(|event| event.event),
+            AluEvents::DivRem(events) => events.get(i).map(|event| event.event),
+        }
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = AluEvent> + 'a {
+        (0..self.len()).map(move |i| self.get(i).unwrap())
+    }
+
+    /// The position of the first event for which `pred` is false, see [`slice::partition_point`].
+    pub fn partition_point(&self, pred: impl Fn(&AluEvent) -> bool) -> usize {
+        match self {
+            AluEvents::Plain(events) => events.partition_point(pred),
+            AluEvents::Mul(events) => events.partition_point(|event| pred(&event.event)),
+            AluEvents::DivRem(events) => events.partition_point(|event| pred(&event.event)),
+        }
+    }
+
+    fn slice(self, range: Range<usize>) -> Self {
+        match self {
+            AluEvents::Plain(events) => AluEvents::Plain(&events[range]),
+            AluEvents::Mul(events) => AluEvents::Mul(&events[range]),
+            AluEvents::DivRem(events) => AluEvents::DivRem(&events[range]),
+        }
+    }
+}
+
+/// The range of the events of each shard in the event vectors of a record, see
+/// [`ExecutionRecord::build_indices`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EventIndices {
+    /// The shards with CPU events, in increasing order.
+    shards: Vec<u32>,
+
+    /// The CPU events of each shard.
+    cpu: Vec<Range<usize>>,
+
+    /// The ALU events of each shard, by class.
+    alu: [Vec<Range<usize>>; 8],
+
+    /// The lengths of the indexed vectors when the indices were built.
+    lens: [usize; 9],
+}
+
+impl ExecutionRecord {
+    /// The ALU events of `class`.
+    pub fn alu_events(&self, class: AluClass) -> AluEvents<'_> {
+        match class {
+            AluClass::Add => AluEvents::Plain(&self.add_events),
+            AluClass::Sub => AluEvents::Plain(&self.sub_events),
+            AluClass::Bitwise => AluEvents::Plain(&self.bitwise_events),
+            AluClass::ShiftLeft => AluEvents::Plain(&self.shift_left_events),
+            AluClass::ShiftRight => AluEvents::Plain(&self.shift_right_events),
+            AluClass::Lt => AluEvents::Plain(&self.lt_events),
+            AluClass::Mul => AluEvents::Mul(&self.mul_events),
+            AluClass::DivRem => AluEvents::DivRem(&self.divrem_events),
+        }
+    }
+
+    fn indexed_lens(&self) -> [usize; 9] {
+        let mut lens = [self.cpu_events.len(); 9];
+        for class in AluClass::ALL {
+            lens[class as usize + 1] = self.alu_events(class).len();
+        }
+        lens
+    }
+
+    /// Index the CPU and ALU events by shard, for the `*_for_shard` accessors. The indices are
+    /// rebuilt only if the record was mutated since they were last built.
+    ///
+    /// The events are expected in execution order, as emitted by the runtime. ALU events carry no
+    /// shard, so they are attributed to the shard of the CPU event with the same clock and opcode.
+    pub fn build_indices(&mut self) {
+        if self.indices_fresh() {
+            return;
+        }
+        let mut indices = EventIndices {
+            lens: self.indexed_lens(),
+            ..Default::default()
+        };
+        let mut next = [0; 8];
+        let mut start = 0;
+        while start < self.cpu_events.len() {
+            let shard = self.cpu_events[start].shard;
+            let end = self
+                .cpu_events
+                .partition_point(start..self.cpu_events.len(), |event| event.shard == shard);
+            let alu_start = next;
+            for event in self.cpu_events.range(start..end) {
+                let opcode = event.instruction.opcode;
+                let Some(class) = AluClass::from_opcode(opcode) else {
+                    continue;
+                };
+                let i = &mut next[class as usize];
+                let matches = self
+                    .alu_events(class)
+                    .get(*i)
+                    .is_some_and(|alu| alu.clk == event.clk && alu.opcode == opcode);
+                if matches {
+                    *i += 1;
+                }
+            }
+            indices.shards.push(shard);
+            indices.cpu.push(start..end);
+            for (ranges, (lo, hi)) in indices.alu.iter_mut().zip(alu_start.into_iter().zip(next)) {
+                ranges.push(lo..hi);
+            }
+            start = end;
+        }
+        self.indices = Some(indices);
+        self.indices_dirty = false;
+    }
+
+    fn indices_fresh(&self) -> bool {
+        match &self.indices {
+            Some(indices) => !self.indices_dirty && indices.lens == self.indexed_lens(),
+            None => false,
+        }
+    }
+
+    /// The position of `shard` in the indices, panicking if they are stale.
+    fn shard_position(&self, shard: u32) -> Option<usize> {
+        assert!(
+            self.indices_fresh(),
+            "the event indices are stale, call `build_indices` after mutating the record"
+        );
+        self.indices
+            .as_ref()
+            .unwrap()
+            .shards
+            .binary_search(&shard)
+            .ok()
+    }
+
+    /// The positions of the CPU events of `shard`, empty if it has none.
+    fn cpu_range(&self, shard: u32) -> Range<usize> {
+        match self.shard_position(shard) {
+            Some(i) => self.indices.as_ref().unwrap().cpu[i].clone(),
+            None => 0..0,
+        }
+    }
+
+    /// The CPU events of `shard`, empty if it has none. Panics unless the indices were built
+    /// since the record was last mutated.
+    pub fn cpu_events_for_shard(&self, shard: u32) -> ArenaIter<'_, CpuEvent> {
+        self.cpu_events.range(self.cpu_range(shard))
+    }
+
+    /// The ALU events of `class` in `shard`, empty if it has none. Panics unless the indices
+    /// were built since the record was last mutated.
+    pub fn alu_events_for_shard(&self, shard: u32, class: AluClass) -> AluEvents<'_> {
+        let range = match self.shard_position(shard) {
+            Some(i) => self.indices.as_ref().unwrap().alu[class as usize][i].clone(),
+            None => 0..0,
+        };
+        self.alu_events(class).slice(range)
+    }
+
+    /// The CPU events of `shard` with a clock in `clk`. Panics unless the indices were built
+    /// since the record was last mutated.
+    pub fn events_in_clk_range(&self, shard: u32, clk: Range<u32>) -> ArenaIter<'_, CpuEvent> {
+        let range = self.cpu_range(shard);
+        let events = &self.cpu_events;
+        let start = events.partition_point(range.clone(), |event| event.clk < clk.start);
+        let end = events.partition_point(range, |event| event.clk < clk.end);
+        events.range(start..end.max(start))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::Runtime;
+
+    fn sharded_record() -> ExecutionRecord {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.shard_size = 256;
+        runtime.run();
+        runtime.record
+    }
+
+    #[test]
+    fn test_event_indices() {
+        let mut record = sharded_record();
+        record.build_indices();
+        let last_shard = record.cpu_events.last().unwrap().shard;
+        assert!(last_shard > 3);
+
+        for shard in 0..=last_shard + 1 {
+            let expected = record
+                .cpu_events
+                .iter()
+                .filter(|event| event.shard == shard)
+                .copied()
+                .collect::<Vec<_>>();
+            let events = record.cpu_events_for_shard(shard);
+            assert_eq!(events.len(), expected.len());
+            assert!(events.zip(expected.iter()).all(|(a, b)| a.clk == b.clk));
+
+            for class in AluClass::ALL {
+                let expected = expected
+                    .iter()
+                    .filter(|event| AluClass::from_opcode(event.instruction.opcode) == Some(class))
+                    .map(|event| event.clk)
+                    .collect::<Vec<_>>();
+                let clks = record
+                    .alu_events_for_shard(shard, class)
+                    .iter()
+                    .map(|event| event.clk)
+                    .collect::<Vec<_>>();
+                assert_eq!(clks, expected);
+            }
+
+            let (lo, hi) = (100, 300);
+            let expected = expected
+                .iter()
+                .filter(|event| event.clk >= lo && event.clk < hi)
+                .count();
+            assert_eq!(record.events_in_clk_range(shard, lo..hi).len(), expected);
+        }
+        assert_eq!(record.events_in_clk_range(1, 300..100).len(), 0);
+
+        // Events added after execution, e.g. by trace generation, belong to no shard.
+        let adds = record.alu_events_for_shard(1, AluClass::Add).len();
+        let mut output = ExecutionRecord::default();
+        output.add_events.push(record.add_events[0]);
+        record.append(&mut output);
+        assert!(!record.indices_fresh());
+        record.build_indices();
+        assert_eq!(record.alu_events_for_shard(1, AluClass::Add).len(), adds);
+    }
+
+    #[test]
+    #[should_panic(expected = "stale")]
+    fn test_stale_event_indices() {
+        let mut record = sharded_record();
+        record.build_indices();
+        let event = record.cpu_events[0];
+        record.cpu_events.push(event);
+        record.cpu_events_for_shard(1);
+    }
+}
@@ -1,9 +1,10 @@
-use core::fmt::Debug;
+use core::fmt::{Debug, Display};
+use serde::{Deserialize, Serialize};
 
-use super::Opcode;
+use super::{AccessPosition, Opcode, NUM_REGISTERS};
 
 /// An instruction specifies an operation to execute and the operands.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Instruction {
     pub opcode: Opcode,
     pub op_a: u32,
@@ -65,6 +66,11 @@ impl Instruction {
         )
     }
 
+    /// Returns if the instruction is a store instruction.
+    pub fn is_store_instruction(&self) -> bool {
+        matches!(self.opcode, Opcode::SB | Opcode::SH | Opcode::SW)
+    }
+
     /// Returns if the instruction is a branch instruction.
     pub fn is_branch_instruction(&self) -> bool {
         matches!(
@@ -76,6 +82,131 @@ impl Instruction {
     pub fn is_jump_instruction(&self) -> bool {
         matches!(self.opcode, Opcode::JAL | Opcode::JALR)
     }
+
+    /// Returns if the instruction is a Zbb bit manipulation instruction.
+    pub fn is_bitmanip_instruction(&self) -> bool {
+        self.opcode.is_bitmanip()
+    }
+
+    /// Checks that the operand flags and register operands are legal for the opcode's class.
+    ///
+    /// The transpiler only ever produces instructions in the following shapes:
+    ///  - ALU: `(imm_b, imm_c)` is `(false, false)`, `(false, true)`, or `(true, true)` for LUI.
+    ///  - Loads, stores, branches, and JALR: `(false, true)`.
+    ///  - JAL and AUIPC: `(true, true)`, with the immediate in `op_b`.
+    ///  - ECALL: `(false, true)`, with `op_a = a0` and `op_b = t0`.
+    ///  - CLZ, CTZ and CPOP: `(false, true)`, with `op_c = 0`.
+    ///  - Other bit manipulation instructions: `(false, false)`.
+    pub fn validate(&self) -> Result<(), InstructionError> {
+        let (imm_b, imm_c) = (self.imm_b, self.imm_c);
+        let legal_flags = match self.opcode {
+            _ if self.is_alu_instruction() => !imm_b || imm_c,
+            _ if self.is_memory_instruction() || self.is_branch_instruction() => !imm_b && imm_c,
+            Opcode::JALR | Opcode::ECALL => !imm_b && imm_c,
+            Opcode::JAL | Opcode::AUIPC => imm_b && imm_c,
+            Opcode::EBREAK => !imm_b && !imm_c,
+            Opcode::UNIMP => true,
+            Opcode::CLZ | Opcode::CTZ | Opcode::CPOP => !imm_b && imm_c,
+            _ if self.is_bitmanip_instruction() => !imm_b && !imm_c,
+            _ => unreachable!(),
+        };
+        if !legal_flags {
+            return Err(InstructionError::InvalidImmediateFlags {
+                opcode: self.opcode,
+                imm_b,
+                imm_c,
+            });
+        }
+
+        if self.opcode == Opcode::UNIMP {
+            return Ok(());
+        }
+
+        // Every opcode uses `op_a` as a register, while `op_b` and `op_c` are registers unless the
+        // corresponding immediate flag is set.
+        let operands = [
+            (AccessPosition::A, self.op_a, true),
+            (AccessPosition::B, self.op_b, !imm_b),
+            (AccessPosition::C, self.op_c, !imm_c),
+        ];
+        for (position, value, is_register) in operands {
+            if is_register && value >= NUM_REGISTERS {
+                return Err(InstructionError::InvalidRegister {
+                    opcode: self.opcode,
+                    position,
+                    value,
+                });
+            }
+        }
+
+        if self.opcode == Opcode::ECALL && (self.op_a != 10 || self.op_b != 5) {
+            return Err(InstructionError::InvalidEcallOperands {
+                op_a: self.op_a,
+                op_b: self.op_b,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// An error describing why an instruction is malformed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionError {
+    /// The `imm_b`/`imm_c` flags are not a legal combination for the opcode's class.
+    InvalidImmediateFlags {
+        opcode: Opcode,
+        imm_b: bool,
+        imm_c: bool,
+    },
+
+    /// An operand which is decoded as a register does not name one of the 32 registers.
+    InvalidRegister {
+        opcode: Opcode,
+        position: AccessPosition,
+        value: u32,
+    },
+
+    /// An ECALL which does not read the syscall id from t0 and return the result in a0.
+    InvalidEcallOperands { op_a: u32, op_b: u32 },
+
+    /// An instruction of an extension the program does not enable, see `Program::extensions`.
+    ExtensionDisabled { opcode: Opcode },
+}
+
+impl Display for InstructionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstructionError::InvalidImmediateFlags {
+                opcode,
+                imm_b,
+                imm_c,
+            } => write!(
+                f,
+                "invalid immediate flags for {}: imm_b={}, imm_c={}",
+                opcode, imm_b, imm_c
+            ),
+            InstructionError::InvalidRegister {
+                opcode,
+                position,
+                value,
+            } => write!(
+                f,
+                "invalid register for operand {:?} of {}: {}",
+                position, opcode, value
+            ),
+            InstructionError::InvalidEcallOperands { op_a, op_b } => write!(
+                f,
+                "ecall must use a0 and t0 as operands, found %x{} and %x{}",
+                op_a, op_b
+            ),
+            InstructionError::ExtensionDisabled { opcode } => write!(
+                f,
+                "{} belongs to an extension the program does not enable",
+                opcode
+            ),
+        }
+    }
 }
 
 impl Debug for Instruction {
@@ -105,3 +236,142 @@ impl Debug for Instruction {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Program;
+
+    fn assert_invalid_flags(opcode: Opcode, imm_b: bool, imm_c: bool) {
+        let instruction = Instruction::new(opcode, 1, 2, 3, imm_b, imm_c);
+        assert_eq!(
+            instruction.validate(),
+            Err(InstructionError::InvalidImmediateFlags {
+                opcode,
+                imm_b,
+                imm_c
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_alu() {
+        assert!(Instruction::new(Opcode::ADD, 1, 2, 3, false, false)
+            .validate()
+            .is_ok());
+        assert!(Instruction::new(Opcode::ADD, 1, 2, 3, false, true)
+            .validate()
+            .is_ok());
+        assert!(Instruction::new(Opcode::ADD, 1, 0, 0x1000, true, true)
+            .validate()
+            .is_ok());
+        assert_invalid_flags(Opcode::ADD, true, false);
+        assert_invalid_flags(Opcode::DIVU, true, false);
+    }
+
+    #[test]
+    fn test_validate_load_store() {
+        assert!(Instruction::new(Opcode::LW, 1, 2, 8, false, true)
+            .validate()
+            .is_ok());
+        assert_invalid_flags(Opcode::LW, true, true);
+        assert_invalid_flags(Opcode::LBU, false, false);
+        assert_invalid_flags(Opcode::SB, true, true);
+        assert_invalid_flags(Opcode::SW, false, false);
+    }
+
+    #[test]
+    fn test_validate_branch() {
+        assert!(Instruction::new(Opcode::BEQ, 1, 2, 8, false, true)
+            .validate()
+            .is_ok());
+        assert_invalid_flags(Opcode::BNE, true, true);
+        assert_invalid_flags(Opcode::BGEU, false, false);
+    }
+
+    #[test]
+    fn test_validate_jump_and_upper() {
+        assert!(Instruction::new(Opcode::JAL, 1, 0xfffffff8, 0, true, true)
+            .validate()
+            .is_ok());
+        assert!(Instruction::new(Opcode::JALR, 1, 2, 8, false, true)
+            .validate()
+            .is_ok());
+        assert_invalid_flags(Opcode::JAL, false, true);
+        assert_invalid_flags(Opcode::JALR, true, true);
+        assert_invalid_flags(Opcode::AUIPC, false, true);
+    }
+
+    #[test]
+    fn test_validate_system() {
+        assert!(Instruction::new(Opcode::ECALL, 10, 5, 0, false, true)
+            .validate()
+            .is_ok());
+        assert_eq!(
+            Instruction::new(Opcode::ECALL, 11, 5, 0, false, true).validate(),
+            Err(InstructionError::InvalidEcallOperands { op_a: 11, op_b: 5 })
+        );
+        assert_invalid_flags(Opcode::EBREAK, false, true);
+        assert!(Instruction::unimp().validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_bitmanip() {
+        assert!(Instruction::new(Opcode::ANDN, 1, 2, 3, false, false)
+            .validate()
+            .is_ok());
+        assert!(Instruction::new(Opcode::CPOP, 1, 2, 0, false, true)
+            .validate()
+            .is_ok());
+        assert_invalid_flags(Opcode::MAXU, false, true);
+        assert_invalid_flags(Opcode::CLZ, false, false);
+    }
+
+    #[test]
+    fn test_validate_registers() {
+        assert_eq!(
+            Instruction::new(Opcode::ADD, 32, 0, 5, false, true).validate(),
+            Err(InstructionError::InvalidRegister {
+                opcode: Opcode::ADD,
+                position: AccessPosition::A,
+                value: 32
+            })
+        );
+        assert_eq!(
+            Instruction::new(Opcode::SUB, 1, 40, 5, false, false).validate(),
+            Err(InstructionError::InvalidRegister {
+                opcode: Opcode::SUB,
+                position: AccessPosition::B,
+                value: 40
+            })
+        );
+        assert_eq!(
+            Instruction::new(Opcode::XOR, 1, 2, 100, false, false).validate(),
+            Err(InstructionError::InvalidRegister {
+                opcode: Opcode::XOR,
+                position: AccessPosition::C,
+                value: 100
+            })
+        );
+    }
+
+    #[test]
+    fn test_program_validate_reports_indices() {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 29, 0, 5, false, true),
+            Instruction::new(Opcode::LW, 28, 0, 8, true, true),
+            Instruction::new(Opcode::ADD, 30, 0, 37, false, true),
+            Instruction::new(Opcode::BEQ, 1, 2, 8, false, false),
+        ];
+        let program = Program::new(instructions, 0x1000, 0x1000);
+        let err = program.validate().unwrap_err();
+        assert_eq!(err.invalid.len(), 2);
+        assert_eq!(err.invalid[0].index, 1);
+        assert_eq!(err.invalid[0].pc, 0x1004);
+        assert_eq!(err.invalid[1].index, 3);
+        assert_eq!(err.invalid[1].pc, 0x100c);
+        let message = err.to_string();
+        assert!(message.contains("[1] pc=0x1004 lw"));
+        assert!(message.contains("[3] pc=0x100c beq"));
+    }
+}
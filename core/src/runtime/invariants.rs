@@ -0,0 +1,442 @@
+use core::fmt::{Display, Formatter};
+
+use super::{AccessPosition, Instruction, Opcode, Runtime};
+use crate::alu::shift_amount;
+use crate::cpu::{CpuEvent, MemoryRecordEnum};
+
+/// A structural invariant of the CPU table that an event breaks, found by
+/// [`check_cpu_event`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventViolation {
+    /// The event is not in the current shard.
+    WrongShard { expected: u32, found: u32 },
+
+    /// An access is missing, e.g. the read of a register operand.
+    MissingRecord { position: AccessPosition },
+
+    /// An access that the instruction does not make, e.g. a memory access of an ALU instruction.
+    UnexpectedRecord { position: AccessPosition },
+
+    /// A read where the instruction writes, or the other way around.
+    WrongAccessKind { position: AccessPosition },
+
+    /// An access at another shard or clock than its position in the cycle.
+    WrongTimestamp {
+        position: AccessPosition,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+
+    /// An access whose previous access is not before it.
+    WrongPrevTimestamp {
+        position: AccessPosition,
+        timestamp: (u32, u32),
+        prev: (u32, u32),
+    },
+
+    /// An access whose value differs from the operand of the event.
+    WrongValue {
+        position: AccessPosition,
+        expected: u32,
+        found: u32,
+    },
+
+    /// The result in `a` is not what the instruction computes from its operands.
+    WrongResult { expected: u32, found: u32 },
+}
+
+impl Display for EventViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EventViolation::WrongShard { expected, found } => {
+                write!(f, "event is in shard {} instead of {}", found, expected)
+            }
+            EventViolation::MissingRecord { position } => {
+                write!(f, "missing {:?} access", position)
+            }
+            EventViolation::UnexpectedRecord { position } => {
+                write!(f, "unexpected {:?} access", position)
+            }
+            EventViolation::WrongAccessKind { position } => {
+                write!(
+                    f,
+                    "{:?} access is a read instead of a write or vice versa",
+                    position
+                )
+            }
+            EventViolation::WrongTimestamp {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{:?} access at (shard, clk) {:?} instead of {:?}",
+                position, found, expected
+            ),
+            EventViolation::WrongPrevTimestamp {
+                position,
+                timestamp,
+                prev,
+            } => write!(
+                f,
+                "{:?} access at (shard, clk) {:?} follows an access at {:?}",
+                position, timestamp, prev
+            ),
+            EventViolation::WrongValue {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{:?} access has value {} instead of {}",
+                position, found, expected
+            ),
+            EventViolation::WrongResult { expected, found } => {
+                write!(f, "result is {} instead of {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EventViolation {}
+
+/// The access an instruction makes at one position of its cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Access {
+    None,
+    Read,
+    Write,
+}
+
+/// The accesses `instruction` makes at positions A, B, C and memory, in that order.
+pub(crate) fn instruction_accesses(instruction: &Instruction) -> [Access; 4] {
+    let a_access = if instruction.is_branch_instruction() || instruction.is_store_instruction() {
+        Access::Read
+    } else if instruction.op_a == 0 {
+        // Writes to x0 are dropped.
+        Access::None
+    } else {
+        Access::Write
+    };
+    let operand = |immediate: bool| {
+        if immediate {
+            Access::None
+        } else {
+            Access::Read
+        }
+    };
+    let memory_access = if !instruction.is_memory_instruction() {
+        Access::None
+    } else if instruction.is_store_instruction() {
+        Access::Write
+    } else {
+        Access::Read
+    };
+    [
+        a_access,
+        operand(instruction.imm_b),
+        operand(instruction.imm_c),
+        memory_access,
+    ]
+}
+
+/// The result of an ALU instruction, computed independently of `Runtime::execute`.
+pub(crate) fn alu_result(opcode: Opcode, b: u32, c: u32) -> Option<u32> {
+    let result = match opcode {
+        Opcode::ADD => b.wrapping_add(c),
+        Opcode::SUB => b.wrapping_sub(c),
+        Opcode::XOR => b ^ c,
+        Opcode::OR => b | c,
+        Opcode::AND => b & c,
+        Opcode::SLL => b << shift_amount(c),
+        Opcode::SRL => b >> shift_amount(c),
+        Opcode::SRA => ((b as i32) >> shift_amount(c)) as u32,
+        Opcode::SLT => ((b as i32) < (c as i32)) as u32,
+        Opcode::SLTU => (b < c) as u32,
+        Opcode::MUL => b.wrapping_mul(c),
+        Opcode::MULH => ((b as i32 as i64 * c as i32 as i64) >> 32) as u32,
+        Opcode::MULHU => ((b as u64 * c as u64) >> 32) as u32,
+        Opcode::MULHSU => ((b as i32 as i64).wrapping_mul(c as i64) >> 32) as u32,
+        Opcode::DIV if c == 0 => u32::MAX,
+        Opcode::DIV => (b as i32).wrapping_div(c as i32) as u32,
+        Opcode::DIVU if c == 0 => u32::MAX,
+        Opcode::DIVU => b / c,
+        Opcode::REM if c == 0 => b,
+        Opcode::REM => (b as i32).wrapping_rem(c as i32) as u32,
+        Opcode::REMU if c == 0 => b,
+        Opcode::REMU => b % c,
+        Opcode::ANDN => b & !c,
+        Opcode::ORN => b | !c,
+        Opcode::XNOR => !(b ^ c),
+        Opcode::CLZ => b.leading_zeros(),
+        Opcode::CTZ => b.trailing_zeros(),
+        Opcode::CPOP => b.count_ones(),
+        Opcode::MIN => (b as i32).min(c as i32) as u32,
+        Opcode::MAX => (b as i32).max(c as i32) as u32,
+        Opcode::MINU => b.min(c),
+        Opcode::MAXU => b.max(c),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// The value a load puts in its destination register.
+fn load_result(opcode: Opcode, addr: u32, word: u32) -> u32 {
+    let byte = word.to_le_bytes()[(addr % 4) as usize];
+    let half = (word >> (16 * ((addr >> 1) % 2))) & 0xffff;
+    match opcode {
+        Opcode::LB => byte as i8 as i32 as u32,
+        Opcode::LBU => byte as u32,
+        Opcode::LH => half as u16 as i16 as i32 as u32,
+        Opcode::LHU => half,
+        _ => word,
+    }
+}
+
+/// Check the structural invariants of a CPU event of `shard`: its accesses are the ones its
+/// instruction makes, at the clock of their position, with the values of its operands, and its
+/// result is the one the instruction computes.
+///
+/// This approximates the constraints of the CPU table on the host, to catch bugs in trace
+/// generation long before proving, but does not replace them.
+pub fn check_cpu_event(event: &CpuEvent, shard: u32) -> Result<(), EventViolation> {
+    if event.shard != shard {
+        return Err(EventViolation::WrongShard {
+            expected: shard,
+            found: event.shard,
+        });
+    }
+
+    let instruction = &event.instruction;
+    let [a_access, b_access, c_access, memory_access] = instruction_accesses(instruction);
+    let accesses = [
+        (AccessPosition::A, a_access, event.a_record, Some(event.a)),
+        (AccessPosition::B, b_access, event.b_record, Some(event.b)),
+        (AccessPosition::C, c_access, event.c_record, Some(event.c)),
+        (
+            AccessPosition::Memory,
+            memory_access,
+            event.memory_record,
+            event.memory,
+        ),
+    ];
+    for (position, access, record, value) in accesses {
+        let (found, record_shard, timestamp, prev) = match record {
+            None if access == Access::None => continue,
+            None => return Err(EventViolation::MissingRecord { position }),
+            Some(_) if access == Access::None => {
+                return Err(EventViolation::UnexpectedRecord { position })
+            }
+            Some(MemoryRecordEnum::Read(record)) => (
+                Access::Read,
+                record.shard,
+                record.timestamp,
+                (record.prev_shard, record.prev_timestamp),
+            ),
+            Some(MemoryRecordEnum::Write(record)) => (
+                Access::Write,
+                record.shard,
+                record.timestamp,
+                (record.prev_shard, record.prev_timestamp),
+            ),
+        };
+        if found != access {
+            return Err(EventViolation::WrongAccessKind { position });
+        }
+        let expected = (event.shard, event.clk + position as u32);
+        if (record_shard, timestamp) != expected {
+            return Err(EventViolation::WrongTimestamp {
+                position,
+                expected,
+                found: (record_shard, timestamp),
+            });
+        }
+        if prev >= expected {
+            return Err(EventViolation::WrongPrevTimestamp {
+                position,
+                timestamp: expected,
+                prev,
+            });
+        }
+        let record_value = record.unwrap().value();
+        match value {
+            Some(value) if value != record_value => {
+                return Err(EventViolation::WrongValue {
+                    position,
+                    expected: value,
+                    found: record_value,
+                })
+            }
+            None => return Err(EventViolation::MissingRecord { position }),
+            _ => {}
+        }
+    }
+
+    let (b, c) = (event.b, event.c);
+    let expected = match instruction.opcode {
+        Opcode::JAL | Opcode::JALR => Some(event.pc.wrapping_add(4)),
+        Opcode::AUIPC => event.u_imm().map(|imm| event.pc.wrapping_add(imm)),
+        Opcode::LB | Opcode::LH | Opcode::LW | Opcode::LBU | Opcode::LHU => event
+            .memory
+            .map(|word| load_result(instruction.opcode, b.wrapping_add(c), word)),
+        opcode => alu_result(opcode, b, c),
+    };
+    match expected {
+        Some(expected) if expected != event.a => Err(EventViolation::WrongResult {
+            expected,
+            found: event.a,
+        }),
+        _ => Ok(()),
+    }
+}
+
+impl Runtime {
+    /// Check every CPU event against [`check_cpu_event`] as it is emitted, panicking with the event
+    /// on the first violation. This slows execution down and is meant for debugging trace
+    /// generation. It is also enabled by setting `VALIDATE_EVENTS=true`.
+    pub fn set_event_validation(&mut self, enabled: bool) {
+        self.event_validation = enabled;
+    }
+
+    /// Validate an event about to be pushed to the record.
+    pub(crate) fn validate_cpu_event(&self, event: &CpuEvent) {
+        // The events of unconstrained blocks have no accesses, and an instruction that faults
+        // stops execution anyway.
+        if self.unconstrained || self.pending_error.is_some() {
+            return;
+        }
+        if let Err(violation) = check_cpu_event(event, self.current_shard()) {
+            panic!(
+                "invalid cpu event at pc=0x{:08x}: {}\n  instruction: {:?}\n  event: {:?}",
+                event.pc, violation, event.instruction, event
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::cpu::MemoryWriteRecord;
+    use crate::runtime::tests::{fibonacci_program, ssz_withdrawals_program};
+    use crate::runtime::Program;
+
+    fn events(program: Program) -> Vec<CpuEvent> {
+        let mut runtime = Runtime::new(program);
+        runtime.set_event_validation(true);
+        runtime.run();
+        runtime.record.cpu_events.to_vec()
+    }
+
+    #[test]
+    fn test_valid_events() {
+        events(ssz_withdrawals_program());
+    }
+
+    #[test]
+    fn test_event_violations() {
+        let events = events(fibonacci_program());
+        let check = |tamper: &dyn Fn(&mut CpuEvent) -> bool| {
+            let mut event = events
+                .iter()
+                .copied()
+                .find(|event| tamper(&mut event.clone()))
+                .unwrap();
+            tamper(&mut event);
+            check_cpu_event(&event, event.shard).unwrap_err()
+        };
+
+        let violation = check(&|event| {
+            let Some(MemoryRecordEnum::Write(record)) = &mut event.a_record else {
+                return false;
+            };
+            record.timestamp += 1;
+            true
+        });
+        assert!(matches!(
+            violation,
+            EventViolation::WrongTimestamp {
+                position: AccessPosition::A,
+                ..
+            }
+        ));
+
+        let violation = check(&|event| {
+            if !event.instruction.is_branch_instruction() {
+                return false;
+            }
+            let record = event.a_record.unwrap();
+            event.a_record = Some(MemoryRecordEnum::Write(MemoryWriteRecord {
+                value: record.value(),
+                shard: event.shard,
+                timestamp: event.clk + AccessPosition::A as u32,
+                ..Default::default()
+            }));
+            true
+        });
+        assert_eq!(
+            violation,
+            EventViolation::WrongAccessKind {
+                position: AccessPosition::A
+            }
+        );
+
+        let violation = check(&|event| {
+            if event.instruction.is_memory_instruction() || event.instruction.imm_c {
+                return false;
+            }
+            event.memory_record = event.c_record;
+            true
+        });
+        assert_eq!(
+            violation,
+            EventViolation::UnexpectedRecord {
+                position: AccessPosition::Memory
+            }
+        );
+
+        let violation = check(&|event| {
+            if event.instruction.opcode != Opcode::ADD || event.instruction.op_a == 0 {
+                return false;
+            }
+            event.a = event.a.wrapping_add(1);
+            if let Some(MemoryRecordEnum::Write(record)) = &mut event.a_record {
+                record.value = event.a;
+            }
+            true
+        });
+        assert!(matches!(violation, EventViolation::WrongResult { .. }));
+
+        let violation = check(&|event| {
+            if !event.instruction.is_memory_instruction() {
+                return false;
+            }
+            event.memory_record = None;
+            true
+        });
+        assert_eq!(
+            violation,
+            EventViolation::MissingRecord {
+                position: AccessPosition::Memory
+            }
+        );
+
+        let event = events[0];
+        assert_eq!(
+            check_cpu_event(&event, event.shard + 1),
+            Err(EventViolation::WrongShard {
+                expected: event.shard + 1,
+                found: event.shard,
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid cpu event")]
+    fn test_event_validation_panics() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.set_event_validation(true);
+        runtime.event_tamper = Some(|event| event.clk += 1);
+        runtime.run();
+    }
+}
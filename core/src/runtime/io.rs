@@ -1,30 +1,447 @@
 use serde::de::DeserializeOwned;
-use serde::Serialize;
-use std::io::Read;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{ErrorKind, Read};
+use std::sync::{Arc, Condvar, Mutex};
 
-use super::Runtime;
+use super::{type_hash, Error, ExecutionError, FrameType, InputSchema, Runtime};
+
+/// The number of bytes pulled from a stdin reader at once.
+pub const STDIN_READER_CHUNK_SIZE: usize = 1 << 16;
+
+/// Statistics about the data a program read and wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IoStats {
+    /// The number of bytes in the input stream, including hints written by the program and the
+    /// bytes read from the stdin reader.
+    pub input_bytes: usize,
+
+    /// The number of input bytes the program read.
+    pub input_bytes_read: usize,
+
+    /// The number of bytes written to the output stream.
+    pub output_bytes: usize,
+
+    /// The number of bytes written to each of the other output channels.
+    pub channel_bytes: BTreeMap<u32, usize>,
+}
+
+/// Who wrote an entry of the input queue, see [`Runtime::input_queue_log`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputOrigin {
+    /// The host, with one of the `write_stdin` methods.
+    Host,
+
+    /// The guest, with a `WRITE` to file descriptor 4 outside of an unconstrained block.
+    GuestConstrained,
+
+    /// The guest, with a `WRITE` to file descriptor 4 or a `HINT_SLICE` in an unconstrained
+    /// block that has since exited.
+    GuestUnconstrained,
+}
+
+/// A write to the input stream: `len` bytes from offset `position`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputQueueEntry {
+    pub position: usize,
+    pub origin: InputOrigin,
+    pub len: usize,
+}
+
+/// The bytes a helper thread reads ahead from a stdin reader, see [`StdinReader::prefetch`].
+#[derive(Default)]
+struct PrefetchState {
+    /// The bytes read ahead that were not yet taken by the runtime, in order.
+    bytes: VecDeque<u8>,
+
+    /// The number of bytes to keep read ahead.
+    wanted: usize,
+
+    eof: bool,
+
+    /// The error the reader failed with, after which the helper thread stops.
+    error: Option<ErrorKind>,
+
+    /// Set once the stdin reader is dropped, to stop the helper thread.
+    closed: bool,
+}
+
+type SharedPrefetch = Arc<(Mutex<PrefetchState>, Condvar)>;
+
+/// Read from `reader` on a helper thread until `wanted` bytes are read ahead, the reader ends or
+/// fails, or the stdin reader is dropped.
+#[cfg(not(feature = "wasm"))]
+fn prefetch_reader(mut reader: Box<dyn Read + Send>, shared: SharedPrefetch) {
+    let (state, changed) = &*shared;
+    let mut chunk = vec![0; STDIN_READER_CHUNK_SIZE];
+    loop {
+        {
+            let mut state = state.lock().unwrap();
+            while !state.closed && state.bytes.len() >= state.wanted {
+                state = changed.wait(state).unwrap();
+            }
+            if state.closed {
+                return;
+            }
+        }
+        let result = loop {
+            match reader.read(&mut chunk) {
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        let mut state = state.lock().unwrap();
+        match result {
+            Ok(0) => state.eof = true,
+            Ok(read) => state.bytes.extend(&chunk[..read]),
+            Err(err) => state.error = Some(err.kind()),
+        }
+        changed.notify_all();
+        if state.eof || state.error.is_some() {
+            return;
+        }
+    }
+}
+
+/// A reader the input stream falls back to once its in-memory bytes are consumed, see
+/// [`Runtime::set_stdin_reader`].
+pub(crate) struct StdinReader {
+    /// The reader, until it is moved to the helper thread of the first prefetch.
+    reader: Option<Box<dyn Read + Send>>,
+
+    /// The state shared with the helper thread, once the guest asked to prefetch input.
+    prefetch: Option<SharedPrefetch>,
+
+    /// The bytes pulled from the reader that are still buffered, starting at offset `start` of
+    /// the reader's stream.
+    buf: Vec<u8>,
+    start: usize,
+
+    /// The offset of the next byte to read.
+    pos: usize,
+
+    /// The offset a fork may rewind to, from which on bytes stay buffered.
+    mark: Option<usize>,
+
+    eof: bool,
+}
+
+impl StdinReader {
+    pub(crate) fn new(reader: Box<dyn Read + Send>) -> Self {
+        Self {
+            reader: Some(reader),
+            prefetch: None,
+            buf: Vec::new(),
+            start: 0,
+            pos: 0,
+            mark: None,
+            eof: false,
+        }
+    }
+
+    /// The next byte of the reader, or `None` at the end of its stream.
+    pub(crate) fn next_byte(&mut self) -> std::io::Result<Option<u8>> {
+        while self.pos == self.start + self.buf.len() {
+            if self.eof {
+                return Ok(None);
+            }
+            self.refill()?;
+        }
+        let byte = self.buf[self.pos - self.start];
+        self.pos += 1;
+        Ok(Some(byte))
+    }
+
+    /// Drop the bytes that can no longer be read and pull the next chunk from the reader.
+    fn refill(&mut self) -> std::io::Result<()> {
+        let keep = self.mark.unwrap_or(self.pos).min(self.pos);
+        self.buf.drain(..keep - self.start);
+        self.start = keep;
+
+        if let Some(shared) = &self.prefetch {
+            let (state, changed) = &**shared;
+            let mut state = state.lock().unwrap();
+            state.wanted = state.wanted.max(1);
+            changed.notify_all();
+            while state.bytes.is_empty() && !state.eof && state.error.is_none() {
+                state = changed.wait(state).unwrap();
+            }
+            if state.bytes.is_empty() {
+                if let Some(kind) = state.error {
+                    return Err(kind.into());
+                }
+                self.eof = true;
+                return Ok(());
+            }
+            let taken = state.bytes.len();
+            self.buf.extend(state.bytes.drain(..));
+            state.wanted = state.wanted.saturating_sub(taken);
+            return Ok(());
+        }
+
+        let reader = self.reader.as_mut().expect("the reader is not prefetched");
+        let len = self.buf.len();
+        self.buf.resize(len + STDIN_READER_CHUNK_SIZE, 0);
+        let read = loop {
+            match reader.read(&mut self.buf[len..]) {
+                Ok(read) => break read,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.buf.truncate(len);
+                    return Err(err);
+                }
+            }
+        };
+        self.buf.truncate(len + read);
+        self.eof = read == 0;
+        Ok(())
+    }
+
+    /// Have a helper thread read ahead until at least `len` bytes from the current one are
+    /// buffered, while execution continues. The reader is only ever read from the helper thread
+    /// from then on, so the bytes are read in the same order.
+    #[cfg(not(feature = "wasm"))]
+    pub(crate) fn prefetch(&mut self, len: usize) {
+        let buffered = self.start + self.buf.len() - self.pos;
+        if len <= buffered || self.eof {
+            return;
+        }
+        let shared = match &self.prefetch {
+            Some(shared) => shared.clone(),
+            None => {
+                let shared = SharedPrefetch::default();
+                let reader = self.reader.take().expect("the reader is not prefetched");
+                let thread_shared = shared.clone();
+                std::thread::Builder::new()
+                    .name("sp1-stdin-prefetch".to_string())
+                    .spawn(move || prefetch_reader(reader, thread_shared))
+                    .expect("failed to spawn the stdin prefetch thread");
+                self.prefetch = Some(shared.clone());
+                shared
+            }
+        };
+        let (state, changed) = &*shared;
+        let mut state = state.lock().unwrap();
+        state.wanted = state.wanted.max(len - buffered);
+        changed.notify_all();
+    }
+
+    /// Without threads, the bytes are only read when the guest reads them.
+    #[cfg(feature = "wasm")]
+    pub(crate) fn prefetch(&mut self, _len: usize) {}
+
+    /// Keep every byte from the current one on buffered until [`StdinReader::rewind`], returning
+    /// the offset to rewind to.
+    pub(crate) fn mark(&mut self) -> usize {
+        self.mark = Some(self.pos);
+        self.pos
+    }
+
+    /// Go back to the offset returned by [`StdinReader::mark`], so that the bytes read since are
+    /// read again.
+    pub(crate) fn rewind(&mut self, pos: usize) {
+        assert!(pos >= self.start, "stdin reader rewound past its buffer");
+        self.pos = pos;
+        self.mark = None;
+    }
+}
+
+impl Drop for StdinReader {
+    fn drop(&mut self) {
+        if let Some(shared) = &self.prefetch {
+            let (state, changed) = &**shared;
+            state.lock().unwrap().closed = true;
+            changed.notify_all();
+        }
+    }
+}
 
 impl Read for Runtime {
+    /// Read from the output stream, stopping at its end.
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.read_stdout_slice(buf);
-        Ok(buf.len())
+        let available = self.state.output_stream.len() - self.state.output_stream_ptr;
+        let len = buf.len().min(available);
+        self.read_stdout_slice(&mut buf[..len]);
+        Ok(len)
     }
 }
 
 impl Runtime {
+    /// Tag the frames written to the input stream from now on and let the guest check the type of
+    /// each frame before reading it. Every frame written must match the next frame of `schema`.
+    ///
+    /// Without a schema the input stream is a plain sequence of bytes.
+    pub fn set_input_schema(&mut self, schema: InputSchema) {
+        self.input_schema = Some(schema);
+    }
+
+    /// Record that a frame of type `frame` is written next, checking it against the schema.
+    fn tag_input_frame(&mut self, frame: FrameType) {
+        let Some(schema) = &self.input_schema else {
+            return;
+        };
+        let index = self.input_frames.len();
+        match schema.frames.get(index) {
+            Some(expected) if *expected == frame => {}
+            Some(expected) => panic!(
+                "input schema expects {} for frame {}, but {} was written",
+                expected, index, frame
+            ),
+            None => panic!(
+                "input schema has {} frames, but another {} was written",
+                schema.frames.len(),
+                frame
+            ),
+        }
+        self.input_frames.push(frame.tag());
+    }
+
+    /// Pull input lazily from `reader` once the bytes written to the input stream are consumed,
+    /// in chunks of [`STDIN_READER_CHUNK_SIZE`] bytes, so large inputs do not have to be loaded
+    /// before execution.
+    ///
+    /// Bytes written to the input stream, including hints written by the guest, are always read
+    /// before the next byte of the reader. Reading past the end of the reader returns
+    /// [`LWA_INPUT_EOF`](crate::syscall::LWA_INPUT_EOF) to the guest, and a failing reader stops
+    /// execution with [`ExecutionError::InputReadFailed`]. The bytes read from the reader in an
+    /// unconstrained block are read again after it. They are not covered by checkpoints or the
+    /// input schema.
+    ///
+    /// Once the guest asks to prefetch input with `PREFETCH_INPUT`, the reader is moved to a helper
+    /// thread that reads ahead while execution continues, so `reader` must be `Send`.
+    pub fn set_stdin_reader<R: Read + Send + 'static>(&mut self, reader: R) {
+        self.stdin_reader = Some(StdinReader::new(Box::new(reader)));
+    }
+
+    /// Make sure the next `len` bytes of the input stream are buffered, reading ahead from the
+    /// stdin reader on a helper thread while execution continues, so that reading them later does
+    /// not wait for the host. Does nothing without a stdin reader.
+    pub(crate) fn prefetch_input(&mut self, len: usize) {
+        let in_memory = self.state.input_stream.len() - self.state.input_stream_ptr;
+        if let Some(reader) = &mut self.stdin_reader {
+            if len > in_memory {
+                reader.prefetch(len - in_memory);
+            }
+        }
+    }
+
+    /// Read the next byte of the input stream, falling back to the stdin reader.
+    pub(crate) fn read_input_byte(&mut self) -> Result<u8, ExecutionError> {
+        let pc = self.state.pc;
+        if let Some(&byte) = self.state.input_stream.get(self.state.input_stream_ptr) {
+            self.state.input_stream_ptr += 1;
+            return Ok(byte);
+        }
+        let Some(reader) = &mut self.stdin_reader else {
+            return Err(ExecutionError::InputExhausted { pc });
+        };
+        match reader.next_byte() {
+            Ok(Some(byte)) => Ok(byte),
+            Ok(None) => Err(ExecutionError::InputExhausted { pc }),
+            Err(err) => Err(ExecutionError::InputReadFailed {
+                kind: err.kind(),
+                pc,
+            }),
+        }
+    }
+
+    /// Append `bytes` to the input stream, logging the position they were written at.
+    pub(crate) fn append_input(&mut self, origin: InputOrigin, bytes: &[u8]) {
+        self.input_queue_log.push(InputQueueEntry {
+            position: self.state.input_stream.len(),
+            origin,
+            len: bytes.len(),
+        });
+        self.state.input_stream.extend_from_slice(bytes);
+    }
+
+    /// Append bytes written by the guest to the input stream. In an unconstrained block they are
+    /// staged instead, and appended in the order they were written once the block exits.
+    pub(crate) fn append_guest_input(&mut self, bytes: &[u8]) {
+        if self.unconstrained {
+            self.unconstrained_state.staged_inputs.push(bytes.to_vec());
+        } else {
+            self.append_input(InputOrigin::GuestConstrained, bytes);
+        }
+    }
+
+    /// Drop the input stream from offset `len` on, along with the entries written there.
+    pub(crate) fn truncate_input_queue(&mut self, len: usize) {
+        self.state.input_stream.truncate(len);
+        let kept = self
+            .input_queue_log
+            .partition_point(|entry| entry.position < len);
+        self.input_queue_log.truncate(kept);
+    }
+
+    /// Every write to the input stream in the order the guest reads them, with who made it.
+    ///
+    /// Writes of the host and of constrained guest code are appended where they are made. Writes
+    /// made in an unconstrained block are appended in their original order when it exits, and
+    /// dropped if it never does.
+    pub fn input_queue_log(&self) -> &[InputQueueEntry] {
+        &self.input_queue_log
+    }
+
     pub fn write_stdin<T: Serialize>(&mut self, input: &T) {
+        self.tag_input_frame(FrameType::Bincode(type_hash::<T>()));
         let mut buf = Vec::new();
         bincode::serialize_into(&mut buf, input).expect("serialization failed");
-        self.state.input_stream.extend(buf);
+        self.append_input(InputOrigin::Host, &buf);
     }
 
     pub fn write_stdin_slice(&mut self, input: &[u8]) {
-        self.state.input_stream.extend(input);
+        self.tag_input_frame(FrameType::Bytes(input.len() as u32));
+        self.append_input(InputOrigin::Host, input);
+    }
+
+    pub fn write_stdin_u32(&mut self, input: u32) {
+        self.tag_input_frame(FrameType::U32);
+        self.append_input(InputOrigin::Host, &input.to_le_bytes());
     }
 
+    /// Read the next value from the output stream, see [`Runtime::try_read_stdout`].
+    ///
+    /// Panics if the output stream does not hold a value of type `T`.
     pub fn read_stdout<T: DeserializeOwned>(&mut self) -> T {
-        let result = bincode::deserialize_from::<_, T>(self);
-        result.unwrap()
+        self.try_read_stdout()
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Read the next value from the output stream, leaving the stream untouched if it does not
+    /// hold a value of type `T` at its current position.
+    pub fn try_read_stdout<T: DeserializeOwned>(&mut self) -> Result<T, Error> {
+        let offset = self.state.output_stream_ptr;
+        bincode::deserialize_from::<_, T>(&mut *self).map_err(|err| {
+            self.state.output_stream_ptr = offset;
+            Error::Output {
+                offset,
+                message: err.to_string(),
+            }
+        })
+    }
+
+    /// The output written so far to the given logical output channel.
+    ///
+    /// Channel zero is the stream read by `read_stdout`.
+    pub fn output_channel(&self, channel: u32) -> &[u8] {
+        self.state.output_channel(channel)
+    }
+
+    /// Statistics about the data the program read and wrote so far.
+    pub fn io_stats(&self) -> IoStats {
+        let reader_bytes = self.stdin_reader.as_ref().map_or(0, |reader| reader.pos);
+        IoStats {
+            input_bytes: self.state.input_stream.len() + reader_bytes,
+            input_bytes_read: self.state.input_stream_ptr + reader_bytes,
+            output_bytes: self.state.output_stream.len(),
+            channel_bytes: self
+                .state
+                .output_channels
+                .iter()
+                .map(|(channel, buf)| (*channel, buf.len()))
+                .collect(),
+        }
     }
 
     pub fn read_stdout_slice(&mut self, buf: &mut [u8]) {
@@ -40,10 +457,17 @@ impl Runtime {
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::runtime::Program;
+    use crate::runtime::{
+        CallError, ExecutionRecord, Instruction, Opcode, Program, Register, SyscallCode,
+    };
+    use crate::syscall::LWA_INPUT_EOF;
+    use crate::utils::asm::assemble;
     use crate::utils::tests::IO_ELF;
     use crate::utils::{self, prove_core, BabyBearBlake3};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
     use serde::Deserialize;
+    use std::time::{Duration, Instant};
 
     #[derive(Serialize, Deserialize, Debug, PartialEq)]
     struct MyPointUnaligned {
@@ -99,4 +523,485 @@ pub mod tests {
         let config = BabyBearBlake3::new();
         prove_core(config, runtime);
     }
+
+    fn write_channel_instructions(channel: u32, ptr: u32, len: u32) -> Vec<Instruction> {
+        vec![
+            Instruction::new(Opcode::ADD, 5, 0, 113, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, channel, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, ptr, false, true),
+            Instruction::new(Opcode::ADD, 12, 0, len, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ]
+    }
+
+    fn channels_program() -> Program {
+        let mut instructions = Vec::new();
+        instructions.extend(write_channel_instructions(0, 0x1000, 4));
+        instructions.extend(write_channel_instructions(1, 0x1004, 4));
+        instructions.extend(write_channel_instructions(7, 0x1008, 3));
+        instructions.extend(vec![
+            // Enter an unconstrained block, skipping it once it has been rolled back.
+            Instruction::new(Opcode::ADD, 5, 0, 110, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::BEQ, 10, 0, 32, false, true),
+        ]);
+        instructions.extend(write_channel_instructions(1, 0x100c, 4));
+        instructions.extend(vec![
+            Instruction::new(Opcode::ADD, 5, 0, 111, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, 111, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ]);
+        let mut program = Program::new(instructions, 0, 0);
+        program
+            .memory_image
+            .insert(0x1000, u32::from_le_bytes(*b"zero"));
+        program
+            .memory_image
+            .insert(0x1004, u32::from_le_bytes(*b"one!"));
+        program
+            .memory_image
+            .insert(0x1008, u32::from_le_bytes(*b"7th?"));
+        program
+            .memory_image
+            .insert(0x100c, u32::from_le_bytes(*b"hint"));
+        program
+    }
+
+    #[test]
+    fn test_output_channels() {
+        let mut runtime = Runtime::new(channels_program());
+        runtime.run();
+        assert_eq!(runtime.output_channel(0), b"zero");
+        assert_eq!(runtime.output_channel(1), b"one!");
+        assert_eq!(runtime.output_channel(7), b"7th");
+        assert_eq!(runtime.output_channel(2), b"");
+
+        // Channel zero is the existing output stream.
+        let mut buf = [0u8; 4];
+        runtime.read_stdout_slice(&mut buf);
+        assert_eq!(&buf, b"zero");
+
+        // The serialized record keeps every channel.
+        let record = ExecutionRecord::try_from_bytes(&runtime.record.to_bytes()).unwrap();
+        assert_eq!(
+            record.output_channels,
+            BTreeMap::from([
+                (0, b"zero".to_vec()),
+                (1, b"one!".to_vec()),
+                (7, b"7th".to_vec())
+            ])
+        );
+    }
+
+    /// A reader returning at most `chunk` bytes per call.
+    struct ChunkedReader {
+        data: Vec<u8>,
+        chunk: usize,
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data.drain(..len);
+            Ok(len)
+        }
+    }
+
+    fn words(words: impl Iterator<Item = u32>) -> Vec<u8> {
+        words.flat_map(u32::to_le_bytes).collect()
+    }
+
+    /// Reads `count` words of input and sums them into x9.
+    fn sum_words_program(count: u32) -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 9, 0, 0, false, true),
+            Instruction::new(Opcode::ADD, 12, 0, count, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 4, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, 101, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::ADD, 9, 9, 10, false, false),
+            Instruction::new(Opcode::ADD, 12, 12, -1i32 as u32, false, true),
+            Instruction::new(Opcode::BNE, 12, 0, -20i32 as u32, false, true),
+        ];
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_stdin_reader() {
+        let mut runtime = Runtime::new(sum_words_program(1000));
+        runtime.write_stdin_slice(&words(0..2));
+        runtime.set_stdin_reader(ChunkedReader {
+            data: words(2..1000),
+            chunk: 7,
+        });
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert_eq!(runtime.register(Register::X9), (0..1000).sum::<u32>());
+        assert_eq!(runtime.io_stats().input_bytes_read, 4000);
+    }
+
+    #[test]
+    fn test_stdin_reader_eof() {
+        let mut runtime = Runtime::new(sum_words_program(3));
+        runtime.set_stdin_reader(ChunkedReader {
+            data: words(0..2).into_iter().chain([1, 2]).collect(),
+            chunk: 7,
+        });
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert_eq!(runtime.register(Register::X10), LWA_INPUT_EOF);
+        assert_eq!(runtime.register(Register::X11), LWA_INPUT_EOF);
+        assert_eq!(
+            runtime.register(Register::X9),
+            1u32.wrapping_add(LWA_INPUT_EOF)
+        );
+        assert_eq!(runtime.io_stats().input_bytes_read, 10);
+    }
+
+    #[test]
+    fn test_stdin_reader_unconstrained() {
+        // The word read in the unconstrained block is read again after it.
+        let program = assemble(
+            &format!(
+                "
+                        li   t0, {enter}
+                        ecall
+                        beq  a0, zero, skip
+                        li   a1, 4
+                        li   t0, {lwa}
+                        ecall
+                        li   t0, {exit}
+                        ecall
+                skip:   li   a1, 4
+                        li   t0, {lwa}
+                        ecall
+                        mv   s0, a0
+                        li   a1, 4
+                        li   t0, {lwa}
+                        ecall
+                        mv   s1, a0
+                ",
+                enter = SyscallCode::ENTER_UNCONSTRAINED as u32,
+                exit = SyscallCode::EXIT_UNCONSTRAINED as u32,
+                lwa = SyscallCode::LWA as u32,
+            ),
+            0,
+        )
+        .unwrap();
+        let mut runtime = Runtime::new(program);
+        runtime.set_stdin_reader(ChunkedReader {
+            data: words(0..2),
+            chunk: 7,
+        });
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert_eq!(runtime.register(Register::X8), 0);
+        assert_eq!(runtime.register(Register::X9), 1);
+        assert_eq!(runtime.io_stats().input_bytes_read, 8);
+    }
+
+    #[test]
+    fn test_stdin_reader_rewind() {
+        let data = (0..=255).cycle().take(1 << 18).collect::<Vec<u8>>();
+        let mut reader = StdinReader::new(Box::new(ChunkedReader {
+            data: data.clone(),
+            chunk: STDIN_READER_CHUNK_SIZE - 3,
+        }));
+        let read = |reader: &mut StdinReader, len: usize| {
+            (0..len)
+                .map(|_| reader.next_byte().unwrap().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(read(&mut reader, 100), data[..100]);
+        let mark = reader.mark();
+        let forked = read(&mut reader, 3 * STDIN_READER_CHUNK_SIZE);
+        reader.rewind(mark);
+        assert_eq!(read(&mut reader, 3 * STDIN_READER_CHUNK_SIZE), forked);
+
+        // Without a mark, consumed bytes are dropped from the buffer.
+        read(&mut reader, STDIN_READER_CHUNK_SIZE);
+        assert!(reader.buf.len() < 2 * STDIN_READER_CHUNK_SIZE);
+        assert_eq!(read(&mut reader, 10), data[reader.pos - 10..reader.pos]);
+    }
+
+    /// A reader returning at most `chunk` bytes per call after sleeping, logging the offset of
+    /// each call.
+    struct SlowReader {
+        data: Vec<u8>,
+        pos: usize,
+        chunk: usize,
+        reads: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            std::thread::sleep(Duration::from_millis(5));
+            self.reads.lock().unwrap().push(self.pos);
+            let len = self.chunk.min(buf.len()).min(self.data.len() - self.pos);
+            buf[..len].copy_from_slice(&self.data[self.pos..self.pos + len]);
+            self.pos += len;
+            Ok(len)
+        }
+    }
+
+    /// Asks to prefetch `prefetch` bytes if any, then copies `count` words of input to 0x1000.
+    fn copy_words_program(prefetch: Option<u32>, count: u32) -> Program {
+        let mut instructions = Vec::new();
+        if let Some(len) = prefetch {
+            instructions.extend([
+                Instruction::new(
+                    Opcode::ADD,
+                    5,
+                    0,
+                    SyscallCode::PREFETCH_INPUT as u32,
+                    false,
+                    true,
+                ),
+                Instruction::new(Opcode::ADD, 10, 0, len, false, true),
+                Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            ]);
+        }
+        instructions.extend([
+            Instruction::new(Opcode::ADD, 12, 0, count, false, true),
+            Instruction::new(Opcode::ADD, 13, 0, 0x1000, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 4, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, SyscallCode::LWA as u32, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::SW, 10, 13, 0, false, true),
+            Instruction::new(Opcode::ADD, 13, 13, 4, false, true),
+            Instruction::new(Opcode::ADD, 12, 12, -1i32 as u32, false, true),
+            Instruction::new(Opcode::BNE, 12, 0, -24i32 as u32, false, true),
+        ]);
+        Program::new(instructions, 0, 0)
+    }
+
+    /// Run the program until its first read and return the offsets read from the stdin reader so
+    /// far, waiting for the helper thread to buffer the input if the program prefetches it. Then
+    /// run it to the end and check the words it copied.
+    fn run_prefetch(prefetch: Option<u32>) -> Vec<usize> {
+        let data = (0..4000u32)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect::<Vec<_>>();
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = Runtime::new(copy_words_program(prefetch, 1000));
+        runtime.write_stdin_slice(&data[..4]);
+        runtime.set_stdin_reader(SlowReader {
+            data: data[4..].to_vec(),
+            pos: 0,
+            chunk: 1000,
+            reads: reads.clone(),
+        });
+        runtime.initialize();
+        let first_read = (prefetch.map_or(0, |_| 3) + 4) * 4;
+        while runtime.state.pc != first_read {
+            runtime.step().unwrap();
+        }
+        if prefetch.is_some() {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while reads.lock().unwrap().len() < 4 && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+        let before_read = reads.lock().unwrap().clone();
+        assert_eq!(runtime.io_stats().input_bytes_read, 0);
+
+        while !runtime.is_done() {
+            runtime.step().unwrap();
+        }
+        let copied = (0..1000)
+            .flat_map(|i| runtime.word(0x1000 + i * 4).to_le_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(copied, data);
+        assert_eq!(runtime.io_stats().input_bytes_read, 4000);
+        before_read
+    }
+
+    #[test]
+    fn test_prefetch_input() {
+        // Without the hint, the reader is only read once the guest reads past the bytes written.
+        assert!(run_prefetch(None).is_empty());
+
+        // With it, a helper thread reads ahead before the guest reads anything, until all of the
+        // bytes that were not written are buffered.
+        assert_eq!(run_prefetch(Some(4000)), vec![0, 1000, 2000, 3000]);
+    }
+
+    #[test]
+    fn test_prefetch_without_reader() {
+        let mut runtime = Runtime::new(copy_words_program(Some(4000), 2));
+        runtime.write_stdin_slice(&words(0..2));
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert_eq!(runtime.word(0x1004), 1);
+    }
+
+    /// A program writing words to the input stream in a random interleaving of host writes,
+    /// constrained guest writes and unconstrained blocks, with the words it is expected to read.
+    struct InputQueueProgram {
+        source: String,
+
+        /// The words written by the host before the instruction at each index.
+        host_writes: BTreeMap<usize, u32>,
+
+        /// The index of the function that stages a write and then loops forever.
+        writer: usize,
+
+        /// The instructions at which the writer is called, outside of any block.
+        calls: Vec<usize>,
+
+        /// The words in the order they should end up in the input stream, with who wrote them.
+        expected: Vec<(InputOrigin, u32)>,
+    }
+
+    impl InputQueueProgram {
+        fn generate(rng: &mut StdRng) -> Self {
+            let mut program = Self {
+                source: String::new(),
+                host_writes: BTreeMap::new(),
+                writer: 0,
+                calls: Vec::new(),
+                expected: Vec::new(),
+            };
+            let mut staged = Vec::new();
+            for block in 0..rng.gen_range(1..8) {
+                let unconstrained = rng.gen_bool(0.5);
+                if unconstrained {
+                    program.emit(&[
+                        format!("li t0, {}", SyscallCode::ENTER_UNCONSTRAINED as u32),
+                        "ecall".to_string(),
+                        format!("beq a0, zero, skip{}", block),
+                    ]);
+                }
+                for _ in 0..rng.gen_range(0..6) {
+                    let value = rng.gen();
+                    let code = match rng.gen_range(0..3) {
+                        0 => {
+                            // Host writes in a block are appended right away.
+                            let index = program.len();
+                            program.host_writes.insert(index, value);
+                            program.expected.push((InputOrigin::Host, value));
+                            if !unconstrained && rng.gen_bool(0.5) {
+                                program.calls.push(index);
+                            }
+                            program.emit(&["nop".to_string()]);
+                            continue;
+                        }
+                        1 => SyscallCode::WRITE,
+                        _ if unconstrained => SyscallCode::HINT_SLICE,
+                        _ => SyscallCode::WRITE,
+                    };
+                    program.emit_guest_write(value, code);
+                    if unconstrained {
+                        staged.push((InputOrigin::GuestUnconstrained, value));
+                    } else {
+                        program
+                            .expected
+                            .push((InputOrigin::GuestConstrained, value));
+                    }
+                }
+                if unconstrained {
+                    program.emit(&[
+                        format!("li t0, {}", SyscallCode::EXIT_UNCONSTRAINED as u32),
+                        "ecall".to_string(),
+                    ]);
+                    program.source.push_str(&format!("skip{}:\n", block));
+                    program.expected.append(&mut staged);
+                }
+            }
+
+            // Read back everything written, then write in a block that is never exited.
+            for i in 0..program.expected.len() {
+                program.emit(&[
+                    "li a1, 4".to_string(),
+                    format!("li t0, {}", SyscallCode::LWA as u32),
+                    "ecall".to_string(),
+                    format!("sw a0, {}(zero)", 0x2000 + 4 * i),
+                ]);
+            }
+            program.emit(&[
+                format!("li t0, {}", SyscallCode::ENTER_UNCONSTRAINED as u32),
+                "ecall".to_string(),
+            ]);
+            program.emit_guest_write(rng.gen(), SyscallCode::HINT_SLICE);
+            program.emit(&["j done".to_string()]);
+
+            program.writer = program.len();
+            program.emit_guest_write(rng.gen(), SyscallCode::WRITE);
+            program.source.push_str("spin:\n");
+            program.emit(&["j spin".to_string()]);
+            program.source.push_str("done:\n");
+            program
+        }
+
+        /// The number of instructions emitted so far, one per line.
+        fn len(&self) -> usize {
+            self.source
+                .lines()
+                .filter(|line| !line.ends_with(':'))
+                .count()
+        }
+
+        fn emit(&mut self, lines: &[String]) {
+            for line in lines {
+                self.source.push_str(line);
+                self.source.push('\n');
+            }
+        }
+
+        /// Write `value` to the input stream with `WRITE` to file descriptor 4 or `HINT_SLICE`.
+        fn emit_guest_write(&mut self, value: u32, code: SyscallCode) {
+            let mut lines = vec![
+                format!("li t1, {}", value),
+                "sw t1, 0x1000(zero)".to_string(),
+            ];
+            if code == SyscallCode::WRITE {
+                lines.extend(["li a0, 4", "li a1, 0x1000", "li a2, 4"].map(str::to_string));
+            } else {
+                lines.extend(["li a0, 0x1000", "li a1, 4"].map(str::to_string));
+            }
+            lines.push(format!("li t0, {}", code as u32));
+            lines.push("ecall".to_string());
+            self.emit(&lines);
+        }
+    }
+
+    #[test]
+    fn test_input_queue_order() {
+        for seed in 0..64 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let generated = InputQueueProgram::generate(&mut rng);
+            let program = assemble(&generated.source, 0).unwrap();
+            let mut runtime = Runtime::new(program);
+            runtime.initialize();
+            while !runtime.is_done() {
+                let index = (runtime.state.pc / 4) as usize;
+                if let Some(&value) = generated.host_writes.get(&index) {
+                    runtime.write_stdin_u32(value);
+                }
+                if generated.calls.contains(&index) {
+                    // A block aborted without reaching its exit leaves no trace in the queue.
+                    let log = runtime.input_queue_log().to_vec();
+                    let writer = 4 * generated.writer as u32;
+                    assert!(matches!(
+                        runtime.call_function(writer, &[], 100),
+                        Err(CallError::CycleLimit { .. })
+                    ));
+                    assert_eq!(runtime.input_queue_log(), log);
+                }
+                runtime.step().unwrap();
+            }
+            assert!(runtime.unconstrained);
+
+            let log = runtime.input_queue_log();
+            assert_eq!(log.len(), generated.expected.len(), "seed {}", seed);
+            assert_eq!(runtime.state.input_stream.len(), 4 * log.len());
+            for (i, (entry, (origin, value))) in log.iter().zip(&generated.expected).enumerate() {
+                assert_eq!(entry.position, 4 * i);
+                assert_eq!((entry.origin, entry.len), (*origin, 4), "seed {}", seed);
+                // The guest consumed the words in the order of the log.
+                assert_eq!(runtime.word(0x2000 + 4 * i as u32), *value, "seed {}", seed);
+                let bytes = &runtime.state.input_stream[entry.position..entry.position + 4];
+                assert_eq!(bytes, value.to_le_bytes());
+            }
+        }
+    }
 }
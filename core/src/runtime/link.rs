@@ -0,0 +1,365 @@
+use core::fmt::{Display, Formatter};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Program, NUM_REGISTERS};
+use crate::disassembler::{Elf, WORD_SIZE};
+
+/// The addresses of the registers, which the memory image cannot overlap.
+const REGISTERS: Range<u32> = 0..NUM_REGISTERS;
+
+/// Data linked into a program after it was built, see [`Program::link_blob`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkedBlob {
+    /// The byte ranges of the memory image holding the data, sorted and disjoint.
+    pub ranges: Vec<Range<u32>>,
+}
+
+/// Where a part of the memory image of a program comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Provenance {
+    Registers,
+
+    /// The instructions of the program.
+    Text,
+
+    /// The memory image of the ELF the program was disassembled from.
+    Image,
+
+    /// The data of the nth call to [`Program::link_blob`] or [`Program::link_elf_data`].
+    Blob(usize),
+}
+
+impl Display for Provenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Provenance::Registers => write!(f, "registers"),
+            Provenance::Text => write!(f, "text"),
+            Provenance::Image => write!(f, "original image"),
+            Provenance::Blob(index) => write!(f, "blob #{}", index),
+        }
+    }
+}
+
+/// A range of the address space occupied by a program, see [`Program::image_layout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRange {
+    pub range: Range<u32>,
+    pub provenance: Provenance,
+}
+
+/// An error linking data into a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// The data does not start at a word boundary.
+    Unaligned { addr: u32 },
+
+    /// The data does not fit below the end of the address space.
+    OutOfBounds { addr: u32, len: usize },
+
+    /// The data overlaps a part of the address space that is already occupied.
+    Overlap {
+        addr: u32,
+        blob: usize,
+        existing: Provenance,
+    },
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LinkError::Unaligned { addr } => {
+                write!(f, "linked data at 0x{:08x} is not word-aligned", addr)
+            }
+            LinkError::OutOfBounds { addr, len } => write!(
+                f,
+                "linked data of {} bytes at 0x{:08x} exceeds the address space",
+                len, addr
+            ),
+            LinkError::Overlap {
+                addr,
+                blob,
+                existing,
+            } => write!(
+                f,
+                "{} overlaps {} at 0x{:08x}",
+                Provenance::Blob(*blob),
+                existing,
+                addr
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Coalesce sorted word addresses into byte ranges.
+fn word_ranges(addrs: impl IntoIterator<Item = u32>) -> Vec<Range<u32>> {
+    let mut ranges: Vec<Range<u32>> = Vec::new();
+    for addr in addrs {
+        let end = addr.saturating_add(WORD_SIZE as u32);
+        match ranges.last_mut() {
+            Some(last) if last.end == addr => last.end = end,
+            _ => ranges.push(addr..end),
+        }
+    }
+    ranges
+}
+
+impl Program {
+    /// The byte range of the instructions of the program.
+    fn text_range(&self) -> Range<u32> {
+        let len = (self.instructions.len() * WORD_SIZE) as u32;
+        self.pc_base..self.pc_base.saturating_add(len)
+    }
+
+    /// What occupies the word at `addr`, if anything.
+    fn provenance(&self, addr: u32) -> Option<Provenance> {
+        if REGISTERS.contains(&addr) {
+            return Some(Provenance::Registers);
+        }
+        if self.text_range().contains(&addr) {
+            return Some(Provenance::Text);
+        }
+        if !self.memory_image.contains_key(&addr) {
+            return None;
+        }
+        let blob = self.linked.iter().position(|blob| {
+            let index = blob.ranges.partition_point(|range| range.end <= addr);
+            blob.ranges
+                .get(index)
+                .is_some_and(|range| range.start <= addr)
+        });
+        Some(blob.map_or(Provenance::Image, Provenance::Blob))
+    }
+
+    /// Merge `words` into the memory image as the next linked blob, unless one of them is already
+    /// occupied, in which case the program is left untouched.
+    fn link_words(&mut self, words: BTreeMap<u32, u32>) -> Result<(), LinkError> {
+        let blob = self.linked.len();
+        for &addr in words.keys() {
+            if let Some(existing) = self.provenance(addr) {
+                return Err(LinkError::Overlap {
+                    addr,
+                    blob,
+                    existing,
+                });
+            }
+        }
+        self.linked.push(LinkedBlob {
+            ranges: word_ranges(words.keys().copied()),
+        });
+        self.memory_image.extend(words);
+        Ok(())
+    }
+
+    /// Load `bytes` into the memory image at the word-aligned address `addr`, padding the last
+    /// word with zeros. This lets large data, like lookup tables, be shipped separately from the
+    /// code of the guest.
+    pub fn link_blob(&mut self, addr: u32, bytes: &[u8]) -> Result<(), LinkError> {
+        if addr % WORD_SIZE as u32 != 0 {
+            return Err(LinkError::Unaligned { addr });
+        }
+        let end = addr as u64 + bytes.len().next_multiple_of(WORD_SIZE) as u64;
+        if end > u32::MAX as u64 {
+            return Err(LinkError::OutOfBounds {
+                addr,
+                len: bytes.len(),
+            });
+        }
+        let words = bytes
+            .chunks(WORD_SIZE)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut word = [0; WORD_SIZE];
+                word[..chunk.len()].copy_from_slice(chunk);
+                (addr + (i * WORD_SIZE) as u32, u32::from_le_bytes(word))
+            })
+            .collect();
+        self.link_words(words)
+    }
+
+    /// Load the data segments of the ELF `elf_bytes` into the memory image, ignoring its
+    /// executable segments.
+    pub fn link_elf_data(&mut self, elf_bytes: &[u8]) -> Result<(), LinkError> {
+        let elf = Elf::decode(elf_bytes);
+        let len = (elf.instructions.len() * WORD_SIZE) as u32;
+        let text = elf.pc_base..elf.pc_base.saturating_add(len);
+        let words = elf
+            .memory_image
+            .into_iter()
+            .filter(|(addr, _)| !text.contains(addr))
+            .collect();
+        self.link_words(words)
+    }
+
+    /// The occupied ranges of the address space sorted by address, with where each comes from.
+    pub fn image_layout(&self) -> Vec<ImageRange> {
+        let text = self.text_range();
+        let image = self
+            .memory_image
+            .keys()
+            .copied()
+            .filter(|&addr| self.provenance(addr) == Some(Provenance::Image));
+        let mut layout = vec![ImageRange {
+            range: REGISTERS,
+            provenance: Provenance::Registers,
+        }];
+        if !text.is_empty() {
+            layout.push(ImageRange {
+                range: text,
+                provenance: Provenance::Text,
+            });
+        }
+        layout.extend(word_ranges(image).into_iter().map(|range| ImageRange {
+            range,
+            provenance: Provenance::Image,
+        }));
+        for (index, blob) in self.linked.iter().enumerate() {
+            layout.extend(blob.ranges.iter().map(|range| ImageRange {
+                range: range.clone(),
+                provenance: Provenance::Blob(index),
+            }));
+        }
+        layout.sort_by_key(|range| (range.range.start, range.provenance));
+        layout
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::{Instruction, Opcode, Register, Runtime};
+
+    /// Sum the `count` words starting at `addr` into x13.
+    fn sum_loop(addr: u32, count: u32) -> Vec<Instruction> {
+        vec![
+            Instruction::new(Opcode::ADD, 10, 0, addr, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, count, false, true),
+            // loop:
+            Instruction::new(Opcode::LW, 12, 10, 0, false, true),
+            Instruction::new(Opcode::ADD, 13, 13, 12, false, false),
+            Instruction::new(Opcode::ADD, 10, 10, 4, false, true),
+            Instruction::new(Opcode::SUB, 11, 11, 1, false, true),
+            Instruction::new(Opcode::BNE, 11, 0, -16i32 as u32, false, true),
+        ]
+    }
+
+    fn words(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_link_blobs() {
+        let mut instructions = sum_loop(0x1000, 3);
+        instructions.extend(sum_loop(0x2000, 2));
+        let mut program = Program::new(instructions, 0, 0);
+        program.link_blob(0x1000, &words(&[1, 2, 3])).unwrap();
+        // The last word is padded with zeros.
+        program.link_blob(0x2000, &[10, 0, 0, 0, 20]).unwrap();
+        assert_eq!(program.memory_image[&0x2004], 20);
+
+        let digest = program.digest();
+        let mut runtime = Runtime::new(program.clone());
+        runtime.run();
+        assert_eq!(runtime.register(Register::X13), 36);
+
+        assert_eq!(
+            program.image_layout(),
+            vec![
+                ImageRange {
+                    range: 0..32,
+                    provenance: Provenance::Registers,
+                },
+                ImageRange {
+                    range: 0..56,
+                    provenance: Provenance::Text,
+                },
+                ImageRange {
+                    range: 0x1000..0x100c,
+                    provenance: Provenance::Blob(0),
+                },
+                ImageRange {
+                    range: 0x2000..0x2008,
+                    provenance: Provenance::Blob(1),
+                },
+            ]
+        );
+
+        let mut relinked = Program::new(program.instructions.clone(), 0, 0);
+        relinked.link_blob(0x1000, &words(&[1, 2, 3])).unwrap();
+        relinked.link_blob(0x2000, &[10, 0, 0, 0, 21]).unwrap();
+        assert_ne!(relinked.digest(), digest);
+    }
+
+    #[test]
+    fn test_link_overlap() {
+        let mut program = Program::new(sum_loop(0x1000, 3), 0, 0);
+        program.link_blob(0x1000, &words(&[1, 2, 3])).unwrap();
+
+        let before = program.clone();
+        let err = program.link_blob(0x1008, &words(&[4, 5])).unwrap_err();
+        assert_eq!(
+            err,
+            LinkError::Overlap {
+                addr: 0x1008,
+                blob: 1,
+                existing: Provenance::Blob(0),
+            }
+        );
+        assert_eq!(err.to_string(), "blob #1 overlaps blob #0 at 0x00001008");
+        assert_eq!(program.memory_image, before.memory_image);
+        assert_eq!(program.linked, before.linked);
+
+        assert!(matches!(
+            program.link_blob(8, &[0; 4]),
+            Err(LinkError::Overlap {
+                existing: Provenance::Registers,
+                ..
+            })
+        ));
+        assert!(matches!(
+            program.link_blob(0x40, &[0; 4]),
+            Err(LinkError::Overlap {
+                existing: Provenance::Text,
+                ..
+            })
+        ));
+        assert_eq!(
+            program.link_blob(0x1002, &[0; 4]),
+            Err(LinkError::Unaligned { addr: 0x1002 })
+        );
+        assert!(matches!(
+            program.link_blob(u32::MAX - 3, &[0; 8]),
+            Err(LinkError::OutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_link_elf_data() {
+        let mut program = fibonacci_program();
+        let image = program.memory_image.clone();
+        let err = program
+            .link_elf_data(crate::utils::tests::FIBONACCI_ELF)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            LinkError::Overlap {
+                existing: Provenance::Image,
+                ..
+            }
+        ));
+        assert_eq!(program.memory_image, image);
+
+        let layout = program.image_layout();
+        assert!(layout
+            .iter()
+            .any(|range| range.provenance == Provenance::Image));
+        assert!(layout
+            .windows(2)
+            .all(|pair| pair[0].range.start <= pair[1].range.start));
+    }
+}
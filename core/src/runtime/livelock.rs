@@ -0,0 +1,330 @@
+use core::fmt::{Display, Formatter};
+
+use super::{ExecutionError, Runtime};
+
+/// What the runtime does when it suspects the guest is stuck in a loop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LivelockAction {
+    /// Log a warning, keep the report and continue executing.
+    #[default]
+    Warn,
+
+    /// Stop execution with [`ExecutionError::LivelockSuspected`].
+    Abort,
+}
+
+/// Configuration of the livelock detector, see [`Runtime::set_livelock_config`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LivelockConfig {
+    /// The number of most recent pcs searched for a repeating cycle.
+    pub window: usize,
+
+    /// The number of cycles between two searches of the window.
+    pub check_interval: u32,
+
+    /// The number of iterations of the same cycle after which a livelock is reported.
+    pub max_iterations: u32,
+
+    /// The number of distinct addresses, registers included, the loop may write to. A loop that
+    /// writes to more addresses or invokes a syscall is making progress and is not reported.
+    pub working_set: usize,
+
+    pub action: LivelockAction,
+}
+
+impl Default for LivelockConfig {
+    fn default() -> Self {
+        Self {
+            window: 64,
+            check_interval: 1024,
+            max_iterations: 1 << 20,
+            working_set: 8,
+            action: LivelockAction::default(),
+        }
+    }
+}
+
+/// A loop the guest seems to be stuck in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LivelockSuspected {
+    /// The pcs of one iteration of the loop, starting from the lowest one.
+    pub pcs: Vec<u32>,
+
+    /// The number of iterations observed so far.
+    pub iterations: u32,
+
+    /// The global clock at which the loop was first observed.
+    pub cycle_start: u32,
+
+    /// The function containing the first pc of the loop, if the program has a symbol for it.
+    pub function: Option<String>,
+}
+
+impl Display for LivelockSuspected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "livelock suspected: {} iterations of a loop over {} pcs starting at 0x{:08x} in {} \
+             since cycle {}",
+            self.iterations,
+            self.pcs.len(),
+            self.pcs[0],
+            self.function.as_deref().unwrap_or("<unknown>"),
+            self.cycle_start
+        )
+    }
+}
+
+/// A cycle of pcs the window currently repeats.
+#[derive(Debug)]
+struct Candidate {
+    pcs: Vec<u32>,
+    iterations: u32,
+    cycle_start: u32,
+    reported: bool,
+}
+
+/// Tracks the recent pcs of a runtime to find loops that make no progress.
+///
+/// Every pc is pushed into a ring buffer whose hash is updated incrementally, so the window is
+/// only searched for a cycle when its contents changed since the last check.
+#[derive(Debug)]
+pub(crate) struct LivelockDetector {
+    pub(crate) config: LivelockConfig,
+    pcs: Vec<u32>,
+    head: usize,
+    hash: u64,
+    last_hash: Option<u64>,
+    cycles: u32,
+    written: Vec<u32>,
+    dirty: bool,
+    candidate: Option<Candidate>,
+    reports: Vec<LivelockSuspected>,
+}
+
+/// Mix a pc into a value that is summed into the window hash.
+fn mix(pc: u32) -> u64 {
+    (pc as u64 ^ 0x9e37_79b9_7f4a_7c15).wrapping_mul(0xbf58_476d_1ce4_e5b9)
+}
+
+impl LivelockDetector {
+    pub(crate) fn new(config: LivelockConfig) -> Self {
+        assert!(
+            config.window >= 2,
+            "the livelock window must hold at least 2 pcs"
+        );
+        Self {
+            pcs: Vec::with_capacity(config.window),
+            head: 0,
+            hash: 0,
+            last_hash: None,
+            cycles: 0,
+            written: Vec::with_capacity(config.working_set),
+            dirty: false,
+            candidate: None,
+            reports: Vec::new(),
+            config,
+        }
+    }
+
+    /// Record a write to `addr`.
+    #[inline]
+    pub(crate) fn record_write(&mut self, addr: u32) {
+        if !self.written.contains(&addr) {
+            if self.written.len() < self.config.working_set {
+                self.written.push(addr);
+            } else {
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Record a syscall.
+    #[inline]
+    pub(crate) fn record_syscall(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Record the pc of the next instruction, returning the loop to report if one was found.
+    fn observe(&mut self, pc: u32, global_clk: u32) -> Option<LivelockSuspected> {
+        if self.pcs.len() < self.config.window {
+            self.pcs.push(pc);
+        } else {
+            self.hash = self.hash.wrapping_sub(mix(self.pcs[self.head]));
+            self.pcs[self.head] = pc;
+            self.head = (self.head + 1) % self.config.window;
+        }
+        self.hash = self.hash.wrapping_add(mix(pc));
+
+        self.cycles += 1;
+        if self.cycles < self.config.check_interval.max(1) {
+            return None;
+        }
+        self.check(global_clk)
+    }
+
+    /// Update the current candidate with the cycles executed since the last check.
+    fn check(&mut self, global_clk: u32) -> Option<LivelockSuspected> {
+        let cycles = core::mem::take(&mut self.cycles);
+        if core::mem::take(&mut self.dirty) || self.pcs.len() < self.config.window {
+            self.written.clear();
+            self.candidate = None;
+            self.last_hash = None;
+            return None;
+        }
+
+        let unchanged = self.last_hash == Some(self.hash)
+            && self
+                .candidate
+                .as_ref()
+                .is_some_and(|candidate| self.has_period(candidate.pcs.len()));
+        if !unchanged {
+            let Some(pcs) = self.find_cycle() else {
+                self.candidate = None;
+                self.last_hash = None;
+                return None;
+            };
+            if self.candidate.as_ref().map(|candidate| &candidate.pcs) != Some(&pcs) {
+                self.candidate = Some(Candidate {
+                    pcs,
+                    iterations: 0,
+                    cycle_start: global_clk.saturating_sub(self.config.window as u32),
+                    reported: false,
+                });
+            }
+        }
+        self.last_hash = Some(self.hash);
+
+        let candidate = self.candidate.as_mut().unwrap();
+        candidate.iterations = candidate
+            .iterations
+            .saturating_add(cycles / candidate.pcs.len() as u32);
+        if candidate.reported || candidate.iterations <= self.config.max_iterations {
+            return None;
+        }
+        candidate.reported = true;
+        Some(LivelockSuspected {
+            pcs: candidate.pcs.clone(),
+            iterations: candidate.iterations,
+            cycle_start: candidate.cycle_start,
+            function: None,
+        })
+    }
+
+    /// The pc `i` steps after the oldest pc of the window.
+    fn pc(&self, i: usize) -> u32 {
+        self.pcs[(self.head + i) % self.pcs.len()]
+    }
+
+    /// Whether the window repeats itself every `period` pcs.
+    fn has_period(&self, period: usize) -> bool {
+        (period..self.pcs.len()).all(|i| self.pc(i) == self.pc(i - period))
+    }
+
+    /// Find the shortest cycle the whole window repeats, rotated to start at its lowest pc.
+    fn find_cycle(&self) -> Option<Vec<u32>> {
+        let len = self.pcs.len();
+        let period = (1..=len / 2).find(|&period| self.has_period(period))?;
+        let mut pcs = (len - period..len).map(|i| self.pc(i)).collect::<Vec<_>>();
+        let start = (0..period).min_by_key(|&i| pcs[i]).unwrap();
+        pcs.rotate_left(start);
+        Some(pcs)
+    }
+}
+
+impl Runtime {
+    /// Enable the livelock detector, which reports loops that repeat the same pcs for more than
+    /// `config.max_iterations` iterations without syscalls and while writing to at most
+    /// `config.working_set` addresses.
+    pub fn set_livelock_config(&mut self, config: LivelockConfig) {
+        self.livelock_detector = Some(LivelockDetector::new(config));
+    }
+
+    /// The livelocks reported under [`LivelockAction::Warn`] so far.
+    pub fn livelock_reports(&self) -> &[LivelockSuspected] {
+        self.livelock_detector
+            .as_ref()
+            .map(|detector| detector.reports.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Feed the pc of the next instruction to the livelock detector, if enabled.
+    pub(crate) fn check_livelock(&mut self) -> Result<(), ExecutionError> {
+        let Some(detector) = &mut self.livelock_detector else {
+            return Ok(());
+        };
+        let Some(mut report) = detector.observe(self.state.pc, self.state.global_clk) else {
+            return Ok(());
+        };
+        report.function = self
+            .program
+            .symbols
+            .lookup(report.pcs[0])
+            .map(|symbol| symbol.name.clone());
+        match detector.config.action {
+            LivelockAction::Warn => {
+                tracing::warn!("{}", report);
+                detector.reports.push(report);
+                Ok(())
+            }
+            LivelockAction::Abort => Err(ExecutionError::LivelockSuspected(report)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Instruction, Opcode, Program, Symbol, SymbolTable};
+
+    fn config(action: LivelockAction) -> LivelockConfig {
+        LivelockConfig {
+            max_iterations: 10_000,
+            action,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_infinite_loop_livelock() {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 29, 0, 5, false, true),
+            Instruction::new(Opcode::JAL, 0, 0, 0, true, true),
+        ];
+        let mut program = Program::new(instructions, 0, 0);
+        program.symbols = SymbolTable::new(vec![Symbol::new("spin", 4, 4)]);
+        let mut runtime = Runtime::new(program);
+        runtime.emit_events = false;
+        runtime.set_livelock_config(config(LivelockAction::Abort));
+
+        let Err(ExecutionError::LivelockSuspected(report)) = runtime.try_run() else {
+            panic!("expected a livelock to be detected");
+        };
+        assert_eq!(report.pcs, [4]);
+        assert!(report.iterations > 10_000);
+        assert_eq!(report.function.as_deref(), Some("spin"));
+        assert_eq!(runtime.state.pc, 4);
+    }
+
+    #[test]
+    fn test_memset_is_not_livelock() {
+        let iterations = 100_000;
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, 0x10000, false, true),
+            Instruction::new(Opcode::ADD, 6, 0, iterations, false, true),
+            Instruction::new(Opcode::ADD, 7, 0, 0xff, false, true),
+            // loop:
+            Instruction::new(Opcode::SW, 7, 5, 0, false, true),
+            Instruction::new(Opcode::ADD, 5, 5, 4, false, true),
+            Instruction::new(Opcode::ADD, 6, 6, -1i32 as u32, false, true),
+            Instruction::new(Opcode::BNE, 6, 0, -12i32 as u32, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime.emit_events = false;
+        runtime.set_livelock_config(config(LivelockAction::Abort));
+
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert!(runtime.livelock_reports().is_empty());
+        assert_eq!(runtime.word(0x10000 + 4 * (iterations - 1)), 0xff);
+    }
+}
@@ -0,0 +1,313 @@
+use core::fmt::{Display, Formatter};
+use std::collections::HashMap;
+
+use nohash_hasher::BuildNoHashHasher;
+
+use super::relocate::splitmix64;
+use super::Runtime;
+
+/// Configuration of the access locality collector, see [`Runtime::enable_locality_sampling`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalityConfig {
+    /// One in this many memory accesses is sampled, on average. With a rate of 1 every access is
+    /// a candidate for the reservoir.
+    pub sample_rate: u32,
+
+    /// The number of samples kept. Once the reservoir is full, each new sample replaces a random
+    /// one, so the samples stay a uniform subset of the sampled accesses.
+    pub reservoir_size: usize,
+
+    /// The number of hottest addresses listed in the report.
+    pub top_n: usize,
+
+    /// The seed of the generator choosing the samples, to make reports reproducible.
+    pub seed: u64,
+}
+
+impl Default for LocalityConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 1,
+            reservoir_size: 1 << 16,
+            top_n: 16,
+            seed: 0,
+        }
+    }
+}
+
+/// How local the memory accesses of an execution were, see [`Runtime::locality_report`].
+///
+/// The report is computed from the samples only: it is exact when every access was sampled and
+/// the reservoir never filled up. Otherwise reuse distances are overestimated and sequential runs
+/// are cut short by the accesses that were not kept.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LocalityReport {
+    /// The number of memory accesses seen by the collector, registers excluded.
+    pub accesses: u64,
+
+    /// The number of samples the report is computed from.
+    pub samples: usize,
+
+    /// The number of reuses by distance in cycles to the previous sample of the same address.
+    /// Bucket `i` counts the distances in `[2^i, 2^(i+1))`, and bucket 0 also counts reuses
+    /// within the same cycle.
+    pub reuse_distances: Vec<u64>,
+
+    /// The number of samples of an address not sampled before.
+    pub first_accesses: u64,
+
+    /// The most sampled addresses with their number of samples, hottest first.
+    pub hottest: Vec<(u32, u64)>,
+
+    /// The fraction of the samples that hit the hottest 1% of the sampled addresses.
+    pub hot_fraction: f64,
+
+    /// The average number of samples in a run of accesses to consecutive words.
+    pub avg_sequential_run: f64,
+}
+
+impl Display for LocalityReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        writeln!(
+            f,
+            "{} accesses, {} samples, {} first accesses, {:.1}% to the hottest 1% of addresses, \
+             average sequential run {:.2}",
+            self.accesses,
+            self.samples,
+            self.first_accesses,
+            self.hot_fraction * 100.0,
+            self.avg_sequential_run
+        )?;
+        for (bucket, &count) in self.reuse_distances.iter().enumerate() {
+            if count > 0 {
+                writeln!(f, "  reuse distance >= {}: {}", 1u64 << bucket, count)?;
+            }
+        }
+        for (addr, count) in self.hottest.iter() {
+            writeln!(f, "  0x{:08x}: {}", addr, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Samples the memory accesses as they happen, keeping a bounded reservoir of them.
+#[derive(Debug, Clone)]
+pub(crate) struct LocalityCollector {
+    pub(crate) config: LocalityConfig,
+
+    /// The state of the generator choosing the samples.
+    rng: u64,
+
+    /// The number of accesses seen and of those that were sampled.
+    accesses: u64,
+    sampled: u64,
+
+    /// The sampled accesses, as the address and the global clock of the access.
+    reservoir: Vec<(u32, u32)>,
+}
+
+impl LocalityCollector {
+    pub(crate) fn new(config: LocalityConfig) -> Self {
+        Self {
+            rng: config.seed,
+            config,
+            accesses: 0,
+            sampled: 0,
+            reservoir: Vec::new(),
+        }
+    }
+
+    #[inline]
+    fn next_random(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(1);
+        splitmix64(self.rng)
+    }
+
+    #[inline]
+    pub(crate) fn record(&mut self, addr: u32, clk: u32) {
+        self.accesses += 1;
+        if self.config.sample_rate > 1 && self.next_random() % self.config.sample_rate as u64 != 0 {
+            return;
+        }
+        self.sampled += 1;
+        if self.reservoir.len() < self.config.reservoir_size {
+            self.reservoir.push((addr, clk));
+        } else {
+            let slot = self.next_random() % self.sampled;
+            if let Some(sample) = self.reservoir.get_mut(slot as usize) {
+                *sample = (addr, clk);
+            }
+        }
+    }
+
+    fn report(&self) -> LocalityReport {
+        let mut samples = self.reservoir.clone();
+        samples.sort_by_key(|&(addr, clk)| (clk, addr));
+
+        let mut last_seen = HashMap::<u32, u32, BuildNoHashHasher<u32>>::default();
+        let mut counts = HashMap::<u32, u64, BuildNoHashHasher<u32>>::default();
+        let mut reuse_distances = Vec::new();
+        let mut first_accesses = 0;
+        let mut runs = 0u64;
+        let mut prev_addr = None;
+        for &(addr, clk) in samples.iter() {
+            match last_seen.insert(addr, clk) {
+                Some(prev_clk) => {
+                    let distance = (clk - prev_clk).max(1);
+                    let bucket = distance.ilog2() as usize;
+                    if reuse_distances.len() <= bucket {
+                        reuse_distances.resize(bucket + 1, 0);
+                    }
+                    reuse_distances[bucket] += 1;
+                }
+                None => first_accesses += 1,
+            }
+            *counts.entry(addr).or_default() += 1;
+            if prev_addr.map(|prev: u32| prev.wrapping_add(4)) != Some(addr) {
+                runs += 1;
+            }
+            prev_addr = Some(addr);
+        }
+
+        let mut hottest = counts.into_iter().collect::<Vec<_>>();
+        hottest.sort_by_key(|&(addr, count)| (std::cmp::Reverse(count), addr));
+        let hot = hottest.len().div_ceil(100);
+        let hot_samples = hottest[..hot].iter().map(|&(_, count)| count).sum::<u64>();
+        hottest.truncate(self.config.top_n);
+
+        let ratio = |numerator: f64, denominator: f64| {
+            if denominator == 0.0 {
+                0.0
+            } else {
+                numerator / denominator
+            }
+        };
+        LocalityReport {
+            accesses: self.accesses,
+            samples: samples.len(),
+            reuse_distances,
+            first_accesses,
+            hottest,
+            hot_fraction: ratio(hot_samples as f64, samples.len() as f64),
+            avg_sequential_run: ratio(samples.len() as f64, runs as f64),
+        }
+    }
+}
+
+impl Runtime {
+    /// Sample the memory accesses of the guest from now on, to measure how local they are, see
+    /// [`Runtime::locality_report`]. Accesses to registers and in unconstrained blocks are
+    /// ignored. The collector keeps at most `config.reservoir_size` samples, however long the
+    /// execution is.
+    ///
+    /// Panics if `config.sample_rate` is zero.
+    pub fn enable_locality_sampling(&mut self, config: LocalityConfig) {
+        assert!(config.sample_rate > 0, "sample rate must be positive");
+        self.locality_collector = Some(LocalityCollector::new(config));
+    }
+
+    /// The locality of the memory accesses sampled so far, or `None` if
+    /// [`Runtime::enable_locality_sampling`] was not called.
+    pub fn locality_report(&self) -> Option<LocalityReport> {
+        self.locality_collector
+            .as_ref()
+            .map(LocalityCollector::report)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Instruction, Opcode, Program};
+
+    const WORDS: u32 = 256;
+
+    /// Write `WORDS` consecutive words, then read them back in the same order.
+    fn scan_program() -> Program {
+        let mut instructions = Vec::new();
+        for opcode in [Opcode::SW, Opcode::LW] {
+            instructions.extend([
+                Instruction::new(Opcode::ADD, 5, 0, 0x10000, false, true),
+                Instruction::new(Opcode::ADD, 6, 0, WORDS, false, true),
+                // loop:
+                Instruction::new(opcode, 7, 5, 0, false, true),
+                Instruction::new(Opcode::ADD, 5, 5, 4, false, true),
+                Instruction::new(Opcode::SUB, 6, 6, 1, false, true),
+                Instruction::new(Opcode::BNE, 6, 0, -12i32 as u32, false, true),
+            ]);
+        }
+        Program::new(instructions, 0, 0)
+    }
+
+    /// Increment the same word `WORDS` times.
+    fn hammer_program() -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, 0x10000, false, true),
+            Instruction::new(Opcode::ADD, 6, 0, WORDS, false, true),
+            // loop:
+            Instruction::new(Opcode::LW, 7, 5, 0, false, true),
+            Instruction::new(Opcode::ADD, 7, 7, 1, false, true),
+            Instruction::new(Opcode::SW, 7, 5, 0, false, true),
+            Instruction::new(Opcode::SUB, 6, 6, 1, false, true),
+            Instruction::new(Opcode::BNE, 6, 0, -16i32 as u32, false, true),
+        ];
+        Program::new(instructions, 0, 0)
+    }
+
+    fn report(program: Program, config: LocalityConfig) -> LocalityReport {
+        let mut runtime = Runtime::new(program);
+        runtime.emit_events = false;
+        runtime.enable_locality_sampling(config);
+        runtime.run();
+        runtime.locality_report().unwrap()
+    }
+
+    #[test]
+    fn test_sequential_scan_locality() {
+        let report = report(scan_program(), LocalityConfig::default());
+        assert_eq!(report.accesses, 2 * WORDS as u64);
+        assert_eq!(report.samples, 2 * WORDS as usize);
+        assert_eq!(report.first_accesses, WORDS as u64);
+        assert_eq!(report.avg_sequential_run, WORDS as f64);
+
+        // Each word is read back one pass of 4 instructions per word later.
+        let buckets = report.reuse_distances;
+        assert_eq!(buckets.iter().sum::<u64>(), WORDS as u64);
+        assert_eq!(buckets.len(), (4 * WORDS).ilog2() as usize + 1);
+        assert!(buckets[..buckets.len() - 1].iter().all(|&count| count == 0));
+
+        assert!(report.hottest.iter().all(|&(_, count)| count == 2));
+        assert_eq!(report.hot_fraction, 2.0 * 3.0 / (2 * WORDS) as f64);
+    }
+
+    #[test]
+    fn test_hammered_address_locality() {
+        let report = report(hammer_program(), LocalityConfig::default());
+        assert_eq!(report.accesses, 2 * WORDS as u64);
+        assert_eq!(report.first_accesses, 1);
+        assert_eq!(report.avg_sequential_run, 1.0);
+        assert_eq!(report.hottest, vec![(0x10000, 2 * WORDS as u64)]);
+        assert_eq!(report.hot_fraction, 1.0);
+
+        // The store follows the load after two cycles, the next load after three.
+        assert_eq!(report.reuse_distances, vec![0, 2 * WORDS as u64 - 1]);
+    }
+
+    #[test]
+    fn test_reservoir_bounds_samples() {
+        let config = LocalityConfig {
+            sample_rate: 2,
+            reservoir_size: 32,
+            top_n: 4,
+            seed: 7,
+        };
+        let sampled = report(scan_program(), config.clone());
+        assert_eq!(sampled.accesses, 2 * WORDS as u64);
+        assert_eq!(sampled.samples, 32);
+        assert_eq!(sampled.hottest.len(), 4);
+        assert_eq!(sampled, report(scan_program(), config));
+
+        let runtime = Runtime::new(scan_program());
+        assert!(runtime.locality_report().is_none());
+    }
+}
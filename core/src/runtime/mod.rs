@@ -1,37 +1,162 @@
+mod arena;
+mod backtrace;
+mod boundary;
+mod branch;
+mod bundle;
+mod call;
+mod call_graph;
+mod cancel;
+mod checkpoint;
+mod chrome_trace;
+mod commitment;
+mod condition;
+mod consistency;
+mod cosim;
+mod cost;
+mod dead_store;
+mod debugger;
+mod divergence;
+mod dump;
+mod entry;
+mod error;
+mod estimate;
+mod event_builder;
+mod extensions;
+mod field_check;
+mod filter;
+mod final_memory;
+mod final_state;
+mod format;
+mod frozen;
+mod handle;
+mod incremental;
+mod indices;
 mod instruction;
+mod invariants;
 mod io;
+mod link;
+mod livelock;
+mod locality;
 mod opcode;
+mod options;
+mod patch;
+mod postprocess;
+mod profile;
 mod program;
+mod provenance;
 mod record;
+mod region;
 mod register;
+mod relocate;
+mod report;
+mod restrict;
+mod schema;
+mod scratch;
+mod sharding;
+mod simulate;
+mod sink;
+mod sparse;
+mod stack;
 mod state;
+mod strace;
+mod symbols;
 mod syscall;
-
-use crate::cpu::{MemoryReadRecord, MemoryRecord, MemoryWriteRecord};
-use crate::utils::env;
-use crate::{alu::AluEvent, cpu::CpuEvent};
+mod syscall_args;
+mod syscall_clock;
+mod time_travel;
+mod timing;
+mod trace;
+mod trace_filter;
+mod trace_log;
+
+use crate::cpu::{AuipcEvent, MemoryReadRecord, MemoryRecord, MemoryWriteRecord};
+use crate::syscall::{DEFAULT_MIN_HINTED_SHARD_CYCLES, PANIC_EXIT_CODE};
+use crate::{
+    alu::{
+        shift_amount, AluEvent, BitmanipEvent, DivRemEvent, DivRemMetadata, MulEvent, MulMetadata,
+    },
+    cpu::CpuEvent,
+};
+pub use arena::*;
+pub use backtrace::*;
+pub use boundary::*;
+pub use branch::*;
+pub use bundle::*;
+pub use call::*;
+pub use call_graph::*;
+pub use cancel::*;
+pub use checkpoint::*;
+pub use chrome_trace::*;
+pub use commitment::*;
+pub use condition::*;
+pub use consistency::*;
+pub use cosim::*;
+pub use cost::*;
+pub use dead_store::*;
+pub use debugger::*;
+pub use divergence::*;
+pub use dump::*;
+pub use entry::*;
+pub use error::*;
+pub use estimate::*;
+pub use event_builder::*;
+pub use extensions::*;
+pub use field_check::*;
+pub use filter::*;
+pub use final_memory::*;
+pub use final_state::*;
+pub use format::*;
+pub use frozen::*;
+pub use handle::*;
 use hashbrown::hash_map::Entry;
+pub use incremental::*;
+pub use indices::*;
 pub use instruction::*;
+pub use invariants::*;
+pub use io::{InputOrigin, InputQueueEntry, IoStats, STDIN_READER_CHUNK_SIZE};
+pub use link::*;
+pub use livelock::*;
+pub use locality::*;
 use nohash_hasher::BuildNoHashHasher;
 pub use opcode::*;
+pub use options::*;
+pub use patch::*;
+pub use postprocess::*;
+pub use profile::*;
 pub use program::*;
+pub use provenance::*;
 pub use record::*;
+pub use region::*;
 pub use register::*;
+pub use relocate::*;
+pub use report::*;
+pub use restrict::*;
+pub use schema::*;
+pub use scratch::*;
+pub use sharding::*;
+pub use simulate::*;
+pub use sink::*;
+pub use sparse::*;
+pub use stack::*;
 pub use state::*;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufWriter;
-use std::io::Write;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+pub use strace::*;
+pub use symbols::*;
 pub use syscall::*;
-
-use p3_baby_bear::BabyBear;
-use p3_field::AbstractField;
-
+pub use syscall_args::*;
+pub use syscall_clock::*;
+pub use time_travel::*;
+pub use timing::*;
+pub use trace::*;
+pub use trace_filter::*;
+pub use trace_log::*;
+
+use self::io::StdinReader;
 use self::state::ExecutionState;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AccessPosition {
     Memory = 0,
     // Note that these AccessPositions mean that when when read/writing registers, they must be
@@ -41,6 +166,39 @@ pub enum AccessPosition {
     A = 3,
 }
 
+/// How reads of memory that was never written are handled. Registers and addresses in the
+/// program's memory image always count as initialized.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UninitMemoryPolicy {
+    /// Uninitialized memory reads as zero.
+    #[default]
+    Zero,
+
+    /// Uninitialized memory reads as zero, but the first read of each word is logged, up to
+    /// `max_warnings` times in total.
+    Warn { max_warnings: usize },
+
+    /// Reading uninitialized memory stops execution with `ExecutionError::UninitializedRead`.
+    Trap,
+}
+
+/// The number of registers, which occupy the lowest memory addresses.
+pub(crate) const NUM_REGISTERS: u32 = 32;
+
+/// Addresses up to and including this one are reserved for registers.
+pub(crate) const MAX_REGISTER_ADDR: u32 = 40;
+
+/// Memory words at and above this address cannot be accessed, since their addresses are not
+/// representable as BabyBear elements, see [`check_addr`].
+pub const MEMORY_ADDR_LIMIT: u32 = BABYBEAR_MODULUS;
+
+/// Whether the aligned address `addr` is a word of memory, rather than reserved for registers or
+/// out of range.
+#[inline]
+fn is_memory_word(addr: u32) -> bool {
+    addr > MAX_REGISTER_ADDR && check_addr(addr).is_ok()
+}
+
 /// An implementation of a runtime for the SP1 VM.
 ///
 /// The runtime is responsible for executing a user program and tracing important events which occur
@@ -67,8 +225,48 @@ pub struct Runtime {
     /// A counter for the number of cycles that have been executed in certain functions.
     pub cycle_tracker: HashMap<String, (u32, u32)>,
 
-    /// A buffer for writing trace events to a file.
-    pub trace_buf: Option<BufWriter<File>>,
+    /// A sink for the pc trace, set from [`RuntimeOptions::trace_file`].
+    pub trace_buf: Option<Box<dyn TraceSink>>,
+
+    /// Whether to record CPU and ALU events. Disabling this is much cheaper when only the cycle
+    /// count or the output of the program is needed, but the record can then not be proven.
+    pub emit_events: bool,
+
+    /// Which classes of events are recorded when `emit_events` is set.
+    pub record_filter: RecordFilter,
+
+    /// The entries of the most recent cycles, see [`Runtime::enable_trace_log`].
+    pub(crate) trace_log: Option<TraceLog>,
+
+    /// The functions the pc trace and the trace log are restricted to, see
+    /// [`RuntimeOptions::trace_filter`].
+    pub(crate) trace_filter: Option<TraceFilter>,
+
+    /// The [`Program::min_data_addr`] of the program, computed once as it is checked on every
+    /// access.
+    pub(crate) min_data_addr: u32,
+
+    /// Whether registered syscalls are restricted to their memory footprint, see
+    /// [`Runtime::set_syscall_footprint_checks`].
+    pub(crate) syscall_footprint_checks: bool,
+
+    /// Whether entering an unconstrained block stops execution, see
+    /// [`RuntimeOptions::forbid_unconstrained`].
+    pub(crate) forbid_unconstrained: bool,
+
+    /// The maximum length of a string or byte slice argument of a syscall, see
+    /// [`RuntimeOptions::max_syscall_arg_len`].
+    pub(crate) max_syscall_arg_len: u32,
+
+    /// Whether the accesses of syscalls are journaled, see
+    /// [`Runtime::set_syscall_clock_journal`].
+    pub(crate) syscall_clock_journal: bool,
+
+    /// The scratch region of the guest, see [`RuntimeOptions::scratch_region`].
+    pub(crate) scratch: Option<ScratchRegion>,
+
+    /// How the boundaries of the shards are placed, see [`Runtime::set_sharding_config`].
+    pub(crate) sharding: Option<ShardLayout>,
 
     /// Whether the runtime is in constrained mode or not.
     /// In unconstrained mode, any events, clock, register, or memory changes are reset after leaving
@@ -77,50 +275,370 @@ pub struct Runtime {
 
     pub(crate) unconstrained_state: ForkState,
 
-    pub syscall_map: HashMap<SyscallCode, Rc<dyn Syscall>>,
+    /// How reads of uninitialized memory are handled.
+    pub uninit_memory_policy: UninitMemoryPolicy,
+
+    /// The number of uninitialized reads logged under `UninitMemoryPolicy::Warn`.
+    pub(crate) uninit_warnings: usize,
+
+    /// Whether to check invariants of the execution that are otherwise only checked in debug
+    /// builds, such as each operand of an instruction being written at most once.
+    pub full_validation: bool,
+
+    /// An error raised during the current instruction, returned once it finishes.
+    pub(crate) pending_error: Option<ExecutionError>,
+
+    /// Why the program stopped executing, set by the syscall that halted it.
+    pub halt_reason: HaltReason,
+
+    /// The exit code the guest halted with, see [`Runtime::exit_code`].
+    pub(crate) exit_code: Option<u32>,
+
+    /// A token that cancels execution once set, see [`Runtime::set_cancel_token`].
+    pub(crate) cancel_token: Option<Arc<AtomicBool>>,
+
+    /// The number of cycles between two checks of the cancel token.
+    pub cancel_check_interval: u32,
+
+    /// The conditions that stop execution once they hold, see [`Runtime::add_condition`].
+    pub(crate) conditions: Vec<Condition>,
+
+    /// Detects loops that make no progress, see [`Runtime::set_livelock_config`].
+    pub(crate) livelock_detector: Option<LivelockDetector>,
+
+    /// The schema of the input stream, see [`Runtime::set_input_schema`].
+    pub(crate) input_schema: Option<InputSchema>,
+
+    /// The tags of the frames written to the input stream under a schema.
+    pub(crate) input_frames: Vec<u32>,
+
+    /// The index of the next frame the guest reads.
+    pub(crate) input_frame_ptr: usize,
+
+    /// Every write to the input stream, see [`Runtime::input_queue_log`].
+    pub(crate) input_queue_log: Vec<InputQueueEntry>,
+
+    /// Estimates the rows of the prover tables, see [`Runtime::set_prover_cost_model`].
+    pub(crate) cost_estimator: Option<CostEstimator>,
+
+    /// The reader the input stream falls back to, see [`Runtime::set_stdin_reader`].
+    pub(crate) stdin_reader: Option<StdinReader>,
+
+    /// Samples the host time spent on each instruction, see [`Runtime::enable_host_timing`].
+    pub(crate) host_timer: Option<HostTimer>,
+
+    /// Counts the outcomes of the branches, see [`Runtime::enable_branch_stats`].
+    pub(crate) branch_collector: Option<BranchCollector>,
+
+    /// Whether to append every `ecall` to `syscall_trace`, with the buffers the syscall accessed.
+    pub trace_syscalls: bool,
+
+    /// The number of bytes of each buffer kept in the syscall trace.
+    pub syscall_trace_preview: usize,
+
+    /// The syscalls executed while `trace_syscalls` was set, including those of unconstrained
+    /// blocks.
+    pub syscall_trace: Vec<SyscallTraceEntry>,
+
+    /// The seed of the offset the program is relocated by, see [`Runtime::randomize_layout`].
+    pub(crate) layout_seed: Option<u64>,
+
+    /// The offset the program was relocated by, zero if it was not.
+    pub(crate) layout_offset: u32,
+
+    /// Counts the memory accesses to each region, see [`Runtime::enable_region_stats`].
+    pub(crate) region_counter: Option<RegionCounter>,
+
+    /// Tracks the stored values that are never loaded, see
+    /// [`Runtime::enable_dead_store_tracking`].
+    pub(crate) dead_stores: Option<DeadStoreTracker>,
+
+    /// Samples the memory accesses, see [`Runtime::enable_locality_sampling`].
+    pub(crate) locality_collector: Option<LocalityCollector>,
+
+    /// Samples the program counter and collects the cycle tracker spans, see
+    /// [`Runtime::enable_profiling`].
+    pub(crate) profiler: Option<Profiler>,
+
+    /// The shadow stack of the calls so far, see [`Runtime::enable_call_graph`].
+    pub(crate) call_graph: Option<CallGraphTracker>,
+
+    /// The deltas of the most recent cycles, see [`Runtime::enable_time_travel`].
+    pub(crate) time_travel: Option<TimeTravel>,
+
+    /// Whether to check every CPU event as it is emitted, see [`Runtime::set_event_validation`].
+    pub(crate) event_validation: bool,
+
+    /// Whether to check the final state once execution finishes, see
+    /// [`Runtime::set_final_invariant_checks`].
+    pub(crate) final_invariant_checks: bool,
+
+    /// Whether to hash the memory at every shard boundary, see
+    /// [`Runtime::set_memory_root_hints`].
+    pub(crate) memory_root_hints: bool,
+
+    /// The opcodes the guest may not execute, see [`Runtime::restrict_opcodes`].
+    pub(crate) forbidden_opcodes: HashSet<Opcode>,
+
+    /// The only syscalls the guest may invoke if set, see [`Runtime::restrict_syscalls`].
+    pub(crate) allowed_syscalls: Option<HashSet<SyscallCode>>,
+
+    /// The stack of the guest and its guard region, see [`Runtime::configure_stack`].
+    pub(crate) stack: Option<GuestStack>,
+
+    /// The memory the program starts from if it is shared with other runtimes, see
+    /// [`Runtime::new_from_frozen`]. Its words are loaded into `state.memory` on their first
+    /// access.
+    pub(crate) frozen: Option<Arc<FrozenState>>,
+
+    /// The addresses written so far if the final memory records are sparse, see
+    /// [`Runtime::set_sparse_memory_records`].
+    pub(crate) written_addrs: Option<HashSet<u32, BuildNoHashHasher<u32>>>,
+
+    /// The value of every word written before its first write, see
+    /// [`Runtime::set_write_set_tracking`].
+    pub(crate) write_set: Option<WriteSet>,
+
+    /// Receives the CPU and ALU events instead of the record, see [`Runtime::set_event_sink`].
+    pub(crate) event_sink: Option<Box<dyn EventSink>>,
+
+    /// Applied to every CPU event before it is validated, to inject violations in tests.
+    #[cfg(test)]
+    pub(crate) event_tamper: Option<fn(&mut CpuEvent)>,
+
+    /// The events with values reduced by the field, see [`Runtime::set_field_range_checks`].
+    pub(crate) field_range: Option<FieldRangeSummary>,
+
+    /// Whether reads of more input than was written stop before executing, see
+    /// [`Runtime::set_input_blocking`].
+    pub(crate) input_blocking: bool,
+
+    /// Whether the guest asked to start a new shard with `SHARD_HINT`.
+    pub(crate) shard_hint_pending: bool,
+
+    /// The number of cycles a shard must have before a shard hint ends it. Hints in shorter
+    /// shards are deferred until the shard reaches this size.
+    pub min_hinted_shard_cycles: u32,
+
+    pub syscall_map: SyscallMap,
 }
 
 impl Runtime {
-    // Create a new runtime
+    /// Create a runtime for `program`.
+    ///
+    /// Panics if the program contains malformed instructions, see [`Runtime::try_new`].
     pub fn new(program: Program) -> Self {
-        let program_arc = Arc::new(program);
+        Self::try_new(program).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Create a runtime for `program`, returning an error if it contains malformed instructions.
+    pub fn try_new(program: Program) -> Result<Self, Error> {
+        Self::try_new_with_options(program, RuntimeOptions::from_env())
+    }
+
+    /// Create a runtime for `program` configured by `options` instead of the environment.
+    ///
+    /// Panics if the program contains malformed instructions, see
+    /// [`Runtime::try_new_with_options`].
+    pub fn new_with_options(program: Program, options: RuntimeOptions) -> Self {
+        Self::try_new_with_options(program, options).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Create a runtime for `program` configured by `options` instead of the environment,
+    /// returning an error if the program contains malformed instructions or lacks a function of
+    /// [`RuntimeOptions::trace_filter`].
+    pub fn try_new_with_options(program: Program, options: RuntimeOptions) -> Result<Self, Error> {
+        program.validate()?;
+        let trace_filter = (!options.trace_filter.is_empty())
+            .then(|| TraceFilter::new(&program.symbols, &options.trace_filter))
+            .transpose()?;
+        let mut runtime = Self::with_program(Arc::new(program), options);
+        runtime.trace_filter = trace_filter;
+        Ok(runtime)
+    }
+
+    /// Create a runtime for `program`, which must have been validated.
+    pub(crate) fn with_program(program_arc: Arc<Program>, options: RuntimeOptions) -> Self {
         let record = ExecutionRecord {
             program: program_arc.clone(),
             ..Default::default()
         };
-        // Write pc trace to file if TRACE_FILE is set
-        let trace_buf = if let Ok(trace_file) = std::env::var("TRACE_FILE") {
-            let file = File::create(trace_file).unwrap();
-            Some(BufWriter::new(file))
-        } else {
-            None
-        };
+        let trace_buf = options.trace_file.as_deref().and_then(trace_sink_at);
 
         Self {
             record,
             state: ExecutionState::new(program_arc.pc_start),
             program: program_arc,
             cpu_record: CpuRecord::default(),
-            shard_size: env::shard_size() as u32 * 4,
+            shard_size: options.shard_size * 4,
             cycle_tracker: HashMap::new(),
             trace_buf,
+            emit_events: options.emit_events,
+            record_filter: options.record_filter,
+            trace_log: options
+                .log_instructions
+                .then(|| TraceLog::new(DEFAULT_TRACE_LOG_CAPACITY)),
+            trace_filter: None,
+            min_data_addr: program_arc.min_data_addr(),
+            syscall_footprint_checks: options.syscall_footprint_checks,
+            forbid_unconstrained: options.forbid_unconstrained,
+            max_syscall_arg_len: options.max_syscall_arg_len,
+            syscall_clock_journal: false,
+            scratch: options
+                .scratch_region
+                .map(|(addr, len)| ScratchRegion::new(addr, len)),
+            sharding: None,
             unconstrained: false,
             unconstrained_state: ForkState::default(),
-            syscall_map: default_syscall_map(),
+            uninit_memory_policy: options.uninit_memory_policy,
+            uninit_warnings: 0,
+            full_validation: false,
+            pending_error: None,
+            halt_reason: HaltReason::default(),
+            exit_code: None,
+            cancel_token: None,
+            cancel_check_interval: DEFAULT_CANCEL_CHECK_INTERVAL,
+            conditions: Vec::new(),
+            livelock_detector: None,
+            input_schema: None,
+            input_frames: Vec::new(),
+            input_frame_ptr: 0,
+            input_queue_log: Vec::new(),
+            cost_estimator: None,
+            stdin_reader: None,
+            host_timer: None,
+            branch_collector: None,
+            trace_syscalls: false,
+            syscall_trace_preview: DEFAULT_SYSCALL_TRACE_PREVIEW,
+            syscall_trace: Vec::new(),
+            layout_seed: None,
+            layout_offset: 0,
+            region_counter: None,
+            dead_stores: None,
+            locality_collector: None,
+            profiler: None,
+            call_graph: None,
+            time_travel: None,
+            event_validation: options.validate_events,
+            final_invariant_checks: options.check_final_invariants,
+            memory_root_hints: false,
+            forbidden_opcodes: HashSet::new(),
+            allowed_syscalls: None,
+            stack: None,
+            frozen: None,
+            written_addrs: None,
+            write_set: None,
+            event_sink: None,
+            #[cfg(test)]
+            event_tamper: None,
+            field_range: None,
+            input_blocking: false,
+            shard_hint_pending: false,
+            min_hinted_shard_cycles: DEFAULT_MIN_HINTED_SHARD_CYCLES,
+            syscall_map: SyscallMap::new(),
         }
     }
 
+    /// Prepare the runtime to execute another program as if it was created by `Runtime::new`,
+    /// but keeping the registered syscalls, the configuration and the allocated capacity of the
+    /// memory and the record. This makes executing many small programs back to back much cheaper.
+    ///
+    /// Panics if the program contains malformed instructions, see
+    /// [`Runtime::try_reset_with_program`].
+    pub fn reset_with_program(&mut self, program: Arc<Program>) {
+        self.try_reset_with_program(program)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like [`Runtime::reset_with_program`], returning an error and leaving the runtime untouched
+    /// if the program contains malformed instructions.
+    pub fn try_reset_with_program(&mut self, program: Arc<Program>) -> Result<(), Error> {
+        program.validate()?;
+        if let Some(filter) = &self.trace_filter {
+            self.trace_filter = Some(TraceFilter::new(&program.symbols, &filter.names)?);
+        }
+        // The frozen memory only applies to the program it was frozen with.
+        if let Some(frozen) = &self.frozen {
+            if !Arc::ptr_eq(frozen.program(), &program) {
+                self.frozen = None;
+            }
+        }
+        self.state.reset(program.pc_start);
+        self.record.reset(program.clone());
+        self.min_data_addr = program.min_data_addr();
+        self.program = program;
+        self.cpu_record = CpuRecord::default();
+        self.cycle_tracker.clear();
+        self.unconstrained = false;
+        self.unconstrained_state = ForkState::default();
+        self.uninit_warnings = 0;
+        self.pending_error = None;
+        if let Some(scratch) = &mut self.scratch {
+            scratch.reset();
+        }
+        if let Some(stack) = &mut self.stack {
+            stack.reset();
+        }
+        if let Some(sharding) = &mut self.sharding {
+            sharding.reset();
+        }
+        self.halt_reason = HaltReason::default();
+        self.exit_code = None;
+        self.input_frames.clear();
+        self.input_frame_ptr = 0;
+        self.input_queue_log.clear();
+        self.stdin_reader = None;
+        self.syscall_trace.clear();
+        self.layout_offset = 0;
+        self.shard_hint_pending = false;
+        if let Some(estimator) = &mut self.cost_estimator {
+            *estimator = CostEstimator::new(estimator.model.clone());
+        }
+        if self.host_timer.is_some() {
+            self.host_timer = Some(HostTimer::default());
+        }
+        if self.branch_collector.is_some() {
+            self.branch_collector = Some(BranchCollector::default());
+        }
+        if let Some(counter) = &mut self.region_counter {
+            counter.reset();
+        }
+        if self.dead_stores.is_some() {
+            self.dead_stores = Some(DeadStoreTracker::default());
+        }
+        if let Some(collector) = &mut self.locality_collector {
+            *collector = LocalityCollector::new(collector.config.clone());
+        }
+        if let Some(profiler) = &mut self.profiler {
+            *profiler = Profiler::new(profiler.interval);
+        }
+        if self.call_graph.is_some() {
+            self.call_graph = Some(CallGraphTracker::new(self.program.pc_start));
+        }
+        if let Some(time_travel) = &mut self.time_travel {
+            *time_travel = TimeTravel::new(time_travel.capacity);
+        }
+        if let Some(trace_log) = &mut self.trace_log {
+            *trace_log = TraceLog::new(trace_log.capacity);
+        }
+        if let Some(written) = &mut self.written_addrs {
+            written.clear();
+        }
+        if let Some(write_set) = &mut self.write_set {
+            write_set.clear();
+        }
+        if self.field_range.is_some() {
+            self.field_range = Some(FieldRangeSummary::default());
+        }
+        if let Some(detector) = &mut self.livelock_detector {
+            *detector = LivelockDetector::new(detector.config.clone());
+        }
+        Ok(())
+    }
+
     /// Get the current values of the registers.
     pub fn registers(&self) -> [u32; 32] {
-        let mut registers = [0; 32];
-        for i in 0..32 {
-            let addr = Register::from_u32(i as u32) as u32;
-            registers[i] = match self.state.memory.get(&addr) {
-                Some((value, _, _)) => *value,
-                None => 0,
-            };
-        }
-        registers
+        self.state.dump_registers().values
     }
 
     /// Get the current value of a register.
@@ -132,12 +650,28 @@ impl Runtime {
         }
     }
 
-    /// Get the current value of a word.
+    /// Get the current value of the register with index `index`, returning an error if it is not
+    /// below 32.
+    pub fn try_register(&self, index: u32) -> Result<u32, Error> {
+        let register = Register::try_from_u32(index).ok_or(Error::InvalidRegister { index })?;
+        Ok(self.register(register))
+    }
+
+    /// Get the current value of a word. Unaligned addresses read as zero, see
+    /// [`Runtime::try_word`].
     pub fn word(&self, addr: u32) -> u32 {
         match self.state.memory.get(&addr) {
             Some((value, _, _)) => *value,
-            None => 0,
+            None => self.frozen_word(addr).unwrap_or(0),
+        }
+    }
+
+    /// Get the current value of a word, returning an error if `addr` is not aligned.
+    pub fn try_word(&self, addr: u32) -> Result<u32, Error> {
+        if addr % 4 != 0 {
+            return Err(Error::UnalignedAddress { addr });
         }
+        Ok(self.word(addr))
     }
 
     pub fn byte(&self, addr: u32) -> u8 {
@@ -158,18 +692,75 @@ impl Runtime {
         addr - addr % 4
     }
 
+    /// The address `base + offset` of a load, store or syscall, wrapping around. Returns `None`
+    /// after trapping with `ExecutionError::AddressOutOfRange` if the word containing it is
+    /// reserved for registers or at or above [`MEMORY_ADDR_LIMIT`], and with
+    /// `ExecutionError::ReservedAddress` if it is below [`Program::min_data_addr`].
     #[inline]
-    fn validate_memory_access(&self, addr: u32, position: AccessPosition) {
+    pub(crate) fn effective_address(&mut self, base: u32, offset: u32) -> Option<u32> {
+        let effective = base.wrapping_add(offset);
+        let aligned = self.align(effective);
+        if is_memory_word(aligned) {
+            if aligned >= self.min_data_addr {
+                return Some(effective);
+            }
+            self.trap(ExecutionError::ReservedAddress {
+                addr: aligned,
+                min_data_addr: self.min_data_addr,
+                pc: self.state.pc,
+            });
+            return None;
+        }
+        self.trap(ExecutionError::AddressOutOfRange {
+            base,
+            offset,
+            effective,
+            pc: self.state.pc,
+        });
+        None
+    }
+
+    /// Returns whether the access can be made, after trapping with
+    /// `ExecutionError::AddressOutOfRange` if the address is not representable as a BabyBear
+    /// element. The loads and stores of the guest are checked by [`Runtime::effective_address`]
+    /// before, so this only catches direct accesses.
+    #[inline]
+    fn validate_memory_access(&mut self, addr: u32, position: AccessPosition) -> bool {
         if position == AccessPosition::Memory {
             assert_eq!(addr % 4, 0, "addr is not aligned");
-            let _ = BabyBear::from_canonical_u32(addr);
-            assert!(addr > 40); // Assert that the address is > the max register.
+            if check_addr(addr).is_err() {
+                self.trap(ExecutionError::AddressOutOfRange {
+                    base: addr,
+                    offset: 0,
+                    effective: addr,
+                    pc: self.state.pc,
+                });
+                return false;
+            }
         } else {
             let _ = Register::from_u32(addr);
         }
+        true
     }
 
     pub fn mr(&mut self, addr: u32, shard: u32, clk: u32) -> MemoryReadRecord {
+        self.check_stack_guard(addr);
+        if let Some(counter) = &mut self.region_counter {
+            if addr >= NUM_REGISTERS && !self.unconstrained {
+                counter.record(addr, false);
+            }
+        }
+        if let Some(tracker) = &mut self.dead_stores {
+            if addr >= NUM_REGISTERS && !self.unconstrained {
+                tracker.record_read(addr);
+            }
+        }
+        if let Some(collector) = &mut self.locality_collector {
+            if addr >= NUM_REGISTERS && !self.unconstrained {
+                collector.record(addr, self.state.global_clk);
+            }
+        }
+
         // Get the memory entry.
         let memory_entry = self.state.memory.entry(addr);
         if self.unconstrained {
@@ -185,7 +776,39 @@ impl Runtime {
                 .or_insert(prev_value.copied());
         }
         // If it's the first time accessing this address, initialize previous values as zero.
-        let entry_value = memory_entry.or_insert((0, 0, 0));
+        let entry_value = match memory_entry {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                // The words of a frozen memory are loaded on their first access.
+                let frozen = self.frozen.as_ref().and_then(|frozen| frozen.word(addr));
+                if let Some(value) = frozen {
+                    entry.insert((value, 0, 0))
+                } else {
+                    // Registers start out as zero, so only memory can be uninitialized.
+                    if addr >= NUM_REGISTERS {
+                        match self.uninit_memory_policy {
+                            UninitMemoryPolicy::Zero => {}
+                            UninitMemoryPolicy::Warn { max_warnings } => {
+                                if self.uninit_warnings < max_warnings {
+                                    self.uninit_warnings += 1;
+                                    tracing::warn!(
+                                        "read of uninitialized memory at addr=0x{:x}, pc=0x{:x}",
+                                        addr,
+                                        self.state.pc
+                                    );
+                                }
+                            }
+                            UninitMemoryPolicy::Trap => {
+                                let pc = self.state.pc;
+                                self.pending_error
+                                    .get_or_insert(ExecutionError::UninitializedRead { addr, pc });
+                            }
+                        }
+                    }
+                    entry.insert((0, 0, 0))
+                }
+            }
+        };
         // Get the last time this memory address was accessed, and then update with current clock.
         let (value, prev_shard, prev_timestamp) = *entry_value;
         (entry_value.1, entry_value.2) = (shard, clk);
@@ -194,6 +817,45 @@ impl Runtime {
     }
 
     pub fn mw(&mut self, addr: u32, value: u32, shard: u32, clk: u32) -> MemoryWriteRecord {
+        if self.program.is_readonly(addr) {
+            self.trap(ExecutionError::WriteToReadOnly {
+                addr,
+                pc: self.state.pc,
+            });
+            // Execution stops after this instruction, so the word is left as it is.
+            let (prev_shard, prev_timestamp) = match self.state.memory.get(&addr) {
+                Some(&(_, prev_shard, prev_timestamp)) => (prev_shard, prev_timestamp),
+                None => (0, 0),
+            };
+            let value = self.word(addr);
+            return MemoryWriteRecord::new(value, shard, clk, value, prev_shard, prev_timestamp);
+        }
+        self.check_stack_guard(addr);
+        if let Some(detector) = &mut self.livelock_detector {
+            detector.record_write(addr);
+        }
+        if let Some(counter) = &mut self.region_counter {
+            if addr >= NUM_REGISTERS && !self.unconstrained {
+                counter.record(addr, true);
+            }
+        }
+        if let Some(tracker) = &mut self.dead_stores {
+            if addr >= NUM_REGISTERS && !self.unconstrained {
+                tracker.record_write(addr, self.state.pc);
+            }
+        }
+        if let Some(collector) = &mut self.locality_collector {
+            if addr >= NUM_REGISTERS && !self.unconstrained {
+                collector.record(addr, self.state.global_clk);
+            }
+        }
+        if let Some(written) = &mut self.written_addrs {
+            written.insert(addr);
+        }
+        if let Some(time_travel) = &mut self.time_travel {
+            time_travel.record_write(addr, self.state.memory.get(&addr).copied(), value);
+        }
+
         // Get the memory entry.
         let memory_entry = self.state.memory.entry(addr);
         if self.unconstrained {
@@ -208,17 +870,36 @@ impl Runtime {
                 .entry(addr)
                 .or_insert(prev_value.copied());
         }
-        // If it's the first time accessing this address, initialize previous values as zero.
-        let entry_value = memory_entry.or_insert((0, 0, 0));
+        // If it's the first time accessing this address, initialize previous values as zero, or
+        // as the frozen value of the word.
+        let frozen = &self.frozen;
+        let entry_value = memory_entry.or_insert_with(|| {
+            (
+                frozen
+                    .as_ref()
+                    .and_then(|frozen| frozen.word(addr))
+                    .unwrap_or(0),
+                0,
+                0,
+            )
+        });
         // Get previous values and then update with new values.
         let (prev_value, prev_shard, prev_timestamp) = *entry_value;
         *entry_value = (value, shard, clk);
+        self.record_first_write(addr, prev_value);
         MemoryWriteRecord::new(value, shard, clk, prev_value, prev_shard, prev_timestamp)
     }
 
     /// Read from memory, assuming that all addresses are aligned.
     pub fn mr_cpu(&mut self, addr: u32, position: AccessPosition) -> u32 {
-        self.validate_memory_access(addr, position);
+        if !self.validate_memory_access(addr, position) {
+            return 0;
+        }
+        if position == AccessPosition::Memory {
+            if let Some(value) = self.load_scratch(addr) {
+                return value;
+            }
+        }
 
         let record = self.mr(
             addr,
@@ -227,19 +908,20 @@ impl Runtime {
         );
 
         if !self.unconstrained {
-            match position {
-                AccessPosition::A => self.cpu_record.a = Some(record.into()),
-                AccessPosition::B => self.cpu_record.b = Some(record.into()),
-                AccessPosition::C => self.cpu_record.c = Some(record.into()),
-                AccessPosition::Memory => self.cpu_record.memory = Some(record.into()),
-            }
+            self.cpu_record.insert(position, record.into());
         }
         record.value
     }
 
     /// Write to memory.
     pub fn mw_cpu(&mut self, addr: u32, value: u32, position: AccessPosition) {
-        self.validate_memory_access(addr, position);
+        if !self.validate_memory_access(addr, position) {
+            return;
+        }
+        // Stores to the scratch region are not recorded.
+        if position == AccessPosition::Memory && self.store_scratch(addr, value) {
+            return;
+        }
 
         let record = self.mw(
             addr,
@@ -248,25 +930,18 @@ impl Runtime {
             self.clk_from_position(&position),
         );
 
-        // Set the records.
+        // Set the records. An instruction writes each position at most once, which is only checked
+        // in debug builds or with `full_validation` since it is on the hot path.
         if !self.unconstrained {
-            match position {
-                AccessPosition::A => {
-                    assert!(self.cpu_record.a.is_none());
-                    self.cpu_record.a = Some(record.into());
-                }
-                AccessPosition::B => {
-                    assert!(self.cpu_record.b.is_none());
-                    self.cpu_record.b = Some(record.into());
-                }
-                AccessPosition::C => {
-                    assert!(self.cpu_record.c.is_none());
-                    self.cpu_record.c = Some(record.into());
-                }
-                AccessPosition::Memory => {
-                    assert!(self.cpu_record.memory.is_none());
-                    self.cpu_record.memory = Some(record.into());
+            if cfg!(debug_assertions) || self.full_validation {
+                if let Err(access) = self.cpu_record.set(position, record.into()) {
+                    self.trap(ExecutionError::DuplicateAccess {
+                        access,
+                        pc: self.state.pc,
+                    });
                 }
+            } else {
+                self.cpu_record.insert(position, record.into());
             }
         }
     }
@@ -283,6 +958,7 @@ impl Runtime {
             // P.18 of the RISC-V spec.
             return;
         }
+        self.observe_register_write(register, value);
         // The only time we are writing to a register is when it is register A.
         self.mw_cpu(register as u32, value, AccessPosition::A)
     }
@@ -301,59 +977,138 @@ impl Runtime {
         memory_store_value: Option<u32>,
         record: CpuRecord,
     ) {
+        if !self.emit_events || !self.record_filter.contains(RecordFilter::CPU) {
+            return;
+        }
         let cpu_event = CpuEvent {
             shard,
             clk,
             pc,
             instruction,
             a,
-            a_record: record.a,
+            a_record: record.a(),
             b,
-            b_record: record.b,
+            b_record: record.b(),
             c,
-            c_record: record.c,
+            c_record: record.c(),
             memory: memory_store_value,
-            memory_record: record.memory,
+            memory_record: record.memory(),
+        };
+        #[cfg(test)]
+        let cpu_event = {
+            let mut cpu_event = cpu_event;
+            if let Some(tamper) = self.event_tamper {
+                tamper(&mut cpu_event);
+            }
+            cpu_event
         };
-        self.record.cpu_events.push(cpu_event);
+        if self.event_validation {
+            self.validate_cpu_event(&cpu_event);
+        }
+        if self.field_range.is_some() {
+            self.check_cpu_event_range(&cpu_event);
+        }
+        let start = self.start_record_timer();
+        match &mut self.event_sink {
+            Some(sink) if !self.unconstrained => sink.on_cpu_event(&cpu_event),
+            _ => self.record.on_cpu_event(&cpu_event),
+        }
+        self.stop_record_timer(start);
     }
 
     /// Emit an ALU event.
     fn emit_alu(&mut self, clk: u32, opcode: Opcode, a: u32, b: u32, c: u32) {
-        let event = AluEvent {
-            clk,
-            opcode,
-            a,
-            b,
-            c,
-        };
-        match opcode {
-            Opcode::ADD => {
-                self.record.add_events.push(event);
-            }
-            Opcode::SUB => {
-                self.record.sub_events.push(event);
-            }
-            Opcode::XOR | Opcode::OR | Opcode::AND => {
-                self.record.bitwise_events.push(event);
-            }
-            Opcode::SLL => {
-                self.record.shift_left_events.push(event);
-            }
-            Opcode::SRL | Opcode::SRA => {
-                self.record.shift_right_events.push(event);
+        if !self.emit_events {
+            return;
+        }
+        let event = AluEvent::new(clk, opcode, a, b, c);
+        let class = match opcode {
+            Opcode::ADD if self.record_filter.contains(RecordFilter::ADD) => Some(AluClass::Add),
+            Opcode::SUB if self.record_filter.contains(RecordFilter::SUB) => Some(AluClass::Sub),
+            Opcode::XOR | Opcode::OR | Opcode::AND
+                if self.record_filter.contains(RecordFilter::BITWISE) =>
+            {
+                Some(AluClass::Bitwise)
             }
-            Opcode::SLT | Opcode::SLTU => {
-                self.record.lt_events.push(event);
+            Opcode::SLL if self.record_filter.contains(RecordFilter::SHIFT) => {
+                Some(AluClass::ShiftLeft)
             }
-            Opcode::MUL | Opcode::MULHU | Opcode::MULHSU | Opcode::MULH => {
-                self.record.mul_events.push(event);
+            Opcode::SRL | Opcode::SRA if self.record_filter.contains(RecordFilter::SHIFT) => {
+                Some(AluClass::ShiftRight)
             }
-            Opcode::DIVU | Opcode::REMU | Opcode::DIV | Opcode::REM => {
-                self.record.divrem_events.push(event);
+            Opcode::SLT | Opcode::SLTU if self.record_filter.contains(RecordFilter::LT) => {
+                Some(AluClass::Lt)
             }
-            _ => {}
+            _ => None,
+        };
+        let Some(class) = class else {
+            return;
+        };
+        if self.field_range.is_some() {
+            self.check_alu_event_range(&event);
         }
+        let start = self.start_record_timer();
+        match &mut self.event_sink {
+            Some(sink) if !self.unconstrained => sink.on_alu_event(class, &event),
+            _ => self.record.on_alu_event(class, &event),
+        }
+        self.stop_record_timer(start);
+    }
+
+    /// Emit the event of a MUL, MULH, MULHU or MULHSU instruction with the metadata its result was
+    /// computed from.
+    fn emit_mul(&mut self, opcode: Opcode, b: u32, c: u32, metadata: MulMetadata) {
+        if !self.emit_events || !self.record_filter.contains(RecordFilter::MUL) {
+            return;
+        }
+        let event = MulEvent::new(self.state.clk, opcode, b, c, metadata);
+        if self.field_range.is_some() {
+            self.check_alu_event_range(&event.event);
+        }
+        let start = self.start_record_timer();
+        match &mut self.event_sink {
+            Some(sink) if !self.unconstrained => sink.on_mul_event(&event),
+            _ => self.record.on_mul_event(&event),
+        }
+        self.stop_record_timer(start);
+    }
+
+    /// Emit the event of a DIV, DIVU, REM or REMU instruction with the metadata its result was
+    /// computed from.
+    fn emit_divrem(&mut self, opcode: Opcode, b: u32, c: u32, metadata: DivRemMetadata) {
+        if !self.emit_events || !self.record_filter.contains(RecordFilter::DIVREM) {
+            return;
+        }
+        let event = DivRemEvent::new(self.state.clk, opcode, b, c, metadata);
+        if self.field_range.is_some() {
+            self.check_alu_event_range(&event.event);
+        }
+        let start = self.start_record_timer();
+        match &mut self.event_sink {
+            Some(sink) if !self.unconstrained => sink.on_divrem_event(&event),
+            _ => self.record.on_divrem_event(&event),
+        }
+        self.stop_record_timer(start);
+    }
+
+    /// Emit an AUIPC event for the current instruction.
+    fn emit_auipc(&mut self, imm: u32, result: u32) {
+        if !self.emit_events || !self.record_filter.contains(RecordFilter::CPU) {
+            return;
+        }
+        let event = AuipcEvent {
+            shard: self.current_shard(),
+            clk: self.state.clk,
+            pc: self.state.pc,
+            imm,
+            result,
+        };
+        let start = self.start_record_timer();
+        match &mut self.event_sink {
+            Some(sink) if !self.unconstrained => sink.on_auipc_event(&event),
+            _ => self.record.on_auipc_event(&event),
+        }
+        self.stop_record_timer(start);
     }
 
     /// Fetch the destination register and input operand values for an ALU instruction.
@@ -369,7 +1124,8 @@ impl Runtime {
             let (rd, b, c) = (rd, self.rr(rs1, AccessPosition::B), imm);
             (rd, b, c)
         } else {
-            assert!(instruction.imm_b && instruction.imm_c);
+            // Operand flags are checked when the program is loaded, see `Program::validate`.
+            debug_assert!(instruction.imm_b && instruction.imm_c);
             let (rd, b, c) = (
                 Register::from_u32(instruction.op_a),
                 instruction.op_b,
@@ -386,26 +1142,54 @@ impl Runtime {
         self.emit_alu(self.state.clk, instruction.opcode, a, b, c);
     }
 
-    /// Fetch the input operand values for a load instruction.
+    /// Set the destination register of a bit manipulation instruction and emit its event.
     #[inline(always)]
-    fn load_rr(&mut self, instruction: Instruction) -> (Register, u32, u32, u32, u32) {
+    fn bitmanip_rw(&mut self, instruction: Instruction, rd: Register, a: u32, b: u32, c: u32) {
+        self.rw(rd, a);
+        if !self.emit_events || !self.record_filter.contains(RecordFilter::BITMANIP) {
+            return;
+        }
+        let event = BitmanipEvent::new(
+            self.current_shard(),
+            self.state.clk,
+            instruction.opcode,
+            a,
+            b,
+            c,
+        );
+        let start = self.start_record_timer();
+        match &mut self.event_sink {
+            Some(sink) if !self.unconstrained => sink.on_bitmanip_event(&event),
+            _ => self.record.on_bitmanip_event(&event),
+        }
+        self.stop_record_timer(start);
+    }
+
+    /// Fetch the input operand values for a load instruction, or `None` if the address is out of
+    /// range.
+    #[inline(always)]
+    fn load_rr(&mut self, instruction: Instruction) -> Option<(Register, u32, u32, u32, u32)> {
         let (rd, rs1, imm) = instruction.i_type();
         let (b, c) = (self.rr(rs1, AccessPosition::B), imm);
-        let addr = b.wrapping_add(c);
+        let addr = self.effective_address(b, c)?;
         let memory_value = self.mr_cpu(self.align(addr), AccessPosition::Memory);
-        (rd, b, c, addr, memory_value)
+        Some((rd, b, c, addr, memory_value))
     }
 
-    /// Fetch the input operand values for a store instruction.
+    /// Fetch the input operand values for a store instruction, or `None` if the address is out of
+    /// range.
     #[inline(always)]
-    fn store_rr(&mut self, instruction: Instruction) -> (u32, u32, u32, u32, u32) {
+    fn store_rr(&mut self, instruction: Instruction) -> Option<(u32, u32, u32, u32, u32)> {
         let (rs1, rs2, imm) = instruction.s_type();
         let c = imm;
         let b = self.rr(rs2, AccessPosition::B);
         let a = self.rr(rs1, AccessPosition::A);
-        let addr = b.wrapping_add(c);
-        let memory_value = self.word(self.align(addr));
-        (a, b, c, addr, memory_value)
+        let addr = self.effective_address(b, c)?;
+        let aligned = self.align(addr);
+        let memory_value = self
+            .scratch_word(aligned)
+            .unwrap_or_else(|| self.word(aligned));
+        Some((a, b, c, addr, memory_value))
     }
 
     /// Fetch the input operand values for a branch instruction.
@@ -425,28 +1209,48 @@ impl Runtime {
         self.program.instructions[idx]
     }
 
-    fn get_syscall(&mut self, code: SyscallCode) -> Option<&Rc<dyn Syscall>> {
+    fn get_syscall(&mut self, code: SyscallCode) -> Option<&Arc<dyn Syscall + Send + Sync>> {
         self.syscall_map.get(&code)
     }
 
-    fn max_syscall_cycles(&self) -> u32 {
-        self.syscall_map
-            .values()
-            .map(|syscall| syscall.num_extra_cycles())
-            .max()
-            .unwrap_or(0)
+    /// The bound on the extra cycles of the next instruction: that of its syscall if it is an
+    /// `ecall`, or zero.
+    fn upcoming_syscall_cycles(&self) -> u32 {
+        let idx = self.state.pc.wrapping_sub(self.program.pc_base) / 4;
+        match self.program.instructions.get(idx as usize) {
+            Some(instruction) if instruction.opcode == Opcode::ECALL => {
+                self.syscall_cycles_bound(self.register(Register::X5))
+            }
+            _ => 0,
+        }
+    }
+
+    /// The extra cycles reserved for the syscall with code `code`, or zero if there is none.
+    pub(crate) fn syscall_cycles_bound(&self, code: u32) -> u32 {
+        SyscallCode::try_from_u32(code)
+            .and_then(|code| self.syscall_map.get(&code))
+            .map_or(0, |syscall| syscall.num_extra_cycles())
     }
 
     /// Execute the given instruction over the current state of the runtime.
     fn execute(&mut self, instruction: Instruction) {
-        let pc = self.state.pc;
+        let mut pc = self.state.pc;
         let mut next_pc = self.state.pc.wrapping_add(4);
 
         let rd: Register;
         let (a, b, c): (u32, u32, u32);
         let (addr, memory_read_value): (u32, u32);
         let mut memory_store_value: Option<u32> = None;
-        self.cpu_record = CpuRecord::default();
+        let mut syscall: Option<SyscallCode> = None;
+        self.cpu_record.clear();
+
+        if !self.opcode_allowed(instruction.opcode) {
+            self.trap(ExecutionError::OpcodeForbidden {
+                opcode: instruction.opcode,
+                pc,
+            });
+            return;
+        }
 
         match instruction.opcode {
             // Arithmetic instructions.
@@ -477,17 +1281,17 @@ impl Runtime {
             }
             Opcode::SLL => {
                 (rd, b, c) = self.alu_rr(instruction);
-                a = b.wrapping_shl(c);
+                a = b << shift_amount(c);
                 self.alu_rw(instruction, rd, a, b, c);
             }
             Opcode::SRL => {
                 (rd, b, c) = self.alu_rr(instruction);
-                a = b.wrapping_shr(c);
+                a = b >> shift_amount(c);
                 self.alu_rw(instruction, rd, a, b, c);
             }
             Opcode::SRA => {
                 (rd, b, c) = self.alu_rr(instruction);
-                a = (b as i32).wrapping_shr(c) as u32;
+                a = ((b as i32) >> shift_amount(c)) as u32;
                 self.alu_rw(instruction, rd, a, b, c);
             }
             Opcode::SLT => {
@@ -503,14 +1307,20 @@ impl Runtime {
 
             // Load instructions.
             Opcode::LB => {
-                (rd, b, c, addr, memory_read_value) = self.load_rr(instruction);
+                let Some(operands) = self.load_rr(instruction) else {
+                    return;
+                };
+                (rd, b, c, addr, memory_read_value) = operands;
                 let value = (memory_read_value).to_le_bytes()[(addr % 4) as usize];
                 a = ((value as i8) as i32) as u32;
                 memory_store_value = Some(memory_read_value);
                 self.rw(rd, a);
             }
             Opcode::LH => {
-                (rd, b, c, addr, memory_read_value) = self.load_rr(instruction);
+                let Some(operands) = self.load_rr(instruction) else {
+                    return;
+                };
+                (rd, b, c, addr, memory_read_value) = operands;
                 assert_eq!(addr % 2, 0, "addr is not aligned");
                 let value = match (addr >> 1) % 2 {
                     0 => memory_read_value & 0x0000FFFF,
@@ -522,21 +1332,30 @@ impl Runtime {
                 self.rw(rd, a);
             }
             Opcode::LW => {
-                (rd, b, c, addr, memory_read_value) = self.load_rr(instruction);
+                let Some(operands) = self.load_rr(instruction) else {
+                    return;
+                };
+                (rd, b, c, addr, memory_read_value) = operands;
                 assert_eq!(addr % 4, 0, "addr is not aligned");
                 a = memory_read_value;
                 memory_store_value = Some(memory_read_value);
                 self.rw(rd, a);
             }
             Opcode::LBU => {
-                (rd, b, c, addr, memory_read_value) = self.load_rr(instruction);
+                let Some(operands) = self.load_rr(instruction) else {
+                    return;
+                };
+                (rd, b, c, addr, memory_read_value) = operands;
                 let value = (memory_read_value).to_le_bytes()[(addr % 4) as usize];
                 a = value as u32;
                 memory_store_value = Some(memory_read_value);
                 self.rw(rd, a);
             }
             Opcode::LHU => {
-                (rd, b, c, addr, memory_read_value) = self.load_rr(instruction);
+                let Some(operands) = self.load_rr(instruction) else {
+                    return;
+                };
+                (rd, b, c, addr, memory_read_value) = operands;
                 assert_eq!(addr % 2, 0, "addr is not aligned");
                 let value = match (addr >> 1) % 2 {
                     0 => memory_read_value & 0x0000FFFF,
@@ -550,7 +1369,10 @@ impl Runtime {
 
             // Store instructions.
             Opcode::SB => {
-                (a, b, c, addr, memory_read_value) = self.store_rr(instruction);
+                let Some(operands) = self.store_rr(instruction) else {
+                    return;
+                };
+                (a, b, c, addr, memory_read_value) = operands;
                 let value = match addr % 4 {
                     0 => (a & 0x000000FF) + (memory_read_value & 0xFFFFFF00),
                     1 => ((a & 0x000000FF) << 8) + (memory_read_value & 0xFFFF00FF),
@@ -562,7 +1384,10 @@ impl Runtime {
                 self.mw_cpu(self.align(addr), value, AccessPosition::Memory);
             }
             Opcode::SH => {
-                (a, b, c, addr, memory_read_value) = self.store_rr(instruction);
+                let Some(operands) = self.store_rr(instruction) else {
+                    return;
+                };
+                (a, b, c, addr, memory_read_value) = operands;
                 assert_eq!(addr % 2, 0, "addr is not aligned");
                 let value = match (addr >> 1) % 2 {
                     0 => (a & 0x0000FFFF) + (memory_read_value & 0xFFFF0000),
@@ -573,7 +1398,10 @@ impl Runtime {
                 self.mw_cpu(self.align(addr), value, AccessPosition::Memory);
             }
             Opcode::SW => {
-                (a, b, c, addr, _) = self.store_rr(instruction);
+                let Some(operands) = self.store_rr(instruction) else {
+                    return;
+                };
+                (a, b, c, addr, _) = operands;
                 assert_eq!(addr % 4, 0, "addr is not aligned");
                 let value = a;
                 memory_store_value = Some(value);
@@ -625,6 +1453,9 @@ impl Runtime {
                 a = self.state.pc + 4;
                 self.rw(rd, a);
                 next_pc = self.state.pc.wrapping_add(imm);
+                if self.call_graph.is_some() && !self.unconstrained {
+                    self.observe_jump(Opcode::JAL, rd, next_pc);
+                }
             }
             Opcode::JALR => {
                 let (rd, rs1, imm) = instruction.i_type();
@@ -632,114 +1463,231 @@ impl Runtime {
                 a = self.state.pc + 4;
                 self.rw(rd, a);
                 next_pc = b.wrapping_add(c);
+                if self.call_graph.is_some() && !self.unconstrained {
+                    self.observe_jump(Opcode::JALR, rd, next_pc);
+                }
             }
 
             // Upper immediate instructions.
             Opcode::AUIPC => {
                 let (rd, imm) = instruction.u_type();
+                // Both operands are the immediate, see `CpuEvent::u_imm`.
                 (b, c) = (imm, imm);
-                a = self.state.pc.wrapping_add(b);
+                a = self.state.pc.wrapping_add(imm);
                 self.rw(rd, a);
+                self.emit_auipc(imm, a);
             }
 
             // System instructions.
             Opcode::ECALL => {
                 let t0 = Register::X5;
                 let a0 = Register::X10;
-                let syscall_id = self.register(t0);
-                let syscall = SyscallCode::from_u32(syscall_id);
-
-                let init_clk = self.state.clk;
-                let syscall_impl = self.get_syscall(syscall).cloned();
-                let mut precompile_rt = SyscallContext::new(self);
-
-                if let Some(syscall_impl) = syscall_impl {
-                    a = syscall_impl.execute(&mut precompile_rt);
-                    next_pc = precompile_rt.next_pc;
-                    self.state.clk = precompile_rt.clk;
-                    assert_eq!(init_clk + syscall_impl.num_extra_cycles(), self.state.clk);
+
+                // Snapshot the arguments before the syscall runs, so that syscalls which modify
+                // these registers themselves still see the values passed to the `ecall`.
+                let args = SyscallArgs {
+                    code: self.register(t0),
+                    a0: self.register(a0),
+                    a1: self.register(Register::X11),
+                };
+                let syscall_impl = SyscallCode::try_from_u32(args.code)
+                    .and_then(|syscall| self.get_syscall(syscall).cloned());
+                let Some(syscall_impl) = syscall_impl else {
+                    self.trap(ExecutionError::UnsupportedSyscall {
+                        code: args.code,
+                        pc,
+                    });
+                    return;
+                };
+                if !SyscallCode::try_from_u32(args.code)
+                    .is_some_and(|code| self.syscall_allowed(code))
+                {
+                    self.trap(ExecutionError::SyscallForbidden {
+                        code: args.code,
+                        pc,
+                    });
+                    return;
+                }
+
+                if let Some(detector) = &mut self.livelock_detector {
+                    detector.record_syscall();
+                }
+
+                let mut init_clk = self.state.clk;
+                let was_unconstrained = self.unconstrained;
+                let code = SyscallCode::from_u32(args.code);
+                syscall = Some(code);
+                let checked =
+                    self.syscall_footprint_checks && self.syscall_map.is_registered(&code);
+                let mut precompile_rt = SyscallContext::new(self, args);
+                if checked {
+                    precompile_rt.restrict_to(syscall_impl.memory_footprint(args));
+                }
+                a = syscall_impl.execute(&mut precompile_rt);
+                next_pc = precompile_rt.next_pc;
+                let traced = precompile_rt.traced.take();
+                let violation = precompile_rt.footprint_violation.get();
+                // The syscall advances the clock by the cycles it actually took, which may be
+                // fewer than the bound reserved for it.
+                let bound = syscall_impl.num_extra_cycles();
+                let syscall_clk = match precompile_rt.end(bound) {
+                    Ok(clk) => clk,
+                    Err((fault, journal)) => {
+                        let clk = precompile_rt.clk();
+                        self.trap(ExecutionError::SyscallClockMismatch {
+                            code,
+                            pc,
+                            fault,
+                            journal,
+                        });
+                        clk
+                    }
+                };
+                if let Some(addr) = violation {
+                    self.trap(ExecutionError::SyscallFootprintViolation { code, addr, pc });
+                }
+                if was_unconstrained && !self.unconstrained {
+                    // Exiting an unconstrained block rolled the state back to the `ecall` that
+                    // entered it, whose events were discarded with the block, so this `ecall` is
+                    // recorded in its place: at its pc and clock, returning the result of the exit.
+                    pc = self.state.pc;
+                    init_clk = self.state.clk;
                 } else {
-                    panic!("Unsupported syscall: {:?}", syscall);
+                    self.state.clk = syscall_clk;
+                }
+
+                if let Some(profiler) = &mut self.profiler {
+                    if !self.unconstrained {
+                        profiler.record_syscall(pc, self.state.clk - init_clk);
+                    }
+                }
+
+                // Halting ends execution right after this `ecall`.
+                if args.code == SyscallCode::HALT as u32 {
+                    self.exit_code = Some(args.a0);
+                } else if args.code == SyscallCode::PANIC as u32
+                    || args.code == SyscallCode::ASSERT_FAILED as u32
+                {
+                    self.exit_code = Some(PANIC_EXIT_CODE);
                 }
 
-                // We have to do this AFTER the precompile execution because the CPU event
-                // gets emitted at the end of this loop with the incremented clock.
-                // TODO: fix this.
+                if let Some(buffers) = traced {
+                    let entry = SyscallTraceEntry {
+                        code: SyscallCode::from_u32(args.code),
+                        args,
+                        buffers,
+                        result: a,
+                        extra_cycles: self.state.clk - init_clk,
+                        shard: self.current_shard(),
+                        clk: init_clk,
+                        pc,
+                        unconstrained: self.unconstrained,
+                    };
+                    log::debug!("{}", entry);
+                    self.syscall_trace.push(entry);
+                }
+
+                // The CPU event is emitted with the clock after the syscall, so the accesses of
+                // the `ecall` itself happen at that clock, in the order C, B, A: `c` is the
+                // immediate 0, t0 is read into `b` and the result is written to a0.
+                c = 0;
+                b = self.rr(t0, AccessPosition::B);
                 self.rw(a0, a);
-                (b, c) = (self.rr(t0, AccessPosition::B), 0);
             }
 
             Opcode::EBREAK => {
-                todo!()
+                self.trap(ExecutionError::Breakpoint { pc });
+                return;
             }
 
             // Multiply instructions.
-            Opcode::MUL => {
+            Opcode::MUL | Opcode::MULH | Opcode::MULHU | Opcode::MULHSU => {
                 (rd, b, c) = self.alu_rr(instruction);
-                a = b.wrapping_mul(c);
-                self.alu_rw(instruction, rd, a, b, c);
+                let metadata = MulMetadata::new(instruction.opcode, b, c);
+                a = metadata.result(instruction.opcode);
+                self.rw(rd, a);
+                self.emit_mul(instruction.opcode, b, c, metadata);
             }
-            Opcode::MULH => {
+            Opcode::DIV | Opcode::DIVU | Opcode::REM | Opcode::REMU => {
                 (rd, b, c) = self.alu_rr(instruction);
-                a = (((b as i32) as i64).wrapping_mul((c as i32) as i64) >> 32) as u32;
-                self.alu_rw(instruction, rd, a, b, c);
+                let metadata = DivRemMetadata::new(instruction.opcode, b, c);
+                a = metadata.result(instruction.opcode);
+                self.rw(rd, a);
+                self.emit_divrem(instruction.opcode, b, c, metadata);
             }
-            Opcode::MULHU => {
+
+            // Bit manipulation instructions.
+            Opcode::ANDN => {
                 (rd, b, c) = self.alu_rr(instruction);
-                a = ((b as u64).wrapping_mul(c as u64) >> 32) as u32;
-                self.alu_rw(instruction, rd, a, b, c);
+                a = b & !c;
+                self.bitmanip_rw(instruction, rd, a, b, c);
             }
-            Opcode::MULHSU => {
+            Opcode::ORN => {
                 (rd, b, c) = self.alu_rr(instruction);
-                a = (((b as i32) as i64).wrapping_mul(c as i64) >> 32) as u32;
-                self.alu_rw(instruction, rd, a, b, c);
+                a = b | !c;
+                self.bitmanip_rw(instruction, rd, a, b, c);
             }
-            Opcode::DIV => {
+            Opcode::XNOR => {
                 (rd, b, c) = self.alu_rr(instruction);
-                if c == 0 {
-                    a = u32::MAX;
-                } else {
-                    a = (b as i32).wrapping_div(c as i32) as u32;
-                }
-                self.alu_rw(instruction, rd, a, b, c);
+                a = !(b ^ c);
+                self.bitmanip_rw(instruction, rd, a, b, c);
             }
-            Opcode::DIVU => {
+            Opcode::CLZ => {
                 (rd, b, c) = self.alu_rr(instruction);
-                if c == 0 {
-                    a = u32::MAX;
-                } else {
-                    a = b.wrapping_div(c);
-                }
-                self.alu_rw(instruction, rd, a, b, c);
+                a = b.leading_zeros();
+                self.bitmanip_rw(instruction, rd, a, b, c);
             }
-            Opcode::REM => {
+            Opcode::CTZ => {
                 (rd, b, c) = self.alu_rr(instruction);
-                if c == 0 {
-                    a = b;
-                } else {
-                    a = (b as i32).wrapping_rem(c as i32) as u32;
-                }
-                self.alu_rw(instruction, rd, a, b, c);
+                a = b.trailing_zeros();
+                self.bitmanip_rw(instruction, rd, a, b, c);
             }
-            Opcode::REMU => {
+            Opcode::CPOP => {
                 (rd, b, c) = self.alu_rr(instruction);
-                if c == 0 {
-                    a = b;
-                } else {
-                    a = b.wrapping_rem(c);
-                }
-                self.alu_rw(instruction, rd, a, b, c);
+                a = b.count_ones();
+                self.bitmanip_rw(instruction, rd, a, b, c);
+            }
+            Opcode::MIN => {
+                (rd, b, c) = self.alu_rr(instruction);
+                a = (b as i32).min(c as i32) as u32;
+                self.bitmanip_rw(instruction, rd, a, b, c);
+            }
+            Opcode::MAX => {
+                (rd, b, c) = self.alu_rr(instruction);
+                a = (b as i32).max(c as i32) as u32;
+                self.bitmanip_rw(instruction, rd, a, b, c);
+            }
+            Opcode::MINU => {
+                (rd, b, c) = self.alu_rr(instruction);
+                a = b.min(c);
+                self.bitmanip_rw(instruction, rd, a, b, c);
+            }
+            Opcode::MAXU => {
+                (rd, b, c) = self.alu_rr(instruction);
+                a = b.max(c);
+                self.bitmanip_rw(instruction, rd, a, b, c);
             }
 
             Opcode::UNIMP => {
                 // See https://github.com/riscv-non-isa/riscv-asm-manual/blob/master/riscv-asm.md#instruction-aliases
-                panic!("UNIMP encountered, we should never get here.");
+                self.trap(ExecutionError::Unimplemented { pc });
+                return;
             }
         }
 
         // Update the program counter.
         self.state.pc = next_pc;
 
+        if self.cost_estimator.is_some() && !self.unconstrained {
+            self.estimate_cost(instruction, pc, next_pc, a, syscall);
+        }
+        if self.branch_collector.is_some()
+            && !self.unconstrained
+            && instruction.is_branch_instruction()
+        {
+            self.record_branch(pc, c, next_pc);
+        }
+
         // Emit the CPU event for this cycle.
         self.emit_cpu(
             self.current_shard(),
@@ -754,74 +1702,249 @@ impl Runtime {
         );
     }
 
-    /// Execute the program.
-    pub fn run(&mut self) {
-        tracing::info_span!("load memory").in_scope(|| {
-            // First load the memory image into the memory table.
-            for (addr, value) in self.program.memory_image.iter() {
-                self.state.memory.insert(*addr, (*value, 0, 0));
+    /// Execute the program, panicking if the guest faults, and return why it stopped.
+    pub fn run(&mut self) -> HaltReason {
+        if let Err(err) = self.try_run() {
+            let mut message = match self.program.metadata() {
+                Some(metadata) => format!("{} (guest {})", err, metadata),
+                None => err.to_string(),
+            };
+            if self.trace_log.is_some() {
+                let mut log = Vec::new();
+                self.flush_trace_log(&mut log).unwrap();
+                message.push_str("\nlast cycles:\n");
+                message.push_str(&String::from_utf8_lossy(&log));
             }
-        });
+            panic!("{}", message);
+        }
+        self.halt_reason.clone()
+    }
+
+    /// Execute the program, returning an error if the guest faults.
+    pub fn try_run(&mut self) -> Result<(), ExecutionError> {
+        self.initialize();
+        self.run_steps(u64::MAX)?;
+        if self.final_invariant_checks {
+            self.assert_final_invariants();
+        }
+        self.finalize();
+        Ok(())
+    }
+
+    /// Execute at most `steps` instructions of an initialized runtime, returning whether the
+    /// program finished.
+    pub(crate) fn run_steps(&mut self, steps: u64) -> Result<bool, ExecutionError> {
+        for _ in 0..steps {
+            if self.is_done() {
+                break;
+            }
+            self.check_cancelled()?;
+            self.check_livelock()?;
+            self.step()?;
+        }
+        Ok(self.is_done())
+    }
+
+    /// Stop execution with the given error once the current instruction finishes. Only the first
+    /// error raised during an instruction is kept.
+    pub(crate) fn trap(&mut self, error: ExecutionError) {
+        self.pending_error.get_or_insert(error);
+    }
 
-        let max_syscall_cycles = self.max_syscall_cycles();
+    /// Fork the state of the runtime: until [`Runtime::exit_unconstrained`], the original value of
+    /// every address that is written is saved and events go to a fresh record.
+    pub(crate) fn enter_unconstrained(&mut self) {
+        self.unconstrained = true;
+        self.unconstrained_state = ForkState {
+            global_clk: self.state.global_clk,
+            clk: self.state.clk,
+            pc: self.state.pc,
+            memory_diff: Default::default(),
+            scratch_diff: Default::default(),
+            record: std::mem::take(&mut self.record),
+            op_record: std::mem::take(&mut self.cpu_record),
+            output_channel_lens: self.state.output_channel_lens(),
+            public_values_digester: self.state.public_values_digester.clone(),
+            staged_inputs: Vec::new(),
+            stdin_reader_mark: self.stdin_reader.as_mut().map(StdinReader::mark),
+        };
+    }
+
+    /// Roll the clocks, the program counter, memory, the scratch region, the record, the output
+    /// channels and the digest of the public values back to the fork taken by [`Runtime::enter_unconstrained`].
+    /// Only the writes to the input stream staged in the meantime are kept, appended in the order
+    /// they were made.
+    ///
+    /// The registers written by the `ecall` entering the block, its return value of 1 in `a0`
+    /// included, are rolled back with the rest. The `ecall` exiting the block reads `t0` and
+    /// writes its return value of 0 to `a0` after the rollback, so those accesses persist: it is
+    /// recorded as the `ecall` entering the block, which the guest observes returning 0.
+    ///
+    /// The stdin reader is rewound, so the bytes the block read from it are read again.
+    pub(crate) fn exit_unconstrained(&mut self) {
+        let fork = std::mem::take(&mut self.unconstrained_state);
+        self.state.global_clk = fork.global_clk;
+        self.state.clk = fork.clk;
+        self.state.pc = fork.pc;
+        for (addr, value) in fork.memory_diff {
+            match value {
+                Some(value) => {
+                    self.state.memory.insert(addr, value);
+                }
+                None => {
+                    self.state.memory.remove(&addr);
+                }
+            }
+        }
+        self.restore_scratch(fork.scratch_diff);
+        self.state
+            .truncate_output_channels(&fork.output_channel_lens);
+        self.state.public_values_digester = fork.public_values_digester;
+        self.record = fork.record;
+        self.cpu_record = fork.op_record;
+        self.unconstrained = false;
+        if let (Some(reader), Some(pos)) = (&mut self.stdin_reader, fork.stdin_reader_mark) {
+            reader.rewind(pos);
+        }
+        for bytes in fork.staged_inputs {
+            self.append_input(InputOrigin::GuestUnconstrained, &bytes);
+        }
+        self.restart_cycle_delta();
+    }
+
+    /// Load the program's memory image and prepare to execute the first instruction.
+    ///
+    /// Only needed when driving the runtime with [`Runtime::step`] instead of [`Runtime::run`].
+    pub fn initialize(&mut self) {
+        self.apply_layout();
+        self.update_region_globals();
+
+        self.initialize_stack();
+        // The words of a frozen memory are loaded on their first access instead.
+        if self.frozen.is_none() {
+            tracing::info_span!("load memory").in_scope(|| {
+                // First load the memory image into the memory table.
+                for (addr, value) in self.program.memory_image.iter() {
+                    self.state.memory.insert(*addr, (*value, 0, 0));
+                }
+            });
+        }
+
+        self.record.filter = self.record_filter;
 
         self.state.clk += 1;
-        while self.state.pc.wrapping_sub(self.program.pc_base)
+    }
+
+    /// Whether execution has finished: the guest halted or fell through the last instruction.
+    pub fn is_done(&self) -> bool {
+        self.exit_code.is_some() || self.state.pc == self.program.text_end()
+    }
+
+    /// The exit code passed to the HALT syscall, or [`PANIC_EXIT_CODE`] if the guest panicked or
+    /// an assertion failed.
+    /// `None` until the guest halts, and for programs that end by falling through their last
+    /// instruction.
+    pub fn exit_code(&self) -> Option<u32> {
+        self.exit_code
+    }
+
+    /// Whether the program counter points to an instruction of the program.
+    pub(crate) fn pc_in_program(&self) -> bool {
+        self.state.pc.wrapping_sub(self.program.pc_base)
             < (self.program.instructions.len() * 4) as u32
-        {
-            // Fetch the instruction at the current program counter.
-            let instruction = self.fetch();
+    }
+
+    /// Execute the instruction at the current program counter.
+    pub fn step(&mut self) -> Result<(), ExecutionError> {
+        if !self.pc_in_program() {
+            return Err(ExecutionError::PcOutOfBounds { pc: self.state.pc });
+        }
+        if self.input_blocking {
+            self.check_input_available()?;
+        }
+
+        self.begin_cycle_delta()?;
+
+        // Fetch the instruction at the current program counter.
+        let pc = self.state.pc;
+        let instruction = self.fetch();
+        if !self.unconstrained {
+            self.enter_shard(pc);
+            if let Some(profiler) = &mut self.profiler {
+                if profiler.is_sample(self.state.global_clk) {
+                    profiler.record_sample(pc, self.cycle_tracker.keys().map(String::as_str));
+                }
+            }
+        }
 
+        // Only the cycles inside the functions of the trace filter, if any, are traced.
+        let traced = match &mut self.trace_filter {
+            Some(filter) => filter.contains(pc),
+            None => true,
+        };
+        if traced {
             if let Some(ref mut buf) = self.trace_buf {
                 if !self.unconstrained {
-                    buf.write_all(&u32::to_be_bytes(self.state.pc)).unwrap();
+                    buf.write_pc(self.state.pc);
                 }
             }
 
-            let width = 12;
-            log::trace!(
-                "clk={} [pc=0x{:x?}] {:<width$?} |         x0={:<width$} x1={:<width$} x2={:<width$} x3={:<width$} x4={:<width$} x5={:<width$} x6={:<width$} x7={:<width$} x8={:<width$} x9={:<width$} x10={:<width$} x11={:<width$} x12={:<width$} x13={:<width$} x14={:<width$} x15={:<width$} x16={:<width$} x17={:<width$} x18={:<width$}",
-                self.state.global_clk,
-                self.state.pc,
-                instruction,
-                self.register(Register::X0),
-                self.register(Register::X1),
-                self.register(Register::X2),
-                self.register(Register::X3),
-                self.register(Register::X4),
-                self.register(Register::X5),
-                self.register(Register::X6),
-                self.register(Register::X7),
-                self.register(Register::X8),
-                self.register(Register::X9),
-                self.register(Register::X10),
-                self.register(Register::X11),
-                self.register(Register::X12),
-                self.register(Register::X13),
-                self.register(Register::X14),
-                self.register(Register::X15),
-                self.register(Register::X16),
-                self.register(Register::X17),
-                self.register(Register::X18),
-            );
+            if self.trace_log.is_some() {
+                self.log_cycle(instruction);
+            }
+        }
 
-            // Execute the instruction.
+        // Execute the instruction.
+        let was_unconstrained = self.unconstrained;
+        if self.host_timer.is_some() {
+            self.execute_timed(instruction);
+        } else {
             self.execute(instruction);
+        }
+        self.end_cycle_delta();
+        if let Some(err) = self.pending_error.take() {
+            return Err(err);
+        }
+        // The `ecall` exiting an unconstrained block is executed in place of the one entering it,
+        // which it resumes after.
+        let pc = if was_unconstrained && !self.unconstrained {
+            self.state.pc.wrapping_sub(4)
+        } else {
+            pc
+        };
+        if !self.unconstrained {
+            self.exit_shard(pc);
+        }
 
-            // Increment the clock.
-            self.state.global_clk += 1;
-            self.state.clk += 4;
+        // Increment the clock.
+        self.state.global_clk += 1;
+        self.state.clk += 4;
+
+        // Once the program halts there is no next instruction to make room for, so the last shard
+        // ends with this one.
+        // If there's not enough cycles left for the next instruction, or the guest asked for a new
+        // shard and this one is large enough, move to the next shard.
+        if !self.is_done() && !self.unconstrained && self.shard_ends() {
+            self.complete_shard();
+            self.state.current_shard += 1;
+            self.state.clk = 0;
+            self.shard_hint_pending = false;
+            self.start_shard_layout();
+        }
 
-            // If there's not enough cycles left for another instruction, move to the next shard.
-            // We multiply by 4 because clk is incremented by 4 for each normal instruction.
-            if !self.unconstrained && max_syscall_cycles + self.state.clk >= self.shard_size * 4 {
-                self.state.current_shard += 1;
-                self.state.clk = 0;
-            }
+        if !self.conditions.is_empty() {
+            self.check_conditions(self.state.global_clk - 1, pc)?;
         }
+        Ok(())
+    }
+
+    /// Flush the trace and compute the records needed for the global memory argument.
+    pub fn finalize(&mut self) {
         if let Some(ref mut buf) = self.trace_buf {
-            buf.flush().unwrap();
+            buf.flush();
         }
+        self.complete_run();
+        self.warn_field_range();
 
         // Call postprocess to set up all variables needed for global accounts, like memory
         // argument or any other deferred tables.
@@ -829,70 +1952,32 @@ impl Runtime {
     }
 
     fn postprocess(&mut self) {
-        let mut program_memory_used = HashMap::with_hasher(BuildNoHashHasher::<u32>::default());
-        for (key, value) in &self.program.memory_image {
-            // By default we assume that the program_memory is used.
-            program_memory_used.insert(*key, (*value, 1));
-        }
-
-        let mut first_memory_record = Vec::new();
-        let mut last_memory_record = Vec::new();
-
-        let memory_keys = self.state.memory.keys().cloned().collect::<Vec<u32>>();
-        for addr in memory_keys {
-            let (value, shard, timestamp) = *self.state.memory.get(&addr).unwrap();
-            if shard == 0 && timestamp == 0 {
-                // This means that we never accessed this memory location throughout our entire program.
-                // The only way this can happen is if this was in the program memory image.
-                // We mark this (addr, value) as not used in the `program_memory_used` map.
-                program_memory_used.insert(addr, (value, 0));
-                continue;
-            }
-            // If the memory addr was accessed, we only add it to "first_memory_record" if it was
-            // not in the program_memory_image, otherwise we'll add to the memory argument from
-            // the program_memory_image table.
-            if !self.program.memory_image.contains_key(&addr) {
-                first_memory_record.push((
-                    addr,
-                    MemoryRecord {
-                        value: 0,
-                        shard: 0,
-                        timestamp: 0,
-                    },
-                    1,
-                ));
-            }
-
-            last_memory_record.push((
-                addr,
-                MemoryRecord {
-                    value,
-                    shard,
-                    timestamp,
-                },
-                1,
-            ));
+        self.record.final_shard = Some(ShardExtent {
+            shard: self.state.current_shard,
+            clk: self.state.clk,
+        });
+        self.record.public_values_digest = Some(self.public_values_digest());
+        self.record.output_channels = self.state.output_channels.clone();
+        self.record
+            .output_channels
+            .insert(0, self.state.output_stream.clone());
+
+        if !self.record_filter.contains(RecordFilter::MEMORY) {
+            return;
         }
 
-        let mut program_memory_record = program_memory_used
-            .iter()
-            .map(|(&addr, &(value, used))| {
-                (
-                    addr,
-                    MemoryRecord {
-                        value,
-                        shard: 0,
-                        timestamp: 0,
-                    },
-                    used,
-                )
-            })
-            .collect::<Vec<(u32, MemoryRecord, u32)>>();
-        program_memory_record.sort_by_key(|&(addr, _, _)| addr);
+        let records = self.build_memory_records(true);
+        self.record.first_memory_record = records.first;
+        self.record.last_memory_record = records.last;
+        self.record.program_memory_record = records.program;
 
-        self.record.first_memory_record = first_memory_record;
-        self.record.last_memory_record = last_memory_record;
-        self.record.program_memory_record = program_memory_record;
+        #[cfg(debug_assertions)]
+        if let Err(inconsistencies) = self.record.check_memory_consistency(&self.program) {
+            for inconsistency in inconsistencies.iter() {
+                tracing::error!("memory inconsistency: {}", inconsistency);
+            }
+            panic!("{} memory inconsistencies found", inconsistencies.len());
+        }
     }
 }
 
@@ -904,7 +1989,20 @@ pub mod tests {
         utils::tests::{FIBONACCI_ELF, SSZ_WITHDRAWALS_ELF},
     };
 
-    use super::{Instruction, Opcode, Program, Runtime};
+    use std::sync::Arc;
+
+    use crate::cpu::MemoryRecordEnum;
+    use crate::syscall::SyscallHalt;
+    use crate::utils::asm::assemble;
+
+    use p3_baby_bear::BabyBear;
+    use p3_field::AbstractField;
+
+    use super::{
+        AccessPosition, CpuRecord, ExecutionError, Instruction, Opcode, Program, Runtime,
+        ShardExtent, Syscall, SyscallArgs, SyscallCode, SyscallContext, UninitMemoryPolicy,
+        DEFAULT_MIN_DATA_ADDR, MEMORY_ADDR_LIMIT,
+    };
 
     pub fn simple_program() -> Program {
         let instructions = vec![
@@ -939,6 +2037,627 @@ pub mod tests {
         assert_eq!(runtime.register(Register::X31), 42);
     }
 
+    #[test]
+    fn test_try_run_errors() {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, 12345, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::UnsupportedSyscall { code: 12345, pc: 4 })
+        );
+        assert_eq!(runtime.state.pc, 4);
+
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, 101, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 4, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime.write_stdin_slice(&[1, 2]);
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::InputExhausted { pc: 8 })
+        );
+
+        let instructions = vec![Instruction::new(Opcode::UNIMP, 0, 0, 0, true, true)];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::Unimplemented { pc: 0 })
+        );
+    }
+
+    fn load_program(base: u32, offset: u32) -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, base, false, true),
+            Instruction::new(Opcode::LW, 6, 5, offset, false, true),
+        ];
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_address_out_of_range() {
+        let mut runtime = Runtime::new(load_program(8, -16i32 as u32));
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::AddressOutOfRange {
+                base: 8,
+                offset: -16i32 as u32,
+                effective: 0xffff_fff8,
+                pc: 4,
+            })
+        );
+
+        // Wrapping around into the registers.
+        let mut runtime = Runtime::new(load_program(u32::MAX - 3, 8));
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::AddressOutOfRange {
+                base: u32::MAX - 3,
+                offset: 8,
+                effective: 4,
+                pc: 4,
+            })
+        );
+
+        // Stores are checked the same way.
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, MEMORY_ADDR_LIMIT + 3, false, true),
+            Instruction::new(Opcode::SB, 6, 5, 0, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        assert!(matches!(
+            runtime.try_run(),
+            Err(ExecutionError::AddressOutOfRange { pc: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_reserved_addresses() {
+        let mut program = load_program(44, 0);
+        program.data_floor = Some(0x1000);
+        assert_eq!(program.min_data_addr(), 0x1000);
+        let mut runtime = Runtime::new(program.clone());
+        let err = runtime.try_run().unwrap_err();
+        assert_eq!(
+            err,
+            ExecutionError::ReservedAddress {
+                addr: 44,
+                min_data_addr: 0x1000,
+                pc: 4,
+            }
+        );
+        assert!(err.to_string().contains("address 0x2c is reserved"));
+        assert_eq!(runtime.register(Register::X6), 0);
+        assert!(!runtime.state.memory.contains_key(&44));
+
+        // The last reserved word, and the first one at the limit.
+        program.instructions[0].op_c = 0x1000;
+        program.instructions[1].op_c = -4i32 as u32;
+        let mut runtime = Runtime::new(program.clone());
+        assert!(matches!(
+            runtime.try_run(),
+            Err(ExecutionError::ReservedAddress { addr: 0xffc, .. })
+        ));
+        program.instructions[1].op_c = 0;
+        let mut runtime = Runtime::new(program);
+        assert_eq!(runtime.try_run(), Ok(()));
+
+        // Without a data floor, it is derived from the layout.
+        let mut program = load_program(0x200, 0);
+        assert_eq!(program.min_data_addr(), 44);
+        program.pc_base = 0x20_0000;
+        program.pc_start = 0x20_0000;
+        assert_eq!(program.min_data_addr(), DEFAULT_MIN_DATA_ADDR);
+        program.memory_image.insert(0x800, 1);
+        assert_eq!(program.min_data_addr(), 0x800);
+    }
+
+    #[test]
+    fn test_address_in_range() {
+        // The limit is the BabyBear modulus.
+        assert_eq!(
+            BabyBear::from_canonical_u32(MEMORY_ADDR_LIMIT - 1) + BabyBear::one(),
+            BabyBear::zero()
+        );
+
+        // The last word below the limit, and a base that wraps around to a legal address.
+        let top = (MEMORY_ADDR_LIMIT - 1) & !3;
+        for (base, offset) in [(top - 16, 16), (-16i32 as u32, 0x1010)] {
+            let mut instructions = vec![
+                Instruction::new(Opcode::ADD, 7, 0, 42, false, true),
+                Instruction::new(Opcode::ADD, 5, 0, base, false, true),
+                Instruction::new(Opcode::SW, 7, 5, offset, false, true),
+            ];
+            instructions.extend(load_program(base, offset).instructions);
+            let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+            assert_eq!(runtime.try_run(), Ok(()));
+            assert_eq!(runtime.register(Register::X6), 42);
+            assert_eq!(runtime.word(base.wrapping_add(offset)), 42);
+        }
+    }
+
+    /// A syscall that overwrites t0 before returning the sum of its arguments. Syscalls can only
+    /// access registers through the runtime, since their memory accesses must be in range.
+    struct ClobberT0Syscall;
+
+    impl Syscall for ClobberT0Syscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            let (shard, clk) = (ctx.current_shard(), ctx.clk());
+            ctx.rt.mw(Register::X5 as u32, 0, shard, clk);
+            let args = ctx.args();
+            args.code + args.a0 + args.a1
+        }
+    }
+
+    #[test]
+    fn test_ecall_with_clobbered_t0() {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, 113, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, 7, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 9, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime
+            .syscall_map
+            .insert(SyscallCode::WRITE_CHANNEL, Arc::new(ClobberT0Syscall));
+        runtime.run();
+
+        // The syscall sees the arguments as they were when the ecall was executed.
+        assert_eq!(runtime.register(Register::X10), 113 + 7 + 9);
+        assert_eq!(runtime.register(Register::X5), 0);
+
+        // The ecall reads t0 at B and then writes a0 at A, after the syscall's own accesses.
+        let event = runtime.record.cpu_events.last().unwrap();
+        assert_eq!(event.b, 0);
+        assert_eq!(event.a, 113 + 7 + 9);
+        let Some(MemoryRecordEnum::Read(b_record)) = event.b_record else {
+            panic!("expected a read of t0");
+        };
+        let Some(MemoryRecordEnum::Write(a_record)) = event.a_record else {
+            panic!("expected a write to a0");
+        };
+        assert_eq!(b_record.prev_timestamp, event.clk);
+        assert_eq!(b_record.timestamp, event.clk + 2);
+        assert_eq!(a_record.timestamp, event.clk + 3);
+    }
+
+    /// A malformed syscall that writes a0 itself, although the ecall writes its result there.
+    struct WriteA0Syscall;
+
+    impl Syscall for WriteA0Syscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            ctx.rt.rw(Register::X10, 1);
+            2
+        }
+    }
+
+    #[test]
+    fn test_duplicate_access() {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, 113, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime.full_validation = true;
+        runtime
+            .syscall_map
+            .insert(SyscallCode::WRITE_CHANNEL, Arc::new(WriteA0Syscall));
+
+        let Err(ExecutionError::DuplicateAccess { access, pc }) = runtime.try_run() else {
+            panic!("expected a duplicate access");
+        };
+        assert_eq!(pc, 4);
+        assert_eq!(access.position, AccessPosition::A);
+        assert_eq!(access.existing.value(), 1);
+        assert_eq!(access.new.value(), 2);
+
+        let mut record = CpuRecord::default();
+        assert_eq!(record.b(), None);
+        assert!(record.set(AccessPosition::B, access.existing).is_ok());
+        assert_eq!(record.b(), Some(access.existing));
+        assert!(record.set(AccessPosition::B, access.new).is_err());
+        assert_eq!(record.len(), 1);
+        record.clear();
+        assert!(record.is_empty());
+    }
+
+    /// A syscall taking 4 extra cycles if a0 is 0 and 40 otherwise, except for a0 = 2 where it
+    /// exceeds its bound. It returns the cycles it took.
+    struct VariableSyscall;
+
+    impl Syscall for VariableSyscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            let cycles = [4, 40, 44][ctx.args().a0 as usize];
+            ctx.advance(cycles);
+            cycles
+        }
+
+        fn num_extra_cycles(&self) -> u32 {
+            40
+        }
+    }
+
+    fn variable_syscall_program(a0: impl IntoIterator<Item = u32>) -> Runtime {
+        let mut instructions = Vec::new();
+        for a0 in a0 {
+            instructions.extend([
+                Instruction::new(Opcode::ADD, 5, 0, 113, false, true),
+                Instruction::new(Opcode::ADD, 10, 0, a0, false, true),
+                Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            ]);
+        }
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime
+            .syscall_map
+            .insert(SyscallCode::WRITE_CHANNEL, Arc::new(VariableSyscall));
+        runtime
+    }
+
+    #[test]
+    fn test_variable_syscall_cycles() {
+        let mut runtime = variable_syscall_program((0..12).map(|i| i % 2));
+        runtime.shard_size = 16;
+        runtime.run();
+
+        let events = &runtime.record.cpu_events;
+        assert_eq!(events.len(), 36);
+        assert!(events.last().unwrap().shard > 2);
+        for (prev, event) in events.iter().zip(events.iter().skip(1)) {
+            // Only the syscall itself needs to fit in the shard, not the bound of every syscall.
+            assert!(event.clk < runtime.shard_size * 4);
+            if event.instruction.opcode == Opcode::ECALL && prev.shard == event.shard {
+                assert_eq!(event.clk - prev.clk, 4 + event.a);
+            }
+        }
+    }
+
+    #[test]
+    fn test_syscall_exceeding_bound() {
+        let mut runtime = variable_syscall_program([0, 2]);
+        assert!(matches!(
+            runtime.try_run(),
+            Err(ExecutionError::SyscallClockMismatch {
+                code: SyscallCode::WRITE_CHANNEL,
+                pc: 20,
+                fault: ClockFault::OverBound {
+                    cycles: 44,
+                    bound: 40
+                },
+                ..
+            })
+        ));
+    }
+
+    /// Copies a1 words from a0 to a0 + 0x1000 at 4 cycles per word, as many as fit in the shard,
+    /// and returns the number of words copied.
+    struct ChunkedCopySyscall;
+
+    impl Syscall for ChunkedCopySyscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            let SyscallArgs {
+                a0: src, a1: len, ..
+            } = ctx.args();
+            ctx.chunked_execute(len, 4, |ctx, i| {
+                let (_, value) = ctx.mr(src + 4 * i);
+                ctx.mw(src + 0x1000 + 4 * i, value);
+            })
+        }
+
+        fn num_extra_cycles(&self) -> u32 {
+            1 << 16
+        }
+    }
+
+    #[test]
+    fn test_chunked_syscall() {
+        let mut program = assemble(
+            "
+                    li   s0, 0x1000
+                    li   s1, 20
+            loop:   li   t0, 113
+                    mv   a0, s0
+                    mv   a1, s1
+                    ecall
+                    slli t1, a0, 2
+                    add  s0, s0, t1
+                    sub  s1, s1, a0
+                    bne  s1, zero, loop
+            ",
+            0,
+        )
+        .unwrap();
+        for i in 0..20 {
+            program.memory_image.insert(0x1000 + 4 * i, i + 1);
+        }
+        let mut runtime = Runtime::new(program);
+        runtime
+            .syscall_map
+            .insert(SyscallCode::WRITE_CHANNEL, Arc::new(ChunkedCopySyscall));
+        runtime.shard_size = 16;
+        runtime.run();
+
+        for i in 0..20 {
+            assert_eq!(runtime.word(0x2000 + 4 * i), i + 1);
+        }
+        // The first call copies the 15 words that fit in a shard, and the second one the rest in
+        // a new shard. Both start their shard and advance the clock by exactly 4 per word.
+        let calls = runtime
+            .record
+            .cpu_events
+            .iter()
+            .filter(|event| event.instruction.opcode == Opcode::ECALL)
+            .map(|event| (event.shard, event.clk, event.a))
+            .collect::<Vec<_>>();
+        assert_eq!(calls, vec![(2, 60, 15), (4, 20, 5)]);
+        assert!(runtime
+            .record
+            .cpu_events
+            .iter()
+            .all(|event| event.clk < runtime.shard_size * 4));
+    }
+
+    /// Three instructions and an `ecall` halting with exit code 7, so that a shard of four
+    /// instructions is full right after it. The last instruction is never executed.
+    fn halt_at_boundary_program() -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, SyscallCode::HALT as u32, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, 7, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 1, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::ADD, 12, 0, 1, false, true),
+        ];
+        Program::new(instructions, 0, 0)
+    }
+
+    /// The HALT syscall taking 8 extra cycles.
+    struct SlowHaltSyscall;
+
+    impl Syscall for SlowHaltSyscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            ctx.advance(8);
+            SyscallHalt::new().execute(ctx)
+        }
+
+        fn num_extra_cycles(&self) -> u32 {
+            8
+        }
+    }
+
+    #[test]
+    fn test_halt_at_shard_boundary() {
+        let mut runtime = Runtime::new(halt_at_boundary_program());
+        runtime.shard_size = 4;
+        runtime.run();
+        assert_eq!(runtime.exit_code(), Some(7));
+        assert_eq!(runtime.register(Register::X12), 0);
+        // The shard is not advanced past the halt.
+        assert_eq!(runtime.current_shard(), 1);
+        assert_eq!(runtime.record.cpu_events.len(), 4);
+        assert_eq!(
+            runtime.record.final_shard,
+            Some(ShardExtent { shard: 1, clk: 17 })
+        );
+
+        // The 8 cycles reserved for the halt do not fit, so it starts a new shard.
+        let mut runtime = Runtime::new(halt_at_boundary_program());
+        runtime.shard_size = 4;
+        runtime
+            .syscall_map
+            .insert(SyscallCode::HALT, Arc::new(SlowHaltSyscall));
+        runtime.run();
+        assert_eq!(runtime.exit_code(), Some(7));
+        let halt = runtime.record.cpu_events.last().unwrap();
+        assert_eq!((halt.shard, halt.clk), (2, 8));
+        assert_eq!(
+            runtime.record.final_shard,
+            Some(ShardExtent { shard: 2, clk: 12 })
+        );
+
+        // Falling through the last instruction at the boundary ends the shard the same way.
+        let mut program = halt_at_boundary_program();
+        program.instructions.truncate(3);
+        program
+            .instructions
+            .push(Instruction::new(Opcode::ADD, 12, 0, 1, false, true));
+        let mut runtime = Runtime::new(program);
+        runtime.shard_size = 4;
+        runtime.run();
+        assert_eq!(runtime.exit_code(), None);
+        assert_eq!(
+            runtime.record.final_shard,
+            Some(ShardExtent { shard: 1, clk: 17 })
+        );
+    }
+
+    #[test]
+    fn test_pc_out_of_bounds() {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 5, 0, 1, false, true),
+            Instruction::new(Opcode::JAL, 0, 0x100, 0, true, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::PcOutOfBounds { pc: 0x104 })
+        );
+    }
+
+    /// Loads 0x1000, 0x1004 and again 0x1000 into x5, x7 and x8, after storing 9 at 0x2000 and
+    /// reading it back into x9.
+    fn uninit_read_program() -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 6, 0, 0x2000, false, true),
+            Instruction::new(Opcode::ADD, 7, 0, 9, false, true),
+            Instruction::new(Opcode::SW, 7, 6, 0, false, true),
+            Instruction::new(Opcode::LW, 9, 6, 0, false, true),
+            Instruction::new(Opcode::ADD, 6, 0, 0x1000, false, true),
+            Instruction::new(Opcode::LW, 5, 6, 0, false, true),
+            Instruction::new(Opcode::LW, 7, 6, 4, false, true),
+            Instruction::new(Opcode::LW, 8, 6, 0, false, true),
+        ];
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_uninit_memory_policy() {
+        let mut runtime = Runtime::new(uninit_read_program());
+        runtime.run();
+        assert_eq!(runtime.register(Register::X5), 0);
+        assert_eq!(runtime.register(Register::X9), 9);
+        assert_eq!(runtime.uninit_warnings, 0);
+
+        let mut runtime = Runtime::new(uninit_read_program());
+        runtime.uninit_memory_policy = UninitMemoryPolicy::Warn { max_warnings: 10 };
+        runtime.run();
+        assert_eq!(runtime.uninit_warnings, 2);
+
+        let mut runtime = Runtime::new(uninit_read_program());
+        runtime.uninit_memory_policy = UninitMemoryPolicy::Warn { max_warnings: 1 };
+        runtime.run();
+        assert_eq!(runtime.uninit_warnings, 1);
+
+        let mut runtime = Runtime::new(uninit_read_program());
+        runtime.uninit_memory_policy = UninitMemoryPolicy::Trap;
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::UninitializedRead {
+                addr: 0x1000,
+                pc: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_uninit_memory_policy_image() {
+        let mut program = uninit_read_program();
+        program.memory_image.insert(0x1000, 5);
+        program.memory_image.insert(0x1004, 6);
+
+        let mut runtime = Runtime::new(program.clone());
+        runtime.uninit_memory_policy = UninitMemoryPolicy::Warn { max_warnings: 10 };
+        runtime.run();
+        assert_eq!(runtime.uninit_warnings, 0);
+
+        let mut runtime = Runtime::new(program);
+        runtime.uninit_memory_policy = UninitMemoryPolicy::Trap;
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert_eq!(runtime.register(Register::X5), 5);
+        assert_eq!(runtime.register(Register::X7), 6);
+    }
+
+    /// Loads the word at 0x1000 into x5 and copies it to 0x1004, before executing `store` of x5
+    /// relative to 0x1000, with 0x1000 to 0x1004 marked as read-only.
+    fn readonly_program(store: Instruction) -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 6, 0, 0x1000, false, true),
+            Instruction::new(Opcode::LW, 5, 6, 0, false, true),
+            Instruction::new(Opcode::SW, 5, 6, 4, false, true),
+            store,
+        ];
+        let mut program = Program::new(instructions, 0, 0);
+        program.memory_image.insert(0x1000, 7);
+        program.mark_readonly(0x1000..0x1004);
+        program
+    }
+
+    #[test]
+    fn test_write_to_readonly() {
+        let mut runtime = Runtime::new(readonly_program(Instruction::new(
+            Opcode::ADD,
+            7,
+            5,
+            1,
+            false,
+            true,
+        )));
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert_eq!(runtime.register(Register::X5), 7);
+        assert_eq!(runtime.word(0x1004), 7);
+        assert!(runtime
+            .record
+            .program_memory_record
+            .iter()
+            .any(|&(addr, _, used)| addr == 0x1000 && used == 1));
+
+        let store = Instruction::new(Opcode::SW, 5, 6, 0, false, true);
+        let mut runtime = Runtime::new(readonly_program(store));
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::WriteToReadOnly {
+                addr: 0x1000,
+                pc: 12
+            })
+        );
+
+        // `sb` writes the whole word containing the byte.
+        let store = Instruction::new(Opcode::SB, 5, 6, 3, false, true);
+        let mut runtime = Runtime::new(readonly_program(store));
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::WriteToReadOnly {
+                addr: 0x1000,
+                pc: 12
+            })
+        );
+        // The word is not modified.
+        assert_eq!(runtime.word(0x1000), 7);
+    }
+
+    #[test]
+    fn test_mark_readonly() {
+        let mut program = Program::new(vec![], 0, 0);
+        program.mark_readonly(0x2000..0x2008);
+        program.mark_readonly(0x1000..0x1001);
+        program.mark_readonly(0x2010..0x2020);
+        program.mark_readonly(0x2004..0x2010);
+        program.mark_readonly(0x3000..0x3000);
+        assert_eq!(program.readonly, vec![0x1000..0x1001, 0x2000..0x2020]);
+        assert!(program.is_readonly(0x1000));
+        assert!(!program.is_readonly(0x1004));
+        assert!(!program.is_readonly(0x1ffc));
+        assert!(program.is_readonly(0x201c));
+        assert!(!program.is_readonly(0x2020));
+    }
+
+    #[test]
+    fn test_reset_with_program() {
+        let mut fresh = Runtime::new(fibonacci_program());
+        fresh.run();
+
+        let program = Arc::new(fibonacci_program());
+        let mut runtime = Runtime::new(simple_program());
+        runtime.write_stdin_slice(&[1, 2, 3]);
+        runtime.run();
+        for _ in 0..2 {
+            runtime.reset_with_program(program.clone());
+            runtime.run();
+            assert_eq!(runtime.record.to_bytes(), fresh.record.to_bytes());
+            assert_eq!(runtime.state.to_bytes(), fresh.state.to_bytes());
+            assert_eq!(runtime.registers(), fresh.registers());
+        }
+    }
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn test_runtime_is_send() {
+        assert_send::<Runtime>();
+
+        let mut runtime = Runtime::new(simple_program());
+        let runtime = std::thread::spawn(move || {
+            runtime.run();
+            runtime
+        })
+        .join()
+        .unwrap();
+        assert_eq!(runtime.register(Register::X31), 42);
+    }
+
     #[test]
     fn test_add() {
         // main:
@@ -1076,6 +2795,55 @@ pub mod tests {
         assert_eq!(runtime.register(Register::X31), 1);
     }
 
+    /// Executes `opcode x31, x29, x30` with x29 = b and x30 = c for each `(opcode, b, c)`.
+    pub fn shift_program(shifts: &[(Opcode, u32, u32)]) -> Program {
+        let instructions = shifts
+            .iter()
+            .flat_map(|&(opcode, b, c)| {
+                [
+                    Instruction::new(Opcode::ADD, 29, 0, b, false, true),
+                    Instruction::new(Opcode::ADD, 30, 0, c, false, true),
+                    Instruction::new(opcode, 31, 29, 30, false, false),
+                ]
+            })
+            .collect();
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_shift_amount_masking() {
+        let b = 0x87654321u32;
+        let shifts = [
+            (Opcode::SLL, 32, b),
+            (Opcode::SLL, 33, b << 1),
+            (Opcode::SLL, 0xffffffe1, b << 1),
+            (Opcode::SRL, 32, b),
+            (Opcode::SRL, 33, b >> 1),
+            (Opcode::SRL, 0xffffffe1, b >> 1),
+            (Opcode::SRA, 32, b),
+            (Opcode::SRA, 33, 0xc3b2a190),
+            (Opcode::SRA, 0xffffffe1, 0xc3b2a190),
+        ];
+        for (opcode, c, expected) in shifts {
+            let mut runtime = Runtime::new(shift_program(&[(opcode, b, c)]));
+            runtime.run();
+            assert_eq!(
+                runtime.register(Register::X31),
+                expected,
+                "{:?} by {}",
+                opcode,
+                c
+            );
+
+            // The event keeps the unmasked amount, which the chips mask themselves.
+            let events = match opcode {
+                Opcode::SLL => &runtime.record.shift_left_events,
+                _ => &runtime.record.shift_right_events,
+            };
+            assert_eq!(events[0].c, c);
+        }
+    }
+
     #[test]
     fn test_slt() {
         //     addi x29, x0, 5
@@ -1540,5 +3308,125 @@ pub mod tests {
         // Assert SH cases
         assert_eq!(runtime.register(Register::X12), 0x12346525);
         assert_eq!(runtime.register(Register::X11), 0x65256525);
+
+        // Assert the final memory
+        let memory = runtime.record.final_memory();
+        assert_eq!(memory.word(0x27654320).unwrap(), 0x12348765);
+        assert_eq!(memory.byte(0x27654321).unwrap(), 0x87);
+        assert_eq!(memory.slice(0x27654322, 2).unwrap(), vec![0x34, 0x12]);
+        assert_eq!(memory.word(0x43627530).unwrap(), 0x65256525);
+        assert_eq!(memory.u64_le(0x27654320).unwrap(), 0x12348765);
+    }
+
+    /// Run the program assembled from `source` and return the runtime.
+    fn run_asm(source: &str, pc_base: u32) -> Runtime {
+        let mut runtime = Runtime::new(assemble(source, pc_base).unwrap());
+        runtime.run();
+        runtime
+    }
+
+    fn count_events(runtime: &Runtime, opcode: Opcode) -> usize {
+        runtime
+            .record
+            .cpu_events
+            .iter()
+            .filter(|event| event.instruction.opcode == opcode)
+            .count()
+    }
+
+    #[test]
+    fn test_asm_counted_loop() {
+        let runtime = run_asm(
+            "
+                    li   t1, 10
+                    li   a0, 0
+            loop:   addi a0, a0, 3
+                    addi t1, t1, -1
+                    bne  t1, zero, loop
+            ",
+            0,
+        );
+        assert_eq!(runtime.register(Register::X10), 30);
+        assert_eq!(runtime.register(Register::X6), 0);
+        assert_eq!(runtime.record.cpu_events.len(), 2 + 10 * 3);
+        assert_eq!(count_events(&runtime, Opcode::BNE), 10);
+    }
+
+    #[test]
+    fn test_asm_forward_branch() {
+        let source = |branch: &str| {
+            format!(
+                "
+                        li   a0, 1
+                        li   a1, 2
+                        {} a0, a0, skip
+                        li   a0, 100
+                        li   a1, 200
+                skip:   add  a2, a0, a1
+                ",
+                branch
+            )
+        };
+
+        // The block is skipped when the branch is taken.
+        let runtime = run_asm(&source("beq"), 0);
+        assert_eq!(runtime.register(Register::X12), 3);
+        assert_eq!(runtime.record.cpu_events.len(), 4);
+
+        let runtime = run_asm(&source("bne"), 0);
+        assert_eq!(runtime.register(Register::X12), 300);
+        assert_eq!(runtime.record.cpu_events.len(), 6);
+    }
+
+    #[test]
+    fn test_asm_call_return() {
+        let runtime = run_asm(
+            "
+                    li   a0, 5
+                    call double
+                    call double
+                    j    end
+            double: add  a0, a0, a0
+                    ret
+            end:
+            ",
+            0x2000,
+        );
+        assert_eq!(runtime.register(Register::X10), 20);
+        // The return address of the second call.
+        assert_eq!(runtime.register(Register::X1), 0x200c);
+        assert_eq!(runtime.record.cpu_events.len(), 8);
+        assert_eq!(count_events(&runtime, Opcode::JAL), 3);
+        assert_eq!(count_events(&runtime, Opcode::JALR), 2);
+        assert_eq!(runtime.record.cpu_events[0].pc, 0x2000);
+    }
+
+    #[test]
+    fn test_asm_loop_with_ecall() {
+        let runtime = run_asm(
+            &format!(
+                "
+                        li   t1, 3
+                        li   s0, 0x2000
+                loop:   sw   t1, 0(s0)
+                        li   t0, {}
+                        li   a0, 3
+                        mv   a1, s0
+                        li   a2, 4
+                        ecall
+                        addi t1, t1, -1
+                        bne  t1, zero, loop
+                ",
+                SyscallCode::WRITE as u32
+            ),
+            0,
+        );
+        assert_eq!(
+            runtime.state.output_stream,
+            [3, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0]
+        );
+        assert_eq!(runtime.register(Register::X6), 0);
+        assert_eq!(runtime.record.cpu_events.len(), 2 + 3 * 8);
+        assert_eq!(count_events(&runtime, Opcode::ECALL), 3);
     }
 }
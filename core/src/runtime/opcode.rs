@@ -1,9 +1,10 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
 use p3_field::Field;
 
 /// An opcode specifies which operation to execute.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub enum Opcode {
     // Arithmetic instructions.
@@ -59,6 +60,18 @@ pub enum Opcode {
 
     // Miscellaneaous instructions.
     UNIMP = 39,
+
+    // Bit manipulation instructions of Zbb, see `Extensions::ZBB`.
+    ANDN = 40,
+    ORN = 41,
+    XNOR = 42,
+    CLZ = 43,
+    CTZ = 44,
+    CPOP = 45,
+    MIN = 46,
+    MAX = 47,
+    MINU = 48,
+    MAXU = 49,
 }
 
 impl Display for Opcode {
@@ -108,11 +121,38 @@ impl Opcode {
             Opcode::REM => "rem",
             Opcode::REMU => "remu",
             Opcode::UNIMP => "unimp",
+            Opcode::ANDN => "andn",
+            Opcode::ORN => "orn",
+            Opcode::XNOR => "xnor",
+            Opcode::CLZ => "clz",
+            Opcode::CTZ => "ctz",
+            Opcode::CPOP => "cpop",
+            Opcode::MIN => "min",
+            Opcode::MAX => "max",
+            Opcode::MINU => "minu",
+            Opcode::MAXU => "maxu",
         }
     }
 }
 
 impl Opcode {
+    /// Whether the opcode is one of the Zbb bit manipulation instructions.
+    pub fn is_bitmanip(&self) -> bool {
+        matches!(
+            self,
+            Opcode::ANDN
+                | Opcode::ORN
+                | Opcode::XNOR
+                | Opcode::CLZ
+                | Opcode::CTZ
+                | Opcode::CPOP
+                | Opcode::MIN
+                | Opcode::MAX
+                | Opcode::MINU
+                | Opcode::MAXU
+        )
+    }
+
     pub fn as_field<F: Field>(self) -> F {
         F::from_canonical_u32(self as u32)
     }
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use super::{RecordFilter, UninitMemoryPolicy, DEFAULT_MAX_SYSCALL_ARG_LEN};
+use crate::utils::env;
+
+/// The settings of a [`Runtime`](super::Runtime) that would otherwise be read from the process
+/// environment or from global state, see
+/// [`Runtime::new_with_options`](super::Runtime::new_with_options).
+///
+/// The runtime only reads its options when it is created, so two runtimes created with the same
+/// options execute the same way whatever the environment is when they run. The default options
+/// are those of an empty environment, see [`RuntimeOptions::from_env`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RuntimeOptions {
+    /// The number of cycles of a shard, a power of two.
+    pub shard_size: u32,
+
+    /// The file the pc trace is written to, if any.
+    pub trace_file: Option<String>,
+
+    /// Whether the last [`DEFAULT_TRACE_LOG_CAPACITY`](super::DEFAULT_TRACE_LOG_CAPACITY) cycles
+    /// are kept in the trace log, see
+    /// [`Runtime::enable_trace_log`](super::Runtime::enable_trace_log).
+    pub log_instructions: bool,
+
+    /// Whether every CPU event is checked as it is emitted, see
+    /// [`Runtime::set_event_validation`](super::Runtime::set_event_validation).
+    pub validate_events: bool,
+
+    /// Whether the final state is checked once execution finishes, see
+    /// [`Runtime::set_final_invariant_checks`](super::Runtime::set_final_invariant_checks).
+    pub check_final_invariants: bool,
+
+    /// Whether to record events, see [`Runtime::emit_events`](super::Runtime::emit_events).
+    pub emit_events: bool,
+
+    /// Which classes of events are recorded.
+    pub record_filter: RecordFilter,
+
+    /// How reads of uninitialized memory are handled.
+    pub uninit_memory_policy: UninitMemoryPolicy,
+
+    /// Whether registered syscalls are restricted to their memory footprint, see
+    /// [`Runtime::set_syscall_footprint_checks`](super::Runtime::set_syscall_footprint_checks).
+    pub syscall_footprint_checks: bool,
+
+    /// Whether entering an unconstrained block stops execution with
+    /// [`ExecutionError::UnconstrainedForbidden`](super::ExecutionError::UnconstrainedForbidden),
+    /// so that every cycle is covered by the proof. See
+    /// [`Program::uses_unconstrained`](super::Program::uses_unconstrained) to reject such guests
+    /// before executing them.
+    pub forbid_unconstrained: bool,
+
+    /// The address and length in bytes of a region of memory the guest stores to for the host
+    /// to read while it runs, for instance to report its progress, see
+    /// [`Runtime::scratch`](super::Runtime::scratch).
+    ///
+    /// The region is not provable: stores to it have no memory record and the region is left out
+    /// of the memory argument, and loads from it stop execution with
+    /// [`ExecutionError::ScratchRead`](super::ExecutionError::ScratchRead) outside of
+    /// unconstrained blocks, so that its values cannot flow back into constrained execution.
+    /// Stores made in unconstrained blocks are rolled back when the block exits, like every
+    /// other write.
+    pub scratch_region: Option<(u32, u32)>,
+
+    /// The names of the functions the pc trace and the trace log are restricted to, or empty to
+    /// trace every cycle. Only the cycles whose pc is inside one of the functions are traced, so
+    /// the functions they call are left out unless they are named as well.
+    ///
+    /// The functions are looked up in [`Program::symbols`](super::Program::symbols) when the
+    /// runtime is created, which fails with [`Error::UnknownSymbol`](super::Error::UnknownSymbol)
+    /// if one is missing.
+    pub trace_filter: Vec<String>,
+
+    /// The maximum length in bytes of each string or byte slice argument a syscall reads with
+    /// [`SyscallContext::read_bytes_checked`](super::SyscallContext::read_bytes_checked). Longer
+    /// arguments make the syscall return
+    /// [`SYSCALL_ERR_LENGTH_TOO_LARGE`](super::SYSCALL_ERR_LENGTH_TOO_LARGE) to the guest.
+    pub max_syscall_arg_len: u32,
+}
+
+impl Default for RuntimeOptions {
+    fn default() -> Self {
+        Self {
+            shard_size: 1 << 19,
+            trace_file: None,
+            log_instructions: false,
+            validate_events: false,
+            check_final_invariants: false,
+            emit_events: true,
+            record_filter: RecordFilter::default(),
+            uninit_memory_policy: UninitMemoryPolicy::default(),
+            syscall_footprint_checks: false,
+            forbid_unconstrained: false,
+            scratch_region: None,
+            trace_filter: Vec::new(),
+            max_syscall_arg_len: DEFAULT_MAX_SYSCALL_ARG_LEN,
+        }
+    }
+}
+
+impl RuntimeOptions {
+    /// The options set by the environment: `SHARD_SIZE`, `TRACE_FILE`, `VALIDATE_EVENTS` and
+    /// `CHECK_FINAL_INVARIANTS`, read through [`env`], and whether the logger is enabled at the
+    /// trace level. This is what [`Runtime::new`](super::Runtime::new) uses.
+    pub fn from_env() -> Self {
+        Self::from_lookup(&env::process_var)
+    }
+
+    /// The options [`RuntimeOptions::from_env`] would read from an environment holding exactly
+    /// `vars`, whatever the environment of the process is. This is how the settings are passed on
+    /// targets without an environment, such as `wasm32-unknown-unknown`.
+    pub fn from_vars(vars: &HashMap<String, String>) -> Self {
+        Self::from_lookup(&|key| vars.get(key).cloned())
+    }
+
+    fn from_lookup(vars: env::Vars) -> Self {
+        Self {
+            shard_size: env::shard_size_from(vars) as u32,
+            trace_file: env::trace_file_from(vars),
+            log_instructions: log::log_enabled!(log::Level::Trace),
+            validate_events: env::validate_events_from(vars),
+            check_final_invariants: env::check_final_invariants_from(vars),
+            ..Self::default()
+        }
+    }
+
+    /// Panics if `shard_size` is not a power of two.
+    pub fn with_shard_size(mut self, shard_size: u32) -> Self {
+        assert!(
+            shard_size.is_power_of_two(),
+            "shard size must be a power of two"
+        );
+        self.shard_size = shard_size;
+        self
+    }
+
+    pub fn with_trace_file(mut self, trace_file: Option<String>) -> Self {
+        self.trace_file = trace_file;
+        self
+    }
+
+    pub fn with_log_instructions(mut self, enabled: bool) -> Self {
+        self.log_instructions = enabled;
+        self
+    }
+
+    pub fn with_event_validation(mut self, enabled: bool) -> Self {
+        self.validate_events = enabled;
+        self
+    }
+
+    pub fn with_final_invariant_checks(mut self, enabled: bool) -> Self {
+        self.check_final_invariants = enabled;
+        self
+    }
+
+    pub fn with_emit_events(mut self, enabled: bool) -> Self {
+        self.emit_events = enabled;
+        self
+    }
+
+    pub fn with_record_filter(mut self, filter: RecordFilter) -> Self {
+        self.record_filter = filter;
+        self
+    }
+
+    pub fn with_uninit_memory_policy(mut self, policy: UninitMemoryPolicy) -> Self {
+        self.uninit_memory_policy = policy;
+        self
+    }
+
+    pub fn with_syscall_footprint_checks(mut self, enabled: bool) -> Self {
+        self.syscall_footprint_checks = enabled;
+        self
+    }
+
+    pub fn with_forbid_unconstrained(mut self, forbidden: bool) -> Self {
+        self.forbid_unconstrained = forbidden;
+        self
+    }
+
+    /// Panics if `addr` or `len` is not a multiple of 4, or if the region extends past the end
+    /// of the address space.
+    pub fn with_scratch_region(mut self, addr: u32, len: u32) -> Self {
+        assert!(
+            addr % 4 == 0 && len % 4 == 0,
+            "scratch region must be aligned to words"
+        );
+        assert!(
+            addr.checked_add(len).is_some(),
+            "scratch region overflows the address space"
+        );
+        self.scratch_region = Some((addr, len));
+        self
+    }
+
+    pub fn with_trace_filter(mut self, functions: Vec<String>) -> Self {
+        self.trace_filter = functions;
+        self
+    }
+
+    pub fn with_max_syscall_arg_len(mut self, len: u32) -> Self {
+        self.max_syscall_arg_len = len;
+        self
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::Runtime;
+
+    fn run(runtime: &mut Runtime) -> (u32, u32) {
+        runtime.run();
+        let last = runtime.record.cpu_events.last().unwrap();
+        (last.shard, runtime.state.global_clk)
+    }
+
+    #[test]
+    fn test_options_from_vars() {
+        let vars = HashMap::from([
+            ("SHARD_SIZE".to_string(), (1 << 8).to_string()),
+            ("CHECK_FINAL_INVARIANTS".to_string(), "true".to_string()),
+            ("UNRELATED".to_string(), "true".to_string()),
+        ]);
+        let options = RuntimeOptions::from_vars(&vars).with_log_instructions(false);
+        assert_eq!(
+            options,
+            RuntimeOptions::default()
+                .with_shard_size(1 << 8)
+                .with_final_invariant_checks(true)
+        );
+        assert_eq!(
+            RuntimeOptions::from_vars(&HashMap::new()).with_log_instructions(false),
+            RuntimeOptions::default()
+        );
+    }
+
+    #[test]
+    fn test_options_kept_across_resets() {
+        let options = RuntimeOptions::default()
+            .with_shard_size(1 << 8)
+            .with_log_instructions(false);
+        let mut expected = Runtime::new_with_options(fibonacci_program(), options.clone());
+        let expected = run(&mut expected);
+        assert!(expected.0 > 1);
+
+        let mut runtime = Runtime::new_with_options(fibonacci_program(), options);
+        let actual = run(&mut runtime);
+        runtime.reset_with_program(Arc::new(fibonacci_program()));
+        let rerun = run(&mut runtime);
+
+        assert_eq!(actual, expected);
+        assert_eq!(rerun, expected);
+        assert_eq!(runtime.shard_size, (1 << 8) * 4);
+        assert!(runtime.trace_buf.is_none());
+    }
+
+    #[test]
+    fn test_options_applied() {
+        let options = RuntimeOptions::default()
+            .with_emit_events(false)
+            .with_record_filter(RecordFilter::CPU)
+            .with_final_invariant_checks(true);
+        let runtime = Runtime::new_with_options(fibonacci_program(), options);
+        assert!(!runtime.emit_events);
+        assert_eq!(runtime.record_filter, RecordFilter::CPU);
+        assert!(runtime.final_invariant_checks);
+        assert_eq!(runtime.shard_size, (1 << 19) * 4);
+    }
+}
@@ -0,0 +1,422 @@
+use core::fmt::{Display, Formatter};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Extensions, Instruction, LinkedBlob, Program, ProgramValidationError, SymbolTable,
+    NUM_REGISTERS,
+};
+use crate::disassembler::{GuestMetadata, WORD_SIZE};
+
+/// The addresses of the registers, which the memory image may not hold.
+const REGISTERS: Range<u32> = 0..NUM_REGISTERS;
+
+/// Consecutive instructions replacing or appended to those of a program, see [`ProgramPatch`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstructionPatch {
+    /// The index of the first instruction.
+    pub index: u32,
+
+    pub instructions: Vec<Instruction>,
+}
+
+/// The changes from one version of a program to another, see [`Program::diff`]. Only the parts of
+/// the program that changed are included.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProgramPatch {
+    /// The [`Program::digest`] of the program the patch applies to.
+    pub base_digest: [u8; 32],
+
+    /// The [`Program::digest`] of the patched program.
+    pub target_digest: [u8; 32],
+
+    pub pc_start: Option<u32>,
+
+    pub pc_base: Option<u32>,
+
+    /// The number of instructions of the patched program, if it changed.
+    pub instruction_count: Option<u32>,
+
+    /// The ranges of instructions that changed, sorted and disjoint.
+    pub instructions: Vec<InstructionPatch>,
+
+    /// The words added to the memory image.
+    pub added: BTreeMap<u32, u32>,
+
+    /// The addresses of the words removed from the memory image.
+    pub removed: Vec<u32>,
+
+    /// The new values of the words of the memory image that changed.
+    pub modified: BTreeMap<u32, u32>,
+
+    pub symbols: Option<SymbolTable>,
+
+    pub readonly: Option<Vec<Range<u32>>>,
+
+    pub linked: Option<Vec<LinkedBlob>>,
+
+    pub metadata: Option<Option<GuestMetadata>>,
+
+    pub extensions: Option<Extensions>,
+}
+
+impl ProgramPatch {
+    /// Whether the patch changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.pc_start.is_none()
+            && self.pc_base.is_none()
+            && self.instruction_count.is_none()
+            && self.instructions.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+            && self.symbols.is_none()
+            && self.readonly.is_none()
+            && self.linked.is_none()
+            && self.metadata.is_none()
+            && self.extensions.is_none()
+    }
+}
+
+/// An error applying a [`ProgramPatch`].
+#[derive(Debug, Clone)]
+pub enum PatchError {
+    /// The patch was made for another program.
+    BaseMismatch { expected: [u8; 32], found: [u8; 32] },
+
+    /// A range of instructions starts past the end of the instructions patched so far.
+    InstructionsOutOfRange { index: u32, len: usize },
+
+    /// The patch removes or modifies a word that is not in the memory image.
+    MissingWord { addr: u32 },
+
+    /// The patch adds a word that is already in the memory image.
+    ExistingWord { addr: u32 },
+
+    /// The patch puts a word in the memory image at an unaligned address or at a register.
+    InvalidWord { addr: u32 },
+
+    /// The patched program contains invalid instructions.
+    Invalid(ProgramValidationError),
+
+    /// The patched program is not the one the patch was made for.
+    TargetMismatch { expected: [u8; 32], found: [u8; 32] },
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PatchError::BaseMismatch { expected, found } => write!(
+                f,
+                "patch applies to program {} but was given program {}",
+                hex::encode(expected),
+                hex::encode(found)
+            ),
+            PatchError::InstructionsOutOfRange { index, len } => write!(
+                f,
+                "patch of {} instructions at index {} is out of range",
+                len, index
+            ),
+            PatchError::MissingWord { addr } => {
+                write!(f, "patch changes missing word at 0x{:08x}", addr)
+            }
+            PatchError::ExistingWord { addr } => {
+                write!(f, "patch adds existing word at 0x{:08x}", addr)
+            }
+            PatchError::InvalidWord { addr } => {
+                write!(f, "patch adds invalid word at 0x{:08x}", addr)
+            }
+            PatchError::Invalid(error) => write!(f, "patched {}", error),
+            PatchError::TargetMismatch { expected, found } => write!(
+                f,
+                "patch was made for program {} but produced program {}",
+                hex::encode(expected),
+                hex::encode(found)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl From<ProgramValidationError> for PatchError {
+    fn from(error: ProgramValidationError) -> Self {
+        PatchError::Invalid(error)
+    }
+}
+
+fn same_instruction(a: &Instruction, b: &Instruction) -> bool {
+    (a.opcode, a.op_a, a.op_b, a.op_c, a.imm_b, a.imm_c)
+        == (b.opcode, b.op_a, b.op_b, b.op_c, b.imm_b, b.imm_c)
+}
+
+/// `new` if it differs from `old`.
+fn changed<T: PartialEq + Clone>(old: &T, new: &T) -> Option<T> {
+    (old != new).then(|| new.clone())
+}
+
+impl Program {
+    /// The changes turning `self` into `other`, to send `other` to a machine that has `self`.
+    pub fn diff(&self, other: &Program) -> ProgramPatch {
+        let mut instructions = Vec::<InstructionPatch>::new();
+        for (index, instruction) in other.instructions.iter().enumerate() {
+            let unchanged = self
+                .instructions
+                .get(index)
+                .is_some_and(|old| same_instruction(old, instruction));
+            if unchanged {
+                continue;
+            }
+            match instructions.last_mut() {
+                Some(last) if last.index as usize + last.instructions.len() == index => {
+                    last.instructions.push(*instruction);
+                }
+                _ => instructions.push(InstructionPatch {
+                    index: index as u32,
+                    instructions: vec![*instruction],
+                }),
+            }
+        }
+
+        let mut patch = ProgramPatch {
+            base_digest: self.digest(),
+            target_digest: other.digest(),
+            pc_start: changed(&self.pc_start, &other.pc_start),
+            pc_base: changed(&self.pc_base, &other.pc_base),
+            instruction_count: changed(&self.instructions.len(), &other.instructions.len())
+                .map(|len| len as u32),
+            instructions,
+            symbols: changed(&self.symbols, &other.symbols),
+            readonly: changed(&self.readonly, &other.readonly),
+            linked: changed(&self.linked, &other.linked),
+            metadata: changed(&self.metadata, &other.metadata),
+            extensions: changed(&self.extensions, &other.extensions),
+            ..Default::default()
+        };
+        for (&addr, &value) in other.memory_image.iter() {
+            match self.memory_image.get(&addr) {
+                None => {
+                    patch.added.insert(addr, value);
+                }
+                Some(&old) if old != value => {
+                    patch.modified.insert(addr, value);
+                }
+                Some(_) => {}
+            }
+        }
+        patch.removed = self
+            .memory_image
+            .keys()
+            .filter(|addr| !other.memory_image.contains_key(addr))
+            .copied()
+            .collect();
+        patch
+    }
+
+    /// The program `patch` was made for from `self`, checking that `self` is the program it was
+    /// made from and that the result is valid and is the program it was made for.
+    pub fn apply_patch(&self, patch: &ProgramPatch) -> Result<Program, PatchError> {
+        let digest = self.digest();
+        if digest != patch.base_digest {
+            return Err(PatchError::BaseMismatch {
+                expected: patch.base_digest,
+                found: digest,
+            });
+        }
+
+        let mut program = self.clone();
+        if let Some(pc_start) = patch.pc_start {
+            program.pc_start = pc_start;
+        }
+        if let Some(pc_base) = patch.pc_base {
+            program.pc_base = pc_base;
+        }
+
+        let count = patch
+            .instruction_count
+            .map_or(program.instructions.len(), |count| count as usize);
+        program.instructions.truncate(count);
+        for range in patch.instructions.iter() {
+            let index = range.index as usize;
+            let out_of_range = PatchError::InstructionsOutOfRange {
+                index: range.index,
+                len: range.instructions.len(),
+            };
+            if index > program.instructions.len() || index + range.instructions.len() > count {
+                return Err(out_of_range);
+            }
+            let overlap = (program.instructions.len() - index).min(range.instructions.len());
+            program.instructions[index..index + overlap]
+                .copy_from_slice(&range.instructions[..overlap]);
+            program
+                .instructions
+                .extend_from_slice(&range.instructions[overlap..]);
+        }
+        if program.instructions.len() != count {
+            return Err(PatchError::InstructionsOutOfRange {
+                index: program.instructions.len() as u32,
+                len: count - program.instructions.len(),
+            });
+        }
+
+        for addr in patch.removed.iter() {
+            if program.memory_image.remove(addr).is_none() {
+                return Err(PatchError::MissingWord { addr: *addr });
+            }
+        }
+        for (&addr, &value) in patch.modified.iter() {
+            match program.memory_image.get_mut(&addr) {
+                Some(word) => *word = value,
+                None => return Err(PatchError::MissingWord { addr }),
+            }
+        }
+        for (&addr, &value) in patch.added.iter() {
+            if addr % WORD_SIZE as u32 != 0 || REGISTERS.contains(&addr) {
+                return Err(PatchError::InvalidWord { addr });
+            }
+            if program.memory_image.insert(addr, value).is_some() {
+                return Err(PatchError::ExistingWord { addr });
+            }
+        }
+
+        if let Some(symbols) = &patch.symbols {
+            program.symbols = symbols.clone();
+        }
+        if let Some(readonly) = &patch.readonly {
+            program.readonly = readonly.clone();
+        }
+        if let Some(linked) = &patch.linked {
+            program.linked = linked.clone();
+        }
+        if let Some(metadata) = &patch.metadata {
+            program.metadata = metadata.clone();
+        }
+        if let Some(extensions) = patch.extensions {
+            program.extensions = extensions;
+        }
+
+        program.validate()?;
+        let digest = program.digest();
+        if digest != patch.target_digest {
+            return Err(PatchError::TargetMismatch {
+                expected: patch.target_digest,
+                found: digest,
+            });
+        }
+        Ok(program)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::Opcode;
+
+    /// Fibonacci with a few instructions changed and appended, and part of its data changed.
+    fn updated_program() -> Program {
+        let mut program = fibonacci_program();
+        program.instructions[3] = Instruction::new(Opcode::ADD, 5, 0, 7, false, true);
+        program.instructions[4] = Instruction::new(Opcode::XOR, 6, 5, 5, false, false);
+        program.instructions[40] = Instruction::new(Opcode::ADD, 5, 0, 9, false, true);
+        program
+            .instructions
+            .push(Instruction::new(Opcode::ADD, 5, 0, 1, false, true));
+        let addrs = program.memory_image.keys().copied().collect::<Vec<_>>();
+        program.memory_image.remove(&addrs[0]);
+        *program.memory_image.get_mut(&addrs[10]).unwrap() ^= 1;
+        program.memory_image.insert(0x7000_0000, 42);
+        program.mark_readonly(0x7000_0000..0x7000_0004);
+        program
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let base = fibonacci_program();
+        let target = updated_program();
+        let patch = base.diff(&target);
+        assert_eq!(patch.instructions.len(), 3);
+        assert_eq!(
+            (
+                patch.instructions[0].index,
+                patch.instructions[0].instructions.len()
+            ),
+            (3, 2)
+        );
+        assert_eq!(
+            patch.instruction_count,
+            Some(base.instructions.len() as u32 + 1)
+        );
+        assert_eq!(patch.added.len(), 1);
+        assert_eq!(patch.removed.len(), 1);
+        assert_eq!(patch.modified.len(), 1);
+        assert!(patch.pc_start.is_none() && patch.readonly.is_some());
+
+        let patched = base.apply_patch(&patch).unwrap();
+        assert_eq!(patched.digest(), target.digest());
+
+        let decoded = ProgramPatch::try_from_bytes(&patch.to_bytes()).unwrap();
+        assert_eq!(
+            base.apply_patch(&decoded).unwrap().digest(),
+            target.digest()
+        );
+
+        // Patching back to the base removes the appended instruction.
+        let reverse = target.diff(&base);
+        let reverted = target.apply_patch(&reverse).unwrap();
+        assert_eq!(reverted.digest(), base.digest());
+        assert_eq!(reverted.instructions.len(), base.instructions.len());
+    }
+
+    #[test]
+    fn test_wrong_base() {
+        let base = fibonacci_program();
+        let target = updated_program();
+        let patch = base.diff(&target);
+        let err = target.apply_patch(&patch).unwrap_err();
+        let PatchError::BaseMismatch { expected, found } = err else {
+            panic!("expected a digest mismatch, got {}", err);
+        };
+        assert_eq!(expected, base.digest());
+        assert_eq!(found, target.digest());
+    }
+
+    #[test]
+    fn test_empty_diff() {
+        let program = fibonacci_program();
+        let patch = program.diff(&program.clone());
+        assert!(patch.is_empty());
+        assert_eq!(patch.base_digest, patch.target_digest);
+        assert_eq!(
+            program.apply_patch(&patch).unwrap().digest(),
+            program.digest()
+        );
+    }
+
+    #[test]
+    fn test_invalid_patches() {
+        let base = fibonacci_program();
+        let mut patch = base.diff(&updated_program());
+        patch.added.insert(0x7000_0002, 1);
+        assert!(matches!(
+            base.apply_patch(&patch),
+            Err(PatchError::InvalidWord { addr: 0x7000_0002 })
+        ));
+
+        let mut patch = base.diff(&updated_program());
+        patch.instructions[2].index += 2;
+        assert!(matches!(
+            base.apply_patch(&patch),
+            Err(PatchError::InstructionsOutOfRange { .. })
+        ));
+
+        let mut patch = base.diff(&updated_program());
+        *patch.modified.values_mut().next().unwrap() ^= 2;
+        assert!(matches!(
+            base.apply_patch(&patch),
+            Err(PatchError::TargetMismatch { .. })
+        ));
+    }
+}
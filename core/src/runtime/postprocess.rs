@@ -0,0 +1,308 @@
+use p3_maybe_rayon::prelude::*;
+
+use super::{Runtime, MAX_REGISTER_ADDR};
+use crate::cpu::MemoryRecord;
+
+/// The number of addresses of the final memory state each task of
+/// [`Runtime::build_memory_records`] processes.
+pub const POSTPROCESS_CHUNK_SIZE: usize = 1 << 16;
+
+/// A memory record with its multiplicity, as stored in the execution record.
+type MemoryRecordEntry = (u32, MemoryRecord, u32);
+
+/// The memory records built from the final memory state, see [`Runtime::build_memory_records`].
+#[derive(Debug, Clone, Default)]
+pub struct MemoryRecords {
+    pub first: Vec<MemoryRecordEntry>,
+    pub last: Vec<MemoryRecordEntry>,
+    pub program: Vec<MemoryRecordEntry>,
+}
+
+/// The records of a range of addresses, and the words of the memory image it never accessed, by
+/// their index in the image.
+#[derive(Default)]
+struct PartialRecords {
+    first: Vec<MemoryRecordEntry>,
+    last: Vec<MemoryRecordEntry>,
+    unused_image: Vec<(usize, u32)>,
+}
+
+impl Runtime {
+    /// Build the first, last and program memory records of the execution from the final memory
+    /// state, all sorted by address, leaving out the words below
+    /// [`Program::min_data_addr`](super::Program::min_data_addr) other than the registers.
+    ///
+    /// With `parallel`, the addresses are sorted and then processed in ranges of
+    /// [`POSTPROCESS_CHUNK_SIZE`] on the rayon thread pool. The records are exactly the same as
+    /// without it, and builds without the `parallel` feature always process them serially.
+    pub fn build_memory_records(&self, parallel: bool) -> MemoryRecords {
+        let (image_addrs, image_values): (Vec<u32>, Vec<u32>) = self
+            .program
+            .memory_image
+            .iter()
+            .map(|(addr, value)| (*addr, *value))
+            .unzip();
+        let written = self.written_addrs.as_ref();
+        let min_data_addr = self.min_data_addr;
+
+        let mut memory = self
+            .state
+            .memory
+            .iter()
+            .map(|(addr, record)| (*addr, *record))
+            .collect::<Vec<_>>();
+        sort_by_addr(&mut memory, parallel);
+
+        let process = |chunk: &[(u32, (u32, u32, u32))]| {
+            let mut partial = PartialRecords::default();
+            for &(addr, (value, shard, timestamp)) in chunk {
+                // The reserved words between the registers and the data can only be in the memory
+                // state after an access that stopped execution, and are never part of the memory
+                // argument.
+                if addr > MAX_REGISTER_ADDR && addr < min_data_addr {
+                    continue;
+                }
+                let image_index = image_addrs.binary_search(&addr).ok();
+                if shard == 0 && timestamp == 0 {
+                    // This means that we never accessed this memory location throughout our
+                    // entire program. The only way this can happen is if this was in the program
+                    // memory image, and we mark it as not used. Words zeroed by the runtime, such
+                    // as the stack, are not part of the image.
+                    if let Some(index) = image_index {
+                        partial.unused_image.push((index, value));
+                    }
+                    continue;
+                }
+                // If the memory addr was accessed, we only add it to the first records if it was
+                // not in the program memory image, otherwise we'll add to the memory argument
+                // from the program memory image table.
+                if image_index.is_none() {
+                    partial.first.push((
+                        addr,
+                        MemoryRecord {
+                            value: 0,
+                            shard: 0,
+                            timestamp: 0,
+                        },
+                        1,
+                    ));
+                }
+
+                let image_value = image_index.map(|index| image_values[index]);
+                if Runtime::elides_final_record(written, image_value, addr, value) {
+                    continue;
+                }
+                partial.last.push((
+                    addr,
+                    MemoryRecord {
+                        value,
+                        shard,
+                        timestamp,
+                    },
+                    1,
+                ));
+            }
+            partial
+        };
+        let partials = if parallel {
+            memory
+                .par_chunks(POSTPROCESS_CHUNK_SIZE)
+                .map(process)
+                .collect::<Vec<_>>()
+        } else {
+            memory
+                .chunks(POSTPROCESS_CHUNK_SIZE)
+                .map(process)
+                .collect::<Vec<_>>()
+        };
+
+        // By default we assume that the program memory is used. The words of a frozen memory that
+        // were never loaded are not.
+        let mut records = MemoryRecords {
+            program: image_addrs
+                .iter()
+                .zip(image_values.iter())
+                .map(|(&addr, &value)| {
+                    let used = self.frozen.is_none() || self.state.memory.contains_key(&addr);
+                    (
+                        addr,
+                        MemoryRecord {
+                            value,
+                            shard: 0,
+                            timestamp: 0,
+                        },
+                        used as u32,
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        };
+        for partial in partials {
+            records.first.extend(partial.first);
+            records.last.extend(partial.last);
+            for (index, value) in partial.unused_image {
+                records.program[index].1.value = value;
+                records.program[index].2 = 0;
+            }
+        }
+        records
+    }
+}
+
+/// Sort the final memory state by address, on the rayon thread pool with `parallel`.
+fn sort_by_addr(memory: &mut [(u32, (u32, u32, u32))], parallel: bool) {
+    #[cfg(feature = "parallel")]
+    if parallel {
+        memory.par_sort_unstable_by_key(|(addr, _)| *addr);
+        return;
+    }
+    #[cfg(not(feature = "parallel"))]
+    let _ = parallel;
+    memory.sort_unstable_by_key(|(addr, _)| *addr);
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::Program;
+
+    /// The serial construction the records were first built with, which they must keep matching.
+    fn reference_records(runtime: &Runtime) -> MemoryRecords {
+        let mut program_memory_used = HashMap::new();
+        for (key, value) in &runtime.program.memory_image {
+            program_memory_used.insert(*key, (*value, 1));
+        }
+        let mut first = Vec::new();
+        let mut last = Vec::new();
+        let mut memory_keys = runtime.state.memory.keys().cloned().collect::<Vec<u32>>();
+        memory_keys.sort_unstable();
+        for addr in memory_keys {
+            let (value, shard, timestamp) = *runtime.state.memory.get(&addr).unwrap();
+            let image_value = runtime.program.memory_image.get(&addr).copied();
+            if shard == 0 && timestamp == 0 {
+                if image_value.is_some() {
+                    program_memory_used.insert(addr, (value, 0));
+                }
+                continue;
+            }
+            if image_value.is_none() {
+                first.push((addr, MemoryRecord::default(), 1));
+            }
+            let written = runtime.written_addrs.as_ref();
+            if Runtime::elides_final_record(written, image_value, addr, value) {
+                continue;
+            }
+            last.push((
+                addr,
+                MemoryRecord {
+                    value,
+                    shard,
+                    timestamp,
+                },
+                1,
+            ));
+        }
+        let mut program = program_memory_used
+            .iter()
+            .map(|(&addr, &(value, used))| {
+                (
+                    addr,
+                    MemoryRecord {
+                        value,
+                        shard: 0,
+                        timestamp: 0,
+                    },
+                    used,
+                )
+            })
+            .collect::<Vec<_>>();
+        program.sort_by_key(|&(addr, _, _)| addr);
+        MemoryRecords {
+            first,
+            last,
+            program,
+        }
+    }
+
+    fn flatten(entries: &[MemoryRecordEntry]) -> Vec<(u32, u32, u32, u32, u32)> {
+        entries
+            .iter()
+            .map(|(addr, record, used)| {
+                (*addr, record.value, record.shard, record.timestamp, *used)
+            })
+            .collect()
+    }
+
+    fn assert_same(records: &MemoryRecords, expected: &MemoryRecords) {
+        assert_eq!(flatten(&records.first), flatten(&expected.first));
+        assert_eq!(flatten(&records.last), flatten(&expected.last));
+        assert_eq!(flatten(&records.program), flatten(&expected.program));
+    }
+
+    /// A runtime whose final memory mixes untouched and accessed words of a memory image with
+    /// accessed and zeroed words outside of it, over several chunks.
+    fn random_runtime(seed: u64, sparse: bool) -> Runtime {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut image = BTreeMap::new();
+        for _ in 0..50_000 {
+            image.insert(rng.gen_range(0..1 << 20) * 4, rng.gen());
+        }
+        let mut program = Program::new(vec![], 0, 0);
+        program.memory_image = image.clone();
+        let mut runtime = Runtime::new(program);
+        runtime.set_sparse_memory_records(sparse);
+        for (&addr, &value) in image.iter() {
+            let entry = match rng.gen_range(0..3) {
+                0 => (value, 0, 0),
+                1 => (value, 1, rng.gen_range(1..1000)),
+                _ => (rng.gen(), rng.gen_range(1..4), rng.gen_range(1..1000)),
+            };
+            runtime.state.memory.insert(addr, entry);
+            if let Some(written) = &mut runtime.written_addrs {
+                if entry.0 != value || rng.gen_bool(0.1) {
+                    written.insert(addr);
+                }
+            }
+        }
+        for _ in 0..200_000 {
+            let addr = rng.gen_range(0..1 << 22) * 4;
+            let entry = if rng.gen_bool(0.2) {
+                (0, 0, 0)
+            } else {
+                (rng.gen(), rng.gen_range(1..4), rng.gen_range(1..1000))
+            };
+            runtime.state.memory.entry(addr).or_insert(entry);
+        }
+        runtime
+    }
+
+    #[test]
+    fn test_memory_records_match_reference() {
+        for (seed, sparse) in [(0, false), (1, true), (2, false)] {
+            let runtime = random_runtime(seed, sparse);
+            assert!(runtime.state.memory.len() > 2 * POSTPROCESS_CHUNK_SIZE);
+            let expected = reference_records(&runtime);
+            assert_same(&runtime.build_memory_records(true), &expected);
+            assert_same(&runtime.build_memory_records(false), &expected);
+        }
+    }
+
+    #[test]
+    fn test_postprocess_unchanged() {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.run();
+        let records = MemoryRecords {
+            first: runtime.record.first_memory_record.clone(),
+            last: runtime.record.last_memory_record.clone(),
+            program: runtime.record.program_memory_record.clone(),
+        };
+        assert_same(&records, &reference_records(&runtime));
+        assert_same(&runtime.build_memory_records(false), &records);
+    }
+}
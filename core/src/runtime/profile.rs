@@ -0,0 +1,445 @@
+//! Attribution of the cycles of an execution to the functions of the guest.
+//!
+//! Two sources are merged. The spans the guest marks with `cycle-tracker-start:` and
+//! `cycle-tracker-end:` give exact counts for the code they cover, and the program counter,
+//! sampled every few instructions, estimates where the rest of the cycles went. A sample taken
+//! while spans are open counts for each of them, and for no function otherwise, so that the
+//! estimate of a span can be compared with its exact count.
+use std::collections::{BTreeMap, HashMap};
+
+use nohash_hasher::BuildNoHashHasher;
+use serde::Serialize;
+
+use super::Runtime;
+
+/// The cycles of one function, or of one cycle tracker span, in a [`PerformanceReport`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FunctionCycles {
+    pub function: String,
+
+    /// The cycles spent in the spans of the cycle tracker with this name, including the spans
+    /// nested in them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exact_cycles: Option<u64>,
+
+    /// The cycles estimated from the samples.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampled_cycles: Option<u64>,
+
+    /// The extra cycles of the syscalls invoked from the function, which are counted by the clock
+    /// of the shard but not by `global_clk`.
+    pub syscall_cycles: u64,
+
+    /// The number of spans of the cycle tracker with this name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub call_count: Option<u64>,
+
+    /// The sampled estimate minus the exact count, for functions with both.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discrepancy: Option<i64>,
+}
+
+impl FunctionCycles {
+    /// The exact cycles if known, or else the sampled estimate.
+    pub fn cycles(&self) -> u64 {
+        self.exact_cycles.or(self.sampled_cycles).unwrap_or(0)
+    }
+}
+
+/// The totals of a [`PerformanceReport`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PerformanceTotals {
+    /// The cycles in the outermost spans of the cycle tracker.
+    pub exact_cycles: u64,
+
+    /// The estimated cycles outside of every span.
+    pub sampled_cycles: u64,
+
+    pub syscall_cycles: u64,
+
+    /// The number of instructions executed, which `exact_cycles + sampled_cycles` estimates.
+    pub global_clk: u64,
+
+    /// The bound on the error of the estimate: every stretch of instructions outside of the spans
+    /// is off by less than one sample interval.
+    pub tolerance: u64,
+}
+
+/// The cycles of an execution attributed to the functions of the guest, see
+/// [`Runtime::performance_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PerformanceReport {
+    pub sample_interval: u32,
+
+    /// The functions, sorted by decreasing cycles.
+    pub functions: Vec<FunctionCycles>,
+
+    pub totals: PerformanceTotals,
+}
+
+impl PerformanceReport {
+    pub fn builder(global_clk: u64, sample_interval: u32) -> PerformanceReportBuilder {
+        PerformanceReportBuilder {
+            global_clk,
+            sample_interval,
+            functions: BTreeMap::new(),
+            totals: PerformanceTotals::default(),
+            outer_spans: 0,
+        }
+    }
+
+    /// Whether the exact and sampled cycles add up to `global_clk` within the tolerance.
+    pub fn reconciles(&self) -> bool {
+        let totals = &self.totals;
+        (totals.exact_cycles + totals.sampled_cycles).abs_diff(totals.global_clk)
+            <= totals.tolerance
+    }
+
+    /// The report as JSON.
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).expect("failed to serialize the report")
+    }
+}
+
+/// Merges cycle tracker spans, samples and syscall cycles into a [`PerformanceReport`].
+#[derive(Debug, Clone)]
+pub struct PerformanceReportBuilder {
+    global_clk: u64,
+    sample_interval: u32,
+    functions: BTreeMap<String, FunctionCycles>,
+    totals: PerformanceTotals,
+    outer_spans: u64,
+}
+
+impl PerformanceReportBuilder {
+    fn function(&mut self, function: &str) -> &mut FunctionCycles {
+        self.functions
+            .entry(function.to_string())
+            .or_insert_with(|| FunctionCycles {
+                function: function.to_string(),
+                ..Default::default()
+            })
+    }
+
+    /// Add a span of the cycle tracker, nested in `depth` other spans.
+    pub fn exact_span(mut self, function: &str, cycles: u64, depth: u32) -> Self {
+        if depth == 0 {
+            self.totals.exact_cycles += cycles;
+            self.outer_spans += 1;
+        }
+        let entry = self.function(function);
+        *entry.exact_cycles.get_or_insert(0) += cycles;
+        *entry.call_count.get_or_insert(0) += 1;
+        self
+    }
+
+    /// Add `count` samples of `function`. Samples outside of every span count toward the totals.
+    pub fn samples(mut self, function: &str, count: u64, in_span: bool) -> Self {
+        let cycles = count * self.sample_interval as u64;
+        if !in_span {
+            self.totals.sampled_cycles += cycles;
+        }
+        *self.function(function).sampled_cycles.get_or_insert(0) += cycles;
+        self
+    }
+
+    /// Add extra cycles of syscalls invoked from `function`.
+    pub fn syscall_cycles(mut self, function: &str, cycles: u64) -> Self {
+        self.totals.syscall_cycles += cycles;
+        self.function(function).syscall_cycles += cycles;
+        self
+    }
+
+    pub fn build(self) -> PerformanceReport {
+        let mut functions = self
+            .functions
+            .into_values()
+            .map(|mut function| {
+                if let (Some(exact), Some(sampled)) =
+                    (function.exact_cycles, function.sampled_cycles)
+                {
+                    function.discrepancy = Some(sampled as i64 - exact as i64);
+                }
+                function
+            })
+            .collect::<Vec<_>>();
+        // The sort is stable, so functions with the same cycles stay sorted by name.
+        functions.sort_by_key(|function| std::cmp::Reverse(function.cycles()));
+        PerformanceReport {
+            sample_interval: self.sample_interval,
+            functions,
+            totals: PerformanceTotals {
+                global_clk: self.global_clk,
+                tolerance: (self.outer_spans + 1) * self.sample_interval as u64,
+                ..self.totals
+            },
+        }
+    }
+}
+
+/// The data [`Runtime::performance_report`] is built from.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Profiler {
+    pub(crate) interval: u32,
+
+    /// The closed spans of the cycle tracker, as their name, cycles and depth.
+    spans: Vec<(String, u64, u32)>,
+
+    /// The number of samples taken while each span of the cycle tracker was open.
+    span_samples: HashMap<String, u64>,
+
+    /// The number of samples at each pc taken outside of every span.
+    pc_samples: HashMap<u32, u64, BuildNoHashHasher<u32>>,
+
+    /// The extra cycles of the syscalls invoked at each pc.
+    syscall_cycles: HashMap<u32, u64, BuildNoHashHasher<u32>>,
+}
+
+impl Profiler {
+    pub(crate) fn new(interval: u32) -> Self {
+        Self {
+            interval,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the instruction at `global_clk` is sampled.
+    #[inline]
+    pub(crate) fn is_sample(&self, global_clk: u32) -> bool {
+        global_clk % self.interval == 0
+    }
+
+    pub(crate) fn record_sample<'a>(&mut self, pc: u32, open_spans: impl Iterator<Item = &'a str>) {
+        let mut in_span = false;
+        for span in open_spans {
+            in_span = true;
+            *self.span_samples.entry(span.to_string()).or_default() += 1;
+        }
+        if !in_span {
+            *self.pc_samples.entry(pc).or_default() += 1;
+        }
+    }
+
+    pub(crate) fn record_span(&mut self, name: &str, cycles: u32, depth: u32) {
+        self.spans.push((name.to_string(), cycles as u64, depth));
+    }
+
+    pub(crate) fn record_syscall(&mut self, pc: u32, extra_cycles: u32) {
+        if extra_cycles > 0 {
+            *self.syscall_cycles.entry(pc).or_default() += extra_cycles as u64;
+        }
+    }
+}
+
+impl Runtime {
+    /// Profile the execution from now on, sampling the program counter every `sample_interval`
+    /// instructions, see [`Runtime::performance_report`]. Unconstrained blocks are not profiled.
+    pub fn enable_profiling(&mut self, sample_interval: u32) {
+        assert!(sample_interval > 0, "the sample interval must not be zero");
+        self.profiler = Some(Profiler::new(sample_interval));
+    }
+
+    /// The cycles of the execution so far attributed to the functions of the guest, as described
+    /// in the [module documentation](self). Syscall cycles are attributed to the function
+    /// containing the `ecall`. Returns `None` if [`Runtime::enable_profiling`] was not called.
+    pub fn performance_report(&self) -> Option<PerformanceReport> {
+        let profiler = self.profiler.as_ref()?;
+        let mut builder =
+            PerformanceReport::builder(self.state.global_clk as u64, profiler.interval);
+        for (name, cycles, depth) in profiler.spans.iter() {
+            builder = builder.exact_span(name, *cycles, *depth);
+        }
+        for (name, count) in profiler.span_samples.iter() {
+            builder = builder.samples(name, *count, true);
+        }
+        for (pc, count) in profiler.pc_samples.iter() {
+            builder = builder.samples(&self.function_name(*pc), *count, false);
+        }
+        for (pc, cycles) in profiler.syscall_cycles.iter() {
+            builder = builder.syscall_cycles(&self.function_name(*pc), *cycles);
+        }
+        Some(builder.build())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::runtime::{Program, Symbol, SymbolTable, Syscall, SyscallCode, SyscallContext};
+    use crate::utils::asm::assemble;
+
+    /// A syscall taking 40 extra cycles.
+    struct SlowSyscall;
+
+    impl Syscall for SlowSyscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            ctx.advance(40);
+            0
+        }
+
+        fn num_extra_cycles(&self) -> u32 {
+            40
+        }
+    }
+
+    const STRINGS: u32 = 0x10000;
+
+    const MARKERS: [&str; 4] = [
+        "cycle-tracker-start: f\n",
+        "cycle-tracker-end: f\n",
+        "cycle-tracker-start: g\n",
+        "cycle-tracker-end: g\n",
+    ];
+
+    /// `main` calls `f` and `g` in cycle tracker spans, and then the uninstrumented `hot`, which
+    /// runs the longest. `g` invokes a slow syscall.
+    fn profiled_program() -> Program {
+        let write = |marker: usize| {
+            format!(
+                "
+                    li   t0, {}
+                    li   a0, 1
+                    li   a1, {}
+                    li   a2, {}
+                    ecall
+                ",
+                SyscallCode::WRITE as u32,
+                STRINGS + 64 * marker as u32,
+                MARKERS[marker].len()
+            )
+        };
+        let source = format!(
+            "
+            main:   {}
+                    call f
+                    {}
+                    {}
+                    call g
+                    {}
+                    call hot
+                    j    done
+            f:      li   t1, 200
+            f_loop: addi t1, t1, -1
+                    bne  t1, zero, f_loop
+                    ret
+            g:      li   t0, 113
+                    ecall
+                    li   t1, 100
+            g_loop: addi t1, t1, -1
+                    bne  t1, zero, g_loop
+                    ret
+            hot:    li   t1, 1000
+            h_loop: addi a3, a3, 3
+                    addi t1, t1, -1
+                    bne  t1, zero, h_loop
+                    ret
+            done:   nop
+            ",
+            write(0),
+            write(1),
+            write(2),
+            write(3)
+        );
+        let mut program = assemble(&source, 0).unwrap();
+        for (i, marker) in MARKERS.iter().enumerate() {
+            for (j, word) in marker.as_bytes().chunks(4).enumerate() {
+                let mut bytes = [0; 4];
+                bytes[..word.len()].copy_from_slice(word);
+                let addr = STRINGS + 64 * i as u32 + 4 * j as u32;
+                program.memory_image.insert(addr, u32::from_le_bytes(bytes));
+            }
+        }
+        let len = program.instructions.len() as u32 * 4;
+        // The functions start after `main`, which is 4 markers of 5 instructions, 3 calls and a
+        // jump.
+        let main_len = (4 * 5 + 4) * 4;
+        program.symbols = SymbolTable::new(vec![
+            Symbol::new("main", 0, main_len),
+            Symbol::new("f", main_len, 16),
+            Symbol::new("g", main_len + 16, 24),
+            Symbol::new("hot", main_len + 40, 20),
+            Symbol::new("done", len - 4, 4),
+        ]);
+        program
+    }
+
+    fn function<'a>(report: &'a PerformanceReport, name: &str) -> &'a FunctionCycles {
+        report
+            .functions
+            .iter()
+            .find(|function| function.function == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_performance_report() {
+        let mut runtime = Runtime::new(profiled_program());
+        runtime
+            .syscall_map
+            .insert(SyscallCode::WRITE_CHANNEL, Arc::new(SlowSyscall));
+        runtime.enable_profiling(10);
+        runtime.run();
+        let report = runtime.performance_report().unwrap();
+
+        assert!(report.reconciles());
+        assert_eq!(report.totals.global_clk, runtime.state.global_clk as u64);
+        assert_eq!(report.totals.tolerance, 30);
+        assert_eq!(report.totals.syscall_cycles, 40);
+
+        // The tracked functions have exact counts, checked against their samples.
+        for (name, loop_cycles) in [("f", 400), ("g", 200)] {
+            let tracked = function(&report, name);
+            let exact = tracked.exact_cycles.unwrap();
+            assert!(exact > loop_cycles && exact < loop_cycles + 20);
+            assert_eq!(tracked.call_count, Some(1));
+            assert!(tracked.discrepancy.unwrap().abs() <= 10);
+            assert_eq!(tracked.cycles(), exact);
+        }
+        assert_eq!(function(&report, "g").syscall_cycles, 40);
+
+        // The hot loop is only sampled, and is the most expensive function.
+        let hot = function(&report, "hot");
+        assert_eq!(report.functions[0].function, "hot");
+        assert_eq!(hot.exact_cycles, None);
+        assert_eq!(hot.call_count, None);
+        assert!(hot.sampled_cycles.unwrap().abs_diff(3002) <= 10);
+
+        let json: Value = serde_json::from_slice(&report.to_json()).unwrap();
+        let hot = json["functions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|function| function["function"] == "hot")
+            .unwrap();
+        assert!(hot.get("exact_cycles").is_none());
+        assert_eq!(json["totals"]["syscall_cycles"], 40);
+    }
+
+    #[test]
+    fn test_report_builder() {
+        let report = PerformanceReport::builder(100, 10)
+            .exact_span("f", 40, 0)
+            .exact_span("inner", 20, 1)
+            .samples("f", 5, true)
+            .samples("main", 6, false)
+            .build();
+        assert_eq!(report.totals.exact_cycles, 40);
+        assert_eq!(report.totals.sampled_cycles, 60);
+        assert_eq!(report.totals.tolerance, 20);
+        assert!(report.reconciles());
+        assert_eq!(function(&report, "f").discrepancy, Some(10));
+        assert_eq!(function(&report, "inner").discrepancy, None);
+        assert_eq!(
+            report
+                .functions
+                .iter()
+                .map(|function| function.function.as_str())
+                .collect::<Vec<_>>(),
+            vec!["main", "f", "inner"]
+        );
+    }
+}
@@ -1,9 +1,20 @@
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::ops::Range;
 
-use super::Instruction;
+use super::{
+    Extensions, Instruction, InstructionError, LinkedBlob, SymbolTable, MAX_REGISTER_ADDR,
+};
+use crate::disassembler::GuestMetadata;
+
+/// The lowest address of memory guests may access, unless their text or memory image starts
+/// below it. The words from the registers up to it are reserved, so that dereferencing a null
+/// pointer faults instead of reading zeroes.
+pub const DEFAULT_MIN_DATA_ADDR: u32 = 0x1000;
 
 /// A program that can be executed by the VM.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Program {
     /// The instructions of the program.
     pub instructions: Vec<Instruction>,
@@ -16,4 +27,197 @@ pub struct Program {
 
     /// The initial memory image, useful for global constants.
     pub memory_image: BTreeMap<u32, u32>,
+
+    /// The function symbols of the ELF the program was disassembled from, used to symbolize
+    /// backtraces.
+    pub symbols: SymbolTable,
+
+    /// Byte ranges the guest may read but not write, sorted and disjoint, see
+    /// [`Program::mark_readonly`].
+    #[serde(default)]
+    pub readonly: Vec<Range<u32>>,
+
+    /// The data linked into the memory image after the program was built, see
+    /// [`Program::link_blob`].
+    #[serde(default)]
+    pub linked: Vec<LinkedBlob>,
+
+    /// The build information of the guest, read from the metadata section of its ELF.
+    #[serde(default)]
+    pub metadata: Option<GuestMetadata>,
+
+    /// The extensions beyond RV32IM the program may use.
+    #[serde(default)]
+    pub extensions: Extensions,
+
+    /// The lowest address of memory the guest may access, in place of the one derived from its
+    /// layout, see [`Program::min_data_addr`].
+    #[serde(default)]
+    pub data_floor: Option<u32>,
+}
+
+impl Program {
+    /// A hash of the instructions, the start and base addresses, the memory image, the read-only
+    /// ranges, the linked data, the guest metadata, the extensions and the data floor of the
+    /// program.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        bincode::serialize_into(
+            &mut hasher,
+            &(
+                &self.instructions,
+                self.pc_start,
+                self.pc_base,
+                &self.memory_image,
+                &self.readonly,
+                &self.linked,
+                &self.metadata,
+                self.extensions,
+                self.data_floor,
+            ),
+        )
+        .expect("failed to serialize the program");
+        *hasher.finalize().as_bytes()
+    }
+
+    /// The build information of the guest, if its ELF has a `.sp1_meta` section, see
+    /// [`crate::disassembler::inject_metadata`].
+    pub fn metadata(&self) -> Option<&GuestMetadata> {
+        self.metadata.as_ref()
+    }
+
+    /// The address right after the last instruction, where execution finishes when the guest
+    /// falls through to it.
+    pub fn text_end(&self) -> u32 {
+        self.pc_base
+            .wrapping_add(self.instructions.len() as u32 * 4)
+    }
+
+    /// The lowest address of memory the guest may access: the `data_floor` if set, and otherwise
+    /// [`DEFAULT_MIN_DATA_ADDR`] or the start of the text if lower. It is never above the memory
+    /// image, nor below the first word after the registers. Loads, stores and syscalls accessing
+    /// the words in between stop execution with `ExecutionError::ReservedAddress`.
+    pub fn min_data_addr(&self) -> u32 {
+        let floor = self
+            .data_floor
+            .unwrap_or_else(|| DEFAULT_MIN_DATA_ADDR.min(self.pc_base));
+        let image = self.memory_image.keys().next().copied().unwrap_or(u32::MAX);
+        (floor.min(image) & !3).max(MAX_REGISTER_ADDR + 4)
+    }
+
+    /// Forbid the guest from writing to the bytes in `range`, typically part of the memory image
+    /// holding trusted data. Writes to a word overlapping a read-only range, including `sb` and
+    /// `sh` to its other bytes, stop execution with `ExecutionError::WriteToReadOnly`.
+    pub fn mark_readonly(&mut self, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+        let (mut start, mut end) = (range.start, range.end);
+        // Merge the new range with every range it overlaps or touches.
+        let first = self.readonly.partition_point(|r| r.end < start);
+        let last = self.readonly.partition_point(|r| r.start <= end);
+        if first < last {
+            start = start.min(self.readonly[first].start);
+            end = end.max(self.readonly[last - 1].end);
+        }
+        self.readonly.splice(first..last, [start..end]);
+    }
+
+    /// Whether the word at the aligned address `addr` overlaps a read-only range.
+    #[inline]
+    pub fn is_readonly(&self, addr: u32) -> bool {
+        if self.readonly.is_empty() {
+            return false;
+        }
+        let index = self.readonly.partition_point(|r| r.end <= addr);
+        self.readonly
+            .get(index)
+            .is_some_and(|r| r.start < addr.saturating_add(4))
+    }
+
+    /// Validates every instruction of the program, collecting all of the malformed ones and those
+    /// of an extension the program does not enable.
+    pub fn validate(&self) -> Result<(), ProgramValidationError> {
+        let invalid = self
+            .instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, instruction)| {
+                instruction
+                    .validate()
+                    .and_then(|()| {
+                        if self.extensions.allows(instruction.opcode) {
+                            Ok(())
+                        } else {
+                            Err(InstructionError::ExtensionDisabled {
+                                opcode: instruction.opcode,
+                            })
+                        }
+                    })
+                    .err()
+                    .map(|error| InvalidInstruction {
+                        index,
+                        pc: self.pc_base.wrapping_add(index as u32 * 4),
+                        instruction: *instruction,
+                        error,
+                    })
+            })
+            .collect::<Vec<_>>();
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(ProgramValidationError {
+                invalid,
+                metadata: self.metadata.clone(),
+            })
+        }
+    }
+}
+
+/// A malformed instruction found while validating a program.
+#[derive(Debug, Clone, Copy)]
+pub struct InvalidInstruction {
+    /// The index of the instruction in the program.
+    pub index: usize,
+
+    /// The program counter of the instruction.
+    pub pc: u32,
+
+    /// The instruction itself.
+    pub instruction: Instruction,
+
+    /// The reason the instruction is malformed.
+    pub error: InstructionError,
 }
+
+/// The error returned by `Program::validate`, listing every malformed instruction.
+#[derive(Debug, Clone)]
+pub struct ProgramValidationError {
+    pub invalid: Vec<InvalidInstruction>,
+
+    /// The build information of the guest, to tell which build is malformed.
+    pub metadata: Option<GuestMetadata>,
+}
+
+impl Display for ProgramValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "program contains {} invalid instruction(s):",
+            self.invalid.len()
+        )?;
+        if let Some(metadata) = &self.metadata {
+            writeln!(f, "  guest: {}", metadata)?;
+        }
+        for invalid in self.invalid.iter() {
+            writeln!(
+                f,
+                "  [{}] pc=0x{:x} {:?}: {}",
+                invalid.index, invalid.pc, invalid.instruction, invalid.error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ProgramValidationError {}
@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use super::{ExecutionCheckpoint, ExecutionRecord};
+
+/// How the events of a shard were produced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShardProvenance {
+    /// Executed by the runtime from the start of the program.
+    #[default]
+    Executed,
+
+    /// Executed by a runtime resumed from a checkpoint, including the shard the checkpoint was
+    /// taken in.
+    ResumedFromCheckpoint,
+
+    /// Rebuilt from a stream of event frames, see [`ExecutionRecord::from_event_frames`].
+    Replayed,
+}
+
+impl Display for ShardProvenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ShardProvenance::Executed => write!(f, "executed"),
+            ShardProvenance::ResumedFromCheckpoint => write!(f, "resumed from a checkpoint"),
+            ShardProvenance::Replayed => write!(f, "replayed"),
+        }
+    }
+}
+
+/// Where a checkpoint was taken, which identifies it among the checkpoints of an execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CheckpointId {
+    /// The shard the checkpoint was taken in.
+    pub shard: u32,
+
+    /// The number of instructions executed before the checkpoint.
+    pub global_clk: u32,
+}
+
+impl ExecutionCheckpoint {
+    pub fn id(&self) -> CheckpointId {
+        CheckpointId {
+            shard: self.state.current_shard,
+            global_clk: self.state.global_clk,
+        }
+    }
+}
+
+/// How the shards of a record were produced, see [`ExecutionRecord::provenance`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordProvenance {
+    /// The provenance of each shard with events, by shard index.
+    pub shards: BTreeMap<u32, ShardProvenance>,
+
+    /// The checkpoints execution was resumed from, in order.
+    pub checkpoints: Vec<CheckpointId>,
+
+    /// The identifiers of the event streams replayed into the record, in order.
+    pub replays: Vec<u64>,
+}
+
+impl RecordProvenance {
+    /// The provenance of `shard`, if it has events.
+    pub fn shard(&self, shard: u32) -> Option<ShardProvenance> {
+        self.shards.get(&shard).copied()
+    }
+
+    /// The provenance of `shard` alone, with the identifiers of the whole record.
+    pub(crate) fn restricted_to(&self, shard: u32) -> Self {
+        Self {
+            shards: self
+                .shards
+                .get_key_value(&shard)
+                .map(|(&k, &v)| (k, v))
+                .into_iter()
+                .collect(),
+            checkpoints: self.checkpoints.clone(),
+            replays: self.replays.clone(),
+        }
+    }
+
+    /// The first shard tagged differently in `self` and `other`.
+    pub fn conflict(&self, other: &Self) -> Option<ProvenanceConflict> {
+        self.shards
+            .iter()
+            .find_map(|(&shard, &left)| match other.shard(shard) {
+                Some(right) if right != left => Some(ProvenanceConflict { shard, left, right }),
+                _ => None,
+            })
+    }
+
+    /// Add the tags and identifiers of `other`, keeping the tags of `self` for shards in both.
+    pub(crate) fn absorb(&mut self, other: Self) {
+        for (shard, provenance) in other.shards {
+            self.shards.entry(shard).or_insert(provenance);
+        }
+        for checkpoint in other.checkpoints {
+            if !self.checkpoints.contains(&checkpoint) {
+                self.checkpoints.push(checkpoint);
+            }
+        }
+        for replay in other.replays {
+            if !self.replays.contains(&replay) {
+                self.replays.push(replay);
+            }
+        }
+    }
+}
+
+/// A shard produced differently in two records given to [`ExecutionRecord::merge`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProvenanceConflict {
+    pub shard: u32,
+
+    /// The provenance of the shard in the record merged into.
+    pub left: ShardProvenance,
+
+    /// The provenance of the shard in the record merged from.
+    pub right: ShardProvenance,
+}
+
+impl Display for ProvenanceConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "shard {} was {} in one record but {} in the other",
+            self.shard, self.left, self.right
+        )
+    }
+}
+
+impl std::error::Error for ProvenanceConflict {}
+
+impl ExecutionRecord {
+    /// How the shards of the record were produced, and the checkpoints and replays involved.
+    pub fn provenance(&self) -> &RecordProvenance {
+        &self.provenance
+    }
+
+    /// Like [`ExecutionRecord::append`], but refusing to merge records in which a shard was
+    /// produced differently, unless `allow_mixed_provenance`. The records are left untouched on
+    /// error. With `allow_mixed_provenance`, shards keep the provenance they have in `self`.
+    pub fn merge(
+        &mut self,
+        other: &mut ExecutionRecord,
+        allow_mixed_provenance: bool,
+    ) -> Result<(), ProvenanceConflict> {
+        if !allow_mixed_provenance {
+            if let Some(conflict) = self.provenance.conflict(&other.provenance) {
+                return Err(conflict);
+            }
+        }
+        self.append(other);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::{Runtime, ShardingConfig};
+
+    /// Executes fibonacci in shards of 256 cycles, resuming it from a checkpoint taken in the
+    /// middle of the third shard.
+    fn resumed_runtime() -> (Runtime, CheckpointId) {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.shard_size = 256;
+        runtime.initialize();
+        while runtime.state.global_clk < 2 * 256 / 4 + 10 {
+            runtime.step().unwrap();
+        }
+        let checkpoint = runtime.checkpoint();
+        assert_eq!(checkpoint.id().shard, 3);
+
+        let mut resumed = Runtime::new(fibonacci_program());
+        resumed.shard_size = 256;
+        resumed.restore(&checkpoint, &runtime.record);
+        while !resumed.is_done() {
+            resumed.step().unwrap();
+        }
+        resumed.finalize();
+        (resumed, checkpoint.id())
+    }
+
+    #[test]
+    fn test_resumed_shards() {
+        let (runtime, checkpoint) = resumed_runtime();
+        let provenance = runtime.record.provenance();
+        assert_eq!(provenance.checkpoints, vec![checkpoint]);
+        assert!(provenance.replays.is_empty());
+
+        let last_shard = runtime.record.cpu_events.last().unwrap().shard;
+        assert!(last_shard > 4);
+        for shard in 1..=last_shard {
+            let expected = if shard < checkpoint.shard {
+                ShardProvenance::Executed
+            } else {
+                ShardProvenance::ResumedFromCheckpoint
+            };
+            assert_eq!(provenance.shard(shard), Some(expected), "shard {}", shard);
+        }
+        assert_eq!(provenance.shard(last_shard + 1), None);
+
+        // Each sharded record keeps the tag of its own shard.
+        let config = ShardingConfig {
+            shard_size: runtime.shard_size as usize / 4,
+            ..Default::default()
+        };
+        for shard in runtime.record.clone().shard(&config) {
+            let provenance = shard.provenance();
+            assert_eq!(
+                provenance.shard(shard.index),
+                runtime.record.provenance().shard(shard.index)
+            );
+            assert_eq!(provenance.checkpoints, vec![checkpoint]);
+        }
+
+        // Executing from the start tags every shard as executed.
+        let mut executed = Runtime::new(fibonacci_program());
+        executed.shard_size = 256;
+        executed.run();
+        let provenance = executed.record.provenance();
+        assert!(provenance.checkpoints.is_empty());
+        assert_eq!(provenance.shards.len(), last_shard as usize);
+        assert!(provenance
+            .shards
+            .values()
+            .all(|&provenance| provenance == ShardProvenance::Executed));
+    }
+
+    #[test]
+    fn test_merge_mixed_provenance() {
+        let tagged = |provenance| {
+            let mut record = ExecutionRecord::default();
+            record.index = 1;
+            record.provenance.shards.insert(1, provenance);
+            record
+        };
+
+        let mut record = tagged(ShardProvenance::Executed);
+        let mut other = tagged(ShardProvenance::ResumedFromCheckpoint);
+        other.provenance.checkpoints.push(CheckpointId {
+            shard: 1,
+            global_clk: 10,
+        });
+        let conflict = record.merge(&mut other, false).unwrap_err();
+        assert_eq!(
+            conflict,
+            ProvenanceConflict {
+                shard: 1,
+                left: ShardProvenance::Executed,
+                right: ShardProvenance::ResumedFromCheckpoint,
+            }
+        );
+        assert_eq!(
+            conflict.to_string(),
+            "shard 1 was executed in one record but resumed from a checkpoint in the other"
+        );
+        assert!(record.provenance().checkpoints.is_empty());
+
+        record.merge(&mut other, true).unwrap();
+        assert_eq!(
+            record.provenance().shard(1),
+            Some(ShardProvenance::Executed)
+        );
+        assert_eq!(record.provenance().checkpoints.len(), 1);
+
+        let mut same = tagged(ShardProvenance::Executed);
+        record.merge(&mut same, false).unwrap();
+    }
+
+    #[test]
+    fn test_provenance_round_trip() {
+        let (mut runtime, checkpoint) = resumed_runtime();
+        runtime.record.provenance.replays.push(7);
+        let decoded = ExecutionRecord::try_from_bytes(&runtime.record.to_bytes()).unwrap();
+        assert_eq!(decoded.provenance(), runtime.record.provenance());
+        assert_eq!(decoded.provenance().checkpoints, vec![checkpoint]);
+        assert_eq!(decoded.provenance().replays, vec![7]);
+    }
+}
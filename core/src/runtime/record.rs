@@ -1,12 +1,18 @@
+use core::fmt::{Display, Formatter};
 use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::hash::BuildHasher;
 use std::sync::Arc;
 
 use super::program::Program;
-use super::Opcode;
-use crate::alu::AluEvent;
+use super::{
+    AccessPosition, EventArena, EventIndices, GuestAssertion, Opcode, RecordFilter,
+    RecordProvenance, ShardBoundary,
+};
+use crate::alu::{AluEvent, BitmanipEvent, DivRemEvent, MulEvent};
 use crate::bytes::{ByteLookupEvent, ByteOpcode};
-use crate::cpu::{CpuEvent, MemoryRecordEnum};
+use crate::cpu::{AuipcEvent, CpuEvent, MemoryReadRecord, MemoryRecordEnum};
 use crate::field::event::FieldEvent;
 use crate::runtime::MemoryRecord;
 use crate::syscall::precompiles::blake3::Blake3CompressInnerEvent;
@@ -15,29 +21,37 @@ use crate::syscall::precompiles::k256::K256DecompressEvent;
 use crate::syscall::precompiles::keccak256::KeccakPermuteEvent;
 use crate::syscall::precompiles::sha256::{ShaCompressEvent, ShaExtendEvent};
 use crate::syscall::precompiles::{ECAddEvent, ECDoubleEvent};
+use crate::syscall::Mem64Event;
 use crate::utils::env;
 
 /// A record of the execution of a program. Contains event data for everything that happened during
 /// the execution of the shard.
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct ExecutionRecord {
     /// The index of the shard.
     pub index: u32,
 
     /// The program.
+    #[serde(with = "crate::utils::serialization::arc")]
     pub program: Arc<Program>,
 
-    /// A trace of the CPU events which get emitted during execution.
-    pub cpu_events: Vec<CpuEvent>,
+    /// A trace of the CPU events which get emitted during execution, one per cycle, stored in
+    /// chunks.
+    #[serde(with = "crate::utils::serialization::cpu_events")]
+    pub cpu_events: EventArena<CpuEvent>,
+
+    /// A trace of the AUIPC events, one per AUIPC CPU event.
+    pub auipc_events: Vec<AuipcEvent>,
 
     /// Multiplicity counts for each instruction in the program.
+    #[serde(with = "crate::utils::serialization::hash_map")]
     pub instruction_counts: HashMap<u32, usize>,
 
     /// A trace of the ADD, and ADDI events.
     pub add_events: Vec<AluEvent>,
 
     /// A trace of the MUL events.
-    pub mul_events: Vec<AluEvent>,
+    pub mul_events: Vec<MulEvent>,
 
     /// A trace of the SUB events.
     pub sub_events: Vec<AluEvent>,
@@ -52,13 +66,17 @@ pub struct ExecutionRecord {
     pub shift_right_events: Vec<AluEvent>,
 
     /// A trace of the DIV, DIVU, REM, and REMU events.
-    pub divrem_events: Vec<AluEvent>,
+    pub divrem_events: Vec<DivRemEvent>,
 
     /// A trace of the SLT, SLTI, SLTU, and SLTIU events.
     pub lt_events: Vec<AluEvent>,
 
-    /// A trace of the byte lookups needed.
-    pub byte_lookups: BTreeMap<ByteLookupEvent, usize>,
+    /// A trace of the ANDN, ORN, XNOR, CLZ, CTZ, CPOP, MIN, MAX, MINU and MAXU events.
+    pub bitmanip_events: Vec<BitmanipEvent>,
+
+    /// The multiplicity of each byte lookup needed, by the index of the shard that emitted it.
+    #[serde(with = "crate::utils::serialization::nested_hash_map")]
+    pub byte_lookups: BTreeMap<u32, HashMap<ByteLookupEvent, usize>>,
 
     /// A trace of field LTU events.
     pub field_events: Vec<FieldEvent>,
@@ -81,11 +99,63 @@ pub struct ExecutionRecord {
 
     pub blake3_compress_inner_events: Vec<Blake3CompressInnerEvent>,
 
+    /// The accesses of the `LOAD64` and `STORE64` syscalls.
+    pub mem64_events: Vec<Mem64Event>,
+
     /// Information needed for global chips. This shouldn't really be here but for legacy reasons,
     /// we keep this information in this struct for now.
     pub first_memory_record: Vec<(u32, MemoryRecord, u32)>,
     pub last_memory_record: Vec<(u32, MemoryRecord, u32)>,
     pub program_memory_record: Vec<(u32, MemoryRecord, u32)>,
+
+    /// Where execution ended, set once it finishes.
+    #[serde(default)]
+    pub final_shard: Option<ShardExtent>,
+
+    /// The assertion of the guest that failed and halted it, if any.
+    #[serde(default)]
+    pub guest_assertion: Option<GuestAssertion>,
+
+    /// The digest of the public values of the guest, set once execution finishes, see
+    /// [`crate::runtime::Runtime::public_values_digest`].
+    #[serde(default)]
+    pub public_values_digest: Option<[u8; 32]>,
+
+    /// The bytes the guest wrote to each output channel, channel zero included, set once
+    /// execution finishes, see [`crate::runtime::Runtime::output_channel`].
+    #[serde(default)]
+    pub output_channels: BTreeMap<u32, Vec<u8>>,
+
+    /// The state of the machine where each shard starts, see
+    /// [`ExecutionRecord::shard_boundaries`].
+    #[serde(default)]
+    pub shard_boundaries: Vec<ShardBoundary>,
+
+    /// How each shard was produced, see [`ExecutionRecord::provenance`].
+    #[serde(default)]
+    pub provenance: RecordProvenance,
+
+    /// Which classes of events are recorded, set by the runtime.
+    #[serde(skip)]
+    pub filter: RecordFilter,
+
+    /// The events of each shard, see [`ExecutionRecord::build_indices`].
+    #[serde(skip)]
+    pub(crate) indices: Option<EventIndices>,
+
+    /// Whether the record was mutated through its methods since the indices were built. Events
+    /// pushed to the vectors directly are detected by their lengths.
+    #[serde(skip)]
+    pub(crate) indices_dirty: bool,
+}
+
+/// How far execution got into a shard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardExtent {
+    pub shard: u32,
+
+    /// The clock after the last instruction executed in the shard.
+    pub clk: u32,
 }
 
 pub struct ShardingConfig {
@@ -102,6 +172,16 @@ pub struct ShardingConfig {
     pub keccak_len: usize,
     pub weierstrass_add_len: usize,
     pub weierstrass_double_len: usize,
+
+    /// Whether the runtime ends every shard but the last at a power of two of cycles, the
+    /// largest not above the shard size, so that no row of the CPU trace is padding. See
+    /// [`Runtime::set_sharding_config`](super::Runtime::set_sharding_config).
+    pub round_to_pow2: bool,
+
+    /// If set, a final shard that would take less than this fraction of the shard size is merged
+    /// into the previous one, as long as the events of the merged shard stay within the lengths
+    /// above.
+    pub merge_small_final_shard: Option<f64>,
 }
 
 impl ShardingConfig {
@@ -127,6 +207,8 @@ impl Default for ShardingConfig {
             keccak_len: shard_size,
             weierstrass_add_len: shard_size,
             weierstrass_double_len: shard_size,
+            round_to_pow2: false,
+            merge_small_final_shard: None,
         }
     }
 }
@@ -151,6 +233,100 @@ pub struct ShardStats {
     pub nb_weierstrass_add_events: usize,
     pub nb_weierstrass_double_events: usize,
     pub nb_k256_decompress_events: usize,
+    pub nb_blake3_compress_inner_events: usize,
+    pub nb_mem64_events: usize,
+}
+
+impl ShardStats {
+    /// The number of events of each event class, keyed by a short class name.
+    pub fn event_counts(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("cpu", self.nb_cpu_events),
+            ("add", self.nb_add_events),
+            ("sub", self.nb_sub_events),
+            ("mul", self.nb_mul_events),
+            ("divrem", self.nb_divrem_events),
+            ("shift_left", self.nb_shift_left_events),
+            ("shift_right", self.nb_shift_right_events),
+            ("lt", self.nb_lt_events),
+            ("bitwise", self.nb_bitwise_events),
+            ("field", self.nb_field_events),
+            ("sha_extend", self.nb_sha_extend_events),
+            ("sha_compress", self.nb_sha_compress_events),
+            ("keccak_permute", self.nb_keccak_permute_events),
+            ("ed_add", self.nb_ed_add_events),
+            ("ed_decompress", self.nb_ed_decompress_events),
+            ("weierstrass_add", self.nb_weierstrass_add_events),
+            ("weierstrass_double", self.nb_weierstrass_double_events),
+            ("k256_decompress", self.nb_k256_decompress_events),
+            (
+                "blake3_compress_inner",
+                self.nb_blake3_compress_inner_events,
+            ),
+            ("mem64", self.nb_mem64_events),
+        ]
+    }
+}
+
+/// The event class name used for byte lookups in a [`PaddingReport`].
+pub const BYTE_LOOKUP_CLASS: &str = "byte";
+
+/// Tables whose count exceeds the previous power of two by at most this ratio get a suggestion.
+const PADDING_SUGGESTION_RATIO: f64 = 1.25;
+
+/// The number of events of one class in one shard, and the height its table gets padded to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaddingEntry {
+    pub shard: u32,
+    pub class: &'static str,
+    pub count: usize,
+    pub padded: usize,
+}
+
+impl PaddingEntry {
+    /// How far `count` is past the previous power of two, or `None` if it does not exceed it.
+    pub fn overshoot(&self) -> Option<f64> {
+        let lower = self.padded / 2;
+        if lower == 0 || self.count <= lower {
+            return None;
+        }
+        Some(self.count as f64 / lower as f64)
+    }
+}
+
+/// A per-shard breakdown of how close each table is to the next power of two.
+///
+/// Byte lookups are reported as the total multiplicity of all lookups, since the byte table has a
+/// fixed height and every lookup contributes to its multiplicity columns.
+#[derive(Debug, Clone, Default)]
+pub struct PaddingReport {
+    pub entries: Vec<PaddingEntry>,
+    pub suggestions: Vec<String>,
+}
+
+impl PaddingReport {
+    /// Returns the entry for the given shard and event class, if that class had any events.
+    pub fn entry(&self, shard: u32, class: &str) -> Option<&PaddingEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.shard == shard && e.class == class)
+    }
+}
+
+impl std::fmt::Display for PaddingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(
+                f,
+                "shard {} {}: {} (padded to {})",
+                entry.shard, entry.class, entry.count, entry.padded
+            )?;
+        }
+        for suggestion in self.suggestions.iter() {
+            writeln!(f, "{}", suggestion)?;
+        }
+        Ok(())
+    }
 }
 
 impl ExecutionRecord {
@@ -162,21 +338,146 @@ impl ExecutionRecord {
         }
     }
 
+    /// Reset to an empty record for `program`, as created by `Runtime::new`, keeping the allocated
+    /// capacity of the event vectors and the chunks of the CPU events.
+    pub(crate) fn reset(&mut self, program: Arc<Program>) {
+        let Self {
+            index,
+            program: program_field,
+            cpu_events,
+            auipc_events,
+            instruction_counts,
+            add_events,
+            mul_events,
+            sub_events,
+            bitwise_events,
+            shift_left_events,
+            shift_right_events,
+            divrem_events,
+            lt_events,
+            bitmanip_events,
+            byte_lookups,
+            field_events,
+            sha_extend_events,
+            sha_compress_events,
+            keccak_permute_events,
+            ed_add_events,
+            ed_decompress_events,
+            weierstrass_add_events,
+            weierstrass_double_events,
+            k256_decompress_events,
+            blake3_compress_inner_events,
+            mem64_events,
+            first_memory_record,
+            last_memory_record,
+            program_memory_record,
+            final_shard,
+            guest_assertion,
+            public_values_digest,
+            output_channels,
+            shard_boundaries,
+            provenance,
+            filter: _,
+            indices,
+            indices_dirty,
+        } = self;
+        *index = 0;
+        *program_field = program;
+        cpu_events.clear();
+        auipc_events.clear();
+        instruction_counts.clear();
+        add_events.clear();
+        mul_events.clear();
+        sub_events.clear();
+        bitwise_events.clear();
+        shift_left_events.clear();
+        shift_right_events.clear();
+        divrem_events.clear();
+        lt_events.clear();
+        bitmanip_events.clear();
+        byte_lookups.clear();
+        field_events.clear();
+        sha_extend_events.clear();
+        sha_compress_events.clear();
+        keccak_permute_events.clear();
+        ed_add_events.clear();
+        ed_decompress_events.clear();
+        weierstrass_add_events.clear();
+        weierstrass_double_events.clear();
+        k256_decompress_events.clear();
+        blake3_compress_inner_events.clear();
+        mem64_events.clear();
+        first_memory_record.clear();
+        last_memory_record.clear();
+        program_memory_record.clear();
+        *final_shard = None;
+        *guest_assertion = None;
+        *public_values_digest = None;
+        output_channels.clear();
+        shard_boundaries.clear();
+        *provenance = RecordProvenance::default();
+        *indices = None;
+        *indices_dirty = false;
+    }
+
+    /// Split the record into the records of its shards.
+    ///
+    /// The CPU events stay in the shards the runtime executed them in, so that the boundaries it
+    /// placed under [`Runtime::set_sharding_config`](super::Runtime::set_sharding_config) are
+    /// kept. A shard with more than `config.shard_size()` CPU events is split further, except a
+    /// final shard merged under [`ShardingConfig::merge_small_final_shard`].
     pub fn shard(self, config: &ShardingConfig) -> Vec<Self> {
-        // Make the shard vector by splitting CPU and program events.
-        let mut shards = self
-            .cpu_events
-            .chunks(config.shard_size())
-            .enumerate()
-            .map(|(i, chunk)| {
-                let mut shard = ExecutionRecord::default();
-                shard.index = (i + 1) as u32;
-                shard.program = self.program.clone();
-                shard.cpu_events = chunk.to_vec();
-
-                shard
-            })
-            .collect::<Vec<_>>();
+        let shard_size = config.shard_size();
+        let merged_len = config
+            .merge_small_final_shard
+            .map_or(shard_size, |threshold| {
+                shard_size + (shard_size as f64 * threshold) as usize
+            });
+
+        // Make the shard vector by splitting the CPU events.
+        let mut shards = Vec::new();
+        let len = self.cpu_events.len();
+        let mut start = 0;
+        while start < len {
+            let current = self.cpu_events[start].shard;
+            let shard_end = self
+                .cpu_events
+                .partition_point(start..len, |event| event.shard == current);
+            let max_len = if shard_end == len {
+                merged_len
+            } else {
+                shard_size
+            };
+            let end = if shard_end - start <= max_len {
+                shard_end
+            } else {
+                start + shard_size
+            };
+            let mut shard = ExecutionRecord::default();
+            shard.index = (shards.len() + 1) as u32;
+            shard.program = self.program.clone();
+            shard.cpu_events = self.cpu_events.range(start..end).copied().collect();
+            shard.filter = self.filter;
+            shard.provenance = self.provenance.restricted_to(shard.index);
+
+            shards.push(shard);
+            start = end;
+        }
+
+        // Keep the AUIPC and bit manipulation events with their CPU events.
+        let mut auipc_events = self.auipc_events.iter().peekable();
+        let mut bitmanip_events = self.bitmanip_events.iter().peekable();
+        for shard in shards.iter_mut() {
+            let last = shard.cpu_events.last().unwrap();
+            let end = (last.shard, last.clk);
+            while let Some(event) = auipc_events.next_if(|event| (event.shard, event.clk) <= end) {
+                shard.auipc_events.push(*event);
+            }
+            while let Some(event) = bitmanip_events.next_if(|event| (event.shard, event.clk) <= end)
+            {
+                shard.bitmanip_events.push(*event);
+            }
+        }
 
         // Shard all the other events according to the configuration.
 
@@ -321,8 +622,19 @@ impl ExecutionRecord {
             .blake3_compress_inner_events
             .extend_from_slice(&self.blake3_compress_inner_events);
 
-        // Put all byte lookups in the first shard (as the table size is fixed)
-        first.byte_lookups.extend(&self.byte_lookups);
+        // LOAD64 and STORE64 events.
+        first.mem64_events.extend_from_slice(&self.mem64_events);
+
+        // Keep the byte lookups of each shard with it. Lookups emitted by chips of the whole
+        // record are put in the first shard, as the table size is fixed.
+        for (index, lookups) in self.byte_lookups {
+            let position = shards
+                .iter()
+                .position(|shard| shard.index == index)
+                .unwrap_or(0);
+            let shard = &mut shards[position];
+            shard.add_byte_lookup_multiplicities(shard.index, lookups);
+        }
 
         // Put the memory records in the last shard.
         let last_shard = shards.last_mut().unwrap();
@@ -336,15 +648,23 @@ impl ExecutionRecord {
         last_shard
             .program_memory_record
             .extend_from_slice(&self.program_memory_record);
+        last_shard.final_shard = self.final_shard;
+        last_shard.guest_assertion = self.guest_assertion;
+        last_shard.public_values_digest = self.public_values_digest;
+        last_shard.output_channels = self.output_channels;
+        last_shard.shard_boundaries = self.shard_boundaries;
 
         shards
     }
 
+    /// Adds a multiplication emitted by another chip, deriving its metadata.
     pub fn add_mul_event(&mut self, mul_event: AluEvent) {
-        self.mul_events.push(mul_event);
+        self.indices_dirty = true;
+        self.mul_events.push(mul_event.into());
     }
 
     pub fn add_lt_event(&mut self, lt_event: AluEvent) {
+        self.indices_dirty = true;
         self.lt_events.push(lt_event);
     }
 
@@ -356,21 +676,61 @@ impl ExecutionRecord {
         self.field_events.extend_from_slice(field_events);
     }
 
+    /// Adds a byte lookup to the shard of this record.
     pub fn add_byte_lookup_event(&mut self, blu_event: ByteLookupEvent) {
-        self.byte_lookups
+        if !self.filter.contains(RecordFilter::BYTE_LOOKUPS) {
+            return;
+        }
+        *self
+            .byte_lookups
+            .entry(self.index)
+            .or_default()
             .entry(blu_event)
-            .and_modify(|i| *i += 1)
-            .or_insert(1);
+            .or_insert(0) += 1;
+    }
+
+    /// Adds byte lookups with their multiplicities to the given shard.
+    pub fn add_byte_lookup_multiplicities(
+        &mut self,
+        shard: u32,
+        lookups: impl IntoIterator<Item = (ByteLookupEvent, usize)>,
+    ) {
+        if !self.filter.contains(RecordFilter::BYTE_LOOKUPS) {
+            return;
+        }
+        let shard_lookups = self.byte_lookups.entry(shard).or_default();
+        for (blu_event, mult) in lookups {
+            *shard_lookups.entry(blu_event).or_insert(0) += mult;
+        }
+    }
+
+    /// The total number of byte lookups, counted with multiplicity.
+    pub fn nb_byte_lookups(&self) -> usize {
+        self.byte_lookups
+            .values()
+            .flat_map(|lookups| lookups.values())
+            .sum()
     }
 
-    pub fn add_alu_events(&mut self, alu_events: HashMap<Opcode, Vec<AluEvent>>) {
-        for opcode in alu_events.keys() {
+    pub fn add_alu_events<S: BuildHasher>(
+        &mut self,
+        alu_events: HashMap<Opcode, Vec<AluEvent>, S>,
+    ) {
+        self.indices_dirty = true;
+        // Visit the opcodes in a fixed order, since several of them share an event vector.
+        let mut opcodes = alu_events.keys().collect::<Vec<_>>();
+        opcodes.sort_by_key(|opcode| **opcode as u32);
+        for opcode in opcodes {
             match opcode {
                 Opcode::ADD => {
                     self.add_events.extend_from_slice(&alu_events[opcode]);
                 }
                 Opcode::MUL | Opcode::MULH | Opcode::MULHU | Opcode::MULHSU => {
-                    self.mul_events.extend_from_slice(&alu_events[opcode]);
+                    self.mul_events.extend(
+                        alu_events[opcode]
+                            .iter()
+                            .map(|&event| MulEvent::from(event)),
+                    );
                 }
                 Opcode::SUB => {
                     self.sub_events.extend_from_slice(&alu_events[opcode]);
@@ -473,14 +833,169 @@ impl ExecutionRecord {
             nb_weierstrass_add_events: self.weierstrass_add_events.len(),
             nb_weierstrass_double_events: self.weierstrass_double_events.len(),
             nb_k256_decompress_events: self.k256_decompress_events.len(),
+            nb_blake3_compress_inner_events: self.blake3_compress_inner_events.len(),
+            nb_mem64_events: self.mem64_events.len(),
+        }
+    }
+
+    /// A hash of the contents of the record, equal for the records of two runs of the same program
+    /// with the same inputs on any machine.
+    ///
+    /// Events are hashed in order, maps in key order and memory records by address, together
+    /// with the shard index and the [`Program::digest`] of the program. The provenance is left
+    /// out, as it tells how the record was produced rather than what it holds, and so are the
+    /// filter and the indices, which are not part of the serialized record.
+    pub fn digest(&self) -> [u8; 32] {
+        fn write<T: Serialize + ?Sized>(hasher: &mut blake3::Hasher, value: &T) {
+            bincode::serialize_into(hasher, value).expect("failed to serialize the record");
+        }
+
+        // Destructured so that a new field has to be hashed or explicitly left out.
+        let Self {
+            index,
+            program,
+            cpu_events,
+            auipc_events,
+            instruction_counts,
+            add_events,
+            mul_events,
+            sub_events,
+            bitwise_events,
+            shift_left_events,
+            shift_right_events,
+            divrem_events,
+            lt_events,
+            bitmanip_events,
+            byte_lookups,
+            field_events,
+            sha_extend_events,
+            sha_compress_events,
+            keccak_permute_events,
+            ed_add_events,
+            ed_decompress_events,
+            weierstrass_add_events,
+            weierstrass_double_events,
+            k256_decompress_events,
+            blake3_compress_inner_events,
+            mem64_events,
+            first_memory_record,
+            last_memory_record,
+            program_memory_record,
+            final_shard,
+            guest_assertion,
+            public_values_digest,
+            output_channels,
+            shard_boundaries,
+            provenance: _,
+            filter: _,
+            indices: _,
+            indices_dirty: _,
+        } = self;
+
+        let mut hasher = blake3::Hasher::new();
+        write(&mut hasher, index);
+        write(&mut hasher, &program.digest());
+        write(&mut hasher, cpu_events);
+        write(&mut hasher, auipc_events);
+        write(
+            &mut hasher,
+            &instruction_counts.iter().collect::<BTreeMap<_, _>>(),
+        );
+        write(&mut hasher, add_events);
+        write(&mut hasher, mul_events);
+        write(&mut hasher, sub_events);
+        write(&mut hasher, bitwise_events);
+        write(&mut hasher, shift_left_events);
+        write(&mut hasher, shift_right_events);
+        write(&mut hasher, divrem_events);
+        write(&mut hasher, lt_events);
+        write(&mut hasher, bitmanip_events);
+        for (shard, lookups) in byte_lookups.iter() {
+            write(&mut hasher, shard);
+            write(&mut hasher, &lookups.iter().collect::<BTreeMap<_, _>>());
+        }
+        write(&mut hasher, field_events);
+        write(&mut hasher, sha_extend_events);
+        write(&mut hasher, sha_compress_events);
+        write(&mut hasher, keccak_permute_events);
+        write(&mut hasher, ed_add_events);
+        write(&mut hasher, ed_decompress_events);
+        write(&mut hasher, weierstrass_add_events);
+        write(&mut hasher, weierstrass_double_events);
+        write(&mut hasher, k256_decompress_events);
+        write(&mut hasher, blake3_compress_inner_events);
+        write(&mut hasher, mem64_events);
+        for records in [
+            first_memory_record,
+            last_memory_record,
+            program_memory_record,
+        ] {
+            let mut records = records.clone();
+            records.sort_by_key(|(addr, _, _)| *addr);
+            write(&mut hasher, &records);
+        }
+        write(&mut hasher, final_shard);
+        write(&mut hasher, guest_assertion);
+        write(&mut hasher, public_values_digest);
+        write(&mut hasher, output_channels);
+        write(&mut hasher, shard_boundaries);
+        *hasher.finalize().as_bytes()
+    }
+
+    /// Computes, for every shard this record would be split into under `config`, the number of
+    /// events of each class and the power of two its table gets padded to.
+    pub fn padding_report(&self, config: &ShardingConfig) -> PaddingReport {
+        let mut report = PaddingReport::default();
+        for shard in self.clone().shard(config) {
+            let mut counts = shard.stats().event_counts();
+            counts.push((BYTE_LOOKUP_CLASS, shard.nb_byte_lookups()));
+            for (class, count) in counts {
+                if count == 0 {
+                    continue;
+                }
+                report.entries.push(PaddingEntry {
+                    shard: shard.index,
+                    class,
+                    count,
+                    padded: count.next_power_of_two(),
+                });
+            }
+        }
+
+        // The byte table has a fixed height, so shrinking the shard does not change its size.
+        for entry in report
+            .entries
+            .iter()
+            .filter(|e| e.class != BYTE_LOOKUP_CLASS)
+        {
+            let Some(overshoot) = entry.overshoot() else {
+                continue;
+            };
+            if overshoot > PADDING_SUGGESTION_RATIO {
+                continue;
+            }
+            let decrease = ((1.0 - 1.0 / overshoot) * 100.0).ceil() as usize;
+            report.suggestions.push(format!(
+                "shard {} uses {:.2}\u{00d7}2^{} {} rows; decreasing shard_size {}% would halve the {} table",
+                entry.shard,
+                overshoot,
+                (entry.padded / 2).trailing_zeros(),
+                entry.class,
+                decrease,
+                entry.class
+            ));
         }
+
+        report
     }
 
     /// Append the events from another execution record to this one, leaving the other one empty.
     pub fn append(&mut self, other: &mut ExecutionRecord) {
         assert_eq!(self.index, other.index, "Shard index mismatch");
+        self.indices_dirty = true;
 
         self.cpu_events.append(&mut other.cpu_events);
+        self.auipc_events.append(&mut other.auipc_events);
         self.add_events.append(&mut other.add_events);
         self.sub_events.append(&mut other.sub_events);
         self.mul_events.append(&mut other.mul_events);
@@ -490,6 +1005,7 @@ impl ExecutionRecord {
             .append(&mut other.shift_right_events);
         self.divrem_events.append(&mut other.divrem_events);
         self.lt_events.append(&mut other.lt_events);
+        self.bitmanip_events.append(&mut other.bitmanip_events);
         self.field_events.append(&mut other.field_events);
         self.sha_extend_events.append(&mut other.sha_extend_events);
         self.sha_compress_events
@@ -507,12 +1023,10 @@ impl ExecutionRecord {
             .append(&mut other.k256_decompress_events);
         self.blake3_compress_inner_events
             .append(&mut other.blake3_compress_inner_events);
+        self.mem64_events.append(&mut other.mem64_events);
 
-        for (event, mult) in other.byte_lookups.iter_mut() {
-            self.byte_lookups
-                .entry(*event)
-                .and_modify(|i| *i += *mult)
-                .or_insert(*mult);
+        for (shard, lookups) in std::mem::take(&mut other.byte_lookups) {
+            self.add_byte_lookup_multiplicities(shard, lookups);
         }
 
         self.first_memory_record
@@ -521,13 +1035,269 @@ impl ExecutionRecord {
             .append(&mut other.last_memory_record);
         self.program_memory_record
             .append(&mut other.program_memory_record);
+        self.shard_boundaries.append(&mut other.shard_boundaries);
+        self.provenance
+            .absorb(std::mem::take(&mut other.provenance));
     }
 }
 
-#[derive(Debug, Copy, Clone, Default)]
+/// A second access recorded at a position of a [`CpuRecord`] that was already accessed during the
+/// same instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DuplicateAccess {
+    pub position: AccessPosition,
+
+    /// The access recorded first.
+    pub existing: MemoryRecordEnum,
+
+    /// The access that was recorded again.
+    pub new: MemoryRecordEnum,
+}
+
+impl Display for DuplicateAccess {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:?} is accessed twice in one instruction: {:?} and then {:?}",
+            self.position, self.existing, self.new
+        )
+    }
+}
+
+/// The memory accesses of the current instruction, one per [`AccessPosition`].
+///
+/// The records are stored inline and a bit per position tells whether it was accessed, so that
+/// clearing the record between two instructions only resets the bits.
+#[derive(Debug, Copy, Clone)]
 pub struct CpuRecord {
-    pub a: Option<MemoryRecordEnum>,
-    pub b: Option<MemoryRecordEnum>,
-    pub c: Option<MemoryRecordEnum>,
-    pub memory: Option<MemoryRecordEnum>,
+    records: [MemoryRecordEnum; 4],
+    valid: u8,
+}
+
+impl Default for CpuRecord {
+    fn default() -> Self {
+        Self {
+            records: [MemoryRecordEnum::Read(MemoryReadRecord::default()); 4],
+            valid: 0,
+        }
+    }
+}
+
+impl CpuRecord {
+    /// The access at `position`, if any.
+    #[inline]
+    pub fn get(&self, position: AccessPosition) -> Option<MemoryRecordEnum> {
+        let index = position as usize;
+        (self.valid & (1 << index) != 0).then_some(self.records[index])
+    }
+
+    #[inline]
+    pub fn a(&self) -> Option<MemoryRecordEnum> {
+        self.get(AccessPosition::A)
+    }
+
+    #[inline]
+    pub fn b(&self) -> Option<MemoryRecordEnum> {
+        self.get(AccessPosition::B)
+    }
+
+    #[inline]
+    pub fn c(&self) -> Option<MemoryRecordEnum> {
+        self.get(AccessPosition::C)
+    }
+
+    #[inline]
+    pub fn memory(&self) -> Option<MemoryRecordEnum> {
+        self.get(AccessPosition::Memory)
+    }
+
+    /// The number of positions that were accessed.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.valid.count_ones() as usize
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.valid == 0
+    }
+
+    /// Record the access at `position`, failing if it was already accessed.
+    #[inline]
+    pub fn set(
+        &mut self,
+        position: AccessPosition,
+        record: MemoryRecordEnum,
+    ) -> Result<(), DuplicateAccess> {
+        if let Some(existing) = self.get(position) {
+            return Err(DuplicateAccess {
+                position,
+                existing,
+                new: record,
+            });
+        }
+        self.insert(position, record);
+        Ok(())
+    }
+
+    /// Record the access at `position`, replacing any previous one.
+    #[inline]
+    pub fn insert(&mut self, position: AccessPosition, record: MemoryRecordEnum) {
+        let index = position as usize;
+        self.records[index] = record;
+        self.valid |= 1 << index;
+    }
+
+    /// Forget every access.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.valid = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::RandomState;
+
+    use p3_baby_bear::BabyBear;
+
+    use super::*;
+    use crate::air::MachineAir;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::{Instruction, Runtime};
+    use crate::stark::RiscvAir;
+
+    fn config(shard_size: usize) -> ShardingConfig {
+        ShardingConfig {
+            shard_size,
+            add_len: shard_size,
+            mul_len: shard_size,
+            sub_len: shard_size,
+            bitwise_len: shard_size,
+            shift_left_len: shard_size,
+            shift_right_len: shard_size,
+            divrem_len: shard_size,
+            lt_len: shard_size,
+            field_len: shard_size * 4,
+            keccak_len: shard_size,
+            weierstrass_add_len: shard_size,
+            weierstrass_double_len: shard_size,
+            round_to_pow2: false,
+            merge_small_final_shard: None,
+        }
+    }
+
+    #[test]
+    fn test_padding_report() {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 29, 0, 5, false, true),
+            Instruction::new(Opcode::ADD, 30, 0, 37, false, true),
+            Instruction::new(Opcode::MUL, 31, 30, 29, false, false),
+            Instruction::new(Opcode::MUL, 31, 31, 29, false, false),
+            Instruction::new(Opcode::MUL, 31, 31, 29, false, false),
+            Instruction::new(Opcode::MUL, 31, 31, 29, false, false),
+            Instruction::new(Opcode::MUL, 31, 31, 29, false, false),
+            Instruction::new(Opcode::ADD, 31, 31, 29, false, false),
+            Instruction::new(Opcode::ADD, 31, 31, 29, false, false),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime.run();
+
+        let mut record = runtime.record;
+        for _ in 0..3 {
+            record.add_u8_range_check(1, 2);
+        }
+        record.add_u8_range_check(3, 4);
+
+        let report = record.padding_report(&config(8));
+        let entry = |shard, class| report.entry(shard, class).cloned();
+        let expected = |shard, class, count, padded| {
+            Some(PaddingEntry {
+                shard,
+                class,
+                count,
+                padded,
+            })
+        };
+
+        assert_eq!(entry(1, "cpu"), expected(1, "cpu", 8, 8));
+        assert_eq!(entry(1, "add"), expected(1, "add", 4, 4));
+        assert_eq!(entry(1, "mul"), expected(1, "mul", 5, 8));
+        assert_eq!(entry(2, "cpu"), expected(2, "cpu", 1, 1));
+        assert_eq!(entry(2, "mul"), None);
+        // Two distinct lookups with multiplicities 3 and 1.
+        assert_eq!(
+            entry(1, BYTE_LOOKUP_CLASS),
+            expected(1, BYTE_LOOKUP_CLASS, 4, 4)
+        );
+        assert_eq!(report.entries.len(), 5);
+
+        assert_eq!(
+            report.suggestions,
+            vec![
+                "shard 1 uses 1.25\u{00d7}2^2 mul rows; decreasing shard_size 20% would halve the mul table"
+                    .to_string()
+            ]
+        );
+    }
+
+    /// Execute fibonacci and generate the dependencies of every chip, returning the digest of the
+    /// resulting record.
+    fn fibonacci_digest() -> [u8; 32] {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.run();
+        let mut record = runtime.record;
+        for air in RiscvAir::<BabyBear>::get_all() {
+            let mut output = ExecutionRecord {
+                index: record.index,
+                ..Default::default()
+            };
+            air.generate_dependencies(&record, &mut output);
+            record.append(&mut output);
+        }
+        record.digest()
+    }
+
+    #[test]
+    fn test_record_digest() {
+        let digest = fibonacci_digest();
+        // A new thread gets different keys for every hasher it creates.
+        let other = std::thread::spawn(fibonacci_digest).join().unwrap();
+        assert_eq!(digest, other);
+
+        // The order of the events must not depend on the hasher of the map they are added from.
+        let events = [
+            AluEvent::new(0, Opcode::SLTU, 1, 2, 3),
+            AluEvent::new(1, Opcode::SLT, 0, 3, 2),
+        ];
+        let digests = (0..8)
+            .map(|i| {
+                let mut alu_events = HashMap::with_hasher(RandomState::new());
+                for event in events.iter().cycle().skip(i % 2).take(2) {
+                    alu_events.insert(event.opcode, vec![*event]);
+                }
+                let mut record = ExecutionRecord::default();
+                record.add_alu_events(alu_events);
+                record.digest()
+            })
+            .collect::<Vec<_>>();
+        assert!(digests.iter().all(|d| *d == digests[0]));
+
+        let mut record = ExecutionRecord::default();
+        record.add_alu_events(HashMap::<Opcode, Vec<AluEvent>>::from([(
+            Opcode::ADD,
+            vec![events[0]],
+        )]));
+        assert_ne!(record.digest(), digests[0]);
+
+        // What the execution produced besides its events is hashed, but not how the record was
+        // produced.
+        let mut finished = record.clone();
+        finished.final_shard = Some(ShardExtent { shard: 1, clk: 8 });
+        finished.public_values_digest = Some([1; 32]);
+        assert_ne!(finished.digest(), record.digest());
+        let mut replayed = record.clone();
+        replayed.provenance.replays.push(1);
+        assert_eq!(replayed.digest(), record.digest());
+    }
 }
@@ -0,0 +1,238 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Runtime, NUM_REGISTERS};
+
+/// The initial stack pointer of guests built with the SP1 entrypoint. The stack grows down from
+/// it, towards the registers.
+pub const DEFAULT_STACK_TOP: u32 = 0x0020_0400;
+
+/// The names of the regions every address not in a named region falls in.
+const GLOBALS: &str = "globals";
+const STACK: &str = "stack";
+const HEAP: &str = "heap";
+
+/// Memory accesses of the guest to one region of the address space, see
+/// [`Runtime::region_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegionStats {
+    pub name: String,
+
+    /// The number of words the guest read, counting every access.
+    pub reads: u64,
+
+    /// The number of words the guest wrote, counting every access.
+    pub writes: u64,
+
+    /// The number of distinct words the guest accessed.
+    pub words: usize,
+}
+
+/// The memory used by an execution, see [`Runtime::memory_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    /// The number of distinct words of memory the guest accessed, excluding the registers.
+    pub words: usize,
+
+    /// The accesses to each region, empty unless [`Runtime::enable_region_stats`] was called.
+    pub regions: Vec<RegionStats>,
+}
+
+/// Counts the accesses to each region as they happen. The regions named by the host come first,
+/// followed by the globals, the stack and the heap.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RegionCounter {
+    named: Vec<(String, Range<u32>)>,
+
+    /// The memory image of the program.
+    globals: Range<u32>,
+
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+}
+
+impl RegionCounter {
+    fn new(named: Vec<(String, Range<u32>)>, globals: Range<u32>) -> Self {
+        let len = named.len() + 3;
+        Self {
+            named,
+            globals,
+            reads: vec![0; len],
+            writes: vec![0; len],
+        }
+    }
+
+    /// The index of the region `addr` falls in.
+    #[inline]
+    fn classify(&self, addr: u32) -> usize {
+        if let Some(index) = self
+            .named
+            .iter()
+            .position(|(_, range)| range.contains(&addr))
+        {
+            return index;
+        }
+        let defaults = self.named.len();
+        if self.globals.contains(&addr) {
+            defaults
+        } else if addr < DEFAULT_STACK_TOP {
+            defaults + 1
+        } else {
+            defaults + 2
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record(&mut self, addr: u32, write: bool) {
+        let index = self.classify(addr);
+        if write {
+            self.writes[index] += 1;
+        } else {
+            self.reads[index] += 1;
+        }
+    }
+
+    /// Forget the accesses counted so far.
+    pub(crate) fn reset(&mut self) {
+        self.reads.fill(0);
+        self.writes.fill(0);
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.named
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .chain([GLOBALS, STACK, HEAP])
+    }
+}
+
+impl Runtime {
+    /// Count the memory accesses of the guest to each region of the address space from now on:
+    /// the regions named with [`Runtime::name_region`], then the memory image as "globals", the
+    /// addresses below [`DEFAULT_STACK_TOP`] as "stack" and everything else as "heap".
+    pub fn enable_region_stats(&mut self) {
+        if self.region_counter.is_none() {
+            self.region_counter = Some(RegionCounter::new(Vec::new(), self.program.image_range()));
+        }
+    }
+
+    /// Attribute the accesses to the addresses in `lo..hi` to a region called `name`, taking
+    /// precedence over the default regions and the regions named later. This enables the region
+    /// statistics.
+    pub fn name_region(&mut self, name: &str, lo: u32, hi: u32) {
+        self.enable_region_stats();
+        let counter = self.region_counter.as_mut().unwrap();
+        let mut named = std::mem::take(&mut counter.named);
+        named.push((name.to_string(), lo..hi));
+        *counter = RegionCounter::new(named, counter.globals.clone());
+    }
+
+    /// The accesses to each region so far, or `None` if [`Runtime::enable_region_stats`] was not
+    /// called. Accesses in unconstrained blocks are not counted.
+    pub fn region_stats(&self) -> Option<Vec<RegionStats>> {
+        let counter = self.region_counter.as_ref()?;
+        let mut words = vec![0; counter.reads.len()];
+        // The words of the memory image that were never accessed are still at shard 0.
+        for (&addr, &(_, shard, _)) in self.state.memory.iter() {
+            if addr >= NUM_REGISTERS && shard != 0 {
+                words[counter.classify(addr)] += 1;
+            }
+        }
+        Some(
+            counter
+                .names()
+                .enumerate()
+                .map(|(i, name)| RegionStats {
+                    name: name.to_string(),
+                    reads: counter.reads[i],
+                    writes: counter.writes[i],
+                    words: words[i],
+                })
+                .collect(),
+        )
+    }
+
+    /// The memory used by the guest so far.
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats {
+            words: self
+                .state
+                .memory
+                .iter()
+                .filter(|&(&addr, &(_, shard, _))| addr >= NUM_REGISTERS && shard != 0)
+                .count(),
+            regions: self.region_stats().unwrap_or_default(),
+        }
+    }
+
+    /// Refresh the memory image of the region counter, once the program is laid out.
+    pub(crate) fn update_region_globals(&mut self) {
+        if let Some(counter) = &mut self.region_counter {
+            counter.globals = self.program.image_range();
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Instruction, Opcode, Program};
+
+    /// Write 10 distinct heap words and 3 stack words, read one of them back and read a global.
+    fn regions_program() -> Program {
+        let mut instructions = vec![
+            Instruction::new(Opcode::ADD, 10, 0, 0x0800_0000, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 10, false, true),
+            // loop:
+            Instruction::new(Opcode::SW, 11, 10, 0, false, true),
+            Instruction::new(Opcode::ADD, 10, 10, 4, false, true),
+            Instruction::new(Opcode::SUB, 11, 11, 1, false, true),
+            Instruction::new(Opcode::BNE, 11, 0, -12i32 as u32, false, true),
+            Instruction::new(Opcode::ADD, 2, 0, 0x0020_0000, false, true),
+        ];
+        for offset in [0, 4, 8] {
+            instructions.push(Instruction::new(Opcode::SW, 0, 2, offset, false, true));
+        }
+        instructions.push(Instruction::new(Opcode::LW, 12, 2, 4, false, true));
+        instructions.push(Instruction::new(Opcode::LW, 13, 0, 0x1000, false, true));
+        let mut program = Program::new(instructions, 0, 0);
+        program.memory_image.insert(0x1000, 7);
+        program.memory_image.insert(0x1004, 8);
+        program
+    }
+
+    fn stats(name: &str, reads: u64, writes: u64, words: usize) -> RegionStats {
+        RegionStats {
+            name: name.to_string(),
+            reads,
+            writes,
+            words,
+        }
+    }
+
+    #[test]
+    fn test_region_stats() {
+        let mut runtime = Runtime::new(regions_program());
+        runtime.enable_region_stats();
+        runtime.run();
+        assert_eq!(
+            runtime.region_stats().unwrap(),
+            vec![
+                stats(GLOBALS, 1, 0, 1),
+                stats(STACK, 1, 3, 3),
+                stats(HEAP, 0, 10, 10),
+            ]
+        );
+        assert_eq!(runtime.memory_stats().words, 14);
+        assert!(Runtime::new(regions_program()).region_stats().is_none());
+
+        // Named regions take precedence over the defaults.
+        let mut runtime = Runtime::new(regions_program());
+        runtime.name_region("buffer", 0x0800_0000, 0x0800_0010);
+        runtime.run();
+        let regions = runtime.memory_stats().regions;
+        assert_eq!(regions[0], stats("buffer", 0, 4, 4));
+        assert_eq!(regions[3], stats(HEAP, 0, 6, 6));
+    }
+}
@@ -1,3 +1,10 @@
+/// The ABI names of the registers, indexed by register.
+pub const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
 /// A register stores a 32-bit value used by operations.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Register {
@@ -38,7 +45,13 @@ pub enum Register {
 impl Register {
     #[inline(always)]
     pub fn from_u32(value: u32) -> Self {
-        match value {
+        Self::try_from_u32(value).unwrap_or_else(|| panic!("invalid register {}", value))
+    }
+
+    /// The register with index `value`, or `None` if it is not below 32.
+    #[inline(always)]
+    pub fn try_from_u32(value: u32) -> Option<Self> {
+        let register = match value {
             0 => Register::X0,
             1 => Register::X1,
             2 => Register::X2,
@@ -71,7 +84,8 @@ impl Register {
             29 => Register::X29,
             30 => Register::X30,
             31 => Register::X31,
-            _ => panic!("invalid register {}", value),
-        }
+            _ => return None,
+        };
+        Some(register)
     }
 }
@@ -0,0 +1,348 @@
+use core::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::{Instruction, Opcode, Program, Runtime, Symbol, SymbolTable};
+
+/// The alignment of the offsets chosen by [`Runtime::randomize_layout`], which keeps the
+/// alignment of the data of the guest.
+pub const LAYOUT_ALIGNMENT: u32 = 1 << 12;
+
+/// The maximum offset chosen by [`Runtime::randomize_layout`].
+const MAX_LAYOUT_OFFSET: u32 = 1 << 24;
+
+/// An error relocating a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelocationError {
+    /// The offset is not a multiple of the word size.
+    Unaligned { offset: u32 },
+
+    /// Part of the program would be moved past the end of the address space.
+    OutOfBounds { offset: u32, end: u32 },
+}
+
+impl Display for RelocationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RelocationError::Unaligned { offset } => {
+                write!(f, "relocation offset 0x{:x} is not word-aligned", offset)
+            }
+            RelocationError::OutOfBounds { offset, end } => write!(
+                f,
+                "relocating by 0x{:x} moves the program ending at 0x{:08x} past the address space",
+                offset, end
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RelocationError {}
+
+/// An absolute address into the memory image materialized by an instruction, which breaks once
+/// the program is relocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbsoluteAddress {
+    /// The pc of the instruction materializing the address.
+    pub pc: u32,
+
+    pub addr: u32,
+}
+
+impl Display for AbsoluteAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "pc=0x{:08x} uses the absolute address 0x{:08x} of the memory image",
+            self.pc, self.addr
+        )
+    }
+}
+
+/// The constant an instruction loads into its destination register, for `lui` and `li`.
+fn materialized_constant(instruction: &Instruction) -> Option<u32> {
+    let loads_constant = instruction.opcode == Opcode::ADD
+        && instruction.op_b == 0
+        && instruction.imm_c
+        && instruction.op_a != 0;
+    loads_constant.then_some(instruction.op_c)
+}
+
+/// The immediate `instruction` adds to the register `base`, for `addi` and loads and stores.
+fn offset_from(instruction: &Instruction, base: u32) -> Option<u32> {
+    let adds_immediate = (instruction.opcode == Opcode::ADD || instruction.is_memory_instruction())
+        && !instruction.imm_b
+        && instruction.imm_c
+        && instruction.op_b == base;
+    adds_immediate.then_some(instruction.op_c)
+}
+
+impl Program {
+    /// The byte range spanned by the memory image, empty if there is none.
+    pub(crate) fn image_range(&self) -> Range<u32> {
+        match (
+            self.memory_image.first_key_value(),
+            self.memory_image.last_key_value(),
+        ) {
+            (Some((&first, _)), Some((&last, _))) => first..last.saturating_add(4),
+            _ => 0..0,
+        }
+    }
+
+    /// The end of the highest address used by the program.
+    fn end_address(&self) -> u32 {
+        let text_end = self.pc_base as u64 + self.instructions.len() as u64 * 4;
+        let symbols_end = self
+            .symbols
+            .iter()
+            .map(|symbol| symbol.addr as u64 + symbol.size as u64)
+            .max();
+        let ranges_end = self
+            .readonly
+            .iter()
+            .chain(self.linked.iter().flat_map(|blob| blob.ranges.iter()))
+            .map(|range| range.end as u64)
+            .max();
+        [
+            Some(text_end),
+            Some(self.image_range().end as u64),
+            symbols_end,
+            ranges_end,
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap()
+        .min(u32::MAX as u64) as u32
+    }
+
+    /// Find the instructions that materialize absolute addresses into the memory image, with
+    /// `lui`, `li` or a load or store relative to x0, which a relocated program cannot run. The
+    /// `auipc` instructions of position-independent code are relative to the pc and are fine.
+    pub fn absolute_addresses(&self) -> Vec<AbsoluteAddress> {
+        let image = self.image_range();
+        let mut found = Vec::new();
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            let pc = self.pc_base.wrapping_add(i as u32 * 4);
+            let addr = if let Some(constant) = materialized_constant(instruction) {
+                // `lui` is usually followed by the `addi`, load or store adding the low bits.
+                let low = self
+                    .instructions
+                    .get(i + 1)
+                    .and_then(|next| offset_from(next, instruction.op_a));
+                constant.wrapping_add(low.unwrap_or(0))
+            } else if let Some(offset) = offset_from(instruction, 0) {
+                offset
+            } else {
+                continue;
+            };
+            if image.contains(&addr) {
+                found.push(AbsoluteAddress { pc, addr });
+            }
+        }
+        found
+    }
+
+    /// Move the program `offset` bytes up the address space: its instructions, entrypoint,
+    /// memory image, symbols, read-only ranges and linked data. Only position-independent guests
+    /// run the same once relocated, so the instructions that would break are returned, see
+    /// [`Program::absolute_addresses`].
+    pub fn relocate(&mut self, offset: u32) -> Result<Vec<AbsoluteAddress>, RelocationError> {
+        if offset % 4 != 0 {
+            return Err(RelocationError::Unaligned { offset });
+        }
+        let end = self.end_address();
+        if end.checked_add(offset).is_none() || self.pc_start.checked_add(offset).is_none() {
+            return Err(RelocationError::OutOfBounds { offset, end });
+        }
+        let absolute = self.absolute_addresses();
+
+        let shift = |range: &Range<u32>| range.start + offset..range.end + offset;
+        self.pc_start += offset;
+        self.pc_base += offset;
+        self.memory_image = self
+            .memory_image
+            .iter()
+            .map(|(&addr, &value)| (addr + offset, value))
+            .collect();
+        self.symbols = SymbolTable::new(
+            self.symbols
+                .iter()
+                .map(|symbol| Symbol {
+                    addr: symbol.addr + offset,
+                    ..symbol.clone()
+                })
+                .collect(),
+        );
+        self.readonly = self.readonly.iter().map(shift).collect();
+        for blob in self.linked.iter_mut() {
+            blob.ranges = blob.ranges.iter().map(shift).collect();
+        }
+        Ok(absolute)
+    }
+}
+
+/// The splitmix64 generator, to derive offsets from seeds without a dependency.
+pub(super) fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Runtime {
+    /// Relocate the program by a pseudo-random offset derived from `seed` when execution starts,
+    /// to check that a position-independent guest does not depend on the absolute addresses of its
+    /// data. The offset is a nonzero multiple of [`LAYOUT_ALIGNMENT`], see
+    /// [`Runtime::layout_offset`].
+    pub fn randomize_layout(&mut self, seed: u64) {
+        self.layout_seed = Some(seed);
+    }
+
+    /// The offset the program was relocated by, zero unless the layout was randomized.
+    pub fn layout_offset(&self) -> u32 {
+        self.layout_offset
+    }
+
+    /// Relocate the program if its layout is randomized and it was not relocated yet.
+    pub(crate) fn apply_layout(&mut self) {
+        let Some(seed) = self.layout_seed else {
+            return;
+        };
+        if self.layout_offset != 0 {
+            return;
+        }
+        let room = u32::MAX - self.program.end_address();
+        let pages = room.min(MAX_LAYOUT_OFFSET) / LAYOUT_ALIGNMENT;
+        if pages == 0 {
+            log::warn!("no room to relocate the program, its layout is not randomized");
+            return;
+        }
+        let offset = (1 + splitmix64(seed) % pages as u64) as u32 * LAYOUT_ALIGNMENT;
+
+        let mut program = (*self.program).clone();
+        let absolute = program
+            .relocate(offset)
+            .expect("the offset fits below the end of the address space");
+        for address in absolute.iter() {
+            log::warn!("relocated program may break: {}", address);
+        }
+        self.program = Arc::new(program);
+        self.record.program = self.program.clone();
+        self.state.pc = self.program.pc_start;
+        self.layout_offset = offset;
+        // The frozen memory is at the addresses before relocation, so the relocated image, which
+        // includes it, is loaded instead.
+        self.frozen = None;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Register, SyscallCode};
+
+    /// Write the two words of data at 0x1000, found relative to the pc, to the output stream and
+    /// load the first one into x13.
+    fn relocatable_program() -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::AUIPC, 11, 0x1000, 0x1000, true, true),
+            Instruction::new(Opcode::LW, 13, 11, 0, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, SyscallCode::WRITE as u32, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, 3, false, true),
+            Instruction::new(Opcode::ADD, 12, 0, 8, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        let mut program = Program::new(instructions, 0, 0);
+        program.memory_image.insert(0x1000, 0xdeadbeef);
+        program.memory_image.insert(0x1004, 0x12345678);
+        program
+    }
+
+    fn outputs(program: Program) -> (Vec<u8>, u32) {
+        let mut runtime = Runtime::new(program);
+        runtime.run();
+        (
+            runtime.state.output_stream.clone(),
+            runtime.register(Register::X13),
+        )
+    }
+
+    #[test]
+    fn test_relocate() {
+        let program = relocatable_program();
+        assert!(program.absolute_addresses().is_empty());
+        let expected = outputs(program.clone());
+        assert_eq!(expected.1, 0xdeadbeef);
+
+        for offset in [0x1_0000, 0x20_0000] {
+            let mut relocated = program.clone();
+            assert_eq!(relocated.relocate(offset), Ok(Vec::new()));
+            assert_eq!((relocated.pc_start, relocated.pc_base), (offset, offset));
+            assert_eq!(relocated.memory_image[&(0x1000 + offset)], 0xdeadbeef);
+            assert_eq!(outputs(relocated), expected);
+        }
+
+        let mut relocated = program.clone();
+        assert_eq!(
+            relocated.relocate(2),
+            Err(RelocationError::Unaligned { offset: 2 })
+        );
+        assert!(matches!(
+            relocated.relocate(u32::MAX - 0xfff),
+            Err(RelocationError::OutOfBounds { .. })
+        ));
+        assert_eq!(relocated.digest(), program.digest());
+    }
+
+    #[test]
+    fn test_randomize_layout() {
+        let expected = outputs(relocatable_program());
+        let mut offsets = Vec::new();
+        for seed in [1, 2] {
+            let mut runtime = Runtime::new(relocatable_program());
+            runtime.randomize_layout(seed);
+            runtime.run();
+            let offset = runtime.layout_offset();
+            assert!(offset > 0 && offset % LAYOUT_ALIGNMENT == 0);
+            assert_eq!(runtime.program.pc_base, offset);
+            assert_eq!(
+                (
+                    runtime.state.output_stream.clone(),
+                    runtime.register(Register::X13)
+                ),
+                expected
+            );
+            offsets.push(offset);
+        }
+        assert_ne!(offsets[0], offsets[1]);
+    }
+
+    #[test]
+    fn test_absolute_addresses() {
+        let instructions = vec![
+            // lw x13, 0x1000(x0)
+            Instruction::new(Opcode::LW, 13, 0, 0x1000, false, true),
+            // lui x11, 0x1000; lw x14, 4(x11)
+            Instruction::new(Opcode::ADD, 11, 0, 0x1000, true, true),
+            Instruction::new(Opcode::LW, 14, 11, 4, false, true),
+            // li x15, 0x2000 is outside of the image.
+            Instruction::new(Opcode::ADD, 15, 0, 0x2000, false, true),
+        ];
+        let mut program = Program::new(instructions, 0, 0);
+        program.memory_image.insert(0x1000, 1);
+        program.memory_image.insert(0x1004, 2);
+
+        let expected = vec![
+            AbsoluteAddress {
+                pc: 0,
+                addr: 0x1000,
+            },
+            AbsoluteAddress {
+                pc: 4,
+                addr: 0x1004,
+            },
+        ];
+        assert_eq!(program.absolute_addresses(), expected);
+        assert_eq!(program.relocate(0x1_0000), Ok(expected));
+    }
+}
@@ -0,0 +1,302 @@
+use core::fmt::{Display, Formatter};
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{IoStats, MemoryStats, Opcode, Runtime, SyscallCode};
+
+/// The version of the schema of [`ExecutionReport`]. Must be incremented whenever a field is
+/// added, removed or changes meaning.
+pub const EXECUTION_REPORT_VERSION: u32 = 1;
+
+/// The metrics of an execution, for tracking the cycles and memory of a guest across builds, see
+/// [`Runtime::execution_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionReport {
+    /// The version of the schema, [`EXECUTION_REPORT_VERSION`] for reports built by this version.
+    pub schema_version: u32,
+
+    /// The number of instructions executed, excluding those of unconstrained blocks.
+    pub cycles: u64,
+
+    /// The number of shards.
+    pub shards: u32,
+
+    /// The number of executions of each opcode, by mnemonic.
+    pub opcode_counts: BTreeMap<String, u64>,
+
+    /// The number of calls of each syscall, by name.
+    pub syscall_counts: BTreeMap<String, u64>,
+
+    pub memory: MemoryStats,
+
+    pub io: IoStats,
+
+    /// The number of CPU events of the largest shard.
+    pub peak_record_events: u64,
+
+    pub public_values_digest: [u8; 32],
+}
+
+/// How much each metric of an [`ExecutionReport`] may grow, in percent of its baseline, before
+/// [`ExecutionReport::compare`] flags it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegressionThresholds {
+    /// The threshold of the metrics without an override.
+    pub default_percent: f64,
+
+    /// The thresholds of individual metrics, by the names of [`ExecutionReport::metrics`].
+    pub overrides: BTreeMap<String, f64>,
+}
+
+impl Default for RegressionThresholds {
+    fn default() -> Self {
+        Self {
+            default_percent: 5.0,
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl RegressionThresholds {
+    pub fn with_override(mut self, metric: &str, percent: f64) -> Self {
+        self.overrides.insert(metric.to_string(), percent);
+        self
+    }
+
+    fn percent(&self, metric: &str) -> f64 {
+        self.overrides
+            .get(metric)
+            .copied()
+            .unwrap_or(self.default_percent)
+    }
+}
+
+/// A metric that grew beyond its threshold, see [`ExecutionReport::compare`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Regression {
+    pub metric: String,
+    pub before: u64,
+    pub after: u64,
+}
+
+impl Display for Regression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}: {} -> {}", self.metric, self.before, self.after)
+    }
+}
+
+/// An error loading an [`ExecutionReport`] from JSON.
+#[derive(Debug)]
+pub enum ReportError {
+    /// The report was written with a different version of the schema.
+    VersionMismatch { expected: u32, found: u32 },
+
+    /// The data is not a report.
+    Json(serde_json::Error),
+}
+
+impl Display for ReportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReportError::VersionMismatch { expected, found } => write!(
+                f,
+                "unsupported execution report schema version: expected {}, found {}",
+                expected, found
+            ),
+            ReportError::Json(error) => write!(f, "invalid execution report: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+impl ExecutionReport {
+    /// The metrics compared by [`ExecutionReport::compare`], by name: `cycles`, `shards`,
+    /// `memory_words`, `input_bytes`, `output_bytes`, `peak_record_events`, and the count of each
+    /// opcode and syscall as `opcode.<mnemonic>` and `syscall.<name>`.
+    pub fn metrics(&self) -> BTreeMap<String, u64> {
+        let mut metrics = BTreeMap::from([
+            ("cycles".to_string(), self.cycles),
+            ("shards".to_string(), self.shards as u64),
+            ("memory_words".to_string(), self.memory.words as u64),
+            ("input_bytes".to_string(), self.io.input_bytes as u64),
+            ("output_bytes".to_string(), self.io.output_bytes as u64),
+            ("peak_record_events".to_string(), self.peak_record_events),
+        ]);
+        for (mnemonic, count) in self.opcode_counts.iter() {
+            metrics.insert(format!("opcode.{}", mnemonic), *count);
+        }
+        for (name, count) in self.syscall_counts.iter() {
+            metrics.insert(format!("syscall.{}", name), *count);
+        }
+        metrics
+    }
+
+    /// The metrics that grew from `baseline` by more than their threshold, sorted by name. A
+    /// metric absent from one of the reports counts as zero there, and any growth from zero is
+    /// flagged. Metrics that shrank are not regressions.
+    pub fn compare(
+        &self,
+        baseline: &ExecutionReport,
+        thresholds: &RegressionThresholds,
+    ) -> Vec<Regression> {
+        let before = baseline.metrics();
+        let after = self.metrics();
+        let mut names = before.keys().chain(after.keys()).collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .filter_map(|metric| {
+                let before = before.get(metric).copied().unwrap_or(0);
+                let after = after.get(metric).copied().unwrap_or(0);
+                let limit = before as f64 * (1.0 + thresholds.percent(metric) / 100.0);
+                (after > before && after as f64 > limit).then(|| Regression {
+                    metric: metric.clone(),
+                    before,
+                    after,
+                })
+            })
+            .collect()
+    }
+
+    /// The report as JSON.
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).expect("failed to serialize the report")
+    }
+
+    /// Load a report written by [`ExecutionReport::to_json`], returning an error if it was
+    /// written with another version of the schema.
+    pub fn from_json(bytes: &[u8]) -> Result<Self, ReportError> {
+        #[derive(Deserialize)]
+        struct Header {
+            schema_version: u32,
+        }
+
+        let Header { schema_version } = serde_json::from_slice(bytes).map_err(ReportError::Json)?;
+        if schema_version != EXECUTION_REPORT_VERSION {
+            return Err(ReportError::VersionMismatch {
+                expected: EXECUTION_REPORT_VERSION,
+                found: schema_version,
+            });
+        }
+        serde_json::from_slice(bytes).map_err(ReportError::Json)
+    }
+}
+
+impl Runtime {
+    /// The metrics of the execution so far. The opcode and syscall counts and the size of the
+    /// largest shard come from the CPU events, so they are empty unless those are recorded.
+    pub fn execution_report(&self) -> ExecutionReport {
+        let mut opcode_counts = BTreeMap::<String, u64>::new();
+        let mut syscall_counts = BTreeMap::<String, u64>::new();
+        let mut shard_events = BTreeMap::<u32, u64>::new();
+        for event in self.record.cpu_events.iter() {
+            let opcode = event.instruction.opcode;
+            *opcode_counts
+                .entry(opcode.mnemonic().to_string())
+                .or_default() += 1;
+            if opcode == Opcode::ECALL {
+                let name = match SyscallCode::try_from_u32(event.b) {
+                    Some(code) => format!("{:?}", code),
+                    None => format!("0x{:x}", event.b),
+                };
+                *syscall_counts.entry(name).or_default() += 1;
+            }
+            *shard_events.entry(event.shard).or_default() += 1;
+        }
+        ExecutionReport {
+            schema_version: EXECUTION_REPORT_VERSION,
+            cycles: self.state.global_clk as u64,
+            shards: self.state.current_shard,
+            opcode_counts,
+            syscall_counts,
+            memory: self.memory_stats(),
+            io: self.io_stats(),
+            peak_record_events: shard_events.values().copied().max().unwrap_or(0),
+            public_values_digest: self.public_values_digest(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::RuntimeOptions;
+
+    fn fibonacci_report() -> ExecutionReport {
+        let options = RuntimeOptions::default().with_shard_size(1 << 8);
+        let mut runtime = Runtime::new_with_options(fibonacci_program(), options);
+        runtime.run();
+        runtime.execution_report()
+    }
+
+    #[test]
+    fn test_compare_perturbed() {
+        let baseline = fibonacci_report();
+        assert_eq!(baseline.cycles, fibonacci_report().cycles);
+        assert!(baseline.shards > 1);
+        assert_eq!(
+            baseline.opcode_counts.values().sum::<u64>(),
+            baseline.cycles
+        );
+        assert!(baseline.syscall_counts["HALT"] >= 1);
+        // The runtime scales the shard size of the options by 4.
+        assert_eq!(baseline.peak_record_events, 1 << 10);
+        let thresholds = RegressionThresholds::default().with_override("memory_words", 50.0);
+        assert!(baseline.compare(&baseline, &thresholds).is_empty());
+
+        let mut report = baseline.clone();
+        report.cycles = baseline.cycles * 2;
+        // Within the threshold of its override.
+        report.memory.words = baseline.memory.words * 5 / 4;
+        *report.opcode_counts.get_mut("add").unwrap() += 1000;
+        report.syscall_counts.insert("SHA_EXTEND".to_string(), 1);
+        // Improvements are not regressions.
+        report.shards = 1;
+        let regressions = report.compare(&baseline, &thresholds);
+        let add = baseline.opcode_counts["add"];
+        assert_eq!(
+            regressions,
+            vec![
+                Regression {
+                    metric: "cycles".to_string(),
+                    before: baseline.cycles,
+                    after: baseline.cycles * 2,
+                },
+                Regression {
+                    metric: "opcode.add".to_string(),
+                    before: add,
+                    after: add + 1000,
+                },
+                Regression {
+                    metric: "syscall.SHA_EXTEND".to_string(),
+                    before: 0,
+                    after: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let report = fibonacci_report();
+        let json = report.to_json();
+        assert_eq!(ExecutionReport::from_json(&json).unwrap(), report);
+
+        let mut value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        value["schema_version"] = (EXECUTION_REPORT_VERSION + 1).into();
+        let err = ExecutionReport::from_json(&serde_json::to_vec(&value).unwrap()).unwrap_err();
+        assert!(matches!(
+            err,
+            ReportError::VersionMismatch { expected, found }
+                if expected == EXECUTION_REPORT_VERSION && found == EXECUTION_REPORT_VERSION + 1
+        ));
+        assert!(matches!(
+            ExecutionReport::from_json(b"{}"),
+            Err(ReportError::Json(_))
+        ));
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::{BTreeSet, HashSet};
+
+use super::{ExecutionError, Opcode, Program, Register, Runtime, SyscallCode};
+
+impl Program {
+    /// The opcodes of the instructions of the program, whether they are reachable or not.
+    pub fn scan_opcodes(&self) -> BTreeSet<Opcode> {
+        self.instructions
+            .iter()
+            .map(|instruction| instruction.opcode)
+            .collect()
+    }
+
+    /// Whether the program may enter an unconstrained block, see
+    /// [`Program::unconstrained_ecalls`].
+    pub fn uses_unconstrained(&self) -> bool {
+        !self.unconstrained_ecalls().is_empty()
+    }
+
+    /// The pcs of the `ecall`s that enter an unconstrained block, to reject guests before
+    /// executing them when [`RuntimeOptions::forbid_unconstrained`] is set.
+    ///
+    /// This is best-effort: an `ecall` is found if the last instruction writing `t0` before it in
+    /// the same basic block loads the code of `ENTER_UNCONSTRAINED` as an immediate, as
+    /// `li t0, ENTER_UNCONSTRAINED` does. A guest computing the code at run time is not found.
+    ///
+    /// [`RuntimeOptions::forbid_unconstrained`]: super::RuntimeOptions::forbid_unconstrained
+    pub fn unconstrained_ecalls(&self) -> Vec<u32> {
+        let code = SyscallCode::ENTER_UNCONSTRAINED as u32;
+        let t0 = Register::X5 as u32;
+        let mut pcs = Vec::new();
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            if instruction.opcode != Opcode::ECALL {
+                continue;
+            }
+            let loads_code = self.instructions[..index]
+                .iter()
+                .rev()
+                .take_while(|instruction| {
+                    instruction.opcode != Opcode::ECALL
+                        && !instruction.is_branch_instruction()
+                        && !instruction.is_jump_instruction()
+                })
+                .find(|instruction| !instruction.is_store_instruction() && instruction.op_a == t0)
+                .is_some_and(|instruction| {
+                    instruction.opcode == Opcode::ADD
+                        && !instruction.imm_b
+                        && instruction.op_b == 0
+                        && instruction.imm_c
+                        && instruction.op_c == code
+                });
+            if loads_code {
+                pcs.push(self.pc_base.wrapping_add(index as u32 * 4));
+            }
+        }
+        pcs
+    }
+}
+
+impl Runtime {
+    /// Forbid the guest from executing the opcodes in `blacklist`, for deployments where some
+    /// chips are not audited. Executing one stops execution with
+    /// `ExecutionError::OpcodeForbidden`. See [`Runtime::check_restrictions`] to reject such
+    /// programs before executing them.
+    pub fn restrict_opcodes(&mut self, blacklist: HashSet<Opcode>) {
+        self.forbidden_opcodes = blacklist;
+    }
+
+    /// Only allow the guest to invoke the syscalls in `whitelist`, and `HALT` which every guest
+    /// ends with. Invoking another one stops execution with `ExecutionError::SyscallForbidden`.
+    pub fn restrict_syscalls(&mut self, whitelist: HashSet<SyscallCode>) {
+        self.allowed_syscalls = Some(whitelist);
+    }
+
+    /// Restrict the syscalls registered on this runtime, such as third-party precompiles, to the
+    /// memory in their `Syscall::memory_footprint`. An access outside of it is skipped and stops
+    /// execution with `ExecutionError::SyscallFootprintViolation` once the syscall returns. The
+    /// built-in syscalls are trusted and not checked.
+    pub fn set_syscall_footprint_checks(&mut self, enabled: bool) {
+        self.syscall_footprint_checks = enabled;
+    }
+
+    /// Check that the program contains no instruction with a forbidden opcode, reachable or not,
+    /// returning the first one otherwise.
+    pub fn check_restrictions(&self) -> Result<(), ExecutionError> {
+        let opcodes = self.program.scan_opcodes();
+        if opcodes
+            .iter()
+            .all(|opcode| !self.forbidden_opcodes.contains(opcode))
+        {
+            return Ok(());
+        }
+        let (index, instruction) = self
+            .program
+            .instructions
+            .iter()
+            .enumerate()
+            .find(|(_, instruction)| self.forbidden_opcodes.contains(&instruction.opcode))
+            .unwrap();
+        Err(ExecutionError::OpcodeForbidden {
+            opcode: instruction.opcode,
+            pc: self.program.pc_base.wrapping_add(index as u32 * 4),
+        })
+    }
+
+    /// Whether the guest may execute an instruction with `opcode`.
+    #[inline]
+    pub(crate) fn opcode_allowed(&self, opcode: Opcode) -> bool {
+        self.forbidden_opcodes.is_empty() || !self.forbidden_opcodes.contains(&opcode)
+    }
+
+    /// Whether the guest may invoke the syscall `code`.
+    pub(crate) fn syscall_allowed(&self, code: SyscallCode) -> bool {
+        match &self.allowed_syscalls {
+            Some(allowed) => code == SyscallCode::HALT || allowed.contains(&code),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::ops::Range;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runtime::{
+        Instruction, Register, RuntimeOptions, Syscall, SyscallArgs, SyscallContext,
+    };
+
+    /// Divide 42 by 5 and write the quotient to the output stream.
+    fn div_program() -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 29, 0, 42, false, true),
+            Instruction::new(Opcode::DIV, 30, 29, 5, false, true),
+            Instruction::new(Opcode::SW, 30, 0, 0x1000, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, SyscallCode::WRITE as u32, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, 3, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 0x1000, false, true),
+            Instruction::new(Opcode::ADD, 12, 0, 4, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_restrict_opcodes() {
+        let program = div_program();
+        assert!(program.scan_opcodes().contains(&Opcode::DIV));
+        assert!(!program.scan_opcodes().contains(&Opcode::REM));
+
+        let mut runtime = Runtime::new(program.clone());
+        runtime.restrict_opcodes(HashSet::from([Opcode::DIV, Opcode::REM]));
+        let forbidden = Err(ExecutionError::OpcodeForbidden {
+            opcode: Opcode::DIV,
+            pc: 4,
+        });
+        assert_eq!(runtime.check_restrictions(), forbidden);
+        assert_eq!(runtime.try_run(), forbidden);
+        assert_eq!(runtime.register(Register::X30), 0);
+
+        let mut runtime = Runtime::new(program);
+        runtime.restrict_opcodes(HashSet::from([Opcode::REM]));
+        assert_eq!(runtime.check_restrictions(), Ok(()));
+        runtime.run();
+        assert_eq!(runtime.register(Register::X30), 8);
+    }
+
+    #[test]
+    fn test_restrict_syscalls() {
+        let mut runtime = Runtime::new(div_program());
+        runtime.restrict_syscalls(HashSet::from([SyscallCode::WRITE]));
+        runtime.run();
+        assert_eq!(runtime.state.output_stream, 8u32.to_le_bytes());
+
+        let mut runtime = Runtime::new(div_program());
+        runtime.restrict_syscalls(HashSet::from([SyscallCode::HINT_LEN]));
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::SyscallForbidden {
+                code: SyscallCode::WRITE as u32,
+                pc: 28,
+            })
+        );
+        assert!(runtime.state.output_stream.is_empty());
+    }
+
+    /// Fills the `a1` words at `a0` with their index, and the word after them too if `overflow`.
+    struct FillSyscall {
+        overflow: bool,
+    }
+
+    impl Syscall for FillSyscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            let args = ctx.args();
+            let words = args.a1 + self.overflow as u32;
+            for i in 0..words {
+                ctx.mw(args.a0 + 4 * i, i + 1);
+            }
+            0
+        }
+
+        fn memory_footprint(&self, args: SyscallArgs) -> Vec<Range<u32>> {
+            vec![args.a0..args.a0 + 4 * args.a1]
+        }
+    }
+
+    /// Fill 4 words at 0x1000 with the syscall registered for `SHA_EXTEND`.
+    fn fill_runtime(overflow: bool) -> Runtime {
+        let instructions = vec![
+            Instruction::new(
+                Opcode::ADD,
+                5,
+                0,
+                SyscallCode::SHA_EXTEND as u32,
+                false,
+                true,
+            ),
+            Instruction::new(Opcode::ADD, 10, 0, 0x1000, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 4, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime
+            .syscall_map
+            .insert(SyscallCode::SHA_EXTEND, Arc::new(FillSyscall { overflow }));
+        runtime.set_syscall_footprint_checks(true);
+        runtime
+    }
+
+    #[test]
+    fn test_syscall_footprint() {
+        let mut runtime = fill_runtime(false);
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert_eq!(runtime.word(0x100c), 4);
+
+        let mut runtime = fill_runtime(true);
+        let err = runtime.try_run().unwrap_err();
+        assert_eq!(
+            err,
+            ExecutionError::SyscallFootprintViolation {
+                code: SyscallCode::SHA_EXTEND,
+                addr: 0x1010,
+                pc: 12,
+            }
+        );
+        assert!(err.to_string().contains("SHA_EXTEND"));
+        assert!(err.to_string().contains("addr=0x1010"));
+        // The accesses in the footprint are made, the one outside of it is not.
+        assert_eq!(runtime.word(0x100c), 4);
+        assert_eq!(runtime.word(0x1010), 0);
+
+        let mut runtime = fill_runtime(true);
+        runtime.set_syscall_footprint_checks(false);
+        runtime.run();
+        assert_eq!(runtime.word(0x1010), 5);
+    }
+
+    #[test]
+    fn test_builtin_syscalls_unchecked() {
+        let mut runtime = Runtime::new(div_program());
+        runtime.set_syscall_footprint_checks(true);
+        runtime.run();
+        assert_eq!(runtime.state.output_stream, 8u32.to_le_bytes());
+    }
+
+    /// Writes 1 to 0x1000 in an unconstrained block, then exits with the word at 0x1000.
+    fn unconstrained_program() -> Program {
+        let ecall = |code: SyscallCode| {
+            [
+                Instruction::new(Opcode::ADD, 5, 0, code as u32, false, true),
+                Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            ]
+        };
+        let mut instructions = vec![Instruction::new(Opcode::ADD, 6, 0, 1, false, true)];
+        instructions.extend(ecall(SyscallCode::ENTER_UNCONSTRAINED));
+        instructions.extend([
+            Instruction::new(Opcode::BEQ, 10, 0, 16, false, true),
+            Instruction::new(Opcode::SW, 6, 0, 0x1000, false, true),
+        ]);
+        instructions.extend(ecall(SyscallCode::EXIT_UNCONSTRAINED));
+        instructions.push(Instruction::new(Opcode::LW, 10, 0, 0x1000, false, true));
+        instructions.extend(ecall(SyscallCode::HALT));
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_forbid_unconstrained() {
+        let program = unconstrained_program();
+        assert_eq!(program.unconstrained_ecalls(), vec![8]);
+        assert!(program.uses_unconstrained());
+        assert!(!div_program().uses_unconstrained());
+
+        let mut runtime = Runtime::new(program.clone());
+        runtime.run();
+        assert_eq!(runtime.register(Register::X10), 0);
+
+        let options = RuntimeOptions::default().with_forbid_unconstrained(true);
+        let mut runtime = Runtime::new_with_options(program, options.clone());
+        let err = runtime.try_run().unwrap_err();
+        assert_eq!(err, ExecutionError::UnconstrainedForbidden { pc: 8 });
+        assert!(!runtime.unconstrained);
+        assert!(err.to_string().contains("pc=0x8"));
+
+        let mut runtime = Runtime::new_with_options(div_program(), options);
+        runtime.run();
+        assert_eq!(runtime.state.output_stream, 8u32.to_le_bytes());
+    }
+}
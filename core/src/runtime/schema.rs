@@ -0,0 +1,180 @@
+use core::fmt::{Display, Formatter};
+
+/// The type of a frame of the input stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameType {
+    /// A little-endian u32.
+    U32,
+
+    /// Raw bytes of the given length.
+    Bytes(u32),
+
+    /// A bincode-serialized value whose type has the given [`type_hash`].
+    Bincode(u32),
+}
+
+/// A 32-bit FNV-1a hash. The guest computes frame tags with the same function.
+const fn fnv1a(bytes: &[u8]) -> u32 {
+    let mut hash = 0x811c9dc5u32;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x01000193);
+        i += 1;
+    }
+    hash
+}
+
+/// A hash of the name of `T`, used to tag bincode frames.
+///
+/// Type names are only stable for a given compiler version, so the host and the guest must be
+/// built with the same toolchain for tags of bincode frames to match.
+pub fn type_hash<T: ?Sized>() -> u32 {
+    fnv1a(core::any::type_name::<T>().as_bytes())
+}
+
+impl FrameType {
+    /// The compact tag identifying this frame type, which the guest passes to the `READ_FRAME`
+    /// syscall before reading a frame.
+    pub fn tag(&self) -> u32 {
+        let (kind, param) = match self {
+            FrameType::U32 => (0u8, 0),
+            FrameType::Bytes(len) => (1, *len),
+            FrameType::Bincode(hash) => (2, *hash),
+        };
+        let [b0, b1, b2, b3] = param.to_le_bytes();
+        fnv1a(&[kind, b0, b1, b2, b3])
+    }
+}
+
+impl Display for FrameType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FrameType::U32 => write!(f, "u32"),
+            FrameType::Bytes(len) => write!(f, "bytes[{}]", len),
+            FrameType::Bincode(hash) => write!(f, "bincode(0x{:08x})", hash),
+        }
+    }
+}
+
+/// The sequence of frames the host writes to the input stream, see
+/// [`Runtime::set_input_schema`](super::Runtime::set_input_schema).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputSchema {
+    pub frames: Vec<FrameType>,
+}
+
+impl InputSchema {
+    pub fn new(frames: Vec<FrameType>) -> Self {
+        Self { frames }
+    }
+
+    /// Append a u32 frame.
+    pub fn u32(mut self) -> Self {
+        self.frames.push(FrameType::U32);
+        self
+    }
+
+    /// Append a frame of `len` raw bytes.
+    pub fn bytes(mut self, len: u32) -> Self {
+        self.frames.push(FrameType::Bytes(len));
+        self
+    }
+
+    /// Append a bincode frame holding a `T`.
+    pub fn bincode<T: ?Sized>(mut self) -> Self {
+        self.frames.push(FrameType::Bincode(type_hash::<T>()));
+        self
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{
+        ExecutionError, Instruction, Opcode, Program, Register, Runtime, SyscallCode,
+    };
+
+    /// Read the frame of type `frame` and then its first word into `rd`.
+    fn read_frame(frame: FrameType, len: u32, rd: u32) -> Vec<Instruction> {
+        vec![
+            Instruction::new(
+                Opcode::ADD,
+                5,
+                0,
+                SyscallCode::READ_FRAME as u32,
+                false,
+                true,
+            ),
+            Instruction::new(Opcode::ADD, 10, 0, frame.tag(), false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, SyscallCode::LWA as u32, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, len, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::ADD, rd, 10, 0, false, true),
+        ]
+    }
+
+    /// A guest that reads a u32 and then 4 bytes.
+    fn program() -> Program {
+        let mut instructions = read_frame(FrameType::U32, 4, 20);
+        instructions.extend(read_frame(FrameType::Bytes(4), 4, 21));
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_matching_schema() {
+        let mut runtime = Runtime::new(program());
+        runtime.set_input_schema(InputSchema::default().u32().bytes(4));
+        runtime.write_stdin_u32(7);
+        runtime.write_stdin_slice(&[1, 2, 3, 4]);
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert_eq!(runtime.register(Register::X20), 7);
+        assert_eq!(runtime.register(Register::X21), 0x04030201);
+
+        // Without a schema, frames are not tagged and not checked.
+        let mut runtime = Runtime::new(program());
+        runtime.write_stdin_slice(&[1, 2, 3, 4]);
+        runtime.write_stdin_u32(7);
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert_eq!(runtime.register(Register::X20), 0x04030201);
+    }
+
+    #[test]
+    fn test_swapped_frames() {
+        let mut runtime = Runtime::new(program());
+        runtime.set_input_schema(InputSchema::default().bytes(4).u32());
+        runtime.write_stdin_slice(&[1, 2, 3, 4]);
+        runtime.write_stdin_u32(7);
+        let err = runtime.try_run().unwrap_err();
+        assert_eq!(
+            err,
+            ExecutionError::InputFrameMismatch {
+                frame: 0,
+                expected: FrameType::U32.tag(),
+                found: FrameType::Bytes(4).tag(),
+                pc: 8,
+            }
+        );
+        assert!(err.to_string().starts_with("input frame 0"));
+        assert_eq!(runtime.state.input_stream_ptr, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "input schema expects bytes[4]")]
+    fn test_write_against_schema() {
+        let mut runtime = Runtime::new(program());
+        runtime.set_input_schema(InputSchema::default().bytes(4));
+        runtime.write_stdin(&7u64);
+    }
+
+    #[test]
+    fn test_frame_tags() {
+        assert_ne!(FrameType::U32.tag(), FrameType::Bytes(4).tag());
+        assert_ne!(FrameType::Bytes(4).tag(), FrameType::Bytes(5).tag());
+        assert_eq!(
+            FrameType::Bincode(type_hash::<u64>()),
+            InputSchema::default().bincode::<u64>().frames[0]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use hashbrown::HashMap;
+use nohash_hasher::BuildNoHashHasher;
+
+use super::{ExecutionError, Runtime};
+
+/// A range of memory the guest stores to for the host to read while it runs, see
+/// [`RuntimeOptions::scratch_region`](super::RuntimeOptions::scratch_region).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScratchRegion {
+    pub(crate) addr: u32,
+
+    /// The words of the region, zero until the guest stores to them.
+    words: Vec<u32>,
+}
+
+impl ScratchRegion {
+    pub(crate) fn new(addr: u32, len: u32) -> Self {
+        Self {
+            addr,
+            words: vec![0; (len / 4) as usize],
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.words.fill(0);
+    }
+
+    /// The index of the word at the aligned address `addr`, if it is in the region.
+    #[inline]
+    fn index(&self, addr: u32) -> Option<usize> {
+        let index = (addr.wrapping_sub(self.addr) / 4) as usize;
+        (addr >= self.addr && index < self.words.len()).then_some(index)
+    }
+}
+
+impl Runtime {
+    /// The words of the scratch region as the guest last stored them, empty if there is none.
+    /// Only reads the runtime, so it can be called between any two cycles.
+    pub fn scratch(&self) -> &[u32] {
+        self.scratch
+            .as_ref()
+            .map_or(&[], |scratch| scratch.words.as_slice())
+    }
+
+    /// The value of the word at the aligned address `addr` if it is in the scratch region.
+    #[inline]
+    pub(crate) fn scratch_word(&self, addr: u32) -> Option<u32> {
+        let scratch = self.scratch.as_ref()?;
+        scratch.index(addr).map(|index| scratch.words[index])
+    }
+
+    /// Load the word at the aligned address `addr` of the scratch region for the guest, which
+    /// stops execution with [`ExecutionError::ScratchRead`] outside of unconstrained blocks.
+    #[inline]
+    pub(crate) fn load_scratch(&mut self, addr: u32) -> Option<u32> {
+        let value = self.scratch_word(addr)?;
+        if self.unconstrained {
+            return Some(value);
+        }
+        self.trap(ExecutionError::ScratchRead {
+            addr,
+            pc: self.state.pc,
+        });
+        Some(0)
+    }
+
+    /// Store `value` to the word at the aligned address `addr` if it is in the scratch region,
+    /// returning whether it is. Stores in unconstrained blocks are rolled back when they exit,
+    /// like every other write.
+    #[inline]
+    pub(crate) fn store_scratch(&mut self, addr: u32, value: u32) -> bool {
+        let Some(scratch) = &mut self.scratch else {
+            return false;
+        };
+        let Some(index) = scratch.index(addr) else {
+            return false;
+        };
+        let prev_value = std::mem::replace(&mut scratch.words[index], value);
+        if self.unconstrained {
+            self.unconstrained_state
+                .scratch_diff
+                .entry(addr)
+                .or_insert(prev_value);
+        }
+        true
+    }
+
+    /// Restore the words of the scratch region to the values in `diff`, by address.
+    pub(crate) fn restore_scratch(&mut self, diff: HashMap<u32, u32, BuildNoHashHasher<u32>>) {
+        let Some(scratch) = &mut self.scratch else {
+            return;
+        };
+        for (addr, value) in diff {
+            if let Some(index) = scratch.index(addr) {
+                scratch.words[index] = value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::cpu::MemoryRecordEnum;
+    use crate::runtime::{Instruction, Opcode, Program, Register, RuntimeOptions, SyscallCode};
+    use crate::utils::asm::assemble;
+
+    const SCRATCH_ADDR: u32 = 0x3000;
+
+    /// Stores the iteration count of a loop of 10 to the second word of the scratch region, and
+    /// the result of the loop to 0x4000.
+    fn progress_program() -> Program {
+        assemble(
+            "        li   t0, 0
+                     li   t1, 10
+                     li   t2, 0x3000
+             loop:   addi t0, t0, 1
+                     sb   t0, 4(t2)
+                     sw   t0, 4(t2)
+                     bne  t0, t1, loop
+                     sw   t0, 0x4000(zero)",
+            0,
+        )
+        .unwrap()
+    }
+
+    fn scratch_runtime(program: Program) -> Runtime {
+        let options = RuntimeOptions::default().with_scratch_region(SCRATCH_ADDR, 16);
+        Runtime::new_with_options(program, options)
+    }
+
+    #[test]
+    fn test_progress_counter() {
+        let mut runtime = scratch_runtime(progress_program());
+        runtime.initialize();
+        let mut observed = vec![];
+        while !runtime.is_done() {
+            runtime.step().unwrap();
+            let counter = runtime.scratch()[1];
+            if observed.last() != Some(&counter) {
+                observed.push(counter);
+            }
+        }
+        assert_eq!(observed, (0..=10).collect::<Vec<_>>());
+        assert_eq!(runtime.scratch(), [0, 10, 0, 0]);
+        assert_eq!(runtime.word(0x4000), 10);
+        runtime.finalize();
+
+        let record = &runtime.record;
+        let scratch = SCRATCH_ADDR..SCRATCH_ADDR + 16;
+        assert!(record
+            .first_memory_record
+            .iter()
+            .chain(record.last_memory_record.iter())
+            .all(|(addr, _, _)| !scratch.contains(addr)));
+        let stores = record
+            .cpu_events
+            .iter()
+            .filter(|event| event.instruction.is_store_instruction())
+            .map(|event| event.memory_record)
+            .collect::<Vec<_>>();
+        assert_eq!(stores.len(), 21);
+        assert!(stores[..20].iter().all(Option::is_none));
+        assert!(matches!(stores[20], Some(MemoryRecordEnum::Write(_))));
+    }
+
+    #[test]
+    fn test_unconstrained_stores_rolled_back() {
+        let ecall = |code: SyscallCode| {
+            [
+                Instruction::new(Opcode::ADD, 5, 0, code as u32, false, true),
+                Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            ]
+        };
+        let mut instructions = vec![
+            Instruction::new(Opcode::ADD, 7, 0, SCRATCH_ADDR, false, true),
+            Instruction::new(Opcode::ADD, 6, 0, 1, false, true),
+            Instruction::new(Opcode::SW, 6, 7, 0, false, true),
+        ];
+        instructions.extend(ecall(SyscallCode::ENTER_UNCONSTRAINED));
+        // Skip the block when it is entered again after exiting.
+        instructions.push(Instruction::new(Opcode::BEQ, 10, 0, 16, false, true));
+        instructions.extend([
+            Instruction::new(Opcode::ADD, 6, 0, 2, false, true),
+            Instruction::new(Opcode::SW, 6, 7, 0, false, true),
+            Instruction::new(Opcode::SW, 6, 7, 4, false, true),
+        ]);
+        instructions.extend(ecall(SyscallCode::EXIT_UNCONSTRAINED));
+        instructions.push(Instruction::new(Opcode::SW, 6, 7, 8, false, true));
+
+        let mut runtime = scratch_runtime(Program::new(instructions, 0, 0));
+        runtime.initialize();
+        let mut in_block = vec![];
+        while !runtime.is_done() {
+            runtime.step().unwrap();
+            if runtime.unconstrained {
+                in_block = runtime.scratch().to_vec();
+            }
+        }
+        assert_eq!(in_block, [2, 2, 0, 0]);
+        assert_eq!(runtime.scratch(), [1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_scratch_load() {
+        let program = assemble(
+            "li   t2, 0x3000
+             sw   t2, 8(t2)
+             lw   t0, 8(t2)",
+            0,
+        )
+        .unwrap();
+        let mut runtime = scratch_runtime(program.clone());
+        assert_eq!(
+            runtime.try_run(),
+            Err(ExecutionError::ScratchRead {
+                addr: 0x3008,
+                pc: 8
+            })
+        );
+        assert_eq!(runtime.scratch()[2], 0x3000);
+
+        // Without a scratch region the region is ordinary memory.
+        let mut runtime = Runtime::new(program);
+        runtime.run();
+        assert_eq!(runtime.register(Register::X5), 0x3000);
+        assert!(runtime.scratch().is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+use super::{Opcode, Register, Runtime, ShardingConfig, SyscallCode};
+
+/// How the runtime places the boundaries of its shards, see [`Runtime::set_sharding_config`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ShardLayout {
+    /// See [`ShardingConfig::round_to_pow2`].
+    round_to_pow2: bool,
+
+    /// See [`ShardingConfig::merge_small_final_shard`].
+    merge_threshold: Option<f64>,
+
+    /// The maximum number of events of each class in a shard.
+    caps: Vec<(&'static str, usize)>,
+
+    /// The number of events of each class in the record when the current shard started.
+    shard_start: Vec<(&'static str, usize)>,
+
+    /// Whether the current shard was extended to the end of execution.
+    merged: bool,
+}
+
+impl ShardLayout {
+    fn new(config: &ShardingConfig) -> Self {
+        Self {
+            round_to_pow2: config.round_to_pow2,
+            merge_threshold: config.merge_small_final_shard,
+            caps: vec![
+                ("add", config.add_len),
+                ("sub", config.sub_len),
+                ("mul", config.mul_len),
+                ("bitwise", config.bitwise_len),
+                ("shift_left", config.shift_left_len),
+                ("shift_right", config.shift_right_len),
+                ("divrem", config.divrem_len),
+                ("lt", config.lt_len),
+                ("field", config.field_len),
+                ("keccak_permute", config.keccak_len),
+                ("weierstrass_add", config.weierstrass_add_len),
+                ("weierstrass_double", config.weierstrass_double_len),
+            ],
+            shard_start: Vec::new(),
+            merged: false,
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.shard_start.clear();
+        self.merged = false;
+    }
+
+    /// Whether the events of the current shard, `current` in the record, and those of the rest of
+    /// the execution, `tail`, fit in one shard.
+    fn within_caps(
+        &self,
+        current: &[(&'static str, usize)],
+        tail: &[(&'static str, usize)],
+    ) -> bool {
+        self.caps.iter().all(|&(class, cap)| {
+            count(current, class) - count(&self.shard_start, class) + count(tail, class) <= cap
+        })
+    }
+}
+
+fn count(counts: &[(&'static str, usize)], class: &str) -> usize {
+    counts
+        .iter()
+        .find(|(name, _)| *name == class)
+        .map_or(0, |(_, count)| *count)
+}
+
+impl Runtime {
+    /// Place the boundaries of the shards as set by the [`ShardingConfig::round_to_pow2`] and
+    /// [`ShardingConfig::merge_small_final_shard`] options of `config`, within the event caps of
+    /// its `*_len` fields. The size of the shards is still [`Runtime::shard_size`].
+    pub fn set_sharding_config(&mut self, config: &ShardingConfig) {
+        self.sharding = Some(ShardLayout::new(config));
+    }
+
+    /// The number of instructions executed in the current shard.
+    fn shard_cycles(&self) -> u32 {
+        let entry = self
+            .record
+            .shard_boundaries
+            .last()
+            .map_or(0, |boundary| boundary.entry_global_clk);
+        self.state.global_clk - entry
+    }
+
+    /// Whether the current shard ends after the instruction that was just executed, because the
+    /// next one does not fit in it or the guest asked for a new shard.
+    pub(crate) fn shard_ends(&mut self) -> bool {
+        let layout = self.sharding.as_ref();
+        if layout.is_some_and(|layout| layout.merged) {
+            return false;
+        }
+        // We multiply by 4 because clk is incremented by 4 for each normal instruction.
+        let clk = self.upcoming_syscall_cycles() + self.state.clk;
+        let full = clk >= self.shard_size * 4;
+        let hinted = self.shard_hint_pending && self.state.clk >= self.min_hinted_shard_cycles * 4;
+        let ends = if layout.is_some_and(|layout| layout.round_to_pow2) {
+            // A shard only ends at a power of two of cycles, when the next power of two does not
+            // fit in it. Syscalls taking extra cycles may still fill it before.
+            let cycles = self.shard_cycles();
+            let target = 1 << (31 - self.shard_size.leading_zeros());
+            let doubled = clk + 4 * (cycles - 1) >= self.shard_size * 4;
+            full || cycles >= target || (cycles.is_power_of_two() && (hinted || doubled))
+        } else {
+            full || hinted
+        };
+        ends && !self.merge_final_shard()
+    }
+
+    /// Record the number of events in the record at the start of a new shard.
+    pub(crate) fn start_shard_layout(&mut self) {
+        if let Some(layout) = &mut self.sharding {
+            if layout.merge_threshold.is_some() {
+                layout.shard_start = self.record.stats().event_counts();
+            }
+        }
+    }
+
+    /// Whether the rest of the execution is short enough to be merged into the current shard,
+    /// in which case the shard is extended to the end of execution.
+    fn merge_final_shard(&mut self) -> bool {
+        let Some(threshold) = self
+            .sharding
+            .as_ref()
+            .and_then(|layout| layout.merge_threshold)
+        else {
+            return false;
+        };
+        let current = self.record.stats().event_counts();
+        let max_cycles = (self.shard_size as f64 * threshold) as u32;
+        let Some(tail) = self.lookahead(max_cycles) else {
+            return false;
+        };
+        let layout = self.sharding.as_mut().unwrap();
+        layout.merged = layout.within_caps(&current, &tail);
+        layout.merged
+    }
+
+    /// Execute up to `max_cycles` instructions ahead in a fork of the state, like an unconstrained
+    /// block, and roll them back. Returns the number of events of each class of the rest of the
+    /// execution if it ends within them.
+    ///
+    /// The lookahead stops at the first `ecall` other than `HALT`, since syscalls may have effects
+    /// outside of the runtime, such as reading the input, so an execution making such calls near
+    /// its end is never merged.
+    fn lookahead(&mut self, max_cycles: u32) -> Option<Vec<(&'static str, usize)>> {
+        self.enter_unconstrained();
+        let mut cycles = 0;
+        let ends = loop {
+            if self.is_done() {
+                break true;
+            }
+            if cycles == max_cycles || !self.pc_in_program() {
+                break false;
+            }
+            let instruction = self.fetch();
+            if instruction.opcode == Opcode::ECALL {
+                // The `HALT` is the last cycle of the execution.
+                break self.register(Register::X5) == SyscallCode::HALT as u32;
+            }
+            self.execute(instruction);
+            if self.pending_error.take().is_some() {
+                break false;
+            }
+            self.state.global_clk += 1;
+            self.state.clk += 4;
+            cycles += 1;
+        };
+        let tail = self.record.stats().event_counts();
+        self.exit_unconstrained();
+        ends.then_some(tail)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::{ExecutionRecord, Extensions, Instruction, Program};
+    use crate::stark::{LocalProver, RiscvStark};
+    use crate::utils::asm::assemble;
+    use crate::utils::{BabyBearBlake3, StarkUtils};
+
+    /// The number of CPU events of each shard.
+    fn shard_cycles(runtime: &Runtime) -> Vec<usize> {
+        let mut shards = BTreeMap::<u32, usize>::new();
+        for event in runtime.record.cpu_events.iter() {
+            *shards.entry(event.shard).or_default() += 1;
+        }
+        shards.into_values().collect()
+    }
+
+    /// The number of CPU events of each record.
+    fn record_cycles(shards: &[ExecutionRecord]) -> Vec<usize> {
+        shards.iter().map(|shard| shard.cpu_events.len()).collect()
+    }
+
+    /// Prove the record of `runtime`, as split by the machine, and verify the proof.
+    fn prove_and_verify(runtime: Runtime) {
+        let machine = RiscvStark::new(BabyBearBlake3::new());
+        let (pk, vk) = machine.setup(runtime.program.as_ref());
+        let mut challenger = machine.config().challenger();
+        let proof = machine.prove::<LocalProver<_>>(&pk, runtime.record, &mut challenger);
+        let mut challenger = machine.config().challenger();
+        machine.verify(&vk, &proof, &mut challenger).unwrap();
+    }
+
+    fn run(program: Program, shard_size: u32, config: ShardingConfig) -> Runtime {
+        let mut runtime = Runtime::new(program);
+        runtime.shard_size = shard_size;
+        runtime.set_sharding_config(&config);
+        runtime.run();
+        runtime.record.check_access_ordering().unwrap();
+        runtime
+    }
+
+    /// Counts down from `n` in a loop of 2 instructions, taking `2 * n + 1` cycles.
+    fn countdown_program(n: u32) -> Program {
+        assemble(
+            &format!(
+                "       li   t0, {}
+                 loop:  addi t0, t0, -1
+                        bne  t0, zero, loop",
+                n
+            ),
+            0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pow2_shard_cycles() {
+        let config = ShardingConfig {
+            round_to_pow2: true,
+            ..ShardingConfig::default()
+        };
+        let runtime = run(fibonacci_program(), 100, config);
+        let cycles = shard_cycles(&runtime);
+        assert!(cycles.len() > 2);
+        let (last, full) = cycles.split_last().unwrap();
+        assert!(full.iter().all(|&cycles| cycles == 64));
+        assert!(*last <= 64);
+
+        // Shards without rounding take the whole shard size.
+        let runtime = run(fibonacci_program(), 100, ShardingConfig::default());
+        assert_eq!(shard_cycles(&runtime)[0], 100);
+    }
+
+    #[test]
+    fn test_merge_small_final_shard() {
+        // 4 shards of 256 cycles and a tail of 13, 5% of a shard.
+        let program = countdown_program(518);
+        let runtime = run(program.clone(), 256, ShardingConfig::default());
+        assert_eq!(shard_cycles(&runtime), vec![256, 256, 256, 256, 13]);
+
+        let config = ShardingConfig {
+            merge_small_final_shard: Some(0.1),
+            ..ShardingConfig::default()
+        };
+        let merged = run(program.clone(), 256, config);
+        assert_eq!(shard_cycles(&merged), vec![256, 256, 256, 269]);
+        assert_eq!(merged.state.current_shard, 4);
+        assert_eq!(merged.register(Register::X5), 0);
+
+        // A tail above the threshold is kept in its own shard.
+        let config = ShardingConfig {
+            merge_small_final_shard: Some(0.04),
+            ..ShardingConfig::default()
+        };
+        let runtime = run(program.clone(), 256, config);
+        assert_eq!(shard_cycles(&runtime).len(), 5);
+
+        // So is a tail whose events exceed the caps of the merged shard: the last shard has 128
+        // `addi` and the tail 6 more.
+        let config = ShardingConfig {
+            merge_small_final_shard: Some(0.1),
+            add_len: 130,
+            ..ShardingConfig::default()
+        };
+        let runtime = run(program, 256, config);
+        assert_eq!(shard_cycles(&runtime).len(), 5);
+    }
+
+    #[test]
+    fn test_shard_pow2_record() {
+        let pow2_config = |shard_size| ShardingConfig {
+            shard_size,
+            round_to_pow2: true,
+            ..ShardingConfig::default()
+        };
+        let runtime = run(fibonacci_program(), 100, pow2_config(100));
+        let shards = runtime.record.clone().shard(&pow2_config(100));
+        assert_eq!(record_cycles(&shards), shard_cycles(&runtime));
+        assert!(shards.iter().all(|shard| shard.cpu_events.len() <= 64));
+        prove_and_verify(runtime);
+    }
+
+    #[test]
+    fn test_shard_merged_record() {
+        let merge_config = || ShardingConfig {
+            shard_size: 256,
+            merge_small_final_shard: Some(0.1),
+            ..ShardingConfig::default()
+        };
+        let runtime = run(countdown_program(518), 256, merge_config());
+        let shards = runtime.record.clone().shard(&merge_config());
+        assert_eq!(record_cycles(&shards), vec![256, 256, 256, 269]);
+        assert_eq!(
+            shards.iter().map(|shard| shard.index).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+
+        // Without merging, a final shard above the shard size is split.
+        let config = ShardingConfig {
+            shard_size: 256,
+            ..ShardingConfig::default()
+        };
+        let shards = runtime.record.clone().shard(&config);
+        assert_eq!(record_cycles(&shards), vec![256, 256, 256, 256, 13]);
+        prove_and_verify(runtime);
+    }
+
+    #[test]
+    fn test_shard_side_tables() {
+        // An `auipc` and a `cpop` in every iteration of a loop of 100, taking 401 cycles.
+        let mut program = Program::new(
+            vec![
+                Instruction::new(Opcode::ADD, 5, 0, 100, false, true),
+                Instruction::new(Opcode::AUIPC, 6, 0, 0, true, true),
+                Instruction::new(Opcode::CPOP, 7, 6, 0, false, true),
+                Instruction::new(Opcode::ADD, 5, 5, -1i32 as u32, false, true),
+                Instruction::new(Opcode::BNE, 5, 0, -12i32 as u32, false, true),
+            ],
+            0,
+            0,
+        );
+        program.extensions = Extensions::ZBB;
+        let runtime = run(program, 64, ShardingConfig::default());
+        assert_eq!(shard_cycles(&runtime), vec![64, 64, 64, 64, 64, 64, 17]);
+
+        // The shards of the runtime are split further at the shard size.
+        let config = ShardingConfig {
+            shard_size: 48,
+            ..ShardingConfig::default()
+        };
+        let shards = runtime.record.shard(&config);
+        assert_eq!(
+            record_cycles(&shards),
+            vec![48, 16, 48, 16, 48, 16, 48, 16, 48, 16, 48, 16, 17]
+        );
+        let mut auipc_total = 0;
+        let mut bitmanip_total = 0;
+        for shard in shards.iter() {
+            let keys = |opcode| {
+                shard
+                    .cpu_events
+                    .iter()
+                    .filter(|event| event.instruction.opcode == opcode)
+                    .map(|event| (event.shard, event.clk))
+                    .collect::<Vec<_>>()
+            };
+            let auipc = shard
+                .auipc_events
+                .iter()
+                .map(|event| (event.shard, event.clk))
+                .collect::<Vec<_>>();
+            let bitmanip = shard
+                .bitmanip_events
+                .iter()
+                .map(|event| (event.shard, event.clk))
+                .collect::<Vec<_>>();
+            assert_eq!(auipc, keys(Opcode::AUIPC));
+            assert_eq!(bitmanip, keys(Opcode::CPOP));
+            auipc_total += auipc.len();
+            bitmanip_total += bitmanip.len();
+        }
+        assert_eq!((auipc_total, bitmanip_total), (100, 100));
+    }
+}
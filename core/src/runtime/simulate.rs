@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use nohash_hasher::BuildNoHashHasher;
+use serde::{Deserialize, Serialize};
+
+use super::{Error, IoStats, Program, RecordFilter, Runtime, RuntimeOptions, NUM_REGISTERS};
+
+/// The value each word written had before its first write.
+pub(crate) type WriteSet = HashMap<u32, u32, BuildNoHashHasher<u32>>;
+
+/// A word of memory an execution changed, see [`Runtime::state_diff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WordDiff {
+    pub addr: u32,
+
+    /// The value of the word before the execution, from the memory image or zero.
+    pub old: u32,
+
+    /// The value of the word once the execution finished or stopped.
+    pub new: u32,
+}
+
+/// The outcome of a simulated execution, see [`simulate`]. Its size only depends on the words
+/// written and the output, not on the memory read or the length of the execution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimulationResult {
+    /// The number of instructions executed, excluding those of unconstrained blocks and the one
+    /// that stopped execution with an error.
+    pub cycles: u64,
+
+    /// The error that stopped execution, formatted, or `None` if it succeeded.
+    pub error: Option<String>,
+
+    /// The exit code the guest halted with, if it halted.
+    pub exit_code: Option<u32>,
+
+    /// The words of memory the execution changed, up to the error if it failed.
+    pub diff: Vec<WordDiff>,
+
+    /// The bytes written to the output stream.
+    pub output: Vec<u8>,
+
+    pub public_values_digest: [u8; 32],
+
+    pub io: IoStats,
+}
+
+impl SimulationResult {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Execute `program` on the input `stdin` and express its outcome as the words of memory it
+/// changed, for pre-simulating guests whose records are not needed.
+///
+/// Only the CPU events and the memory records are recorded, whatever the record filter of
+/// `options`. Errors stopping the execution are part of the result, so that the changes made
+/// before them are kept: this only fails if the runtime cannot be created, see
+/// [`Runtime::try_new_with_options`].
+pub fn simulate(
+    program: Program,
+    stdin: &[u8],
+    options: RuntimeOptions,
+) -> Result<SimulationResult, Error> {
+    let options = options.with_record_filter(RecordFilter::CPU | RecordFilter::MEMORY);
+    let mut runtime = Runtime::try_new_with_options(program, options)?;
+    runtime.set_write_set_tracking(true);
+    runtime.write_stdin_slice(stdin);
+    let error = runtime.try_run().err();
+    Ok(SimulationResult {
+        cycles: runtime.state.global_clk as u64,
+        error: error.map(|error| error.to_string()),
+        exit_code: runtime.exit_code(),
+        diff: runtime.state_diff(),
+        output: runtime.state.output_stream.clone(),
+        public_values_digest: runtime.public_values_digest(),
+        io: runtime.io_stats(),
+    })
+}
+
+impl Runtime {
+    /// Keep the value every word of memory had before it was first written, so that
+    /// [`Runtime::state_diff`] only has to look at the words written. The writes are tracked
+    /// from the start of the execution, so this must be set before it starts.
+    pub fn set_write_set_tracking(&mut self, enabled: bool) {
+        self.write_set = enabled.then(HashMap::default);
+    }
+
+    /// Record that the word at `addr`, whose value was `old`, was written.
+    #[inline]
+    pub(crate) fn record_first_write(&mut self, addr: u32, old: u32) {
+        if let Some(write_set) = &mut self.write_set {
+            if addr >= NUM_REGISTERS {
+                write_set.entry(addr).or_insert(old);
+            }
+        }
+    }
+
+    /// The words of memory whose value differs from the one they had before the execution,
+    /// sorted by address, leaving out the registers. Only the words written are compared, so
+    /// this is cheap whatever the size of the memory.
+    ///
+    /// Empty unless [`Runtime::set_write_set_tracking`] was enabled before the execution.
+    pub fn state_diff(&self) -> Vec<WordDiff> {
+        let Some(write_set) = &self.write_set else {
+            return Vec::new();
+        };
+        let mut diff = write_set
+            .iter()
+            .filter_map(|(&addr, &old)| {
+                let new = self
+                    .state
+                    .memory
+                    .get(&addr)
+                    .map_or(0, |(value, _, _)| *value);
+                (new != old).then_some(WordDiff { addr, old, new })
+            })
+            .collect::<Vec<_>>();
+        diff.sort_by_key(|word| word.addr);
+        diff
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::ExecutionError;
+    use crate::utils::asm::assemble;
+    use crate::utils::tests::FIBONACCI_IO_ELF;
+
+    const STATE: u32 = 0x10000;
+
+    /// Increments the first word of the state, overwrites the third and stores to the fifth,
+    /// which is not in the image, then stores the second word back unchanged. With `fail`, it
+    /// faults before the last stores.
+    fn transaction_program(fail: bool) -> Program {
+        let mut program = assemble(
+            &format!(
+                "       li   t0, {}
+                        lw   t1, 0(t0)
+                        addi t1, t1, 1
+                        sw   t1, 0(t0)
+                        li   t2, 7
+                        sw   t2, 8(t0)
+                        {}
+                        sw   t2, 16(t0)
+                        lw   t3, 4(t0)
+                        sw   t3, 4(t0)",
+                STATE,
+                if fail { "unimp" } else { "nop" }
+            ),
+            0,
+        )
+        .unwrap();
+        for i in 0..4 {
+            program.memory_image.insert(STATE + 4 * i, 10 * (i + 1));
+        }
+        program
+    }
+
+    #[test]
+    fn test_simulate_diff() {
+        let result = simulate(transaction_program(false), &[], RuntimeOptions::default()).unwrap();
+        assert!(result.succeeded());
+        assert_eq!(
+            result.diff,
+            vec![
+                WordDiff {
+                    addr: STATE,
+                    old: 10,
+                    new: 11
+                },
+                WordDiff {
+                    addr: STATE + 8,
+                    old: 30,
+                    new: 7
+                },
+                WordDiff {
+                    addr: STATE + 16,
+                    old: 0,
+                    new: 7
+                },
+            ]
+        );
+        assert_eq!(result.cycles, 10);
+
+        let bytes = bincode::serialize(&result).unwrap();
+        assert!(bytes.len() < 256);
+        assert_eq!(
+            bincode::deserialize::<SimulationResult>(&bytes).unwrap(),
+            result
+        );
+    }
+
+    #[test]
+    fn test_simulate_failure() {
+        let result = simulate(transaction_program(true), &[], RuntimeOptions::default()).unwrap();
+        assert!(!result.succeeded());
+        assert_eq!(
+            result.error,
+            Some(ExecutionError::Unimplemented { pc: 24 }.to_string())
+        );
+        assert_eq!(result.cycles, 6);
+        let addrs = result.diff.iter().map(|word| word.addr).collect::<Vec<_>>();
+        assert_eq!(addrs, vec![STATE, STATE + 8]);
+    }
+
+    #[test]
+    fn test_simulate_cycles() {
+        let stdin = 10u32.to_le_bytes();
+        let mut runtime = Runtime::new(Program::from(FIBONACCI_IO_ELF));
+        runtime.write_stdin_slice(&stdin);
+        runtime.run();
+
+        let result = simulate(
+            Program::from(FIBONACCI_IO_ELF),
+            &stdin,
+            RuntimeOptions::default(),
+        )
+        .unwrap();
+        assert!(result.succeeded());
+        assert_eq!(result.cycles, runtime.state.global_clk as u64);
+        assert_eq!(result.output, runtime.state.output_stream);
+        assert_eq!(result.public_values_digest, runtime.public_values_digest());
+        assert_eq!(result.io, runtime.io_stats());
+        assert!(!result.diff.is_empty());
+        assert!(result.diff.iter().all(|word| word.old != word.new));
+    }
+}
@@ -0,0 +1,451 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use super::{AluClass, ExecutionRecord, FormatError, Runtime, ShardProvenance};
+use crate::alu::{AluEvent, BitmanipEvent, DivRemEvent, MulEvent};
+use crate::cpu::{AuipcEvent, CpuEvent};
+
+/// A shard that finished executing, see [`EventSink::on_shard_complete`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardMetadata {
+    pub shard: u32,
+
+    /// The clock at the end of the shard.
+    pub clk: u32,
+
+    /// The number of instructions executed by the end of the shard, in all shards.
+    pub global_clk: u32,
+}
+
+/// A finished execution, see [`EventSink::on_run_complete`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunSummary {
+    /// The number of instructions executed.
+    pub global_clk: u32,
+
+    /// The number of shards.
+    pub shards: u32,
+
+    /// The exit code the guest halted with, if it halted.
+    pub exit_code: Option<u32>,
+}
+
+/// A destination for the CPU, AUIPC, ALU and bit manipulation events emitted during execution,
+/// see [`Runtime::set_event_sink`]. Events of unconstrained blocks are never passed to a sink.
+pub trait EventSink: Send {
+    fn on_cpu_event(&mut self, event: &CpuEvent);
+
+    fn on_auipc_event(&mut self, event: &AuipcEvent);
+
+    fn on_alu_event(&mut self, class: AluClass, event: &AluEvent);
+
+    /// Called for the events of [`AluClass::Mul`], with their metadata.
+    fn on_mul_event(&mut self, event: &MulEvent) {
+        self.on_alu_event(AluClass::Mul, &event.event);
+    }
+
+    /// Called for the events of [`AluClass::DivRem`], with their metadata.
+    fn on_divrem_event(&mut self, event: &DivRemEvent) {
+        self.on_alu_event(AluClass::DivRem, &event.event);
+    }
+
+    fn on_bitmanip_event(&mut self, event: &BitmanipEvent);
+
+    /// Called once every event of a shard was passed to the sink.
+    fn on_shard_complete(&mut self, _shard: &ShardMetadata) {}
+
+    /// Called once the execution finished, after the last shard completed.
+    fn on_run_complete(&mut self, _summary: &RunSummary) {}
+}
+
+/// The default sink, which keeps the events in the record.
+impl EventSink for ExecutionRecord {
+    #[inline(always)]
+    fn on_cpu_event(&mut self, event: &CpuEvent) {
+        self.cpu_events.push(*event);
+    }
+
+    #[inline(always)]
+    fn on_auipc_event(&mut self, event: &AuipcEvent) {
+        self.auipc_events.push(*event);
+    }
+
+    #[inline(always)]
+    fn on_alu_event(&mut self, class: AluClass, event: &AluEvent) {
+        match class {
+            AluClass::Add => self.add_events.push(*event),
+            AluClass::Sub => self.sub_events.push(*event),
+            AluClass::Bitwise => self.bitwise_events.push(*event),
+            AluClass::ShiftLeft => self.shift_left_events.push(*event),
+            AluClass::ShiftRight => self.shift_right_events.push(*event),
+            AluClass::Lt => self.lt_events.push(*event),
+            AluClass::Mul => self.mul_events.push((*event).into()),
+            AluClass::DivRem => self.divrem_events.push((*event).into()),
+        }
+    }
+
+    #[inline(always)]
+    fn on_mul_event(&mut self, event: &MulEvent) {
+        self.mul_events.push(*event);
+    }
+
+    #[inline(always)]
+    fn on_divrem_event(&mut self, event: &DivRemEvent) {
+        self.divrem_events.push(*event);
+    }
+
+    #[inline(always)]
+    fn on_bitmanip_event(&mut self, event: &BitmanipEvent) {
+        self.bitmanip_events.push(*event);
+    }
+}
+
+/// A frame written by [`StreamingEventSink`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EventFrame {
+    Cpu(CpuEvent),
+    Auipc(AuipcEvent),
+    Alu(AluClass, AluEvent),
+    Mul(MulEvent),
+    DivRem(DivRemEvent),
+    Bitmanip(BitmanipEvent),
+    ShardComplete(ShardMetadata),
+    RunComplete(RunSummary),
+}
+
+impl EventFrame {
+    /// Pass the frame to `sink`, as the runtime passed it to the streaming sink.
+    pub fn replay(&self, sink: &mut dyn EventSink) {
+        match self {
+            EventFrame::Cpu(event) => sink.on_cpu_event(event),
+            EventFrame::Auipc(event) => sink.on_auipc_event(event),
+            EventFrame::Alu(class, event) => sink.on_alu_event(*class, event),
+            EventFrame::Mul(event) => sink.on_mul_event(event),
+            EventFrame::DivRem(event) => sink.on_divrem_event(event),
+            EventFrame::Bitmanip(event) => sink.on_bitmanip_event(event),
+            EventFrame::ShardComplete(shard) => sink.on_shard_complete(shard),
+            EventFrame::RunComplete(summary) => sink.on_run_complete(summary),
+        }
+    }
+}
+
+impl ExecutionRecord {
+    /// Rebuild a record from the frames of an event stream, tagging every completed shard as
+    /// [`ShardProvenance::Replayed`] from the stream identified by `replay_id`.
+    pub fn from_event_frames(frames: &[EventFrame], replay_id: u64) -> Self {
+        let mut record = Self::default();
+        for frame in frames {
+            frame.replay(&mut record);
+            if let EventFrame::ShardComplete(shard) = frame {
+                record
+                    .provenance
+                    .shards
+                    .insert(shard.shard, ShardProvenance::Replayed);
+            }
+        }
+        record.provenance.replays.push(replay_id);
+        record
+    }
+}
+
+/// A sink that writes every event to a writer, such as a file or a TCP stream, as a frame of its
+/// length as four little-endian bytes followed by the bincode encoding of an [`EventFrame`].
+///
+/// The writer is flushed once the run completes. Writing stops at the first error, which is
+/// available from [`StreamingEventSink::error`].
+pub struct StreamingEventSink<W: Write + Send> {
+    writer: W,
+    buf: Vec<u8>,
+    error: Option<std::io::Error>,
+}
+
+impl<W: Write + Send> StreamingEventSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buf: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// The first error writing the frames, after which nothing more was written.
+    pub fn error(&self) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+
+    fn write_frame(&mut self, frame: &EventFrame) {
+        if self.error.is_some() {
+            return;
+        }
+        self.buf.clear();
+        self.buf.extend_from_slice(&[0; 4]);
+        bincode::serialize_into(&mut self.buf, frame).expect("failed to serialize the event");
+        let len = (self.buf.len() - 4) as u32;
+        self.buf[..4].copy_from_slice(&len.to_le_bytes());
+        if let Err(err) = self.writer.write_all(&self.buf) {
+            self.error = Some(err);
+        }
+    }
+}
+
+impl<W: Write + Send> EventSink for StreamingEventSink<W> {
+    fn on_cpu_event(&mut self, event: &CpuEvent) {
+        self.write_frame(&EventFrame::Cpu(*event));
+    }
+
+    fn on_auipc_event(&mut self, event: &AuipcEvent) {
+        self.write_frame(&EventFrame::Auipc(*event));
+    }
+
+    fn on_alu_event(&mut self, class: AluClass, event: &AluEvent) {
+        self.write_frame(&EventFrame::Alu(class, *event));
+    }
+
+    fn on_mul_event(&mut self, event: &MulEvent) {
+        self.write_frame(&EventFrame::Mul(*event));
+    }
+
+    fn on_divrem_event(&mut self, event: &DivRemEvent) {
+        self.write_frame(&EventFrame::DivRem(*event));
+    }
+
+    fn on_bitmanip_event(&mut self, event: &BitmanipEvent) {
+        self.write_frame(&EventFrame::Bitmanip(*event));
+    }
+
+    fn on_shard_complete(&mut self, shard: &ShardMetadata) {
+        self.write_frame(&EventFrame::ShardComplete(*shard));
+    }
+
+    fn on_run_complete(&mut self, summary: &RunSummary) {
+        self.write_frame(&EventFrame::RunComplete(*summary));
+        if self.error.is_none() {
+            if let Err(err) = self.writer.flush() {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+/// Decode the frames written by a [`StreamingEventSink`].
+pub fn decode_event_frames(bytes: &[u8]) -> Result<Vec<EventFrame>, FormatError> {
+    const KIND: &str = "event stream";
+    let mut frames = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let frame = rest
+            .get(..4)
+            .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+            .and_then(|len| rest.get(4..4 + len))
+            .ok_or(FormatError::Corrupted { kind: KIND })?;
+        frames.push(
+            bincode::deserialize(frame)
+                .map_err(|error| FormatError::Decode { kind: KIND, error })?,
+        );
+        rest = &rest[4 + frame.len()..];
+    }
+    Ok(frames)
+}
+
+impl Runtime {
+    /// Pass the CPU, AUIPC, ALU and bit manipulation events to `sink` instead of keeping them in
+    /// the record, e.g. to stream them to a database. The record then holds every other event,
+    /// but cannot be proven.
+    pub fn set_event_sink(&mut self, sink: Box<dyn EventSink>) {
+        self.event_sink = Some(sink);
+    }
+
+    /// Remove the event sink, so that events are kept in the record again.
+    pub fn take_event_sink(&mut self) -> Option<Box<dyn EventSink>> {
+        self.event_sink.take()
+    }
+
+    /// Tell the event sink that the current shard is complete.
+    pub(crate) fn complete_shard(&mut self) {
+        if let Some(sink) = &mut self.event_sink {
+            sink.on_shard_complete(&ShardMetadata {
+                shard: self.state.current_shard,
+                clk: self.state.clk,
+                global_clk: self.state.global_clk,
+            });
+        }
+    }
+
+    /// Tell the event sink that the last shard and the execution are complete.
+    pub(crate) fn complete_run(&mut self) {
+        self.complete_shard();
+        if let Some(sink) = &mut self.event_sink {
+            sink.on_run_complete(&RunSummary {
+                global_clk: self.state.global_clk,
+                shards: self.state.current_shard,
+                exit_code: self.exit_code,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::io::{Read, Seek, SeekFrom};
+
+    use p3_baby_bear::BabyBear;
+    use p3_matrix::dense::RowMajorMatrix;
+
+    use super::*;
+    use crate::air::MachineAir;
+    use crate::cpu::CpuChip;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::{Extensions, Instruction, Opcode, Program};
+
+    /// Run `runtime` with a sink streaming to a file, and decode the frames it wrote.
+    fn run_streaming(runtime: &mut Runtime) -> Vec<EventFrame> {
+        let mut file = tempfile::tempfile().unwrap();
+        runtime.set_event_sink(Box::new(StreamingEventSink::new(std::io::BufWriter::new(
+            file.try_clone().unwrap(),
+        ))));
+        runtime.run();
+        let mut bytes = Vec::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut bytes).unwrap();
+        decode_event_frames(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_streaming_sink() {
+        let mut expected = Runtime::new(fibonacci_program());
+        expected.shard_size = 1 << 10;
+        expected.run();
+
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.shard_size = 1 << 10;
+        let frames = run_streaming(&mut runtime);
+        assert!(runtime.record.cpu_events.is_empty());
+        assert!(runtime.record.add_events.is_empty());
+
+        let mut record = ExecutionRecord::default();
+        for frame in frames.iter() {
+            frame.replay(&mut record);
+        }
+        assert_eq!(
+            bincode::serialize(&record.cpu_events).unwrap(),
+            bincode::serialize(&expected.record.cpu_events).unwrap()
+        );
+        for class in AluClass::ALL {
+            assert_eq!(
+                bincode::serialize(&record.alu_events(class).iter().collect::<Vec<_>>()).unwrap(),
+                bincode::serialize(&expected.record.alu_events(class).iter().collect::<Vec<_>>())
+                    .unwrap()
+            );
+        }
+        assert_eq!(
+            bincode::serialize(&record.mul_events).unwrap(),
+            bincode::serialize(&expected.record.mul_events).unwrap()
+        );
+        assert_eq!(
+            bincode::serialize(&record.divrem_events).unwrap(),
+            bincode::serialize(&expected.record.divrem_events).unwrap()
+        );
+
+        let shards = frames
+            .iter()
+            .filter_map(|frame| match frame {
+                EventFrame::ShardComplete(shard) => Some(shard.shard),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let last_shard = expected.record.cpu_events.last().unwrap().shard;
+        assert!(last_shard > 1);
+        assert_eq!(shards, (1..=last_shard).collect::<Vec<_>>());
+        let Some(EventFrame::RunComplete(summary)) = frames.last() else {
+            panic!("expected the run to complete");
+        };
+        assert_eq!(summary.global_clk, expected.state.global_clk);
+        assert_eq!(summary.shards, last_shard);
+
+        let replayed = ExecutionRecord::from_event_frames(&frames, 1);
+        assert_eq!(replayed.cpu_events.len(), record.cpu_events.len());
+        assert_eq!(replayed.provenance().replays, vec![1]);
+        assert_eq!(replayed.provenance().shards.len(), last_shard as usize);
+        assert!(replayed
+            .provenance()
+            .shards
+            .values()
+            .all(|&provenance| provenance == ShardProvenance::Replayed));
+    }
+
+    #[test]
+    fn test_streaming_sink_auipc() {
+        let program = Program::new(
+            vec![
+                Instruction::new(Opcode::AUIPC, 10, 0x1000, 0x1000, true, true),
+                Instruction::new(Opcode::ADD, 11, 10, 4, false, true),
+                Instruction::new(Opcode::AUIPC, 12, 0xffff_f000, 0xffff_f000, true, true),
+            ],
+            0x2000,
+            0x2000,
+        );
+        let mut expected = Runtime::new(program.clone());
+        expected.run();
+        assert_eq!(expected.record.auipc_events.len(), 2);
+
+        let mut runtime = Runtime::new(program);
+        let frames = run_streaming(&mut runtime);
+        assert!(runtime.record.auipc_events.is_empty());
+        let record = ExecutionRecord::from_event_frames(&frames, 1);
+        assert_eq!(record.auipc_events, expected.record.auipc_events);
+
+        let chip = CpuChip::default();
+        let trace: RowMajorMatrix<BabyBear> =
+            chip.generate_trace(&record, &mut ExecutionRecord::default());
+        let expected_trace: RowMajorMatrix<BabyBear> =
+            chip.generate_trace(&expected.record, &mut ExecutionRecord::default());
+        assert_eq!(trace.values, expected_trace.values);
+    }
+
+    #[test]
+    fn test_streaming_sink_bitmanip() {
+        let mut program = Program::new(
+            vec![
+                Instruction::new(Opcode::ADD, 10, 0, 0xf0, false, true),
+                Instruction::new(Opcode::ADD, 11, 0, 3, false, true),
+                Instruction::new(Opcode::CLZ, 12, 10, 0, false, true),
+                Instruction::new(Opcode::ANDN, 13, 10, 11, false, false),
+                Instruction::new(Opcode::CPOP, 14, 13, 0, false, true),
+            ],
+            0,
+            0,
+        );
+        program.extensions = Extensions::ZBB;
+        let mut expected = Runtime::new(program.clone());
+        expected.run();
+        assert_eq!(expected.record.bitmanip_events.len(), 3);
+
+        let mut runtime = Runtime::new(program);
+        let frames = run_streaming(&mut runtime);
+        assert!(runtime.record.bitmanip_events.is_empty());
+        let record = ExecutionRecord::from_event_frames(&frames, 1);
+        assert_eq!(
+            bincode::serialize(&record.bitmanip_events).unwrap(),
+            bincode::serialize(&expected.record.bitmanip_events).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_corrupted_frames() {
+        let mut bytes = Vec::new();
+        let mut sink = StreamingEventSink::new(&mut bytes);
+        sink.on_run_complete(&RunSummary {
+            global_clk: 1,
+            shards: 1,
+            exit_code: Some(0),
+        });
+        assert!(sink.error().is_none());
+        assert_eq!(decode_event_frames(&bytes).unwrap().len(), 1);
+        assert!(matches!(
+            decode_event_frames(&bytes[..bytes.len() - 1]),
+            Err(FormatError::Corrupted { .. })
+        ));
+        assert!(decode_event_frames(&bytes[..2]).is_err());
+    }
+}
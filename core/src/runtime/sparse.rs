@@ -0,0 +1,174 @@
+use std::collections::{BTreeSet, HashSet};
+
+use nohash_hasher::BuildNoHashHasher;
+
+use super::{MemoryInconsistency, Runtime};
+
+impl Runtime {
+    /// Omit the words of the memory image the guest only read from `last_memory_record`. Their
+    /// final value is the one of the image, so their `program_memory_record` entry, which stays
+    /// marked as used, stands for both ends of their memory argument. Guests reading a large
+    /// image without writing it then have much smaller final memory records.
+    ///
+    /// This changes the shape of the record: the memory chips still expect every used word to be
+    /// finalized, so such records can be checked but not proven. The writes are tracked from the
+    /// start of the execution, so this must be set before it starts.
+    pub fn set_sparse_memory_records(&mut self, enabled: bool) {
+        self.written_addrs = enabled.then(HashSet::default);
+    }
+
+    /// Whether the final record of `addr`, with final value `value`, is omitted from
+    /// `last_memory_record`. This is the only place the rule is decided.
+    pub(crate) fn elides_final_record(
+        written_addrs: Option<&HashSet<u32, BuildNoHashHasher<u32>>>,
+        image_value: Option<u32>,
+        addr: u32,
+        value: u32,
+    ) -> bool {
+        let Some(written) = written_addrs else {
+            return false;
+        };
+        !written.contains(&addr) && image_value == Some(value)
+    }
+
+    /// Checks that every used word of the memory image without a final record was never
+    /// written, and still holds its value from the image.
+    pub fn check_elided_memory(&self) -> Result<(), Vec<MemoryInconsistency>> {
+        let finalized = self
+            .record
+            .last_memory_record
+            .iter()
+            .map(|(addr, _, _)| *addr)
+            .collect::<BTreeSet<u32>>();
+        let mut inconsistencies = Vec::new();
+        for (addr, record, used) in self.record.program_memory_record.iter() {
+            if *used == 0 || finalized.contains(addr) {
+                continue;
+            }
+            let written = match &self.written_addrs {
+                Some(written) => written.contains(addr),
+                None => true,
+            };
+            let value = self.state.memory.get(addr).map(|(value, _, _)| *value);
+            if written || value != Some(record.value) {
+                inconsistencies.push(MemoryInconsistency::ElidedWrittenMemory { addr: *addr });
+            }
+        }
+        if inconsistencies.is_empty() {
+            Ok(())
+        } else {
+            Err(inconsistencies)
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::cpu::MemoryRecord;
+    use crate::runtime::{Program, Register};
+    use crate::utils::asm::assemble;
+
+    const IMAGE_START: u32 = 0x10000;
+    const IMAGE_WORDS: u32 = 256;
+
+    /// Sum the words of the image, optionally writing the sum over its first word.
+    fn image_program(write: bool) -> Program {
+        let mut program = assemble(
+            &format!(
+                "
+                        li   t0, {}
+                        li   t1, {}
+                        li   a0, 0
+                loop:   lw   t2, 0(t0)
+                        add  a0, a0, t2
+                        addi t0, t0, 4
+                        addi t1, t1, -1
+                        bne  t1, zero, loop
+                        {}
+                ",
+                IMAGE_START,
+                IMAGE_WORDS,
+                if write {
+                    format!("sw a0, {}(zero)", IMAGE_START)
+                } else {
+                    "nop".to_string()
+                }
+            ),
+            0,
+        )
+        .unwrap();
+        for i in 0..IMAGE_WORDS {
+            program.memory_image.insert(IMAGE_START + 4 * i, i + 1);
+        }
+        program
+    }
+
+    fn run(write: bool, sparse: bool) -> Runtime {
+        let mut runtime = Runtime::new(image_program(write));
+        runtime.set_sparse_memory_records(sparse);
+        runtime.run();
+        assert_eq!(
+            runtime.record.check_memory_consistency(&runtime.program),
+            Ok(())
+        );
+        assert_eq!(runtime.check_elided_memory(), Ok(()));
+        runtime
+    }
+
+    #[test]
+    fn test_sparse_memory_records() {
+        let dense = run(false, false);
+        let sparse = run(false, true);
+        assert_eq!(dense.register(Register::X10), 256 * 257 / 2);
+        assert!(dense.record.last_memory_record.len() > IMAGE_WORDS as usize);
+        assert!(sparse.record.last_memory_record.len() < 32);
+        assert!(sparse
+            .record
+            .last_memory_record
+            .iter()
+            .all(|(addr, _, _)| *addr < IMAGE_START));
+        let entries = |records: &[(u32, MemoryRecord, u32)]| {
+            records
+                .iter()
+                .map(|(addr, record, used)| (*addr, record.value, *used))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            entries(&dense.record.program_memory_record),
+            entries(&sparse.record.program_memory_record)
+        );
+        assert_eq!(
+            entries(&dense.record.first_memory_record),
+            entries(&sparse.record.first_memory_record)
+        );
+    }
+
+    #[test]
+    fn test_written_word_is_finalized() {
+        let sparse = run(true, true);
+        let finalized = sparse
+            .record
+            .last_memory_record
+            .iter()
+            .filter(|(addr, _, _)| *addr >= IMAGE_START)
+            .collect::<Vec<_>>();
+        assert_eq!(finalized.len(), 1);
+        assert_eq!(finalized[0].0, IMAGE_START);
+        assert_eq!(finalized[0].1.value, 256 * 257 / 2);
+        run(true, false);
+
+        // A written word without a final record is caught.
+        let mut sparse = sparse;
+        sparse
+            .record
+            .last_memory_record
+            .retain(|(addr, _, _)| *addr != IMAGE_START);
+        assert_eq!(
+            sparse.check_elided_memory(),
+            Err(vec![MemoryInconsistency::ElidedWrittenMemory {
+                addr: IMAGE_START
+            }])
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use std::ops::Range;
+
+use super::{ExecutionError, Register, Runtime, NUM_REGISTERS};
+
+/// The default size of the guard region below a stack configured with
+/// [`Runtime::configure_stack`].
+pub const DEFAULT_STACK_GUARD_SIZE: u32 = 0x1000;
+
+/// The stack of the guest, see [`Runtime::configure_stack`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackConfig {
+    /// The initial stack pointer. The stack grows down from it.
+    pub top: u32,
+
+    /// The size of the stack in bytes.
+    pub size: u32,
+
+    /// The size in bytes of the region right below the stack the guest may not access.
+    pub guard_size: u32,
+
+    /// Whether to initialize the words of the stack to zero before execution, so that reading
+    /// them is not an uninitialized read. This inserts every word of the stack into the memory.
+    pub zero_init: bool,
+}
+
+impl StackConfig {
+    pub fn new(top: u32, size: u32) -> Self {
+        Self {
+            top,
+            size,
+            guard_size: DEFAULT_STACK_GUARD_SIZE,
+            zero_init: false,
+        }
+    }
+
+    /// The addresses of the stack.
+    pub fn range(&self) -> Range<u32> {
+        self.top.saturating_sub(self.size)..self.top
+    }
+
+    /// The addresses of the guard region.
+    pub fn guard(&self) -> Range<u32> {
+        let bottom = self.range().start;
+        bottom.saturating_sub(self.guard_size)..bottom
+    }
+}
+
+/// A configured stack and the lowest stack pointer seen so far.
+#[derive(Debug, Clone)]
+pub(crate) struct GuestStack {
+    pub(crate) config: StackConfig,
+    guard: Range<u32>,
+    low_water: u32,
+}
+
+impl GuestStack {
+    fn new(config: StackConfig) -> Self {
+        Self {
+            guard: config.guard(),
+            low_water: config.top,
+            config,
+        }
+    }
+
+    /// Whether `addr` is in the guard region.
+    #[inline]
+    pub(crate) fn guards(&self, addr: u32) -> bool {
+        self.guard.contains(&addr)
+    }
+
+    /// Forget the stack pointers seen so far, for another execution.
+    pub(crate) fn reset(&mut self) {
+        self.guard = self.config.guard();
+        self.low_water = self.config.top;
+    }
+
+    /// Record that the stack pointer was set to `sp`.
+    #[inline]
+    pub(crate) fn record_sp(&mut self, sp: u32) {
+        if sp < self.low_water {
+            self.low_water = sp;
+        }
+    }
+}
+
+impl Runtime {
+    /// Declare that the stack of the guest is the `size` bytes below `top`, and forbid any access
+    /// to the [`DEFAULT_STACK_GUARD_SIZE`] bytes below it with `ExecutionError::ProtectionFault`,
+    /// which catches stack overflows before they corrupt the memory below. The peak usage is
+    /// available from [`Runtime::stack_usage`] afterwards.
+    pub fn configure_stack(&mut self, top: u32, size: u32) {
+        self.set_stack_config(StackConfig::new(top, size));
+    }
+
+    /// Like [`Runtime::configure_stack`], with the size of the guard region and the initialization
+    /// of the stack set explicitly. Must be called before execution starts.
+    pub fn set_stack_config(&mut self, config: StackConfig) {
+        self.stack = Some(GuestStack::new(config));
+    }
+
+    /// The number of bytes between the top of the stack and the lowest stack pointer so far, or
+    /// `None` if no stack was configured.
+    pub fn stack_usage(&self) -> Option<u32> {
+        let stack = self.stack.as_ref()?;
+        Some(stack.config.top - stack.low_water)
+    }
+
+    /// Zero the words of the stack if configured. Must be called before the memory image is
+    /// loaded, which takes precedence.
+    pub(crate) fn initialize_stack(&mut self) {
+        let Some(stack) = &mut self.stack else {
+            return;
+        };
+        stack.reset();
+        if !stack.config.zero_init {
+            return;
+        }
+        let range = stack.config.range();
+        for addr in (range.start.next_multiple_of(4)..range.end).step_by(4) {
+            // The words of a frozen memory keep their value, as they do when the memory image is
+            // loaded after the stack.
+            let value = self.frozen_word(addr).unwrap_or(0);
+            self.state.memory.entry(addr).or_insert((value, 0, 0));
+        }
+    }
+
+    /// Record a write to a register, tracking the stack pointer. Values above the stack, which
+    /// guests sometimes use `sp` for before setting it up, are ignored.
+    #[inline]
+    pub(crate) fn observe_register_write(&mut self, register: Register, value: u32) {
+        if register == Register::X2 {
+            if let Some(stack) = &mut self.stack {
+                if value <= stack.config.top {
+                    stack.record_sp(value);
+                }
+            }
+        }
+    }
+
+    /// Trap if `addr` is in the guard region of the stack.
+    #[inline]
+    pub(crate) fn check_stack_guard(&mut self, addr: u32) {
+        if self.stack.as_ref().is_some_and(|stack| stack.guards(addr)) {
+            self.trap(ExecutionError::ProtectionFault {
+                region: "stack guard".to_string(),
+                addr,
+                pc: self.state.pc,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runtime::{Instruction, Opcode, Program, UninitMemoryPolicy};
+
+    const TOP: u32 = 0x0020_0000;
+
+    /// Compute the sum of `1..=n` recursively, where every call pushes the return address and `n`
+    /// in a frame of 16 bytes, and store the result at 0x1000.
+    fn recursive_program(n: u32) -> Program {
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 2, 0, TOP, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, n, false, true),
+            // call sum
+            Instruction::new(Opcode::JAL, 1, 12, 0, true, true),
+            Instruction::new(Opcode::SW, 10, 0, 0x1000, false, true),
+            Instruction::new(Opcode::JAL, 0, 48, 0, true, true),
+            // sum: if n == 0, return 0.
+            Instruction::new(Opcode::BEQ, 10, 0, 40, false, true),
+            Instruction::new(Opcode::ADD, 2, 2, -16i32 as u32, false, true),
+            Instruction::new(Opcode::SW, 1, 2, 0, false, true),
+            Instruction::new(Opcode::SW, 10, 2, 4, false, true),
+            Instruction::new(Opcode::SUB, 10, 10, 1, false, true),
+            Instruction::new(Opcode::JAL, 1, -20i32 as u32, 0, true, true),
+            Instruction::new(Opcode::LW, 11, 2, 4, false, true),
+            Instruction::new(Opcode::ADD, 10, 10, 11, false, false),
+            Instruction::new(Opcode::LW, 1, 2, 0, false, true),
+            Instruction::new(Opcode::ADD, 2, 2, 16, false, true),
+            // ret
+            Instruction::new(Opcode::JALR, 0, 1, 0, false, true),
+        ];
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_stack_usage() {
+        let mut runtime = Runtime::new(recursive_program(10));
+        runtime.configure_stack(TOP, 0x1000);
+        runtime.run();
+        assert_eq!(runtime.word(0x1000), 55);
+        assert_eq!(runtime.stack_usage(), Some(160));
+        assert_eq!(Runtime::new(recursive_program(10)).stack_usage(), None);
+    }
+
+    #[test]
+    fn test_stack_usage_after_reset() {
+        let mut runtime = Runtime::new(recursive_program(10));
+        runtime.configure_stack(TOP, 0x400);
+        runtime.run();
+        assert_eq!(runtime.stack_usage(), Some(160));
+
+        runtime.reset_with_program(Arc::new(recursive_program(2)));
+        assert_eq!(runtime.stack_usage(), Some(0));
+        runtime.run();
+        assert_eq!(runtime.word(0x1000), 3);
+        assert_eq!(runtime.stack_usage(), Some(32));
+
+        runtime.reset_with_program(Arc::new(recursive_program(100)));
+        assert!(matches!(
+            runtime.try_run(),
+            Err(ExecutionError::ProtectionFault { addr, .. }) if addr == TOP - 0x410
+        ));
+    }
+
+    #[test]
+    fn test_zero_init_stack() {
+        let program = Program::new(
+            vec![
+                Instruction::new(Opcode::ADD, 2, 0, TOP, false, true),
+                Instruction::new(Opcode::LW, 5, 2, -8i32 as u32, false, true),
+            ],
+            0,
+            0,
+        );
+        let mut runtime = Runtime::new(program.clone());
+        runtime.uninit_memory_policy = UninitMemoryPolicy::Trap;
+        runtime.configure_stack(TOP, 0x100);
+        assert!(runtime.try_run().is_err());
+
+        // Zeroed stack words are not uninitialized, and the words never accessed are not part of
+        // the memory records.
+        let mut runtime = Runtime::new(program);
+        runtime.uninit_memory_policy = UninitMemoryPolicy::Trap;
+        runtime.set_stack_config(StackConfig {
+            zero_init: true,
+            ..StackConfig::new(TOP, 0x100)
+        });
+        assert_eq!(runtime.try_run(), Ok(()));
+        assert_eq!(runtime.memory_stats().words, 1);
+        assert!(runtime.record.program_memory_record.is_empty());
+        let words = runtime
+            .record
+            .first_memory_record
+            .iter()
+            .filter(|(addr, _, _)| *addr >= NUM_REGISTERS)
+            .count();
+        assert_eq!(words, 1);
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut runtime = Runtime::new(recursive_program(100));
+        runtime.configure_stack(TOP, 0x400);
+        let err = runtime.try_run().unwrap_err();
+        assert_eq!(
+            err,
+            ExecutionError::ProtectionFault {
+                region: "stack guard".to_string(),
+                addr: TOP - 0x410,
+                pc: 28,
+            }
+        );
+        assert!(err.to_string().contains("stack guard"));
+        assert_eq!(runtime.stack_usage(), Some(0x410));
+    }
+}
@@ -1,10 +1,13 @@
+use std::collections::BTreeMap;
+
 use hashbrown::HashMap;
 use nohash_hasher::BuildNoHashHasher;
+use serde::{Deserialize, Serialize};
 
-use super::{CpuRecord, ExecutionRecord};
+use super::{CpuRecord, ExecutionRecord, PublicValuesDigester};
 
 /// Holds data describing the current state of a program's execution.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExecutionState {
     /// The global clock keeps track of how many instrutions have been executed through all shards.
     pub global_clk: u32,
@@ -21,6 +24,7 @@ pub struct ExecutionState {
 
     /// The memory which instructions operate over. Values contain the memory value and last shard
     /// + timestamp that each memory address was accessed.
+    #[serde(with = "crate::utils::serialization::hash_map")]
     pub memory: HashMap<u32, (u32, u32, u32), BuildNoHashHasher<u32>>,
 
     /// A stream of input values (global to the entire program).
@@ -34,9 +38,88 @@ pub struct ExecutionState {
 
     /// A ptr to the current position in the output stream, incremented when reading from output_stream.
     pub output_stream_ptr: usize,
+
+    /// Output streams for the logical output channels other than channel zero, which is always
+    /// `output_stream`.
+    pub output_channels: BTreeMap<u32, Vec<u8>>,
+
+    /// The running digest of the public values in `output_stream`, see
+    /// [`ExecutionState::public_values_digest`].
+    pub(crate) public_values_digester: PublicValuesDigester,
 }
 
 impl ExecutionState {
+    /// The output written so far to the given channel.
+    pub fn output_channel(&self, channel: u32) -> &[u8] {
+        if channel == 0 {
+            &self.output_stream
+        } else {
+            self.output_channels
+                .get(&channel)
+                .map(|buf| buf.as_slice())
+                .unwrap_or(&[])
+        }
+    }
+
+    /// A mutable reference to the output stream of the given channel.
+    pub fn output_channel_mut(&mut self, channel: u32) -> &mut Vec<u8> {
+        if channel == 0 {
+            &mut self.output_stream
+        } else {
+            self.output_channels.entry(channel).or_default()
+        }
+    }
+
+    /// The current lengths of all output channels, including channel zero.
+    pub(crate) fn output_channel_lens(&self) -> BTreeMap<u32, usize> {
+        let mut lens = BTreeMap::new();
+        lens.insert(0, self.output_stream.len());
+        for (channel, buf) in self.output_channels.iter() {
+            lens.insert(*channel, buf.len());
+        }
+        lens
+    }
+
+    /// Truncates the output channels back to the given lengths, dropping channels that did not
+    /// exist when the lengths were taken.
+    pub(crate) fn truncate_output_channels(&mut self, lens: &BTreeMap<u32, usize>) {
+        self.output_stream
+            .truncate(lens.get(&0).copied().unwrap_or(0));
+        self.output_channels
+            .retain(|channel, _| lens.contains_key(channel));
+        for (channel, buf) in self.output_channels.iter_mut() {
+            buf.truncate(lens[channel]);
+        }
+    }
+
+    /// Reset to the state of `ExecutionState::new(pc_start)`, keeping the allocated capacity.
+    pub(crate) fn reset(&mut self, pc_start: u32) {
+        let Self {
+            global_clk,
+            current_shard,
+            clk,
+            pc,
+            memory,
+            input_stream,
+            input_stream_ptr,
+            output_stream,
+            output_stream_ptr,
+            output_channels,
+            public_values_digester,
+        } = self;
+        *global_clk = 0;
+        *current_shard = 1;
+        *clk = 0;
+        *pc = pc_start;
+        memory.clear();
+        input_stream.clear();
+        *input_stream_ptr = 0;
+        output_stream.clear();
+        *output_stream_ptr = 0;
+        output_channels.clear();
+        *public_values_digester = PublicValuesDigester::new(public_values_digester.hasher());
+    }
+
     pub fn new(pc_start: u32) -> Self {
         Self {
             global_clk: 0,
@@ -49,6 +132,8 @@ impl ExecutionState {
             input_stream_ptr: 0,
             output_stream: Vec::new(),
             output_stream_ptr: 0,
+            output_channels: BTreeMap::new(),
+            public_values_digester: PublicValuesDigester::default(),
         }
     }
 }
@@ -68,9 +153,27 @@ pub(crate) struct ForkState {
     /// Only contains the original memory values for addresses that have been modified
     pub(crate) memory_diff: HashMap<u32, Option<(u32, u32, u32)>, BuildNoHashHasher<u32>>,
 
+    /// The original values of the words of the scratch region that have been stored to, by
+    /// address.
+    pub(crate) scratch_diff: HashMap<u32, u32, BuildNoHashHasher<u32>>,
+
     /// Full record from original state
     pub(crate) op_record: CpuRecord,
 
     /// Full shard from original state
     pub(crate) record: ExecutionRecord,
+
+    /// Original lengths of the output channels, including channel zero.
+    pub(crate) output_channel_lens: BTreeMap<u32, usize>,
+
+    /// The digest of the public values, restored with the output stream.
+    pub(crate) public_values_digester: PublicValuesDigester,
+
+    /// The writes to the input stream made in the block, with `HINT_SLICE` or `WRITE`, appended
+    /// in this order when the block exits.
+    pub(crate) staged_inputs: Vec<Vec<u8>>,
+
+    /// The offset of the stdin reader, rewound to when the block exits so that the bytes read from
+    /// it in the block are read again.
+    pub(crate) stdin_reader_mark: Option<usize>,
 }
@@ -0,0 +1,241 @@
+use core::fmt::{Display, Formatter};
+
+use super::{SyscallArgs, SyscallCode, SyscallContext};
+
+/// The default number of bytes of each buffer kept in a [`SyscallTraceEntry`].
+pub const DEFAULT_SYSCALL_TRACE_PREVIEW: usize = 32;
+
+/// Whether a syscall read or wrote a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferAccess {
+    Read,
+    Write,
+}
+
+/// Consecutive words a syscall accessed through [`SyscallContext::mr`] or [`SyscallContext::mw`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallBuffer {
+    pub access: BufferAccess,
+
+    /// The address of the first word.
+    pub addr: u32,
+
+    /// The length of the buffer in bytes.
+    pub len: u32,
+
+    /// The first bytes of the buffer, at most `Runtime::syscall_trace_preview` of them. The
+    /// values are those read or written by the syscall.
+    pub preview: Vec<u8>,
+}
+
+/// An `ecall` executed while `Runtime::trace_syscalls` was set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallTraceEntry {
+    pub code: SyscallCode,
+
+    /// The raw values of t0, a0 and a1.
+    pub args: SyscallArgs,
+
+    /// The buffers the syscall accessed, in order.
+    pub buffers: Vec<SyscallBuffer>,
+
+    /// The value the syscall wrote to a0.
+    pub result: u32,
+
+    /// The cycles the syscall took on top of the `ecall` itself.
+    pub extra_cycles: u32,
+
+    pub shard: u32,
+    pub clk: u32,
+    pub pc: u32,
+
+    /// Whether the syscall ran in an unconstrained block, whose effects are discarded.
+    pub unconstrained: bool,
+}
+
+impl Display for SyscallTraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "[shard {} clk {} pc=0x{:08x}] {:?}(t0=0x{:x}, a0=0x{:x}, a1=0x{:x}) = 0x{:x}",
+            self.shard,
+            self.clk,
+            self.pc,
+            self.code,
+            self.args.code,
+            self.args.a0,
+            self.args.a1,
+            self.result
+        )?;
+        if self.extra_cycles > 0 {
+            write!(f, " +{} cycles", self.extra_cycles)?;
+        }
+        for buffer in self.buffers.iter() {
+            let access = match buffer.access {
+                BufferAccess::Read => "read",
+                BufferAccess::Write => "write",
+            };
+            write!(f, " {} 0x{:08x}[{}]=", access, buffer.addr, buffer.len)?;
+            for byte in buffer.preview.iter() {
+                write!(f, "{:02x}", byte)?;
+            }
+            if buffer.preview.len() < buffer.len as usize {
+                write!(f, "..")?;
+            }
+        }
+        if self.unconstrained {
+            write!(f, " (unconstrained)")?;
+        }
+        Ok(())
+    }
+}
+
+impl SyscallContext<'_> {
+    /// Record an access of the syscall to the word at `addr`, extending the last buffer if the
+    /// word follows it.
+    pub(crate) fn trace_access(&mut self, access: BufferAccess, addr: u32, value: u32) {
+        let preview_len = self.rt.syscall_trace_preview;
+        let Some(buffers) = &mut self.traced else {
+            return;
+        };
+        let buffer = match buffers.last_mut() {
+            Some(last) if last.access == access && last.addr.wrapping_add(last.len) == addr => last,
+            _ => {
+                buffers.push(SyscallBuffer {
+                    access,
+                    addr,
+                    len: 0,
+                    preview: Vec::new(),
+                });
+                buffers.last_mut().unwrap()
+            }
+        };
+        buffer.len += 4;
+        let remaining = preview_len.saturating_sub(buffer.preview.len());
+        buffer
+            .preview
+            .extend(value.to_le_bytes().into_iter().take(remaining));
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runtime::tests::ecall_lwa_program;
+    use crate::runtime::{Instruction, Opcode, Program, Runtime, Syscall};
+
+    /// Copies the a1 words at a0 to a0 + 0x100, taking 4 extra cycles.
+    struct CopySyscall;
+
+    impl Syscall for CopySyscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            let args = ctx.args();
+            let (_, values) = ctx.mr_slice(args.a0, args.a1 as usize);
+            ctx.advance(4);
+            ctx.mw_slice(args.a0 + 0x100, &values);
+            values.len() as u32
+        }
+
+        fn num_extra_cycles(&self) -> u32 {
+            4
+        }
+    }
+
+    /// `ecall_lwa_program`, a copy of 4 words at 0x1000 and a function at 28 doing another `LWA`.
+    fn traced_program() -> Program {
+        let mut instructions = ecall_lwa_program().instructions;
+        instructions.extend([
+            Instruction::new(
+                Opcode::ADD,
+                5,
+                0,
+                SyscallCode::WRITE_CHANNEL as u32,
+                false,
+                true,
+            ),
+            Instruction::new(Opcode::ADD, 10, 0, 0x1000, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 4, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::JAL, 0, 16, 0, true, true),
+            // The function:
+            Instruction::new(Opcode::ADD, 5, 0, SyscallCode::LWA as u32, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::JALR, 0, 1, 0, false, true),
+        ]);
+        let mut program = Program::new(instructions, 0, 0);
+        for (i, word) in [0x04030201, 0x08070605, 0x0c0b0a09, 0x100f0e0d]
+            .into_iter()
+            .enumerate()
+        {
+            program.memory_image.insert(0x1000 + i as u32 * 4, word);
+        }
+        program
+    }
+
+    #[test]
+    fn test_trace_syscalls() {
+        let mut runtime = Runtime::new(traced_program());
+        runtime
+            .syscall_map
+            .insert(SyscallCode::WRITE_CHANNEL, Arc::new(CopySyscall));
+        runtime.trace_syscalls = true;
+        runtime.syscall_trace_preview = 6;
+        runtime.run();
+        runtime.call_function(28, &[0, 0], 100).unwrap();
+
+        let trace = &runtime.syscall_trace;
+        assert_eq!(trace.len(), 3);
+        assert_eq!(
+            trace[0],
+            SyscallTraceEntry {
+                code: SyscallCode::LWA,
+                args: SyscallArgs {
+                    code: 101,
+                    a0: 0,
+                    a1: 0,
+                },
+                buffers: Vec::new(),
+                result: 0,
+                extra_cycles: 0,
+                shard: 1,
+                clk: 5,
+                pc: 4,
+                unconstrained: false,
+            }
+        );
+
+        let copy = &trace[1];
+        assert_eq!(copy.code, SyscallCode::WRITE_CHANNEL);
+        assert_eq!((copy.args.a0, copy.args.a1), (0x1000, 4));
+        assert_eq!((copy.result, copy.extra_cycles), (4, 4));
+        assert_eq!((copy.clk, copy.pc), (21, 20));
+        assert_eq!(
+            copy.buffers,
+            vec![
+                SyscallBuffer {
+                    access: BufferAccess::Read,
+                    addr: 0x1000,
+                    len: 16,
+                    preview: vec![1, 2, 3, 4, 5, 6],
+                },
+                SyscallBuffer {
+                    access: BufferAccess::Write,
+                    addr: 0x1100,
+                    len: 16,
+                    preview: vec![1, 2, 3, 4, 5, 6],
+                },
+            ]
+        );
+        assert_eq!(
+            copy.to_string(),
+            "[shard 1 clk 21 pc=0x00000014] WRITE_CHANNEL(t0=0x71, a0=0x1000, a1=0x4) = 0x4 \
+             +4 cycles read 0x00001000[16]=010203040506.. write 0x00001100[16]=010203040506.."
+        );
+
+        assert_eq!((trace[2].code, trace[2].pc), (SyscallCode::LWA, 32));
+        assert!(trace[2].unconstrained);
+        assert!(trace[2].to_string().ends_with("(unconstrained)"));
+    }
+}
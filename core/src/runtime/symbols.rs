@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+/// A function from the symbol table of the ELF a program was disassembled from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Symbol {
+    /// The demangled name of the function.
+    pub name: String,
+
+    /// The address of the first instruction of the function.
+    pub addr: u32,
+
+    /// The size of the function in bytes, or zero if unknown.
+    pub size: u32,
+}
+
+impl Symbol {
+    /// Create a symbol, demangling its name if it is a mangled Rust symbol.
+    pub fn new(name: &str, addr: u32, size: u32) -> Self {
+        Self {
+            name: format!("{:#}", rustc_demangle::demangle(name)),
+            addr,
+            size,
+        }
+    }
+
+    /// Whether `pc` is inside the function.
+    pub fn contains(&self, pc: u32) -> bool {
+        pc.wrapping_sub(self.addr) < self.size.max(1)
+    }
+}
+
+/// The function symbols of a program, sorted by address.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Create a symbol table from symbols in any order.
+    pub fn new(mut symbols: Vec<Symbol>) -> Self {
+        symbols.sort_by_key(|symbol| symbol.addr);
+        Self { symbols }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter()
+    }
+
+    /// Find the function containing `pc`.
+    pub fn lookup(&self, pc: u32) -> Option<&Symbol> {
+        let candidates = &self.symbols[..self.symbols.partition_point(|symbol| symbol.addr <= pc)];
+        let addr = candidates.last()?.addr;
+        candidates
+            .iter()
+            .rev()
+            .take_while(|symbol| symbol.addr == addr)
+            .find(|symbol| symbol.contains(pc))
+    }
+
+    /// Find a function by its demangled name.
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+
+    #[test]
+    fn test_symbol_lookup() {
+        let table = SymbolTable::new(vec![
+            Symbol::new(
+                "_ZN3std9panicking12default_hook17h0123456789abcdefE",
+                0x100,
+                0x20,
+            ),
+            Symbol::new("main", 0x0, 0x10),
+            Symbol::new("unsized", 0x200, 0),
+        ]);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.lookup(0x4).unwrap().name, "main");
+        assert!(table.lookup(0x10).is_none());
+        assert_eq!(
+            table.lookup(0x11c).unwrap().name,
+            "std::panicking::default_hook"
+        );
+        assert!(table.lookup(0x120).is_none());
+        assert_eq!(table.lookup(0x200).unwrap().name, "unsized");
+        assert!(table.lookup(0x204).is_none());
+        assert_eq!(table.get("main").unwrap().addr, 0);
+    }
+
+    #[test]
+    fn test_elf_symbols() {
+        let program = fibonacci_program();
+        assert_eq!(program.symbols.get("main").unwrap().addr, 0x2009d4);
+        assert_eq!(
+            program.symbols.lookup(0x2017c0).unwrap().name,
+            "std::panicking::default_hook"
+        );
+    }
+}
@@ -1,7 +1,11 @@
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
 
-use crate::runtime::{Register, Runtime};
+use crate::runtime::{
+    BufferAccess, Error, RecordFilter, Register, Runtime, SyscallBuffer, SyscallClock,
+};
 use crate::syscall::precompiles::blake3::Blake3CompressInnerChip;
 use crate::syscall::precompiles::edwards::EdAddAssignChip;
 use crate::syscall::precompiles::edwards::EdDecompressChip;
@@ -11,7 +15,9 @@ use crate::syscall::precompiles::sha256::{ShaCompressChip, ShaExtendChip};
 use crate::syscall::precompiles::weierstrass::WeierstrassAddAssignChip;
 use crate::syscall::precompiles::weierstrass::WeierstrassDoubleAssignChip;
 use crate::syscall::{
-    SyscallEnterUnconstrained, SyscallExitUnconstrained, SyscallHalt, SyscallLWA, SyscallWrite,
+    SyscallAssertFailed, SyscallEnterUnconstrained, SyscallExitUnconstrained, SyscallHalt,
+    SyscallHintSlice, SyscallLWA, SyscallLoad64, SyscallPanic, SyscallPrefetchInput,
+    SyscallReadFrame, SyscallShardHint, SyscallStore64, SyscallWrite, SyscallWriteChannel,
 };
 use crate::utils::ec::edwards::ed25519::{Ed25519, Ed25519Parameters};
 use crate::utils::ec::weierstrass::secp256k1::Secp256k1;
@@ -60,13 +66,46 @@ pub enum SyscallCode {
     /// Executes the `BLAKE3_COMPRESS_INNER` precompile.
     BLAKE3_COMPRESS_INNER = 112,
 
+    /// Writes to one of the logical output channels.
+    WRITE_CHANNEL = 113,
+
+    /// Stages a slice of memory to be appended to the input stream when the current unconstrained
+    /// block exits.
+    HINT_SLICE = 114,
+
+    /// Halts the program after a panic, capturing the panic message and a backtrace.
+    PANIC = 115,
+
+    /// Checks the type of the next frame of the input stream, when the host set an input schema.
+    READ_FRAME = 116,
+
+    /// Asks the runtime to start a new shard after the current instruction.
+    SHARD_HINT = 117,
+
+    /// Halts the program after a failed assertion, capturing its message and values.
+    ASSERT_FAILED = 118,
+
+    /// Loads a little-endian u64 from two consecutive words.
+    LOAD64 = 119,
+
+    /// Stores a little-endian u64 to two consecutive words.
+    STORE64 = 120,
+
+    /// Asks the runtime to buffer the next bytes of input ahead of the reads of the guest.
+    PREFETCH_INPUT = 121,
+
     WRITE = 999,
 }
 
 impl SyscallCode {
     /// Create a syscall from a u32.
     pub fn from_u32(value: u32) -> Self {
-        match value {
+        Self::try_from_u32(value).unwrap_or_else(|| panic!("invalid syscall number: {}", value))
+    }
+
+    /// Get the syscall code for the given value, or `None` if there is no such syscall.
+    pub fn try_from_u32(value: u32) -> Option<Self> {
+        let code = match value {
             100 => SyscallCode::HALT,
             101 => SyscallCode::LWA,
             102 => SyscallCode::SHA_EXTEND,
@@ -80,54 +119,202 @@ impl SyscallCode {
             110 => SyscallCode::ENTER_UNCONSTRAINED,
             111 => SyscallCode::EXIT_UNCONSTRAINED,
             112 => SyscallCode::BLAKE3_COMPRESS_INNER,
+            113 => SyscallCode::WRITE_CHANNEL,
+            114 => SyscallCode::HINT_SLICE,
+            115 => SyscallCode::PANIC,
+            116 => SyscallCode::READ_FRAME,
+            117 => SyscallCode::SHARD_HINT,
+            118 => SyscallCode::ASSERT_FAILED,
+            119 => SyscallCode::LOAD64,
+            120 => SyscallCode::STORE64,
+            121 => SyscallCode::PREFETCH_INPUT,
             999 => SyscallCode::WRITE,
-            _ => panic!("invalid syscall number: {}", value),
-        }
+            _ => return None,
+        };
+        Some(code)
     }
 }
 
 pub trait Syscall {
-    /// Execute the syscall and return the resulting value of register a0.
+    /// Execute the syscall and return the resulting value of register a0. The syscall consumes
+    /// the extra cycles it advances the clock by with [`SyscallContext::advance`], at most
+    /// `num_extra_cycles`.
     fn execute(&self, ctx: &mut SyscallContext) -> u32;
 
-    /// The number of extra cycles that the syscall takes to execute. Unless this syscall is complex
-    /// and requires many cycles, this should be zero.
+    /// An upper bound on the number of extra cycles that the syscall takes to execute, reserved in
+    /// the shard before it runs. Unless this syscall is complex and requires many cycles, this
+    /// should be zero.
     fn num_extra_cycles(&self) -> u32 {
         0
     }
+
+    /// The byte ranges of memory the syscall may access when invoked with `args`, typically the
+    /// buffers passed by pointer and length. Registered syscalls accessing memory outside of
+    /// their footprint stop execution with `ExecutionError::SyscallFootprintViolation` once
+    /// [`Runtime::set_syscall_footprint_checks`] is enabled, so a syscall that does not declare
+    /// its footprint may not access memory at all. Built-in syscalls are trusted and not checked.
+    fn memory_footprint(&self, _args: SyscallArgs) -> Vec<Range<u32>> {
+        Vec::new()
+    }
+}
+
+/// The arguments of a syscall, read from the registers when the `ecall` is executed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SyscallArgs {
+    /// The syscall code, passed in t0.
+    pub code: u32,
+
+    /// The first argument, passed in a0.
+    pub a0: u32,
+
+    /// The second argument, passed in a1.
+    pub a1: u32,
 }
 
 /// A runtime for syscalls that is protected so that developers cannot arbitrarily modify the runtime.
 pub struct SyscallContext<'a> {
     current_shard: u32,
-    pub clk: u32,
+
+    /// The clock of the accesses, see [`SyscallContext::advance`].
+    pub(crate) clock: SyscallClock,
+
+    args: SyscallArgs,
 
     pub(crate) next_pc: u32,
     pub(crate) rt: &'a mut Runtime,
+
+    /// The buffers accessed so far, when the runtime traces syscalls.
+    pub(crate) traced: Option<Vec<SyscallBuffer>>,
+
+    /// The sorted and disjoint byte ranges the syscall may access, or `None` if it is not checked.
+    pub(crate) footprint: Option<Vec<Range<u32>>>,
+
+    /// The first address accessed outside of the footprint.
+    pub(crate) footprint_violation: Cell<Option<u32>>,
 }
 
 impl<'a> SyscallContext<'a> {
-    pub fn new(runtime: &'a mut Runtime) -> Self {
+    pub fn new(runtime: &'a mut Runtime, args: SyscallArgs) -> Self {
         let current_shard = runtime.current_shard();
-        let clk = runtime.state.clk;
+        let clock = SyscallClock::new(runtime.state.clk, runtime.syscall_clock_journal);
         Self {
             current_shard,
-            clk,
+            clock,
+            args,
             next_pc: runtime.state.pc.wrapping_add(4),
+            traced: runtime.trace_syscalls.then(Vec::new),
             rt: runtime,
+            footprint: None,
+            footprint_violation: Cell::new(None),
+        }
+    }
+
+    /// Restrict the accesses of the syscall to `ranges`, merged into sorted disjoint ranges.
+    pub(crate) fn restrict_to(&mut self, mut ranges: Vec<Range<u32>>) {
+        ranges.retain(|range| !range.is_empty());
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<u32>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        self.footprint = Some(merged);
+    }
+
+    /// Whether the `len` bytes at `addr` are inside the footprint of the syscall, remembering the
+    /// first access that is not.
+    #[inline]
+    fn in_footprint(&self, addr: u32, len: u32) -> bool {
+        let Some(footprint) = &self.footprint else {
+            return true;
+        };
+        let index = footprint.partition_point(|range| range.end <= addr);
+        let inside = footprint.get(index).is_some_and(|range| {
+            range.start <= addr && addr as u64 + len as u64 <= range.end as u64
+        });
+        if !inside && self.footprint_violation.get().is_none() {
+            self.footprint_violation.set(Some(addr));
         }
+        inside
+    }
+
+    /// The arguments the syscall was invoked with.
+    pub fn args(&self) -> SyscallArgs {
+        self.args
     }
 
     pub fn record_mut(&mut self) -> &mut ExecutionRecord {
         &mut self.rt.record
     }
 
+    /// Whether precompiles should push their events to the record.
+    pub fn emit_precompile_events(&self) -> bool {
+        self.rt.record_filter.contains(RecordFilter::PRECOMPILES)
+    }
+
     pub fn current_shard(&self) -> u32 {
         self.rt.state.current_shard
     }
 
+    /// The number of extra cycles the syscall may still take, by advancing the clock, without
+    /// exceeding the cycles reserved for it or running past the end of the current shard.
+    /// Unconstrained blocks never end a shard, so only the reservation applies in them.
+    ///
+    /// The runtime only starts a syscall in a shard with room for its whole
+    /// [`Syscall::num_extra_cycles`], or at the start of a new shard. A syscall reserving more
+    /// cycles than a shard holds therefore starts every shard it runs in, and gets the whole
+    /// shard, see [`SyscallContext::chunked_execute`].
+    pub fn remaining_shard_cycles(&self) -> u32 {
+        let shard_end = match self.rt.unconstrained {
+            true => u32::MAX,
+            false => (self.rt.shard_size * 4).saturating_sub(1),
+        };
+        let reserved_end = self
+            .rt
+            .state
+            .clk
+            .saturating_add(self.rt.syscall_cycles_bound(self.args.code));
+        shard_end.min(reserved_end).saturating_sub(self.clk())
+    }
+
+    /// Process as many of `total_units` units of work as fit in
+    /// [`SyscallContext::remaining_shard_cycles`], calling `f` with the index of every unit and
+    /// advancing the clock by `cycles_per_unit` after it, and return the number of units processed.
+    ///
+    /// This is how length-dependent syscalls deal with requests longer than a shard: the syscall
+    /// processes a prefix of the request and returns its length in a0, and the guest invokes it
+    /// again with the rest, which then starts in a new shard. Such syscalls reserve more cycles
+    /// than a shard holds, so that every invocation gets a whole shard and makes progress.
+    pub fn chunked_execute(
+        &mut self,
+        total_units: u32,
+        cycles_per_unit: u32,
+        mut f: impl FnMut(&mut Self, u32),
+    ) -> u32 {
+        let units = match cycles_per_unit {
+            0 => total_units,
+            _ => total_units.min(self.remaining_shard_cycles() / cycles_per_unit),
+        };
+        for i in 0..units {
+            f(self, i);
+            self.advance(cycles_per_unit);
+        }
+        units
+    }
+
     pub fn mr(&mut self, addr: u32) -> (MemoryReadRecord, u32) {
-        let record = self.rt.mr(addr, self.current_shard, self.clk);
+        // Accesses outside of the footprint are skipped and stop execution once the syscall
+        // returns.
+        if !self.in_footprint(addr, 4) {
+            return (MemoryReadRecord::default(), 0);
+        }
+        // An address out of range stops execution once the syscall returns.
+        self.rt.effective_address(addr, 0);
+        self.clock.read(addr);
+        let record = self.rt.mr(addr, self.current_shard, self.clk());
+        self.trace_access(BufferAccess::Read, addr, record.value);
         (record, record.value)
     }
 
@@ -135,7 +322,7 @@ impl<'a> SyscallContext<'a> {
         let mut records = Vec::new();
         let mut values = Vec::new();
         for i in 0..len {
-            let (record, value) = self.mr(addr + i as u32 * 4);
+            let (record, value) = self.mr(addr.wrapping_add(i as u32 * 4));
             records.push(record);
             values.push(value);
         }
@@ -143,18 +330,38 @@ impl<'a> SyscallContext<'a> {
     }
 
     pub fn mw(&mut self, addr: u32, value: u32) -> MemoryWriteRecord {
-        self.rt.mw(addr, value, self.current_shard, self.clk)
+        if !self.in_footprint(addr, 4) {
+            return MemoryWriteRecord::default();
+        }
+        self.rt.effective_address(addr, 0);
+        self.trace_access(BufferAccess::Write, addr, value);
+        self.clock.write(addr);
+        self.rt.mw(addr, value, self.current_shard, self.clk())
     }
 
     pub fn mw_slice(&mut self, addr: u32, values: &[u32]) -> Vec<MemoryWriteRecord> {
         let mut records = Vec::new();
         for i in 0..values.len() {
-            let record = self.mw(addr + i as u32 * 4, values[i]);
+            let record = self.mw(addr.wrapping_add(i as u32 * 4), values[i]);
             records.push(record);
         }
         records
     }
 
+    /// Write `value` to `register` at the current clock, for syscalls that return more than a0.
+    /// The ecall writes its result to a0 after the syscall returns, so a0 cannot be written here.
+    pub fn rw(&mut self, register: Register, value: u32) -> MemoryWriteRecord {
+        assert!(
+            !matches!(register, Register::X0 | Register::X10),
+            "syscalls cannot write {:?}",
+            register
+        );
+        self.rt.observe_register_write(register, value);
+        self.clock.write(register as u32);
+        self.rt
+            .mw(register as u32, value, self.current_shard, self.clk())
+    }
+
     /// Get the current value of a register, but doesn't use a memory record.
     /// This is generally unconstrained, so you must be careful using it.
     pub fn register_unsafe(&self, register: Register) -> u32 {
@@ -162,70 +369,365 @@ impl<'a> SyscallContext<'a> {
     }
 
     pub fn byte_unsafe(&self, addr: u32) -> u8 {
+        if !self.in_footprint(addr, 1) {
+            return 0;
+        }
         self.rt.byte(addr)
     }
 
     pub fn word_unsafe(&self, addr: u32) -> u32 {
+        if !self.in_footprint(addr, 4) {
+            return 0;
+        }
         self.rt.word(addr)
     }
 
     pub fn slice_unsafe(&self, addr: u32, len: usize) -> Vec<u32> {
         let mut values = Vec::new();
         for i in 0..len {
-            values.push(self.rt.word(addr + i as u32 * 4));
+            values.push(self.word_unsafe(addr + i as u32 * 4));
         }
         values
     }
 
+    /// Like [`SyscallContext::word_unsafe`], returning an error if `addr` is not aligned.
+    pub fn try_word(&self, addr: u32) -> Result<u32, Error> {
+        if !self.in_footprint(addr, 4) {
+            return Ok(0);
+        }
+        self.rt.try_word(addr)
+    }
+
+    /// Like [`SyscallContext::slice_unsafe`], returning an error if `addr` is not aligned or the
+    /// slice extends past the end of the address space, as it may for pointers from the guest.
+    pub fn try_slice(&self, addr: u32, len: usize) -> Result<Vec<u32>, Error> {
+        if addr % 4 != 0 {
+            return Err(Error::UnalignedAddress { addr });
+        }
+        let last = u32::try_from(len.saturating_sub(1))
+            .ok()
+            .and_then(|last| last.checked_mul(4))
+            .and_then(|offset| addr.checked_add(offset));
+        if last.is_none() {
+            return Err(Error::AddressOverflow { addr, len });
+        }
+        Ok(self.slice_unsafe(addr, len))
+    }
+
     pub fn set_next_pc(&mut self, next_pc: u32) {
         self.next_pc = next_pc;
     }
 }
 
-pub fn default_syscall_map() -> HashMap<SyscallCode, Rc<dyn Syscall>> {
-    let mut syscall_map = HashMap::<SyscallCode, Rc<dyn Syscall>>::default();
-    syscall_map.insert(SyscallCode::HALT, Rc::new(SyscallHalt {}));
-    syscall_map.insert(SyscallCode::LWA, Rc::new(SyscallLWA::new()));
-    syscall_map.insert(SyscallCode::SHA_EXTEND, Rc::new(ShaExtendChip::new()));
-    syscall_map.insert(SyscallCode::SHA_COMPRESS, Rc::new(ShaCompressChip::new()));
+pub fn default_syscall_map() -> HashMap<SyscallCode, Arc<dyn Syscall + Send + Sync>> {
+    let mut syscall_map = HashMap::<SyscallCode, Arc<dyn Syscall + Send + Sync>>::default();
+    syscall_map.insert(SyscallCode::HALT, Arc::new(SyscallHalt {}));
+    syscall_map.insert(SyscallCode::PANIC, Arc::new(SyscallPanic::new()));
+    syscall_map.insert(
+        SyscallCode::ASSERT_FAILED,
+        Arc::new(SyscallAssertFailed::new()),
+    );
+    syscall_map.insert(SyscallCode::LWA, Arc::new(SyscallLWA::new()));
+    syscall_map.insert(SyscallCode::LOAD64, Arc::new(SyscallLoad64::new()));
+    syscall_map.insert(SyscallCode::STORE64, Arc::new(SyscallStore64::new()));
+    syscall_map.insert(SyscallCode::READ_FRAME, Arc::new(SyscallReadFrame::new()));
+    syscall_map.insert(
+        SyscallCode::PREFETCH_INPUT,
+        Arc::new(SyscallPrefetchInput::new()),
+    );
+    syscall_map.insert(SyscallCode::SHA_EXTEND, Arc::new(ShaExtendChip::new()));
+    syscall_map.insert(SyscallCode::SHA_COMPRESS, Arc::new(ShaCompressChip::new()));
     syscall_map.insert(
         SyscallCode::ED_ADD,
-        Rc::new(EdAddAssignChip::<Ed25519>::new()),
+        Arc::new(EdAddAssignChip::<Ed25519>::new()),
     );
     syscall_map.insert(
         SyscallCode::ED_DECOMPRESS,
-        Rc::new(EdDecompressChip::<Ed25519Parameters>::new()),
+        Arc::new(EdDecompressChip::<Ed25519Parameters>::new()),
     );
     syscall_map.insert(
         SyscallCode::KECCAK_PERMUTE,
-        Rc::new(KeccakPermuteChip::new()),
+        Arc::new(KeccakPermuteChip::new()),
     );
     syscall_map.insert(
         SyscallCode::SECP256K1_ADD,
-        Rc::new(WeierstrassAddAssignChip::<Secp256k1>::new()),
+        Arc::new(WeierstrassAddAssignChip::<Secp256k1>::new()),
     );
     syscall_map.insert(
         SyscallCode::SECP256K1_DOUBLE,
-        Rc::new(WeierstrassDoubleAssignChip::<Secp256k1>::new()),
+        Arc::new(WeierstrassDoubleAssignChip::<Secp256k1>::new()),
     );
-    syscall_map.insert(SyscallCode::SHA_COMPRESS, Rc::new(ShaCompressChip::new()));
+    syscall_map.insert(SyscallCode::SHA_COMPRESS, Arc::new(ShaCompressChip::new()));
     syscall_map.insert(
         SyscallCode::SECP256K1_DECOMPRESS,
-        Rc::new(K256DecompressChip::new()),
+        Arc::new(K256DecompressChip::new()),
     );
     syscall_map.insert(
         SyscallCode::BLAKE3_COMPRESS_INNER,
-        Rc::new(Blake3CompressInnerChip::new()),
+        Arc::new(Blake3CompressInnerChip::new()),
     );
     syscall_map.insert(
         SyscallCode::ENTER_UNCONSTRAINED,
-        Rc::new(SyscallEnterUnconstrained::new()),
+        Arc::new(SyscallEnterUnconstrained::new()),
     );
     syscall_map.insert(
         SyscallCode::EXIT_UNCONSTRAINED,
-        Rc::new(SyscallExitUnconstrained::new()),
+        Arc::new(SyscallExitUnconstrained::new()),
+    );
+    syscall_map.insert(SyscallCode::HINT_SLICE, Arc::new(SyscallHintSlice::new()));
+    syscall_map.insert(SyscallCode::SHARD_HINT, Arc::new(SyscallShardHint::new()));
+    syscall_map.insert(SyscallCode::WRITE, Arc::new(SyscallWrite::new()));
+    syscall_map.insert(
+        SyscallCode::WRITE_CHANNEL,
+        Arc::new(SyscallWriteChannel::new()),
     );
-    syscall_map.insert(SyscallCode::WRITE, Rc::new(SyscallWrite::new()));
 
     syscall_map
 }
+
+/// The syscalls of [`default_syscall_map`], built once and shared by every runtime.
+struct BuiltinSyscalls {
+    map: HashMap<SyscallCode, Arc<dyn Syscall + Send + Sync>>,
+
+    /// The largest number of extra cycles of a built-in syscall.
+    max_cycles: u32,
+}
+
+static BUILTIN_SYSCALLS: OnceLock<BuiltinSyscalls> = OnceLock::new();
+
+fn builtin_syscalls() -> &'static BuiltinSyscalls {
+    BUILTIN_SYSCALLS.get_or_init(|| {
+        let map = default_syscall_map();
+        let max_cycles = map
+            .values()
+            .map(|syscall| syscall.num_extra_cycles())
+            .max()
+            .unwrap_or(0);
+        BuiltinSyscalls { map, max_cycles }
+    })
+}
+
+/// The syscalls of a runtime: the built-in ones of [`default_syscall_map`], shared by every
+/// runtime, overlaid with the ones registered on or removed from this runtime. Creating one
+/// allocates nothing until a syscall is registered.
+#[derive(Clone)]
+pub struct SyscallMap {
+    /// The syscalls registered on this runtime, or `None` for built-in syscalls removed from it.
+    overlay: HashMap<SyscallCode, Option<Arc<dyn Syscall + Send + Sync>>>,
+
+    /// Whether the built-in syscalls are included, until the map is cleared.
+    builtins: bool,
+}
+
+impl Default for SyscallMap {
+    fn default() -> Self {
+        Self {
+            overlay: HashMap::new(),
+            builtins: true,
+        }
+    }
+}
+
+impl SyscallMap {
+    /// A map of the built-in syscalls.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A map without any syscall.
+    pub fn empty() -> Self {
+        Self {
+            overlay: HashMap::new(),
+            builtins: false,
+        }
+    }
+
+    /// Whether the map holds exactly the built-in syscalls, shared with other runtimes.
+    pub fn is_default(&self) -> bool {
+        self.builtins && self.overlay.is_empty()
+    }
+
+    pub fn get(&self, code: &SyscallCode) -> Option<&Arc<dyn Syscall + Send + Sync>> {
+        match self.overlay.get(code) {
+            Some(syscall) => syscall.as_ref(),
+            None if self.builtins => builtin_syscalls().map.get(code),
+            None => None,
+        }
+    }
+
+    pub fn contains_key(&self, code: &SyscallCode) -> bool {
+        self.get(code).is_some()
+    }
+
+    /// Whether the syscall for `code` was registered on this runtime rather than built in.
+    pub fn is_registered(&self, code: &SyscallCode) -> bool {
+        self.overlay.get(code).is_some_and(Option::is_some)
+    }
+
+    /// Register `syscall` for `code` on this runtime only, returning the syscall it replaces.
+    pub fn insert(
+        &mut self,
+        code: SyscallCode,
+        syscall: Arc<dyn Syscall + Send + Sync>,
+    ) -> Option<Arc<dyn Syscall + Send + Sync>> {
+        let previous = self.get(&code).cloned();
+        self.overlay.insert(code, Some(syscall));
+        previous
+    }
+
+    /// Remove the syscall for `code` from this runtime only, returning it.
+    pub fn remove(&mut self, code: &SyscallCode) -> Option<Arc<dyn Syscall + Send + Sync>> {
+        let previous = self.get(code).cloned();
+        if self.builtins && builtin_syscalls().map.contains_key(code) {
+            self.overlay.insert(*code, None);
+        } else {
+            self.overlay.remove(code);
+        }
+        previous
+    }
+
+    /// Remove every syscall, including the built-in ones.
+    pub fn clear(&mut self) {
+        self.overlay.clear();
+        self.builtins = false;
+    }
+
+    /// Keep only the syscalls for which `f` returns true.
+    pub fn retain(
+        &mut self,
+        mut f: impl FnMut(&SyscallCode, &Arc<dyn Syscall + Send + Sync>) -> bool,
+    ) {
+        let removed = self
+            .iter()
+            .filter(|(code, syscall)| !f(code, syscall))
+            .map(|(code, _)| *code)
+            .collect::<Vec<_>>();
+        for code in removed {
+            self.remove(&code);
+        }
+    }
+
+    /// The syscalls of the map, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&SyscallCode, &Arc<dyn Syscall + Send + Sync>)> {
+        let builtins = self
+            .builtins
+            .then(|| builtin_syscalls().map.iter())
+            .into_iter()
+            .flatten()
+            .filter(|(code, _)| !self.overlay.contains_key(*code));
+        let overlay = self
+            .overlay
+            .iter()
+            .filter_map(|(code, syscall)| syscall.as_ref().map(|syscall| (code, syscall)));
+        builtins.chain(overlay)
+    }
+
+    /// The largest number of extra cycles of a syscall of the map, or zero if it is empty. Only
+    /// the registered syscalls are visited unless built-in ones were removed.
+    pub fn max_syscall_cycles(&self) -> u32 {
+        let removed = !self.builtins || self.overlay.values().any(Option::is_none);
+        let syscalls = if removed {
+            self.iter().map(|(_, syscall)| syscall).collect::<Vec<_>>()
+        } else {
+            self.overlay.values().flatten().collect()
+        };
+        let max_cycles = syscalls
+            .iter()
+            .map(|syscall| syscall.num_extra_cycles())
+            .max()
+            .unwrap_or(0);
+        if removed {
+            max_cycles
+        } else {
+            max_cycles.max(builtin_syscalls().max_cycles)
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::simple_program;
+
+    struct SlowSyscall;
+
+    impl Syscall for SlowSyscall {
+        fn execute(&self, _: &mut SyscallContext) -> u32 {
+            0
+        }
+
+        fn num_extra_cycles(&self) -> u32 {
+            1 << 20
+        }
+    }
+
+    #[test]
+    fn test_shared_builtins() {
+        let defaults = default_syscall_map();
+        let runtime = Runtime::new(simple_program());
+        let other = Runtime::new(simple_program());
+        assert!(runtime.syscall_map.is_default());
+        assert_eq!(runtime.syscall_map.iter().count(), defaults.len());
+        for code in defaults.keys() {
+            assert!(Arc::ptr_eq(
+                runtime.syscall_map.get(code).unwrap(),
+                other.syscall_map.get(code).unwrap()
+            ));
+        }
+        assert_eq!(
+            runtime.syscall_map.max_syscall_cycles(),
+            defaults
+                .values()
+                .map(|syscall| syscall.num_extra_cycles())
+                .max()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_overlay() {
+        let mut runtime = Runtime::new(simple_program());
+        let halt = runtime.syscall_map.get(&SyscallCode::HALT).unwrap().clone();
+        let replaced = runtime
+            .syscall_map
+            .insert(SyscallCode::HALT, Arc::new(SlowSyscall));
+        assert!(Arc::ptr_eq(&replaced.unwrap(), &halt));
+        assert_eq!(runtime.syscall_map.max_syscall_cycles(), 1 << 20);
+        assert!(runtime.syscall_map.remove(&SyscallCode::WRITE).is_some());
+        assert!(!runtime.syscall_map.contains_key(&SyscallCode::WRITE));
+        assert!(!runtime.syscall_map.is_default());
+
+        // Other runtimes still see the built-in syscalls.
+        let other = Runtime::new(simple_program());
+        assert!(Arc::ptr_eq(
+            other.syscall_map.get(&SyscallCode::HALT).unwrap(),
+            &halt
+        ));
+        assert!(other.syscall_map.contains_key(&SyscallCode::WRITE));
+        assert!(other.syscall_map.max_syscall_cycles() < 1 << 20);
+
+        runtime
+            .syscall_map
+            .retain(|code, _| *code == SyscallCode::HALT || *code == SyscallCode::SHA_EXTEND);
+        let mut codes = runtime
+            .syscall_map
+            .iter()
+            .map(|(code, _)| *code as u32)
+            .collect::<Vec<_>>();
+        codes.sort_unstable();
+        assert_eq!(
+            codes,
+            vec![SyscallCode::HALT as u32, SyscallCode::SHA_EXTEND as u32]
+        );
+        assert_eq!(runtime.syscall_map.max_syscall_cycles(), 1 << 20);
+
+        runtime.syscall_map.clear();
+        assert_eq!(runtime.syscall_map.iter().count(), 0);
+        assert_eq!(runtime.syscall_map.max_syscall_cycles(), 0);
+        runtime
+            .syscall_map
+            .insert(SyscallCode::WRITE_CHANNEL, Arc::new(SlowSyscall));
+        assert_eq!(runtime.syscall_map.iter().count(), 1);
+        assert!(SyscallMap::empty().get(&SyscallCode::HALT).is_none());
+    }
+}
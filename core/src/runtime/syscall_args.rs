@@ -0,0 +1,262 @@
+use core::fmt::{Display, Formatter};
+
+use super::{SyscallContext, NUM_REGISTERS};
+
+/// The default of [`RuntimeOptions::max_syscall_arg_len`](super::RuntimeOptions::max_syscall_arg_len).
+pub const DEFAULT_MAX_SYSCALL_ARG_LEN: u32 = 1 << 20;
+
+/// Returned in a0 by syscalls taking a string argument whose bytes are not valid UTF-8.
+pub const SYSCALL_ERR_INVALID_UTF8: u32 = 0xffff_fff1;
+
+/// Returned in a0 by syscalls taking a string or byte slice argument longer than
+/// [`RuntimeOptions::max_syscall_arg_len`](super::RuntimeOptions::max_syscall_arg_len).
+pub const SYSCALL_ERR_LENGTH_TOO_LARGE: u32 = 0xffff_fff2;
+
+/// Returned in a0 by syscalls taking a string or byte slice argument that wraps past the end of
+/// the address space.
+pub const SYSCALL_ERR_ADDRESS_WRAP: u32 = 0xffff_fff3;
+
+/// Returned in a0 by syscalls taking a string or byte slice argument that starts in the
+/// registers.
+pub const SYSCALL_ERR_REGISTER_RANGE: u32 = 0xffff_fff4;
+
+/// A string or byte slice argument of a syscall that cannot be read, see
+/// [`SyscallContext::read_bytes_checked`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallArgError {
+    /// The bytes are not valid UTF-8 from the byte at offset `valid_up_to` on.
+    InvalidUtf8 { valid_up_to: usize },
+
+    /// The argument is longer than the maximum length of an argument.
+    LengthTooLarge { len: u32, max: u32 },
+
+    /// The last byte of the argument is past the end of the address space.
+    AddressWrap { addr: u32, len: u32 },
+
+    /// The argument starts at the address of a register rather than of memory.
+    RegisterRange { addr: u32 },
+}
+
+impl SyscallArgError {
+    /// The code returned to the guest in a0 for this error.
+    pub fn code(&self) -> u32 {
+        match self {
+            SyscallArgError::InvalidUtf8 { .. } => SYSCALL_ERR_INVALID_UTF8,
+            SyscallArgError::LengthTooLarge { .. } => SYSCALL_ERR_LENGTH_TOO_LARGE,
+            SyscallArgError::AddressWrap { .. } => SYSCALL_ERR_ADDRESS_WRAP,
+            SyscallArgError::RegisterRange { .. } => SYSCALL_ERR_REGISTER_RANGE,
+        }
+    }
+}
+
+impl Display for SyscallArgError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SyscallArgError::InvalidUtf8 { valid_up_to } => {
+                write!(f, "invalid UTF-8 at byte {}", valid_up_to)
+            }
+            SyscallArgError::LengthTooLarge { len, max } => {
+                write!(f, "argument of {} bytes is longer than {} bytes", len, max)
+            }
+            SyscallArgError::AddressWrap { addr, len } => write!(
+                f,
+                "argument of {} bytes at 0x{:x} wraps past the end of the address space",
+                len, addr
+            ),
+            SyscallArgError::RegisterRange { addr } => {
+                write!(f, "argument at 0x{:x} starts in the registers", addr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SyscallArgError {}
+
+impl SyscallContext<'_> {
+    /// Read the `len` bytes at `addr` passed by the guest, or return an error if they cannot be
+    /// read: if `len` is larger than
+    /// [`RuntimeOptions::max_syscall_arg_len`](super::RuntimeOptions::max_syscall_arg_len), if
+    /// they wrap past the end of the address space, or if they start in the registers.
+    ///
+    /// The words are read without memory records, as no chip proves the reads, so the bytes may
+    /// only be used on the host, e.g. for output or hints.
+    pub fn read_bytes_checked(&self, addr: u32, len: u32) -> Result<Vec<u8>, SyscallArgError> {
+        let max = self.rt.max_syscall_arg_len;
+        if len > max {
+            return Err(SyscallArgError::LengthTooLarge { len, max });
+        }
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let Some(last) = addr.checked_add(len - 1) else {
+            return Err(SyscallArgError::AddressWrap { addr, len });
+        };
+        if addr < NUM_REGISTERS {
+            return Err(SyscallArgError::RegisterRange { addr });
+        }
+        let (first_word, last_word) = (addr & !3, last & !3);
+        let mut bytes = Vec::with_capacity((last_word - first_word) as usize + 4);
+        for word in (first_word..=last_word).step_by(4) {
+            bytes.extend_from_slice(&self.word_unsafe(word).to_le_bytes());
+        }
+        let start = (addr - first_word) as usize;
+        bytes.truncate(start + len as usize);
+        bytes.drain(..start);
+        Ok(bytes)
+    }
+
+    /// Read the string of `len` bytes at `addr` passed by the guest, see
+    /// [`SyscallContext::read_bytes_checked`], returning an error if it is not valid UTF-8.
+    pub fn read_str(&self, addr: u32, len: u32) -> Result<String, SyscallArgError> {
+        let bytes = self.read_bytes_checked(addr, len)?;
+        String::from_utf8(bytes).map_err(|err| SyscallArgError::InvalidUtf8 {
+            valid_up_to: err.utf8_error().valid_up_to(),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{
+        Program, Register, Runtime, RuntimeOptions, Syscall, SyscallArgs, SyscallCode,
+    };
+    use crate::syscall::SyscallHintSlice;
+    use crate::utils::asm::assemble;
+
+    const MESSAGE: u32 = 0x1000;
+
+    /// Prints the `len` bytes at `addr` to stdout.
+    fn print_program(addr: u32, len: u32, bytes: &[u8]) -> Program {
+        let mut program = assemble(
+            &format!(
+                "li   t0, {}
+                 li   a0, 1
+                 li   a1, {}
+                 li   a2, {}
+                 ecall",
+                SyscallCode::WRITE as u32,
+                addr,
+                len
+            ),
+            0,
+        )
+        .unwrap();
+        for (i, chunk) in bytes.chunks(4).enumerate() {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            program
+                .memory_image
+                .insert(MESSAGE + 4 * i as u32, u32::from_le_bytes(word));
+        }
+        program
+    }
+
+    /// The value of a0 after printing the `len` bytes at `addr`.
+    fn print(addr: u32, len: u32, bytes: &[u8], options: RuntimeOptions) -> u32 {
+        let mut runtime = Runtime::new_with_options(print_program(addr, len, bytes), options);
+        runtime.run();
+        runtime.register(Register::X10)
+    }
+
+    #[test]
+    fn test_print_error_codes() {
+        assert_eq!(print(MESSAGE, 5, b"hello", RuntimeOptions::default()), 0);
+        assert_eq!(
+            print(MESSAGE, 6, b"hel\xfflo", RuntimeOptions::default()),
+            SYSCALL_ERR_INVALID_UTF8
+        );
+        assert_eq!(
+            print(0xffff_fff0, 0x20, b"", RuntimeOptions::default()),
+            SYSCALL_ERR_ADDRESS_WRAP
+        );
+        assert_eq!(
+            print(
+                MESSAGE,
+                5,
+                b"hello",
+                RuntimeOptions::default().with_max_syscall_arg_len(4)
+            ),
+            SYSCALL_ERR_LENGTH_TOO_LARGE
+        );
+        assert_eq!(
+            print(8, 4, b"", RuntimeOptions::default()),
+            SYSCALL_ERR_REGISTER_RANGE
+        );
+    }
+
+    #[test]
+    fn test_read_str() {
+        let mut runtime = Runtime::new(print_program(MESSAGE, 0, b"xhello\0\0ab\xffcd"));
+        runtime.initialize();
+        runtime.state.clk = 40;
+        let args = SyscallArgs {
+            code: SyscallCode::WRITE as u32,
+            a0: 1,
+            a1: MESSAGE,
+        };
+        let ctx = SyscallContext::new(&mut runtime, args);
+        // A string starting inside a word and ending in the next one.
+        assert_eq!(ctx.read_str(MESSAGE + 1, 5).unwrap(), "hello");
+        assert_eq!(
+            ctx.read_str(MESSAGE + 8, 5),
+            Err(SyscallArgError::InvalidUtf8 { valid_up_to: 2 })
+        );
+        assert_eq!(ctx.read_bytes_checked(MESSAGE, 0), Ok(Vec::new()));
+        assert_eq!(
+            ctx.read_bytes_checked(u32::MAX, 2),
+            Err(SyscallArgError::AddressWrap {
+                addr: u32::MAX,
+                len: 2
+            })
+        );
+        assert_eq!(
+            ctx.read_bytes_checked(MESSAGE, DEFAULT_MAX_SYSCALL_ARG_LEN + 1),
+            Err(SyscallArgError::LengthTooLarge {
+                len: DEFAULT_MAX_SYSCALL_ARG_LEN + 1,
+                max: DEFAULT_MAX_SYSCALL_ARG_LEN
+            })
+        );
+
+        // The words are read without records.
+        for word in [MESSAGE, MESSAGE + 4, MESSAGE + 8, MESSAGE + 12] {
+            assert_eq!(runtime.state.memory.get(&word).unwrap().2, 0);
+        }
+    }
+
+    #[test]
+    fn test_slice_error_codes() {
+        // A channel write wrapping past the end of the address space.
+        let program = assemble(
+            &format!(
+                "li   t0, {}
+                 li   a0, 1
+                 li   a1, 0xfffffff0
+                 li   a2, 0x20
+                 ecall",
+                SyscallCode::WRITE_CHANNEL as u32,
+            ),
+            0,
+        )
+        .unwrap();
+        let mut runtime = Runtime::new(program);
+        runtime.run();
+        assert_eq!(runtime.register(Register::X10), SYSCALL_ERR_ADDRESS_WRAP);
+        assert!(runtime.output_channel(1).is_empty());
+
+        // A hint longer than the maximum length.
+        let mut runtime = Runtime::new(print_program(MESSAGE, 0, b""));
+        runtime.initialize();
+        runtime.unconstrained = true;
+        let args = SyscallArgs {
+            code: SyscallCode::HINT_SLICE as u32,
+            a0: MESSAGE,
+            a1: u32::MAX,
+        };
+        let mut ctx = SyscallContext::new(&mut runtime, args);
+        assert_eq!(
+            SyscallHintSlice::new().execute(&mut ctx),
+            SYSCALL_ERR_LENGTH_TOO_LARGE
+        );
+    }
+}
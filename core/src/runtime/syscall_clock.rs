@@ -0,0 +1,325 @@
+use core::fmt::{Display, Formatter};
+
+use super::{Runtime, SyscallContext};
+
+/// An access or clock advance of a syscall, in the order the syscall made them, see
+/// [`Runtime::set_syscall_clock_journal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallJournalEntry {
+    /// The word at `addr` was read at `clk`.
+    Read { addr: u32, clk: u32 },
+
+    /// The word at `addr`, or the register with that address, was written at `clk`.
+    Write { addr: u32, clk: u32 },
+
+    /// The clock was advanced from `clk` by `cycles`.
+    Advance { clk: u32, cycles: u32 },
+}
+
+impl Display for SyscallJournalEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SyscallJournalEntry::Read { addr, clk } => write!(f, "read 0x{:x} at {}", addr, clk),
+            SyscallJournalEntry::Write { addr, clk } => {
+                write!(f, "write 0x{:x} at {}", addr, clk)
+            }
+            SyscallJournalEntry::Advance { clk, cycles } => {
+                write!(f, "advance {} -> {}", clk, clk + cycles)
+            }
+        }
+    }
+}
+
+/// A mistake in the clock accounting of a syscall, see
+/// [`ExecutionError::SyscallClockMismatch`](super::ExecutionError::SyscallClockMismatch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockFault {
+    /// The syscall advanced the clock by more cycles than its `Syscall::num_extra_cycles`.
+    OverBound { cycles: u32, bound: u32 },
+
+    /// The syscall advanced the clock at `clk` without an access since its previous advance,
+    /// typically advancing twice for the same access.
+    DoubleAdvance { clk: u32 },
+}
+
+impl Display for ClockFault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ClockFault::OverBound { cycles, bound } => write!(
+                f,
+                "took {} extra cycles, more than its bound of {}",
+                cycles, bound
+            ),
+            ClockFault::DoubleAdvance { clk } => write!(
+                f,
+                "advanced the clock at {} without an access since its previous advance",
+                clk
+            ),
+        }
+    }
+}
+
+/// The clock of a syscall, which only advances through [`SyscallContext::advance`] and records
+/// the accesses made at each clock.
+#[derive(Debug, Clone)]
+pub(crate) struct SyscallClock {
+    /// The clock of the `ecall`.
+    start: u32,
+
+    clk: u32,
+
+    /// Whether the clock was advanced since the last access, after at least one advance.
+    advanced: bool,
+
+    /// The first clock advanced twice without an access in between.
+    double_advance: Option<u32>,
+
+    /// The accesses and advances so far, when the journal is enabled.
+    journal: Option<Vec<SyscallJournalEntry>>,
+}
+
+impl SyscallClock {
+    pub(crate) fn new(clk: u32, journal: bool) -> Self {
+        Self {
+            start: clk,
+            clk,
+            advanced: false,
+            double_advance: None,
+            journal: journal.then(Vec::new),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn clk(&self) -> u32 {
+        self.clk
+    }
+
+    #[inline]
+    pub(crate) fn read(&mut self, addr: u32) {
+        self.advanced = false;
+        if let Some(journal) = &mut self.journal {
+            journal.push(SyscallJournalEntry::Read {
+                addr,
+                clk: self.clk,
+            });
+        }
+    }
+
+    #[inline]
+    pub(crate) fn write(&mut self, addr: u32) {
+        self.advanced = false;
+        if let Some(journal) = &mut self.journal {
+            journal.push(SyscallJournalEntry::Write {
+                addr,
+                clk: self.clk,
+            });
+        }
+    }
+
+    #[inline]
+    fn advance(&mut self, cycles: u32) {
+        if self.advanced && self.double_advance.is_none() {
+            self.double_advance = Some(self.clk);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.push(SyscallJournalEntry::Advance {
+                clk: self.clk,
+                cycles,
+            });
+        }
+        self.clk += cycles;
+        self.advanced = true;
+    }
+
+    /// Check the accounting of a syscall that may take at most `bound` extra cycles.
+    fn check(&self, bound: u32) -> Result<(), ClockFault> {
+        let cycles = self.clk - self.start;
+        if cycles > bound {
+            return Err(ClockFault::OverBound { cycles, bound });
+        }
+        match self.double_advance {
+            Some(clk) => Err(ClockFault::DoubleAdvance { clk }),
+            None => Ok(()),
+        }
+    }
+
+    fn journal(&self) -> &[SyscallJournalEntry] {
+        self.journal.as_deref().unwrap_or_default()
+    }
+}
+
+impl<'a> SyscallContext<'a> {
+    /// The clock the accesses of the syscall currently happen at.
+    pub fn clk(&self) -> u32 {
+        self.clock.clk()
+    }
+
+    /// Advance the clock by `cycles`, so that the following accesses happen at the new clock.
+    /// The syscall takes the cycles it advances the clock by, at most its
+    /// `Syscall::num_extra_cycles`.
+    ///
+    /// Every advance but the first must follow an access: advancing twice in a row is almost
+    /// always the same access counted twice, and stops execution with
+    /// [`ExecutionError::SyscallClockMismatch`](super::ExecutionError::SyscallClockMismatch) once
+    /// the syscall returns.
+    pub fn advance(&mut self, cycles: u32) {
+        self.clock.advance(cycles);
+    }
+
+    /// Panic with the journal of the syscall if its clock accounting is wrong, for the tests of
+    /// syscall implementations. The journal is empty unless
+    /// [`Runtime::set_syscall_clock_journal`] was enabled before creating the context.
+    pub fn assert_balanced(&self) {
+        let bound = self.rt.syscall_cycles_bound(self.args().code);
+        if let Err(fault) = self.clock.check(bound) {
+            panic!(
+                "syscall {} {}, journal: {}",
+                self.args().code,
+                fault,
+                format_journal(self.clock.journal())
+            );
+        }
+    }
+
+    /// Check the clock accounting of the syscall, which may take at most `bound` extra cycles,
+    /// once it returns, and return its final clock, or the fault with the journal of the syscall.
+    pub(crate) fn end(
+        &mut self,
+        bound: u32,
+    ) -> Result<u32, (ClockFault, Vec<SyscallJournalEntry>)> {
+        match self.clock.check(bound) {
+            Ok(()) => Ok(self.clock.clk()),
+            Err(fault) => Err((fault, self.clock.journal.take().unwrap_or_default())),
+        }
+    }
+}
+
+/// The entries of a journal, separated by commas.
+pub(crate) fn format_journal(journal: &[SyscallJournalEntry]) -> String {
+    if journal.is_empty() {
+        return "empty, see Runtime::set_syscall_clock_journal".to_string();
+    }
+    journal
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Runtime {
+    /// Record every access and clock advance of the syscalls, so that the error of a syscall
+    /// miscounting its cycles lists them, see [`SyscallJournalEntry`]. This slows down syscalls
+    /// and is meant for debugging their implementations.
+    pub fn set_syscall_clock_journal(&mut self, enabled: bool) {
+        self.syscall_clock_journal = enabled;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::runtime::{
+        ExecutionError, Instruction, Opcode, Program, Syscall, SyscallArgs, SyscallCode,
+    };
+
+    /// Copies the word at a0 to a0 + 4, but advances the clock twice after the read.
+    struct MiscountingSyscall;
+
+    impl Syscall for MiscountingSyscall {
+        fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+            let addr = ctx.args().a0;
+            let (_, value) = ctx.mr(addr);
+            ctx.advance(4);
+            ctx.advance(4);
+            ctx.mw(addr + 4, value);
+            ctx.advance(4);
+            0
+        }
+
+        fn num_extra_cycles(&self) -> u32 {
+            12
+        }
+    }
+
+    fn miscounting_runtime() -> Runtime {
+        let instructions = vec![
+            Instruction::new(
+                Opcode::ADD,
+                5,
+                0,
+                SyscallCode::WRITE_CHANNEL as u32,
+                false,
+                true,
+            ),
+            Instruction::new(Opcode::ADD, 10, 0, 0x1000, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ];
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime
+            .syscall_map
+            .insert(SyscallCode::WRITE_CHANNEL, Arc::new(MiscountingSyscall));
+        runtime
+    }
+
+    #[test]
+    fn test_miscounting_syscall() {
+        let mut runtime = miscounting_runtime();
+        runtime.set_syscall_clock_journal(true);
+        let err = runtime.try_run().unwrap_err();
+        assert_eq!(
+            err,
+            ExecutionError::SyscallClockMismatch {
+                code: SyscallCode::WRITE_CHANNEL,
+                pc: 8,
+                fault: ClockFault::DoubleAdvance { clk: 13 },
+                journal: vec![
+                    SyscallJournalEntry::Read {
+                        addr: 0x1000,
+                        clk: 9
+                    },
+                    SyscallJournalEntry::Advance { clk: 9, cycles: 4 },
+                    SyscallJournalEntry::Advance { clk: 13, cycles: 4 },
+                    SyscallJournalEntry::Write {
+                        addr: 0x1004,
+                        clk: 17
+                    },
+                    SyscallJournalEntry::Advance { clk: 17, cycles: 4 },
+                ],
+            }
+        );
+        assert!(err
+            .to_string()
+            .contains("read 0x1000 at 9, advance 9 -> 13, advance 13 -> 17"));
+
+        // Without the journal the fault is still detected.
+        let err = miscounting_runtime().try_run().unwrap_err();
+        assert!(matches!(
+            err,
+            ExecutionError::SyscallClockMismatch { ref journal, .. } if journal.is_empty()
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "took 16 extra cycles, more than its bound of 12")]
+    fn test_assert_balanced() {
+        let mut runtime = miscounting_runtime();
+        let args = SyscallArgs {
+            code: SyscallCode::WRITE_CHANNEL as u32,
+            a0: 0x1000,
+            a1: 0,
+        };
+        let mut ctx = SyscallContext::new(&mut runtime, args);
+        ctx.mr(0x1000);
+        ctx.advance(4);
+        ctx.mw(0x1004, 0);
+        ctx.advance(4);
+        ctx.assert_balanced();
+        assert_eq!(ctx.clk(), 8);
+
+        ctx.mr(0x1004);
+        ctx.advance(8);
+        ctx.assert_balanced();
+    }
+}
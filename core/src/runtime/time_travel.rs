@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+
+use hashbrown::HashMap;
+use nohash_hasher::BuildNoHashHasher;
+
+use super::{ExecutionError, Runtime};
+
+/// The value, shard and timestamp of a word of memory, `None` if it was never accessed.
+type MemoryEntry = Option<(u32, u32, u32)>;
+
+/// The changes made to the architectural state by one cycle, see
+/// [`Runtime::enable_time_travel`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleDelta {
+    /// The pc of the instruction executed in the cycle.
+    pub pc: u32,
+
+    /// The global clock of the cycle.
+    pub global_clk: u32,
+
+    /// The shard and the clock within it of the cycle.
+    pub shard: u32,
+    pub clk: u32,
+
+    /// The words written in the cycle, registers included, with the entry of the memory before
+    /// the write, `None` if the word was never accessed, and the value written.
+    pub writes: Vec<(u32, MemoryEntry, u32)>,
+}
+
+/// A ring of the deltas of the most recent cycles, and of those undone by
+/// [`Runtime::reverse_step`].
+///
+/// Each delta takes about 40 bytes plus 20 bytes per word written, so most cycles take about 60
+/// bytes and a capacity of one million cycles about 60 MB. Syscalls writing large buffers take
+/// proportionally more.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TimeTravel {
+    pub(crate) capacity: usize,
+
+    /// The deltas of the most recent cycles, oldest first.
+    deltas: VecDeque<CycleDelta>,
+
+    /// The delta of the cycle being executed.
+    current: Option<CycleDelta>,
+
+    /// The deltas undone by `reverse_step`, most recently undone last, with the entries of the
+    /// words they wrote as they were before being undone.
+    undone: Vec<(CycleDelta, Vec<MemoryEntry>)>,
+
+    /// The pc and the clocks the state was rewound from.
+    present: Option<(u32, u32, u32, u32)>,
+}
+
+impl TimeTravel {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            deltas: VecDeque::with_capacity(capacity.min(1 << 16)),
+            current: None,
+            undone: Vec::new(),
+            present: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_write(&mut self, addr: u32, prev: MemoryEntry, value: u32) {
+        if let Some(delta) = &mut self.current {
+            delta.writes.push((addr, prev, value));
+        }
+    }
+}
+
+/// Set the entry of `addr` in `memory`, returning the previous one.
+fn set_entry(
+    memory: &mut HashMap<u32, (u32, u32, u32), BuildNoHashHasher<u32>>,
+    addr: u32,
+    entry: MemoryEntry,
+) -> MemoryEntry {
+    match entry {
+        Some(entry) => memory.insert(addr, entry),
+        None => memory.remove(&addr),
+    }
+}
+
+impl Runtime {
+    /// Keep the deltas of the last `capacity` cycles from now on, so that the state of the
+    /// machine before them can be inspected with [`Runtime::reverse_step`] without executing the
+    /// program again. The cycles of unconstrained blocks are not kept, as the blocks are rolled
+    /// back when they exit: the cycle exiting a block is kept as a second execution of the
+    /// `ecall` that entered it.
+    pub fn enable_time_travel(&mut self, capacity: usize) {
+        self.time_travel = Some(TimeTravel::new(capacity));
+    }
+
+    /// Undo the last `n` cycles kept, or as many as there are, restoring the pc, the clocks,
+    /// the registers and the memory to their values before them. Returns the number of cycles
+    /// undone.
+    ///
+    /// This is for inspection only: the record and the timestamps of the words that were only read
+    /// are not rewound, and [`Runtime::step`] fails with [`ExecutionError::Rewound`] until
+    /// [`Runtime::restore_present`] replays the undone cycles.
+    pub fn reverse_step(&mut self, n: usize) -> usize {
+        let Some(time_travel) = &mut self.time_travel else {
+            return 0;
+        };
+        let mut undone = 0;
+        while undone < n {
+            let Some(delta) = time_travel.deltas.pop_back() else {
+                break;
+            };
+            time_travel.present.get_or_insert((
+                self.state.pc,
+                self.state.global_clk,
+                self.state.current_shard,
+                self.state.clk,
+            ));
+            let mut overwritten = Vec::with_capacity(delta.writes.len());
+            for &(addr, prev, _) in delta.writes.iter().rev() {
+                overwritten.push(set_entry(&mut self.state.memory, addr, prev));
+            }
+            self.state.pc = delta.pc;
+            self.state.global_clk = delta.global_clk;
+            self.state.current_shard = delta.shard;
+            self.state.clk = delta.clk;
+            time_travel.undone.push((delta, overwritten));
+            undone += 1;
+        }
+        undone
+    }
+
+    /// Replay the cycles undone by [`Runtime::reverse_step`], so that execution can continue.
+    pub fn restore_present(&mut self) {
+        let Some(time_travel) = &mut self.time_travel else {
+            return;
+        };
+        // Undoing is a sequence of assignments, so assigning the overwritten entries back in the
+        // opposite order restores the memory exactly, timestamps included.
+        while let Some((delta, overwritten)) = time_travel.undone.pop() {
+            for (&(addr, _, _), &entry) in delta.writes.iter().zip(overwritten.iter().rev()) {
+                set_entry(&mut self.state.memory, addr, entry);
+            }
+            time_travel.deltas.push_back(delta);
+        }
+        if let Some(state) = time_travel.present.take() {
+            (
+                self.state.pc,
+                self.state.global_clk,
+                self.state.current_shard,
+                self.state.clk,
+            ) = state;
+        }
+    }
+
+    /// The number of cycles the state is behind the execution, after [`Runtime::reverse_step`].
+    pub fn rewound_cycles(&self) -> usize {
+        self.time_travel
+            .as_ref()
+            .map_or(0, |time_travel| time_travel.undone.len())
+    }
+
+    /// Start the delta of the cycle about to be executed, or fail if the state was rewound.
+    pub(crate) fn begin_cycle_delta(&mut self) -> Result<(), ExecutionError> {
+        let Some(time_travel) = &mut self.time_travel else {
+            return Ok(());
+        };
+        if !time_travel.undone.is_empty() {
+            return Err(ExecutionError::Rewound {
+                cycles: time_travel.undone.len(),
+                pc: self.state.pc,
+            });
+        }
+        time_travel.current = None;
+        if !self.unconstrained {
+            self.restart_cycle_delta();
+        }
+        Ok(())
+    }
+
+    /// Start the delta of the current cycle over from the current state. Called once an
+    /// unconstrained block exits, so that the cycle of the exit only keeps the writes made after
+    /// the state was rolled back, such as the return value of the syscall.
+    pub(crate) fn restart_cycle_delta(&mut self) {
+        if let Some(time_travel) = &mut self.time_travel {
+            time_travel.current = Some(CycleDelta {
+                pc: self.state.pc,
+                global_clk: self.state.global_clk,
+                shard: self.state.current_shard,
+                clk: self.state.clk,
+                writes: Vec::new(),
+            });
+        }
+    }
+
+    /// Keep the delta of the cycle that was executed, dropping the oldest one if the ring is full.
+    pub(crate) fn end_cycle_delta(&mut self) {
+        let Some(time_travel) = &mut self.time_travel else {
+            return;
+        };
+        let Some(delta) = time_travel.current.take() else {
+            return;
+        };
+        if time_travel.capacity == 0 {
+            return;
+        }
+        if time_travel.deltas.len() == time_travel.capacity {
+            time_travel.deltas.pop_front();
+        }
+        time_travel.deltas.push_back(delta);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+
+    fn run_to(runtime: &mut Runtime, clk: u32) {
+        while runtime.state.global_clk < clk {
+            runtime.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reverse_step() {
+        let mut expected = Runtime::new(fibonacci_program());
+        expected.initialize();
+        run_to(&mut expected, 900);
+
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.enable_time_travel(128);
+        runtime.initialize();
+        run_to(&mut runtime, 1000);
+        assert_eq!(runtime.reverse_step(100), 100);
+        assert_eq!(runtime.rewound_cycles(), 100);
+
+        assert_eq!(runtime.state.global_clk, 900);
+        assert_eq!(runtime.state.pc, expected.state.pc);
+        assert_eq!(runtime.registers(), expected.registers());
+        for (addr, &(value, _, _)) in runtime.state.memory.iter() {
+            let expected = expected.state.memory.get(addr).map_or(0, |entry| entry.0);
+            assert_eq!(value, expected, "addr 0x{:x}", addr);
+        }
+
+        // Only the capacity of the ring can be undone.
+        assert_eq!(runtime.reverse_step(1000), 28);
+        assert_eq!(runtime.state.global_clk, 872);
+    }
+
+    #[test]
+    fn test_step_after_reverse() {
+        let mut expected = Runtime::new(fibonacci_program());
+        expected.initialize();
+        while !expected.is_done() {
+            expected.step().unwrap();
+        }
+
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.enable_time_travel(128);
+        runtime.initialize();
+        run_to(&mut runtime, 1000);
+        runtime.reverse_step(100);
+        let pc = runtime.state.pc;
+        assert_eq!(
+            runtime.step(),
+            Err(ExecutionError::Rewound { cycles: 100, pc })
+        );
+
+        runtime.restore_present();
+        assert_eq!(runtime.rewound_cycles(), 0);
+        assert_eq!(runtime.state.global_clk, 1000);
+        while !runtime.is_done() {
+            runtime.step().unwrap();
+        }
+        assert_eq!(runtime.registers(), expected.registers());
+        assert_eq!(runtime.state.memory, expected.state.memory);
+        assert_eq!(
+            runtime.record.cpu_events.len(),
+            expected.record.cpu_events.len()
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use core::fmt::{Display, Formatter};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+
+use super::{Instruction, Opcode, Register, Runtime, SyscallCode};
+
+/// Every this many instructions of an opcode class, one is timed.
+pub const HOST_TIMING_SAMPLE_INTERVAL: u32 = 1024;
+
+/// A group of opcodes the host interpreter executes in a similar way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OpcodeClass {
+    /// Additions, subtractions, bitwise operations, shifts and comparisons.
+    Alu,
+    Mul,
+    DivRem,
+    /// Loads and stores.
+    Memory,
+    Branch,
+    /// Jumps and `auipc`.
+    Jump,
+    Syscall,
+    /// `ebreak` and `unimp`.
+    Trap,
+}
+
+const NUM_OPCODE_CLASSES: usize = 8;
+
+impl OpcodeClass {
+    pub fn of(opcode: Opcode) -> Self {
+        match opcode {
+            Opcode::MUL | Opcode::MULH | Opcode::MULHU | Opcode::MULHSU => OpcodeClass::Mul,
+            Opcode::DIV | Opcode::DIVU | Opcode::REM | Opcode::REMU => OpcodeClass::DivRem,
+            Opcode::LB
+            | Opcode::LH
+            | Opcode::LW
+            | Opcode::LBU
+            | Opcode::LHU
+            | Opcode::SB
+            | Opcode::SH
+            | Opcode::SW => OpcodeClass::Memory,
+            Opcode::BEQ | Opcode::BNE | Opcode::BLT | Opcode::BGE | Opcode::BLTU | Opcode::BGEU => {
+                OpcodeClass::Branch
+            }
+            Opcode::JAL | Opcode::JALR | Opcode::AUIPC => OpcodeClass::Jump,
+            Opcode::ECALL => OpcodeClass::Syscall,
+            Opcode::EBREAK | Opcode::UNIMP => OpcodeClass::Trap,
+            _ => OpcodeClass::Alu,
+        }
+    }
+
+    fn all() -> [Self; NUM_OPCODE_CLASSES] {
+        [
+            OpcodeClass::Alu,
+            OpcodeClass::Mul,
+            OpcodeClass::DivRem,
+            OpcodeClass::Memory,
+            OpcodeClass::Branch,
+            OpcodeClass::Jump,
+            OpcodeClass::Syscall,
+            OpcodeClass::Trap,
+        ]
+    }
+}
+
+/// Host timings of one opcode class or syscall.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HostTiming {
+    /// The number of instructions executed.
+    pub executed: u64,
+
+    /// The number of instructions that were timed.
+    pub sampled: u64,
+
+    /// The host nanoseconds spent executing the timed instructions.
+    pub sampled_nanos: u64,
+}
+
+impl HostTiming {
+    /// The average host nanoseconds per instruction.
+    pub fn average_nanos(&self) -> f64 {
+        if self.sampled == 0 {
+            return 0.0;
+        }
+        self.sampled_nanos as f64 / self.sampled as f64
+    }
+
+    /// The host nanoseconds all executed instructions are estimated to have taken.
+    pub fn estimated_nanos(&self) -> f64 {
+        self.average_nanos() * self.executed as f64
+    }
+
+    fn add_sample(&mut self, nanos: u64) {
+        self.sampled += 1;
+        self.sampled_nanos += nanos;
+    }
+}
+
+/// Where the host interpreter spends its time, see [`Runtime::enable_host_timing`].
+#[derive(Debug, Clone, Default)]
+pub struct HostTimingReport {
+    /// The timings of each opcode class that was executed.
+    pub classes: BTreeMap<OpcodeClass, HostTiming>,
+
+    /// The timings of the `ecall` instructions invoking each syscall.
+    pub syscalls: HashMap<SyscallCode, HostTiming>,
+
+    /// The host nanoseconds the timed instructions spent pushing cpu and ALU events to the record.
+    pub sampled_record_nanos: u64,
+}
+
+impl HostTimingReport {
+    /// The host nanoseconds all executed instructions are estimated to have taken.
+    pub fn estimated_total_nanos(&self) -> f64 {
+        self.classes.values().map(HostTiming::estimated_nanos).sum()
+    }
+
+    /// The share of the time of the timed instructions spent pushing events to the record, the
+    /// rest being spent on computation.
+    pub fn record_fraction(&self) -> f64 {
+        let sampled_nanos: u64 = self
+            .classes
+            .values()
+            .map(|timing| timing.sampled_nanos)
+            .sum();
+        if sampled_nanos == 0 {
+            return 0.0;
+        }
+        self.sampled_record_nanos as f64 / sampled_nanos as f64
+    }
+}
+
+impl Display for HostTimingReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        writeln!(
+            f,
+            "host time: ~{:.3}ms, {:.1}% pushing events",
+            self.estimated_total_nanos() / 1e6,
+            self.record_fraction() * 100.0
+        )?;
+        for (class, timing) in self.classes.iter() {
+            writeln!(
+                f,
+                "  {:?}: {} executed, {:.1}ns average",
+                class,
+                timing.executed,
+                timing.average_nanos()
+            )?;
+        }
+        let mut syscalls = self.syscalls.iter().collect::<Vec<_>>();
+        syscalls.sort_by_key(|(code, _)| **code as u32);
+        for (code, timing) in syscalls {
+            writeln!(
+                f,
+                "  {:?}: {} executed, {:.1}ns average",
+                code,
+                timing.executed,
+                timing.average_nanos()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Samples the host time of instructions as they are executed.
+#[derive(Debug, Default)]
+pub(crate) struct HostTimer {
+    classes: [HostTiming; NUM_OPCODE_CLASSES],
+    syscalls: HashMap<SyscallCode, HostTiming>,
+
+    /// Whether the current instruction is timed, in which case pushing events is timed too.
+    pub(crate) sampling: bool,
+    record_nanos: u64,
+}
+
+impl Runtime {
+    /// Time every [`HOST_TIMING_SAMPLE_INTERVAL`]th instruction of each opcode class from now on,
+    /// to find out where the host interpreter spends its time.
+    pub fn enable_host_timing(&mut self) {
+        self.host_timer = Some(HostTimer::default());
+    }
+
+    /// The host timings sampled so far, or `None` if [`Runtime::enable_host_timing`] was not
+    /// called.
+    pub fn host_timing_report(&self) -> Option<HostTimingReport> {
+        let timer = self.host_timer.as_ref()?;
+        Some(HostTimingReport {
+            classes: OpcodeClass::all()
+                .into_iter()
+                .zip(timer.classes)
+                .filter(|(_, timing)| timing.executed > 0)
+                .collect(),
+            syscalls: timer.syscalls.clone(),
+            sampled_record_nanos: timer.record_nanos,
+        })
+    }
+
+    /// Execute `instruction`, timing it if it is the next sample of its class.
+    pub(crate) fn execute_timed(&mut self, instruction: Instruction) {
+        let class = OpcodeClass::of(instruction.opcode);
+        let syscall = match class {
+            OpcodeClass::Syscall => SyscallCode::try_from_u32(self.register(Register::X5)),
+            _ => None,
+        };
+
+        let timer = self.host_timer.as_mut().unwrap();
+        let timing = &mut timer.classes[class as usize];
+        timing.executed += 1;
+        let sample = timing.executed % HOST_TIMING_SAMPLE_INTERVAL as u64 == 1;
+        let syscall_sample = syscall.map(|code| {
+            let timing = timer.syscalls.entry(code).or_default();
+            timing.executed += 1;
+            timing.executed % HOST_TIMING_SAMPLE_INTERVAL as u64 == 1
+        });
+        if !sample && syscall_sample != Some(true) {
+            self.execute(instruction);
+            return;
+        }
+
+        timer.sampling = true;
+        let start = Instant::now();
+        self.execute(instruction);
+        let nanos = start.elapsed().as_nanos() as u64;
+
+        let timer = self.host_timer.as_mut().unwrap();
+        timer.sampling = false;
+        if sample {
+            timer.classes[class as usize].add_sample(nanos);
+        }
+        if let (Some(code), Some(true)) = (syscall, syscall_sample) {
+            timer.syscalls.get_mut(&code).unwrap().add_sample(nanos);
+        }
+    }
+
+    /// The current time if the current instruction is timed, to time pushing its events.
+    #[inline]
+    pub(crate) fn start_record_timer(&self) -> Option<Instant> {
+        self.host_timer
+            .as_ref()
+            .filter(|timer| timer.sampling)
+            .map(|_| Instant::now())
+    }
+
+    #[inline]
+    pub(crate) fn stop_record_timer(&mut self, start: Option<Instant>) {
+        if let (Some(start), Some(timer)) = (start, &mut self.host_timer) {
+            timer.record_nanos += start.elapsed().as_nanos() as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use std::time::Duration;
+
+    fn run_time(timed: bool) -> (Duration, Runtime) {
+        let mut runtime = Runtime::new(fibonacci_program());
+        if timed {
+            runtime.enable_host_timing();
+        }
+        let start = Instant::now();
+        runtime.run();
+        (start.elapsed(), runtime)
+    }
+
+    #[test]
+    fn test_host_timing_report() {
+        let (elapsed, runtime) = run_time(true);
+        let report = runtime.host_timing_report().unwrap();
+
+        let executed: u64 = report.classes.values().map(|timing| timing.executed).sum();
+        assert_eq!(executed, runtime.state.global_clk as u64);
+        assert!(report.classes[&OpcodeClass::Alu].sampled > 0);
+        assert!(report.classes[&OpcodeClass::Syscall].sampled > 0);
+        assert!(!report.syscalls.is_empty());
+        assert!(report.sampled_record_nanos > 0);
+        assert!(report.record_fraction() < 1.0);
+
+        // The instructions are most of the time of a run.
+        let total = report.estimated_total_nanos();
+        let elapsed = elapsed.as_nanos() as f64;
+        assert!(total > elapsed / 10.0 && total < elapsed * 2.0);
+        assert!(Runtime::new(fibonacci_program())
+            .host_timing_report()
+            .is_none());
+
+        // The best of a few runs is compared, to be robust to noise.
+        let best = |timed| (0..3).map(|_| run_time(timed).0).min().unwrap();
+        let (uninstrumented, instrumented) = (best(false), best(true));
+        assert!(instrumented.as_secs_f64() < uninstrumented.as_secs_f64() * 1.5);
+    }
+}
@@ -0,0 +1,384 @@
+use std::io::Write;
+
+use hashbrown::HashMap;
+use nohash_hasher::BuildNoHashHasher;
+
+use super::format::{deflate, inflate, read_header, write_header, HEADER_LEN};
+use super::{Encoding, FormatError};
+
+/// A destination for the program counter trace, which contains the pc of every instruction
+/// executed in constrained mode.
+pub trait TraceSink: Send {
+    /// Record that the instruction at `pc` is about to be executed.
+    fn write_pc(&mut self, pc: u32);
+
+    /// Flush any buffered trace data.
+    fn flush(&mut self);
+}
+
+/// Any writer can be used as a trace sink, e.g. `std::io::sink()` to discard the trace. Each pc is
+/// written as four big-endian bytes.
+impl<W: Write + Send> TraceSink for W {
+    fn write_pc(&mut self, pc: u32) {
+        self.write_all(&pc.to_be_bytes()).unwrap();
+    }
+
+    fn flush(&mut self) {
+        Write::flush(self).unwrap();
+    }
+}
+
+/// Identifies an encoded pc trace, see [`EncodedTraceSink`].
+const TRACE_MAGIC: [u8; 4] = *b"SP1T";
+
+/// Must be incremented whenever the encoding of the pcs changes.
+const TRACE_VERSION: u32 = 1;
+
+const TRACE_KIND: &str = "pc trace";
+
+/// The number of bytes of encoded pcs buffered before they are written out as a chunk.
+const TRACE_CHUNK_SIZE: usize = 1 << 16;
+
+/// Predicts each pc of a trace from the previous one: the pc that followed it the last time it
+/// was executed, or the next instruction. Loops and repeated calls are predicted exactly.
+#[derive(Default)]
+struct PcPredictor {
+    prev: u32,
+    successors: HashMap<u32, u32, BuildNoHashHasher<u32>>,
+}
+
+impl PcPredictor {
+    #[inline]
+    fn predict(&self) -> u32 {
+        match self.successors.get(&self.prev) {
+            Some(&pc) => pc,
+            None => self.prev.wrapping_add(4),
+        }
+    }
+
+    #[inline]
+    fn observe(&mut self, pc: u32) {
+        self.successors.insert(self.prev, pc);
+        self.prev = pc;
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte < 0x80 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Encodes a pc trace as the differences with the pcs predicted by [`PcPredictor`]: a varint
+/// count of correctly predicted pcs, followed by the zigzag-encoded difference of the next pc
+/// plus one, or zero where the trace was flushed.
+#[derive(Default)]
+pub struct PcTraceEncoder {
+    predictor: PcPredictor,
+    run: u64,
+    bytes: Vec<u8>,
+}
+
+impl PcTraceEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn push(&mut self, pc: u32) {
+        let predicted = self.predictor.predict();
+        if pc == predicted {
+            self.run += 1;
+        } else {
+            let delta = pc.wrapping_sub(predicted) as i32;
+            let zigzag = ((delta << 1) ^ (delta >> 31)) as u32;
+            write_varint(&mut self.bytes, self.run);
+            write_varint(&mut self.bytes, zigzag as u64 + 1);
+            self.run = 0;
+        }
+        self.predictor.observe(pc);
+    }
+
+    /// The number of encoded bytes buffered, not counting the pending run.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty() && self.run == 0
+    }
+
+    /// Take the encoding of the pcs pushed since the last call. The predictions carry over, so
+    /// the bytes only decode after those taken before.
+    pub fn take_bytes(&mut self) -> Vec<u8> {
+        if self.run > 0 {
+            write_varint(&mut self.bytes, self.run);
+            write_varint(&mut self.bytes, 0);
+            self.run = 0;
+        }
+        std::mem::take(&mut self.bytes)
+    }
+}
+
+/// Decode the pcs encoded by a [`PcTraceEncoder`].
+fn decode_pcs(bytes: &[u8]) -> Result<Vec<u32>, FormatError> {
+    let corrupted = || FormatError::Corrupted { kind: TRACE_KIND };
+    let mut predictor = PcPredictor::default();
+    let mut pcs = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let run = read_varint(bytes, &mut pos).ok_or_else(corrupted)?;
+        for _ in 0..run {
+            let pc = predictor.predict();
+            pcs.push(pc);
+            predictor.observe(pc);
+        }
+        let zigzag = read_varint(bytes, &mut pos).ok_or_else(corrupted)?;
+        if zigzag == 0 {
+            continue;
+        }
+        let zigzag = u32::try_from(zigzag - 1).map_err(|_| corrupted())?;
+        let delta = (zigzag >> 1) as i32 ^ -((zigzag & 1) as i32);
+        let pc = predictor.predict().wrapping_add(delta as u32);
+        pcs.push(pc);
+        predictor.observe(pc);
+    }
+    Ok(pcs)
+}
+
+/// A trace sink writing an encoded trace, many times smaller than the raw one. The trace starts
+/// with a header like the other encoded values, followed by chunks of encoded pcs, each prefixed
+/// with its length as a little-endian u32. With [`Encoding::Raw`], the pcs are written as four
+/// big-endian bytes each and without a header, like a plain writer. See [`decode_pc_trace`].
+pub struct EncodedTraceSink<W: Write + Send> {
+    writer: W,
+    encoding: Encoding,
+    encoder: PcTraceEncoder,
+    header_written: bool,
+}
+
+impl<W: Write + Send> EncodedTraceSink<W> {
+    /// Write the trace with [`Encoding::DEFAULT`].
+    pub fn new(writer: W) -> Self {
+        Self::with_encoding(writer, Encoding::DEFAULT)
+    }
+
+    pub fn with_encoding(writer: W, encoding: Encoding) -> Self {
+        Self {
+            writer,
+            encoding,
+            encoder: PcTraceEncoder::new(),
+            header_written: false,
+        }
+    }
+
+    fn write_chunk(&mut self) {
+        if self.encoder.is_empty() {
+            return;
+        }
+        if !self.header_written {
+            let mut header = Vec::new();
+            write_header(&mut header, TRACE_MAGIC, TRACE_VERSION, self.encoding);
+            self.writer.write_all(&header).unwrap();
+            self.header_written = true;
+        }
+        let mut chunk = self.encoder.take_bytes();
+        if self.encoding == Encoding::Compressed {
+            chunk = deflate(&chunk);
+        }
+        self.writer
+            .write_all(&(chunk.len() as u32).to_le_bytes())
+            .unwrap();
+        self.writer.write_all(&chunk).unwrap();
+    }
+}
+
+impl<W: Write + Send> TraceSink for EncodedTraceSink<W> {
+    fn write_pc(&mut self, pc: u32) {
+        if self.encoding == Encoding::Raw {
+            self.writer.write_all(&pc.to_be_bytes()).unwrap();
+            return;
+        }
+        self.encoder.push(pc);
+        if self.encoder.len() >= TRACE_CHUNK_SIZE {
+            self.write_chunk();
+        }
+    }
+
+    fn flush(&mut self) {
+        self.write_chunk();
+        Write::flush(&mut self.writer).unwrap();
+    }
+}
+
+impl<W: Write + Send> Drop for EncodedTraceSink<W> {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.flush();
+        }
+    }
+}
+
+/// Encode a pc trace in memory, as an [`EncodedTraceSink`] would write it.
+pub fn encode_pc_trace(pcs: &[u32], encoding: Encoding) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut sink = EncodedTraceSink::with_encoding(&mut bytes, encoding);
+    for &pc in pcs {
+        sink.write_pc(pc);
+    }
+    drop(sink);
+    bytes
+}
+
+/// Decode a pc trace written by an [`EncodedTraceSink`], or by a plain writer before traces were
+/// encoded.
+pub fn decode_pc_trace(bytes: &[u8]) -> Result<Vec<u32>, FormatError> {
+    let corrupted = || FormatError::Corrupted { kind: TRACE_KIND };
+    if bytes.len() < 4 || bytes[..4] != TRACE_MAGIC {
+        if bytes.len() % 4 != 0 {
+            return Err(corrupted());
+        }
+        return Ok(bytes
+            .chunks_exact(4)
+            .map(|pc| u32::from_be_bytes(pc.try_into().unwrap()))
+            .collect());
+    }
+    let (version, encoding) = read_header(bytes, TRACE_KIND, TRACE_MAGIC)?;
+    if version != TRACE_VERSION {
+        return Err(FormatError::VersionMismatch {
+            kind: TRACE_KIND,
+            expected: TRACE_VERSION,
+            found: version,
+        });
+    }
+    let mut encoded = Vec::new();
+    let mut rest = &bytes[HEADER_LEN..];
+    while !rest.is_empty() {
+        let len = rest
+            .get(..4)
+            .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+            .ok_or_else(corrupted)?;
+        let chunk = rest.get(4..4 + len).ok_or_else(corrupted)?;
+        match encoding {
+            Encoding::Compressed => encoded.extend(inflate(chunk, TRACE_KIND)?),
+            _ => encoded.extend_from_slice(chunk),
+        }
+        rest = &rest[4 + len..];
+    }
+    decode_pcs(&encoded)
+}
+
+/// A sink writing the encoded pc trace to the file at `path`, see [`decode_pc_trace`], or `None`
+/// with a warning if it cannot be created, so that a bad `TRACE_FILE` does not stop the
+/// execution.
+#[cfg(not(feature = "wasm"))]
+pub(crate) fn trace_sink_at(path: &str) -> Option<Box<dyn TraceSink>> {
+    match std::fs::File::create(path) {
+        Ok(file) => Some(Box::new(EncodedTraceSink::new(std::io::BufWriter::new(
+            file,
+        )))),
+        Err(err) => {
+            tracing::warn!(
+                "failed to create the trace file {}, not tracing: {}",
+                path,
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Without a filesystem, tracing has to be set up explicitly through `Runtime::trace_buf`.
+#[cfg(feature = "wasm")]
+pub(crate) fn trace_sink_at(_path: &str) -> Option<Box<dyn TraceSink>> {
+    None
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::tests::fibonacci_program;
+    use crate::runtime::Runtime;
+
+    fn fibonacci_pcs() -> Vec<u32> {
+        let mut runtime = Runtime::new(fibonacci_program());
+        runtime.run();
+        runtime
+            .record
+            .cpu_events
+            .iter()
+            .map(|event| event.pc)
+            .collect()
+    }
+
+    #[test]
+    fn test_pc_trace_round_trip() {
+        let pcs = fibonacci_pcs();
+        for encoding in [Encoding::Raw, Encoding::Compact, Encoding::Compressed] {
+            let bytes = encode_pc_trace(&pcs, encoding);
+            assert_eq!(decode_pc_trace(&bytes).unwrap(), pcs);
+        }
+        assert_eq!(encode_pc_trace(&pcs, Encoding::Raw).len(), pcs.len() * 4);
+
+        // A trace flushed midway decodes to the same pcs.
+        let mut bytes = Vec::new();
+        let mut sink = EncodedTraceSink::with_encoding(&mut bytes, Encoding::Compact);
+        for (i, &pc) in pcs.iter().enumerate() {
+            sink.write_pc(pc);
+            if i % 1000 == 0 {
+                TraceSink::flush(&mut sink);
+            }
+        }
+        drop(sink);
+        assert_eq!(decode_pc_trace(&bytes).unwrap(), pcs);
+        assert_eq!(decode_pc_trace(&[]).unwrap(), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_pc_trace_compression_ratio() {
+        let pcs = fibonacci_pcs();
+        for encoding in [Encoding::Compact, Encoding::Compressed] {
+            let bytes = encode_pc_trace(&pcs, encoding);
+            assert!(bytes.len() * 10 <= pcs.len() * 4);
+        }
+    }
+
+    #[test]
+    fn test_corrupted_pc_trace() {
+        let pcs = fibonacci_pcs();
+        let bytes = encode_pc_trace(&pcs, Encoding::Compact);
+        assert!(matches!(
+            decode_pc_trace(&bytes[..bytes.len() - 1]),
+            Err(FormatError::Corrupted { .. })
+        ));
+        assert!(matches!(
+            decode_pc_trace(&bytes[..HEADER_LEN + 2]),
+            Err(FormatError::Corrupted { .. })
+        ));
+        assert!(decode_pc_trace(&[0, 0, 0, 4, 0]).is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "wasm"))]
+    fn test_trace_file_not_created() {
+        assert!(trace_sink_at("/nonexistent/sp1/trace.bin").is_none());
+        let path = std::env::temp_dir().join("sp1_test_trace_file_not_created.bin");
+        assert!(trace_sink_at(path.to_str().unwrap()).is_some());
+        std::fs::remove_file(path).unwrap();
+    }
+}
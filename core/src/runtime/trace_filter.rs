@@ -0,0 +1,224 @@
+use super::{Error, SymbolTable};
+
+/// The number of near matches listed by [`Error::UnknownSymbol`].
+const MAX_NEAR_MATCHES: usize = 5;
+
+/// The address ranges of the functions the trace is restricted to, see
+/// [`RuntimeOptions::trace_filter`](super::RuntimeOptions::trace_filter).
+#[derive(Debug, Clone)]
+pub(crate) struct TraceFilter {
+    /// The names of the functions, to resolve them again when the program changes.
+    pub(crate) names: Vec<String>,
+
+    /// The start and length of the ranges of the functions, sorted and merged where they overlap.
+    ranges: Vec<(u32, u32)>,
+
+    /// The index of the range containing the last pc found in one, so that the pcs of a function
+    /// are found without searching the ranges.
+    last: usize,
+}
+
+impl TraceFilter {
+    /// Resolve the functions named `names` in `symbols`, returning an error with the near matches
+    /// of the first one that is not found.
+    pub(crate) fn new(symbols: &SymbolTable, names: &[String]) -> Result<Self, Error> {
+        let mut ranges = names
+            .iter()
+            .map(|name| match symbols.get(name) {
+                Some(symbol) => Ok((symbol.addr as u64, symbol.size.max(1) as u64)),
+                None => Err(Error::UnknownSymbol {
+                    name: name.clone(),
+                    near_matches: near_matches(symbols, name),
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        ranges.sort();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for (start, len) in ranges {
+            match merged.last_mut() {
+                Some((last_start, last_len)) if start <= *last_start + *last_len => {
+                    *last_len = (*last_len).max(start + len - *last_start);
+                }
+                _ => merged.push((start, len)),
+            }
+        }
+        Ok(Self {
+            names: names.to_vec(),
+            ranges: merged
+                .into_iter()
+                .map(|(start, len)| (start as u32, len.min(u32::MAX as u64) as u32))
+                .collect(),
+            last: 0,
+        })
+    }
+
+    /// Whether `pc` is inside one of the functions.
+    #[inline]
+    pub(crate) fn contains(&mut self, pc: u32) -> bool {
+        let (start, len) = self.ranges[self.last];
+        if pc.wrapping_sub(start) < len {
+            return true;
+        }
+        let index = self.ranges.partition_point(|&(start, _)| start <= pc);
+        if index == 0 {
+            return false;
+        }
+        let (start, len) = self.ranges[index - 1];
+        let inside = pc - start < len;
+        if inside {
+            self.last = index - 1;
+        }
+        inside
+    }
+}
+
+/// The functions of `symbols` whose name, or its last path segment, contains `name` or is a few
+/// edits away from it, closest first.
+fn near_matches(symbols: &SymbolTable, name: &str) -> Vec<String> {
+    let max_distance = (name.len() / 4).max(2);
+    let mut matches = symbols
+        .iter()
+        .filter_map(|symbol| {
+            let segment = symbol.name.rsplit("::").next().unwrap_or(&symbol.name);
+            let distance = edit_distance(name, &symbol.name).min(edit_distance(name, segment));
+            let contains = symbol.name.contains(name);
+            (contains || distance <= max_distance).then(|| (distance, symbol.name.clone()))
+        })
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches.dedup();
+    matches
+        .into_iter()
+        .take(MAX_NEAR_MATCHES)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// The number of characters to insert, remove or replace to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let replaced = diagonal + (a != *b) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::runtime::{Program, Runtime, RuntimeOptions, Symbol, TraceSink};
+    use crate::utils::asm::assemble;
+
+    use super::*;
+
+    /// A trace sink keeping the pcs in memory.
+    struct PcTrace(Arc<Mutex<Vec<u32>>>);
+
+    impl TraceSink for PcTrace {
+        fn write_pc(&mut self, pc: u32) {
+            self.0.lock().unwrap().push(pc);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    const INNER: (u32, u32) = (20, 12);
+
+    /// Calls `inner` three times from a loop, with `inner` and `outer` in the symbol table.
+    fn calling_program() -> Program {
+        let mut program = assemble(
+            "        li   s0, 3
+             loop:   call inner
+                     addi s0, s0, -1
+                     bne  s0, zero, loop
+                     j    end
+             inner:  addi t0, t0, 1
+                     addi t0, t0, 2
+                     ret
+             end:    nop",
+            0,
+        )
+        .unwrap();
+        program.symbols = SymbolTable::new(vec![
+            Symbol::new("outer", 0, 20),
+            Symbol::new("inner", INNER.0, INNER.1),
+        ]);
+        program
+    }
+
+    /// The pc trace and the pcs of the trace log of an execution filtered by `names`.
+    fn traced_pcs(names: &[&str]) -> (Vec<u32>, Vec<u32>) {
+        let names = names.iter().map(|name| name.to_string()).collect();
+        let options = RuntimeOptions::default().with_trace_filter(names);
+        let mut runtime = Runtime::try_new_with_options(calling_program(), options).unwrap();
+        let pcs = Arc::new(Mutex::new(Vec::new()));
+        runtime.trace_buf = Some(Box::new(PcTrace(pcs.clone())));
+        runtime.enable_trace_log(1024);
+        runtime.run();
+        let logged = runtime.trace_log().map(|entry| entry.pc).collect();
+        let pcs = pcs.lock().unwrap().clone();
+        (pcs, logged)
+    }
+
+    #[test]
+    fn test_trace_inner_function() {
+        let (all, logged) = traced_pcs(&[]);
+        assert_eq!(all, logged);
+        let expected = all
+            .iter()
+            .copied()
+            .filter(|pc| pc.wrapping_sub(INNER.0) < INNER.1)
+            .collect::<Vec<_>>();
+        assert_eq!(expected.len(), 9);
+
+        let (pcs, logged) = traced_pcs(&["inner"]);
+        assert_eq!(pcs, expected);
+        assert_eq!(logged, expected);
+
+        // Both functions cover every cycle but the last `nop`.
+        let (pcs, _) = traced_pcs(&["outer", "inner"]);
+        assert_eq!(pcs, all[..all.len() - 1]);
+    }
+
+    #[test]
+    fn test_unknown_function() {
+        let options = RuntimeOptions::default().with_trace_filter(vec!["iner".to_string()]);
+        let err = Runtime::try_new_with_options(calling_program(), options).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                Error::UnknownSymbol { name, near_matches }
+                    if name == "iner" && near_matches == &["inner".to_string()]
+            ),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("did you mean inner?"), "{}", err);
+
+        // The functions are resolved again for another program.
+        let options = RuntimeOptions::default().with_trace_filter(vec!["inner".to_string()]);
+        let mut runtime = Runtime::try_new_with_options(calling_program(), options).unwrap();
+        let mut program = calling_program();
+        program.symbols = SymbolTable::default();
+        assert!(matches!(
+            runtime.try_reset_with_program(Arc::new(program)),
+            Err(Error::UnknownSymbol { .. })
+        ));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("inner", "inner"), 0);
+        assert_eq!(edit_distance("iner", "inner"), 1);
+        assert_eq!(edit_distance("main", "mian"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,191 @@
+use core::fmt::{Display, Formatter};
+use std::collections::VecDeque;
+use std::io::Write;
+
+use super::{Instruction, Runtime, ABI_NAMES};
+
+/// The number of cycles kept in the trace log when it is enabled by
+/// [`RuntimeOptions::log_instructions`](super::RuntimeOptions::log_instructions).
+pub const DEFAULT_TRACE_LOG_CAPACITY: usize = 1024;
+
+/// A cycle of the trace log, see [`Runtime::enable_trace_log`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceLogEntry {
+    pub global_clk: u32,
+
+    pub pc: u32,
+
+    pub instruction: Instruction,
+
+    /// The registers the instruction operates on, `op_a` and then the registers of `op_b` and
+    /// `op_c` that are not immediates, with their values before the instruction.
+    pub registers: [Option<(u32, u32)>; 3],
+}
+
+/// The cycle as `clk=<global_clk> [pc=0x<pc>] <instruction> | <register>=0x<value> ...`.
+impl Display for TraceLogEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "clk={} [pc=0x{:x}] {:<12?} |",
+            self.global_clk, self.pc, self.instruction
+        )?;
+        for (register, value) in self.registers.iter().flatten() {
+            write!(f, " {}=0x{:08x}", ABI_NAMES[*register as usize], value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A ring of the entries of the most recent cycles.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TraceLog {
+    pub(crate) capacity: usize,
+
+    /// The entries of the most recent cycles, oldest first.
+    entries: VecDeque<TraceLogEntry>,
+}
+
+impl TraceLog {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity.min(1 << 16)),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn push(&mut self, entry: TraceLogEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+impl Runtime {
+    /// Keep the entries of the last `capacity` cycles from now on, to be formatted on demand by
+    /// [`Runtime::flush_trace_log`]. The cycles are only recorded, so this is much cheaper than
+    /// formatting every cycle as it executes. [`Runtime::run`] includes the log in its panic
+    /// message when the guest faults.
+    pub fn enable_trace_log(&mut self, capacity: usize) {
+        self.trace_log = Some(TraceLog::new(capacity));
+    }
+
+    /// The entries of the trace log, oldest first.
+    pub fn trace_log(&self) -> impl Iterator<Item = &TraceLogEntry> {
+        self.trace_log
+            .iter()
+            .flat_map(|trace_log| trace_log.entries.iter())
+    }
+
+    /// Write the entries of the trace log to `writer`, one line each, oldest first, and remove
+    /// them from the log.
+    pub fn flush_trace_log(&mut self, writer: &mut impl Write) -> std::io::Result<()> {
+        let Some(trace_log) = &mut self.trace_log else {
+            return Ok(());
+        };
+        while let Some(entry) = trace_log.entries.pop_front() {
+            writeln!(writer, "{}", entry)?;
+        }
+        Ok(())
+    }
+
+    /// Record the cycle of `instruction` at the current pc in the trace log.
+    pub(crate) fn log_cycle(&mut self, instruction: Instruction) {
+        let value = |register: u32| {
+            let entry = self.state.memory.get(&register);
+            (register, entry.map_or(0, |(value, _, _)| *value))
+        };
+        let registers = [
+            Some(value(instruction.op_a)),
+            (!instruction.imm_b).then(|| value(instruction.op_b)),
+            (!instruction.imm_c).then(|| value(instruction.op_c)),
+        ];
+        let entry = TraceLogEntry {
+            global_clk: self.state.global_clk,
+            pc: self.state.pc,
+            instruction,
+            registers,
+        };
+        if let Some(trace_log) = &mut self.trace_log {
+            trace_log.push(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use super::*;
+    use crate::runtime::{ExecutionError, Program, RuntimeOptions};
+    use crate::utils::asm::assemble;
+
+    /// Count down from 3 and fault on an unimplemented instruction once done.
+    fn faulting_program() -> Program {
+        assemble(
+            "        li   t0, 3
+             loop:   addi t0, t0, -1
+                     bne  t0, zero, loop
+                     unimp",
+            0,
+        )
+        .unwrap()
+    }
+
+    fn flush(runtime: &mut Runtime) -> Vec<String> {
+        let mut log = Vec::new();
+        runtime.flush_trace_log(&mut log).unwrap();
+        String::from_utf8(log)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_flush_after_fault() {
+        let options = RuntimeOptions::default().with_log_instructions(true);
+        let mut runtime = Runtime::new_with_options(faulting_program(), options);
+        assert!(runtime.try_run().is_err());
+        let log = flush(&mut runtime);
+        assert_eq!(log.len(), 8);
+        for (clk, line) in log.iter().enumerate() {
+            assert!(line.starts_with(&format!("clk={} ", clk)), "{}", line);
+        }
+        assert!(log[0].contains("[pc=0x0]"), "{}", log[0]);
+        assert!(log[5].contains("t0=0x00000001"), "{}", log[5]);
+        assert!(log[7].contains("[pc=0xc]"), "{}", log[7]);
+        assert!(flush(&mut runtime).is_empty());
+    }
+
+    #[test]
+    fn test_ring_capacity() {
+        let mut runtime = Runtime::new_with_options(faulting_program(), RuntimeOptions::default());
+        assert!(runtime.try_run().is_err());
+        assert_eq!(runtime.trace_log().count(), 0);
+
+        let mut runtime = Runtime::new(faulting_program());
+        runtime.enable_trace_log(3);
+        let err = runtime.try_run().unwrap_err();
+        let clks = runtime
+            .trace_log()
+            .map(|entry| entry.global_clk)
+            .collect::<Vec<_>>();
+        assert_eq!(clks, vec![5, 6, 7]);
+
+        let mut runtime = Runtime::new(faulting_program());
+        runtime.enable_trace_log(3);
+        let message = catch_unwind(AssertUnwindSafe(|| runtime.run()))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert!(message.starts_with(&err.to_string()), "{}", message);
+        assert!(message.contains("clk=7 [pc=0xc]"), "{}", message);
+        assert_eq!(err, ExecutionError::Unimplemented { pc: 0xc });
+    }
+}
@@ -106,6 +106,7 @@ impl<SC: StarkGenericConfig> RiscvStark<SC> {
         chips.iter().for_each(|chip| {
             let mut output = ExecutionRecord::default();
             output.index = record.index;
+            output.filter = record.filter;
             chip.generate_dependencies(&record, &mut output);
             record.append(&mut output);
         });
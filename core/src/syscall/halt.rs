@@ -1,4 +1,4 @@
-use crate::runtime::{Register, Syscall, SyscallContext};
+use crate::runtime::{GuestAssertion, HaltReason, Register, Syscall, SyscallContext};
 
 pub struct SyscallHalt;
 
@@ -11,6 +11,96 @@ impl SyscallHalt {
 impl Syscall for SyscallHalt {
     fn execute(&self, ctx: &mut SyscallContext) -> u32 {
         ctx.set_next_pc(0);
-        ctx.register_unsafe(Register::X10)
+        ctx.args().a0
+    }
+}
+
+/// The exit code of a guest that panicked, see [`crate::runtime::Runtime::exit_code`].
+pub const PANIC_EXIT_CODE: u32 = 1;
+
+/// Halts the program after a guest panic. The message is passed as a pointer in a0 and a length
+/// in a1. Invalid UTF-8 in the message is replaced, and a message that cannot be read is replaced
+/// by the reason it cannot.
+pub struct SyscallPanic;
+
+impl SyscallPanic {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Syscall for SyscallPanic {
+    fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+        let args = ctx.args();
+        let message = read_message(ctx, args.a0, args.a1);
+        let backtrace = ctx.rt.backtrace();
+        ctx.rt.halt_reason = HaltReason::Panicked { message, backtrace };
+        ctx.set_next_pc(0);
+        args.a0
+    }
+}
+
+/// Read the message of a halting syscall, which cannot report errors to the guest.
+fn read_message(ctx: &SyscallContext, addr: u32, len: u32) -> String {
+    match ctx.read_bytes_checked(addr, len) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(err) => format!("<unreadable message: {}>", err),
+    }
+}
+
+/// The number of bytes of an assertion message that are kept, see [`SyscallAssertFailed`].
+pub const MAX_ASSERTION_MESSAGE_LEN: u32 = 1 << 10;
+
+/// Halts the program after a failed assertion, see [`GuestAssertion`]. The code is passed in a0,
+/// the message as a pointer in a1 and a length in a2, and the expected and actual values as
+/// pointers to 32 bytes in a3 and a4. Any of the pointers may be null.
+///
+/// Only the first [`MAX_ASSERTION_MESSAGE_LEN`] bytes of the message are read, followed by the
+/// number of bytes left out, as the assertion is kept in the execution record.
+pub struct SyscallAssertFailed;
+
+impl SyscallAssertFailed {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Syscall for SyscallAssertFailed {
+    fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+        let args = ctx.args();
+        let value = |ctx: &SyscallContext, register: Register| {
+            // A null pointer is in the registers, so it reads as no value.
+            let value = ctx
+                .read_bytes_checked(ctx.register_unsafe(register), 32)
+                .ok()?;
+            Some(value.try_into().unwrap())
+        };
+        let len = ctx.register_unsafe(Register::X12);
+        let message = (args.a1 != 0).then(|| {
+            let mut message = read_message(ctx, args.a1, len.min(MAX_ASSERTION_MESSAGE_LEN));
+            if len > MAX_ASSERTION_MESSAGE_LEN {
+                message.push_str(&format!(
+                    "... <{} more bytes>",
+                    len - MAX_ASSERTION_MESSAGE_LEN
+                ));
+            }
+            message
+        });
+        let assertion = GuestAssertion {
+            pc: ctx.rt.state.pc,
+            code: args.a0,
+            message,
+            expected: value(ctx, Register::X13),
+            actual: value(ctx, Register::X14),
+            clk: ctx.clk(),
+        };
+        let backtrace = ctx.rt.backtrace();
+        ctx.rt.record.guest_assertion = Some(assertion.clone());
+        ctx.rt.halt_reason = HaltReason::AssertionFailed {
+            assertion,
+            backtrace,
+        };
+        ctx.set_next_pc(0);
+        args.a0
     }
 }
@@ -1,7 +1,11 @@
-use std::process::exit;
+use crate::runtime::{ExecutionError, Register, Syscall, SyscallContext};
 
-use crate::runtime::{Register, Syscall, SyscallContext};
+/// Returned in a0 and a1 by `LWA` when the stdin reader of the runtime reaches its end. A read
+/// that succeeds leaves the number of bytes requested in a1, so guests tell the two apart by a1.
+/// The bytes of the word read before the end are consumed.
+pub const LWA_INPUT_EOF: u32 = 0xffff_fff5;
 
+/// Reads the next a1 bytes of the input stream, at most 4, into the word returned in a0.
 pub struct SyscallLWA;
 
 impl SyscallLWA {
@@ -13,21 +17,83 @@ impl SyscallLWA {
 impl Syscall for SyscallLWA {
     fn execute(&self, ctx: &mut SyscallContext) -> u32 {
         // TODO: in the future this will be used for private vs. public inputs.
-        let a0 = Register::X10;
-        let a1 = Register::X11;
-        let _ = ctx.register_unsafe(a0);
-        let num_bytes = ctx.register_unsafe(a1) as usize;
+        let num_bytes = ctx.args().a1 as usize;
         let mut read_bytes = [0u8; 4];
-        for i in 0..num_bytes {
-            if ctx.rt.state.input_stream_ptr >= ctx.rt.state.input_stream.len() {
-                tracing::error!(
-                    "Not enough input words were passed in. Use --input to pass in more words."
-                );
-                exit(1);
+        for byte in read_bytes.iter_mut().take(num_bytes) {
+            match ctx.rt.read_input_byte() {
+                Ok(value) => *byte = value,
+                // The in-memory input is always read first, so running out of input with a stdin
+                // reader means the reader reached its end.
+                Err(ExecutionError::InputExhausted { .. }) if ctx.rt.stdin_reader.is_some() => {
+                    ctx.rw(Register::X11, LWA_INPUT_EOF);
+                    return LWA_INPUT_EOF;
+                }
+                Err(err) => {
+                    if let ExecutionError::InputExhausted { .. } = err {
+                        tracing::error!(
+                            "Not enough input words were passed in. Use --input to pass in more words."
+                        );
+                    }
+                    ctx.rt.trap(err);
+                    return 0;
+                }
             }
-            read_bytes[i] = ctx.rt.state.input_stream[ctx.rt.state.input_stream_ptr];
-            ctx.rt.state.input_stream_ptr += 1;
         }
         u32::from_le_bytes(read_bytes)
     }
 }
+
+/// Checks that the next frame of the input stream has the tag passed in a0. Does nothing when the
+/// host did not set an input schema.
+pub struct SyscallReadFrame;
+
+impl SyscallReadFrame {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Syscall for SyscallReadFrame {
+    fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+        let expected = ctx.args().a0;
+        if ctx.rt.input_schema.is_none() {
+            return expected;
+        }
+        let pc = ctx.rt.state.pc;
+        let frame = ctx.rt.input_frame_ptr;
+        let Some(&found) = ctx.rt.input_frames.get(frame) else {
+            ctx.rt.trap(ExecutionError::InputExhausted { pc });
+            return 0;
+        };
+        if found != expected {
+            ctx.rt.trap(ExecutionError::InputFrameMismatch {
+                frame: frame as u32,
+                expected,
+                found,
+                pc,
+            });
+            return 0;
+        }
+        ctx.rt.input_frame_ptr += 1;
+        expected
+    }
+}
+
+/// Asks the runtime to buffer the next a0 bytes of input from its stdin reader on a helper thread,
+/// so that reading them does not wait for the host. A hint that changes nothing the guest can
+/// observe, and a no-op without a stdin reader.
+pub struct SyscallPrefetchInput;
+
+impl SyscallPrefetchInput {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Syscall for SyscallPrefetchInput {
+    fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+        let len = ctx.args().a0 as usize;
+        ctx.rt.prefetch_input(len);
+        0
+    }
+}
@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::{MemoryReadRecord, MemoryRecordEnum, MemoryWriteRecord};
+use crate::runtime::{Register, Syscall, SyscallContext};
+
+/// Returned in a0 by `LOAD64` and `STORE64` when the address is not a multiple of 8, in which
+/// case no memory is accessed. `LOAD64` also returns it in a1, so guests must check the alignment
+/// themselves to tell it apart from a loaded value.
+pub const MEM64_MISALIGNED: u32 = 0xffff_fffe;
+
+/// The cycles taken by `LOAD64` and `STORE64`, one for each word accessed.
+const MEM64_CYCLES: u32 = 2;
+
+/// The memory and register accesses of a `LOAD64` or `STORE64`, kept in
+/// [`ExecutionRecord::mem64_events`](crate::runtime::ExecutionRecord::mem64_events) for a chip
+/// proving them. A misaligned `STORE64` accesses nothing and has no event.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Mem64Event {
+    pub shard: u32,
+    pub clk: u32,
+    pub addr: u32,
+    /// The reads of `LOAD64` or the writes of `STORE64`, the low word first, or `None` if the
+    /// address is misaligned.
+    pub words: Option<[MemoryRecordEnum; 2]>,
+    /// The write of the high word to a1 by `LOAD64`.
+    pub a1_record: Option<MemoryWriteRecord>,
+}
+
+/// Loads the little-endian u64 at the 8-aligned address in a0, returning its low word in a0 and
+/// its high word in a1.
+pub struct SyscallLoad64;
+
+impl SyscallLoad64 {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Read the two words at `addr`, the low word first, each in its own cycle. Returns
+    /// [`MEM64_MISALIGNED`] without accessing memory if `addr` is not 8-aligned.
+    pub fn load(ctx: &mut SyscallContext, addr: u32) -> Result<[MemoryReadRecord; 2], u32> {
+        if addr % 8 != 0 {
+            return Err(MEM64_MISALIGNED);
+        }
+        let (lo, _) = ctx.mr(addr);
+        ctx.advance(1);
+        let (hi, _) = ctx.mr(addr + 4);
+        ctx.advance(1);
+        Ok([lo, hi])
+    }
+}
+
+impl Syscall for SyscallLoad64 {
+    fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+        let addr = ctx.args().a0;
+        let (shard, clk) = (ctx.current_shard(), ctx.clk());
+        let result = Self::load(ctx, addr);
+        let (lo, hi) = match result {
+            Ok([lo, hi]) => (lo.value, hi.value),
+            Err(code) => (code, code),
+        };
+        let a1_record = ctx.rw(Register::X11, hi);
+        if ctx.emit_precompile_events() {
+            ctx.record_mut().mem64_events.push(Mem64Event {
+                shard,
+                clk,
+                addr,
+                words: result
+                    .ok()
+                    .map(|records| records.map(MemoryRecordEnum::Read)),
+                a1_record: Some(a1_record),
+            });
+        }
+        lo
+    }
+
+    fn num_extra_cycles(&self) -> u32 {
+        MEM64_CYCLES
+    }
+}
+
+/// Stores the u64 with its low word in a0 and its high word in a1 to the 8-aligned address in a2,
+/// little-endian. Returns 0, or [`MEM64_MISALIGNED`].
+pub struct SyscallStore64;
+
+impl SyscallStore64 {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Write the two words of `value` at `addr`, the low word first, each in its own cycle.
+    /// Returns [`MEM64_MISALIGNED`] without accessing memory if `addr` is not 8-aligned.
+    pub fn store(
+        ctx: &mut SyscallContext,
+        addr: u32,
+        value: u64,
+    ) -> Result<[MemoryWriteRecord; 2], u32> {
+        if addr % 8 != 0 {
+            return Err(MEM64_MISALIGNED);
+        }
+        let lo = ctx.mw(addr, value as u32);
+        ctx.advance(1);
+        let hi = ctx.mw(addr + 4, (value >> 32) as u32);
+        ctx.advance(1);
+        Ok([lo, hi])
+    }
+}
+
+impl Syscall for SyscallStore64 {
+    fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+        let args = ctx.args();
+        let addr = ctx.register_unsafe(Register::X12);
+        let value = (args.a1 as u64) << 32 | args.a0 as u64;
+        let (shard, clk) = (ctx.current_shard(), ctx.clk());
+        let records = match Self::store(ctx, addr, value) {
+            Ok(records) => records,
+            Err(code) => return code,
+        };
+        if ctx.emit_precompile_events() {
+            ctx.record_mut().mem64_events.push(Mem64Event {
+                shard,
+                clk,
+                addr,
+                words: Some(records.map(MemoryRecordEnum::Write)),
+                a1_record: None,
+            });
+        }
+        0
+    }
+
+    fn num_extra_cycles(&self) -> u32 {
+        MEM64_CYCLES
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::{Program, Runtime, SyscallArgs, SyscallCode};
+    use crate::utils::asm::assemble;
+
+    /// Stores two words at 0x1000 with `sw`, then copies the u64 there to 0x1008 after adding one
+    /// to it, `iterations` times. The copy uses the syscalls if `syscalls`, and `lw`/`sw`
+    /// otherwise.
+    fn copy_program(syscalls: bool, iterations: u32) -> Program {
+        let copy = if syscalls {
+            format!(
+                "
+                mv   a0, s0
+                li   t0, {}
+                ecall
+                addi a0, a0, 1
+                addi a2, s0, 8
+                li   t0, {}
+                ecall
+                ",
+                SyscallCode::LOAD64 as u32,
+                SyscallCode::STORE64 as u32
+            )
+        } else {
+            "
+            lw   a0, 0(s0)
+            lw   a1, 4(s0)
+            addi a0, a0, 1
+            sw   a0, 8(s0)
+            sw   a1, 12(s0)
+            "
+            .to_string()
+        };
+        let source = format!(
+            "
+                    li   s0, 0x1000
+                    li   t1, 0x01234567
+                    sw   t1, 0(s0)
+                    li   t1, 0x89abcdef
+                    sw   t1, 4(s0)
+                    li   s1, {}
+            loop:   {}
+                    addi s1, s1, -1
+                    bne  s1, zero, loop
+            ",
+            iterations, copy
+        );
+        assemble(&source, 0).unwrap()
+    }
+
+    fn run(program: Program, shard_size: u32) -> Runtime {
+        let mut runtime = Runtime::new(program);
+        runtime.shard_size = shard_size;
+        runtime.trace_syscalls = true;
+        runtime.run();
+        assert!(runtime
+            .record
+            .check_memory_consistency(&runtime.program)
+            .is_ok());
+        runtime
+    }
+
+    #[test]
+    fn test_memory_records() {
+        let mut runtime = Runtime::new(Program::new(vec![], 0, 0));
+        runtime.state.memory.insert(0x1000, (0x01234567, 1, 8));
+        runtime.state.memory.insert(0x1004, (0x89abcdef, 1, 12));
+        runtime.state.clk = 40;
+        let args = SyscallArgs {
+            code: SyscallCode::LOAD64 as u32,
+            a0: 0x1000,
+            a1: 0,
+        };
+        let mut ctx = SyscallContext::new(&mut runtime, args);
+
+        let [lo, hi] = SyscallLoad64::load(&mut ctx, 0x1000).unwrap();
+        assert_eq!(
+            (lo.value, lo.timestamp, lo.prev_timestamp),
+            (0x01234567, 40, 8)
+        );
+        assert_eq!(
+            (hi.value, hi.timestamp, hi.prev_timestamp),
+            (0x89abcdef, 41, 12)
+        );
+
+        let [lo, hi] = SyscallStore64::store(&mut ctx, 0x1000, 0x1122334455667788).unwrap();
+        assert_eq!(
+            (lo.prev_value, lo.value, lo.timestamp),
+            (0x01234567, 0x55667788, 42)
+        );
+        assert_eq!(
+            (hi.prev_value, hi.value, hi.timestamp),
+            (0x89abcdef, 0x11223344, 43)
+        );
+        assert_eq!(lo.prev_timestamp, 40);
+        assert_eq!(ctx.clk(), 44);
+
+        assert_eq!(SyscallLoad64::load(&mut ctx, 0x1004), Err(MEM64_MISALIGNED));
+        assert_eq!(
+            SyscallStore64::store(&mut ctx, 0x1002, 0),
+            Err(MEM64_MISALIGNED)
+        );
+        assert_eq!(ctx.clk(), 44);
+
+        // The accesses of the syscall reach the record.
+        assert_eq!(SyscallLoad64::new().execute(&mut ctx), 0x55667788);
+        let event = runtime.record.mem64_events[0];
+        assert_eq!((event.clk, event.addr), (44, 0x1000));
+        let Some([MemoryRecordEnum::Read(lo), MemoryRecordEnum::Read(hi)]) = event.words else {
+            panic!("expected two reads, found {:?}", event.words);
+        };
+        assert_eq!(
+            (lo.value, lo.timestamp, lo.prev_timestamp),
+            (0x55667788, 44, 42)
+        );
+        assert_eq!(
+            (hi.value, hi.timestamp, hi.prev_timestamp),
+            (0x11223344, 45, 43)
+        );
+        let a1 = event.a1_record.unwrap();
+        assert_eq!((a1.value, a1.timestamp), (0x11223344, 46));
+    }
+
+    #[test]
+    fn test_same_memory_as_word_accesses() {
+        let syscalls = run(copy_program(true, 3), 1 << 16);
+        let words = run(copy_program(false, 3), 1 << 16);
+        let memory = syscalls.state.dump_memory_sorted(Some(0x1000..0x2000));
+        assert_eq!(
+            memory,
+            vec![
+                (0x1000, 0x01234567),
+                (0x1004, 0x89abcdef),
+                (0x1008, 0x01234568),
+                (0x100c, 0x89abcdef)
+            ]
+        );
+        assert_eq!(memory, words.state.dump_memory_sorted(Some(0x1000..0x2000)));
+        assert_eq!(syscalls.register(Register::X11), 0x89abcdef);
+    }
+
+    #[test]
+    fn test_shard_boundaries() {
+        let runtime = run(copy_program(true, 20), 8);
+        let expected = run(copy_program(false, 20), 8);
+        assert_eq!(
+            runtime.state.dump_memory_sorted(None),
+            expected.state.dump_memory_sorted(None)
+        );
+        assert!(runtime.state.current_shard > 5);
+
+        // Both accesses of every call are in the shard of its `ecall`, which still fits in it.
+        assert_eq!(runtime.syscall_trace.len(), 40);
+        for entry in runtime.syscall_trace.iter() {
+            assert_eq!(entry.extra_cycles, MEM64_CYCLES);
+            let event = runtime
+                .record
+                .cpu_events
+                .iter()
+                .find(|event| event.pc == entry.pc && event.clk == entry.clk + MEM64_CYCLES)
+                .unwrap();
+            assert_eq!(event.shard, entry.shard);
+            assert!(event.clk < runtime.shard_size * 4);
+        }
+    }
+
+    #[test]
+    fn test_misaligned() {
+        let program = assemble(
+            &format!(
+                "
+                li   a0, 0x1004
+                li   t0, {}
+                ecall
+                mv   s0, a0
+                li   a2, 0x1001
+                li   t0, {}
+                ecall
+                ",
+                SyscallCode::LOAD64 as u32,
+                SyscallCode::STORE64 as u32
+            ),
+            0,
+        )
+        .unwrap();
+        let runtime = run(program, 1 << 16);
+        assert_eq!(runtime.register(Register::X8), MEM64_MISALIGNED);
+        assert_eq!(runtime.register(Register::X11), MEM64_MISALIGNED);
+        assert_eq!(runtime.register(Register::X10), MEM64_MISALIGNED);
+        assert!(runtime
+            .state
+            .dump_memory_sorted(Some(0x1000..0x2000))
+            .is_empty());
+    }
+}
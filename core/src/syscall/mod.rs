@@ -1,10 +1,14 @@
 mod halt;
 mod lwa;
+mod mem64;
 pub mod precompiles;
+mod shard;
 mod unconstrained;
 mod write;
 
 pub use halt::*;
 pub use lwa::*;
+pub use mem64::*;
+pub use shard::*;
 pub use unconstrained::*;
 pub use write::*;
@@ -1,5 +1,4 @@
 use crate::cpu::{MemoryReadRecord, MemoryWriteRecord};
-use crate::runtime::Register;
 use crate::runtime::Syscall;
 use crate::syscall::precompiles::blake3::{
     g_func, Blake3CompressInnerChip, Blake3CompressInnerEvent, G_INDEX, MSG_SCHEDULE,
@@ -14,10 +13,10 @@ impl Syscall for Blake3CompressInnerChip {
 
     fn execute(&self, rt: &mut SyscallContext) -> u32 {
         // TODO: These pointers have to be constrained.
-        let state_ptr = rt.register_unsafe(Register::X10);
-        let message_ptr = rt.register_unsafe(Register::X11);
+        let state_ptr = rt.args().a0;
+        let message_ptr = rt.args().a1;
 
-        let saved_clk = rt.clk;
+        let saved_clk = rt.clk();
         let mut message_reads =
             [[[MemoryReadRecord::default(); NUM_MSG_WORDS_PER_CALL]; OPERATION_COUNT]; ROUND_COUNT];
         let mut state_writes = [[[MemoryWriteRecord::default(); NUM_STATE_WORDS_PER_CALL];
@@ -54,22 +53,24 @@ impl Syscall for Blake3CompressInnerChip {
                 }
 
                 // Increment the clock for the next call of g.
-                rt.clk += 4;
+                rt.advance(4);
             }
         }
 
         let shard = rt.current_shard();
 
-        rt.record_mut()
-            .blake3_compress_inner_events
-            .push(Blake3CompressInnerEvent {
-                shard,
-                clk: saved_clk,
-                state_ptr,
-                message_reads,
-                state_writes,
-                message_ptr,
-            });
+        if rt.emit_precompile_events() {
+            rt.record_mut()
+                .blake3_compress_inner_events
+                .push(Blake3CompressInnerEvent {
+                    shard,
+                    clk: saved_clk,
+                    state_ptr,
+                    message_reads,
+                    state_writes,
+                    message_ptr,
+                });
+        }
 
         state_ptr
     }
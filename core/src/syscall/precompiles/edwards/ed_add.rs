@@ -116,7 +116,9 @@ impl<E: EllipticCurve + EdwardsParameters> Syscall for EdAddAssignChip<E> {
 
     fn execute(&self, rt: &mut SyscallContext) -> u32 {
         let event = create_ec_add_event::<E>(rt);
-        rt.record_mut().ed_add_events.push(event);
+        if rt.emit_precompile_events() {
+            rt.record_mut().ed_add_events.push(event);
+        }
         event.p_ptr + 1
     }
 }
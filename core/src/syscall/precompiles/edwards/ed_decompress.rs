@@ -34,13 +34,14 @@ use p3_air::{Air, AirBuilder, BaseAir};
 use p3_field::AbstractField;
 use p3_field::PrimeField32;
 use p3_matrix::MatrixRowSlices;
+use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
 
 use p3_matrix::dense::RowMajorMatrix;
 use sp1_derive::AlignedBorrow;
 use std::fmt::Debug;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct EdDecompressEvent {
     pub shard: u32,
     pub clk: u32,
@@ -197,12 +198,10 @@ pub struct EdDecompressChip<E> {
 
 impl<E: EdwardsParameters> Syscall for EdDecompressChip<E> {
     fn execute(&self, rt: &mut SyscallContext) -> u32 {
-        let a0 = crate::runtime::Register::X10;
-
-        let start_clk = rt.clk;
+        let start_clk = rt.clk();
 
         // TODO: this will have to be be constrained, but can do it later.
-        let slice_ptr = rt.register_unsafe(a0);
+        let slice_ptr = rt.args().a0;
         if slice_ptr % 4 != 0 {
             panic!();
         }
@@ -240,20 +239,22 @@ impl<E: EdwardsParameters> Syscall for EdDecompressChip<E> {
         let x_memory_records: [MemoryWriteRecord; 8] = x_memory_records_vec.try_into().unwrap();
 
         let shard = rt.current_shard();
-        rt.record_mut()
-            .ed_decompress_events
-            .push(EdDecompressEvent {
-                shard,
-                clk: start_clk,
-                ptr: slice_ptr,
-                sign: sign_bool,
-                y_bytes,
-                decompressed_x_bytes: decompressed_x_bytes.try_into().unwrap(),
-                x_memory_records,
-                y_memory_records,
-            });
-
-        rt.clk += 4;
+        if rt.emit_precompile_events() {
+            rt.record_mut()
+                .ed_decompress_events
+                .push(EdDecompressEvent {
+                    shard,
+                    clk: start_clk,
+                    ptr: slice_ptr,
+                    sign: sign_bool,
+                    y_bytes,
+                    decompressed_x_bytes: decompressed_x_bytes.try_into().unwrap(),
+                    x_memory_records,
+                    y_memory_records,
+                });
+        }
+
+        rt.advance(4);
 
         slice_ptr
     }
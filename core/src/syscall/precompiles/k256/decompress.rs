@@ -37,13 +37,14 @@ use p3_air::{Air, BaseAir};
 use p3_field::AbstractField;
 use p3_field::PrimeField32;
 use p3_matrix::MatrixRowSlices;
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 use p3_matrix::dense::RowMajorMatrix;
 use sp1_derive::AlignedBorrow;
 use std::fmt::Debug;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct K256DecompressEvent {
     pub shard: u32,
     pub clk: u32,
@@ -76,12 +77,10 @@ impl Syscall for K256DecompressChip {
     }
 
     fn execute(&self, rt: &mut SyscallContext) -> u32 {
-        let a0 = crate::runtime::Register::X10;
-
-        let start_clk = rt.clk;
+        let start_clk = rt.clk();
 
         // TODO: this will have to be be constrained, but can do it later.
-        let slice_ptr = rt.register_unsafe(a0);
+        let slice_ptr = rt.args().a0;
         if slice_ptr % 4 != 0 {
             panic!();
         }
@@ -116,20 +115,22 @@ impl Syscall for K256DecompressChip {
         let y_memory_records: [MemoryWriteRecord; 8] = y_memory_records_vec.try_into().unwrap();
 
         let shard = rt.current_shard();
-        rt.record_mut()
-            .k256_decompress_events
-            .push(K256DecompressEvent {
-                shard,
-                clk: start_clk,
-                ptr: slice_ptr,
-                is_odd: is_odd != 0,
-                x_bytes,
-                decompressed_y_bytes,
-                x_memory_records,
-                y_memory_records,
-            });
-
-        rt.clk += 4;
+        if rt.emit_precompile_events() {
+            rt.record_mut()
+                .k256_decompress_events
+                .push(K256DecompressEvent {
+                    shard,
+                    clk: start_clk,
+                    ptr: slice_ptr,
+                    is_odd: is_odd != 0,
+                    x_bytes,
+                    decompressed_y_bytes,
+                    x_memory_records,
+                    y_memory_records,
+                });
+        }
+
+        rt.advance(4);
 
         slice_ptr
     }
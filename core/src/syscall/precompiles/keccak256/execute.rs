@@ -1,5 +1,5 @@
 use crate::{
-    runtime::{Register, Syscall},
+    runtime::Syscall,
     syscall::precompiles::{keccak256::KeccakPermuteEvent, SyscallContext},
 };
 
@@ -22,9 +22,9 @@ impl Syscall for KeccakPermuteChip {
 
     fn execute(&self, rt: &mut SyscallContext) -> u32 {
         // Read `state_ptr` from register a0.
-        let state_ptr = rt.register_unsafe(Register::X10);
+        let state_ptr = rt.args().a0;
 
-        let saved_clk = rt.clk;
+        let saved_clk = rt.clk();
         let mut state_read_records = Vec::new();
         let mut state_write_records = Vec::new();
 
@@ -82,7 +82,7 @@ impl Syscall for KeccakPermuteChip {
             state[0] ^= RC[i];
         }
 
-        rt.clk += self.num_extra_cycles() - 4;
+        rt.advance(self.num_extra_cycles() - 4);
         let mut values_to_write = Vec::new();
         for i in 0..25 {
             let most_sig = ((state[i] >> 32) & 0xFFFFFFFF) as u32;
@@ -94,21 +94,23 @@ impl Syscall for KeccakPermuteChip {
         let write_records = rt.mw_slice(state_ptr, values_to_write.as_slice());
         state_write_records.extend_from_slice(&write_records);
 
-        rt.clk += 4;
+        rt.advance(4);
 
         // Push the Keccak permute event.
         let shard = rt.current_shard();
-        rt.record_mut()
-            .keccak_permute_events
-            .push(KeccakPermuteEvent {
-                shard,
-                clk: saved_clk,
-                pre_state: saved_state.as_slice().try_into().unwrap(),
-                post_state: state.as_slice().try_into().unwrap(),
-                state_read_records: state_read_records.as_slice().try_into().unwrap(),
-                state_write_records: state_write_records.as_slice().try_into().unwrap(),
-                state_addr: state_ptr,
-            });
+        if rt.emit_precompile_events() {
+            rt.record_mut()
+                .keccak_permute_events
+                .push(KeccakPermuteEvent {
+                    shard,
+                    clk: saved_clk,
+                    pre_state: saved_state.as_slice().try_into().unwrap(),
+                    post_state: state.as_slice().try_into().unwrap(),
+                    state_read_records: state_read_records.as_slice().try_into().unwrap(),
+                    state_write_records: state_write_records.as_slice().try_into().unwrap(),
+                    state_addr: state_ptr,
+                });
+        }
 
         state_ptr
     }
@@ -1,4 +1,5 @@
 use crate::syscall::precompiles::{MemoryReadRecord, MemoryWriteRecord};
+use serde::{Deserialize, Serialize};
 
 use p3_keccak_air::KeccakAir;
 
@@ -12,13 +13,15 @@ const STATE_SIZE: usize = 25;
 // The permutation state is 25 u64's.  Our word size is 32 bits, so it is 50 words.
 const STATE_NUM_WORDS: usize = 25 * 2;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct KeccakPermuteEvent {
     pub shard: u32,
     pub clk: u32,
     pub pre_state: [u64; STATE_SIZE],
     pub post_state: [u64; STATE_SIZE],
+    #[serde(with = "crate::utils::serialization::array")]
     pub state_read_records: [MemoryReadRecord; STATE_NUM_WORDS],
+    #[serde(with = "crate::utils::serialization::array")]
     pub state_write_records: [MemoryWriteRecord; STATE_NUM_WORDS],
     pub state_addr: u32,
 }
@@ -65,7 +68,7 @@ pub mod permute_tests {
     pub fn test_keccak_permute_program_execute() {
         let program = keccak_permute_program();
         let mut runtime = Runtime::new(program);
-        runtime.run()
+        runtime.run();
     }
 
     #[test]
@@ -6,6 +6,7 @@ pub mod sha256;
 pub mod weierstrass;
 
 use num::BigUint;
+use serde::{Deserialize, Serialize};
 
 use crate::air::SP1AirBuilder;
 use crate::operations::field::params::Limbs;
@@ -15,7 +16,7 @@ use crate::utils::ec::{AffinePoint, EllipticCurve};
 use crate::{cpu::MemoryReadRecord, cpu::MemoryWriteRecord};
 
 /// Elliptic curve add event.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ECAddEvent {
     pub shard: u32,
     pub clk: u32,
@@ -29,13 +30,12 @@ pub struct ECAddEvent {
 }
 
 pub fn create_ec_add_event<E: EllipticCurve>(rt: &mut SyscallContext) -> ECAddEvent {
-    let a0 = crate::runtime::Register::X10;
     let a1 = crate::runtime::Register::X11;
 
-    let start_clk = rt.clk;
+    let start_clk = rt.clk();
 
     // TODO: these will have to be be constrained, but can do it later.
-    let p_ptr = rt.register_unsafe(a0);
+    let p_ptr = rt.args().a0;
     if p_ptr % 4 != 0 {
         panic!();
     }
@@ -50,7 +50,7 @@ pub fn create_ec_add_event<E: EllipticCurve>(rt: &mut SyscallContext) -> ECAddEv
     let q_memory_records = q_memory_records_vec.try_into().unwrap();
     let q: [u32; 16] = q_vec.try_into().unwrap();
     // When we write to p, we want the clk to be incremented.
-    rt.clk += 4;
+    rt.advance(4);
 
     let p_affine = AffinePoint::<E>::from_words_le(&p);
     let q_affine = AffinePoint::<E>::from_words_le(&q);
@@ -59,7 +59,7 @@ pub fn create_ec_add_event<E: EllipticCurve>(rt: &mut SyscallContext) -> ECAddEv
 
     let p_memory_records = rt.mw_slice(p_ptr, &result_words).try_into().unwrap();
 
-    rt.clk += 4;
+    rt.advance(4);
 
     ECAddEvent {
         shard: rt.current_shard(),
@@ -75,7 +75,7 @@ pub fn create_ec_add_event<E: EllipticCurve>(rt: &mut SyscallContext) -> ECAddEv
 }
 
 /// Elliptic curve double event.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ECDoubleEvent {
     pub shard: u32,
     pub clk: u32,
@@ -85,12 +85,10 @@ pub struct ECDoubleEvent {
 }
 
 pub fn create_ec_double_event<E: EllipticCurve>(rt: &mut SyscallContext) -> ECDoubleEvent {
-    let a0 = crate::runtime::Register::X10;
-
-    let start_clk = rt.clk;
+    let start_clk = rt.clk();
 
     // TODO: these will have to be be constrained, but can do it later.
-    let p_ptr = rt.register_unsafe(a0);
+    let p_ptr = rt.args().a0;
     if p_ptr % 4 != 0 {
         panic!();
     }
@@ -98,7 +96,7 @@ pub fn create_ec_double_event<E: EllipticCurve>(rt: &mut SyscallContext) -> ECDo
     let p: [u32; 16] = rt.slice_unsafe(p_ptr, 16).try_into().unwrap();
 
     // When we write to p, we want the clk to be incremented.
-    rt.clk += 4;
+    rt.advance(4);
 
     let p_affine = AffinePoint::<E>::from_words_le(&p);
     let result_affine = E::ec_double(&p_affine);
@@ -106,7 +104,7 @@ pub fn create_ec_double_event<E: EllipticCurve>(rt: &mut SyscallContext) -> ECDo
 
     let p_memory_records = rt.mw_slice(p_ptr, &result_words).try_into().unwrap();
 
-    rt.clk += 4;
+    rt.advance(4);
 
     ECDoubleEvent {
         shard: rt.current_shard(),
@@ -1,5 +1,5 @@
 use crate::{
-    runtime::{Register, Syscall},
+    runtime::Syscall,
     syscall::precompiles::{
         sha256::{ShaCompressEvent, SHA_COMPRESS_K},
         SyscallContext,
@@ -15,11 +15,11 @@ impl Syscall for ShaCompressChip {
 
     fn execute(&self, rt: &mut SyscallContext) -> u32 {
         // Read `w_ptr` from register a0.
-        let w_ptr = rt.register_unsafe(Register::X10);
+        let w_ptr = rt.args().a0;
 
         // Set the clock back to the original value and begin executing the
         // precompile.
-        let saved_clk = rt.clk;
+        let saved_clk = rt.clk();
         let saved_w_ptr = w_ptr;
         let mut h_read_records = Vec::new();
         let mut w_i_read_records = Vec::new();
@@ -32,7 +32,7 @@ impl Syscall for ShaCompressChip {
             let (record, value) = rt.mr(w_ptr + (H_START_IDX + i as u32) * 4);
             h_read_records.push(record);
             hx[i] = value;
-            rt.clk += 4;
+            rt.advance(4);
         }
 
         let mut original_w = Vec::new();
@@ -69,7 +69,7 @@ impl Syscall for ShaCompressChip {
             b = a;
             a = temp1.wrapping_add(temp2);
 
-            rt.clk += 4;
+            rt.advance(4);
         }
 
         // Execute the "finalize" phase.
@@ -80,21 +80,23 @@ impl Syscall for ShaCompressChip {
                 hx[i].wrapping_add(v[i]),
             );
             h_write_records.push(record);
-            rt.clk += 4;
+            rt.advance(4);
         }
 
         // Push the SHA extend event.
         let shard = rt.current_shard();
-        rt.record_mut().sha_compress_events.push(ShaCompressEvent {
-            shard,
-            clk: saved_clk,
-            w_and_h_ptr: saved_w_ptr,
-            w: original_w.try_into().unwrap(),
-            h: hx,
-            h_read_records: h_read_records.try_into().unwrap(),
-            w_i_read_records: w_i_read_records.try_into().unwrap(),
-            h_write_records: h_write_records.try_into().unwrap(),
-        });
+        if rt.emit_precompile_events() {
+            rt.record_mut().sha_compress_events.push(ShaCompressEvent {
+                shard,
+                clk: saved_clk,
+                w_and_h_ptr: saved_w_ptr,
+                w: original_w.try_into().unwrap(),
+                h: hx,
+                h_read_records: h_read_records.try_into().unwrap(),
+                w_i_read_records: w_i_read_records.try_into().unwrap(),
+                h_write_records: h_write_records.try_into().unwrap(),
+            });
+        }
 
         w_ptr
     }
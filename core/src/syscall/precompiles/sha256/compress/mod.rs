@@ -1,4 +1,5 @@
 use crate::cpu::{MemoryReadRecord, MemoryWriteRecord};
+use serde::{Deserialize, Serialize};
 
 mod air;
 mod columns;
@@ -16,14 +17,16 @@ pub const SHA_COMPRESS_K: [u32; 64] = [
     0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
 ];
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ShaCompressEvent {
     pub shard: u32,
     pub clk: u32,
     pub w_and_h_ptr: u32,
+    #[serde(with = "crate::utils::serialization::array")]
     pub w: [u32; 64],
     pub h: [u32; 8],
     pub h_read_records: [MemoryReadRecord; 8],
+    #[serde(with = "crate::utils::serialization::array")]
     pub w_i_read_records: [MemoryReadRecord; 64],
     pub h_write_records: [MemoryWriteRecord; 8],
 }
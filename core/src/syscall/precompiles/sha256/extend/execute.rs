@@ -1,5 +1,5 @@
 use crate::{
-    runtime::{Register, Syscall},
+    runtime::Syscall,
     syscall::precompiles::{sha256::ShaExtendEvent, SyscallContext},
 };
 
@@ -11,14 +11,11 @@ impl Syscall for ShaExtendChip {
     }
 
     fn execute(&self, rt: &mut SyscallContext) -> u32 {
-        // Initialize the registers.
-        let a0 = Register::X10;
-
-        // Read `w_ptr` from register a0 or x5.
+        // Read `w_ptr` from register a0.
         // TODO: this is underconstrained.
-        let w_ptr = rt.register_unsafe(a0);
+        let w_ptr = rt.args().a0;
 
-        let clk_init = rt.clk;
+        let clk_init = rt.clk();
         let w_ptr_init = w_ptr;
         let mut w_i_minus_15_reads = Vec::new();
         let mut w_i_minus_2_reads = Vec::new();
@@ -29,7 +26,7 @@ impl Syscall for ShaExtendChip {
             // Read w[i-15].
             let (record, w_i_minus_15) = rt.mr(w_ptr + (i - 15) * 4);
             w_i_minus_15_reads.push(record);
-            rt.clk += 4;
+            rt.advance(4);
 
             // Compute `s0`.
             let s0 =
@@ -38,7 +35,7 @@ impl Syscall for ShaExtendChip {
             // Read w[i-2].
             let (record, w_i_minus_2) = rt.mr(w_ptr + (i - 2) * 4);
             w_i_minus_2_reads.push(record);
-            rt.clk += 4;
+            rt.advance(4);
 
             // Compute `s1`.
             let s1 =
@@ -47,12 +44,12 @@ impl Syscall for ShaExtendChip {
             // Read w[i-16].
             let (record, w_i_minus_16) = rt.mr(w_ptr + (i - 16) * 4);
             w_i_minus_16_reads.push(record);
-            rt.clk += 4;
+            rt.advance(4);
 
             // Read w[i-7].
             let (record, w_i_minus_7) = rt.mr(w_ptr + (i - 7) * 4);
             w_i_minus_7_reads.push(record);
-            rt.clk += 4;
+            rt.advance(4);
 
             // Compute `w_i`.
             let w_i = s1
@@ -62,21 +59,23 @@ impl Syscall for ShaExtendChip {
 
             // Write w[i].
             w_i_writes.push(rt.mw(w_ptr + i * 4, w_i));
-            rt.clk += 4;
+            rt.advance(4);
         }
 
         // Push the SHA extend event.
         let shard = rt.current_shard();
-        rt.record_mut().sha_extend_events.push(ShaExtendEvent {
-            shard,
-            clk: clk_init,
-            w_ptr: w_ptr_init,
-            w_i_minus_15_reads: w_i_minus_15_reads.try_into().unwrap(),
-            w_i_minus_2_reads: w_i_minus_2_reads.try_into().unwrap(),
-            w_i_minus_16_reads: w_i_minus_16_reads.try_into().unwrap(),
-            w_i_minus_7_reads: w_i_minus_7_reads.try_into().unwrap(),
-            w_i_writes: w_i_writes.try_into().unwrap(),
-        });
+        if rt.emit_precompile_events() {
+            rt.record_mut().sha_extend_events.push(ShaExtendEvent {
+                shard,
+                clk: clk_init,
+                w_ptr: w_ptr_init,
+                w_i_minus_15_reads: w_i_minus_15_reads.try_into().unwrap(),
+                w_i_minus_2_reads: w_i_minus_2_reads.try_into().unwrap(),
+                w_i_minus_16_reads: w_i_minus_16_reads.try_into().unwrap(),
+                w_i_minus_7_reads: w_i_minus_7_reads.try_into().unwrap(),
+                w_i_writes: w_i_writes.try_into().unwrap(),
+            });
+        }
 
         w_ptr
     }
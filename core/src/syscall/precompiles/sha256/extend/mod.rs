@@ -7,16 +7,22 @@ mod trace;
 pub use columns::*;
 
 use crate::cpu::{MemoryReadRecord, MemoryWriteRecord};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ShaExtendEvent {
     pub shard: u32,
     pub clk: u32,
     pub w_ptr: u32,
+    #[serde(with = "crate::utils::serialization::array")]
     pub w_i_minus_15_reads: [MemoryReadRecord; 48],
+    #[serde(with = "crate::utils::serialization::array")]
     pub w_i_minus_2_reads: [MemoryReadRecord; 48],
+    #[serde(with = "crate::utils::serialization::array")]
     pub w_i_minus_16_reads: [MemoryReadRecord; 48],
+    #[serde(with = "crate::utils::serialization::array")]
     pub w_i_minus_7_reads: [MemoryReadRecord; 48],
+    #[serde(with = "crate::utils::serialization::array")]
     pub w_i_writes: [MemoryWriteRecord; 48],
 }
 
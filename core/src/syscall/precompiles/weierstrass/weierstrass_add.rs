@@ -68,7 +68,9 @@ pub struct WeierstrassAddAssignChip<E> {
 impl<E: EllipticCurve> Syscall for WeierstrassAddAssignChip<E> {
     fn execute(&self, rt: &mut SyscallContext) -> u32 {
         let event = create_ec_add_event::<E>(rt);
-        rt.record_mut().weierstrass_add_events.push(event);
+        if rt.emit_precompile_events() {
+            rt.record_mut().weierstrass_add_events.push(event);
+        }
         event.p_ptr + 1
     }
 
@@ -69,7 +69,9 @@ pub struct WeierstrassDoubleAssignChip<E> {
 impl<E: EllipticCurve + WeierstrassParameters> Syscall for WeierstrassDoubleAssignChip<E> {
     fn execute(&self, rt: &mut SyscallContext) -> u32 {
         let event = create_ec_double_event::<E>(rt);
-        rt.record_mut().weierstrass_double_events.push(event);
+        if rt.emit_precompile_events() {
+            rt.record_mut().weierstrass_double_events.push(event);
+        }
         event.p_ptr + 1
     }
 
@@ -0,0 +1,92 @@
+use crate::runtime::{Syscall, SyscallContext};
+
+/// The default number of cycles a shard must have before a shard hint ends it.
+pub const DEFAULT_MIN_HINTED_SHARD_CYCLES: u32 = 1 << 16;
+
+/// Asks the runtime to start a new shard once the current instruction finishes, so that shards
+/// can be aligned with the phases of the guest. The hint is deferred until the current shard has
+/// at least `Runtime::min_hinted_shard_cycles` cycles, and ignored in unconstrained blocks.
+pub struct SyscallShardHint;
+
+impl SyscallShardHint {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Syscall for SyscallShardHint {
+    fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+        if !ctx.rt.unconstrained {
+            ctx.rt.shard_hint_pending = true;
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::runtime::{Instruction, Opcode, Program, Runtime, SyscallCode};
+
+    fn ecall(code: SyscallCode) -> [Instruction; 2] {
+        [
+            Instruction::new(Opcode::ADD, 5, 0, code as u32, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ]
+    }
+
+    /// Four blocks of 8 instructions, each ending with a shard hint.
+    fn hint_program() -> Program {
+        let mut instructions = vec![];
+        for _ in 0..4 {
+            instructions.extend([Instruction::new(Opcode::ADD, 29, 29, 1, false, true); 6]);
+            instructions.extend(ecall(SyscallCode::SHARD_HINT));
+        }
+        Program::new(instructions, 0, 0)
+    }
+
+    /// The shard of every CPU event after running `program`.
+    fn shards(program: Program, min_hinted_shard_cycles: u32) -> Vec<u32> {
+        let mut runtime = Runtime::new(program);
+        runtime.min_hinted_shard_cycles = min_hinted_shard_cycles;
+        runtime.run();
+        assert!(runtime
+            .record
+            .check_memory_consistency(&runtime.program)
+            .is_ok());
+        runtime
+            .record
+            .cpu_events
+            .iter()
+            .map(|event| event.shard)
+            .collect()
+    }
+
+    #[test]
+    fn test_shard_hints() {
+        let expected = (0..32).map(|i| 1 + i / 8).collect::<Vec<_>>();
+        assert_eq!(shards(hint_program(), 8), expected);
+
+        // Hints in shards shorter than 12 cycles are deferred until the shard reaches 12 cycles.
+        let expected = (0..32).map(|i| 1 + i / 12).collect::<Vec<_>>();
+        assert_eq!(shards(hint_program(), 12), expected);
+
+        // Without hints, the program fits in a single shard.
+        let mut program = hint_program();
+        program
+            .instructions
+            .retain(|instruction| instruction.opcode != Opcode::ECALL);
+        assert!(shards(program, 8).iter().all(|&shard| shard == 1));
+    }
+
+    #[test]
+    fn test_shard_hint_ignored_when_unconstrained() {
+        let mut instructions = ecall(SyscallCode::ENTER_UNCONSTRAINED).to_vec();
+        // Skip the block when it is entered again after exiting.
+        instructions.push(Instruction::new(Opcode::BEQ, 10, 0, 20, false, true));
+        instructions.extend(ecall(SyscallCode::SHARD_HINT));
+        instructions.extend(ecall(SyscallCode::EXIT_UNCONSTRAINED));
+        instructions.extend([Instruction::new(Opcode::ADD, 29, 29, 1, false, true); 16]);
+        let shards = shards(Program::new(instructions, 0, 0), 1);
+        assert!(shards.iter().all(|&shard| shard == 1));
+    }
+}
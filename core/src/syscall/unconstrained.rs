@@ -1,5 +1,4 @@
-use crate::runtime::{ForkState, Syscall, SyscallContext};
-use hashbrown::HashMap;
+use crate::runtime::{ExecutionError, ForkState, Syscall, SyscallContext};
 
 pub struct SyscallEnterUnconstrained;
 
@@ -14,15 +13,12 @@ impl Syscall for SyscallEnterUnconstrained {
         if ctx.rt.unconstrained {
             panic!("Unconstrained block is already active.");
         }
-        ctx.rt.unconstrained = true;
-        ctx.rt.unconstrained_state = ForkState {
-            global_clk: ctx.rt.state.global_clk,
-            clk: ctx.rt.state.clk,
-            pc: ctx.rt.state.pc,
-            memory_diff: HashMap::default(),
-            record: std::mem::take(&mut ctx.rt.record),
-            op_record: std::mem::take(&mut ctx.rt.cpu_record),
-        };
+        if ctx.rt.forbid_unconstrained {
+            let pc = ctx.rt.state.pc;
+            ctx.rt.trap(ExecutionError::UnconstrainedForbidden { pc });
+            return 0;
+        }
+        ctx.rt.enter_unconstrained();
         1
     }
 }
@@ -37,27 +33,240 @@ impl SyscallExitUnconstrained {
 
 impl Syscall for SyscallExitUnconstrained {
     fn execute(&self, ctx: &mut SyscallContext) -> u32 {
-        // Reset the state of the runtime.
+        // Reset the state of the runtime. The return value is written after the reset, so the
+        // guest resumes after the `ecall` entering the block with 0 in a0.
         if ctx.rt.unconstrained {
-            ctx.rt.state.global_clk = ctx.rt.unconstrained_state.global_clk;
-            ctx.rt.state.clk = ctx.rt.unconstrained_state.clk;
-            ctx.rt.state.pc = ctx.rt.unconstrained_state.pc;
+            ctx.rt.exit_unconstrained();
             ctx.next_pc = ctx.rt.state.pc.wrapping_add(4);
-            for (addr, value) in ctx.rt.unconstrained_state.memory_diff.drain() {
-                match value {
-                    Some(value) => {
-                        ctx.rt.state.memory.insert(addr, value);
-                    }
-                    None => {
-                        ctx.rt.state.memory.remove(&addr);
-                    }
-                }
-            }
-            ctx.rt.record = std::mem::take(&mut ctx.rt.unconstrained_state.record);
-            ctx.rt.cpu_record = std::mem::take(&mut ctx.rt.unconstrained_state.op_record);
-            ctx.rt.unconstrained = false;
         }
         ctx.rt.unconstrained_state = ForkState::default();
         0
     }
 }
+
+/// Copies a slice of memory, as seen by the unconstrained block, into a staging buffer that is
+/// appended to the input stream once the block exits. This lets hint code hand large witnesses
+/// back to constrained execution without writing them out word by word.
+///
+/// The pointer is passed in a0 and the length in bytes in a1. Outside of an unconstrained block
+/// nothing is staged. Returns 0, or the error code of a slice that cannot be read, see
+/// [`SyscallArgError`](crate::runtime::SyscallArgError).
+pub struct SyscallHintSlice;
+
+impl SyscallHintSlice {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Syscall for SyscallHintSlice {
+    fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+        let args = ctx.args();
+        if !ctx.rt.unconstrained {
+            tracing::warn!("hint slice staged outside of an unconstrained block is ignored");
+            return 0;
+        }
+        let bytes = match ctx.read_bytes_checked(args.a0, args.a1) {
+            Ok(bytes) => bytes,
+            Err(err) => return err.code(),
+        };
+        ctx.rt.append_guest_input(&bytes);
+        0
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::cpu::MemoryRecordEnum;
+    use crate::runtime::{
+        Instruction, Opcode, Program, Register, Runtime, SyscallCode, SYSCALL_ERR_ADDRESS_WRAP,
+        SYSCALL_ERR_LENGTH_TOO_LARGE,
+    };
+
+    fn ecall(code: SyscallCode) -> [Instruction; 2] {
+        [
+            Instruction::new(Opcode::ADD, 5, 0, code as u32, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+        ]
+    }
+
+    /// Fills 4KB at 0x10000 with the words 0..1024 inside an unconstrained block and stages them,
+    /// then reads them back from the input stream and sums them into x9.
+    fn hint_program() -> Program {
+        let mut instructions = vec![];
+        instructions.extend(ecall(SyscallCode::ENTER_UNCONSTRAINED));
+        // Skip the block when it is entered again after exiting.
+        instructions.push(Instruction::new(Opcode::BEQ, 10, 0, 48, false, true));
+        instructions.extend([
+            Instruction::new(Opcode::ADD, 6, 0, 0x10000, false, true),
+            Instruction::new(Opcode::ADD, 7, 0, 0x11000, false, true),
+            Instruction::new(Opcode::ADD, 8, 0, 0, false, true),
+            Instruction::new(Opcode::SW, 8, 6, 0, false, true),
+            Instruction::new(Opcode::ADD, 8, 8, 1, false, true),
+            Instruction::new(Opcode::ADD, 6, 6, 4, false, true),
+            Instruction::new(Opcode::BNE, 6, 7, -12i32 as u32, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, 0x10000, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 4096, false, true),
+        ]);
+        instructions.extend(ecall(SyscallCode::HINT_SLICE));
+        instructions.extend(ecall(SyscallCode::EXIT_UNCONSTRAINED));
+        instructions.extend([
+            Instruction::new(Opcode::ADD, 9, 0, 0, false, true),
+            Instruction::new(Opcode::ADD, 12, 0, 1024, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 4, false, true),
+        ]);
+        instructions.extend(ecall(SyscallCode::LWA));
+        instructions.extend([
+            Instruction::new(Opcode::ADD, 9, 9, 10, false, false),
+            Instruction::new(Opcode::ADD, 12, 12, -1i32 as u32, false, true),
+            Instruction::new(Opcode::BNE, 12, 0, -16i32 as u32, false, true),
+        ]);
+        Program::new(instructions, 0, 0)
+    }
+
+    #[test]
+    fn test_hint_slice() {
+        let mut runtime = Runtime::new(hint_program());
+        runtime.run();
+
+        let expected = (0..1024u32)
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<u8>>();
+        assert_eq!(runtime.state.input_stream, expected);
+        assert_eq!(runtime.state.input_stream_ptr, 4096);
+        assert_eq!(runtime.register(Register::X9), (0..1024).sum::<u32>());
+        // The buffer itself was written in the unconstrained block and rolled back.
+        assert_eq!(runtime.word(0x10004), 0);
+    }
+
+    #[test]
+    fn test_hint_slice_not_leaked() {
+        // Staging outside of an unconstrained block does nothing.
+        let mut instructions = vec![
+            Instruction::new(Opcode::ADD, 10, 0, 0, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 8, false, true),
+        ];
+        instructions.extend(ecall(SyscallCode::HINT_SLICE));
+        let mut runtime = Runtime::new(Program::new(instructions.clone(), 0, 0));
+        runtime.run();
+        assert!(runtime.state.input_stream.is_empty());
+
+        // A block that never exits does not append its staged data.
+        let mut aborted = ecall(SyscallCode::ENTER_UNCONSTRAINED).to_vec();
+        aborted.extend(instructions);
+        let mut runtime = Runtime::new(Program::new(aborted, 0, 0));
+        runtime.run();
+        assert!(runtime.unconstrained);
+        assert!(runtime.state.input_stream.is_empty());
+
+        // Staged data is appended once per block, and a second block starts out empty.
+        let mut twice = hint_program().instructions[..16].to_vec();
+        twice.extend(hint_program().instructions);
+        let mut runtime = Runtime::new(Program::new(twice, 0, 0));
+        runtime.run();
+        assert_eq!(runtime.state.input_stream.len(), 2 * 4096);
+        assert_eq!(
+            runtime.state.input_stream[..4096],
+            runtime.state.input_stream[4096..]
+        );
+        assert_eq!(runtime.state.input_stream_ptr, 4096);
+    }
+
+    #[test]
+    fn test_hint_slice_checked() {
+        // Slices wrapping past the end of the address space or longer than the maximum length
+        // return an error code and stage nothing. The block never exits, so a0 keeps the code.
+        for (ptr, len, code) in [
+            (0xffff_fffc, 8, SYSCALL_ERR_ADDRESS_WRAP),
+            (0x10000, u32::MAX, SYSCALL_ERR_LENGTH_TOO_LARGE),
+        ] {
+            let mut instructions = ecall(SyscallCode::ENTER_UNCONSTRAINED).to_vec();
+            instructions.extend([
+                Instruction::new(Opcode::ADD, 10, 0, ptr, false, true),
+                Instruction::new(Opcode::ADD, 11, 0, len, false, true),
+            ]);
+            instructions.extend(ecall(SyscallCode::HINT_SLICE));
+            let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+            runtime.run();
+            assert!(runtime.unconstrained);
+            assert_eq!(runtime.register(Register::X10), code);
+            assert!(runtime.unconstrained_state.staged_inputs.is_empty());
+        }
+    }
+
+    /// A block clobbering a0 and t1, skipped when the `ecall` entering it returns 0, followed by
+    /// the sum of the values of a0 observed after the block into x8 and a count of blocks into x7.
+    fn branching_block() -> Vec<Instruction> {
+        let mut instructions = ecall(SyscallCode::ENTER_UNCONSTRAINED).to_vec();
+        instructions.extend([
+            Instruction::new(Opcode::BEQ, 10, 0, 20, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, 99, false, true),
+            Instruction::new(Opcode::ADD, 6, 0, 5, false, true),
+        ]);
+        instructions.extend(ecall(SyscallCode::EXIT_UNCONSTRAINED));
+        instructions.extend([
+            Instruction::new(Opcode::ADD, 8, 8, 10, false, false),
+            Instruction::new(Opcode::ADD, 7, 7, 1, false, true),
+        ]);
+        instructions
+    }
+
+    #[test]
+    fn test_exit_return_value_persists() {
+        let mut instructions = branching_block();
+        instructions.extend(branching_block());
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime.run();
+
+        assert_eq!(runtime.register(Register::X7), 2);
+        assert_eq!(runtime.register(Register::X8), 0);
+        assert_eq!(runtime.register(Register::X10), 0);
+        assert_eq!(runtime.register(Register::X6), 0);
+        assert_eq!(
+            runtime.register(Register::X5),
+            SyscallCode::ENTER_UNCONSTRAINED as u32
+        );
+    }
+
+    #[test]
+    fn test_exit_ecall_events() {
+        let mut instructions = branching_block();
+        instructions.extend(branching_block());
+        let mut runtime = Runtime::new(Program::new(instructions, 0, 0));
+        runtime.run();
+
+        // Each block is recorded as its `ecall` entering it, returning 0, at the clock following
+        // the previous instruction.
+        let events = runtime.record.cpu_events.iter().collect::<Vec<_>>();
+        let pcs = events.iter().map(|event| event.pc).collect::<Vec<_>>();
+        assert_eq!(pcs, vec![0, 4, 8, 28, 32, 36, 40, 44, 64, 68]);
+        for pair in events.windows(2) {
+            assert_eq!(pair[1].clk, pair[0].clk + 4);
+        }
+        for event in events
+            .iter()
+            .filter(|event| event.instruction.opcode == Opcode::ECALL)
+        {
+            assert_eq!(event.a, 0);
+            assert_eq!(event.b, SyscallCode::ENTER_UNCONSTRAINED as u32);
+            let Some(MemoryRecordEnum::Write(record)) = event.a_record else {
+                panic!("the ecall does not write a0");
+            };
+            assert_eq!(record.value, 0);
+            assert_eq!(record.timestamp, event.clk);
+            assert!(record.prev_timestamp < event.clk);
+        }
+        assert!(runtime
+            .record
+            .check_memory_consistency(&runtime.program)
+            .is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unconstrained block is already active.")]
+    fn test_nested_blocks_rejected() {
+        let mut instructions = ecall(SyscallCode::ENTER_UNCONSTRAINED).to_vec();
+        instructions.extend(branching_block());
+        Runtime::new(Program::new(instructions, 0, 0)).run();
+    }
+}
@@ -13,21 +13,18 @@ impl SyscallWrite {
 
 impl Syscall for SyscallWrite {
     fn execute(&self, ctx: &mut SyscallContext) -> u32 {
-        let a0 = Register::X10;
-        let a1 = Register::X11;
-        let a2 = Register::X12;
-        let rt = &mut ctx.rt;
-        let fd = rt.register(a0);
-        if fd == 1 || fd == 2 || fd == 3 || fd == 4 {
-            let write_buf = rt.register(a1);
-            let nbytes = rt.register(a2);
-            // Read nbytes from memory starting at write_buf.
-            let bytes = (0..nbytes)
-                .map(|i| rt.byte(write_buf + i))
-                .collect::<Vec<u8>>();
-            let slice = bytes.as_slice();
+        let args = ctx.args();
+        let fd = args.a0;
+        let write_buf = args.a1;
+        let nbytes = ctx.register_unsafe(Register::X12);
+        if fd == 1 || fd == 2 {
+            // Strings that cannot be read are reported to the guest instead of printed.
+            let s = match ctx.read_str(write_buf, nbytes) {
+                Ok(s) => s,
+                Err(err) => return err.code(),
+            };
+            let rt = &mut ctx.rt;
             if fd == 1 {
-                let s = core::str::from_utf8(slice).unwrap();
                 if s.contains("cycle-tracker-start:") {
                     let fn_name = s
                         .split("cycle-tracker-start:")
@@ -48,6 +45,9 @@ impl Syscall for SyscallWrite {
                         .trim_end()
                         .trim_start();
                     let (start, depth) = rt.cycle_tracker.remove(fn_name).unwrap_or((0, 0));
+                    if let Some(profiler) = &mut rt.profiler {
+                        profiler.record_span(fn_name, rt.state.global_clk - start, depth);
+                    }
                     // Leftpad by 2 spaces for each depth.
                     let padding = (0..depth).map(|_| "│ ").collect::<String>();
                     log::info!(
@@ -58,17 +58,54 @@ impl Syscall for SyscallWrite {
                 } else {
                     log::info!("stdout: {}", s.trim_end());
                 }
-            } else if fd == 2 {
-                let s = core::str::from_utf8(slice).unwrap();
+            } else {
                 log::info!("stderr: {}", s.trim_end());
-            } else if fd == 3 {
-                rt.state.output_stream.extend_from_slice(slice);
-            } else if fd == 4 {
-                rt.state.input_stream.extend_from_slice(slice);
+            }
+        } else if fd == 3 || fd == 4 {
+            let bytes = match ctx.read_bytes_checked(write_buf, nbytes) {
+                Ok(bytes) => bytes,
+                Err(err) => return err.code(),
+            };
+            if fd == 3 {
+                ctx.rt.state.commit_public_values(&bytes);
             } else {
-                unreachable!()
+                ctx.rt.append_guest_input(&bytes);
             }
         }
         0
     }
 }
+
+/// Appends a buffer to one of the logical output channels.
+///
+/// The channel id is passed in a0, and the buffer pointer and length in a1 and a2. Channel zero is
+/// the same stream that `WRITE` appends to for file descriptor 3. Returns 0, or the error code of
+/// a buffer that cannot be read, see [`SyscallArgError`](crate::runtime::SyscallArgError).
+pub struct SyscallWriteChannel;
+
+impl SyscallWriteChannel {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Syscall for SyscallWriteChannel {
+    fn execute(&self, ctx: &mut SyscallContext) -> u32 {
+        let args = ctx.args();
+        let channel = args.a0;
+        let nbytes = ctx.register_unsafe(Register::X12);
+        let bytes = match ctx.read_bytes_checked(args.a1, nbytes) {
+            Ok(bytes) => bytes,
+            Err(err) => return err.code(),
+        };
+        if channel == 0 {
+            ctx.rt.state.commit_public_values(&bytes);
+        } else {
+            ctx.rt
+                .state
+                .output_channel_mut(channel)
+                .extend_from_slice(&bytes);
+        }
+        0
+    }
+}
@@ -0,0 +1,454 @@
+//! A tiny assembler for writing runtime tests as RISC-V assembly instead of instruction vectors.
+//!
+//! Each line holds an optional `label:`, an optional instruction and an optional `#` comment.
+//! Instructions take the mnemonics of [`Opcode`], with registers named `x0` to `x31` or by their
+//! ABI names, and immediates in decimal or hexadecimal:
+//!
+//! ```text
+//!         li   t1, 10
+//! loop:   addi a0, a0, 3       # sum += 3
+//!         addi t1, t1, -1
+//!         bne  t1, zero, loop
+//!         sw   a0, 0x1000(zero)
+//! ```
+//!
+//! The ALU mnemonics take either a register or an immediate as their last operand, and the `i`
+//! suffixed forms (`addi`, `slli`, ...) are accepted as well. Branches and `jal` take a label or a
+//! byte offset, loads, stores and `jalr` an `offset(base)` operand. The pseudo-instructions `li`,
+//! `mv`, `j`, `call`, `ret` and `nop` are supported.
+use core::fmt::{Display, Formatter};
+use std::collections::HashMap;
+
+use crate::runtime::{Instruction, Opcode, Program, ABI_NAMES};
+
+/// An error in the source of [`assemble`], with the 1-based line it occurred on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// The opcode of an ALU mnemonic, with or without the `i` suffix of its immediate form.
+fn alu_opcode(mnemonic: &str) -> Option<Opcode> {
+    let opcode = match mnemonic {
+        "add" | "addi" => Opcode::ADD,
+        "sub" => Opcode::SUB,
+        "xor" | "xori" => Opcode::XOR,
+        "or" | "ori" => Opcode::OR,
+        "and" | "andi" => Opcode::AND,
+        "sll" | "slli" => Opcode::SLL,
+        "srl" | "srli" => Opcode::SRL,
+        "sra" | "srai" => Opcode::SRA,
+        "slt" | "slti" => Opcode::SLT,
+        "sltu" | "sltiu" => Opcode::SLTU,
+        "mul" => Opcode::MUL,
+        "mulh" => Opcode::MULH,
+        "mulhu" => Opcode::MULHU,
+        "mulhsu" => Opcode::MULHSU,
+        "div" => Opcode::DIV,
+        "divu" => Opcode::DIVU,
+        "rem" => Opcode::REM,
+        "remu" => Opcode::REMU,
+        _ => return None,
+    };
+    Some(opcode)
+}
+
+fn load_opcode(mnemonic: &str) -> Option<Opcode> {
+    match mnemonic {
+        "lb" => Some(Opcode::LB),
+        "lh" => Some(Opcode::LH),
+        "lw" => Some(Opcode::LW),
+        "lbu" => Some(Opcode::LBU),
+        "lhu" => Some(Opcode::LHU),
+        _ => None,
+    }
+}
+
+fn store_opcode(mnemonic: &str) -> Option<Opcode> {
+    match mnemonic {
+        "sb" => Some(Opcode::SB),
+        "sh" => Some(Opcode::SH),
+        "sw" => Some(Opcode::SW),
+        _ => None,
+    }
+}
+
+fn branch_opcode(mnemonic: &str) -> Option<Opcode> {
+    match mnemonic {
+        "beq" => Some(Opcode::BEQ),
+        "bne" => Some(Opcode::BNE),
+        "blt" => Some(Opcode::BLT),
+        "bge" => Some(Opcode::BGE),
+        "bltu" => Some(Opcode::BLTU),
+        "bgeu" => Some(Opcode::BGEU),
+        _ => None,
+    }
+}
+
+fn parse_register(operand: &str) -> Result<u32, String> {
+    if let Some(index) = ABI_NAMES.iter().position(|&name| name == operand) {
+        return Ok(index as u32);
+    }
+    if operand == "fp" {
+        return Ok(8);
+    }
+    operand
+        .strip_prefix('x')
+        .and_then(|index| index.parse::<u32>().ok())
+        .filter(|&index| index < 32)
+        .ok_or_else(|| format!("invalid register `{}`", operand))
+}
+
+/// Parse a decimal or `0x` hexadecimal immediate, optionally negative, that fits in 32 bits.
+fn parse_immediate(operand: &str) -> Result<u32, String> {
+    let (negative, digits) = match operand.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, operand),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => digits.parse::<i64>(),
+    }
+    .ok()
+    .filter(|&value| value <= u32::MAX as i64)
+    .ok_or_else(|| format!("invalid immediate `{}`", operand))?;
+    let value = if negative { -value } else { value };
+    Ok(value as u32)
+}
+
+fn is_immediate(operand: &str) -> bool {
+    operand.starts_with(|c: char| c.is_ascii_digit() || c == '-')
+}
+
+fn is_label(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Parse an `offset(base)` operand, where the offset defaults to zero.
+fn parse_address(operand: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid address `{}`, expected `offset(register)`", operand);
+    let (offset, base) = operand.split_once('(').ok_or_else(invalid)?;
+    let base = base.strip_suffix(')').ok_or_else(invalid)?;
+    let offset = match offset.trim() {
+        "" => 0,
+        offset => parse_immediate(offset)?,
+    };
+    Ok((parse_register(base.trim())?, offset))
+}
+
+/// An instruction whose branch or jump target may be a label defined later.
+struct Pending {
+    line: usize,
+    instruction: Instruction,
+    target: Option<String>,
+}
+
+/// Assemble `source` into a program starting at its first instruction, placed at `pc_base`.
+pub fn assemble(source: &str, pc_base: u32) -> Result<Program, AsmError> {
+    let mut labels = HashMap::new();
+    let mut pending = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: String| AsmError {
+            line: line_number,
+            message,
+        };
+        let mut line = line.split('#').next().unwrap().trim();
+
+        // Any number of labels may precede the instruction.
+        while let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(error(format!("invalid label `{}`", label)));
+            }
+            let pc = pc_base + 4 * pending.len() as u32;
+            if labels.insert(label.to_string(), pc).is_some() {
+                return Err(error(format!("duplicate label `{}`", label)));
+            }
+            line = rest.trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let operands = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .collect::<Vec<_>>();
+        let (instruction, target) = parse_instruction(&mnemonic.to_lowercase(), &operands)
+            .map_err(|message| error(format!("{} in `{}`", message, line)))?;
+        pending.push(Pending {
+            line: line_number,
+            instruction,
+            target,
+        });
+    }
+
+    let instructions = pending
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut pending)| {
+            if let Some(target) = pending.target {
+                let pc = pc_base + 4 * index as u32;
+                let target_pc = labels.get(&target).ok_or_else(|| AsmError {
+                    line: pending.line,
+                    message: format!("undefined label `{}`", target),
+                })?;
+                let offset = target_pc.wrapping_sub(pc);
+                match pending.instruction.opcode {
+                    Opcode::JAL => pending.instruction.op_b = offset,
+                    _ => pending.instruction.op_c = offset,
+                }
+            }
+            Ok(pending.instruction)
+        })
+        .collect::<Result<Vec<_>, AsmError>>()?;
+    Ok(Program::new(instructions, pc_base, pc_base))
+}
+
+/// Parse a branch or jump target: a byte offset, or a label resolved once all are known.
+fn parse_target(operand: &str) -> Result<(u32, Option<String>), String> {
+    if is_immediate(operand) {
+        Ok((parse_immediate(operand)?, None))
+    } else if is_label(operand) {
+        Ok((0, Some(operand.to_string())))
+    } else {
+        Err(format!("invalid target `{}`", operand))
+    }
+}
+
+fn parse_instruction(
+    mnemonic: &str,
+    operands: &[&str],
+) -> Result<(Instruction, Option<String>), String> {
+    let expect = |count: usize| {
+        if operands.len() == count {
+            Ok(())
+        } else {
+            Err(format!(
+                "`{}` takes {} operands, found {}",
+                mnemonic,
+                count,
+                operands.len()
+            ))
+        }
+    };
+
+    if let Some(opcode) = alu_opcode(mnemonic) {
+        expect(3)?;
+        let (rd, rs1) = (parse_register(operands[0])?, parse_register(operands[1])?);
+        let instruction = if is_immediate(operands[2]) {
+            Instruction::new(opcode, rd, rs1, parse_immediate(operands[2])?, false, true)
+        } else if mnemonic.ends_with('i') {
+            return Err(format!("`{}` takes an immediate", mnemonic));
+        } else {
+            Instruction::new(opcode, rd, rs1, parse_register(operands[2])?, false, false)
+        };
+        return Ok((instruction, None));
+    }
+    if let Some(opcode) = load_opcode(mnemonic) {
+        expect(2)?;
+        let (base, offset) = parse_address(operands[1])?;
+        let rd = parse_register(operands[0])?;
+        return Ok((
+            Instruction::new(opcode, rd, base, offset, false, true),
+            None,
+        ));
+    }
+    if let Some(opcode) = store_opcode(mnemonic) {
+        expect(2)?;
+        let (base, offset) = parse_address(operands[1])?;
+        let rs2 = parse_register(operands[0])?;
+        return Ok((
+            Instruction::new(opcode, rs2, base, offset, false, true),
+            None,
+        ));
+    }
+    if let Some(opcode) = branch_opcode(mnemonic) {
+        expect(3)?;
+        let (rs1, rs2) = (parse_register(operands[0])?, parse_register(operands[1])?);
+        let (offset, target) = parse_target(operands[2])?;
+        return Ok((
+            Instruction::new(opcode, rs1, rs2, offset, false, true),
+            target,
+        ));
+    }
+
+    let instruction = match mnemonic {
+        "jal" | "j" | "call" => {
+            let (rd, target) = match (mnemonic, operands) {
+                ("jal", [rd, target]) => (parse_register(rd)?, target),
+                ("jal", [target]) | ("call", [target]) => (1, target),
+                ("j", [target]) => (0, target),
+                _ => return Err(format!("`{}` takes a target", mnemonic)),
+            };
+            let (offset, target) = parse_target(target)?;
+            return Ok((
+                Instruction::new(Opcode::JAL, rd, offset, 0, true, true),
+                target,
+            ));
+        }
+        "jalr" => {
+            let (rd, base, offset) = match operands {
+                [rs1] if !rs1.contains('(') => (1, parse_register(rs1)?, 0),
+                [rd, address] if address.contains('(') => {
+                    let (base, offset) = parse_address(address)?;
+                    (parse_register(rd)?, base, offset)
+                }
+                [rd, rs1, offset] => (
+                    parse_register(rd)?,
+                    parse_register(rs1)?,
+                    parse_immediate(offset)?,
+                ),
+                _ => return Err("`jalr` takes `rd, offset(rs1)`".to_string()),
+            };
+            Instruction::new(Opcode::JALR, rd, base, offset, false, true)
+        }
+        "ret" => {
+            expect(0)?;
+            Instruction::new(Opcode::JALR, 0, 1, 0, false, true)
+        }
+        "auipc" => {
+            expect(2)?;
+            let imm = parse_immediate(operands[1])? << 12;
+            Instruction::new(
+                Opcode::AUIPC,
+                parse_register(operands[0])?,
+                imm,
+                imm,
+                true,
+                true,
+            )
+        }
+        "li" => {
+            expect(2)?;
+            let imm = parse_immediate(operands[1])?;
+            Instruction::new(
+                Opcode::ADD,
+                parse_register(operands[0])?,
+                0,
+                imm,
+                false,
+                true,
+            )
+        }
+        "mv" => {
+            expect(2)?;
+            let (rd, rs) = (parse_register(operands[0])?, parse_register(operands[1])?);
+            Instruction::new(Opcode::ADD, rd, rs, 0, false, true)
+        }
+        "nop" => {
+            expect(0)?;
+            Instruction::new(Opcode::ADD, 0, 0, 0, false, true)
+        }
+        "ecall" => {
+            expect(0)?;
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true)
+        }
+        "ebreak" => {
+            expect(0)?;
+            Instruction::new(Opcode::EBREAK, 0, 0, 0, false, false)
+        }
+        "unimp" => {
+            expect(0)?;
+            Instruction::new(Opcode::UNIMP, 0, 0, 0, true, true)
+        }
+        _ => return Err(format!("unknown mnemonic `{}`", mnemonic)),
+    };
+    Ok((instruction, None))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn error(source: &str) -> AsmError {
+        assemble(source, 0).unwrap_err()
+    }
+
+    #[test]
+    fn test_assemble() {
+        let program = assemble(
+            "
+            # A comment.
+            start:  li   t0, 0x10
+                    addi a0, t0, -1     # immediate form
+                    sub  a1, a0, t0
+                    lw   a2, 8(sp)
+                    sb   a2, (a1)
+            loop:   bne  a0, zero, loop
+                    jal  ra, end
+                    jalr zero, 0(ra)
+                    ecall
+            end:    ret
+            ",
+            0x1000,
+        )
+        .unwrap();
+        assert_eq!(program.pc_base, 0x1000);
+        assert_eq!(program.pc_start, 0x1000);
+        let expected = [
+            Instruction::new(Opcode::ADD, 5, 0, 0x10, false, true),
+            Instruction::new(Opcode::ADD, 10, 5, -1i32 as u32, false, true),
+            Instruction::new(Opcode::SUB, 11, 10, 5, false, false),
+            Instruction::new(Opcode::LW, 12, 2, 8, false, true),
+            Instruction::new(Opcode::SB, 12, 11, 0, false, true),
+            Instruction::new(Opcode::BNE, 10, 0, 0, false, true),
+            Instruction::new(Opcode::JAL, 1, 12, 0, true, true),
+            Instruction::new(Opcode::JALR, 0, 1, 0, false, true),
+            Instruction::new(Opcode::ECALL, 10, 5, 0, false, true),
+            Instruction::new(Opcode::JALR, 0, 1, 0, false, true),
+        ];
+        assert_eq!(
+            format!("{:?}", program.instructions),
+            format!("{:?}", expected)
+        );
+        assert!(program.validate().is_ok());
+
+        // Backward targets wrap around, and registers can be named by index.
+        let program = assemble("x: nop\nbeq x1, x31, x\nj -8", 0).unwrap();
+        assert_eq!(program.instructions[1].op_c, -4i32 as u32);
+        assert_eq!(program.instructions[2].op_b, -8i32 as u32);
+        assert_eq!(program.instructions[1].op_b, 31);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert_eq!(
+            error("nop\n  frob a0, a1"),
+            AsmError {
+                line: 2,
+                message: "unknown mnemonic `frob` in `frob a0, a1`".to_string()
+            }
+        );
+        assert_eq!(error("\n\nadd a0, a1").line, 3);
+        assert_eq!(
+            error("add a0, x32, 1").message,
+            "invalid register `x32` in `add a0, x32, 1`"
+        );
+        assert_eq!(error("li a0, 0x1_0000_0000").line, 1);
+        assert_eq!(error("li a0, 12abc").line, 1);
+        assert_eq!(error("addi a0, a0, a1").line, 1);
+        assert_eq!(error("lw a0, 4").line, 1);
+        assert_eq!(error("a:\nb: a: nop").message, "duplicate label `a`");
+        assert_eq!(error("1x: nop").message, "invalid label `1x`");
+        assert_eq!(
+            error("nop\nbeq a0, a1, missing\nnop").to_string(),
+            "line 2: undefined label `missing`"
+        );
+    }
+}
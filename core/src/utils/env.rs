@@ -1,10 +1,22 @@
+/// A source of the settings in this module: the process environment, or the values passed to
+/// [`RuntimeOptions::from_vars`](crate::runtime::RuntimeOptions::from_vars).
+pub(crate) type Vars<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+pub(crate) fn process_var(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+}
+
 /// Gets the number of rows which by default should be used for each chip to maximize padding.
 ///
 /// Some chips, such as FieldLTU, may use a constant multiple of this value to optimize performance.
 pub fn shard_size() -> usize {
-    let value = match std::env::var("SHARD_SIZE") {
-        Ok(val) => val.parse().unwrap(),
-        Err(_) => 1 << 19,
+    shard_size_from(&process_var)
+}
+
+pub(crate) fn shard_size_from(vars: Vars) -> usize {
+    let value = match vars("SHARD_SIZE") {
+        Some(val) => val.parse().unwrap(),
+        None => 1 << 19,
     };
     assert!(value != 0 && (value & (value - 1)) == 0);
     value
@@ -12,16 +24,49 @@ pub fn shard_size() -> usize {
 
 /// Gets the number of shards after which we should save the shard commits to disk.
 pub fn save_disk_threshold() -> usize {
-    match std::env::var("SAVE_DISK_THRESHOLD") {
-        Ok(val) => val.parse().unwrap(),
-        Err(_) => 256,
+    match process_var("SAVE_DISK_THRESHOLD") {
+        Some(val) => val.parse().unwrap(),
+        None => 256,
     }
 }
 
 /// Gets the flag for whether to recreate the shard commitments instead of saving them to disk.
 pub fn reconstruct_commitments() -> bool {
-    match std::env::var("RECONSTRUCT_COMMITMENTS") {
-        Ok(val) => val == "true",
-        Err(_) => true,
+    match process_var("RECONSTRUCT_COMMITMENTS") {
+        Some(val) => val == "true",
+        None => true,
+    }
+}
+
+/// Gets the file the pc trace of every execution should be written to, if any.
+pub fn trace_file() -> Option<String> {
+    trace_file_from(&process_var)
+}
+
+pub(crate) fn trace_file_from(vars: Vars) -> Option<String> {
+    vars("TRACE_FILE")
+}
+
+/// Gets the flag for whether every CPU event should be checked as it is emitted.
+pub fn validate_events() -> bool {
+    validate_events_from(&process_var)
+}
+
+pub(crate) fn validate_events_from(vars: Vars) -> bool {
+    match vars("VALIDATE_EVENTS") {
+        Some(val) => val == "true",
+        None => false,
+    }
+}
+
+/// Gets the flag for whether the final state of every execution should be checked.
+pub fn check_final_invariants() -> bool {
+    check_final_invariants_from(&process_var)
+}
+
+pub(crate) fn check_final_invariants_from(vars: Vars) -> bool {
+    match vars("CHECK_FINAL_INVARIANTS") {
+        Some(val) => val == "true",
+        None => false,
     }
 }
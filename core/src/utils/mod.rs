@@ -1,3 +1,5 @@
+#[cfg(any(test, feature = "test-utils"))]
+pub mod asm;
 mod buffer;
 pub mod ec;
 pub mod env;
@@ -5,6 +7,9 @@ mod logger;
 mod poseidon2_instance;
 mod programs;
 mod prove;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod riscv_tests;
+pub mod serialization;
 mod tracer;
 
 pub use buffer::*;
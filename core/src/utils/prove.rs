@@ -158,6 +158,7 @@ where
 
 pub use baby_bear_keccak::BabyBearKeccak;
 pub use baby_bear_poseidon2::BabyBearPoseidon2;
+pub(crate) use baby_bear_poseidon2::{poseidon2_perm, Perm as Poseidon2Perm};
 use p3_air::Air;
 use p3_matrix::dense::RowMajorMatrix;
 use p3_uni_stark::Proof;
@@ -234,9 +235,14 @@ pub(super) mod baby_bear_poseidon2 {
         }
     }
 
+    /// The Poseidon2 permutation the config hashes and compresses with.
+    pub fn poseidon2_perm() -> Perm {
+        Perm::new(8, 22, RC_16_30.to_vec(), DiffusionMatrixBabybear)
+    }
+
     impl BabyBearPoseidon2 {
         pub fn new() -> Self {
-            let perm = Perm::new(8, 22, RC_16_30.to_vec(), DiffusionMatrixBabybear);
+            let perm = poseidon2_perm();
 
             let hash = MyHash::new(perm.clone());
 
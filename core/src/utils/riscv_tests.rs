@@ -0,0 +1,312 @@
+//! A harness running the ISA tests of [riscv-tests](https://github.com/riscv-software-src/riscv-tests)
+//! against the runtime.
+//!
+//! Each test binary runs numbered test cases one after the other and reports the outcome by
+//! storing a word to its `tohost` symbol: `1` if every case passed, and `(case << 1) | 1` for the
+//! first case that failed. The binaries must be built with a test environment that stores to
+//! `tohost` directly instead of trapping with `ecall`, and without machine-mode CSR accesses,
+//! which the runtime does not support.
+//!
+//! The suites are only run by the ignored tests of this module, from the binaries in the
+//! directory named by `RISCV_TESTS_DIR`:
+//!
+//! ```text
+//! RISCV_TESTS_DIR=/path/to/isa cargo test -p sp1-core --features test-utils -- --ignored riscv_tests
+//! ```
+use core::fmt::{Display, Formatter};
+use std::path::Path;
+
+use crate::disassembler::Elf;
+use crate::runtime::{
+    Comparison, Condition, Error, ExecutionError, Operand, Program, Runtime, StopReason,
+};
+
+/// The name of the symbol the tests report their outcome to.
+pub const TOHOST: &str = "tohost";
+
+/// The number of cycles after which a test is considered stuck.
+pub const DEFAULT_MAX_CYCLES: u64 = 1 << 20;
+
+/// The tests of the base integer instruction set, as named in `rv32ui-p-<test>`.
+pub const RV32UI: &[&str] = &[
+    "add", "addi", "and", "andi", "auipc", "beq", "bge", "bgeu", "blt", "bltu", "bne", "fence_i",
+    "jal", "jalr", "lb", "lbu", "lh", "lhu", "lui", "lw", "ma_data", "or", "ori", "sb", "sh",
+    "simple", "sll", "slli", "slt", "slti", "sltiu", "sltu", "sra", "srai", "srl", "srli", "sub",
+    "sw", "xor", "xori",
+];
+
+/// The tests of the multiplication and division extension, as named in `rv32um-p-<test>`.
+pub const RV32UM: &[&str] = &[
+    "div", "divu", "mul", "mulh", "mulhsu", "mulhu", "rem", "remu",
+];
+
+/// The tests expected to fail, with the reason.
+pub const KNOWN_FAILURES: &[(&str, &str)] = &[
+    (
+        "rv32ui-p-fence_i",
+        "the program is immutable, so self-modifying code and FENCE.I are not supported",
+    ),
+    (
+        "rv32ui-p-ma_data",
+        "misaligned loads and stores are rejected instead of being emulated",
+    ),
+];
+
+/// How a riscv-tests binary finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RiscvTestOutcome {
+    /// Every test case passed, after `cycles` cycles.
+    Passed { cycles: u64 },
+
+    /// The test case `case` failed, reported by the store at `pc`.
+    Failed { case: u32, pc: u32 },
+
+    /// The store at `pc` wrote a value to `tohost` outside of the protocol.
+    InvalidTohost { value: u32, pc: u32 },
+
+    /// The program finished at `pc` without writing to `tohost`.
+    Halted { pc: u32 },
+
+    /// The guest faulted.
+    Fault(ExecutionError),
+
+    /// The program was still running at `pc` after the maximum number of cycles.
+    Timeout { pc: u32 },
+}
+
+impl RiscvTestOutcome {
+    pub fn is_pass(&self) -> bool {
+        matches!(self, RiscvTestOutcome::Passed { .. })
+    }
+}
+
+impl Display for RiscvTestOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RiscvTestOutcome::Passed { cycles } => write!(f, "passed in {} cycles", cycles),
+            RiscvTestOutcome::Failed { case, pc } => {
+                write!(f, "test case {} failed at pc=0x{:08x}", case, pc)
+            }
+            RiscvTestOutcome::InvalidTohost { value, pc } => write!(
+                f,
+                "invalid value 0x{:x} written to {} at pc=0x{:08x}",
+                value, TOHOST, pc
+            ),
+            RiscvTestOutcome::Halted { pc } => {
+                write!(f, "halted at pc=0x{:08x} without writing to {}", pc, TOHOST)
+            }
+            RiscvTestOutcome::Fault(err) => write!(f, "{}", err),
+            RiscvTestOutcome::Timeout { pc } => write!(f, "timed out at pc=0x{:08x}", pc),
+        }
+    }
+}
+
+/// An error loading a riscv-tests binary.
+#[derive(Debug)]
+pub enum RiscvTestError {
+    Load(Error),
+
+    /// The binary has no `tohost` symbol to report its outcome to.
+    MissingTohost,
+}
+
+impl Display for RiscvTestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RiscvTestError::Load(err) => write!(f, "{}", err),
+            RiscvTestError::MissingTohost => write!(f, "no `{}` symbol", TOHOST),
+        }
+    }
+}
+
+impl std::error::Error for RiscvTestError {}
+
+impl From<Error> for RiscvTestError {
+    fn from(err: Error) -> Self {
+        RiscvTestError::Load(err)
+    }
+}
+
+/// A riscv-tests binary, ready to run.
+#[derive(Debug, Clone)]
+pub struct RiscvTest {
+    pub program: Program,
+
+    /// The address of the `tohost` word.
+    pub tohost: u32,
+}
+
+impl RiscvTest {
+    pub fn new(program: Program, tohost: u32) -> Self {
+        Self { program, tohost }
+    }
+
+    /// Load a test from its ELF, locating `tohost` in its symbol table.
+    pub fn from_elf(input: &[u8]) -> Result<Self, RiscvTestError> {
+        let elf = Elf::try_decode(input).map_err(Error::from)?;
+        let tohost = elf
+            .objects
+            .get(TOHOST)
+            .ok_or(RiscvTestError::MissingTohost)?
+            .addr;
+        let program = Program::try_from_elf(input).map_err(Error::from)?;
+        Ok(Self::new(program, tohost))
+    }
+
+    /// Run the test until it writes to `tohost`, for at most `max_cycles` cycles.
+    pub fn run(&self, max_cycles: u64) -> RiscvTestOutcome {
+        let mut runtime = Runtime::new(self.program.clone());
+        runtime.emit_events = false;
+        runtime.add_condition(Condition::Compare {
+            comparison: Comparison::Ne,
+            left: Operand::Word(self.tohost),
+            right: Operand::Constant(0),
+        });
+        runtime.initialize();
+        loop {
+            if runtime.is_done() {
+                return RiscvTestOutcome::Halted {
+                    pc: runtime.state.pc,
+                };
+            }
+            if runtime.state.global_clk as u64 >= max_cycles {
+                return RiscvTestOutcome::Timeout {
+                    pc: runtime.state.pc,
+                };
+            }
+            match runtime.step() {
+                Ok(()) => {}
+                Err(ExecutionError::Stopped(StopReason::ConditionMet { clk, pc, .. })) => {
+                    return match runtime.word(self.tohost) {
+                        1 => RiscvTestOutcome::Passed {
+                            cycles: clk as u64 + 1,
+                        },
+                        value if value & 1 == 1 => RiscvTestOutcome::Failed {
+                            case: value >> 1,
+                            pc,
+                        },
+                        value => RiscvTestOutcome::InvalidTohost { value, pc },
+                    };
+                }
+                Err(err) => return RiscvTestOutcome::Fault(err),
+            }
+        }
+    }
+}
+
+/// Run the tests of `suite` in `dir`, named `<suite>-p-<test>`, returning the outcome of each
+/// test that did not go as expected: a failure not in [`KNOWN_FAILURES`], or a known failure
+/// that passed.
+pub fn run_suite(
+    dir: &Path,
+    suite: &str,
+    tests: &[&str],
+    max_cycles: u64,
+) -> Vec<(String, Result<RiscvTestOutcome, RiscvTestError>)> {
+    let mut unexpected = Vec::new();
+    for test in tests {
+        let name = format!("{}-p-{}", suite, test);
+        let path = dir.join(&name);
+        let outcome = std::fs::read(&path)
+            .map_err(|error| {
+                RiscvTestError::Load(Error::Io {
+                    path: path.display().to_string(),
+                    error,
+                })
+            })
+            .and_then(|bytes| RiscvTest::from_elf(&bytes))
+            .map(|test| test.run(max_cycles));
+        let passed = matches!(&outcome, Ok(outcome) if outcome.is_pass());
+        let known = KNOWN_FAILURES.iter().any(|(known, _)| *known == name);
+        if passed == known {
+            unexpected.push((name, outcome));
+        }
+    }
+    unexpected
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::utils::asm::assemble;
+
+    const TOHOST_ADDR: u32 = 0x2000;
+
+    fn outcome(source: &str, max_cycles: u64) -> RiscvTestOutcome {
+        RiscvTest::new(assemble(source, 0).unwrap(), TOHOST_ADDR).run(max_cycles)
+    }
+
+    /// Store `value` to `tohost` as the test environment does, then spin.
+    fn report(value: u32) -> String {
+        format!(
+            "        li   gp, {}
+                     sw   gp, 0x2000(zero)
+             spin:   j    spin",
+            value
+        )
+    }
+
+    #[test]
+    fn test_tohost_protocol() {
+        assert_eq!(
+            outcome(&report(1), 100),
+            RiscvTestOutcome::Passed { cycles: 2 }
+        );
+        assert_eq!(
+            outcome(&report((3 << 1) | 1), 100),
+            RiscvTestOutcome::Failed { case: 3, pc: 4 }
+        );
+        assert_eq!(
+            outcome(&report(2), 100),
+            RiscvTestOutcome::InvalidTohost { value: 2, pc: 4 }
+        );
+    }
+
+    #[test]
+    fn test_out_of_protocol_termination() {
+        assert_eq!(outcome("li gp, 1", 100), RiscvTestOutcome::Halted { pc: 4 });
+        assert_eq!(
+            outcome("spin: j spin", 100),
+            RiscvTestOutcome::Timeout { pc: 0 }
+        );
+        assert_eq!(
+            outcome("nop\nunimp", 100),
+            RiscvTestOutcome::Fault(ExecutionError::Unimplemented { pc: 4 })
+        );
+        assert_eq!(
+            RiscvTestOutcome::Failed { case: 3, pc: 4 }.to_string(),
+            "test case 3 failed at pc=0x00000004"
+        );
+    }
+
+    fn run_official(suite: &str, tests: &[&str]) {
+        let dir = std::env::var("RISCV_TESTS_DIR")
+            .expect("RISCV_TESTS_DIR must name the directory of the riscv-tests binaries");
+        let unexpected = run_suite(Path::new(&dir), suite, tests, DEFAULT_MAX_CYCLES);
+        let messages = unexpected
+            .iter()
+            .map(|(name, outcome)| match outcome {
+                Ok(outcome) => format!("{}: {}", name, outcome),
+                Err(err) => format!("{}: {}", name, err),
+            })
+            .collect::<Vec<_>>();
+        assert!(
+            unexpected.is_empty(),
+            "{} unexpected outcomes:\n{}",
+            unexpected.len(),
+            messages.join("\n")
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rv32ui() {
+        run_official("rv32ui", RV32UI);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rv32um() {
+        run_official("rv32um", RV32UM);
+    }
+}
@@ -0,0 +1,251 @@
+//! Helpers for deriving `Serialize` and `Deserialize` on types serde has no impls for, to be used
+//! with `#[serde(with = "...")]`.
+
+/// Arrays of any length. Serde only implements its traits for arrays of up to 32 elements.
+pub mod array {
+    use core::fmt::Formatter;
+    use core::marker::PhantomData;
+
+    use serde::de::{Error, SeqAccess, Visitor};
+    use serde::ser::SerializeTuple;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, T, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for element in array.iter() {
+            tuple.serialize_element(element)?;
+        }
+        tuple.end()
+    }
+
+    struct ArrayVisitor<T, const N: usize>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>, const N: usize> Visitor<'de> for ArrayVisitor<T, N> {
+        type Value = [T; N];
+
+        fn expecting(&self, formatter: &mut Formatter) -> core::fmt::Result {
+            write!(formatter, "an array of length {}", N)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut elements = Vec::with_capacity(N);
+            for i in 0..N {
+                let element = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(i, &self))?;
+                elements.push(element);
+            }
+            Ok(elements
+                .try_into()
+                .unwrap_or_else(|_| unreachable!("exactly N elements were read")))
+        }
+    }
+
+    pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        deserializer.deserialize_tuple(N, ArrayVisitor::<T, N>(PhantomData))
+    }
+}
+
+/// `hashbrown` maps with any hasher. Entries are written in key order, so equal maps always
+/// serialize to the same bytes.
+pub mod hash_map {
+    use core::hash::{BuildHasher, Hash};
+
+    use hashbrown::HashMap;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, K, V, H>(map: &HashMap<K, V, H>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        K: Serialize + Ord,
+        V: Serialize,
+    {
+        let mut entries = map.iter().collect::<Vec<_>>();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        serializer.collect_seq(entries)
+    }
+
+    pub fn deserialize<'de, D, K, V, H>(deserializer: D) -> Result<HashMap<K, V, H>, D::Error>
+    where
+        D: Deserializer<'de>,
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        H: BuildHasher + Default,
+    {
+        let entries = Vec::<(K, V)>::deserialize(deserializer)?;
+        let mut map = HashMap::with_capacity_and_hasher(entries.len(), H::default());
+        map.extend(entries);
+        Ok(map)
+    }
+}
+
+/// `BTreeMap`s of `hashbrown` maps, such as maps from shards to per-shard maps. The inner maps are
+/// written in key order like [`hash_map`].
+pub mod nested_hash_map {
+    use core::hash::{BuildHasher, Hash};
+    use std::collections::BTreeMap;
+
+    use hashbrown::HashMap;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, K1, K2, V, H>(
+        map: &BTreeMap<K1, HashMap<K2, V, H>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        K1: Serialize,
+        K2: Serialize + Ord,
+        V: Serialize,
+    {
+        serializer.collect_seq(map.iter().map(|(key, inner)| {
+            let mut entries = inner.iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            (key, entries)
+        }))
+    }
+
+    pub fn deserialize<'de, D, K1, K2, V, H>(
+        deserializer: D,
+    ) -> Result<BTreeMap<K1, HashMap<K2, V, H>>, D::Error>
+    where
+        D: Deserializer<'de>,
+        K1: Deserialize<'de> + Ord,
+        K2: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        H: BuildHasher + Default,
+    {
+        let entries = Vec::<(K1, Vec<(K2, V)>)>::deserialize(deserializer)?;
+        Ok(entries
+            .into_iter()
+            .map(|(key, inner)| {
+                let mut map = HashMap::with_capacity_and_hasher(inner.len(), H::default());
+                map.extend(inner);
+                (key, map)
+            })
+            .collect())
+    }
+}
+
+/// Values behind an `Arc`, without enabling serde's `rc` feature. Shared values are written out
+/// once per reference.
+pub mod arc {
+    use std::sync::Arc;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, T>(value: &Arc<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        value.as_ref().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Arc<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        T::deserialize(deserializer).map(Arc::new)
+    }
+}
+
+/// CPU events, with the shard, clock and pc of each event written as the difference with the
+/// previous event: a shard change, a clock increment restarting from zero in every shard and a
+/// jump from the next instruction. These are mostly zero, which a varint encoding writes in one
+/// byte each.
+pub mod cpu_events {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::cpu::{CpuEvent, MemoryRecordEnum};
+    use crate::runtime::{EventArena, Instruction};
+
+    /// An event without its shard, clock and pc, followed by its operands and accesses.
+    type Deltas<I, R> = (u32, i32, i32, I, u32, R, u32, R, u32, R, Option<u32>, R);
+
+    pub fn serialize<S>(events: &EventArena<CpuEvent>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (mut shard, mut clk, mut pc) = (0u32, 0u32, 0u32);
+        serializer.collect_seq(events.iter().map(|event| {
+            if event.shard != shard {
+                clk = 0;
+            }
+            let deltas: Deltas<&Instruction, &Option<MemoryRecordEnum>> = (
+                event.shard.wrapping_sub(shard),
+                event.clk.wrapping_sub(clk) as i32,
+                event.pc.wrapping_sub(pc.wrapping_add(4)) as i32,
+                &event.instruction,
+                event.a,
+                &event.a_record,
+                event.b,
+                &event.b_record,
+                event.c,
+                &event.c_record,
+                event.memory,
+                &event.memory_record,
+            );
+            (shard, clk, pc) = (event.shard, event.clk, event.pc);
+            deltas
+        }))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<EventArena<CpuEvent>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let deltas =
+            Vec::<Deltas<Instruction, Option<MemoryRecordEnum>>>::deserialize(deserializer)?;
+        let (mut shard, mut clk, mut pc) = (0u32, 0u32, 0u32);
+        Ok(deltas
+            .into_iter()
+            .map(
+                |(
+                    shard_delta,
+                    clk_delta,
+                    pc_delta,
+                    instruction,
+                    a,
+                    a_record,
+                    b,
+                    b_record,
+                    c,
+                    c_record,
+                    memory,
+                    memory_record,
+                )| {
+                    if shard_delta != 0 {
+                        clk = 0;
+                    }
+                    shard = shard.wrapping_add(shard_delta);
+                    clk = clk.wrapping_add(clk_delta as u32);
+                    pc = pc.wrapping_add(4).wrapping_add(pc_delta as u32);
+                    CpuEvent {
+                        shard,
+                        clk,
+                        pc,
+                        instruction,
+                        a,
+                        a_record,
+                        b,
+                        b_record,
+                        c,
+                        c_record,
+                        memory,
+                        memory_record,
+                    }
+                },
+            )
+            .collect())
+    }
+}
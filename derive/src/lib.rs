@@ -36,21 +36,40 @@ pub fn aligned_borrow_derive(input: TokenStream) -> TokenStream {
 
     // Get struct name from ast
     let name = &ast.ident;
+
+    // Besides the element type `T`, the struct may have const generic parameters, such as a length.
+    let const_params = ast
+        .generics
+        .const_params()
+        .map(|param| {
+            let ident = &param.ident;
+            let ty = &param.ty;
+            quote! { const #ident: #ty }
+        })
+        .collect::<Vec<_>>();
+    let const_idents = ast
+        .generics
+        .const_params()
+        .map(|param| &param.ident)
+        .collect::<Vec<_>>();
+
     let methods = quote! {
-        impl<T> Borrow<#name<T>> for [T] {
-            fn borrow(&self) -> &#name<T> {
-                debug_assert_eq!(self.len(), size_of::<#name<u8>>());
-                let (prefix, shorts, _suffix) = unsafe { self.align_to::<#name<T>>() };
+        impl<T #(, #const_params)*> Borrow<#name<T #(, #const_idents)*>> for [T] {
+            fn borrow(&self) -> &#name<T #(, #const_idents)*> {
+                debug_assert_eq!(self.len(), size_of::<#name<u8 #(, #const_idents)*>>());
+                let (prefix, shorts, _suffix) =
+                    unsafe { self.align_to::<#name<T #(, #const_idents)*>>() };
                 debug_assert!(prefix.is_empty(), "Alignment should match");
                 debug_assert_eq!(shorts.len(), 1);
                 &shorts[0]
             }
         }
 
-        impl<T> BorrowMut<#name<T>> for [T] {
-            fn borrow_mut(&mut self) -> &mut #name<T> {
-                debug_assert_eq!(self.len(), size_of::<#name<u8>>());
-                let (prefix, shorts, _suffix) = unsafe { self.align_to_mut::<#name<T>>() };
+        impl<T #(, #const_params)*> BorrowMut<#name<T #(, #const_idents)*>> for [T] {
+            fn borrow_mut(&mut self) -> &mut #name<T #(, #const_idents)*> {
+                debug_assert_eq!(self.len(), size_of::<#name<u8 #(, #const_idents)*>>());
+                let (prefix, shorts, _suffix) =
+                    unsafe { self.align_to_mut::<#name<T #(, #const_idents)*>>() };
                 debug_assert!(prefix.is_empty(), "Alignment should match");
                 debug_assert_eq!(shorts.len(), 1);
                 &mut shorts[0]
@@ -0,0 +1,35 @@
+use sp1_core::runtime::{simulate, Program, RuntimeOptions};
+use sp1_core::{utils, SP1Stdin};
+
+/// The ELF we want to execute inside the zkVM.
+const ELF: &[u8] = include_bytes!("../../program/elf/riscv32im-succinct-zkvm-elf");
+
+fn main() {
+    // Setup a tracer for logging.
+    utils::setup_tracer();
+
+    // Create an input stream and write '5000' to it.
+    let mut stdin = SP1Stdin::new();
+    stdin.write(&5000u32);
+
+    // Execute the program without proving it, keeping only the words of memory it changed.
+    let result = simulate(
+        Program::from(ELF),
+        &stdin.buffer.data,
+        RuntimeOptions::default(),
+    )
+    .expect("creating the runtime failed");
+    if let Some(error) = &result.error {
+        println!("execution failed: {}", error);
+    }
+
+    println!("cycles: {}", result.cycles);
+    println!("output: {} bytes", result.output.len());
+    println!("changed words: {}", result.diff.len());
+    for word in result.diff.iter().take(10) {
+        println!(
+            "  0x{:08x}: 0x{:08x} -> 0x{:08x}",
+            word.addr, word.old, word.new
+        );
+    }
+}
@@ -2,6 +2,7 @@
 use core::arch::asm;
 
 /// Halts the program.
+#[no_mangle]
 pub extern "C" fn syscall_halt() -> ! {
     #[cfg(target_os = "zkvm")]
     unsafe {
@@ -15,3 +16,51 @@ pub extern "C" fn syscall_halt() -> ! {
     #[cfg(not(target_os = "zkvm"))]
     unreachable!()
 }
+
+/// Halts the program after a panic with the given message.
+#[allow(unused_variables)]
+#[no_mangle]
+pub extern "C" fn syscall_panic(msg_ptr: *const u8, len: usize) -> ! {
+    #[cfg(target_os = "zkvm")]
+    unsafe {
+        asm!(
+            "ecall",
+            in("t0") crate::syscalls::PANIC,
+            in("a0") msg_ptr,
+            in("a1") len,
+        );
+        unreachable!()
+    }
+
+    #[cfg(not(target_os = "zkvm"))]
+    unreachable!()
+}
+
+/// Halts the program after a failed assertion, with a code, a message and the expected and actual
+/// values as 32 bytes each. Any of the pointers may be null.
+#[allow(unused_variables)]
+#[no_mangle]
+pub extern "C" fn syscall_assert_failed(
+    code: u32,
+    msg_ptr: *const u8,
+    len: usize,
+    expected: *const [u8; 32],
+    actual: *const [u8; 32],
+) -> ! {
+    #[cfg(target_os = "zkvm")]
+    unsafe {
+        asm!(
+            "ecall",
+            in("t0") crate::syscalls::ASSERT_FAILED,
+            in("a0") code,
+            in("a1") msg_ptr,
+            in("a2") len,
+            in("a3") expected,
+            in("a4") actual,
+        );
+        unreachable!()
+    }
+
+    #[cfg(not(target_os = "zkvm"))]
+    unreachable!()
+}
@@ -55,6 +55,42 @@ pub extern "C" fn syscall_read(fd: u32, read_buf: *mut u8, nbytes: usize) {
     unreachable!()
 }
 
+/// Asks the runtime to buffer the next `len` bytes of input ahead of the reads, while execution
+/// continues. A hint only: the bytes read are the same with or without it.
+#[allow(unused_variables)]
+#[no_mangle]
+pub extern "C" fn syscall_prefetch_input(len: usize) {
+    #[cfg(target_os = "zkvm")]
+    unsafe {
+        asm!(
+            "ecall",
+            in("t0") crate::syscalls::PREFETCH_INPUT,
+            in("a0") len,
+        );
+    }
+
+    #[cfg(not(target_os = "zkvm"))]
+    unreachable!()
+}
+
+/// Checks that the next frame of the input stream has the given tag, when the host set an input
+/// schema. Execution stops with an error on a mismatch.
+#[allow(unused_variables)]
+#[no_mangle]
+pub extern "C" fn syscall_read_frame(tag: u32) {
+    #[cfg(target_os = "zkvm")]
+    unsafe {
+        asm!(
+            "ecall",
+            in("t0") crate::syscalls::READ_FRAME,
+            in("a0") tag,
+        );
+    }
+
+    #[cfg(not(target_os = "zkvm"))]
+    unreachable!()
+}
+
 /// Write data to the prover.
 #[allow(unused_variables)]
 #[no_mangle]
@@ -73,3 +109,22 @@ pub extern "C" fn syscall_write(fd: u32, write_buf: *const u8, nbytes: usize) {
     #[cfg(not(target_os = "zkvm"))]
     unreachable!()
 }
+
+/// Write data to one of the logical output channels. Channel 0 is the same stream as `FD_IO`.
+#[allow(unused_variables)]
+#[no_mangle]
+pub extern "C" fn syscall_write_channel(channel: u32, write_buf: *const u8, nbytes: usize) {
+    #[cfg(target_os = "zkvm")]
+    unsafe {
+        asm!(
+            "ecall",
+            in("t0") crate::syscalls::WRITE_CHANNEL,
+            in("a0") channel,
+            in("a1") write_buf,
+            in("a2") nbytes,
+        );
+    }
+
+    #[cfg(not(target_os = "zkvm"))]
+    unreachable!()
+}
@@ -0,0 +1,47 @@
+#[cfg(target_os = "zkvm")]
+use core::arch::asm;
+
+/// Loads the little-endian u64 at `addr`, which must be 8-aligned, in a single syscall instead of
+/// two word loads. Returns the low word in a0 and the high word in a1, or `MEM64_MISALIGNED` in
+/// both if `addr` is not aligned.
+#[allow(unused_variables)]
+#[no_mangle]
+pub extern "C" fn syscall_load64(addr: *const u64) -> u64 {
+    #[cfg(target_os = "zkvm")]
+    unsafe {
+        let lo: u32;
+        let hi: u32;
+        asm!(
+            "ecall",
+            in("t0") crate::syscalls::LOAD64,
+            inout("a0") addr => lo,
+            out("a1") hi,
+        );
+        (hi as u64) << 32 | lo as u64
+    }
+
+    #[cfg(not(target_os = "zkvm"))]
+    unreachable!()
+}
+
+/// Stores `value` little-endian at `addr`, which must be 8-aligned, in a single syscall instead of
+/// two word stores. Returns 0, or `MEM64_MISALIGNED` if `addr` is not aligned.
+#[allow(unused_variables)]
+#[no_mangle]
+pub extern "C" fn syscall_store64(addr: *mut u64, value: u64) -> u32 {
+    #[cfg(target_os = "zkvm")]
+    unsafe {
+        let status: u32;
+        asm!(
+            "ecall",
+            in("t0") crate::syscalls::STORE64,
+            inout("a0") value as u32 => status,
+            in("a1") (value >> 32) as u32,
+            in("a2") addr,
+        );
+        status
+    }
+
+    #[cfg(not(target_os = "zkvm"))]
+    unreachable!()
+}
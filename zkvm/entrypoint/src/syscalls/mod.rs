@@ -3,10 +3,12 @@ mod ed25519;
 mod halt;
 mod io;
 mod keccak_permute;
+mod mem64;
 mod memory;
 mod secp256k1;
 mod sha_compress;
 mod sha_extend;
+mod shard;
 mod sys;
 mod unconstrained;
 
@@ -14,10 +16,12 @@ pub use ed25519::*;
 pub use halt::*;
 pub use io::*;
 pub use keccak_permute::*;
+pub use mem64::*;
 pub use memory::*;
 pub use secp256k1::*;
 pub use sha_compress::*;
 pub use sha_extend::*;
+pub use shard::*;
 pub use sys::*;
 pub use unconstrained::*;
 
@@ -60,5 +64,50 @@ pub const EXIT_UNCONSTRAINED: u32 = 111;
 /// Executes `BLAKE3_COMPRESS_INNER`.
 pub const BLAKE3_COMPRESS_INNER: u32 = 112;
 
+/// Writes to one of the logical output channels.
+pub const WRITE_CHANNEL: u32 = 113;
+
+/// Stages a slice to be appended to the input stream when the unconstrained block exits.
+pub const HINT_SLICE: u32 = 114;
+
+/// Halts the program after a panic, reporting the panic message and a backtrace.
+pub const PANIC: u32 = 115;
+
+/// Checks the type of the next frame of the input stream.
+pub const READ_FRAME: u32 = 116;
+
+/// Asks the runtime to start a new shard.
+pub const SHARD_HINT: u32 = 117;
+
+/// Halts the program after a failed assertion, reporting its message and values.
+pub const ASSERT_FAILED: u32 = 118;
+
+/// Loads a little-endian u64 from two consecutive words.
+pub const LOAD64: u32 = 119;
+
+/// Stores a little-endian u64 to two consecutive words.
+pub const STORE64: u32 = 120;
+
+/// Asks the runtime to buffer the next bytes of input.
+pub const PREFETCH_INPUT: u32 = 121;
+
+/// Returned by `LOAD64` and `STORE64` for an address that is not 8-aligned.
+pub const MEM64_MISALIGNED: u32 = 0xffff_fffe;
+
+/// Returned by `WRITE` to stdout or stderr for a string that is not valid UTF-8.
+pub const ERR_INVALID_UTF8: u32 = 0xffff_fff1;
+
+/// Returned by `WRITE` to stdout or stderr for a string longer than the runtime accepts.
+pub const ERR_LENGTH_TOO_LARGE: u32 = 0xffff_fff2;
+
+/// Returned by `WRITE` to stdout or stderr for a string past the end of the address space.
+pub const ERR_ADDRESS_WRAP: u32 = 0xffff_fff3;
+
+/// Returned by `WRITE` to stdout or stderr for a string starting in the registers.
+pub const ERR_REGISTER_RANGE: u32 = 0xffff_fff4;
+
+/// Returned in a0 and a1 by `LWA` once the input is exhausted, when the host reads it lazily.
+pub const LWA_INPUT_EOF: u32 = 0xffff_fff5;
+
 /// Writes to a file descriptor. Currently only used for `STDOUT/STDERR`.
 pub const WRITE: u32 = 999;
@@ -0,0 +1,15 @@
+#[cfg(target_os = "zkvm")]
+use core::arch::asm;
+
+/// Asks the runtime to start a new shard once this syscall returns. Ignored in unconstrained
+/// blocks.
+#[no_mangle]
+pub fn syscall_shard_hint() {
+    #[cfg(target_os = "zkvm")]
+    unsafe {
+        asm!(
+            "ecall",
+            in("t0") crate::syscalls::SHARD_HINT,
+        );
+    }
+}
@@ -1,10 +1,46 @@
-use crate::syscalls::{syscall_halt, syscall_write};
+use crate::syscalls::{
+    syscall_assert_failed, syscall_load64, syscall_panic, syscall_prefetch_input,
+    syscall_shard_hint, syscall_store64, syscall_write,
+};
 
 #[allow(clippy::missing_safety_doc)]
 #[no_mangle]
 pub unsafe extern "C" fn sys_panic(msg_ptr: *const u8, len: usize) -> ! {
     sys_write(2, msg_ptr, len);
-    syscall_halt();
+    syscall_panic(msg_ptr, len);
+}
+
+/// Halts the program after a failed assertion, which the host receives with the message and the
+/// expected and actual values. Pass null pointers to omit any of them.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn sys_assert_failed(
+    code: u32,
+    msg_ptr: *const u8,
+    len: usize,
+    expected: *const [u8; 32],
+    actual: *const [u8; 32],
+) -> ! {
+    if !msg_ptr.is_null() {
+        sys_write(2, msg_ptr, len);
+    }
+    syscall_assert_failed(code, msg_ptr, len, expected, actual);
+}
+
+/// Loads the u64 at `addr` with a single syscall. Panics if `addr` is not 8-aligned.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn sys_load64(addr: *const u64) -> u64 {
+    assert!(addr as usize % 8 == 0, "sys_load64: misaligned address");
+    syscall_load64(addr)
+}
+
+/// Stores `value` at `addr` with a single syscall. Panics if `addr` is not 8-aligned.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn sys_store64(addr: *mut u64, value: u64) {
+    assert!(addr as usize % 8 == 0, "sys_store64: misaligned address");
+    syscall_store64(addr, value);
 }
 
 #[allow(unused_variables)]
@@ -31,3 +67,17 @@ pub fn sys_write(fd: u32, write_buf: *const u8, nbytes: usize) {
         syscall_write(fd, write_buf, nbytes);
     }
 }
+
+/// Asks the runtime to start a new shard at the next opportunity, e.g. between two phases of the
+/// program where little memory is live. Shorter shards than the runtime's minimum are extended.
+#[no_mangle]
+pub fn sys_shard_hint() {
+    syscall_shard_hint();
+}
+
+/// Asks the runtime to buffer the next `len` bytes of input, e.g. before a phase of the program
+/// that computes before reading a large input, so that the host reads it in the meantime.
+#[no_mangle]
+pub fn sys_prefetch_input(len: usize) {
+    syscall_prefetch_input(len);
+}
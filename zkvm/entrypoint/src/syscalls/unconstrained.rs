@@ -36,3 +36,22 @@ pub fn syscall_exit_unconstrained() {
     #[cfg(not(target_os = "zkvm"))]
     println!("Exiting unconstrained execution block");
 }
+
+/// Stages `len` bytes at `ptr` to be appended to the input stream once the current unconstrained
+/// block exits, from where they can be read back with `syscall_read`.
+#[allow(unused_variables)]
+#[no_mangle]
+pub extern "C" fn syscall_hint_slice(ptr: *const u8, len: usize) {
+    #[cfg(target_os = "zkvm")]
+    unsafe {
+        asm!(
+            "ecall",
+            in("t0") crate::syscalls::HINT_SLICE,
+            in("a0") ptr,
+            in("a1") len,
+        );
+    }
+
+    #[cfg(not(target_os = "zkvm"))]
+    unreachable!()
+}
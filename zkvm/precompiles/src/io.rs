@@ -1,5 +1,8 @@
 #![allow(unused_unsafe)]
-use crate::{syscall_read, syscall_write};
+use crate::{
+    syscall_hint_slice, syscall_prefetch_input, syscall_read, syscall_read_frame, syscall_write,
+    syscall_write_channel,
+};
 use bincode;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -41,6 +44,25 @@ impl std::io::Write for SyscallWriter {
     }
 }
 
+pub struct ChannelWriter {
+    channel: u32,
+}
+
+impl std::io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let nbytes = buf.len();
+        let write_buf = buf.as_ptr();
+        unsafe {
+            syscall_write_channel(self.channel, write_buf, nbytes);
+        }
+        Ok(nbytes)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 pub fn read<T: DeserializeOwned>() -> T {
     let my_reader = SyscallReader { fd: FD_IO };
     let result = bincode::deserialize_from::<_, T>(my_reader);
@@ -52,6 +74,61 @@ pub fn read_slice(buf: &mut [u8]) {
     my_reader.read_exact(buf).unwrap();
 }
 
+/// Asks the host to buffer the next `len` bytes of input while the program continues, so that
+/// reading them later does not wait for it.
+pub fn prefetch(len: usize) {
+    unsafe {
+        syscall_prefetch_input(len);
+    }
+}
+
+/// A 32-bit FNV-1a hash, matching the one the runtime computes frame tags with.
+const fn fnv1a(bytes: &[u8]) -> u32 {
+    let mut hash = 0x811c9dc5u32;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x01000193);
+        i += 1;
+    }
+    hash
+}
+
+/// The tag of an input frame of the given kind, see `FrameType::tag` in the runtime.
+fn frame_tag(kind: u8, param: u32) -> u32 {
+    let [b0, b1, b2, b3] = param.to_le_bytes();
+    fnv1a(&[kind, b0, b1, b2, b3])
+}
+
+/// Like [`read`], but first checks that the host wrote a `T` when it set an input schema.
+pub fn read_tagged<T: DeserializeOwned>() -> T {
+    let type_hash = fnv1a(core::any::type_name::<T>().as_bytes());
+    unsafe {
+        syscall_read_frame(frame_tag(2, type_hash));
+    }
+    read()
+}
+
+/// Reads a u32 written with `write_stdin_u32`, checking its type when the host set an input
+/// schema.
+pub fn read_u32_tagged() -> u32 {
+    unsafe {
+        syscall_read_frame(frame_tag(0, 0));
+    }
+    let mut buf = [0u8; 4];
+    read_slice(&mut buf);
+    u32::from_le_bytes(buf)
+}
+
+/// Like [`read_slice`], but first checks that the host wrote exactly `buf.len()` bytes when it
+/// set an input schema.
+pub fn read_slice_tagged(buf: &mut [u8]) {
+    unsafe {
+        syscall_read_frame(frame_tag(1, buf.len() as u32));
+    }
+    read_slice(buf);
+}
+
 pub fn write<T: Serialize>(value: &T) {
     let writer = SyscallWriter { fd: FD_IO };
     bincode::serialize_into(writer, value).expect("serialization failed");
@@ -71,3 +148,21 @@ pub fn hint_slice(buf: &[u8]) {
     let mut my_reader = SyscallWriter { fd: FD_HINT };
     my_reader.write_all(buf).unwrap();
 }
+
+/// Stages a buffer from inside an `unconstrained!` block. The whole buffer is appended to the
+/// input stream in one go when the block exits.
+pub fn stage_hint_slice(buf: &[u8]) {
+    unsafe {
+        syscall_hint_slice(buf.as_ptr(), buf.len());
+    }
+}
+
+pub fn write_channel<T: Serialize>(channel: u32, value: &T) {
+    let writer = ChannelWriter { channel };
+    bincode::serialize_into(writer, value).expect("serialization failed");
+}
+
+pub fn write_slice_channel(channel: u32, buf: &[u8]) {
+    let mut my_writer = ChannelWriter { channel };
+    my_writer.write_all(buf).unwrap();
+}
@@ -5,7 +5,10 @@ pub mod unconstrained;
 extern "C" {
     pub fn syscall_halt() -> !;
     pub fn syscall_write(fd: u32, write_buf: *const u8, nbytes: usize);
+    pub fn syscall_write_channel(channel: u32, write_buf: *const u8, nbytes: usize);
     pub fn syscall_read(fd: u32, read_buf: *mut u8, nbytes: usize);
+    pub fn syscall_read_frame(tag: u32);
+    pub fn syscall_prefetch_input(len: usize);
     pub fn syscall_sha256_extend(w: *mut u32);
     pub fn syscall_sha256_compress(w: *mut u32, state: *mut u32);
     pub fn syscall_ed_add(p: *mut u32, q: *mut u32);
@@ -17,5 +20,7 @@ extern "C" {
     pub fn syscall_blake3_compress_inner(p: *mut u32, q: *const u32);
     pub fn syscall_enter_unconstrained() -> bool;
     pub fn syscall_exit_unconstrained();
+    pub fn syscall_hint_slice(ptr: *const u8, len: usize);
+    pub fn syscall_shard_hint();
     pub fn sys_alloc_aligned(bytes: usize, align: usize) -> *mut u8;
 }